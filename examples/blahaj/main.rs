@@ -13,7 +13,13 @@ use lunar_engine::{
     ecs::{Component, ComponentReference, EntityBuilder, World},
     input,
     math::{Quaternion, Vec3},
-    rendering::{self, extensions::Base},
+    rendering::{
+        self,
+        extensions::{
+            Base,
+            post_processing::{PostProcessing, Tonemapping, Vignette},
+        },
+    },
     structures::Color,
 };
 use lunar_engine_derive::{dependencies, marker_component};
@@ -25,6 +31,7 @@ struct MyState {
     world: World,
     assset_store: AssetStore,
     extension: Base,
+    post_processing: PostProcessing,
     blahaj_mesh: u128,
     blahaj_mat: u128,
 }
@@ -68,6 +75,10 @@ fn init(state: &mut MyState) {
 
     log::info!("Initializing scene");
 
+    //Render in hdr, so that post processing can be applied
+    rendering::set_hdr(true);
+    state.post_processing = PostProcessing::default();
+
     state.extension = Base::new_with_color(
         0,
         true,
@@ -163,12 +174,30 @@ fn run(state: &mut MyState) {
         }
     }
 
+    //Toggle the post processing effects
+    if input::KeyState::Down == input::key(KeyCode::KeyF) {
+        state.post_processing.fxaa = !state.post_processing.fxaa;
+    }
+    if input::KeyState::Down == input::key(KeyCode::KeyV) {
+        state.post_processing.vignette = match state.post_processing.vignette {
+            Some(_) => None,
+            None => Some(Vignette::default()),
+        };
+    }
+    if input::KeyState::Down == input::key(KeyCode::KeyT) {
+        state.post_processing.tonemapping = match state.post_processing.tonemapping {
+            Tonemapping::None => Tonemapping::Reinhard,
+            Tonemapping::Reinhard => Tonemapping::Aces,
+            Tonemapping::Aces => Tonemapping::None,
+        };
+    }
+
     state.world.update();
     debug!("Called render!");
     rendering::render(
         &state.world,
         &mut state.assset_store,
-        &mut [&mut state.extension],
+        &mut [&mut state.extension, &mut state.post_processing],
     );
     state.frame += 1;
 }
@@ -0,0 +1,193 @@
+use core::f32;
+use std::cell::OnceCell;
+
+use log::info;
+use lunar_engine::{
+    asset_managment::AssetStore,
+    assets::{self, materials::Lit, mesh::SphereData},
+    components::{
+        camera::MainCamera,
+        fps::FpsRecorder,
+        light::{DirectionalLight, PointLight},
+        mesh::Mesh,
+        transform::Transform,
+    },
+    delta_time,
+    ecs::{Component, ComponentReference, EntityBuilder, World},
+    math::{Quaternion, Vec3},
+    rendering::{extensions::Base, render},
+    structures::Color,
+};
+use lunar_engine_derive::dependencies;
+use rand::Rng;
+
+///Moves the light in a circle around the center of the scene
+struct Orbit {
+    radius: f32,
+    height: f32,
+    angle: f32,
+    speed: f32,
+    transform: OnceCell<ComponentReference<Transform>>,
+}
+
+impl Component for Orbit {
+    #[dependencies(Transform)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            radius: 10.0,
+            height: 1.0,
+            angle: 0.0,
+            speed: 0.5,
+            transform: OnceCell::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.angle += self.speed * delta_time();
+        let (sin, cos) = self.angle.sin_cos();
+
+        let mut t = self.transform.get().unwrap().borrow_mut();
+        t.position = Vec3::new(cos * self.radius, self.height, sin * self.radius);
+    }
+
+    fn set_self_reference(&mut self, reference: lunar_engine::ecs::SelfReferenceGuard) {
+        self.transform
+            .set(reference.get_component::<Transform>().unwrap())
+            .unwrap();
+    }
+}
+
+#[derive(Default)]
+struct State {
+    extension: Base,
+    asset_store: AssetStore,
+    world: World,
+}
+
+const fn end(_state: &mut State) {}
+
+fn generate_scene(world: &mut World, assets: &mut AssetStore, num_lights: u32) {
+    let floor = assets.register(assets::Mesh::new_box(Vec3::new(80, 0.2, 80)));
+    let sphere = assets.register(assets::Mesh::new_sphere(SphereData {
+        radius: 0.5,
+        rings: 16,
+        segments: 32,
+    }));
+    let material = assets.register(Lit::new(None, Some(Color::white()), None, 0.2));
+
+    world
+        .add_entity(
+            EntityBuilder::new()
+                .create_component(|| Transform {
+                    position: Vec3::new(0, -0.1, 0),
+                    ..Default::default()
+                })
+                .create_component(|| Mesh::new(floor, material))
+                .create()
+                .unwrap(),
+        )
+        .unwrap();
+
+    //A grid of spheres, so there's something for the lights to shine on
+    for x in -15..=15 {
+        for z in -15..=15 {
+            world
+                .add_entity(
+                    EntityBuilder::new()
+                        .create_component(|| Transform {
+                            position: Vec3::new(x * 2, 0.5, z * 2),
+                            ..Default::default()
+                        })
+                        .create_component(|| Mesh::new(sphere, material))
+                        .create()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+    }
+
+    //Many small lights, each of them only reaches a few of the clusters
+    let mut rng = rand::thread_rng();
+    for _ in 0..num_lights {
+        let color = Color::from_hsl(rng.gen_range(0.0..360.0), 1.0, 0.5);
+        world
+            .add_entity(
+                EntityBuilder::new()
+                    .add_component::<Transform>()
+                    .create_component(|| PointLight::new(color, rng.gen_range(2.0..6.0), 3.0))
+                    .create_component(|| Orbit {
+                        radius: rng.gen_range(1.0..40.0),
+                        height: rng.gen_range(0.3..2.0),
+                        angle: rng.gen_range(0.0..f32::consts::TAU),
+                        speed: rng.gen_range(-0.5..0.5),
+                        transform: OnceCell::new(),
+                    })
+                    .create()
+                    .unwrap(),
+            )
+            .unwrap();
+    }
+}
+
+fn init(state: &mut State) {
+    let assets = &mut state.asset_store;
+    let world = &mut state.world;
+
+    let num_lights = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(4000);
+    info!("Num of lights: {num_lights}");
+
+    generate_scene(world, assets, num_lights);
+
+    world
+        .add_entity(
+            EntityBuilder::new()
+                .create_component(|| Transform {
+                    position: (0, 20, -45).into(),
+                    rotation: Quaternion::from_euler(Vec3::new(25, 0, 0)),
+                    ..Default::default()
+                })
+                .add_component::<MainCamera>()
+                .add_component::<FpsRecorder>()
+                .create()
+                .unwrap(),
+        )
+        .unwrap();
+
+    world
+        .add_entity(
+            EntityBuilder::new()
+                //Only a bit of ambient light, so the point lights are easy to see
+                .create_component(|| DirectionalLight {
+                    color: Color::white(),
+                    direction: Vec3::new(0, -1, 0),
+                    ambient_color: Color::new(0.02, 0.02, 0.02, 1),
+                    intensity: 0.0,
+                })
+                .create()
+                .unwrap(),
+        )
+        .unwrap();
+
+    assets.intialize_all().unwrap();
+}
+
+fn run(state: &mut State) {
+    state.world.update();
+    render(
+        &state.world,
+        &mut state.asset_store,
+        &mut [&mut state.extension],
+    );
+}
+
+fn main() {
+    let state = lunar_engine::State::new(State::default());
+
+    state.run(init, run, end);
+}
@@ -0,0 +1,551 @@
+//! Asset pack format used by `lunar-engine`
+//!
+//! A pack is a single file that contains many named files (assets), so that a game can be shipped
+//! as a single blob instead of a directory of loose files
+//!
+//! # Format
+//!
+//! All numbers are little endian
+//!
+//! | Field        | Size           | Description                      |
+//! |--------------|----------------|----------------------------------|
+//! | Magic        | 4              | `LPAK`                           |
+//! | Version      | 4              | Format version, currently `1`    |
+//! | Entry count  | 4              | Number of entries in the index   |
+//! | Index        | variable       | `Entry count` index entries      |
+//! | Data         | variable       | Data of all the entries          |
+//!
+//! Each index entry is laid out as follows
+//!
+//! | Field        | Size           | Description                                    |
+//! |--------------|----------------|------------------------------------------------|
+//! | Name length  | 2              | Length of the name in bytes                    |
+//! | Name         | `Name length`  | Utf-8 name of the entry, `/` separated         |
+//! | Offset       | 8              | Offset of the data from the start of the file  |
+//! | Stored size  | 8              | Size of the data as it is stored in the pack   |
+//! | Size         | 8              | Size of the data after decompression           |
+//! | Compression  | 1              | `0` - none, `1` - deflate                      |
+#![allow(clippy::cast_possible_truncation)]
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+///Magic bytes at the start of every pack
+pub const MAGIC: [u8; 4] = *b"LPAK";
+///Current version of the format
+pub const VERSION: u32 = 1;
+
+///Size of the header (magic, version and entry count)
+const HEADER_SIZE: usize = 12;
+///Size of an index entry without the name
+const ENTRY_SIZE: usize = 2 + 8 + 8 + 8 + 1;
+
+#[derive(Debug)]
+///Errors that may occur when reading or writing packs
+pub enum Error {
+    ///An io error occured
+    Io(std::io::Error),
+    ///The data does not start with [`MAGIC`]
+    InvalidMagic,
+    ///The pack was created with an unsupported version of the format
+    UnsupportedVersion(u32),
+    ///The data ended before it was expected to
+    Truncated,
+    ///Entry name is invalid (not utf-8, empty or too long)
+    InvalidName,
+    ///Pack contains more than one entry with the same name
+    DuplicateName(String),
+    ///Entry uses an unknown compression method
+    UnknownCompression(u8),
+    ///Requested entry does not exist
+    NotFound(String),
+    ///Failed to decompress an entry
+    Decompression,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Io error: {error}"),
+            Self::InvalidMagic => write!(f, "Data is not a lunar pack"),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported pack version {v}"),
+            Self::Truncated => write!(f, "Pack is truncated"),
+            Self::InvalidName => write!(f, "Invalid entry name"),
+            Self::DuplicateName(name) => write!(f, "Duplicate entry {name}"),
+            Self::UnknownCompression(c) => write!(f, "Unknown compression method {c}"),
+            Self::NotFound(name) => write!(f, "Entry {name} not found"),
+            Self::Decompression => write!(f, "Failed to decompress entry"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///Compression method of an entry
+pub enum Compression {
+    ///Data is stored as is
+    None,
+    ///Data is compressed using deflate
+    Deflate,
+}
+
+impl Compression {
+    const fn to_u8(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Deflate => 1,
+        }
+    }
+
+    const fn from_u8(value: u8) -> Result<Self, Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Deflate),
+            c => Err(Error::UnknownCompression(c)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+///An entry in the index of a pack
+pub struct Entry {
+    name: String,
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    compression: Compression,
+}
+
+impl Entry {
+    ///Returns the name of the entry
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    ///Returns the size of the entry after decompression
+    #[must_use]
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    ///Returns the size of the entry as it is stored in the pack
+    #[must_use]
+    pub const fn stored_size(&self) -> u64 {
+        self.stored_size
+    }
+
+    ///Returns the compression method of the entry
+    #[must_use]
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+}
+
+///A loaded asset pack
+///
+///The whole pack is kept in memory, entries are decompressed when read
+pub struct Pack {
+    data: Vec<u8>,
+    ///Sorted by name
+    entries: Vec<Entry>,
+}
+
+///Reads little endian values from a byte slice
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.position.checked_add(len).ok_or(Error::Truncated)?;
+        let out = self.data.get(self.position..end).ok_or(Error::Truncated)?;
+        self.position = end;
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl Pack {
+    ///Parses a pack from bytes
+    ///
+    ///# Errors
+    ///Returns an error if the data is not a valid pack
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let mut reader = Reader {
+            data: &data,
+            position: 0,
+        };
+
+        if reader.take(4)? != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let count = reader.u32()?;
+        let mut entries = Vec::new();
+
+        for _ in 0..count {
+            let name_len = reader.u16()?;
+            let name = std::str::from_utf8(reader.take(name_len.into())?)
+                .map_err(|_| Error::InvalidName)?
+                .to_owned();
+            let offset = reader.u64()?;
+            let stored_size = reader.u64()?;
+            let size = reader.u64()?;
+            let compression = Compression::from_u8(reader.u8()?)?;
+
+            //Make sure the data is in bounds
+            if offset
+                .checked_add(stored_size)
+                .is_none_or(|end| end > data.len() as u64)
+            {
+                return Err(Error::Truncated);
+            }
+
+            entries.push(Entry {
+                name,
+                offset,
+                stored_size,
+                size,
+                compression,
+            });
+        }
+
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        //Lookups could resolve a duplicated name to either entry
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].name == pair[1].name) {
+            return Err(Error::DuplicateName(pair[0].name.clone()));
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    ///Reads a pack from a file
+    ///
+    ///# Errors
+    ///Returns an error if the file could not be read or is not a valid pack
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    ///Returns all entries of the pack, sorted by name
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    ///Returns the entry with the given name
+    #[must_use]
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .binary_search_by(|e| e.name.as_str().cmp(name))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    ///Checks if the pack contains an entry with the given name
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    ///Reads and decompresses the entry with the given name
+    ///
+    ///# Errors
+    ///Returns an error if the entry does not exist or could not be decompressed
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| Error::NotFound(name.to_owned()))?;
+
+        let start = entry.offset as usize;
+        let data = &self.data[start..start + entry.stored_size as usize];
+
+        match entry.compression {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Deflate => {
+                let out =
+                    miniz_oxide::inflate::decompress_to_vec_with_limit(data, entry.size as usize)
+                        .map_err(|_| Error::Decompression)?;
+
+                if out.len() as u64 == entry.size {
+                    Ok(out)
+                } else {
+                    Err(Error::Decompression)
+                }
+            }
+        }
+    }
+
+    ///Reads the entry with the given name as utf-8 text
+    ///
+    ///# Errors
+    ///Returns an error if the entry does not exist, could not be decompressed or is not valid utf-8
+    pub fn read_to_string(&self, name: &str) -> Result<String, Error> {
+        String::from_utf8(self.read(name)?).map_err(|e| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e.utf8_error(),
+            ))
+        })
+    }
+}
+
+///Builds asset packs
+pub struct PackBuilder {
+    files: Vec<(String, Vec<u8>)>,
+    compression: Compression,
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            compression: Compression::None,
+        }
+    }
+}
+
+impl PackBuilder {
+    ///Creates a new empty pack builder, that does not compress the data
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Sets the compression method used for the entries
+    ///
+    ///Entries that do not get smaller when compressed are stored without compression
+    #[must_use]
+    pub const fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    ///Adds a file to the pack, replacing a file with the same name if it was already added
+    ///
+    ///# Errors
+    ///Returns an error if the name is empty or longer than `u16::MAX` bytes
+    pub fn add(&mut self, name: &str, data: Vec<u8>) -> Result<(), Error> {
+        let name = name.replace('\\', "/");
+        let name = name.trim_start_matches('/');
+        if name.is_empty() || name.len() > u16::MAX as usize {
+            return Err(Error::InvalidName);
+        }
+
+        if let Some(f) = self.files.iter_mut().find(|f| f.0 == name) {
+            f.1 = data;
+        } else {
+            self.files.push((name.to_owned(), data));
+        }
+        Ok(())
+    }
+
+    ///Recursively adds all files in a directory to the pack
+    ///
+    ///Entries are named by their path relative to the directory, using `/` as the separator
+    ///
+    ///# Errors
+    ///Returns an error if the directory could not be read
+    pub fn add_directory(&mut self, path: &Path) -> Result<(), Error> {
+        let mut stack = vec![PathBuf::new()];
+
+        while let Some(relative) = stack.pop() {
+            let mut dir =
+                std::fs::read_dir(path.join(&relative))?.collect::<Result<Vec<_>, _>>()?;
+            dir.sort_by_key(std::fs::DirEntry::file_name);
+
+            for entry in dir {
+                let relative = relative.join(entry.file_name());
+                if entry.file_type()?.is_dir() {
+                    stack.push(relative);
+                    continue;
+                }
+
+                let name = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_str().ok_or(Error::InvalidName))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("/");
+
+                self.add(&name, std::fs::read(entry.path())?)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///Returns the number of files added to the builder
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    ///Checks if no files were added to the builder
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    ///Builds the pack
+    #[must_use]
+    pub fn build(&self) -> Vec<u8> {
+        let stored = self
+            .files
+            .iter()
+            .map(|(_, data)| match self.compression {
+                Compression::None => (Compression::None, None),
+                Compression::Deflate => {
+                    let compressed = miniz_oxide::deflate::compress_to_vec(data, 8);
+                    if compressed.len() < data.len() {
+                        (Compression::Deflate, Some(compressed))
+                    } else {
+                        (Compression::None, None)
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let index_size = self
+            .files
+            .iter()
+            .map(|(name, _)| ENTRY_SIZE + name.len())
+            .sum::<usize>();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+
+        let mut offset = (HEADER_SIZE + index_size) as u64;
+
+        for ((name, data), (compression, compressed)) in self.files.iter().zip(&stored) {
+            let stored_size = compressed.as_ref().map_or(data.len(), Vec::len) as u64;
+
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&stored_size.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.push(compression.to_u8());
+
+            offset += stored_size;
+        }
+
+        for ((_, data), (_, compressed)) in self.files.iter().zip(&stored) {
+            out.extend_from_slice(compressed.as_ref().unwrap_or(data));
+        }
+
+        out
+    }
+
+    ///Builds the pack and writes it into a file
+    ///
+    ///# Errors
+    ///Returns an error if the file could not be written
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.build())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut builder = PackBuilder::new();
+        builder.add("textures/a.png", vec![1, 2, 3, 4]).unwrap();
+        builder.add("b.obj", b"v 0 0 0".to_vec()).unwrap();
+        builder.add("\\empty", Vec::new()).unwrap();
+
+        let pack = Pack::from_bytes(builder.build()).unwrap();
+
+        assert_eq!(pack.entries().len(), 3);
+        assert_eq!(pack.read("textures/a.png").unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(pack.read_to_string("b.obj").unwrap(), "v 0 0 0");
+        assert!(pack.read("empty").unwrap().is_empty());
+        assert!(matches!(pack.read("c"), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_compression() {
+        let data = b"lunar".repeat(1000);
+
+        let mut builder = PackBuilder::new().with_compression(Compression::Deflate);
+        builder.add("compressible", data.clone()).unwrap();
+        builder.add("tiny", vec![7]).unwrap();
+
+        let pack = Pack::from_bytes(builder.build()).unwrap();
+
+        let entry = pack.entry("compressible").unwrap();
+        assert_eq!(entry.compression(), Compression::Deflate);
+        assert!(entry.stored_size() < entry.size());
+        assert_eq!(pack.read("compressible").unwrap(), data);
+
+        //Should not be compressed, as it would only get bigger
+        assert_eq!(pack.entry("tiny").unwrap().compression(), Compression::None);
+        assert_eq!(pack.read("tiny").unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            Pack::from_bytes(b"NOPE".to_vec()),
+            Err(Error::InvalidMagic)
+        ));
+
+        let mut builder = PackBuilder::new();
+        builder.add("a", vec![1; 16]).unwrap();
+        let mut data = builder.build();
+        data.truncate(data.len() - 1);
+        assert!(matches!(Pack::from_bytes(data), Err(Error::Truncated)));
+
+        assert!(matches!(
+            PackBuilder::new().add("", Vec::new()),
+            Err(Error::InvalidName)
+        ));
+
+        //Rename the second entry to the name of the first one
+        let mut builder = PackBuilder::new();
+        builder.add("a", vec![0; 16]).unwrap();
+        builder.add("b", vec![0; 16]).unwrap();
+        let mut data = builder.build();
+        let position = data.iter().position(|b| *b == b'b').unwrap();
+        data[position] = b'a';
+        assert!(matches!(
+            Pack::from_bytes(data),
+            Err(Error::DuplicateName(name)) if name == "a"
+        ));
+    }
+}
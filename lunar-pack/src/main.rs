@@ -0,0 +1,44 @@
+//! Builds a `lunar-engine` asset pack from a directory
+//!
+//! Usage: `lunar-pack [--compress] <input directory> <output file>`
+use std::{path::PathBuf, process::ExitCode};
+
+use lunar_pack::{Compression, PackBuilder};
+
+const USAGE: &str = "Usage: lunar-pack [--compress] <input directory> <output file>";
+
+fn main() -> ExitCode {
+    let mut compression = Compression::None;
+    let mut paths = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-c" | "--compress" => compression = Compression::Deflate,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [input, output] = paths.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let mut builder = PackBuilder::new().with_compression(compression);
+
+    if let Err(e) = builder.add_directory(input) {
+        eprintln!("Failed to read {}: {e}", input.display());
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = builder.write(output) {
+        eprintln!("Failed to write {}: {e}", output.display());
+        return ExitCode::FAILURE;
+    }
+
+    println!("Packed {} files into {}", builder.len(), output.display());
+    ExitCode::SUCCESS
+}
@@ -14,6 +14,12 @@
 //!
 //! Asset initialization may be performed in parallel
 //! Assets are only initialized when first needed (or perhaps on "scene load"?)
+//!
+//! Assets may depend on other assets (for example a material on a texture), see
+//! [`Asset::dependencies`]. Dependencies are always initialized before the assets that depend on
+//! them, and an asset can not be disposed of while its dependents are still initialized
+//!
+//! Assets may be loaded out of asset packs mounted into the store, see [`pack`]
 // Oh god, is this just the entity system but with assets!?!?
 
 use std::{
@@ -25,12 +31,13 @@ use std::{
 use std::thread;
 
 use rand::Rng;
-use vec_key_value_pair::map::VecMap;
+use vec_key_value_pair::{map::VecMap, set::VecSet};
 
 use crate::UUID;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::grimoire;
 
+pub mod pack;
 #[cfg(test)]
 mod tests;
 
@@ -47,6 +54,21 @@ pub enum Error {
     InitializationError(Box<dyn std::error::Error>),
     ///An asset with the given id already exists
     IdAlreadyExists,
+    ///Assets depend on each other in a cycle
+    ///
+    ///Contains the ids of the assets that could not be ordered
+    DependencyCycle(Vec<UUID>),
+    ///An asset depends on an asset that is not registered in the store
+    MissingDependency {
+        ///Id of the asset that declared the dependency
+        asset: UUID,
+        ///Id of the missing dependency
+        dependency: UUID,
+    },
+    ///The asset can not be disposed of, because other initialized assets depend on it
+    ///
+    ///Contains the ids of the dependents
+    HasDependents(Vec<UUID>),
 }
 
 //Send and sync for parallel initialization
@@ -101,6 +123,13 @@ pub trait Asset: Send + Sync + std::any::Any {
     fn set_id(&mut self, id: UUID) -> Result<(), Error>;
     ///Returns whether or not the asset is initialized
     fn is_initialized(&self) -> bool;
+    ///Returns ids of the assets this asset depends on
+    ///
+    ///The dependencies are initialized before this asset, and may not be disposed of while this
+    ///asset is initialized
+    fn dependencies(&self) -> Vec<UUID> {
+        Vec::new()
+    }
 }
 
 ///Reference to an asset inside [`AssetStore`]
@@ -144,12 +173,14 @@ type RwLock<T> = lock_api::RwLock<parking_lot::RawRwLock, T>;
 #[allow(clippy::type_complexity)]
 pub struct AssetStore {
     assets: VecMap<UUID, (Arc<RwLock<dyn Asset>>, std::any::TypeId)>,
+    packs: Vec<Arc<pack::Pack>>,
 }
 
 impl Default for AssetStore {
     fn default() -> Self {
         Self {
             assets: VecMap::new(),
+            packs: Vec::new(),
         }
     }
 }
@@ -211,39 +242,53 @@ impl AssetStore {
 
     ///Initializes all of the assets in the assetstore
     ///
-    ///Utilizes threads to initialize assets in parallel
+    ///Assets are initialized in the order of their dependencies, assets that do not depend on each
+    ///other are initialized in parallel using threads
     ///
     ///# Errors
-    ///Returns an error if one of the assets fails to initialize
+    ///Returns an error if one of the assets fails to initialize, if an asset depends on an asset
+    ///that does not exist, or if there is a dependency cycle
+    ///
+    ///# Panics
+    ///Panics if the initialization of one of the assets panics
     pub fn intialize_all(&self) -> Result<(), Error> {
-        let binding = self.assets.values().collect::<Vec<_>>();
+        for level in self.dependency_levels()? {
+            let binding = level
+                .iter()
+                .map(|id| self.assets.get(id).unwrap().0.clone())
+                .collect::<Vec<_>>();
 
-        #[cfg(not(target_arch = "wasm32"))]
-        let mut chunk_size = self.assets.len() / grimoire::NUM_THREADS;
-        #[cfg(not(target_arch = "wasm32"))]
-        if chunk_size == 0 {
-            chunk_size = 1;
+            Self::initialize_batch(&binding)?;
         }
 
+        Ok(())
+    }
+
+    ///Initializes a batch of assets that do not depend on each other
+    fn initialize_batch(batch: &[Arc<RwLock<dyn Asset>>]) -> Result<(), Error> {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let handles = binding
+            let mut chunk_size = batch.len() / grimoire::NUM_THREADS;
+            if chunk_size == 0 {
+                chunk_size = 1;
+            }
+
+            let handles = batch
                 .chunks(chunk_size)
-                .map(|c| c.iter().map(|i| (*i).clone()).collect::<Vec<_>>())
-                .collect::<Vec<_>>()
-                .into_iter()
+                .map(<[_]>::to_vec)
                 .map(|c| {
                     thread::spawn(move || {
-                        c.clone()
-                            .iter()
-                            .map(move |i| i.0.write().initialize())
+                        c.iter()
+                            .map(move |i| i.write().initialize())
                             .collect::<Vec<_>>()
                     })
                 })
                 .collect::<Vec<_>>();
 
             for h in handles {
-                for r in unsafe { h.join().unwrap_unchecked() } {
+                //A panicking asset is re-raised on the calling thread
+                let results = h.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+                for r in results {
                     if let Err(r) = r {
                         return Err(Error::InitializationError(r));
                     }
@@ -253,7 +298,7 @@ impl AssetStore {
 
         #[cfg(target_arch = "wasm32")]
         {
-            for r in binding.iter().map(|c| c.0.write().initialize()) {
+            for r in batch.iter().map(|c| c.write().initialize()) {
                 if let Err(r) = r {
                     return Err(Error::InitializationError(r));
                 }
@@ -263,24 +308,108 @@ impl AssetStore {
         Ok(())
     }
 
+    ///Sorts all the assets into levels, assets in a level only depend on the assets in the previous
+    ///levels
+    fn dependency_levels(&self) -> Result<Vec<Vec<UUID>>, Error> {
+        //(id, dependencies)
+        let mut remaining = Vec::new();
+
+        for (id, (asset, _)) in &self.assets {
+            let dependencies = asset.read().dependencies();
+
+            for d in &dependencies {
+                if self.assets.get(d).is_none() {
+                    return Err(Error::MissingDependency {
+                        asset: *id,
+                        dependency: *d,
+                    });
+                }
+            }
+
+            remaining.push((*id, dependencies));
+        }
+
+        let mut resolved = VecSet::new();
+        let mut levels = Vec::new();
+
+        while !remaining.is_empty() {
+            let (ready, rest): (Vec<_>, Vec<_>) = remaining
+                .into_iter()
+                .partition(|(_, deps)| deps.iter().all(|d| resolved.contains(d)));
+
+            //Nothing can be resolved, so the rest of the assets are in (or depend on) a cycle
+            if ready.is_empty() {
+                return Err(Error::DependencyCycle(
+                    rest.into_iter().map(|i| i.0).collect(),
+                ));
+            }
+
+            for (id, _) in &ready {
+                resolved.insert(*id);
+            }
+
+            levels.push(ready.into_iter().map(|i| i.0).collect());
+            remaining = rest;
+        }
+
+        Ok(levels)
+    }
+
+    ///Initializes the asset if it's not initialized, initializing all of its dependencies first
+    fn initialize_if_needed(
+        &self,
+        id: UUID,
+        asset: &Arc<RwLock<dyn Asset>>,
+        stack: &mut Vec<UUID>,
+    ) -> Result<(), Error> {
+        if asset.read().is_initialized() {
+            return Ok(());
+        }
+
+        if stack.contains(&id) {
+            return Err(Error::DependencyCycle(stack.clone()));
+        }
+        stack.push(id);
+
+        let dependencies = asset.read().dependencies();
+        for d in dependencies {
+            match self.assets.get(&d) {
+                Some(dependency) => self.initialize_if_needed(d, &dependency.0, stack)?,
+                None => {
+                    return Err(Error::MissingDependency {
+                        asset: id,
+                        dependency: d,
+                    });
+                }
+            }
+        }
+
+        stack.pop();
+
+        let mut x = asset.write();
+        if !x.is_initialized() {
+            let r = x.initialize();
+            drop(x);
+            if let Err(r) = r {
+                return Err(Error::InitializationError(r));
+            }
+        }
+
+        Ok(())
+    }
+
     ///Returns the [`AssetReference`] to an asset inside the `AssetStore` by id
     ///
+    ///If the asset is not initialized, it and all of its dependencies are initialized
+    ///
     ///# Errors
-    ///Returns an error if the object with the given id doesn't exist
+    ///Returns an error if the object with the given id doesn't exist, or if it fails to initialize
     pub fn get_by_id<T: Asset>(&self, id: UUID) -> Result<AssetReference<T>, Error> {
         let this = self.assets.get(&id);
         match this {
             Some(x) => {
-                {
-                    let mut x = x.0.write();
-                    if !x.is_initialized() {
-                        let r = x.initialize();
-                        drop(x);
-                        if let Err(r) = r {
-                            return Err(Error::InitializationError(r));
-                        }
-                    }
-                }
+                self.initialize_if_needed(id, &x.0, &mut Vec::new())?;
+
                 Ok(AssetReference {
                     refernce: Arc::downgrade(&x.0),
                     phantom: std::marker::PhantomData,
@@ -294,22 +423,14 @@ impl AssetStore {
     ///Borrows an asset by its id, same as `get_by_id`, but with the `borrow` call is already made
     ///
     ///# Errors
-    ///Returns an error if the object with the given id doesn't exist
+    ///Returns an error if the object with the given id doesn't exist, or if it fails to initialize
     #[inline(always)]
     pub fn borrow_by_id<T: Asset>(&self, id: UUID) -> Result<AssetGuard<'_, T>, Error> {
         let this = self.assets.get(&id);
         match this {
             Some(x) => {
-                {
-                    let mut x = x.0.write();
-                    if !x.is_initialized() {
-                        let r = x.initialize();
-                        drop(x);
-                        if let Err(r) = r {
-                            return Err(Error::InitializationError(r));
-                        }
-                    }
-                }
+                self.initialize_if_needed(id, &x.0, &mut Vec::new())?;
+
                 Ok({
                     lock_api::RwLockReadGuard::<
                         '_,
@@ -327,22 +448,14 @@ impl AssetStore {
     ///Borrows an asset by its id, same as `get_by_id`, but with the `borrow_mut` call is already made
     ///
     ///# Errors
-    ///Returns an error if the object with the given id doesn't exist
+    ///Returns an error if the object with the given id doesn't exist, or if it fails to initialize
     #[inline(always)]
     pub fn borrow_by_id_mut<T: Asset>(&self, id: UUID) -> Result<AssetGuardMut<'_, T>, Error> {
         let this = self.assets.get(&id);
         match this {
             Some(x) => {
-                {
-                    let mut x = x.0.write();
-                    if !x.is_initialized() {
-                        let r = x.initialize();
-                        drop(x);
-                        if let Err(r) = r {
-                            return Err(Error::InitializationError(r));
-                        }
-                    }
-                }
+                self.initialize_if_needed(id, &x.0, &mut Vec::new())?;
+
                 Ok({
                     lock_api::RwLockWriteGuard::<
                         '_,
@@ -360,20 +473,14 @@ impl AssetStore {
     ///Returns the first asset of type T
     ///
     ///# Errors
-    ///Returns an error if the object of the given type doesn't exist
+    ///Returns an error if the object of the given type doesn't exist, or if it fails to initialize
     pub fn get_by_type<T: Asset + 'static>(&self) -> Result<AssetReference<T>, Error> {
         let type_id = std::any::TypeId::of::<T>();
 
-        for i in self.assets.values() {
+        for (id, i) in &self.assets {
             if i.1 == type_id {
-                let mut x = i.0.write();
-                if !x.is_initialized() {
-                    let r = x.initialize();
-                    drop(x);
-                    if let Err(r) = r {
-                        return Err(Error::InitializationError(r));
-                    }
-                }
+                self.initialize_if_needed(*id, &i.0, &mut Vec::new())?;
+
                 return Ok(AssetReference {
                     refernce: Arc::downgrade(&i.0),
                     phantom: std::marker::PhantomData,
@@ -384,24 +491,113 @@ impl AssetStore {
         Err(Error::DoesNotExist)
     }
 
+    ///Returns true if the asset with the given id exists and is of type T
+    #[must_use]
+    pub fn is_type<T: Asset + 'static>(&self, id: UUID) -> bool {
+        self.assets
+            .get(&id)
+            .is_some_and(|i| i.1 == std::any::TypeId::of::<T>())
+    }
+
+    ///Returns ids of all the assets that directly depend on the asset with the given id
+    #[must_use]
+    pub fn get_dependents(&self, id: UUID) -> Vec<UUID> {
+        self.assets
+            .iter()
+            .filter(|(i, a)| **i != id && a.0.read().dependencies().contains(&id))
+            .map(|(i, _)| *i)
+            .collect()
+    }
+
     ///Disposes of the asset with id
     ///
     ///# Errors
-    ///Returns an error if the object with the given id doesn't exist
+    ///Returns an error if the object with the given id doesn't exist, or if there are initialized
+    ///assets that depend on it, see [`AssetStore::dispose_by_id_cascading`]
     pub fn dispose_by_id(&self, id: UUID) -> Result<(), Error> {
-        match self.assets.get(&id) {
-            Some(it) => it.0.write().dispose(),
-            None => return Err(Error::DoesNotExist),
+        let Some(it) = self.assets.get(&id) else {
+            return Err(Error::DoesNotExist);
+        };
+
+        let dependents = self
+            .get_dependents(id)
+            .into_iter()
+            .filter(|d| self.assets.get(d).unwrap().0.read().is_initialized())
+            .collect::<Vec<_>>();
+
+        if !dependents.is_empty() {
+            return Err(Error::HasDependents(dependents));
         }
+
+        it.0.write().dispose();
         Ok(())
     }
 
+    ///Disposes of the asset with id, disposing of all the assets that depend on it first
+    ///
+    ///# Errors
+    ///Returns an error if the object with the given id doesn't exist
+    pub fn dispose_by_id_cascading(&self, id: UUID) -> Result<(), Error> {
+        if self.assets.get(&id).is_none() {
+            return Err(Error::DoesNotExist);
+        }
+
+        self.dispose_cascading(id, &mut Vec::new());
+        Ok(())
+    }
+
+    fn dispose_cascading(&self, id: UUID, visited: &mut Vec<UUID>) {
+        //Guard against dependency cycles
+        if visited.contains(&id) {
+            return;
+        }
+        visited.push(id);
+
+        for d in self.get_dependents(id) {
+            self.dispose_cascading(d, visited);
+        }
+
+        let asset = &self.assets.get(&id).unwrap().0;
+        if asset.read().is_initialized() {
+            asset.write().dispose();
+        }
+    }
+
     ///Disposes of all assets
+    ///
+    ///Dependents are disposed of before their dependencies
     pub fn dispose_all(&self) {
-        for a in self.assets.values().map(|v| v.0.clone()) {
-            a.write().dispose();
+        let Ok(levels) = self.dependency_levels() else {
+            //The order can not be determined, so just dispose in whatever order
+            for a in self.assets.values().map(|v| v.0.clone()) {
+                a.write().dispose();
+            }
+            return;
+        };
+
+        for id in levels.iter().rev().flatten() {
+            self.assets.get(id).unwrap().0.write().dispose();
         }
     }
+
+    ///Mounts an asset pack, making its files available through [`AssetStore::pack_file`]
+    ///
+    ///Packs mounted later take precedence over the ones mounted earlier
+    pub fn mount_pack(&mut self, pack: pack::Pack) {
+        self.packs.push(Arc::new(pack));
+    }
+
+    ///Returns a handle to a file in one of the mounted packs
+    ///
+    ///The handle can be used to create assets, for example
+    ///[`Texture::new_packed_png`](crate::assets::Texture::new_packed_png)
+    #[must_use]
+    pub fn pack_file(&self, name: &str) -> Option<pack::PackFile> {
+        self.packs
+            .iter()
+            .rev()
+            .find_map(|p| pack::PackFile::new(p.clone(), name))
+    }
 }
 
 impl Drop for AssetStore {
@@ -0,0 +1,88 @@
+//! Loading assets out of asset packs
+//!
+//! Packs are built using the `lunar-pack` binary, or [`PackBuilder`], mounted into an
+//! [`AssetStore`](super::AssetStore) using [`AssetStore::mount_pack`](super::AssetStore::mount_pack)
+//! and then queried using [`AssetStore::pack_file`](super::AssetStore::pack_file)
+//!
+//! On the web target the whole pack is fetched as a single blob using [`fetch_pack`]
+use std::sync::Arc;
+
+pub use lunar_pack::{Compression, Entry, Error, Pack, PackBuilder};
+
+#[derive(Clone)]
+///A handle to a single file inside of a mounted pack
+///
+///Keeps the pack alive, so the data can be read during the initialization of an asset
+pub struct PackFile {
+    pack: Arc<Pack>,
+    name: String,
+}
+
+impl PackFile {
+    ///Creates a handle to the file with the given name, if the pack contains it
+    #[must_use]
+    pub fn new(pack: Arc<Pack>, name: &str) -> Option<Self> {
+        if pack.contains(name) {
+            Some(Self {
+                pack,
+                name: name.to_owned(),
+            })
+        } else {
+            None
+        }
+    }
+
+    ///Returns the name of the file inside the pack
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    ///Reads and decompresses the file
+    ///
+    ///# Errors
+    ///Returns an error if the file could not be decompressed
+    pub fn read(&self) -> Result<Vec<u8>, Error> {
+        self.pack.read(&self.name)
+    }
+
+    ///Reads the file as utf-8 text
+    ///
+    ///# Errors
+    ///Returns an error if the file could not be decompressed or is not valid utf-8
+    pub fn read_to_string(&self) -> Result<String, Error> {
+        self.pack.read_to_string(&self.name)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+///Fetches a pack from the given url
+///
+///# Errors
+///Returns an error if the request failed or if the response is not a valid pack
+pub async fn fetch_pack(url: &str) -> Result<Pack, Error> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let js_error = |e: wasm_bindgen::JsValue| Error::Io(std::io::Error::other(format!("{e:?}")));
+
+    let window = web_sys::window().unwrap();
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+
+    if !response.ok() {
+        return Err(Error::Io(std::io::Error::other(format!(
+            "Failed to fetch {url}: {}",
+            response.status()
+        ))));
+    }
+
+    let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+
+    Pack::from_bytes(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -1,9 +1,14 @@
+use std::sync::Mutex;
+
 use super::*;
 
 struct TestAsset {
     id: Option<UUID>,
     initialized: bool,
     data: i32,
+    dependencies: Vec<UUID>,
+    //Records the order of initialization
+    order: Option<Arc<Mutex<Vec<UUID>>>>,
 }
 impl TestAsset {
     const fn new() -> Self {
@@ -11,6 +16,18 @@ impl TestAsset {
             id: None,
             initialized: false,
             data: 0,
+            dependencies: Vec::new(),
+            order: None,
+        }
+    }
+
+    fn with_dependencies(dependencies: Vec<UUID>, order: Arc<Mutex<Vec<UUID>>>) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            data: 0,
+            dependencies,
+            order: Some(order),
         }
     }
 }
@@ -23,6 +40,9 @@ impl Asset for TestAsset {
     fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
         self.initialized = true;
         self.data = 20;
+        if let Some(o) = &self.order {
+            o.lock().unwrap().push(self.id.unwrap());
+        }
         Ok(())
     }
 
@@ -43,6 +63,35 @@ impl Asset for TestAsset {
     fn is_initialized(&self) -> bool {
         self.initialized
     }
+
+    fn dependencies(&self) -> Vec<UUID> {
+        self.dependencies.clone()
+    }
+}
+
+struct PanickingAsset {
+    id: Option<UUID>,
+}
+
+impl Asset for PanickingAsset {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        panic!("Asset failed to initialize");
+    }
+
+    fn dispose(&mut self) {}
+
+    fn set_id(&mut self, id: UUID) -> Result<(), Error> {
+        self.id = Some(id);
+        Ok(())
+    }
+
+    fn is_initialized(&self) -> bool {
+        false
+    }
 }
 
 #[test]
@@ -83,3 +132,135 @@ fn test_asset_borrowing() {
 
     assert_eq!(borrow.data, -20);
 }
+
+#[test]
+fn test_dependency_order() {
+    let mut store = AssetStore::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    //3 depends on 2 and 1, 2 depends on 1
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![2, 1], order.clone()), 3)
+        .unwrap();
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![1], order.clone()), 2)
+        .unwrap();
+    store
+        .try_register_with_id(TestAsset::with_dependencies(Vec::new(), order.clone()), 1)
+        .unwrap();
+
+    store.intialize_all().unwrap();
+    assert_eq!(*order.lock().unwrap(), vec![1, 2, 3]);
+
+    //Lazy initialization also initializes dependencies first
+    store.dispose_all();
+    order.lock().unwrap().clear();
+
+    store.get_by_id::<TestAsset>(3).unwrap();
+    assert_eq!(*order.lock().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_dependency_errors() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let mut store = AssetStore::new();
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![2], order.clone()), 1)
+        .unwrap();
+
+    assert!(matches!(
+        store.intialize_all(),
+        Err(Error::MissingDependency {
+            asset: 1,
+            dependency: 2
+        })
+    ));
+
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![1], order.clone()), 2)
+        .unwrap();
+
+    assert!(matches!(
+        store.intialize_all(),
+        Err(Error::DependencyCycle(_))
+    ));
+    assert!(matches!(
+        store.get_by_id::<TestAsset>(1),
+        Err(Error::DependencyCycle(_))
+    ));
+}
+
+#[test]
+fn test_initialization_panic() {
+    let mut store = AssetStore::new();
+    store.register(TestAsset::new());
+    store.register(PanickingAsset { id: None });
+
+    //The panic of the worker thread reaches the caller
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| store.intialize_all()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_dependency_disposal() {
+    let mut store = AssetStore::new();
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    store
+        .try_register_with_id(TestAsset::with_dependencies(Vec::new(), order.clone()), 1)
+        .unwrap();
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![1], order.clone()), 2)
+        .unwrap();
+    store
+        .try_register_with_id(TestAsset::with_dependencies(vec![2], order.clone()), 3)
+        .unwrap();
+    store.intialize_all().unwrap();
+
+    assert_eq!(store.get_dependents(1), vec![2]);
+
+    assert!(matches!(
+        store.dispose_by_id(1),
+        Err(Error::HasDependents(d)) if d == vec![2]
+    ));
+    assert!(store.borrow_by_id::<TestAsset>(1).unwrap().is_initialized());
+
+    store.dispose_by_id_cascading(1).unwrap();
+
+    for id in 1..=3 {
+        let a = store.assets.get(&id).unwrap().0.read();
+        assert!(!a.is_initialized());
+    }
+
+    //Once the dependents are disposed of, the asset can be disposed of
+    store.intialize_all().unwrap();
+    store.dispose_by_id(3).unwrap();
+    store.dispose_by_id(2).unwrap();
+    store.dispose_by_id(1).unwrap();
+}
+
+#[test]
+fn test_pack_mounting() {
+    let mut store = AssetStore::new();
+    assert!(store.pack_file("a.txt").is_none());
+
+    let mut builder = pack::PackBuilder::new();
+    builder.add("a.txt", b"first".to_vec()).unwrap();
+    builder.add("b.txt", b"only in first".to_vec()).unwrap();
+    store.mount_pack(pack::Pack::from_bytes(builder.build()).unwrap());
+
+    let mut builder = pack::PackBuilder::new();
+    builder.add("a.txt", b"second".to_vec()).unwrap();
+    store.mount_pack(pack::Pack::from_bytes(builder.build()).unwrap());
+
+    //Packs mounted later take precedence
+    let file = store.pack_file("a.txt").unwrap();
+    assert_eq!(file.name(), "a.txt");
+    assert_eq!(file.read_to_string().unwrap(), "second");
+    assert_eq!(
+        store.pack_file("b.txt").unwrap().read().unwrap(),
+        b"only in first"
+    );
+    assert!(store.pack_file("c.txt").is_none());
+}
@@ -0,0 +1,367 @@
+use crate::{
+    UUID,
+    asset_managment::Asset,
+    math::{Quaternion, Vec3},
+    structures::Color,
+};
+
+///How values between keyframes are calculated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    ///The value of the previous keyframe is used
+    Step,
+    ///Values are interpolated linearly, rotations are spherically interpolated
+    Linear,
+    ///Values are interpolated using a cubic hermite spline, every keyframe stores an in
+    ///tangent, a value and an out tangent
+    CubicSpline,
+}
+
+///A value that can be animated using keyframes
+pub trait Keyframe: Copy {
+    ///Interpolates between the values, returns self if `t` is 0 and `other` if `t` is 1
+    #[must_use]
+    fn interpolate(self, other: Self, t: f32) -> Self;
+    ///Evaluates a cubic hermite spline with the given basis weights
+    #[must_use]
+    fn hermite(weights: [f32; 4], values: [Self; 4]) -> Self;
+}
+
+impl Keyframe for f32 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        (other - self).mul_add(t, self)
+    }
+
+    fn hermite(weights: [f32; 4], values: [Self; 4]) -> Self {
+        values
+            .into_iter()
+            .zip(weights)
+            .fold(0.0, |sum, (v, w)| v.mul_add(w, sum))
+    }
+}
+
+impl Keyframe for Vec3 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+
+    fn hermite(weights: [f32; 4], values: [Self; 4]) -> Self {
+        values
+            .into_iter()
+            .zip(weights)
+            .fold(Self::default(), |sum, (v, w)| sum + v * w)
+    }
+}
+
+impl Keyframe for Quaternion {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.slerp(&other, t)
+    }
+
+    fn hermite(weights: [f32; 4], values: [Self; 4]) -> Self {
+        values
+            .into_iter()
+            .zip(weights)
+            .fold(Self::new(0, 0, 0, 0), |sum, (v, w)| {
+                Self::new(
+                    v.w.mul_add(w, sum.w),
+                    v.x.mul_add(w, sum.x),
+                    v.y.mul_add(w, sum.y),
+                    v.z.mul_add(w, sum.z),
+                )
+            })
+            .normalize()
+    }
+}
+
+impl Keyframe for Color {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        Self {
+            r: self.r.interpolate(other.r, t),
+            g: self.g.interpolate(other.g, t),
+            b: self.b.interpolate(other.b, t),
+            a: self.a.interpolate(other.a, t),
+        }
+    }
+
+    fn hermite(weights: [f32; 4], values: [Self; 4]) -> Self {
+        Self {
+            r: f32::hermite(weights, values.map(|c| c.r)),
+            g: f32::hermite(weights, values.map(|c| c.g)),
+            b: f32::hermite(weights, values.map(|c| c.b)),
+            a: f32::hermite(weights, values.map(|c| c.a)),
+        }
+    }
+}
+
+///Samples the keyframes at the given time, times outside of the keyframes are clamped
+pub(crate) fn sample<T: Keyframe>(
+    times: &[f32],
+    values: &[T],
+    interpolation: Interpolation,
+    time: f32,
+) -> T {
+    let value = |key: usize| {
+        if interpolation == Interpolation::CubicSpline {
+            values[key * 3 + 1]
+        } else {
+            values[key]
+        }
+    };
+
+    let next = times.partition_point(|t| *t <= time);
+    if next == 0 {
+        return value(0);
+    }
+    if next == times.len() {
+        return value(times.len() - 1);
+    }
+
+    let previous = next - 1;
+    let delta = times[next] - times[previous];
+    let t = (time - times[previous]) / delta;
+
+    match interpolation {
+        Interpolation::Step => value(previous),
+        Interpolation::Linear => value(previous).interpolate(value(next), t),
+        Interpolation::CubicSpline => {
+            let (t2, t3) = (t * t, t * t * t);
+            let weights = [
+                2.0f32.mul_add(t3, -3.0 * t2) + 1.0,
+                (2.0f32.mul_add(-t2, t3) + t) * delta,
+                (-2.0f32).mul_add(t3, 3.0 * t2),
+                (t3 - t2) * delta,
+            ];
+            T::hermite(
+                weights,
+                [
+                    values[previous * 3 + 1],
+                    //Out tangent of the previous keyframe
+                    values[previous * 3 + 2],
+                    values[next * 3 + 1],
+                    //In tangent of the next keyframe
+                    values[next * 3],
+                ],
+            )
+        }
+    }
+}
+
+///Checks that the keyframe times are increasing and that there is a value for every keyframe
+pub(crate) fn keyframes_valid(times: &[f32], values: usize, interpolation: Interpolation) -> bool {
+    let values_per_key = if interpolation == Interpolation::CubicSpline {
+        3
+    } else {
+        1
+    };
+
+    !times.is_empty()
+        && values == times.len() * values_per_key
+        && times.iter().all(|t| t.is_finite())
+        && times.windows(2).all(|t| t[0] < t[1])
+}
+
+///Keyframes of a single animated value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    ///Interpolation between the keyframes
+    pub interpolation: Interpolation,
+    ///Times of the keyframes in seconds, in increasing order
+    pub times: Vec<f32>,
+    ///Values of the keyframes, three per keyframe when using [`Interpolation::CubicSpline`]
+    pub values: Vec<T>,
+}
+
+impl<T: Keyframe> Track<T> {
+    ///Creates a new track from the times and the values of the keyframes
+    #[must_use]
+    pub const fn new(interpolation: Interpolation, times: Vec<f32>, values: Vec<T>) -> Self {
+        Self {
+            interpolation,
+            times,
+            values,
+        }
+    }
+
+    ///Creates a new track from `(time, value)` pairs
+    ///
+    ///# Panics
+    ///Panics if the interpolation is [`Interpolation::CubicSpline`], since the keyframes would
+    ///have no tangents
+    #[must_use]
+    pub fn from_keyframes(interpolation: Interpolation, keyframes: &[(f32, T)]) -> Self {
+        assert_ne!(
+            interpolation,
+            Interpolation::CubicSpline,
+            "Cubic spline keyframes need tangents"
+        );
+        Self::new(
+            interpolation,
+            keyframes.iter().map(|k| k.0).collect(),
+            keyframes.iter().map(|k| k.1).collect(),
+        )
+    }
+
+    ///Returns the value of the track at the given time in seconds, times outside of the
+    ///keyframes are clamped
+    ///
+    ///# Panics
+    ///Panics if the track has no keyframes
+    #[must_use]
+    pub fn sample(&self, time: f32) -> T {
+        sample(&self.times, &self.values, self.interpolation, time)
+    }
+
+    ///Returns the time of the last keyframe
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or_default()
+    }
+
+    fn is_valid(&self) -> bool {
+        keyframes_valid(&self.times, self.values.len(), self.interpolation)
+    }
+}
+
+///A property of an entity that is animated by a track
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyTrack {
+    ///Position of the [`crate::components::transform::Transform`]
+    Position(Track<Vec3>),
+    ///Rotation of the [`crate::components::transform::Transform`]
+    Rotation(Track<Quaternion>),
+    ///Scale of the [`crate::components::transform::Transform`]
+    Scale(Track<Vec3>),
+    ///Color of the directional or point light on the entity
+    LightColor(Track<Color>),
+    ///Intensity of the directional or point light on the entity
+    LightIntensity(Track<f32>),
+    ///Color of the material animated by the player
+    MaterialColor(Track<Color>),
+}
+
+impl PropertyTrack {
+    fn duration(&self) -> f32 {
+        match self {
+            Self::Position(t) | Self::Scale(t) => t.duration(),
+            Self::Rotation(t) => t.duration(),
+            Self::LightColor(t) | Self::MaterialColor(t) => t.duration(),
+            Self::LightIntensity(t) => t.duration(),
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Position(t) | Self::Scale(t) => t.is_valid(),
+            Self::Rotation(t) => t.is_valid(),
+            Self::LightColor(t) | Self::MaterialColor(t) => t.is_valid(),
+            Self::LightIntensity(t) => t.is_valid(),
+        }
+    }
+}
+
+///A named event that is fired when the playback reaches its time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    ///Time of the event in seconds
+    pub time: f32,
+    ///Name of the event
+    pub name: String,
+}
+
+///Asset that stores keyframe animations of the properties of an entity
+pub struct AnimationClip {
+    id: Option<UUID>,
+    initialized: bool,
+    name: String,
+    duration: f32,
+    tracks: Vec<PropertyTrack>,
+    events: Vec<AnimationEvent>,
+}
+
+impl AnimationClip {
+    ///Creates a new clip, the duration of the clip is the time of its last keyframe or event
+    #[must_use]
+    pub fn new(name: &str, tracks: Vec<PropertyTrack>, mut events: Vec<AnimationEvent>) -> Self {
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let duration = tracks
+            .iter()
+            .map(PropertyTrack::duration)
+            .chain(events.iter().map(|e| e.time))
+            .fold(0.0, f32::max);
+
+        Self {
+            id: None,
+            initialized: false,
+            name: name.to_owned(),
+            duration,
+            tracks,
+            events,
+        }
+    }
+
+    ///Returns the name of the clip
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    ///Returns the length of the clip in seconds
+    #[must_use]
+    pub const fn get_duration(&self) -> f32 {
+        self.duration
+    }
+
+    ///Sets the length of the clip in seconds, allows for clips that hold their last keyframe
+    pub const fn set_duration(&mut self, duration: f32) {
+        self.duration = duration;
+    }
+
+    ///Returns the tracks of the clip
+    #[must_use]
+    pub fn get_tracks(&self) -> &[PropertyTrack] {
+        &self.tracks
+    }
+
+    ///Returns the events of the clip, ordered by their time
+    #[must_use]
+    pub fn get_events(&self) -> &[AnimationEvent] {
+        &self.events
+    }
+}
+
+impl Asset for AnimationClip {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        if !self.tracks.iter().all(PropertyTrack::is_valid) {
+            return Err(crate::import::invalid(
+                "Animation track has invalid keyframes",
+            ));
+        }
+        if !self.duration.is_finite() || self.duration < 0.0 {
+            return Err(crate::import::invalid("Invalid animation clip duration"));
+        }
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn dispose(&mut self) {
+        self.initialized = false;
+    }
+
+    fn set_id(&mut self, id: UUID) -> Result<(), crate::asset_managment::Error> {
+        if self.id.is_some() {
+            Err(crate::asset_managment::Error::IdAlreadySet)
+        } else {
+            self.id = Some(id);
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+}
@@ -0,0 +1,444 @@
+use std::path::{Path, PathBuf};
+
+use lunar_png::{Image, ImageType};
+
+use crate::{
+    UUID,
+    asset_managment::Asset,
+    assets::{ImageFormat, Texture},
+    helpers::flip_texture,
+    import::invalid,
+};
+
+///A named rectangle of an atlas, in pixels from the top left corner of the image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtlasRegion {
+    ///Name of the region, the file name of a packed image, the name from the json file, or the
+    ///index of a grid cell
+    pub name: String,
+    ///Distance of the left edge from the left of the image
+    pub x: u32,
+    ///Distance of the top edge from the top of the image
+    pub y: u32,
+    ///Width of the region
+    pub width: u32,
+    ///Height of the region
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    ///Returns the texture coordinates of the bottom left and the top right corner of the region
+    ///inside of a texture with the given size
+    #[must_use]
+    pub fn uv_rect(&self, (width, height): (u32, u32)) -> [f32; 4] {
+        let (width, height) = (width as f32, height as f32);
+        //Textures are stored from bottom to top
+        [
+            self.x as f32 / width,
+            1.0 - (self.y + self.height) as f32 / height,
+            (self.x + self.width) as f32 / width,
+            1.0 - self.y as f32 / height,
+        ]
+    }
+}
+
+///Source of an image of the atlas
+enum Source {
+    File(PathBuf),
+    Static(&'static [u8]),
+}
+
+impl Source {
+    fn read(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + Send>> {
+        match self {
+            Self::File(path) => std::fs::read(path).map_err(|e| Box::new(e) as _),
+            Self::Static(data) => Ok(data.to_vec()),
+        }
+    }
+}
+
+///An image used by the atlas, `None` format if it could not be guessed from the file extension
+struct AtlasImage(Source, Option<ImageFormat>);
+
+impl AtlasImage {
+    fn from_path(path: &Path) -> Self {
+        let format = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(ImageFormat::from_extension);
+        Self(Source::File(path.to_owned()), format)
+    }
+
+    ///Decodes the image, rows are stored from top to bottom
+    fn decode(&self) -> Result<Image, Box<dyn std::error::Error + Send>> {
+        let format = self
+            .1
+            .ok_or_else(|| invalid("Unknown format of an atlas image"))?;
+        let mut image = format.decode(self.0.read()?)?;
+        if !matches!(image.img_type, ImageType::Rgba8) {
+            return Err(invalid("Atlas images must be 8 bit rgba images"));
+        }
+        flip_texture(&mut image);
+        Ok(image)
+    }
+}
+
+///Where the regions of the atlas come from
+enum Layout {
+    ///Named images packed together when the atlas is initialized
+    Packed(Vec<(String, AtlasImage)>),
+    ///A single image with the regions described by a json file
+    Json(Source, AtlasImage),
+    ///A single image split into a grid of equally sized cells
+    Grid(AtlasImage, u32, u32),
+}
+
+///Many images stored in a single texture, so sprites that use them can be drawn together
+///
+///The images are either packed into the atlas when it is initialized, described by a json file
+///exported by a texture packing tool, or a grid of equally sized frames of a sprite sheet
+pub struct TextureAtlas {
+    id: Option<UUID>,
+    initialized: bool,
+    layout: Layout,
+    padding: u32,
+    filter: wgpu::FilterMode,
+    regions: Vec<AtlasRegion>,
+    texture: Option<Texture>,
+}
+
+impl TextureAtlas {
+    const fn with_layout(layout: Layout) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            layout,
+            padding: 1,
+            filter: wgpu::FilterMode::Linear,
+            regions: Vec::new(),
+            texture: None,
+        }
+    }
+
+    ///Creates an atlas that packs the image files together when it is initialized
+    ///
+    ///The regions are named after the file names without the extensions, and the formats are
+    ///guessed from the extensions
+    #[must_use]
+    pub fn pack(images: &[&Path]) -> Self {
+        Self::with_layout(Layout::Packed(
+            images
+                .iter()
+                .map(|path| {
+                    let name = path
+                        .file_stem()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    (name, AtlasImage::from_path(path))
+                })
+                .collect(),
+        ))
+    }
+
+    ///Creates an atlas that packs the named images, loaded at compile time, together when it is
+    ///initialized
+    #[must_use]
+    pub fn pack_static(images: &[(&str, &'static [u8], ImageFormat)]) -> Self {
+        Self::with_layout(Layout::Packed(
+            images
+                .iter()
+                .map(|(name, data, format)| {
+                    (
+                        (*name).to_owned(),
+                        AtlasImage(Source::Static(data), Some(*format)),
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    ///Creates an atlas out of an image and a json file describing its regions
+    ///
+    ///See [`import::atlas`](crate::import::atlas) for the supported json layouts
+    #[must_use]
+    pub fn new_json(json: &Path, image: &Path) -> Self {
+        Self::with_layout(Layout::Json(
+            Source::File(json.to_owned()),
+            AtlasImage::from_path(image),
+        ))
+    }
+
+    ///Creates an atlas out of an image and a json file describing its regions, both loaded at
+    ///compile time
+    #[must_use]
+    pub const fn static_json(
+        json: &'static str,
+        image: &'static [u8],
+        format: ImageFormat,
+    ) -> Self {
+        Self::with_layout(Layout::Json(
+            Source::Static(json.as_bytes()),
+            AtlasImage(Source::Static(image), Some(format)),
+        ))
+    }
+
+    ///Creates an atlas out of a sprite sheet with the given number of equally sized columns and
+    ///rows
+    ///
+    ///The regions are named after their index, going from the top left corner to the right and
+    ///then down
+    #[must_use]
+    pub fn new_grid(image: &Path, columns: u32, rows: u32) -> Self {
+        Self::with_layout(Layout::Grid(AtlasImage::from_path(image), columns, rows))
+    }
+
+    ///Creates an atlas out of a sprite sheet loaded at compile time, see [`TextureAtlas::new_grid`]
+    #[must_use]
+    pub const fn static_grid(
+        image: &'static [u8],
+        format: ImageFormat,
+        columns: u32,
+        rows: u32,
+    ) -> Self {
+        Self::with_layout(Layout::Grid(
+            AtlasImage(Source::Static(image), Some(format)),
+            columns,
+            rows,
+        ))
+    }
+
+    ///Sets the number of empty pixels between packed images, which stops the images from bleeding
+    ///into each other when filtered. Defaults to 1
+    ///
+    ///Takes effect the next time the atlas is initialized
+    pub const fn set_padding(&mut self, padding: u32) {
+        self.padding = padding;
+    }
+
+    ///Sets the filter of the texture, [`wgpu::FilterMode::Nearest`] keeps the edges of pixel art
+    ///sharp
+    ///
+    ///Takes effect the next time the atlas is initialized
+    pub const fn set_filter(&mut self, filter: wgpu::FilterMode) {
+        self.filter = filter;
+    }
+
+    ///Returns the regions of the atlas, empty until the atlas is initialized
+    #[must_use]
+    pub fn regions(&self) -> &[AtlasRegion] {
+        &self.regions
+    }
+
+    ///Returns the region with the name
+    #[must_use]
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+
+    ///Returns the index of the region with the name
+    #[must_use]
+    pub fn region_index(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|r| r.name == name)
+    }
+
+    ///Returns the indices of the regions whose names start with the prefix, useful for the frames
+    ///of an animation
+    ///
+    ///The regions are sorted by their names, shorter names first, so `walk_2` comes before
+    ///`walk_10`
+    #[must_use]
+    pub fn frames(&self, prefix: &str) -> Vec<usize> {
+        frames(&self.regions, prefix)
+    }
+
+    ///Returns the width and the height of the atlas, `None` if it is not initialized
+    #[must_use]
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.texture.as_ref().and_then(Texture::size)
+    }
+
+    ///Returns the texture of the atlas, `None` if it is not initialized
+    pub(crate) const fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    ///Builds the image of the atlas, stored from top to bottom, and its regions
+    fn build(&self) -> Result<(Image, Vec<AtlasRegion>), Box<dyn std::error::Error + Send>> {
+        match &self.layout {
+            Layout::Packed(images) => {
+                let decoded = images
+                    .iter()
+                    .map(|(_, image)| image.decode())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let sizes = decoded
+                    .iter()
+                    .map(|i| (i.width, i.height))
+                    .collect::<Vec<_>>();
+                let ((width, height), positions) = pack_rects(&sizes, self.padding);
+
+                let size = (width as usize)
+                    .checked_mul(height as usize)
+                    .and_then(|pixels| pixels.checked_mul(4))
+                    .ok_or_else(|| invalid("Atlas is too large"))?;
+
+                //Every offset is within the atlas, so it fits into an usize
+                let mut data = vec![0; size];
+                for (image, (x, y)) in decoded.iter().zip(&positions) {
+                    let row_size = image.width as usize * 4;
+                    for (row, pixels) in image.data.chunks_exact(row_size).enumerate() {
+                        let start = ((*y as usize + row) * width as usize + *x as usize) * 4;
+                        data[start..start + row_size].copy_from_slice(pixels);
+                    }
+                }
+
+                let regions = images
+                    .iter()
+                    .zip(sizes.iter().zip(positions))
+                    .map(|((name, _), ((width, height), (x, y)))| AtlasRegion {
+                        name: name.clone(),
+                        x,
+                        y,
+                        width: *width,
+                        height: *height,
+                    })
+                    .collect();
+                let image = Image {
+                    img_type: ImageType::Rgba8,
+                    width,
+                    height,
+                    data,
+                };
+                Ok((image, regions))
+            }
+            Layout::Json(json, image) => {
+                let json = String::from_utf8(json.read()?)
+                    .map_err(|_| invalid("Atlas json is not valid utf-8"))?;
+                let regions = crate::import::atlas::parse(&json)?;
+                let image = image.decode()?;
+                if regions.iter().any(|r| {
+                    r.x.checked_add(r.width).is_none_or(|e| e > image.width)
+                        || r.y.checked_add(r.height).is_none_or(|e| e > image.height)
+                }) {
+                    return Err(invalid("Atlas region is outside of the image"));
+                }
+                Ok((image, regions))
+            }
+            Layout::Grid(image, columns, rows) => {
+                let image = image.decode()?;
+                let regions = grid_regions((image.width, image.height), *columns, *rows);
+                Ok((image, regions))
+            }
+        }
+    }
+}
+
+///Returns the indices of the regions whose names start with the prefix, sorted by their names
+pub(crate) fn frames(regions: &[AtlasRegion], prefix: &str) -> Vec<usize> {
+    let mut frames = (0..regions.len())
+        .filter(|i| regions[*i].name.starts_with(prefix))
+        .collect::<Vec<_>>();
+    frames.sort_by(|a, b| {
+        let (a, b) = (&regions[*a].name, &regions[*b].name);
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    });
+    frames
+}
+
+///Splits an image into a grid of equally sized regions, going from the top left corner to the
+///right and then down
+pub(crate) fn grid_regions(
+    (width, height): (u32, u32),
+    columns: u32,
+    rows: u32,
+) -> Vec<AtlasRegion> {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    let (cell_width, cell_height) = (width / columns, height / rows);
+    (0..rows * columns)
+        .map(|i| AtlasRegion {
+            name: i.to_string(),
+            x: i % columns * cell_width,
+            y: i / columns * cell_height,
+            width: cell_width,
+            height: cell_height,
+        })
+        .collect()
+}
+
+///Packs the rectangles into shelves, returns the size of the atlas and the top left corner of
+///every rectangle
+///
+///The width of the atlas is a power of two, large enough for the atlas to be roughly square
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+pub(crate) fn pack_rects(sizes: &[(u32, u32)], padding: u32) -> ((u32, u32), Vec<(u32, u32)>) {
+    let padded = sizes
+        .iter()
+        .map(|(w, h)| (w + padding, h + padding))
+        .collect::<Vec<_>>();
+    let area = padded
+        .iter()
+        .map(|(w, h)| u64::from(*w) * u64::from(*h))
+        .sum::<u64>();
+    let widest = padded.iter().map(|s| s.0).max().unwrap_or(1);
+    let width = ((area as f64).sqrt().ceil() as u32)
+        .max(widest)
+        .next_power_of_two();
+
+    //Taller rectangles first, so the shelves waste less space
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| std::cmp::Reverse(padded[*i].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf) = (0, 0, 0);
+    for i in order {
+        let (w, h) = padded[i];
+        if x + w > width {
+            y += shelf;
+            (x, shelf) = (0, 0);
+        }
+        positions[i] = (x, y);
+        x += w;
+        shelf = shelf.max(h);
+    }
+
+    ((width, (y + shelf).max(1)), positions)
+}
+
+impl Asset for TextureAtlas {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let (mut image, regions) = self.build()?;
+        //Textures are stored from bottom to top
+        flip_texture(&mut image);
+
+        let mut texture = Texture::from_image(image);
+        texture.set_filter(self.filter);
+        _ = texture.set_id(self.get_id());
+        texture.initialize()?;
+
+        self.regions = regions;
+        self.texture = Some(texture);
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn dispose(&mut self) {
+        self.texture = None;
+        self.initialized = false;
+    }
+
+    fn set_id(&mut self, id: UUID) -> Result<(), crate::asset_managment::Error> {
+        if self.id.is_some() {
+            Err(crate::asset_managment::Error::IdAlreadySet)
+        } else {
+            self.id = Some(id);
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    UUID,
+    asset_managment::Asset,
+    import::ttf::{self, FontFile, FontMetrics},
+};
+
+mod layout;
+mod raster;
+
+pub use layout::{LayoutGlyph, LayoutOptions, TextAlignment, TextLayout};
+
+///Size in pixels the glyphs of signed distance field fonts are rasterized at
+const SDF_SIZE: u32 = 48;
+///Distance in pixels covered by the signed distance field on each side of the edges
+const SDF_SPREAD: u32 = 6;
+///Initial width and height of the glyph atlas
+const ATLAS_SIZE: u32 = 512;
+///The glyph atlas does not grow larger than this
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+///Number of glyph atlas textures created so far, used to tell the textures apart
+static ATLAS_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+///Where a rasterized glyph is stored in the atlas, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GlyphRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    ///Distance from the origin of the glyph to the left edge of the bitmap
+    pub left: i32,
+    ///Distance from the baseline to the top edge of the bitmap
+    pub top: i32,
+}
+
+///Single channel texture the glyphs are rasterized into when they are first used
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    width: u32,
+    height: u32,
+    ///Copy of the texture, rows are stored from top to bottom
+    pixels: Vec<u8>,
+    ///Position of the next glyph and the height of the current row of glyphs
+    cursor: (u32, u32),
+    row_height: u32,
+    ///Rows that were changed since the last upload
+    dirty: Option<(u32, u32)>,
+    ///Different for every created texture
+    generation: u32,
+    ///Rasterized glyphs by their index and size, `None` if the glyph did not fit
+    glyphs: HashMap<(u16, u32), Option<GlyphRect>>,
+}
+
+impl GlyphAtlas {
+    fn new(width: u32, height: u32) -> Self {
+        let device = crate::DEVICE.get().unwrap();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph atlas"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph atlas"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+            cursor: (0, 0),
+            row_height: 0,
+            dirty: Some((0, height)),
+            generation: ATLAS_GENERATION.fetch_add(1, Ordering::Relaxed),
+            glyphs: HashMap::new(),
+        }
+    }
+
+    ///Doubles the size of the atlas, keeping the glyphs where they are
+    fn grow(&mut self) -> bool {
+        if self.width >= MAX_ATLAS_SIZE {
+            return false;
+        }
+        let mut grown = Self::new(self.width * 2, self.height * 2);
+        for (row, pixels) in self.pixels.chunks_exact(self.width as usize).enumerate() {
+            let start = row * grown.width as usize;
+            grown.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+        }
+        grown.cursor = self.cursor;
+        grown.row_height = self.row_height;
+        grown.glyphs = std::mem::take(&mut self.glyphs);
+        *self = grown;
+        true
+    }
+
+    ///Finds space for a bitmap with the size, in rows of glyphs
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        //One empty pixel between the glyphs, so they don't bleed into each other
+        let (padded_width, padded_height) = (width + 1, height + 1);
+        loop {
+            if self.cursor.0 + padded_width > self.width {
+                self.cursor = (0, self.cursor.1 + self.row_height);
+                self.row_height = 0;
+            }
+            if self.cursor.1 + padded_height <= self.height && padded_width <= self.width {
+                break;
+            }
+            if !self.grow() {
+                return None;
+            }
+        }
+        let position = self.cursor;
+        self.cursor.0 += padded_width;
+        self.row_height = self.row_height.max(padded_height);
+        Some(position)
+    }
+
+    fn insert(&mut self, bitmap: &raster::Bitmap) -> Option<GlyphRect> {
+        if bitmap.width == 0 {
+            return Some(GlyphRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+                left: 0,
+                top: 0,
+            });
+        }
+        let (x, y) = self.allocate(bitmap.width, bitmap.height)?;
+        for (row, pixels) in bitmap.data.chunks_exact(bitmap.width as usize).enumerate() {
+            let start = (y as usize + row) * self.width as usize + x as usize;
+            self.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+        }
+        let (start, end) = self.dirty.unwrap_or((y, y));
+        self.dirty = Some((start.min(y), end.max(y + bitmap.height)));
+
+        Some(GlyphRect {
+            x,
+            y,
+            width: bitmap.width,
+            height: bitmap.height,
+            left: bitmap.left,
+            top: bitmap.top,
+        })
+    }
+
+    ///Writes the changed rows into the texture
+    fn upload(&mut self) {
+        let Some((start, end)) = self.dirty.take() else {
+            return;
+        };
+        let offset = (start * self.width) as usize;
+        crate::QUEUE.get().unwrap().write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: start,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels[offset..(end * self.width) as usize],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.width),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: end - start,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+enum Source {
+    File(PathBuf),
+    Static(&'static [u8]),
+}
+
+///A TrueType or OpenType font used by [`crate::components::text::Text`]
+///
+///Glyphs are rasterized into an atlas texture when they are first drawn, for every size they are
+///drawn at. Signed distance field fonts rasterize every glyph once and stay sharp at any size and
+///in world space, at the cost of slightly rounder corners
+pub struct Font {
+    id: Option<UUID>,
+    initialized: bool,
+    source: Source,
+    sdf: bool,
+    file: Option<FontFile>,
+    atlas: Option<GlyphAtlas>,
+}
+
+impl Font {
+    const fn with_source(source: Source) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            source,
+            sdf: false,
+            file: None,
+            atlas: None,
+        }
+    }
+
+    ///Creates a new font from a .ttf or .otf file
+    #[must_use]
+    pub fn new(path: &Path) -> Self {
+        Self::with_source(Source::File(path.to_owned()))
+    }
+
+    ///Creates a new font from a file loaded at compile time
+    #[must_use]
+    pub const fn from_static(data: &'static [u8]) -> Self {
+        Self::with_source(Source::Static(data))
+    }
+
+    ///Sets whether the glyphs are stored as signed distance fields
+    ///
+    ///Takes effect the next time the font is initialized
+    pub const fn set_sdf(&mut self, sdf: bool) {
+        self.sdf = sdf;
+    }
+
+    ///Returns whether the glyphs are stored as signed distance fields
+    #[must_use]
+    pub const fn is_sdf(&self) -> bool {
+        self.sdf
+    }
+
+    ///Returns the metrics of the font, `None` if the font is not initialized
+    #[must_use]
+    pub fn metrics(&self) -> Option<FontMetrics> {
+        self.file.as_ref().map(FontFile::metrics)
+    }
+
+    ///Places the glyphs of the text, can be used for measuring text
+    ///
+    ///# Panics
+    ///Panics if the font is not initialized
+    #[must_use]
+    pub fn layout(&self, text: &str, options: &LayoutOptions) -> TextLayout {
+        layout::layout(
+            self.file.as_ref().expect("Font is not initialized"),
+            text,
+            options,
+        )
+    }
+
+    ///Returns the size in pixels the glyphs requested at the size are rasterized at
+    pub(crate) fn raster_size(&self, size: u32) -> u32 {
+        if self.sdf { SDF_SIZE } else { size.max(1) }
+    }
+
+    ///Returns where the glyph rasterized at the size in pixels is stored in the atlas,
+    ///rasterizing it if it is not there yet
+    ///
+    ///Signed distance field fonts ignore the size. Returns `None` if the glyph does not fit into
+    ///the atlas
+    pub(crate) fn glyph(&mut self, glyph: u16, size: u32) -> Option<GlyphRect> {
+        let size = self.raster_size(size);
+        let file = self.file.as_ref()?;
+        let atlas = self
+            .atlas
+            .get_or_insert_with(|| GlyphAtlas::new(ATLAS_SIZE, ATLAS_SIZE));
+        if let Some(rect) = atlas.glyphs.get(&(glyph, size)) {
+            return *rect;
+        }
+
+        let outline = file.outline(glyph).unwrap_or_else(|e| {
+            log::warn!("Failed to read glyph {glyph}: {e}");
+            Vec::new()
+        });
+        let scale = size as f32 / f32::from(file.metrics().units_per_em);
+        let rect = if self.sdf {
+            let bitmap = raster::rasterize(&outline, scale, SDF_SPREAD);
+            atlas.insert(&raster::signed_distance_field(&bitmap, SDF_SPREAD))
+        } else {
+            atlas.insert(&raster::rasterize(&outline, scale, 1))
+        };
+        if rect.is_none() {
+            log::warn!("Glyph atlas is full");
+        }
+        atlas.glyphs.insert((glyph, size), rect);
+        rect
+    }
+
+    ///Writes the newly rasterized glyphs into the texture, creating the texture if no glyph was
+    ///rasterized yet
+    pub(crate) fn upload(&mut self) {
+        if self.file.is_some() {
+            self.atlas
+                .get_or_insert_with(|| GlyphAtlas::new(ATLAS_SIZE, ATLAS_SIZE))
+                .upload();
+        }
+    }
+
+    ///Returns the size of the atlas and a number that changes every time its texture is recreated
+    pub(crate) fn atlas_info(&self) -> Option<((u32, u32), u32)> {
+        self.atlas
+            .as_ref()
+            .map(|a| ((a.width, a.height), a.generation))
+    }
+
+    ///Returns the view and the sampler of the atlas texture
+    pub(crate) fn atlas_binding(&self) -> Option<(&wgpu::TextureView, &wgpu::Sampler)> {
+        self.atlas.as_ref().map(|a| (&a.view, &a.sampler))
+    }
+}
+
+impl Asset for Font {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let data = match &self.source {
+            Source::File(path) => std::fs::read(path).map_err(|e| Box::new(e) as _)?,
+            Source::Static(data) => data.to_vec(),
+        };
+        self.file = Some(ttf::parse(data)?);
+        //The atlas is created when the first glyph is rasterized
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn dispose(&mut self) {
+        self.file = None;
+        self.atlas = None;
+        self.initialized = false;
+    }
+
+    fn set_id(&mut self, id: UUID) -> Result<(), crate::asset_managment::Error> {
+        if self.id.is_some() {
+            Err(crate::asset_managment::Error::IdAlreadySet)
+        } else {
+            self.id = Some(id);
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+}
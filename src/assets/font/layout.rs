@@ -0,0 +1,253 @@
+use crate::import::ttf::FontFile;
+
+///Horizontal alignment of the lines of a text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlignment {
+    ///Lines start at the left edge, the text starts at its origin
+    #[default]
+    Left,
+    ///Lines are centered, the text is centered on its origin
+    Center,
+    ///Lines end at the right edge, the text ends at its origin
+    Right,
+}
+
+impl TextAlignment {
+    ///Returns the part of the free space that is left of a line
+    const fn factor(self) -> f32 {
+        match self {
+            Self::Left => 0.0,
+            Self::Center => 0.5,
+            Self::Right => 1.0,
+        }
+    }
+}
+
+///Options of the text layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutOptions {
+    ///Size of the font, the height of an em
+    pub size: f32,
+    ///Lines that are wider than this are wrapped, at the last space if there is one
+    pub max_width: Option<f32>,
+    ///Alignment of the lines
+    pub alignment: TextAlignment,
+    ///Multiplier of the distance between lines
+    pub line_spacing: f32,
+}
+
+impl LayoutOptions {
+    ///Creates new options with the given font size, no wrapping and left alignment
+    #[must_use]
+    pub const fn new(size: f32) -> Self {
+        Self {
+            size,
+            max_width: None,
+            alignment: TextAlignment::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+///A glyph placed by the layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutGlyph {
+    ///Index of the glyph in the font
+    pub glyph: u16,
+    ///Character the glyph represents
+    pub character: char,
+    ///Position of the origin of the glyph, relative to the origin of the text
+    pub x: f32,
+    ///Position of the baseline of the glyph, below the origin of the text
+    pub y: f32,
+}
+
+///Glyphs of a text placed in lines, the origin of the text is at the top of the first line and
+///the y coordinates point down
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextLayout {
+    ///The placed glyphs
+    pub glyphs: Vec<LayoutGlyph>,
+    ///Distance from the left edge of the widest line to the right edge of it
+    pub width: f32,
+    ///Distance from the top of the first line to the bottom of the last one
+    pub height: f32,
+    ///Horizontal position of the left edge of the text
+    pub left: f32,
+    ///Number of lines
+    pub lines: usize,
+}
+
+///A line being laid out, the characters, their glyphs and the pen positions before them
+#[derive(Default)]
+struct Line {
+    glyphs: Vec<(char, u16, f32)>,
+    width: f32,
+    ///Index of the first glyph after the last space
+    break_at: Option<usize>,
+}
+
+impl Line {
+    fn push(&mut self, font: &FontFile, scale: f32, character: char, glyph: u16) {
+        let kerning = self
+            .glyphs
+            .last()
+            .map_or(0.0, |(_, previous, _)| font.kerning(*previous, glyph));
+        let x = kerning.mul_add(scale, self.width);
+        self.glyphs.push((character, glyph, x));
+        self.width = font.advance(glyph).mul_add(scale, x);
+    }
+
+    ///Returns the width without the trailing spaces
+    fn visible_width(&self) -> f32 {
+        self.glyphs
+            .iter()
+            .rposition(|g| !g.0.is_whitespace())
+            .map_or(0.0, |i| {
+                self.glyphs.get(i + 1).map_or(self.width, |(_, _, x)| *x)
+            })
+    }
+}
+
+///Places the glyphs of the text
+pub(super) fn layout(font: &FontFile, text: &str, options: &LayoutOptions) -> TextLayout {
+    let metrics = font.metrics();
+    let scale = options.size / f32::from(metrics.units_per_em);
+    let space = font.glyph_index(' ').unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = Line::default();
+        for character in paragraph.chars().filter(|c| *c != '\r') {
+            if character == ' ' || character == '\t' {
+                //Tabs are as wide as 4 spaces
+                let count = if character == '\t' { 4 } else { 1 };
+                for _ in 0..count {
+                    line.push(font, scale, ' ', space);
+                }
+                line.break_at = Some(line.glyphs.len());
+                continue;
+            }
+
+            let glyph = font.glyph_index(character).unwrap_or(0);
+            line.push(font, scale, character, glyph);
+
+            let Some(max_width) = options.max_width else {
+                continue;
+            };
+            if line.width > max_width && line.glyphs.len() > 1 {
+                //Wrap the last word, or the last character if the word is wider than the line
+                let split = line
+                    .break_at
+                    .filter(|b| *b > 0 && *b < line.glyphs.len())
+                    .unwrap_or(line.glyphs.len() - 1);
+                let rest = line.glyphs.split_off(split);
+                line.width = rest[0].2;
+                lines.push(std::mem::take(&mut line));
+                for (character, glyph, _) in rest {
+                    line.push(font, scale, character, glyph);
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    let widths = lines.iter().map(Line::visible_width).collect::<Vec<_>>();
+    let widest = widths.iter().copied().fold(0.0, f32::max);
+    let block = options.max_width.unwrap_or(widest);
+    let factor = options.alignment.factor();
+
+    let ascent = f32::from(metrics.ascender) * scale;
+    let descent = f32::from(metrics.descender) * scale;
+    let line_height = metrics.line_height() * scale * options.line_spacing;
+
+    let mut glyphs = Vec::new();
+    for (i, (line, width)) in lines.iter().zip(&widths).enumerate() {
+        let offset = (block - width).mul_add(factor, -block * factor);
+        let baseline = (i as f32).mul_add(line_height, ascent);
+        glyphs.extend(line.glyphs.iter().filter(|g| !g.0.is_whitespace()).map(
+            |(character, glyph, x)| LayoutGlyph {
+                glyph: *glyph,
+                character: *character,
+                x: x + offset,
+                y: baseline,
+            },
+        ));
+    }
+
+    TextLayout {
+        glyphs,
+        width: widest,
+        height: ((lines.len() - 1) as f32).mul_add(line_height, ascent - descent),
+        left: (block - widest).mul_add(factor, -block * factor),
+        lines: lines.len(),
+    }
+}
+
+#[test]
+fn test_text_layout() {
+    let font = crate::import::ttf::parse(crate::import::ttf::test_font(None)).unwrap();
+    let positions = |layout: &TextLayout| {
+        layout
+            .glyphs
+            .iter()
+            .map(|g| (g.character, g.x, g.y))
+            .collect::<Vec<_>>()
+    };
+
+    //An em is 10 units, A and V are 10 wide, the space is 5 wide and A is kerned with V
+    let placed = layout(&font, "AV A", &LayoutOptions::new(10.0));
+    assert_eq!(
+        positions(&placed),
+        vec![('A', 0.0, 8.0), ('V', 9.0, 8.0), ('A', 24.0, 8.0)]
+    );
+    assert_eq!(placed.width, 34.0);
+    assert_eq!(placed.height, 10.0);
+    assert_eq!((placed.left, placed.lines), (0.0, 1));
+
+    //Wraps at the space, the trailing space does not count
+    let options = LayoutOptions {
+        max_width: Some(25.0),
+        line_spacing: 2.0,
+        ..LayoutOptions::new(10.0)
+    };
+    let placed = layout(&font, "AV AB\nB", &options);
+    assert_eq!(
+        positions(&placed),
+        vec![
+            ('A', 0.0, 8.0),
+            ('V', 9.0, 8.0),
+            ('A', 0.0, 28.0),
+            ('B', 10.0, 28.0),
+            ('B', 0.0, 48.0)
+        ]
+    );
+    assert_eq!((placed.width, placed.lines), (20.0, 3));
+    assert_eq!(placed.height, 50.0);
+
+    //Words wider than the line are split
+    let placed = layout(&font, "AAA", &options);
+    assert_eq!(placed.lines, 2);
+    assert_eq!(placed.glyphs[2].x, 0.0);
+
+    //Lines are aligned within the maximum width, centered on the origin
+    let options = LayoutOptions {
+        alignment: TextAlignment::Center,
+        ..options
+    };
+    let placed = layout(&font, "AA\nA", &options);
+    assert_eq!(
+        positions(&placed),
+        vec![('A', -10.0, 8.0), ('A', 0.0, 8.0), ('A', -5.0, 28.0)]
+    );
+    assert_eq!(placed.left, -10.0);
+    let right = layout(
+        &font,
+        "A",
+        &LayoutOptions {
+            alignment: TextAlignment::Right,
+            ..LayoutOptions::new(10.0)
+        },
+    );
+    assert_eq!(right.glyphs[0].x, -10.0);
+}
@@ -0,0 +1,271 @@
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap
+)]
+use crate::import::ttf::Segment;
+
+///Coverage of a rasterized glyph, rows are stored from top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    ///Distance from the origin of the glyph to the left edge of the bitmap, in pixels
+    pub left: i32,
+    ///Distance from the baseline to the top edge of the bitmap, in pixels
+    pub top: i32,
+    pub data: Vec<u8>,
+}
+
+///Rasterizes the outline scaled by the factor, with empty pixels around it
+pub(super) fn rasterize(segments: &[Segment], scale: f32, padding: u32) -> Bitmap {
+    let points = segments.iter().flat_map(|s| match s {
+        Segment::Line(a, b) => vec![*a, *b],
+        Segment::Quad(a, c, b) => vec![*a, *c, *b],
+    });
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for p in points {
+        min = [min[0].min(p[0] * scale), min[1].min(p[1] * scale)];
+        max = [max[0].max(p[0] * scale), max[1].max(p[1] * scale)];
+    }
+    if segments.is_empty() {
+        return Bitmap {
+            width: 0,
+            height: 0,
+            left: 0,
+            top: 0,
+            data: Vec::new(),
+        };
+    }
+
+    let padding = padding.max(1);
+    let left = min[0].floor() as i32 - padding as i32;
+    let top = max[1].ceil() as i32 + padding as i32;
+    let width = (max[0].ceil() as i32 - left) as u32 + padding;
+    let height = (top - min[1].floor() as i32) as u32 + padding;
+
+    //Bitmap coordinates, with y pointing down
+    let to_bitmap = |p: [f32; 2]| {
+        [
+            p[0].mul_add(scale, -left as f32),
+            p[1].mul_add(-scale, top as f32),
+        ]
+    };
+
+    let mut accumulation = vec![0.0; (width * height) as usize + 2];
+    let mut line = |a: [f32; 2], b: [f32; 2]| {
+        accumulate_line(&mut accumulation, width as usize, height as usize, a, b);
+    };
+    for segment in segments {
+        match *segment {
+            Segment::Line(a, b) => line(to_bitmap(a), to_bitmap(b)),
+            Segment::Quad(start, control, end) => {
+                let (start, control, end) = (to_bitmap(start), to_bitmap(control), to_bitmap(end));
+                //Number of lines depends on how much the curve bends
+                let deviation = (start[0] - 2.0f32.mul_add(control[0], -end[0]))
+                    .hypot(start[1] - 2.0f32.mul_add(control[1], -end[1]));
+                let steps = ((deviation * 2.0).sqrt().ceil() as u32).clamp(1, 32);
+                let mut previous = start;
+                for i in 1..=steps {
+                    let t = i as f32 / steps as f32;
+                    let point = [0, 1].map(|axis| {
+                        let (near, far) = (
+                            (1.0 - t).mul_add(start[axis], t * control[axis]),
+                            (1.0 - t).mul_add(control[axis], t * end[axis]),
+                        );
+                        (1.0 - t).mul_add(near, t * far)
+                    });
+                    line(previous, point);
+                    previous = point;
+                }
+            }
+        }
+    }
+
+    //The coverage is the running sum of the signed areas
+    let mut sum = 0.0f32;
+    let data = accumulation[..(width * height) as usize]
+        .iter()
+        .map(|a| {
+            sum += a;
+            (sum.abs().min(1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    Bitmap {
+        width,
+        height,
+        left,
+        top,
+        data,
+    }
+}
+
+///Adds the signed area covered by the line to the cells it crosses
+fn accumulate_line(
+    accumulation: &mut [f32],
+    width: usize,
+    height: usize,
+    a: [f32; 2],
+    b: [f32; 2],
+) {
+    if (a[1] - b[1]).abs() <= f32::EPSILON {
+        return;
+    }
+    let (direction, a, b) = if a[1] < b[1] {
+        (1.0, a, b)
+    } else {
+        (-1.0, b, a)
+    };
+    let dxdy = (b[0] - a[0]) / (b[1] - a[1]);
+    let mut x = a[0];
+    if a[1] < 0.0 {
+        x -= a[1] * dxdy;
+    }
+
+    let start = a[1].max(0.0) as usize;
+    let end = height.min(b[1].ceil() as usize);
+    for y in start..end {
+        let row = y * width;
+        let dy = ((y + 1) as f32).min(b[1]) - (y as f32).max(a[1]);
+        let next_x = dxdy.mul_add(dy, x);
+        let d = dy * direction;
+        let (x0, x1) = if x < next_x { (x, next_x) } else { (next_x, x) };
+        let x0_floor = x0.floor();
+        let x0_index = x0_floor.max(0.0) as usize;
+        let x1_ceil = x1.ceil();
+        let x1_index = x1_ceil.max(0.0) as usize;
+
+        if x1_index <= x0_index + 1 {
+            //The line stays within a single cell
+            let middle = f32::midpoint(x, next_x) - x0_floor;
+            accumulation[row + x0_index] += d - d * middle;
+            accumulation[row + x0_index + 1] += d * middle;
+        } else {
+            let inverse = (x1 - x0).recip();
+            let x0_fraction = x0 - x0_floor;
+            let first = 0.5 * inverse * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+            let x1_fraction = x1 - x1_ceil + 1.0;
+            let last = 0.5 * inverse * x1_fraction * x1_fraction;
+            accumulation[row + x0_index] += d * first;
+            if x1_index == x0_index + 2 {
+                accumulation[row + x0_index + 1] += d * (1.0 - first - last);
+            } else {
+                let second = inverse * (1.5 - x0_fraction);
+                accumulation[row + x0_index + 1] += d * (second - first);
+                for i in x0_index + 2..x1_index - 1 {
+                    accumulation[row + i] += d * inverse;
+                }
+                let covered = ((x1_index - x0_index - 3) as f32).mul_add(inverse, second);
+                accumulation[row + x1_index - 1] += d * (1.0 - covered - last);
+            }
+            accumulation[row + x1_index] += d * last;
+        }
+        x = next_x;
+    }
+}
+
+///Converts the coverage into a signed distance field, 128 is the edge of the glyph and the values
+///change by `127 / spread` per pixel, increasing towards the inside
+pub(super) fn signed_distance_field(bitmap: &Bitmap, spread: u32) -> Bitmap {
+    let (width, height) = (bitmap.width as i32, bitmap.height as i32);
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < width && y < height && bitmap.data[(y * width + x) as usize] >= 128
+    };
+    let radius = spread as i32;
+
+    let mut data = Vec::with_capacity(bitmap.data.len());
+    for y in 0..height {
+        for x in 0..width {
+            let this = inside(x, y);
+            //Squared distance to the closest pixel on the other side of the edge, pixels without
+            //one in range are at least the spread away
+            let mut closest = f32::MAX;
+            for oy in -radius..=radius {
+                for ox in -radius..=radius {
+                    let distance = (ox * ox + oy * oy) as f32;
+                    if distance < closest && inside(x + ox, y + oy) != this {
+                        closest = distance;
+                    }
+                }
+            }
+            let distance = (closest.sqrt() - 0.5).min(spread as f32);
+            let signed = if this { distance } else { -distance };
+            data.push(
+                (signed / spread as f32)
+                    .mul_add(127.0, 128.0)
+                    .clamp(0.0, 255.0) as u8,
+            );
+        }
+    }
+
+    Bitmap {
+        width: bitmap.width,
+        height: bitmap.height,
+        left: bitmap.left,
+        top: bitmap.top,
+        data,
+    }
+}
+
+#[test]
+fn test_rasterize() {
+    let square = [
+        Segment::Line([100.0, 0.0], [100.0, 700.0]),
+        Segment::Line([100.0, 700.0], [900.0, 700.0]),
+        Segment::Line([900.0, 700.0], [900.0, 0.0]),
+        Segment::Line([900.0, 0.0], [100.0, 0.0]),
+    ];
+    let bitmap = rasterize(&square, 0.01, 1);
+    assert_eq!((bitmap.width, bitmap.height), (10, 9));
+    assert_eq!((bitmap.left, bitmap.top), (0, 8));
+    //The square covers whole pixels, surrounded by the padding
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let inside = (1..9).contains(&x) && (1..8).contains(&y);
+            let value = bitmap.data[(y * bitmap.width + x) as usize];
+            assert_eq!(value, if inside { 255 } else { 0 }, "pixel ({x}, {y})");
+        }
+    }
+
+    //Half a pixel is covered on the edges
+    let bitmap = rasterize(&square, 0.01, 1);
+    let shifted = square.map(|s| match s {
+        Segment::Line(a, b) => Segment::Line([a[0] + 50.0, a[1]], [b[0] + 50.0, b[1]]),
+        Segment::Quad(..) => unreachable!(),
+    });
+    let half = rasterize(&shifted, 0.01, 1);
+    assert_eq!(half.width, bitmap.width + 1);
+    assert!(half.data[half.width as usize + 1].abs_diff(128) <= 1);
+    assert_eq!(half.data[half.width as usize + 2], 255);
+
+    //Curves are flattened, the area of a circle is close to pi r^2
+    let circle = (0..16)
+        .map(|i| {
+            let angle = |i: i32| std::f32::consts::TAU * i as f32 / 16.0;
+            let point = |a: f32, r: f32| [a.cos() * r, a.sin() * r];
+            let middle = f32::midpoint(angle(i), angle(i + 1));
+            Segment::Quad(
+                point(angle(i), 10.0),
+                point(middle, 10.0 / (std::f32::consts::PI / 16.0).cos()),
+                point(angle(i + 1), 10.0),
+            )
+        })
+        .collect::<Vec<_>>();
+    let bitmap = rasterize(&circle, 1.0, 1);
+    let area = bitmap
+        .data
+        .iter()
+        .map(|v| f32::from(*v) / 255.0)
+        .sum::<f32>();
+    assert!((area - std::f32::consts::PI * 100.0).abs() < 2.0, "{area}");
+
+    let sdf = signed_distance_field(&rasterize(&square, 0.01, 4), 4);
+    let value = |x: u32, y: u32| sdf.data[(y * sdf.width + x) as usize];
+    //Increases towards the center of the square, 128 is the edge
+    assert!(value(0, 7) < value(2, 7));
+    assert!(value(2, 7) < 128);
+    assert!(value(4, 7) >= 128);
+    assert!(value(4, 7) < value(6, 7));
+    assert_eq!(value(0, 0), 1);
+}
@@ -3,3 +3,7 @@ use super::Texture;
 pub fn generate_empty_texture() -> Texture {
     Texture::static_png(include_bytes!("../../assets/empty_texture.png"))
 }
+
+pub fn generate_empty_cubemap() -> Texture {
+    Texture::solid_cubemap([0, 0, 0, 255])
+}
@@ -3,15 +3,87 @@ use wgpu::CommandEncoder;
 use crate::{
     UUID,
     asset_managment::{Asset, AssetStore},
+    structures::Color,
 };
 
 use super::BindgroupState;
 
+///How the color of a material is combined with what was rendered behind it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BlendMode {
+    ///The material is fully opaque and ignores the alpha
+    #[default]
+    Opaque,
+    ///The material is opaque, but pixels with alpha below the threshold are discarded, useful for
+    ///foliage and fences
+    Cutout(f32),
+    ///The color is blended using its alpha
+    Alpha,
+    ///The color multiplied by its alpha is added to the color behind it
+    Additive,
+    ///The color was already multiplied by its alpha, and is blended using its alpha
+    Premultiplied,
+}
+
+impl BlendMode {
+    ///Returns whether materials with the blend mode are rendered in the transparent queue, after
+    ///all the opaque meshes and sorted from back to front
+    #[must_use]
+    pub const fn is_transparent(self) -> bool {
+        matches!(self, Self::Alpha | Self::Additive | Self::Premultiplied)
+    }
+
+    ///Returns the alpha below which pixels are discarded, 0 if nothing is discarded
+    #[must_use]
+    pub const fn alpha_cutoff(self) -> f32 {
+        match self {
+            Self::Cutout(threshold) => threshold,
+            _ => 0.0,
+        }
+    }
+
+    ///Returns the blend state used by the pipelines of the material
+    #[must_use]
+    pub const fn blend_state(self) -> Option<wgpu::BlendState> {
+        match self {
+            Self::Opaque | Self::Cutout(_) => None,
+            Self::Alpha => Some(wgpu::BlendState::ALPHA_BLENDING),
+            Self::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            Self::Premultiplied => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        }
+    }
+
+    ///Returns whether the pipelines of the material write to the depth buffer, transparent
+    ///materials only test against it
+    #[must_use]
+    pub const fn depth_write(self) -> bool {
+        !self.is_transparent()
+    }
+}
+
 ///Trait for implementing materials
 #[allow(clippy::module_name_repetitions)]
 pub trait MaterialTrait {
     ///Render function of the material
     fn render(&self, render_pass: &mut wgpu::RenderPass);
+    ///Render function of the material for skinned meshes
+    ///
+    ///Returns false if the material does not support skinning, in which case the mesh is rendered
+    ///in its bind pose using [`MaterialTrait::render`]
+    fn render_skinned(&self, _render_pass: &mut wgpu::RenderPass) -> bool {
+        false
+    }
     ///Initialization of the material
     fn intialize(&mut self);
     ///Disposal of the material
@@ -26,6 +98,25 @@ pub trait MaterialTrait {
     }
     ///Updates the bindgroups of the material with new data
     fn update_bindgroups(&mut self, _encoder: &mut CommandEncoder) {}
+    ///Ids of the assets (i.e. textures) used by the material
+    fn dependencies(&self) -> Vec<UUID> {
+        Vec::new()
+    }
+    ///Returns the color of the material, if it has one
+    fn get_color(&self) -> Option<Color> {
+        None
+    }
+    ///Sets the color of the material, does nothing if the material has no color
+    fn set_color(&mut self, _color: Color) {}
+    ///Returns the blend mode of the material
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Opaque
+    }
+    ///Sets the blend mode of the material, does nothing if the material does not support blending
+    ///
+    ///Called before [`MaterialTrait::intialize`] is called again, so the pipelines can be
+    ///recreated
+    fn set_blend_mode(&mut self, _mode: BlendMode) {}
 }
 
 ///Stores material data, wrapper around the material trait object
@@ -64,6 +155,10 @@ impl Asset for Material {
     fn is_initialized(&self) -> bool {
         self.initialized
     }
+
+    fn dependencies(&self) -> Vec<UUID> {
+        self.material.dependencies()
+    }
 }
 
 impl Material {
@@ -83,12 +178,64 @@ impl Material {
         self.material.render(render_pass);
     }
 
+    ///Call the skinned render function of the material, returns false if the material does not
+    ///support skinning
+    pub fn render_skinned(&self, render_pass: &mut wgpu::RenderPass) -> bool {
+        self.material.render_skinned(render_pass)
+    }
+
     ///Returns whether the material is lit, or uses any lighting resources
     #[must_use]
     pub fn is_lit(&self) -> bool {
         self.material.is_lit()
     }
 
+    ///Returns the color of the material, if it has one
+    #[must_use]
+    pub fn get_color(&self) -> Option<Color> {
+        self.material.get_color()
+    }
+
+    ///Sets the color of the material, does nothing if the material has no color
+    pub fn set_color(&mut self, color: Color) {
+        self.material.set_color(color);
+    }
+
+    ///Returns the blend mode of the material
+    #[must_use]
+    pub fn get_blend_mode(&self) -> BlendMode {
+        self.material.blend_mode()
+    }
+
+    ///Returns whether the material is rendered in the transparent queue
+    #[must_use]
+    pub fn is_transparent(&self) -> bool {
+        self.material.blend_mode().is_transparent()
+    }
+
+    ///Sets the blend mode of the material, the pipelines of an initialized material are recreated
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.material.set_blend_mode(mode);
+        if self.initialized {
+            self.material.dispose();
+            self.material.intialize();
+        }
+    }
+
+    ///Returns the material with the blend mode set
+    ///
+    ///# Usage
+    ///```
+    ///# use lunar_engine::assets::{BlendMode, materials::Unlit};
+    ///let glass = Unlit::new(None, None).with_blend_mode(BlendMode::Alpha);
+    ///assert!(glass.is_transparent());
+    ///```
+    #[must_use]
+    pub fn with_blend_mode(mut self, mode: BlendMode) -> Self {
+        self.set_blend_mode(mode);
+        self
+    }
+
     ///Updates the bindgroups of the material
     pub fn update_bindgroups(&mut self, encoder: &mut CommandEncoder) {
         self.material.update_bindgroups(encoder);
@@ -61,6 +61,34 @@ pub const fn vertex_binding() -> [VertexBufferLayout<'static>; 2] {
     ]
 }
 
+///Returns the vertex buffer bindings of skinned meshes, the default bindings followed by the
+///joints and weights of the vertices
+#[must_use]
+pub const fn skinned_vertex_binding() -> [VertexBufferLayout<'static>; 3] {
+    let [vertex, transform] = vertex_binding();
+    [
+        vertex,
+        transform,
+        //Joints and weights
+        wgpu::VertexBufferLayout {
+            array_stride: 24,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint16x4,
+                    offset: 0,
+                    shader_location: 7,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 8,
+                    shader_location: 8,
+                },
+            ],
+        },
+    ]
+}
+
 #[must_use]
 ///Returns whether or not storage buffers are available on the current device
 pub const fn storage_buffer_available() -> bool {
@@ -2,26 +2,28 @@
 use std::num::NonZeroU64;
 
 use bytemuck::bytes_of;
+use wgpu::BufferUsages;
 use wgpu::util::DeviceExt;
-use wgpu::{BufferUsages, TextureUsages};
 use wgpu_shader_checker::include_wgsl;
 
 use crate::UUID;
-use crate::assets::{Material, Texture};
+use crate::assets::{BlendMode, Material, Texture};
 use crate::internal::STAGING_BELT;
-use crate::math::Vec3;
 use crate::structures::Color;
-use crate::{DEVICE, FORMAT, grimoire};
+use crate::{DEVICE, grimoire};
 
 use crate::{assets::BindgroupState, assets::material::MaterialTrait};
 
-use super::helpers::{preprocess_shader, storage_buffer_available, vertex_binding};
+use super::helpers::{
+    preprocess_shader, skinned_vertex_binding, storage_buffer_available, vertex_binding,
+};
 
 ///Basic material that renders an object, with an optional texture and color. This  material is lit.
 ///
 ///If neither the color nor the texture is  set, the material will be white
 pub struct Lit {
     pipeline: Option<wgpu::RenderPipeline>,
+    skinned_pipeline: Option<wgpu::RenderPipeline>,
     bind_group: Option<wgpu::BindGroup>,
     bind_group_layout_f: Option<wgpu::BindGroupLayout>,
     uniform: Option<wgpu::Buffer>,
@@ -29,9 +31,12 @@ pub struct Lit {
     color: Color,
     specular_color: Color,
     shininess: f32,
+    reflectivity: f32,
     bindgroup_sate: BindgroupState,
     changed: bool,
+    blend_mode: BlendMode,
     texture_id: Option<UUID>,
+    environment_id: Option<UUID>,
 }
 
 #[repr(C)]
@@ -40,7 +45,9 @@ struct MaterialData {
     color: Color,
     specular_color: Color,
     shininess: f32,
-    pading: Vec3,
+    reflectivity: f32,
+    alpha_cutoff: f32,
+    pading: f32,
 }
 
 impl Lit {
@@ -58,16 +65,64 @@ impl Lit {
             bind_group_layout_f: None,
             bindgroup_sate: BindgroupState::Uninitialized,
             changed: false,
+            blend_mode: BlendMode::Opaque,
             color: color.unwrap_or(Color::white()),
             pipeline: None,
+            skinned_pipeline: None,
             shininess,
             specular_color: specular_color.unwrap_or(Color::white()),
             texture_id,
             uniform: None,
+            reflectivity: 0.0,
+            environment_id: None,
         }
         .into()
     }
 
+    #[must_use]
+    ///Creates a new material that also reflects an environment cubemap, for example the one used
+    ///by the [`Skybox`](crate::rendering::extensions::skybox::Skybox)
+    ///
+    ///Reflectivity goes from 0 (no reflections) to 1 (a perfect mirror)
+    pub fn new_with_environment(
+        texture_id: Option<UUID>,
+        color: Option<Color>,
+        specular_color: Option<Color>,
+        shininess: f32,
+        environment_id: UUID,
+        reflectivity: f32,
+    ) -> Material {
+        Self {
+            bind_group: None,
+            bind_group_layout_f: None,
+            bindgroup_sate: BindgroupState::Uninitialized,
+            changed: false,
+            blend_mode: BlendMode::Opaque,
+            color: color.unwrap_or(Color::white()),
+            pipeline: None,
+            skinned_pipeline: None,
+            shininess,
+            specular_color: specular_color.unwrap_or(Color::white()),
+            texture_id,
+            uniform: None,
+            reflectivity: reflectivity.clamp(0.0, 1.0),
+            environment_id: Some(environment_id),
+        }
+        .into()
+    }
+
+    ///Returns the reflectivity of the material
+    #[must_use]
+    pub const fn get_reflectivity(&self) -> f32 {
+        self.reflectivity
+    }
+
+    ///Sets the reflectivity of the material, from 0 (no reflections) to 1 (a perfect mirror)
+    pub const fn set_reflectivity(&mut self, reflectivity: f32) {
+        self.reflectivity = reflectivity.clamp(0.0, 1.0);
+        self.changed = true;
+    }
+
     ///Returns the shininess of the material
     #[must_use]
     pub const fn get_shininess(&self) -> f32 {
@@ -118,8 +173,10 @@ impl MaterialTrait for Lit {
 
         let data = MaterialData {
             color: self.color,
-            pading: Vec3::default(),
+            alpha_cutoff: self.blend_mode.alpha_cutoff(),
+            pading: 0.0,
             shininess: self.shininess,
+            reflectivity: self.reflectivity,
             specular_color: self.specular_color,
         };
 
@@ -139,6 +196,12 @@ impl MaterialTrait for Lit {
         render_pass.set_bind_group(1, self.bind_group.as_ref().unwrap(), &[]);
     }
 
+    fn render_skinned(&self, render_pass: &mut wgpu::RenderPass) -> bool {
+        render_pass.set_pipeline(self.skinned_pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(1, self.bind_group.as_ref().unwrap(), &[]);
+        true
+    }
+
     fn intialize(&mut self) {
         let storage_buf_available = storage_buffer_available();
         let device = DEVICE.get().unwrap();
@@ -187,11 +250,29 @@ impl MaterialTrait for Lit {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             });
 
         let cam_bind_group_layout =
             device.create_bind_group_layout(&grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let skinned_cam_bind_group_layout =
+            device.create_bind_group_layout(&grimoire::SKINNED_CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
 
         let directional_light_bind_group_layout = device
             .create_bind_group_layout(&grimoire::DIRECTIONAL_LIGHT_BIND_GROUP_LAYOUT_DESCRIPTOR);
@@ -211,12 +292,26 @@ impl MaterialTrait for Lit {
             push_constant_ranges: &[],
         });
 
+        let skinned_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &skinned_cam_bind_group_layout,
+                    &bind_group_layout_f,
+                    &directional_light_bind_group_layout,
+                    &point_light_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
         self.bind_group_layout_f = Some(bind_group_layout_f);
 
         let data = MaterialData {
             color: self.color,
-            pading: Vec3::default(),
+            alpha_cutoff: self.blend_mode.alpha_cutoff(),
+            pading: 0.0,
             shininess: self.shininess,
+            reflectivity: self.reflectivity,
             specular_color: self.specular_color,
         };
 
@@ -228,52 +323,62 @@ impl MaterialTrait for Lit {
             }),
         );
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &v_shader,
-                entry_point: Some("main"),
-                buffers: &vertex_binding(),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &f_shader,
-                entry_point: Some("main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: *FORMAT.get().unwrap(),
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            multiview: None,
-            cache: None,
-        });
+        let create_pipeline = |layout: &wgpu::PipelineLayout,
+                               entry_point: &str,
+                               buffers: &[wgpu::VertexBufferLayout]| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &v_shader,
+                    entry_point: Some(entry_point),
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: self.blend_mode.depth_write(),
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &f_shader,
+                    entry_point: Some("main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: crate::rendering::scene_format(),
+                        blend: self.blend_mode.blend_state(),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
 
-        self.pipeline = Some(pipeline);
+        self.pipeline = Some(create_pipeline(&pipeline_layout, "main", &vertex_binding()));
+        self.skinned_pipeline = Some(create_pipeline(
+            &skinned_pipeline_layout,
+            "skinned",
+            &skinned_vertex_binding(),
+        ));
     }
 
     fn dispose(&mut self) {
         self.bind_group = None;
         self.pipeline = None;
+        self.skinned_pipeline = None;
         self.bindgroup_sate = BindgroupState::Uninitialized;
         self.uniform = None;
     }
@@ -299,6 +404,20 @@ impl MaterialTrait for Lit {
             texture = asset_store.get_by_id::<Texture>(self.texture_id.unwrap());
         }
 
+        //Materials without an environment reflect an empty black cubemap
+        let environment_id = self
+            .environment_id
+            .unwrap_or(grimoire::DEFAULT_CUBEMAP_ASSET_ID);
+        if environment_id == grimoire::DEFAULT_CUBEMAP_ASSET_ID {
+            //Ignore if it already exists
+            _ = asset_store.try_register_with_id(
+                crate::assets::heleprs::generate_empty_cubemap(),
+                grimoire::DEFAULT_CUBEMAP_ASSET_ID,
+            );
+        }
+        let environment = asset_store.get_by_id::<Texture>(environment_id).unwrap();
+        let environment = environment.borrow();
+
         let binding = texture.unwrap();
         let texture = binding.borrow();
 
@@ -312,29 +431,24 @@ impl MaterialTrait for Lit {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture.texture.as_ref().unwrap().create_view(
-                            &wgpu::TextureViewDescriptor {
-                                label: None,
-                                format: Some(wgpu::TextureFormat::Rgba8Unorm),
-                                dimension: None,
-                                aspect: wgpu::TextureAspect::All,
-                                base_mip_level: 0,
-                                mip_level_count: Some(1),
-                                base_array_layer: 0,
-                                array_layer_count: None,
-                                usage: Some(TextureUsages::TEXTURE_BINDING),
-                            },
-                        ),
-                    ),
+                    resource: wgpu::BindingResource::TextureView(&texture.create_view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
                     resource: wgpu::BindingResource::Sampler(texture.sampler.as_ref().unwrap()),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&environment.create_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(environment.sampler.as_ref().unwrap()),
+                },
             ],
         });
         drop(texture);
+        drop(environment);
 
         self.bind_group = Some(bind_group_f);
         self.bindgroup_sate = BindgroupState::Initialized;
@@ -344,7 +458,31 @@ impl MaterialTrait for Lit {
         self.bindgroup_sate
     }
 
+    fn dependencies(&self) -> Vec<UUID> {
+        self.texture_id
+            .into_iter()
+            .chain(self.environment_id)
+            .collect()
+    }
+
     fn is_lit(&self) -> bool {
         true
     }
+
+    fn get_color(&self) -> Option<Color> {
+        Some(Self::get_color(self))
+    }
+
+    fn set_color(&mut self, color: Color) {
+        Self::set_color(self, color);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+        self.changed = true;
+    }
 }
@@ -2,25 +2,26 @@
 use std::num::NonZeroU64;
 
 use bytemuck::bytes_of;
+use wgpu::BufferUsages;
 use wgpu::util::DeviceExt;
-use wgpu::{BufferUsages, TextureUsages};
 use wgpu_shader_checker::include_wgsl;
 
 use crate::UUID;
-use crate::assets::{Material, Texture};
+use crate::assets::{BlendMode, Material, Texture};
 use crate::internal::STAGING_BELT;
 use crate::structures::Color;
-use crate::{DEVICE, FORMAT, grimoire};
+use crate::{DEVICE, grimoire};
 
 use crate::{assets::BindgroupState, assets::material::MaterialTrait};
 
-use super::helpers::vertex_binding;
+use super::helpers::{skinned_vertex_binding, vertex_binding};
 
 ///Basic material that renders an object, with an optional texture and color. This  material is NOT lit.
 ///
 ///If neither the color nor the texture is  set, the material will be white
 pub struct Unlit {
     pipeline: Option<wgpu::RenderPipeline>,
+    skinned_pipeline: Option<wgpu::RenderPipeline>,
     bind_group: Option<wgpu::BindGroup>,
     bind_group_layout_f: Option<wgpu::BindGroupLayout>,
     uniform: Option<wgpu::Buffer>,
@@ -28,6 +29,7 @@ pub struct Unlit {
     color: Color,
     bindgroup_sate: BindgroupState,
     changed: bool,
+    blend_mode: BlendMode,
     texture_id: Option<UUID>,
 }
 
@@ -35,6 +37,8 @@ pub struct Unlit {
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct MaterialData {
     color: Color,
+    alpha_cutoff: f32,
+    padding: [f32; 3],
 }
 
 impl Unlit {
@@ -47,8 +51,10 @@ impl Unlit {
             bind_group_layout_f: None,
             bindgroup_sate: BindgroupState::Uninitialized,
             changed: false,
+            blend_mode: BlendMode::Opaque,
             color: color.unwrap_or(Color::white()),
             pipeline: None,
+            skinned_pipeline: None,
             texture_id,
             uniform: None,
         }
@@ -79,7 +85,11 @@ impl MaterialTrait for Unlit {
         let mut staging_belt = STAGING_BELT.get().unwrap().write().unwrap();
         let device = DEVICE.get().unwrap();
 
-        let data = MaterialData { color: self.color };
+        let data = MaterialData {
+            color: self.color,
+            alpha_cutoff: self.blend_mode.alpha_cutoff(),
+            padding: [0.0; 3],
+        };
 
         staging_belt
             .write_buffer(
@@ -97,6 +107,12 @@ impl MaterialTrait for Unlit {
         render_pass.set_bind_group(1, self.bind_group.as_ref().unwrap(), &[]);
     }
 
+    fn render_skinned(&self, render_pass: &mut wgpu::RenderPass) -> bool {
+        render_pass.set_pipeline(self.skinned_pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(1, self.bind_group.as_ref().unwrap(), &[]);
+        true
+    }
+
     fn intialize(&mut self) {
         let device = DEVICE.get().unwrap();
 
@@ -138,6 +154,8 @@ impl MaterialTrait for Unlit {
 
         let cam_bind_group_layout =
             device.create_bind_group_layout(&grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let skinned_cam_bind_group_layout =
+            device.create_bind_group_layout(&grimoire::SKINNED_CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
@@ -145,9 +163,20 @@ impl MaterialTrait for Unlit {
             push_constant_ranges: &[],
         });
 
+        let skinned_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&skinned_cam_bind_group_layout, &bind_group_layout_f],
+                push_constant_ranges: &[],
+            });
+
         self.bind_group_layout_f = Some(bind_group_layout_f);
 
-        let data = MaterialData { color: self.color };
+        let data = MaterialData {
+            color: self.color,
+            alpha_cutoff: self.blend_mode.alpha_cutoff(),
+            padding: [0.0; 3],
+        };
 
         self.uniform = Some(
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -157,52 +186,62 @@ impl MaterialTrait for Unlit {
             }),
         );
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &v_shader,
-                entry_point: Some("main"),
-                buffers: &vertex_binding(),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &f_shader,
-                entry_point: Some("main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: *FORMAT.get().unwrap(),
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            multiview: None,
-            cache: None,
-        });
-
-        self.pipeline = Some(pipeline);
+        let create_pipeline = |layout: &wgpu::PipelineLayout,
+                               entry_point: &str,
+                               buffers: &[wgpu::VertexBufferLayout]| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &v_shader,
+                    entry_point: Some(entry_point),
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: self.blend_mode.depth_write(),
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &f_shader,
+                    entry_point: Some("main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: crate::rendering::scene_format(),
+                        blend: self.blend_mode.blend_state(),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        self.pipeline = Some(create_pipeline(&pipeline_layout, "main", &vertex_binding()));
+        self.skinned_pipeline = Some(create_pipeline(
+            &skinned_pipeline_layout,
+            "skinned",
+            &skinned_vertex_binding(),
+        ));
     }
 
     fn dispose(&mut self) {
         self.bind_group = None;
         self.pipeline = None;
+        self.skinned_pipeline = None;
         self.bindgroup_sate = BindgroupState::Uninitialized;
         self.uniform = None;
     }
@@ -241,21 +280,7 @@ impl MaterialTrait for Unlit {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture.texture.as_ref().unwrap().create_view(
-                            &wgpu::TextureViewDescriptor {
-                                label: None,
-                                format: Some(wgpu::TextureFormat::Rgba8Unorm),
-                                dimension: None,
-                                aspect: wgpu::TextureAspect::All,
-                                base_mip_level: 0,
-                                mip_level_count: Some(1),
-                                base_array_layer: 0,
-                                array_layer_count: None,
-                                usage: Some(TextureUsages::TEXTURE_BINDING),
-                            },
-                        ),
-                    ),
+                    resource: wgpu::BindingResource::TextureView(&texture.create_view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -274,7 +299,28 @@ impl MaterialTrait for Unlit {
         self.bindgroup_sate
     }
 
+    fn dependencies(&self) -> Vec<UUID> {
+        self.texture_id.into_iter().collect()
+    }
+
     fn is_lit(&self) -> bool {
         false
     }
+
+    fn get_color(&self) -> Option<Color> {
+        Some(Self::get_color(self))
+    }
+
+    fn set_color(&mut self, color: Color) {
+        Self::set_color(self, color);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+        self.changed = true;
+    }
 }
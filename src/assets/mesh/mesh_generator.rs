@@ -1,19 +1,29 @@
-#![allow(clippy::too_many_lines)]
+#![allow(clippy::too_many_lines, clippy::many_single_char_names)]
 
-use std::f32::consts::PI;
+use std::{collections::HashMap, f32::consts::PI};
 
 use crate::{
-    math::Vec3,
-    structures::{Mesh, Vertex},
+    math::{Vec2, Vec3, Vector},
+    structures::{Index, Mesh, Vertex},
 };
 
-use super::ModelType;
+use super::{
+    CapsuleData, ConeData, CylinderData, ModelType, TerrainData, TorusData,
+    processing::{self, NormalMode},
+};
 
 #[must_use]
 pub fn generate_mesh(mesh_type: &ModelType) -> Mesh {
     match mesh_type {
         ModelType::Box(dimensions) => generate_box(*dimensions),
         ModelType::Sphere(data) => generate_sphere(data.radius, data.segments, data.rings),
+        ModelType::Plane(size, subdivisions) => generate_plane(*size, *subdivisions),
+        ModelType::Icosphere(radius, subdivisions) => generate_icosphere(*radius, *subdivisions),
+        ModelType::Cylinder(data) => generate_cylinder(data),
+        ModelType::Cone(data) => generate_cone(data),
+        ModelType::Capsule(data) => generate_capsule(data),
+        ModelType::Torus(data) => generate_torus(data),
+        ModelType::Terrain(data) => generate_terrain(data),
     }
 }
 
@@ -163,9 +173,119 @@ fn generate_box(dimensions: Vec3) -> Mesh {
         20, 21, 22, 21, 20, 23, //Right face
     ];
 
+    //Every face is mapped to the whole texture, as seen from the outside of the box with the top
+    //face being viewed with -z up
+    for v in &mut o.vertices {
+        let n = v.normal;
+        let (right, up) = if n.y == 0.0 {
+            (Vec3::new(n.z, 0.0, -n.x), Vec3::new(0.0, 1.0, 0.0))
+        } else {
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -n.y))
+        };
+        let coordinate = |axis: Vec3| {
+            let size = axis.abs().dot_product(&dimensions.abs());
+            if size == 0.0 {
+                0.5
+            } else {
+                0.5 + axis.dot_product(&v.coords) / size
+            }
+        };
+        v.texture = Vec2::new(coordinate(right), coordinate(up));
+    }
+
     o
 }
 
+///A point of a profile that is rotated around the y axis by [`lathe`]
+struct ProfilePoint {
+    ///Distance from the y axis
+    radius: f32,
+    y: f32,
+    ///Normal of the profile, x is the component pointing away from the y axis
+    normal: Vec2,
+    ///V texture coordinate of the point
+    v: f32,
+}
+
+///Rotates the profile around the y axis, adding the surface to the mesh
+///
+///The profile should go from the bottom to the top on the outer side of the surface. Points with a
+///radius of 0 are poles, which get a vertex for every segment so that the texture coordinates
+///don't get distorted
+fn lathe(o: &mut Mesh, profile: &[ProfilePoint], segments: u32) {
+    let mut rows = Vec::with_capacity(profile.len());
+
+    for point in profile {
+        rows.push(o.vertices.len() as Index);
+        //Vertices on a pole are placed between the segments, so the seam is not duplicated
+        let (offset, columns) = if point.radius == 0.0 {
+            (0.5, segments)
+        } else {
+            (0.0, segments + 1)
+        };
+
+        for j in 0..columns {
+            let u = (j as f32 + offset) / segments as f32;
+            let (sin, cos) = f32::sin_cos(u * 2.0 * PI);
+
+            o.vertices.push(Vertex {
+                coords: Vec3::new(point.radius * sin, point.y, point.radius * cos),
+                texture: Vec2::new(u, point.v),
+                normal: Vec3::new(point.normal.x * sin, point.normal.y, point.normal.x * cos)
+                    .normalize(),
+            });
+        }
+    }
+
+    for (i, points) in profile.windows(2).enumerate() {
+        for j in 0..segments {
+            let a = rows[i] + j;
+            let b = a + 1;
+            let c = rows[i + 1] + j;
+            let d = c + 1;
+
+            if points[0].radius == 0.0 {
+                o.indices.extend_from_slice(&[a, d, c]);
+            } else if points[1].radius == 0.0 {
+                o.indices.extend_from_slice(&[a, b, c]);
+            } else {
+                o.indices.extend_from_slice(&[a, b, d, a, d, c]);
+            }
+        }
+    }
+}
+
+///Adds a disc facing up or down to the mesh
+fn disc(o: &mut Mesh, radius: f32, y: f32, segments: u32, up: bool) {
+    let facing: f32 = if up { 1.0 } else { -1.0 };
+    let center = o.vertices.len() as Index;
+    let normal = Vec3::new(0.0, facing, 0.0);
+
+    o.vertices.push(Vertex {
+        coords: Vec3::new(0.0, y, 0.0),
+        texture: Vec2::new(0.5, 0.5),
+        normal,
+    });
+    for j in 0..=segments {
+        let (sin, cos) = f32::sin_cos(j as f32 / segments as f32 * 2.0 * PI);
+        o.vertices.push(Vertex {
+            coords: Vec3::new(radius * sin, y, radius * cos),
+            //Seen from the outside, with -z up on the top disc and +z up on the bottom one
+            texture: Vec2::new(facing.mul_add(sin * 0.5, 0.5), cos.mul_add(-0.5, 0.5)),
+            normal,
+        });
+    }
+
+    for j in 0..segments {
+        let a = center + 1 + j;
+        if up {
+            o.indices.extend_from_slice(&[center, a, a + 1]);
+        } else {
+            o.indices.extend_from_slice(&[center, a + 1, a]);
+        }
+    }
+}
+
 #[must_use]
 fn generate_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
     assert!(rings >= 1, "A sphere must have at least one ring");
@@ -173,110 +293,411 @@ fn generate_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
 
     let mut o = Mesh::default();
 
-    //Add top vertex
-    o.vertices.push(Vertex {
-        coords: Vec3::new(0.0, -radius, 0.0),
-        normal: Vec3::new(0.0, -1.0, 0.0),
-        ..Default::default()
-    });
+    //The profile goes from the bottom pole to the top one
+    let profile = (0..rings + 2)
+        .map(|i| {
+            let v = i as f32 / (rings + 1) as f32;
+            //The top pole is placed exactly on the axis
+            let (sin, cos) = if i == rings + 1 {
+                (0.0, -1.0)
+            } else {
+                f32::sin_cos(v * PI)
+            };
+
+            ProfilePoint {
+                radius: radius * sin,
+                y: -radius * cos,
+                normal: Vec2::new(sin, -cos),
+                v,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    lathe(&mut o, &profile, segments);
+    o
+}
 
-    //Step in radians
-    let ring_step = f32::to_radians(180.0 / (rings + 1) as f32);
-    let segment_step = f32::to_radians(360.0 / segments as f32);
+#[must_use]
+fn generate_plane(size: Vec2, subdivisions: u32) -> Mesh {
+    assert!(
+        subdivisions >= 1,
+        "A plane must have at least one subdivision"
+    );
 
-    for i in 0..rings {
-        //Y value for the current ring
-        let theta = ring_step.mul_add(-(i as f32 + 1.0), PI);
+    grid(size, subdivisions + 1, subdivisions + 1, |_, _| 0.0)
+}
 
-        let y = f32::cos(theta);
-        let sin_theta = f32::sin(theta);
-        //iterate over segments
-        for j in 0..segments {
-            //Spherical coords
-            let z = sin_theta * f32::cos(segment_step * j as f32);
-            let x = sin_theta * f32::sin(segment_step * j as f32);
+///Generates a grid in the xz plane centered on the origin, with the rows going from +z to -z
+///
+///The height of the vertices is given by `height(column, row)`
+fn grid(size: Vec2, columns: u32, rows: u32, height: impl Fn(u32, u32) -> f32) -> Mesh {
+    let mut o = Mesh::default();
+
+    for r in 0..rows {
+        for c in 0..columns {
+            let u = c as f32 / (columns - 1) as f32;
+            let v = r as f32 / (rows - 1) as f32;
 
             o.vertices.push(Vertex {
-                coords: Vec3 { x, y, z } * radius,
-                //This should work since the length is 1
-                normal: Vec3 { x, y, z },
-                ..Default::default()
+                coords: Vec3::new((u - 0.5) * size.x, height(c, r), (0.5 - v) * size.y),
+                texture: Vec2::new(u, v),
+                normal: Vec3::new(0.0, 1.0, 0.0),
             });
         }
     }
 
-    //Add bottom vertex
-    o.vertices.push(Vertex {
-        coords: Vec3::new(0.0, radius, 0.0),
-        normal: Vec3::new(0.0, 1.0, 0.0),
-        ..Default::default()
-    });
+    for r in 0..rows - 1 {
+        for c in 0..columns - 1 {
+            let a = r * columns + c;
+            let b = a + 1;
+            let d = a + columns + 1;
+            let e = a + columns;
+            o.indices.extend_from_slice(&[a, b, d, a, d, e]);
+        }
+    }
 
-    //Indecies
+    o
+}
 
-    //top tris
-    for i in 0..segments {
-        o.indices.push(0);
-        for j in 1..3 {
-            let mut value = (i + j) % (segments + 1);
-            if value == 0 {
-                value = 1;
-            }
-            o.indices.push(value);
-        }
+#[must_use]
+fn generate_terrain(data: &TerrainData) -> Mesh {
+    let rows = data.heights.len() as u32 / data.width;
+
+    let mut o = grid(
+        Vec2::new(data.size.x, data.size.z),
+        data.width,
+        rows,
+        |c, r| data.heights[(r * data.width + c) as usize] * data.size.y,
+    );
+    processing::recalculate_normals(&mut o, NormalMode::Smooth);
+    o
+}
+
+///Returns the index of the vertex in the middle of the edge, creating it if it does not exist
+fn midpoint(
+    vertices: &mut Vec<Vec3>,
+    cache: &mut HashMap<(Index, Index), Index>,
+    a: Index,
+    b: Index,
+) -> Index {
+    *cache.entry((a.min(b), a.max(b))).or_insert_with(|| {
+        vertices.push(((vertices[a as usize] + vertices[b as usize]) / 2.0).normalize());
+        (vertices.len() - 1) as Index
+    })
+}
+
+#[must_use]
+fn generate_icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = f32::midpoint(1.0, f32::sqrt(5.0));
+
+    let mut vertices = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .map(|v| Vec3::from(v).normalize())
+    .to_vec();
+
+    let mut triangles: Vec<[Index; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut cache = HashMap::new();
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let ab = midpoint(&mut vertices, &mut cache, a, b);
+                let bc = midpoint(&mut vertices, &mut cache, b, c);
+                let ca = midpoint(&mut vertices, &mut cache, c, a);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
     }
 
-    //bottom tris
-    let bottom = (o.vertices.len() - 1) as u32;
+    //Every triangle gets its own vertices, so the texture coordinates can wrap around the seam
+    let mut o = Mesh::default();
+    for triangle in triangles {
+        let mut corners = triangle.map(|i| {
+            let n = vertices[i as usize];
+            Vertex {
+                coords: n * radius,
+                texture: Vec2::new(
+                    f32::atan2(n.x, n.z).rem_euclid(2.0 * PI) / (2.0 * PI),
+                    0.5 + n.y.clamp(-1.0, 1.0).asin() / PI,
+                ),
+                normal: n,
+            }
+        });
 
-    for i in 0..segments {
-        o.indices.push(bottom - segments + i);
-        o.indices.push(bottom);
+        let pole = |v: &Vertex| v.normal.x.abs() < 1e-6 && v.normal.z.abs() < 1e-6;
+        let (min, max) = corners
+            .iter()
+            .filter(|v| !pole(v))
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v.texture.x), max.max(v.texture.x))
+            });
+        if max - min > 0.5 {
+            for v in &mut corners {
+                if v.texture.x < 0.5 {
+                    v.texture.x += 1.0;
+                }
+            }
+        }
+        //Vertices on the poles use the average of the other two
+        for i in 0..3 {
+            if pole(&corners[i]) {
+                corners[i].texture.x = f32::midpoint(
+                    corners[(i + 1) % 3].texture.x,
+                    corners[(i + 2) % 3].texture.x,
+                );
+            }
+        }
 
-        let mut v = bottom - segments + i + 1;
-        if v == bottom {
-            v = bottom - segments;
+        let n =
+            (corners[1].coords - corners[0].coords).cross(&(corners[2].coords - corners[0].coords));
+        if n.dot_product(&corners[0].coords) < 0.0 {
+            corners.swap(1, 2);
         }
-        o.indices.push(v);
-    }
 
-    //Don't do quads if there are no quads
-    if rings == 1 {
-        return o;
+        o.indices
+            .extend((0..3).map(|i| (o.vertices.len() + i) as Index));
+        o.vertices.extend_from_slice(&corners);
     }
 
-    //Quads
-    for i in 0..(rings - 1) {
-        //Offset of the first vertex of the ring
-        let offset = i * segments + 1;
+    processing::weld(&mut o, 0.0);
+    o
+}
 
-        for j in 0..segments {
-            let j = offset + j;
+#[must_use]
+fn generate_cylinder(data: &CylinderData) -> Mesh {
+    assert!(
+        data.segments >= 3,
+        "A cylinder must have at least 3 segments"
+    );
+
+    let mut o = Mesh::default();
+    let h = data.height / 2.0;
+
+    let side = |y: f32, v: f32| ProfilePoint {
+        radius: data.radius,
+        y,
+        normal: Vec2::new(1.0, 0.0),
+        v,
+    };
+    lathe(&mut o, &[side(-h, 0.0), side(h, 1.0)], data.segments);
+    disc(&mut o, data.radius, h, data.segments, true);
+    disc(&mut o, data.radius, -h, data.segments, false);
+    o
+}
+
+#[must_use]
+fn generate_cone(data: &ConeData) -> Mesh {
+    assert!(data.segments >= 3, "A cone must have at least 3 segments");
+
+    let mut o = Mesh::default();
+    let h = data.height / 2.0;
+    let normal = Vec2::new(data.height, data.radius).normalize();
+
+    let profile = [
+        ProfilePoint {
+            radius: data.radius,
+            y: -h,
+            normal,
+            v: 0.0,
+        },
+        ProfilePoint {
+            radius: 0.0,
+            y: h,
+            normal,
+            v: 1.0,
+        },
+    ];
+    lathe(&mut o, &profile, data.segments);
+    disc(&mut o, data.radius, -h, data.segments, false);
+    o
+}
 
-            if j.is_multiple_of(segments) {
-                o.indices.push(j);
-                o.indices.push(j + segments);
-                o.indices.push(j + 1);
+#[must_use]
+fn generate_capsule(data: &CapsuleData) -> Mesh {
+    assert!(
+        data.segments >= 3,
+        "A capsule must have at least 3 segments"
+    );
+    assert!(data.rings >= 1, "A capsule must have at least one ring");
 
-                o.indices.push(j);
-                o.indices.push(j + 1);
-                o.indices.push(j + 1 - segments);
+    let mut o = Mesh::default();
+    let total = data.height.max(data.radius * 2.0);
+    //Half of the height of the cylinder between the hemispheres
+    let h = total / 2.0 - data.radius;
 
+    //Bottom hemisphere going from the pole to the equator, then the top one
+    let mut profile = Vec::new();
+    for (center, start) in [(-h, -PI / 2.0), (h, 0.0)] {
+        for i in 0..=data.rings {
+            //The cylinder has no height, so the equator would be duplicated
+            if h == 0.0 && start == 0.0 && i == 0 {
                 continue;
             }
-            //Tri 1
-            o.indices.push(j);
-            o.indices.push(j + segments);
-            o.indices.push(j + segments + 1);
-
-            //Tri 2
-            o.indices.push(j);
-            o.indices.push(j + segments + 1);
-            o.indices.push(j + 1);
+            //The poles are placed exactly on the axis
+            let (sin, cos) = match (start == 0.0, i == data.rings, i == 0) {
+                (false, _, true) => (-1.0, 0.0),
+                (true, true, _) => (1.0, 0.0),
+                _ => f32::sin_cos(start + i as f32 / data.rings as f32 * PI / 2.0),
+            };
+            let y = data.radius.mul_add(sin, center);
+
+            profile.push(ProfilePoint {
+                radius: data.radius * cos,
+                y,
+                normal: Vec2::new(cos, sin),
+                v: y / total + 0.5,
+            });
         }
     }
 
-    o.indices.reverse();
+    lathe(&mut o, &profile, data.segments);
+    o
+}
+
+#[must_use]
+fn generate_torus(data: &TorusData) -> Mesh {
+    assert!(
+        data.major_segments >= 3 && data.minor_segments >= 3,
+        "A torus must have at least 3 segments"
+    );
+
+    let mut o = Mesh::default();
+
+    //The profile starts on the inside, so the seam is hidden in the hole
+    let profile = (0..=data.minor_segments)
+        .map(|i| {
+            let v = i as f32 / data.minor_segments as f32;
+            let (sin, cos) = f32::sin_cos(v.mul_add(2.0 * PI, PI));
+            ProfilePoint {
+                radius: data.minor_radius.mul_add(cos, data.major_radius),
+                y: data.minor_radius * sin,
+                normal: Vec2::new(cos, sin),
+                v,
+            }
+        })
+        .collect::<Vec<_>>();
 
+    lathe(&mut o, &profile, data.major_segments);
     o
 }
+
+#[test]
+fn test_generated_meshes() {
+    use super::SphereData;
+
+    let models = [
+        ModelType::Box(Vec3::new(1, 2, 3)),
+        ModelType::Sphere(SphereData {
+            radius: 1.0,
+            segments: 8,
+            rings: 5,
+        }),
+        ModelType::Plane(Vec2::new(2, 3), 4),
+        ModelType::Icosphere(1.0, 2),
+        ModelType::Cylinder(CylinderData {
+            radius: 1.0,
+            height: 2.0,
+            segments: 8,
+        }),
+        ModelType::Cone(ConeData {
+            radius: 1.0,
+            height: 2.0,
+            segments: 8,
+        }),
+        ModelType::Capsule(CapsuleData {
+            radius: 0.5,
+            height: 2.0,
+            segments: 8,
+            rings: 3,
+        }),
+        ModelType::Capsule(CapsuleData {
+            radius: 0.5,
+            height: 1.0,
+            segments: 8,
+            rings: 3,
+        }),
+        ModelType::Torus(TorusData {
+            major_radius: 1.0,
+            minor_radius: 0.25,
+            major_segments: 12,
+            minor_segments: 6,
+        }),
+        ModelType::Terrain(TerrainData {
+            size: Vec3::new(4, 1, 4),
+            width: 3,
+            heights: vec![0.0, 0.5, 0.0, 0.5, 1.0, 0.5, 0.0, 0.5, 0.0],
+        }),
+    ];
+
+    for model in &models {
+        let mesh = generate_mesh(model);
+        assert!(!mesh.indices.is_empty());
+
+        //Icosphere triangles that cross the seam wrap around
+        let max_u = if matches!(model, ModelType::Icosphere(..)) {
+            1.5
+        } else {
+            1.0
+        };
+        for v in &mesh.vertices {
+            assert!((v.normal.length() - 1.0).abs() < 1e-4, "{v:?}");
+            assert!((0.0..=1.0).contains(&v.texture.y), "{v:?}");
+            assert!((0.0..=max_u).contains(&v.texture.x), "{v:?}");
+        }
+
+        //Triangles are counter clockwise and face the same way as the normals
+        for t in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[t[i] as usize]);
+            let normal = (b.coords - a.coords).cross(&(c.coords - a.coords));
+            assert!(normal.length() > 1e-6, "Degenerate triangle {t:?}");
+            assert!(
+                normal.dot_product(&(a.normal + b.normal + c.normal)) > 0.0,
+                "{t:?}"
+            );
+        }
+    }
+
+    //Sphere vertices are on the surface
+    let sphere = generate_icosphere(2.0, 1);
+    assert_eq!(sphere.indices.len(), 80 * 3);
+    assert!(
+        sphere
+            .vertices
+            .iter()
+            .all(|v| (v.coords.length() - 2.0).abs() < 1e-5)
+    );
+}
@@ -1,14 +1,21 @@
 use mesh_generator::generate_mesh;
-use std::path::{Path, PathBuf};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 use wgpu::util::DeviceExt;
 
 use crate::{
     DEVICE, UUID,
-    asset_managment::Asset,
-    math::{Vec3, Vector},
+    asset_managment::{Asset, pack::PackFile},
+    math::{Aabb, Vec2, Vec3, Vector},
+    structures::{Index, Vertex, VertexSkin},
 };
 
 mod mesh_generator;
+///Utilities for processing mesh data, such as calculating normals and tangents and optimizing
+///the mesh for rendering
+pub mod processing;
 
 ///Asset that stores mesh data
 pub struct Mesh {
@@ -22,6 +29,88 @@ pub struct Mesh {
     index_count: Option<u32>,
     ///distance to the vertex furthest from the origin
     extent: Option<f32>,
+    ///Bounding box of the mesh
+    bounds: Option<Aabb>,
+    ///Levels of detail of the mesh
+    lods: Lods,
+    ///Joints and weights of the vertices of a skinned mesh
+    skin_buffer: Option<wgpu::Buffer>,
+    ///Whether the triangles are kept on the cpu for raycasts
+    keep_triangles: bool,
+    ///Triangles of the full detail mesh, if they are kept
+    triangles: Option<Vec<[Vec3; 3]>>,
+}
+
+///Error allowed when generating the first lower level of detail, it doubles with every level
+const LOD_TARGET_ERROR: f32 = 0.01;
+
+///Levels of detail of a mesh
+struct Lods {
+    ///Lower detail levels provided by the user
+    provided: Vec<Vec<Index>>,
+    ///Number of lower detail levels to generate
+    generated: u32,
+    ///Screen sizes below which the lower detail levels are used
+    screen_sizes: Vec<f32>,
+    ///Ranges of the index buffer used by each level, including the full detail one
+    ranges: Vec<Range<u32>>,
+}
+
+impl Lods {
+    const fn new() -> Self {
+        Self {
+            provided: Vec::new(),
+            generated: 0,
+            screen_sizes: Vec::new(),
+            ranges: Vec::new(),
+        }
+    }
+
+    ///Returns true if the mesh should have lower detail levels, besides the ones in binary meshes
+    const fn is_requested(&self) -> bool {
+        !self.provided.is_empty() || self.generated > 0
+    }
+
+    ///Returns the lower detail levels of the mesh, they are generated if none were provided
+    ///
+    ///Generation stops early once the mesh can't be simplified any further
+    fn lower_levels(
+        &self,
+        mesh: &crate::structures::Mesh,
+    ) -> Result<Vec<Vec<Index>>, Box<dyn std::error::Error + Send>> {
+        if !self.provided.is_empty() {
+            let vertex_count = mesh.vertices.len();
+            for lod in &self.provided {
+                if lod.len() % 3 != 0 || lod.iter().any(|i| *i as usize >= vertex_count) {
+                    return Err(crate::import::invalid("Invalid lod indices"));
+                }
+            }
+            return Ok(self.provided.clone());
+        }
+
+        let mut levels: Vec<Vec<Index>> = Vec::new();
+        let mut error = LOD_TARGET_ERROR;
+        for _ in 0..self.generated {
+            let previous = levels.last().map_or(mesh.indices.len(), Vec::len);
+            let indices = processing::simplify(mesh, previous / 2, error);
+            //Not worth another level
+            if indices.is_empty() || indices.len() * 10 > previous * 9 {
+                break;
+            }
+            levels.push(indices);
+            error *= 2.0;
+        }
+        Ok(levels)
+    }
+
+    ///Returns the screen size below which the level is used
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn screen_size(&self, lod: usize) -> f32 {
+        self.screen_sizes
+            .get(lod - 1)
+            .copied()
+            .unwrap_or_else(|| 0.5f32.powi(lod as i32))
+    }
 }
 
 ///Description of a uv sphere
@@ -34,12 +123,114 @@ pub struct SphereData {
     pub rings: u32,
 }
 
+///Description of a cylinder standing on the xz plane
+pub struct CylinderData {
+    ///Radius of the cylinder
+    pub radius: f32,
+    ///Height of the cylinder
+    pub height: f32,
+    ///Number of segments around the cylinder
+    pub segments: u32,
+}
+
+///Description of a cone with the base at the bottom and the tip at the top
+pub struct ConeData {
+    ///Radius of the base
+    pub radius: f32,
+    ///Height of the cone
+    pub height: f32,
+    ///Number of segments around the cone
+    pub segments: u32,
+}
+
+///Description of a capsule standing on the xz plane
+pub struct CapsuleData {
+    ///Radius of the hemispheres and the cylinder between them
+    pub radius: f32,
+    ///Total height of the capsule including the hemispheres, at least twice the radius
+    pub height: f32,
+    ///Number of segments around the capsule
+    pub segments: u32,
+    ///Number of rings of each hemisphere
+    pub rings: u32,
+}
+
+///Description of a torus lying in the xz plane
+pub struct TorusData {
+    ///Distance from the center of the torus to the center of the tube
+    pub major_radius: f32,
+    ///Radius of the tube
+    pub minor_radius: f32,
+    ///Number of segments around the torus
+    pub major_segments: u32,
+    ///Number of segments around the tube
+    pub minor_segments: u32,
+}
+
+///Description of a terrain patch generated from a heightmap
+pub struct TerrainData {
+    ///Dimensions of the terrain, the y component is the height of the highest possible point
+    pub size: Vec3,
+    ///Number of height samples along the x axis
+    pub width: u32,
+    ///Heights in the [0; 1] range, stored in rows going from +z to -z, the same way texture rows
+    ///go from bottom to top
+    pub heights: Vec<f32>,
+}
+
+impl TerrainData {
+    ///Creates terrain data using the first channel of the image as the heights, the first row of
+    ///the image is placed at +z
+    ///
+    ///16 bit samples are read as big endian, the way they are stored in png files
+    #[must_use]
+    pub fn from_image(image: &lunar_png::Image, size: Vec3) -> Self {
+        use lunar_png::ImageType;
+
+        let (channels, bytes) = match image.img_type {
+            ImageType::R8 => (1, 1),
+            ImageType::Ra8 => (2, 1),
+            ImageType::Rgb8 => (3, 1),
+            ImageType::Rgba8 => (4, 1),
+            ImageType::R16 => (1, 2),
+            ImageType::Ra16 => (2, 2),
+            ImageType::Rgb16 => (3, 2),
+            ImageType::Rgba16 => (4, 2),
+        };
+
+        Self {
+            size,
+            width: image.width,
+            heights: image
+                .data
+                .chunks_exact(channels * bytes)
+                .map(|p| {
+                    if bytes == 1 {
+                        f32::from(p[0]) / f32::from(u8::MAX)
+                    } else {
+                        f32::from(u16::from_be_bytes([p[0], p[1]])) / f32::from(u16::MAX)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
 ///Model types that a mesh generator can generate
 enum ModelType {
     ///Box, contains a vec3 defining the box dimensions
     Box(Vec3),
     ///Sphere, contains an f32 defining the sphere radius
     Sphere(SphereData),
+    ///Plane, contains its dimensions and the number of subdivisions
+    Plane(Vec2, u32),
+    ///Icosphere, contains its radius and the number of subdivisions
+    Icosphere(f32, u32),
+    Cylinder(CylinderData),
+    Cone(ConeData),
+    Capsule(CapsuleData),
+    Torus(TorusData),
+    Terrain(TerrainData),
 }
 
 ///Ways the mesh can be loaded from file
@@ -47,6 +238,15 @@ enum MeshMode {
     ///An obj file that contains a single mesh
     SingleObjectOBJ(PathBuf),
     StaticSingleObjectOBJ(&'static str),
+    ///An obj file inside of an asset pack that contains a single mesh
+    PackedOBJ(PackFile),
+    ///A binary mesh file, see [`crate::import::lmesh`]
+    Binary(PathBuf),
+    StaticBinary(&'static [u8]),
+    PackedBinary(PackFile),
+    ///The first mesh in a gltf file, see [`crate::import::gltf`]
+    Gltf(PathBuf),
+    StaticGltf(&'static [u8]),
     GeneratedModel(ModelType),
 }
 
@@ -65,6 +265,11 @@ impl Mesh {
             tris_count: None,
             index_count: None,
             extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
         }
     }
 
@@ -87,15 +292,174 @@ impl Mesh {
             vert_count: None,
             index_count: None,
             extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
         })
     }
 
+    ///Creates a new asset that will load the first object in a waveform obj file, that is stored in
+    ///a mounted asset pack
+    ///
+    ///See [`AssetStore::pack_file`](crate::asset_managment::AssetStore::pack_file)
+    #[must_use]
+    pub const fn new_from_packed_obj(file: PackFile) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::PackedOBJ(file),
+            vertex_buffer: None,
+            index_buffer: None,
+            tris_count: None,
+            vert_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        }
+    }
+
+    ///Creates a new asset that will load a binary mesh file (see [`crate::import::lmesh`])
+    ///
+    ///Currently unsupported on the web target
+    ///
+    ///# Errors
+    ///Returns an error if the file does not exist
+    pub fn new_from_binary(path: &Path) -> Result<Self, std::io::Error> {
+        //Verify that file exists
+        std::fs::File::options().read(true).open(path)?;
+        Ok(Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::Binary(path.to_owned()),
+            vertex_buffer: None,
+            index_buffer: None,
+            tris_count: None,
+            vert_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        })
+    }
+
+    ///Creates a new asset that will load a statically loaded binary mesh (see
+    ///[`crate::import::lmesh`])
+    #[must_use]
+    pub const fn new_from_static_binary(mesh: &'static [u8]) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::StaticBinary(mesh),
+            vertex_buffer: None,
+            index_buffer: None,
+            vert_count: None,
+            tris_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        }
+    }
+
+    ///Creates a new asset that will load a binary mesh (see [`crate::import::lmesh`]), that is
+    ///stored in a mounted asset pack
+    ///
+    ///See [`AssetStore::pack_file`](crate::asset_managment::AssetStore::pack_file)
+    #[must_use]
+    pub const fn new_from_packed_binary(file: PackFile) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::PackedBinary(file),
+            vertex_buffer: None,
+            index_buffer: None,
+            tris_count: None,
+            vert_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        }
+    }
+
+    ///Creates a new asset that will load the first mesh in a gltf file, including the joints and
+    ///weights of its vertices if it is skinned (see [`crate::import::gltf`])
+    ///
+    ///# Errors
+    ///Returns an error if the file does not exist
+    pub fn new_from_gltf(path: &Path) -> Result<Self, std::io::Error> {
+        //Verify that file exists
+        std::fs::File::options().read(true).open(path)?;
+        Ok(Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::Gltf(path.to_owned()),
+            vertex_buffer: None,
+            index_buffer: None,
+            tris_count: None,
+            vert_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        })
+    }
+
+    ///Creates a new asset that will load the first mesh in a statically loaded gltf file,
+    ///including the joints and weights of its vertices if it is skinned
+    #[must_use]
+    pub const fn new_from_static_gltf(data: &'static [u8]) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: MeshMode::StaticGltf(data),
+            vertex_buffer: None,
+            index_buffer: None,
+            tris_count: None,
+            vert_count: None,
+            index_count: None,
+            extent: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
+        }
+    }
+
     ///Returns extent of the mesh
     #[must_use]
     pub const fn get_extent(&self) -> f32 {
         self.extent.unwrap()
     }
 
+    ///Returns the bounding box of the mesh
+    ///
+    ///# Panics
+    ///Panics if the asset was not initialized
+    #[must_use]
+    pub const fn get_bounds(&self) -> Aabb {
+        self.bounds.unwrap()
+    }
+
     ///Returns the vertex buffer of the mesh
     ///
     ///# Panics
@@ -141,88 +505,299 @@ impl Mesh {
         self.vert_count.unwrap()
     }
 
-    ///Creates a new mesh that is a box with given dimensions
+    ///Returns true if the vertices of the mesh have joints and weights
     #[must_use]
-    pub fn new_box(dimensions: Vec3) -> Self {
+    pub const fn is_skinned(&self) -> bool {
+        self.skin_buffer.is_some()
+    }
+
+    ///Returns the buffer with the joints and weights of the vertices, if the mesh is skinned
+    #[must_use]
+    pub fn get_skin_buffer(&self) -> Option<wgpu::Buffer> {
+        self.skin_buffer.clone()
+    }
+
+    ///Sets the lower levels of detail of the mesh, from the most to the least detailed
+    ///
+    ///Each level is a list of indices into the vertices of the full detail mesh. Levels stored in
+    ///binary meshes take priority. Takes effect the next time the asset is initialized
+    pub fn set_lods(&mut self, lods: Vec<Vec<Index>>) {
+        self.lods.provided = lods;
+    }
+
+    ///Sets the number of lower levels of detail that are generated by simplifying the mesh, each
+    ///level has about half of the triangles of the previous one
+    ///
+    ///Fewer levels are generated if the mesh can't be simplified any further. Ignored if the
+    ///levels were set with [`Mesh::set_lods`] or are stored in the binary mesh. Takes effect the
+    ///next time the asset is initialized
+    pub const fn set_generated_lod_count(&mut self, count: u32) {
+        self.lods.generated = count;
+    }
+
+    ///Sets the screen sizes at which the levels of detail switch, the screen size is the fraction
+    ///of the screen height covered by the bounding sphere of the mesh
+    ///
+    ///Level `i + 1` is used below `sizes[i]`, the sizes should be decreasing. Levels without a set
+    ///size switch when the screen size halves: 0.5, 0.25, 0.125...
+    pub fn set_lod_screen_sizes(&mut self, sizes: Vec<f32>) {
+        self.lods.screen_sizes = sizes;
+    }
+
+    ///Sets whether the triangles of the full detail mesh are kept on the cpu, so that raycasts
+    ///can hit the triangles instead of the bounding box. Takes effect the next time the asset is
+    ///initialized
+    pub const fn set_keep_triangles(&mut self, keep: bool) {
+        self.keep_triangles = keep;
+    }
+
+    ///Returns the triangles of the full detail mesh, if they are kept on the cpu
+    #[must_use]
+    pub fn get_triangles(&self) -> Option<&[[Vec3; 3]]> {
+        self.triangles.as_deref()
+    }
+
+    ///Returns the number of levels of detail, including the full detail mesh
+    ///
+    ///# Panics
+    ///Panics if the asset was not initialized
+    #[must_use]
+    pub fn get_lod_count(&self) -> usize {
+        assert!(self.initialized, "Mesh was not initialized");
+        self.lods.ranges.len()
+    }
+
+    ///Returns the range of the index buffer used by the level of detail
+    ///
+    ///# Panics
+    ///Panics if the asset was not initialized or the level does not exist
+    #[must_use]
+    pub fn get_lod_range(&self, lod: usize) -> Range<u32> {
+        self.lods.ranges[lod].clone()
+    }
+
+    ///Selects the level of detail for a mesh that covers `screen_size` of the screen height
+    ///
+    ///To avoid switching between levels every frame, the `current` level is kept until the
+    ///screen size is further than `hysteresis` past the switching size, relative to that size
+    #[must_use]
+    pub fn select_lod(&self, screen_size: f32, current: usize, hysteresis: f32) -> usize {
+        let sizes = (1..self.lods.ranges.len()).map(|l| self.lods.screen_size(l));
+        let lowest = sizes
+            .clone()
+            .filter(|s| screen_size < s * (1.0 - hysteresis))
+            .count();
+        let highest = sizes
+            .filter(|s| screen_size < s * (1.0 + hysteresis))
+            .count();
+        current.clamp(lowest, highest)
+    }
+
+    ///Creates a new mesh generated from the model once it is initialized
+    const fn generated(model: ModelType, extent: Option<f32>) -> Self {
         Self {
             id: None,
             initialized: false,
-            extent: Some(
-                (f32::abs(dimensions.x) + f32::abs(dimensions.y) + f32::abs(dimensions.z)) / 2.0,
-            ),
-            mode: MeshMode::GeneratedModel(ModelType::Box(dimensions)),
+            extent,
+            mode: MeshMode::GeneratedModel(model),
             vertex_buffer: None,
             index_buffer: None,
             vert_count: None,
             tris_count: None,
             index_count: None,
+            bounds: None,
+            lods: Lods::new(),
+            skin_buffer: None,
+            keep_triangles: false,
+            triangles: None,
         }
     }
 
+    ///Creates a new mesh that is a box with given dimensions
+    #[must_use]
+    pub fn new_box(dimensions: Vec3) -> Self {
+        Self::generated(
+            ModelType::Box(dimensions),
+            Some((f32::abs(dimensions.x) + f32::abs(dimensions.y) + f32::abs(dimensions.z)) / 2.0),
+        )
+    }
+
     ///Creates a new mesh that is a sphere with the given radius, number of sectors and rings
+    ///
+    ///# Panics
+    ///Panics if the sphere has less than 3 segments or no rings
     #[must_use]
     pub const fn new_sphere(desc: SphereData) -> Self {
-        Self {
-            id: None,
-            initialized: false,
-            extent: Some(desc.radius * 2.0),
-            mode: MeshMode::GeneratedModel(ModelType::Sphere(desc)),
-            vertex_buffer: None,
-            index_count: None,
-            vert_count: None,
-            tris_count: None,
-            index_buffer: None,
-        }
+        assert!(desc.segments >= 3, "A sphere must have at least 3 segments");
+        assert!(desc.rings >= 1, "A sphere must have at least one ring");
+
+        let extent = Some(desc.radius * 2.0);
+        Self::generated(ModelType::Sphere(desc), extent)
+    }
+    ///Creates a new mesh that is a plane lying in the xz plane facing up, split into a grid of
+    ///`subdivisions` by `subdivisions` quads
+    ///
+    ///# Panics
+    ///Panics if `subdivisions` is 0
+    #[must_use]
+    pub const fn new_plane(size: Vec2, subdivisions: u32) -> Self {
+        assert!(
+            subdivisions >= 1,
+            "A plane must have at least one subdivision"
+        );
+
+        Self::generated(ModelType::Plane(size, subdivisions), None)
+    }
+
+    ///Creates a new mesh that is an icosphere with the given radius, every subdivision splits each
+    ///triangle into 4
+    ///
+    ///Texture coordinates of the triangles that cross the seam go past 1, so textures should use
+    ///[`wgpu::AddressMode::Repeat`]
+    #[must_use]
+    pub const fn new_icosphere(radius: f32, subdivisions: u32) -> Self {
+        Self::generated(ModelType::Icosphere(radius, subdivisions), None)
+    }
+
+    ///Creates a new mesh that is a cylinder with caps on both ends
+    ///
+    ///# Panics
+    ///Panics if the cylinder has less than 3 segments
+    #[must_use]
+    pub const fn new_cylinder(desc: CylinderData) -> Self {
+        assert!(
+            desc.segments >= 3,
+            "A cylinder must have at least 3 segments"
+        );
+
+        Self::generated(ModelType::Cylinder(desc), None)
+    }
+
+    ///Creates a new mesh that is a cone with a cap on the base
+    ///
+    ///# Panics
+    ///Panics if the cone has less than 3 segments
+    #[must_use]
+    pub const fn new_cone(desc: ConeData) -> Self {
+        assert!(desc.segments >= 3, "A cone must have at least 3 segments");
+
+        Self::generated(ModelType::Cone(desc), None)
+    }
+
+    ///Creates a new mesh that is a capsule
+    ///
+    ///# Panics
+    ///Panics if the capsule has less than 3 segments or no rings
+    #[must_use]
+    pub const fn new_capsule(desc: CapsuleData) -> Self {
+        assert!(
+            desc.segments >= 3,
+            "A capsule must have at least 3 segments"
+        );
+        assert!(desc.rings >= 1, "A capsule must have at least one ring");
+
+        Self::generated(ModelType::Capsule(desc), None)
+    }
+
+    ///Creates a new mesh that is a torus
+    ///
+    ///# Panics
+    ///Panics if the torus has less than 3 segments in either direction
+    #[must_use]
+    pub const fn new_torus(desc: TorusData) -> Self {
+        assert!(
+            desc.major_segments >= 3 && desc.minor_segments >= 3,
+            "A torus must have at least 3 segments"
+        );
+
+        Self::generated(ModelType::Torus(desc), None)
+    }
+
+    ///Creates a new mesh that is a terrain patch centered on the origin, generated from a
+    ///heightmap
+    ///
+    ///# Panics
+    ///Panics if the heightmap has less than 2 samples in either direction, or if the number of
+    ///heights is not a multiple of the width
+    #[must_use]
+    pub fn new_terrain(desc: TerrainData) -> Self {
+        assert!(
+            desc.width >= 2 && desc.heights.len() >= desc.width as usize * 2,
+            "A terrain must have at least 2 samples in each direction"
+        );
+        assert!(
+            desc.heights.len().is_multiple_of(desc.width as usize),
+            "The number of heights must be a multiple of the width"
+        );
+
+        Self::generated(ModelType::Terrain(desc), None)
     }
 }
 
-impl Asset for Mesh {
-    fn get_id(&self) -> UUID {
-        self.id.unwrap()
+impl Mesh {
+    ///Initializes the mesh from a binary mesh, uploading the data without copying it
+    fn initialize_binary(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let view = crate::import::lmesh::MeshView::parse(data)?;
+
+        self.extent = Some(view.extent());
+        self.bounds = Some(Aabb::new(view.bounds_min(), view.bounds_max()));
+        if view.lod_count() == 1 && self.lods.is_requested() {
+            return self.upload_with_lods(&view.to_mesh());
+        }
+
+        let lods = (0..view.lod_count())
+            .filter_map(|l| view.lod_range(l))
+            .collect();
+        self.upload(view.vertices(), view.all_indices(), lods);
+        Ok(())
+    }
+
+    ///Initializes the mesh from the first mesh of a gltf file
+    fn initialize_gltf(
+        &mut self,
+        gltf: &crate::import::gltf::Gltf,
+    ) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let (mesh, skin) = gltf.mesh(0)?;
+        self.upload_with_lods(&mesh)?;
+
+        self.skin_buffer = skin.map(|skin| {
+            DEVICE
+                .get()
+                .unwrap()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Mesh skin {}", self.get_id())),
+                    contents: bytemuck::cast_slice::<VertexSkin, u8>(&skin),
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+        Ok(())
     }
 
+    ///Creates the lower levels of detail and uploads them together with the mesh
     #[allow(clippy::cast_possible_truncation)]
-    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
-        //This is horrific, but i LOVE this :3
-        let mesh = match &self.mode {
-            MeshMode::SingleObjectOBJ(path) => {
-                //Prase file
-                match crate::import::obj::parse(
-                    //Load file
-                    &(match std::fs::read_to_string(path) {
-                        Ok(it) => it,
-                        Err(err) => return Err(Box::new(err)),
-                    }),
-                )
-                //Get the first mesh
-                .and_then(|i| i.into_iter().nth(0))
-                {
-                    Some(it) => it,
-                    None => {
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::InvalidInput,
-                            "Invalid file",
-                        )));
-                    }
-                }
-            }
-            MeshMode::StaticSingleObjectOBJ(mesh) => {
-                match crate::import::obj::parse(mesh).and_then(|i| i.into_iter().nth(0)) {
-                    Some(it) => it,
-                    None => {
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::InvalidInput,
-                            "Invalid data",
-                        )));
-                    }
-                }
-            }
-            MeshMode::GeneratedModel(mdl_type) => generate_mesh(mdl_type),
-        };
+    fn upload_with_lods(
+        &mut self,
+        mesh: &crate::structures::Mesh,
+    ) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let lower_levels = self.lods.lower_levels(mesh)?;
+        let mut indices = Vec::new();
+        let mut lods = Vec::new();
+        for level in std::iter::once(mesh.indices.clone()).chain(lower_levels) {
+            let start = indices.len() as u32;
+            indices.extend(level);
+            lods.push(start..indices.len() as u32);
+        }
+
+        self.upload(&mesh.vertices, &indices, lods);
+        Ok(())
+    }
 
+    ///Creates the vertex and index buffers, the indices contain all levels of detail
+    #[allow(clippy::cast_possible_truncation)]
+    fn upload(&mut self, vertices: &[Vertex], indices: &[Index], lods: Vec<Range<u32>>) {
         if self.extent.is_none() {
             let mut e = 0.0;
-            for i in &mesh.vertices {
+            for i in vertices {
                 let sqr_len = i.coords.square_length();
                 if sqr_len > e {
                     e = sqr_len;
@@ -230,37 +805,121 @@ impl Asset for Mesh {
             }
             self.extent = Some(e.sqrt());
         }
+        if self.bounds.is_none() {
+            self.bounds =
+                Some(Aabb::from_points(vertices.iter().map(|v| v.coords)).unwrap_or_default());
+        }
 
         let device = DEVICE.get().unwrap();
         let name = format!("Mesh {}", self.get_id());
 
         let vb = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&name),
-            contents: bytemuck::cast_slice(mesh.vertices.as_slice()),
+            contents: bytemuck::cast_slice(vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
 
         let ib = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&name),
-            contents: bytemuck::cast_slice(mesh.indices.as_slice()),
+            contents: bytemuck::cast_slice(indices),
             usage: wgpu::BufferUsages::INDEX,
         });
 
         self.vertex_buffer = Some(vb);
         self.index_buffer = Some(ib);
+        self.triangles = self.keep_triangles.then(|| {
+            indices[lods[0].start as usize..lods[0].end as usize]
+                .chunks_exact(3)
+                .map(|t| [0, 1, 2].map(|i| vertices[t[i] as usize].coords))
+                .collect()
+        });
 
-        self.vert_count = Some(mesh.vertices.len() as u32);
-        self.tris_count = Some((mesh.indices.len() as u32) / 3u32);
-        self.index_count = Some(mesh.indices.len() as u32);
+        let index_count = lods[0].len() as u32;
+        self.vert_count = Some(vertices.len() as u32);
+        self.tris_count = Some(index_count / 3u32);
+        self.index_count = Some(index_count);
+        self.lods.ranges = lods;
 
         self.initialized = true;
-        Ok(())
+    }
+}
+
+impl Asset for Mesh {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        //This is horrific, but i LOVE this :3
+        let mesh = match &self.mode {
+            MeshMode::SingleObjectOBJ(path) => {
+                //Prase file
+                match crate::import::obj::parse(
+                    //Load file
+                    &(match std::fs::read_to_string(path) {
+                        Ok(it) => it,
+                        Err(err) => return Err(Box::new(err)),
+                    }),
+                ) {
+                    //Get the first mesh
+                    Ok(it) => it.into_iter().next().unwrap(),
+                    Err(err) => return Err(Box::new(err)),
+                }
+            }
+            MeshMode::StaticSingleObjectOBJ(mesh) => match crate::import::obj::parse(mesh) {
+                Ok(it) => it.into_iter().next().unwrap(),
+                Err(err) => return Err(Box::new(err)),
+            },
+            MeshMode::PackedOBJ(file) => {
+                match crate::import::obj::parse(
+                    &(match file.read_to_string() {
+                        Ok(it) => it,
+                        Err(err) => return Err(Box::new(err)),
+                    }),
+                ) {
+                    Ok(it) => it.into_iter().next().unwrap(),
+                    Err(err) => return Err(Box::new(err)),
+                }
+            }
+            MeshMode::Binary(path) => {
+                let data = match std::fs::read(path) {
+                    Ok(it) => it,
+                    Err(err) => return Err(Box::new(err)),
+                };
+                return self.initialize_binary(&data);
+            }
+            MeshMode::StaticBinary(data) => {
+                let data: &'static [u8] = data;
+                return self.initialize_binary(data);
+            }
+            MeshMode::PackedBinary(file) => {
+                let data = match file.read() {
+                    Ok(it) => it,
+                    Err(err) => return Err(Box::new(err)),
+                };
+                return self.initialize_binary(&data);
+            }
+            MeshMode::Gltf(path) => {
+                let gltf = crate::import::gltf::Gltf::load(path)?;
+                return self.initialize_gltf(&gltf);
+            }
+            MeshMode::StaticGltf(data) => {
+                let gltf = crate::import::gltf::Gltf::parse(data)?;
+                return self.initialize_gltf(&gltf);
+            }
+            MeshMode::GeneratedModel(mdl_type) => generate_mesh(mdl_type),
+        };
+
+        self.upload_with_lods(&mesh)
     }
 
     fn dispose(&mut self) {
         //Unload index and vertex buffers, clearing memory
         self.vertex_buffer = None;
         self.index_buffer = None;
+        self.skin_buffer = None;
+        self.triangles = None;
         self.initialized = false;
     }
 
@@ -277,3 +936,23 @@ impl Asset for Mesh {
         self.initialized
     }
 }
+
+#[test]
+fn test_lod_selection() {
+    let mut mesh = Mesh::new_box(Vec3::new(1.0, 1.0, 1.0));
+    mesh.lods.ranges = vec![0..36, 36..48, 48..54];
+    mesh.set_lod_screen_sizes(vec![0.4]);
+    assert!((mesh.lods.screen_size(2) - 0.25).abs() < f32::EPSILON);
+
+    assert_eq!(mesh.select_lod(1.0, 0, 0.1), 0);
+    assert_eq!(mesh.select_lod(0.3, 0, 0.1), 1);
+    assert_eq!(mesh.select_lod(0.01, 0, 0.1), 2);
+    //Close to the switching size the current lod is kept
+    assert_eq!(mesh.select_lod(0.38, 0, 0.1), 0);
+    assert_eq!(mesh.select_lod(0.42, 1, 0.1), 1);
+    assert_eq!(mesh.select_lod(0.45, 1, 0.1), 0);
+    assert_eq!(mesh.select_lod(0.26, 2, 0.1), 2);
+    assert_eq!(mesh.select_lod(0.3, 2, 0.1), 1);
+    //Without hysteresis the lod only depends on the size
+    assert_eq!(mesh.select_lod(0.38, 0, 0.0), 1);
+}
@@ -0,0 +1,1043 @@
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::too_many_lines,
+    clippy::many_single_char_names
+)]
+use std::collections::HashMap;
+
+use crate::{
+    math::{Aabb, Vec3, Vec4, Vector},
+    structures::{Index, Mesh, Vertex},
+};
+
+///Number of vertices in the cache simulated by [`optimize_vertex_cache`]
+const CACHE_SIZE: usize = 32;
+///Number of vertices in the cache used for splitting the mesh into clusters by
+///[`optimize_overdraw`]
+const CLUSTER_CACHE_SIZE: u32 = 16;
+///Score given to the vertices of the last added triangle
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+///How quickly the score of a vertex falls off as it moves through the cache
+const CACHE_DECAY_POWER: f32 = 1.5;
+///Boosts the score of vertices that have few triangles left, to avoid leaving lone triangles behind
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+///How normals are calculated by [`recalculate_normals`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalMode {
+    ///Normals are averaged between all of the triangles that share a vertex position, weighted by
+    ///the angle of the triangle corner
+    Smooth,
+    ///Every triangle uses its own normal, vertices are duplicated so that triangles don't share
+    ///them
+    Flat,
+}
+
+///Returns the positions of the corners of a triangle
+fn corners(vertices: &[Vertex], triangle: &[Index]) -> [Vec3; 3] {
+    [0, 1, 2].map(|i| vertices[triangle[i] as usize].coords)
+}
+
+///Returns the normal of a triangle, the length of the normal is twice the area of the triangle
+fn face_normal([a, b, c]: [Vec3; 3]) -> Vec3 {
+    (b - a).cross(&(c - a))
+}
+
+///Returns the angle between two directions
+fn angle(a: Vec3, b: Vec3) -> f32 {
+    a.normalize()
+        .dot_product(&b.normalize())
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+///Returns the bits of a position, so that it can be used for finding vertices in the same place
+fn position_key(position: Vec3) -> [u32; 3] {
+    //Adding 0 turns -0 into 0
+    [position.x, position.y, position.z].map(|c| (c + 0.0).to_bits())
+}
+
+///Returns a unit vector perpendicular to the normal
+fn perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+    let tangent = axis - normal * normal.dot_product(&axis);
+    if tangent.square_length() == 0.0 {
+        axis
+    } else {
+        tangent.normalize()
+    }
+}
+
+///Calculates the bounding box of the mesh
+///
+///Returns `None` if the mesh has no vertices
+#[must_use]
+pub fn calculate_bounds(mesh: &Mesh) -> Option<Aabb> {
+    Aabb::from_points(mesh.vertices.iter().map(|v| v.coords))
+}
+
+///Recalculates the normals of the mesh from its triangles, normals are expected to point out of
+///counter clockwise triangles
+///
+///Flat normals duplicate the vertices of every triangle, after which the vertices that ended up
+///identical are merged back together
+pub fn recalculate_normals(mesh: &mut Mesh, mode: NormalMode) {
+    mesh.indices.truncate(mesh.indices.len() / 3 * 3);
+
+    match mode {
+        NormalMode::Smooth => {
+            let mut normals = HashMap::<[u32; 3], Vec3>::new();
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = corners(&mesh.vertices, triangle);
+                let normal = face_normal([a, b, c]).normalize();
+                for (corner, weight) in [
+                    (a, angle(b - a, c - a)),
+                    (b, angle(c - b, a - b)),
+                    (c, angle(a - c, b - c)),
+                ] {
+                    *normals.entry(position_key(corner)).or_default() += normal * weight;
+                }
+            }
+            for v in &mut mesh.vertices {
+                v.normal = normals
+                    .get(&position_key(v.coords))
+                    .copied()
+                    .unwrap_or_default()
+                    .normalize();
+            }
+        }
+        NormalMode::Flat => {
+            let mut vertices = Vec::with_capacity(mesh.indices.len());
+            for triangle in mesh.indices.chunks_exact(3) {
+                let normal = face_normal(corners(&mesh.vertices, triangle)).normalize();
+                vertices.extend(triangle.iter().map(|&i| Vertex {
+                    normal,
+                    ..mesh.vertices[i as usize]
+                }));
+            }
+            mesh.indices = (0..vertices.len() as Index).collect();
+            mesh.vertices = vertices;
+            weld(mesh, 0.0);
+        }
+    }
+}
+
+///Generates tangents for normal mapping using the mikktspace algorithm
+///
+///The returned tangents match the vertices of the mesh, the w component contains the handedness
+///of the tangent space, so the bitangent is `normal.cross(tangent.xyz) * tangent.w`. Vertices that
+///are shared between triangles with mirrored texture coordinates are split, so the mesh may gain
+///vertices. Normals are expected to be normalized
+///
+///Vertices are only shared by triangles that use the same index, so vertices that are duplicated
+///should be merged with [`weld`] first
+pub fn generate_tangents(mesh: &mut Mesh) -> Vec<Vec4> {
+    mesh.indices.truncate(mesh.indices.len() / 3 * 3);
+    let vertex_count = mesh.vertices.len();
+
+    //Sums of the tangents of a vertex for each orientation, and whether the vertex was used by
+    //triangles of that orientation
+    let mut sums = vec![[Vec3::default(); 2]; vertex_count];
+    let mut used = vec![[false; 2]; vertex_count];
+    //Orientation of each triangle, `None` if the triangle has no area in texture space
+    let mut orientations = Vec::with_capacity(mesh.indices.len() / 3);
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [v0, v1, v2] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+        let d1 = v1.coords - v0.coords;
+        let d2 = v2.coords - v0.coords;
+        let t1 = v1.texture - v0.texture;
+        let t2 = v2.texture - v0.texture;
+
+        let area = t1.x.mul_add(t2.y, -t1.y * t2.x);
+        let tangent = d1 * t2.y - d2 * t1.y;
+        if area == 0.0
+            || tangent.square_length() == 0.0
+            || face_normal([v0, v1, v2].map(|v| v.coords)).square_length() == 0.0
+        {
+            orientations.push(None);
+            continue;
+        }
+        //Flip the tangent so that it always points in the direction of increasing u
+        let preserving = area > 0.0;
+        let tangent = if preserving { tangent } else { -tangent }.normalize();
+        orientations.push(Some(preserving));
+
+        for (corner, a, b) in [(0, 1, 2), (1, 2, 0), (2, 0, 1)] {
+            let index = triangle[corner] as usize;
+            let normal = mesh.vertices[index].normal;
+            let project = |v: Vec3| v - normal * normal.dot_product(&v);
+
+            let edge_a =
+                project(mesh.vertices[triangle[a] as usize].coords - mesh.vertices[index].coords);
+            let edge_b =
+                project(mesh.vertices[triangle[b] as usize].coords - mesh.vertices[index].coords);
+            let weight = angle(edge_a, edge_b);
+
+            sums[index][usize::from(preserving)] += project(tangent).normalize() * weight;
+            used[index][usize::from(preserving)] = true;
+        }
+    }
+
+    let to_tangent = |sum: Vec3, normal: Vec3, preserving: bool| {
+        let tangent = if sum.square_length() == 0.0 {
+            perpendicular(normal)
+        } else {
+            sum.normalize()
+        };
+        Vec4::from((tangent, if preserving { 1.0 } else { -1.0 }))
+    };
+
+    let mut tangents = (0..vertex_count)
+        .map(|i| {
+            //Mirrored triangles are split off if the vertex is used by both orientations
+            let preserving = used[i][1] || !used[i][0];
+            to_tangent(
+                sums[i][usize::from(preserving)],
+                mesh.vertices[i].normal,
+                preserving,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    //Split the vertices that are used by both orientations
+    let mut mirrored = vec![None; vertex_count];
+    for (triangle, orientation) in mesh.indices.chunks_exact_mut(3).zip(orientations) {
+        if orientation != Some(false) {
+            continue;
+        }
+        for index in triangle {
+            let i = *index as usize;
+            if !used[i][1] {
+                continue;
+            }
+            *index = *mirrored[i].get_or_insert_with(|| {
+                mesh.vertices.push(mesh.vertices[i]);
+                tangents.push(to_tangent(sums[i][0], mesh.vertices[i].normal, false));
+                (mesh.vertices.len() - 1) as Index
+            });
+        }
+    }
+
+    tangents
+}
+
+///Merges vertices whose attributes all differ by at most `epsilon` and removes the triangles that
+///became degenerate
+///
+///The order of the vertices is kept and unused vertices are not removed, see
+///[`optimize_vertex_fetch`] for removing them
+pub fn weld(mesh: &mut Mesh, epsilon: f32) {
+    let close = |a: &Vertex, b: &Vertex| {
+        let attributes = |v: &Vertex| {
+            [
+                v.coords.x,
+                v.coords.y,
+                v.coords.z,
+                v.texture.x,
+                v.texture.y,
+                v.normal.x,
+                v.normal.y,
+                v.normal.z,
+            ]
+        };
+        attributes(a)
+            .into_iter()
+            .zip(attributes(b))
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    };
+    //Positions are put into a grid with cells of the size of epsilon, so close vertices are
+    //always in neighbouring cells
+    let cell = |position: Vec3| {
+        if epsilon > 0.0 {
+            [position.x, position.y, position.z].map(|c| (c / epsilon).floor() as i64)
+        } else {
+            position_key(position).map(i64::from)
+        }
+    };
+    let range = if epsilon > 0.0 { -1..=1 } else { 0..=0 };
+
+    let mut grid = HashMap::<[i64; 3], Vec<Index>>::new();
+    let mut vertices = Vec::new();
+    let mut remap = Vec::with_capacity(mesh.vertices.len());
+
+    for v in &mesh.vertices {
+        let [x, y, z] = cell(v.coords);
+        let mut found = None;
+        'search: for dx in range.clone() {
+            for dy in range.clone() {
+                for dz in range.clone() {
+                    let candidates = grid.get(&[x + dx, y + dy, z + dz]);
+                    found = candidates
+                        .into_iter()
+                        .flatten()
+                        .copied()
+                        .find(|&i| close(&vertices[i as usize], v));
+                    if found.is_some() {
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        remap.push(found.unwrap_or_else(|| {
+            vertices.push(*v);
+            let index = (vertices.len() - 1) as Index;
+            grid.entry([x, y, z]).or_default().push(index);
+            index
+        }));
+    }
+
+    mesh.indices = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| [0, 1, 2].map(|i| remap[t[i] as usize]))
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .flatten()
+        .collect();
+    mesh.vertices = vertices;
+}
+
+///Returns the score of a vertex used for picking the next triangle when optimizing the vertex
+///cache
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(0..3) => LAST_TRIANGLE_SCORE,
+        Some(p) => (1.0 - (p - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER),
+    };
+
+    VALENCE_BOOST_SCALE.mul_add(
+        (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER),
+        cache_score,
+    )
+}
+
+///Reorders the triangles of the mesh so that vertices are reused while they are still in the
+///post transform cache of the gpu
+///
+///Uses Tom Forsyth's linear speed vertex cache optimization
+pub fn optimize_vertex_cache(mesh: &mut Mesh) {
+    let triangles = mesh.indices.len() / 3;
+    let vertex_count = mesh.vertices.len();
+    let indices = &mesh.indices[..triangles * 3];
+
+    //Triangles that use each vertex, the triangles of a vertex are stored in
+    //`adjacency[offsets[v]..offsets[v] + remaining[v]]`
+    let mut remaining = vec![0; vertex_count];
+    for &i in indices {
+        remaining[i as usize] += 1;
+    }
+    let offsets = remaining
+        .iter()
+        .scan(0, |offset, &count| {
+            let current = *offset;
+            *offset += count;
+            Some(current)
+        })
+        .collect::<Vec<_>>();
+    let mut adjacency = vec![0; indices.len()];
+    let mut filled = vec![0; vertex_count];
+    for (triangle, t) in indices.chunks_exact(3).enumerate() {
+        for &i in t {
+            adjacency[offsets[i as usize] + filled[i as usize]] = triangle;
+            filled[i as usize] += 1;
+        }
+    }
+
+    let mut cache_position = vec![None; vertex_count];
+    let mut scores = (0..vertex_count)
+        .map(|v| vertex_score(None, remaining[v]))
+        .collect::<Vec<_>>();
+    let triangle_score = |t: usize, scores: &[f32]| {
+        indices[t * 3..t * 3 + 3]
+            .iter()
+            .map(|&i| scores[i as usize])
+            .sum::<f32>()
+    };
+    let mut triangle_scores = (0..triangles)
+        .map(|t| triangle_score(t, &scores))
+        .collect::<Vec<_>>();
+
+    let mut added = vec![false; triangles];
+    let mut cache = Vec::<Index>::with_capacity(CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(indices.len());
+    //Triangles before this one were already added, used when there are no triangles in the cache
+    let mut cursor = 0;
+    let mut best =
+        (0..triangles).max_by(|&a, &b| triangle_scores[a].total_cmp(&triangle_scores[b]));
+
+    while let Some(triangle) = best {
+        added[triangle] = true;
+        let vertices = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(vertices);
+
+        for &v in vertices {
+            let v = v as usize;
+            let list = &mut adjacency[offsets[v]..offsets[v] + remaining[v]];
+            let position = list.iter().position(|&t| t == triangle).unwrap();
+            list.swap(position, remaining[v] - 1);
+            remaining[v] -= 1;
+        }
+
+        //Vertices of the added triangle are moved to the front of the cache
+        let mut new_cache = Vec::with_capacity(CACHE_SIZE + 3);
+        for &v in vertices.iter().chain(&cache) {
+            if !new_cache.contains(&v) {
+                new_cache.push(v);
+            }
+        }
+        for (position, &v) in new_cache.iter().enumerate() {
+            let v = v as usize;
+            cache_position[v] = (position < CACHE_SIZE).then_some(position);
+            scores[v] = vertex_score(cache_position[v], remaining[v]);
+        }
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for &v in &new_cache {
+            let v = v as usize;
+            for &t in &adjacency[offsets[v]..offsets[v] + remaining[v]] {
+                triangle_scores[t] = triangle_score(t, &scores);
+                if triangle_scores[t] > best_score {
+                    best_score = triangle_scores[t];
+                    best = Some(t);
+                }
+            }
+        }
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+
+        if best.is_none() {
+            while cursor < triangles && added[cursor] {
+                cursor += 1;
+            }
+            best = (cursor < triangles).then_some(cursor);
+        }
+    }
+
+    mesh.indices = output;
+}
+
+///Simulates a fifo vertex cache
+struct CacheSimulation {
+    ///Time at which each vertex was put into the cache
+    timestamps: Vec<u32>,
+    time: u32,
+    size: u32,
+}
+
+impl CacheSimulation {
+    fn new(vertex_count: usize, size: u32) -> Self {
+        Self {
+            timestamps: vec![0; vertex_count],
+            time: size + 1,
+            size,
+        }
+    }
+
+    ///Returns the number of vertices of the triangle that were not in the cache
+    fn access(&mut self, triangle: &[Index]) -> u32 {
+        let mut misses = 0;
+        for &v in triangle {
+            if self.time - self.timestamps[v as usize] > self.size {
+                self.timestamps[v as usize] = self.time;
+                self.time += 1;
+                misses += 1;
+            }
+        }
+        misses
+    }
+
+    ///Empties the cache
+    const fn clear(&mut self) {
+        self.time += self.size + 1;
+    }
+}
+
+///Returns the average number of vertices that need to be transformed per triangle when drawing the
+///mesh with a fifo vertex cache of the given size
+///
+///Values range from 0.5 for a perfect grid to 3 when no vertices are reused
+#[must_use]
+pub fn average_cache_miss_ratio(mesh: &Mesh, cache_size: u32) -> f32 {
+    let mut cache = CacheSimulation::new(mesh.vertices.len(), cache_size);
+    let triangles = mesh.indices.chunks_exact(3);
+    let count = triangles.len();
+    if count == 0 {
+        return 0.0;
+    }
+
+    let misses = triangles.map(|t| cache.access(t)).sum::<u32>();
+    misses as f32 / count as f32
+}
+
+///Reorders the triangles of the mesh so that the ones facing outwards are drawn first, which
+///reduces overdraw since they occlude the rest of the mesh
+///
+///The mesh should already be optimized with [`optimize_vertex_cache`], the triangles are split into
+///clusters that are reordered while keeping the order within the cluster. `threshold` controls how
+///much the vertex cache efficiency can degrade, 1.05 allows 5% more cache misses
+pub fn optimize_overdraw(mesh: &mut Mesh, threshold: f32) {
+    let triangles = mesh.indices.chunks_exact(3).collect::<Vec<_>>();
+    if triangles.is_empty() {
+        return;
+    }
+    let mut cache = CacheSimulation::new(mesh.vertices.len(), CLUSTER_CACHE_SIZE);
+
+    //Clusters are started when none of the vertices of a triangle are in the cache, since that
+    //usually means that a new part of the mesh is started
+    let mut hard_boundaries = vec![0];
+    for (i, t) in triangles.iter().enumerate() {
+        if cache.access(t) == 3 && i != 0 {
+            hard_boundaries.push(i);
+        }
+    }
+    hard_boundaries.push(triangles.len());
+
+    //Clusters are split further as long as the cache efficiency stays within the threshold
+    let mut boundaries = Vec::new();
+    for range in hard_boundaries.windows(2) {
+        let (start, end) = (range[0], range[1]);
+
+        cache.clear();
+        let misses = triangles[start..end]
+            .iter()
+            .map(|t| cache.access(t))
+            .sum::<u32>();
+        let target = misses as f32 / (end - start) as f32 * threshold;
+
+        cache.clear();
+        boundaries.push(start);
+        let (mut cluster_start, mut cluster_misses) = (start, 0);
+        for (i, t) in triangles.iter().enumerate().take(end - 1).skip(start) {
+            cluster_misses += cache.access(t);
+            if cluster_misses as f32 / (i + 1 - cluster_start) as f32 <= target {
+                boundaries.push(i + 1);
+                cache.clear();
+                (cluster_start, cluster_misses) = (i + 1, 0);
+            }
+        }
+    }
+    boundaries.push(triangles.len());
+
+    let weighted = |range: &[usize]| {
+        let mut centroid = Vec3::default();
+        let mut normal = Vec3::default();
+        let mut area = 0.0;
+        for t in &triangles[range[0]..range[1]] {
+            let positions = corners(&mesh.vertices, t);
+            let n = face_normal(positions);
+            let a = n.length();
+            centroid += (positions[0] + positions[1] + positions[2]) * (a / 3.0);
+            normal += n;
+            area += a;
+        }
+        (centroid, normal, area)
+    };
+
+    let (sum, _, area) = weighted(&[0, triangles.len()]);
+    let mesh_centroid = if area > 0.0 { sum / area } else { sum };
+
+    let mut clusters = boundaries
+        .windows(2)
+        .filter(|range| range[0] != range[1])
+        .map(|range| {
+            let (sum, normal, area) = weighted(range);
+            let centroid = if area > 0.0 {
+                sum / area
+            } else {
+                mesh_centroid
+            };
+            let sort_key = (centroid - mesh_centroid).dot_product(&normal.normalize());
+            (sort_key, range[0], range[1])
+        })
+        .collect::<Vec<_>>();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    mesh.indices = clusters
+        .into_iter()
+        .flat_map(|(_, start, end)| triangles[start..end].iter().flat_map(|t| t.iter().copied()))
+        .collect();
+}
+
+///Reorders the vertices in the order they are first used by the triangles, improving the
+///locality of vertex fetches, vertices that are not used by any triangle are removed
+pub fn optimize_vertex_fetch(mesh: &mut Mesh) {
+    let mut remap = vec![Index::MAX; mesh.vertices.len()];
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+
+    for index in &mut mesh.indices {
+        let new = &mut remap[*index as usize];
+        if *new == Index::MAX {
+            vertices.push(mesh.vertices[*index as usize]);
+            *new = (vertices.len() - 1) as Index;
+        }
+        *index = *new;
+    }
+
+    mesh.vertices = vertices;
+}
+
+///Runs all of the optimizations on the mesh, first the vertex cache, then the overdraw and then
+///the vertex fetch optimization
+pub fn optimize(mesh: &mut Mesh) {
+    optimize_vertex_cache(mesh);
+    optimize_overdraw(mesh, 1.05);
+    optimize_vertex_fetch(mesh);
+}
+
+///Symmetric matrix and vector describing the sum of squared distances to a set of planes
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    ///Upper triangle of the matrix: xx, xy, xz, yy, yz, zz
+    a: [f64; 6],
+    b: [f64; 3],
+    c: f64,
+}
+
+impl Quadric {
+    ///Creates a quadric of the plane going through the triangle, weighted by its area
+    fn from_triangle([p0, p1, p2]: [Vec3; 3]) -> Self {
+        let normal = face_normal([p0, p1, p2]);
+        let area = f64::from(normal.length()) / 2.0;
+        if area == 0.0 {
+            return Self::default();
+        }
+        let n = normal.normalize();
+        let [x, y, z] = [n.x, n.y, n.z].map(f64::from);
+        let d = -f64::from(n.dot_product(&p0));
+
+        Self {
+            a: [x * x, x * y, x * z, y * y, y * z, z * z].map(|v| v * area),
+            b: [x * d, y * d, z * d].map(|v| v * area),
+            c: d * d * area,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.a.iter_mut().zip(other.a) {
+            *a += b;
+        }
+        for (a, b) in self.b.iter_mut().zip(other.b) {
+            *a += b;
+        }
+        self.c += other.c;
+    }
+
+    ///Returns the sum of the squared distances of the point to the planes, weighted by their area
+    fn error(&self, point: Vec3) -> f64 {
+        let [x, y, z] = [point.x, point.y, point.z].map(f64::from);
+        let [xx, xy, xz, yy, yz, zz] = self.a;
+        let [bx, by, bz] = self.b;
+
+        let quadratic = (2.0 * yz).mul_add(
+            y * z,
+            (2.0 * xz).mul_add(
+                x * z,
+                (2.0 * xy).mul_add(x * y, zz.mul_add(z * z, xx.mul_add(x * x, yy * y * y))),
+            ),
+        );
+        let linear = 2.0 * bz.mul_add(z, bx.mul_add(x, by * y));
+        (quadratic + linear + self.c).max(0.0)
+    }
+}
+
+///Simplifies the mesh by collapsing edges, until the number of indices is at most
+///`target_index_count` or no edge can be collapsed without the error going over `target_error`
+///
+///Returns the new indices, which use the same vertices as the mesh. The error is the distance the
+///surface moves, relative to the size of the mesh, so 0.01 allows the surface to move by 1% of the
+///size of the mesh. Vertices on the borders of the mesh and on texture or normal seams stay in
+///place
+#[must_use]
+pub fn simplify(mesh: &Mesh, target_index_count: usize, target_error: f32) -> Vec<Index> {
+    let vertex_count = mesh.vertices.len();
+    let mut indices = mesh.indices[..mesh.indices.len() / 3 * 3].to_vec();
+
+    //Vertices that are in the same place share quadrics and adjacency
+    let mut first_at = HashMap::new();
+    let representative = mesh
+        .vertices
+        .iter()
+        .enumerate()
+        .map(|(i, v)| *first_at.entry(position_key(v.coords)).or_insert(i as Index))
+        .collect::<Vec<_>>();
+
+    //Seams have multiple vertices in the same place, and borders have edges that only have one
+    //triangle, moving them would open holes or distort the attributes
+    let mut locked = vec![false; vertex_count];
+    for (i, &r) in representative.iter().enumerate() {
+        if r as usize != i {
+            locked[i] = true;
+            locked[r as usize] = true;
+        }
+    }
+    let mut edges = HashMap::<(Index, Index), u32>::new();
+    for t in indices.chunks_exact(3) {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            let (a, b) = (representative[a as usize], representative[b as usize]);
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    for (&(a, b), &count) in &edges {
+        if count == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    for t in indices.chunks_exact(3) {
+        let quadric = Quadric::from_triangle(corners(&mesh.vertices, t));
+        for &i in t {
+            quadrics[representative[i as usize] as usize].add(&quadric);
+        }
+    }
+
+    let scale = calculate_bounds(mesh).map_or(0.0, |b| b.size().max());
+    let max_cost = f64::from(target_error * scale).powi(2);
+    let position = |i: Index| mesh.vertices[i as usize].coords;
+
+    while indices.len() > target_index_count {
+        let mut adjacency = vec![Vec::new(); vertex_count];
+        for (triangle, t) in indices.chunks_exact(3).enumerate() {
+            for &i in t {
+                adjacency[representative[i as usize] as usize].push(triangle);
+            }
+        }
+
+        //Collapses move the first vertex onto the second one
+        let mut candidates = Vec::new();
+        for t in indices.chunks_exact(3) {
+            for (a, b) in [
+                (t[0], t[1]),
+                (t[1], t[2]),
+                (t[2], t[0]),
+                (t[1], t[0]),
+                (t[2], t[1]),
+                (t[0], t[2]),
+            ] {
+                if locked[a as usize] {
+                    continue;
+                }
+                let mut quadric = quadrics[a as usize];
+                quadric.add(&quadrics[representative[b as usize] as usize]);
+                let cost = quadric.error(position(b));
+                if cost <= max_cost {
+                    candidates.push((cost, a, b));
+                }
+            }
+        }
+        candidates.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut remap = (0..vertex_count as Index).collect::<Vec<_>>();
+        let mut touched = vec![false; vertex_count];
+        let mut remaining = indices.len();
+        let mut collapsed = false;
+
+        for (_, a, b) in candidates {
+            if remaining <= target_index_count {
+                break;
+            }
+            let b_representative = representative[b as usize];
+            if touched[a as usize] || touched[b_representative as usize] {
+                continue;
+            }
+
+            //Triangles around the collapsed vertex must not flip or rotate too much
+            let mut removed = 0;
+            let flips = adjacency[a as usize].iter().any(|&triangle| {
+                let t = &indices[triangle * 3..triangle * 3 + 3];
+                if t.iter()
+                    .any(|&i| representative[i as usize] == b_representative)
+                {
+                    removed += 3;
+                    return false;
+                }
+                let before = face_normal(corners(&mesh.vertices, t));
+                let after = face_normal([0, 1, 2].map(|i| {
+                    if t[i] == a {
+                        position(b)
+                    } else {
+                        position(t[i])
+                    }
+                }));
+                //Triangles that rotate too much are usually turned into slivers
+                before.normalize().dot_product(&after.normalize()) < 0.5
+            });
+            if flips {
+                continue;
+            }
+
+            remap[a as usize] = b;
+            let quadric = quadrics[a as usize];
+            quadrics[b_representative as usize].add(&quadric);
+            remaining -= removed;
+            collapsed = true;
+
+            //Neighbours are locked for the rest of the pass, since their triangles changed
+            for &triangle in &adjacency[a as usize] {
+                for &i in &indices[triangle * 3..triangle * 3 + 3] {
+                    touched[representative[i as usize] as usize] = true;
+                }
+            }
+        }
+
+        if !collapsed {
+            break;
+        }
+
+        indices = indices
+            .chunks_exact(3)
+            .map(|t| [0, 1, 2].map(|i| remap[t[i] as usize]))
+            .filter(|t| {
+                let [a, b, c] = t.map(|i| representative[i as usize]);
+                a != b && b != c && a != c
+            })
+            .flatten()
+            .collect();
+    }
+
+    indices
+}
+
+///Builds a flat grid of quads in the xy plane with the texture coordinates following the position
+#[cfg(test)]
+fn grid(size: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    for y in 0..=size {
+        for x in 0..=size {
+            mesh.vertices.push(Vertex {
+                coords: Vec3::new(x, y, 0),
+                texture: crate::math::Vec2::new(x, y) / size as f32,
+                normal: Vec3::new(0, 0, 1),
+            });
+        }
+    }
+    for y in 0..size {
+        for x in 0..size {
+            let i = y * (size + 1) + x;
+            let j = i + size + 1;
+            mesh.indices
+                .extend_from_slice(&[i, i + 1, j, j, i + 1, j + 1]);
+        }
+    }
+    mesh
+}
+
+///Returns the triangles of the mesh as sorted vertices, so meshes can be compared regardless of
+///the order of the triangles and vertices
+#[cfg(test)]
+fn sorted_triangles(mesh: &Mesh) -> Vec<[[u32; 3]; 3]> {
+    let mut triangles = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| {
+            let mut corners = corners(&mesh.vertices, t).map(position_key);
+            //Rotate the corners so the smallest one is first, keeping the winding
+            let first = (0..3).min_by_key(|&i| corners[i]).unwrap();
+            corners.rotate_left(first);
+            corners
+        })
+        .collect::<Vec<_>>();
+    triangles.sort_unstable();
+    triangles
+}
+
+#[test]
+fn test_normals() {
+    let mut mesh = grid(2);
+    for v in &mut mesh.vertices {
+        v.normal = Vec3::default();
+    }
+    recalculate_normals(&mut mesh, NormalMode::Smooth);
+    assert!(
+        mesh.vertices
+            .iter()
+            .all(|v| (v.normal - Vec3::new(0, 0, 1)).length() < 1e-5)
+    );
+
+    //Two triangles folded along the x axis
+    let mut mesh = Mesh {
+        vertices: [(0, 0, 0), (1, 0, 0), (0, 1, 0), (0, -1, -1)]
+            .map(|c| Vertex {
+                coords: c.into(),
+                ..Default::default()
+            })
+            .to_vec(),
+        indices: vec![0, 1, 2, 0, 3, 1],
+    };
+    recalculate_normals(&mut mesh, NormalMode::Smooth);
+    //Shared vertices get the average of both normals
+    let expected = (Vec3::new(0, 0, 1) + Vec3::new(0, -1, 1).normalize()).normalize();
+    assert!((mesh.vertices[0].normal - expected).length() < 1e-5);
+    assert!((mesh.vertices[2].normal - Vec3::new(0, 0, 1)).length() < 1e-5);
+
+    recalculate_normals(&mut mesh, NormalMode::Flat);
+    assert_eq!(mesh.vertices.len(), 6);
+    assert_eq!(mesh.vertices[0].normal, Vec3::new(0, 0, 1));
+    assert!((mesh.vertices[3].normal - Vec3::new(0, -1, 1).normalize()).length() < 1e-5);
+}
+
+#[test]
+fn test_tangents() {
+    let mut mesh = grid(2);
+    let tangents = generate_tangents(&mut mesh);
+    assert_eq!(tangents.len(), mesh.vertices.len());
+    assert!(tangents.iter().all(|t| *t == Vec4::new(1, 0, 0, 1)));
+
+    //Mirror the texture coordinates of the right half
+    let mut mesh = grid(2);
+    for v in &mut mesh.vertices {
+        v.texture.x = 1.0 - (v.coords.x - 1.0).abs();
+    }
+    let tangents = generate_tangents(&mut mesh);
+    //The middle column is shared by both halves, so it is split
+    assert_eq!(mesh.vertices.len(), 12);
+    assert_eq!(tangents.len(), 12);
+    for triangle in mesh.indices.chunks_exact(3) {
+        let mirrored = triangle
+            .iter()
+            .any(|&i| mesh.vertices[i as usize].coords.x > 1.0);
+        for &i in triangle {
+            let expected = if mirrored {
+                Vec4::new(-1, 0, 0, -1)
+            } else {
+                Vec4::new(1, 0, 0, 1)
+            };
+            assert_eq!(tangents[i as usize], expected);
+        }
+    }
+}
+
+#[test]
+fn test_bounds_and_weld() {
+    let mut mesh = grid(2);
+    assert_eq!(
+        calculate_bounds(&mesh),
+        Some(Aabb::new(Vec3::new(0, 0, 0), Vec3::new(2, 2, 0)))
+    );
+    assert_eq!(calculate_bounds(&Mesh::default()), None);
+
+    //Unweld every triangle and offset the copies slightly
+    recalculate_normals(&mut mesh, NormalMode::Flat);
+    assert_eq!(mesh.vertices.len(), 9);
+    let mut unwelded = Mesh {
+        vertices: mesh
+            .indices
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| Vertex {
+                coords: mesh.vertices[v as usize].coords + i as f32 * 1e-5,
+                ..mesh.vertices[v as usize]
+            })
+            .collect(),
+        indices: (0..mesh.indices.len() as Index).collect(),
+    };
+
+    weld(&mut unwelded, 1e-3);
+    assert_eq!(unwelded.vertices.len(), 9);
+    assert_eq!(unwelded.indices.len(), 24);
+
+    //Triangles that collapse are removed
+    weld(&mut unwelded, 1.5);
+    assert!(unwelded.indices.len() < 24);
+    assert!(unwelded.vertices.len() < 9);
+}
+
+#[test]
+fn test_optimize() {
+    let mut mesh = grid(16);
+    //Shuffle the triangles
+    let mut triangles = mesh
+        .indices
+        .chunks_exact(3)
+        .map(<[Index]>::to_vec)
+        .collect::<Vec<_>>();
+    let count = triangles.len();
+    for i in 0..count {
+        triangles.swap(i, (i * 7919 + 13) % count);
+    }
+    mesh.indices = triangles.concat();
+    let expected = sorted_triangles(&mesh);
+    let shuffled = average_cache_miss_ratio(&mesh, 16);
+
+    optimize_vertex_cache(&mut mesh);
+    let optimized = average_cache_miss_ratio(&mesh, 16);
+    assert!(
+        optimized < 0.8 && optimized < shuffled,
+        "{shuffled} -> {optimized}"
+    );
+    assert_eq!(sorted_triangles(&mesh), expected);
+
+    optimize_overdraw(&mut mesh, 1.05);
+    assert_eq!(sorted_triangles(&mesh), expected);
+
+    optimize_vertex_fetch(&mut mesh);
+    assert_eq!(sorted_triangles(&mesh), expected);
+    let mut seen = 0;
+    for &i in &mesh.indices {
+        assert!(i <= seen);
+        seen = seen.max(i + 1);
+    }
+
+    //Unused vertices are removed
+    let mut mesh = grid(1);
+    mesh.indices.truncate(3);
+    optimize(&mut mesh);
+    assert_eq!(mesh.vertices.len(), 3);
+    assert_eq!(mesh.indices, [0, 1, 2]);
+}
+
+#[test]
+fn test_simplify() {
+    //Interior vertices of a flat grid can all be removed without any error
+    let mesh = grid(8);
+    let indices = simplify(&mesh, 0, 0.0);
+    assert!(indices.len() < mesh.indices.len() / 2);
+    assert!(indices.len().is_multiple_of(3));
+    let simplified = Mesh {
+        vertices: mesh.vertices.clone(),
+        indices,
+    };
+    //The border is kept in place, so the grid still covers the same area
+    let area = |m: &Mesh| {
+        m.indices
+            .chunks_exact(3)
+            .map(|t| face_normal(corners(&m.vertices, t)).z / 2.0)
+            .sum::<f32>()
+    };
+    assert!((area(&simplified) - 64.0).abs() < 1e-3);
+    assert!(simplified.indices.iter().all(|&i| {
+        let c = mesh.vertices[i as usize].coords;
+        c.x == 0.0 || c.y == 0.0 || c.x == 8.0 || c.y == 8.0
+    }));
+
+    assert_eq!(simplify(&grid(2), 0, 0.0).len(), 6 * 3);
+
+    //A sphere can't be simplified without error
+    let sphere = super::mesh_generator::generate_mesh(&super::ModelType::Icosphere(1.0, 3));
+    let target = sphere.indices.len() / 4;
+    assert_eq!(simplify(&sphere, target, 0.0).len(), sphere.indices.len());
+    let indices = simplify(&sphere, target, 0.1);
+    assert!(indices.len() <= target && indices.len() > target / 2);
+    //Triangles still face outwards
+    let simplified = Mesh {
+        vertices: sphere.vertices,
+        indices,
+    };
+    for t in simplified.indices.chunks_exact(3) {
+        let c = corners(&simplified.vertices, t);
+        assert!(face_normal(c).dot_product(&(c[0] + c[1] + c[2])) > 0.0);
+    }
+}
@@ -1,5 +1,11 @@
 //! Implemented assets
 
+///Keyframe animation clips of component properties
+pub mod animation;
+///Texture atlas asset that stores many images in a single texture
+pub mod atlas;
+///Font asset and text layout
+pub mod font;
 pub(crate) mod heleprs;
 ///Material struct
 pub mod material;
@@ -7,14 +13,20 @@ pub mod material;
 pub mod materials;
 ///Mesh asset
 pub mod mesh;
+///Skeleton asset used for animating skinned meshes
+pub mod skeleton;
 #[cfg(test)]
 mod tests;
 ///Texture asset
 pub mod texture;
 
-pub use material::Material;
+pub use animation::AnimationClip;
+pub use atlas::TextureAtlas;
+pub use font::Font;
+pub use material::{BlendMode, Material};
 pub use mesh::Mesh;
-pub use texture::Texture;
+pub use skeleton::Skeleton;
+pub use texture::{ColorSpace, ImageFormat, Texture};
 
 #[derive(Clone, Copy)]
 ///Represents bindroup state of an asset that contains gpu related data
@@ -0,0 +1,343 @@
+use std::path::{Path, PathBuf};
+
+use super::animation::{Interpolation, keyframes_valid, sample};
+use crate::{
+    UUID,
+    asset_managment::Asset,
+    import::gltf::Gltf,
+    math::{Mat4x4, Quaternion, Vec3},
+};
+
+///Maximum number of joints in a skeleton that can be rendered
+pub const MAX_JOINTS: usize = 128;
+
+///Transformation of a joint relative to its parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointPose {
+    ///Translation of the joint
+    pub translation: Vec3,
+    ///Rotation of the joint
+    pub rotation: Quaternion,
+    ///Scale of the joint
+    pub scale: Vec3,
+}
+
+impl Default for JointPose {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::default(),
+            rotation: Quaternion::default(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl JointPose {
+    ///Returns the transformation matrix of the pose, scaling first, then rotating and translating
+    #[must_use]
+    pub fn matrix(&self) -> Mat4x4 {
+        Mat4x4::translation_matrix(&self.translation)
+            * self.rotation.matrix()
+            * Mat4x4::scale_matrix(&self.scale)
+    }
+
+    ///Interpolates between the poses, returns self if `t` is 0 and `other` if `t` is 1
+    #[must_use]
+    pub fn blend(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.nlerp(&other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+///A joint of a skeleton
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    ///Name of the joint
+    pub name: String,
+    ///Index of the parent joint, `None` for root joints
+    pub parent: Option<usize>,
+    ///Transforms vertices of the mesh into the space of the joint in the bind pose
+    pub inverse_bind_matrix: Mat4x4,
+    ///Pose of the joint when it is not animated
+    pub rest_pose: JointPose,
+}
+
+///Keyframe values of a channel
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValues {
+    ///Translations of the joint
+    Translation(Vec<Vec3>),
+    ///Rotations of the joint
+    Rotation(Vec<Quaternion>),
+    ///Scales of the joint
+    Scale(Vec<Vec3>),
+}
+
+///Animates a property of a joint
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointChannel {
+    ///Index of the animated joint
+    pub joint: usize,
+    ///Interpolation between the keyframes
+    pub interpolation: Interpolation,
+    ///Times of the keyframes in seconds, in increasing order
+    pub times: Vec<f32>,
+    ///Values of the keyframes, three per keyframe when using [`Interpolation::CubicSpline`]
+    pub values: ChannelValues,
+}
+
+///Animation of the joints of a skeleton
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkeletalClip {
+    ///Name of the clip
+    pub name: String,
+    ///Length of the clip in seconds
+    pub duration: f32,
+    ///Animated properties of the joints
+    pub channels: Vec<JointChannel>,
+}
+
+impl JointChannel {
+    fn is_valid(&self) -> bool {
+        let values = match &self.values {
+            ChannelValues::Translation(v) | ChannelValues::Scale(v) => v.len(),
+            ChannelValues::Rotation(v) => v.len(),
+        };
+        keyframes_valid(&self.times, values, self.interpolation)
+    }
+}
+
+impl SkeletalClip {
+    ///Samples the clip at the given time in seconds, writing the animated properties into the
+    ///pose
+    ///
+    ///Properties that are not animated by the clip are left unchanged
+    pub fn sample(&self, time: f32, pose: &mut [JointPose]) {
+        for channel in &self.channels {
+            let Some(joint) = pose.get_mut(channel.joint) else {
+                continue;
+            };
+            let times = channel.times.as_slice();
+
+            match &channel.values {
+                ChannelValues::Translation(values) => {
+                    joint.translation = sample(times, values, channel.interpolation, time);
+                }
+                ChannelValues::Rotation(values) => {
+                    joint.rotation = sample(times, values, channel.interpolation, time);
+                }
+                ChannelValues::Scale(values) => {
+                    joint.scale = sample(times, values, channel.interpolation, time);
+                }
+            }
+        }
+    }
+}
+
+enum SkeletonMode {
+    Data,
+    Gltf(PathBuf),
+    StaticGltf(&'static [u8]),
+}
+
+///Asset that stores the joints of a skinned mesh and the animation clips of those joints
+pub struct Skeleton {
+    id: Option<UUID>,
+    initialized: bool,
+    mode: SkeletonMode,
+    joints: Vec<Joint>,
+    clips: Vec<SkeletalClip>,
+    ///Joint indices ordered so that parents come before their children
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    ///Creates a new skeleton from the given joints and clips
+    #[must_use]
+    pub const fn new(joints: Vec<Joint>, clips: Vec<SkeletalClip>) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: SkeletonMode::Data,
+            joints,
+            clips,
+            order: Vec::new(),
+        }
+    }
+
+    ///Creates a new asset that will load the first skin in a gltf file and all the animations of
+    ///its joints
+    #[must_use]
+    pub fn new_from_gltf(path: &Path) -> Self {
+        Self {
+            mode: SkeletonMode::Gltf(path.to_owned()),
+            ..Self::new(Vec::new(), Vec::new())
+        }
+    }
+
+    ///Creates a new asset that will load the first skin in a statically loaded gltf file and all
+    ///the animations of its joints
+    #[must_use]
+    pub const fn new_from_static_gltf(data: &'static [u8]) -> Self {
+        Self {
+            id: None,
+            initialized: false,
+            mode: SkeletonMode::StaticGltf(data),
+            joints: Vec::new(),
+            clips: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    ///Returns the joints of the skeleton
+    #[must_use]
+    pub fn get_joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    ///Returns the index of the joint with the given name
+    #[must_use]
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|j| j.name == name)
+    }
+
+    ///Returns the animation clips of the skeleton
+    #[must_use]
+    pub fn get_clips(&self) -> &[SkeletalClip] {
+        &self.clips
+    }
+
+    ///Returns the index of the clip with the given name
+    #[must_use]
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|c| c.name == name)
+    }
+
+    ///Returns the rest poses of all joints
+    #[must_use]
+    pub fn rest_pose(&self) -> Vec<JointPose> {
+        self.joints.iter().map(|j| j.rest_pose).collect()
+    }
+
+    ///Calculates the matrices that transform the vertices of the mesh from the bind pose into the
+    ///given pose
+    ///
+    ///# Panics
+    ///Panics if the asset was not initialized or the pose has a different number of joints
+    #[must_use]
+    pub fn skinning_matrices(&self, pose: &[JointPose]) -> Vec<Mat4x4> {
+        assert!(self.initialized, "Skeleton was not initialized");
+        assert_eq!(pose.len(), self.joints.len(), "Wrong number of joints");
+
+        let mut global = vec![Mat4x4::identity(); self.joints.len()];
+        for &i in &self.order {
+            let local = pose[i].matrix();
+            global[i] = self.joints[i].parent.map_or(local, |p| global[p] * local);
+        }
+
+        global
+            .into_iter()
+            .zip(&self.joints)
+            .map(|(g, j)| g * j.inverse_bind_matrix)
+            .collect()
+    }
+
+    ///Checks the joints and orders them so that parents come before their children
+    fn prepare(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let invalid = crate::import::invalid;
+
+        if self.joints.is_empty() {
+            return Err(invalid("Skeleton has no joints"));
+        }
+        if self.joints.len() > MAX_JOINTS {
+            return Err(invalid("Skeleton has too many joints"));
+        }
+        if self
+            .joints
+            .iter()
+            .any(|j| j.parent >= Some(self.joints.len()))
+        {
+            return Err(invalid("Joint parent does not exist"));
+        }
+        if self
+            .clips
+            .iter()
+            .flat_map(|c| &c.channels)
+            .any(|c| c.joint >= self.joints.len())
+        {
+            return Err(invalid("Animated joint does not exist"));
+        }
+        if !self
+            .clips
+            .iter()
+            .flat_map(|c| &c.channels)
+            .all(JointChannel::is_valid)
+        {
+            return Err(invalid("Skeletal animation channel has invalid keyframes"));
+        }
+
+        let mut order = Vec::with_capacity(self.joints.len());
+        let mut added = vec![false; self.joints.len()];
+        while order.len() < self.joints.len() {
+            let previous = order.len();
+            for (i, joint) in self.joints.iter().enumerate() {
+                if !added[i] && joint.parent.is_none_or(|p| added[p]) {
+                    added[i] = true;
+                    order.push(i);
+                }
+            }
+            if order.len() == previous {
+                return Err(invalid("Joint hierarchy contains a cycle"));
+            }
+        }
+
+        self.order = order;
+        Ok(())
+    }
+}
+
+impl Asset for Skeleton {
+    fn get_id(&self) -> UUID {
+        self.id.unwrap()
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let gltf = match &self.mode {
+            SkeletonMode::Data => None,
+            SkeletonMode::Gltf(path) => Some(Gltf::load(path)?),
+            SkeletonMode::StaticGltf(data) => Some(Gltf::parse(data)?),
+        };
+        if let Some(gltf) = gltf {
+            self.joints = gltf.skeleton(0)?;
+            self.clips = gltf.animations(0)?;
+        }
+
+        self.prepare()?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn dispose(&mut self) {
+        if !matches!(self.mode, SkeletonMode::Data) {
+            self.joints = Vec::new();
+            self.clips = Vec::new();
+        }
+        self.initialized = false;
+    }
+
+    fn set_id(&mut self, id: UUID) -> Result<(), crate::asset_managment::Error> {
+        if self.id.is_some() {
+            Err(crate::asset_managment::Error::IdAlreadySet)
+        } else {
+            self.id = Some(id);
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+}
@@ -15,6 +15,71 @@ fn test_texture_load() {
     texture.initialize().unwrap();
 }
 
+#[test]
+fn test_texture_sampler_settings() {
+    crate::test_utils::generate_gpu();
+    let mut texture = super::Texture::new_png(Path::new("assets/test-data/blahaj.png"));
+    texture.set_id(1).unwrap();
+    texture.set_mip_count(u8::MAX);
+    texture.set_anisotropy(32);
+    texture.set_adress_mode(wgpu::AddressMode::Repeat);
+    texture.set_color_space(super::ColorSpace::Srgb);
+    assert_eq!(texture.anisotropy(), 16);
+
+    texture.initialize().unwrap();
+    let gpu_texture = texture.texture.as_ref().unwrap();
+    assert!(gpu_texture.mip_level_count() > 1);
+    assert_eq!(gpu_texture.format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+}
+
+#[test]
+fn test_cubemap_load() {
+    crate::test_utils::generate_gpu();
+    let face: &'static [u8] = include_bytes!("../../assets/empty_texture.png");
+    let mut cubemap = super::Texture::static_cubemap([face; 6], super::ImageFormat::Png);
+    cubemap.set_id(1).unwrap();
+    assert!(cubemap.is_cubemap());
+
+    cubemap.initialize().unwrap();
+    cubemap.dispose();
+    cubemap.initialize().unwrap();
+    assert_eq!(cubemap.texture.as_ref().unwrap().depth_or_array_layers(), 6);
+
+    let mut equirectangular = super::Texture::new_equirectangular(
+        Path::new("assets/test-data/blahaj.png"),
+        super::ImageFormat::Png,
+        64,
+    );
+    equirectangular.set_id(2).unwrap();
+    equirectangular.initialize().unwrap();
+    assert_eq!(equirectangular.texture.as_ref().unwrap().width(), 64);
+}
+
+#[test]
+fn test_compressed_texture_load() {
+    crate::test_utils::generate_gpu();
+    //8x8 bc1 with all of its mips
+    let dds = crate::import::dds::build_dds(
+        8,
+        4,
+        [32, 4, u32::from_le_bytes(*b"DXT1"), 0, 0, 0, 0, 0],
+        None,
+    );
+    let mut texture = super::Texture::from_bytes(dds, super::ImageFormat::Dds);
+    texture.set_id(1).unwrap();
+    texture.set_color_space(super::ColorSpace::Srgb);
+
+    texture.initialize().unwrap();
+    let gpu_texture = texture.texture.as_ref().unwrap();
+    assert_eq!(gpu_texture.mip_level_count(), 4);
+    //Decompressed if the device doesn't support bc
+    assert!(matches!(
+        gpu_texture.format(),
+        wgpu::TextureFormat::Bc1RgbaUnormSrgb | wgpu::TextureFormat::Rgba8UnormSrgb
+    ));
+    assert!(!texture.is_cubemap());
+}
+
 #[test]
 fn test_mesh_load() {
     crate::test_utils::generate_gpu();
@@ -25,3 +90,281 @@ fn test_mesh_load() {
     mesh.dispose();
     mesh.initialize().unwrap();
 }
+
+#[test]
+fn test_generate_mips() {
+    use super::texture::{generate_mips, max_mip_count};
+
+    assert_eq!(max_mip_count(1, 1), 1);
+    assert_eq!(max_mip_count(256, 16), 9);
+    assert_eq!(max_mip_count(5, 3), 3);
+
+    let image = lunar_png::Image {
+        img_type: lunar_png::ImageType::Rgba8,
+        width: 2,
+        height: 2,
+        data: [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+            [255, 255, 255, 0],
+        ]
+        .concat(),
+    };
+
+    let linear = generate_mips(&image.data, 2, 2, wgpu::TextureFormat::Rgba8Unorm, 2);
+    assert_eq!(linear.len(), 20);
+    assert_eq!(&linear[16..], &[128, 128, 128, 191]);
+
+    //Averaged in linear space
+    let srgb = generate_mips(&image.data, 2, 2, wgpu::TextureFormat::Rgba8UnormSrgb, 2);
+    assert_eq!(&srgb[16..], &[188, 188, 188, 191]);
+
+    let single = generate_mips(&image.data, 2, 2, wgpu::TextureFormat::Rgba8Unorm, 1);
+    assert_eq!(single, image.data);
+}
+
+#[test]
+fn test_mesh_lods() {
+    crate::test_utils::generate_gpu();
+    let mut mesh = super::Mesh::new_icosphere(1.0, 3);
+    mesh.set_id(1).unwrap();
+    mesh.set_generated_lod_count(2);
+    mesh.initialize().unwrap();
+
+    assert_eq!(mesh.get_lod_count(), 3);
+    assert_eq!(mesh.get_lod_range(0).len() as u32, mesh.get_index_count());
+    assert!(mesh.get_lod_range(1).len() < mesh.get_lod_range(0).len());
+    assert!(mesh.get_lod_range(2).len() < mesh.get_lod_range(1).len());
+    assert_eq!(
+        mesh.get_lod_range(2).end,
+        mesh.get_lod_range(1).end + mesh.get_lod_range(2).len() as u32
+    );
+
+    let mut provided = super::Mesh::new_box(crate::math::Vec3::new(1.0, 1.0, 1.0));
+    provided.set_id(2).unwrap();
+    provided.set_lods(vec![vec![0, 1, 2]]);
+    provided.initialize().unwrap();
+    assert_eq!(provided.get_lod_count(), 2);
+    assert_eq!(provided.get_lod_range(1), 36..39);
+}
+
+#[test]
+fn test_skeleton() {
+    use super::{
+        animation::Interpolation,
+        skeleton::{ChannelValues, Joint, JointChannel, JointPose, SkeletalClip},
+    };
+    use crate::math::{Mat4x4, Quaternion, Vec3, Vector as _};
+
+    let joints = vec![
+        Joint {
+            name: "root".to_owned(),
+            parent: None,
+            inverse_bind_matrix: Mat4x4::identity(),
+            rest_pose: JointPose::default(),
+        },
+        Joint {
+            name: "tip".to_owned(),
+            parent: Some(0),
+            inverse_bind_matrix: Mat4x4::translation_matrix(&Vec3::new(0.0, -1.0, 0.0)),
+            rest_pose: JointPose {
+                translation: Vec3::new(0.0, 1.0, 0.0),
+                ..Default::default()
+            },
+        },
+    ];
+    let channel = |interpolation, times: Vec<f32>, values: Vec<Vec3>| JointChannel {
+        joint: 0,
+        interpolation,
+        times,
+        values: ChannelValues::Translation(values),
+    };
+    let clips = vec![
+        SkeletalClip {
+            name: "step".to_owned(),
+            duration: 1.0,
+            channels: vec![channel(
+                Interpolation::Step,
+                vec![0.0, 1.0],
+                vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0)],
+            )],
+        },
+        SkeletalClip {
+            name: "linear".to_owned(),
+            duration: 1.0,
+            channels: vec![channel(
+                Interpolation::Linear,
+                vec![0.0, 1.0],
+                vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0)],
+            )],
+        },
+        //Zero tangents, the curve is smooth at the keyframes
+        SkeletalClip {
+            name: "cubic".to_owned(),
+            duration: 1.0,
+            channels: vec![channel(
+                Interpolation::CubicSpline,
+                vec![0.0, 1.0],
+                vec![
+                    Vec3::default(),
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::default(),
+                    Vec3::default(),
+                    Vec3::new(2.0, 0.0, 0.0),
+                    Vec3::default(),
+                ],
+            )],
+        },
+    ];
+
+    let mut skeleton = super::Skeleton::new(joints, clips);
+    skeleton.set_id(1).unwrap();
+    skeleton.initialize().unwrap();
+    assert_eq!(skeleton.joint_index("tip"), Some(1));
+    assert_eq!(skeleton.clip_index("cubic"), Some(2));
+
+    //The rest pose is the bind pose
+    let rest = skeleton.rest_pose();
+    for m in skeleton.skinning_matrices(&rest) {
+        assert_eq!(m, Mat4x4::identity());
+    }
+
+    let sample = |clip: usize, time: f32| {
+        let mut pose = skeleton.rest_pose();
+        skeleton.get_clips()[clip].sample(time, &mut pose);
+        pose[0].translation.x
+    };
+    assert!((sample(0, 0.75) - 1.0).abs() < 1e-5);
+    assert!((sample(1, 0.75) - 1.75).abs() < 1e-5);
+    assert!((sample(2, 0.5) - 1.5).abs() < 1e-5);
+    assert!((sample(2, 0.25) - 1.156_25).abs() < 1e-5);
+    //Clamped outside of the keyframes
+    assert!((sample(1, 5.0) - 2.0).abs() < 1e-5);
+    assert!((sample(1, -1.0) - 1.0).abs() < 1e-5);
+
+    //Rotating the root moves the vertices of the tip around it
+    let mut pose = skeleton.rest_pose();
+    pose[0].rotation = Quaternion::from_euler(Vec3::new(0.0, 0.0, 90.0));
+    let matrices = skeleton.skinning_matrices(&pose);
+    let tip = matrices[1].transform3(Vec3::new(0.0, 1.0, 0.0));
+    assert!((tip - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-5);
+
+    //Invalid hierarchies are rejected
+    let cyclic = Joint {
+        name: String::new(),
+        parent: Some(0),
+        inverse_bind_matrix: Mat4x4::identity(),
+        rest_pose: JointPose::default(),
+    };
+    let mut skeleton = super::Skeleton::new(vec![cyclic], Vec::new());
+    skeleton.set_id(1).unwrap();
+    assert!(skeleton.initialize().is_err());
+}
+
+#[test]
+fn test_blend_modes() {
+    use super::{BlendMode, materials::Unlit};
+
+    assert!(!BlendMode::Opaque.is_transparent());
+    assert!(!BlendMode::Cutout(0.5).is_transparent());
+    assert!(BlendMode::Alpha.is_transparent());
+    assert!(BlendMode::Additive.is_transparent());
+    assert!(BlendMode::Premultiplied.is_transparent());
+
+    //Cutout is rendered with the opaque meshes, but discards pixels
+    assert!(BlendMode::Cutout(0.5).depth_write());
+    assert!(BlendMode::Cutout(0.5).blend_state().is_none());
+    assert!((BlendMode::Cutout(0.5).alpha_cutoff() - 0.5).abs() < f32::EPSILON);
+    assert!(BlendMode::Alpha.alpha_cutoff().abs() < f32::EPSILON);
+    assert!(!BlendMode::Additive.depth_write());
+
+    let material = Unlit::new(None, None);
+    assert_eq!(material.get_blend_mode(), BlendMode::Opaque);
+    let material = material.with_blend_mode(BlendMode::Premultiplied);
+    assert_eq!(material.get_blend_mode(), BlendMode::Premultiplied);
+    assert!(material.is_transparent());
+}
+
+#[test]
+fn test_texture_atlas() {
+    use super::atlas::{AtlasRegion, frames, grid_regions, pack_rects};
+
+    //Packed rectangles fit in the atlas and don't overlap, including the padding
+    let sizes = [(30, 10), (8, 40), (16, 16), (50, 5), (3, 3), (20, 22)];
+    let ((width, height), positions) = pack_rects(&sizes, 2);
+    assert!(width.is_power_of_two());
+    let rects = sizes
+        .iter()
+        .zip(&positions)
+        .map(|((w, h), (x, y))| (*x, *y, x + w + 2, y + h + 2))
+        .collect::<Vec<_>>();
+    for (i, a) in rects.iter().enumerate() {
+        assert!(a.2 <= width && a.3 <= height);
+        for b in &rects[i + 1..] {
+            assert!(a.2 <= b.0 || b.2 <= a.0 || a.3 <= b.1 || b.3 <= a.1);
+        }
+    }
+
+    //Both json layouts result in the same regions
+    let hash = r#"{"frames": {
+        "walk_10": {"frame": {"x": 0, "y": 0, "w": 16, "h": 32}, "rotated": false},
+        "walk_2": {"frame": {"x": 16, "y": 0, "w": 16, "h": 32}}
+    }, "meta": {"size": {"w": 64, "h": 32}}}"#;
+    let array = r#"{"frames": [
+        {"filename": "walk_10", "frame": {"x": 0, "y": 0, "w": 16, "h": 32}},
+        {"filename": "walk_2", "frame": {"x": 16, "y": 0, "w": 16, "h": 32}}
+    ]}"#;
+    let regions = crate::import::atlas::parse(hash).unwrap();
+    assert_eq!(regions, crate::import::atlas::parse(array).unwrap());
+    assert_eq!(
+        regions[1],
+        AtlasRegion {
+            name: "walk_2".to_owned(),
+            x: 16,
+            y: 0,
+            width: 16,
+            height: 32,
+        }
+    );
+    assert!(crate::import::atlas::parse(r#"{"frames": {"a": {"frame": {"x": 0}}}}"#).is_err());
+    assert!(
+        crate::import::atlas::parse(
+            r#"{"frames": {"a": {"frame": {"x": 0, "y": 0, "w": 1, "h": 1}, "rotated": true}}}"#
+        )
+        .is_err()
+    );
+
+    //Frames are sorted numerically
+    assert_eq!(frames(&regions, "walk"), vec![1, 0]);
+
+    //Textures are stored from bottom to top
+    assert_eq!(regions[1].uv_rect((64, 32)), [0.25, 0.0, 0.5, 1.0]);
+
+    let grid = grid_regions((64, 32), 4, 2);
+    assert_eq!(grid.len(), 8);
+    assert_eq!(
+        (grid[5].x, grid[5].y, grid[5].width, grid[5].height),
+        (16, 16, 16, 16)
+    );
+    assert_eq!(grid[5].name, "5");
+
+    //Regions reaching past the image are rejected, even if their end does not fit into an u32
+    const PIXEL: &[u8] = &[
+        b'q', b'o', b'i', b'f', 0, 0, 0, 1, 0, 0, 0, 1, 4, 0, 0xFF, 255, 0, 0, 255, 0, 0, 0, 0, 0,
+        0, 0, 1,
+    ];
+    for frame in [
+        r#"{"x": 0, "y": 0, "w": 2, "h": 1}"#,
+        r#"{"x": 4294967295, "y": 0, "w": 1, "h": 1}"#,
+        r#"{"x": 0, "y": 1, "w": 1, "h": 4294967295}"#,
+    ] {
+        let json = format!(r#"{{"frames": {{"a": {{"frame": {frame}}}}}}}"#);
+        let mut atlas = super::TextureAtlas::static_json(
+            Box::leak(json.into_boxed_str()),
+            PIXEL,
+            super::ImageFormat::Qoi,
+        );
+        assert!(atlas.initialize().is_err());
+    }
+}
@@ -5,9 +5,17 @@ use std::{
 
 use wgpu::util::DeviceExt;
 
-use crate::{UUID, asset_managment::Asset, helpers::flip_texture};
+use crate::{
+    UUID,
+    asset_managment::{Asset, pack::PackFile},
+    helpers::flip_texture,
+    import::compressed::CompressedImage,
+};
+
+use lunar_png::{Image, ImageType};
 
-use lunar_png::Image;
+///Cubemap helpers
+mod cubemap;
 
 ///Stores texture data
 #[allow(clippy::struct_field_names)]
@@ -16,11 +24,15 @@ pub struct Texture {
     initialized: bool,
     image_format: ImageFormat,
     filepath: Option<PathBuf>,
+    pack_file: Option<PackFile>,
     r#static: Static,
+    layout: Layout,
     mip_count: u8,
     sample_count: u8,
     adress_mode: wgpu::AddressMode,
     filter: wgpu::FilterMode,
+    anisotropy: u16,
+    color_space: ColorSpace,
     pub(crate) sampler: Option<wgpu::Sampler>,
     pub(crate) texture: Option<wgpu::Texture>,
 }
@@ -34,103 +46,590 @@ enum Static {
     No,
 }
 
+///Shape of the texture
+enum Layout {
+    ///A regular 2d texture
+    Flat,
+    ///A cubemap made out of 6 images, in the +X, -X, +Y, -Y, +Z, -Z order
+    Faces(Vec<FaceSource>),
+    ///A cubemap converted from a single equirectangular image, contains the size of the faces
+    Equirectangular(u32),
+    ///A 2d texture that cameras render into, contains its width and height
+    RenderTarget(u32, u32),
+}
+
+///Source of a single cubemap face
+enum FaceSource {
+    File(PathBuf),
+    Static(&'static [u8]),
+}
+
 ///Supported image formats for the asset
-enum ImageFormat {
-    ///.bmp bitmap(only 32 bpp without compression)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    ///.bmp bitmap
     Bmp,
     ///.png image
     Png,
+    ///.jpg image, only baseline encoding is supported
+    Jpeg,
+    ///.tga image
+    Tga,
+    ///.qoi image
+    Qoi,
+    ///.hdr radiance image, loaded as a 16 bit float texture
+    Hdr,
+    ///.ktx2 container, loaded with its own format and mips
+    Ktx2,
+    ///.dds container, loaded with its own format and mips
+    Dds,
+}
+
+///Color space of the texture data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    ///The data is used as is, should be used for non color data like normal maps
+    #[default]
+    Linear,
+    ///The data is srgb encoded and is converted into linear when sampled, should be used for
+    ///color textures
+    Srgb,
+}
+
+impl ImageFormat {
+    ///Guesses the format from a file extension, ignoring its case
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "bmp" => Some(Self::Bmp),
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "tga" => Some(Self::Tga),
+            "qoi" => Some(Self::Qoi),
+            "hdr" => Some(Self::Hdr),
+            "ktx2" => Some(Self::Ktx2),
+            "dds" => Some(Self::Dds),
+            _ => None,
+        }
+    }
+
+    ///Returns whether the format is a container of gpu texture data
+    const fn is_container(self) -> bool {
+        matches!(self, Self::Ktx2 | Self::Dds)
+    }
+
+    ///Format of the gpu texture the image is loaded into, hdr images are always linear
+    const fn texture_format(self, image: &Image, color_space: ColorSpace) -> wgpu::TextureFormat {
+        match (self, color_space) {
+            (Self::Hdr, _) => wgpu::TextureFormat::Rgba16Float,
+            (Self::Ktx2 | Self::Dds, _) if matches!(image.img_type, ImageType::Rgba16) => {
+                wgpu::TextureFormat::Rgba16Float
+            }
+            (_, ColorSpace::Linear) => wgpu::TextureFormat::Rgba8Unorm,
+            (_, ColorSpace::Srgb) => wgpu::TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+
+    ///Parses a ktx2 or dds container
+    fn parse_container(
+        self,
+        data: &[u8],
+    ) -> Result<CompressedImage, Box<dyn std::error::Error + Send>> {
+        match self {
+            Self::Ktx2 => crate::import::ktx2::parse(data),
+            Self::Dds => crate::import::dds::parse(data),
+            _ => Err(crate::import::invalid("Not a texture container")),
+        }
+    }
+
+    ///Decodes the image, rows are stored from bottom to top
+    pub(crate) fn decode(self, data: Vec<u8>) -> Result<Image, Box<dyn std::error::Error + Send>> {
+        let mut image = match self {
+            //Already stored from bottom to top
+            Self::Bmp => return crate::import::bmp::parse(&data),
+            //Stored in the same order as in the file
+            Self::Ktx2 | Self::Dds => return Ok(self.parse_container(&data)?.to_image()),
+            Self::Png => match lunar_png::decode_png(&mut data.into_iter()) {
+                Ok(mut img) => {
+                    img.add_alpha();
+                    img.add_channels();
+
+                    img
+                }
+                Err(err) => return Err(Box::new(err)),
+            },
+            Self::Jpeg => crate::import::jpeg::parse(&data)?,
+            Self::Tga => crate::import::tga::parse(&data)?,
+            Self::Qoi => crate::import::qoi::parse(&data)?,
+            Self::Hdr => crate::import::hdr::parse(&data)?.to_rgba16_float(),
+        };
+        flip_texture(&mut image);
+        Ok(image)
+    }
 }
 
 #[allow(unused_variables)]
 impl Texture {
-    ///Initializes a texture to load a bmp file in runtime
+    ///Initializes a texture to load an image file of the given format in runtime
     ///
     ///Currently unsupported on the web target
-    ///
     #[must_use]
-    pub fn new_bmp(path: &Path) -> Self {
+    pub fn new(path: &Path, format: ImageFormat) -> Self {
         Self {
             id: None,
             initialized: false,
-            image_format: ImageFormat::Bmp,
+            image_format: format,
             filepath: Some(path.to_owned()),
+            pack_file: None,
             r#static: Static::No,
+            layout: Layout::Flat,
             mip_count: 1,
             sample_count: 1,
             adress_mode: wgpu::AddressMode::ClampToEdge,
             filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            color_space: ColorSpace::Linear,
             sampler: None,
             texture: None,
         }
     }
 
-    ///Initializes a texture to parse the texture in runtime, but being loaded at comp time
+    ///Initializes a texture to parse the image in runtime, but being loaded at comp time
     ///
     ///Is only supposed to be used for small textures that are always needed
     #[must_use]
-    pub fn static_bmp(data: &'static [u8]) -> Self {
+    pub fn from_static(data: &'static [u8], format: ImageFormat) -> Self {
+        Self::from_bytes(data.to_vec(), format)
+    }
+
+    ///Initializes a texture to parse the image from bytes that are already in memory, for
+    ///example downloaded or generated in runtime
+    #[must_use]
+    pub const fn from_bytes(data: Vec<u8>, format: ImageFormat) -> Self {
         Self {
             id: None,
             initialized: false,
-            image_format: ImageFormat::Bmp,
+            image_format: format,
             filepath: None,
-            r#static: Static::Yes(data.to_vec(), None),
+            pack_file: None,
+            r#static: Static::Yes(data, None),
+            layout: Layout::Flat,
             mip_count: 1,
             sample_count: 1,
             adress_mode: wgpu::AddressMode::ClampToEdge,
             filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            color_space: ColorSpace::Linear,
             sampler: None,
             texture: None,
         }
     }
 
-    ///Initializes a texture to load a png file in runtime
-    ///
-    ///Currently unsupported on the web target
+    ///Initializes a texture to load an image of the given format out of a mounted asset pack in
+    ///runtime
     ///
+    ///See [`AssetStore::pack_file`](crate::asset_managment::AssetStore::pack_file)
     #[must_use]
-    pub fn new_png(path: &Path) -> Self {
+    pub const fn new_packed(file: PackFile, format: ImageFormat) -> Self {
         Self {
             id: None,
             initialized: false,
-            image_format: ImageFormat::Png,
-            filepath: Some(path.to_owned()),
+            image_format: format,
+            filepath: None,
+            pack_file: Some(file),
             r#static: Static::No,
+            layout: Layout::Flat,
             mip_count: 1,
             sample_count: 1,
             adress_mode: wgpu::AddressMode::ClampToEdge,
             filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            color_space: ColorSpace::Linear,
             sampler: None,
             texture: None,
         }
     }
 
+    ///Initializes a texture to load a bmp file in runtime
+    ///
+    ///Currently unsupported on the web target
+    ///
+    #[must_use]
+    pub fn new_bmp(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Bmp)
+    }
+
+    ///Initializes a texture to parse the texture in runtime, but being loaded at comp time
+    ///
+    ///Is only supposed to be used for small textures that are always needed
+    #[must_use]
+    pub fn static_bmp(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Bmp)
+    }
+
+    ///Initializes a texture to load a png file in runtime
+    ///
+    ///Currently unsupported on the web target
+    ///
+    #[must_use]
+    pub fn new_png(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Png)
+    }
+
     ///Initializes a texture to parse the texture in runtime, but being loaded at comp time
     ///
     ///Is only supposed to be used for small textures that are always needed
     #[must_use]
     pub fn static_png(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Png)
+    }
+
+    ///Initializes a texture to load a jpeg file in runtime
+    ///
+    ///Currently unsupported on the web target
+    #[must_use]
+    pub fn new_jpeg(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Jpeg)
+    }
+
+    ///Initializes a texture to parse a jpeg in runtime, but being loaded at comp time
+    #[must_use]
+    pub fn static_jpeg(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Jpeg)
+    }
+
+    ///Initializes a texture to load a tga file in runtime
+    ///
+    ///Currently unsupported on the web target
+    #[must_use]
+    pub fn new_tga(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Tga)
+    }
+
+    ///Initializes a texture to parse a tga in runtime, but being loaded at comp time
+    #[must_use]
+    pub fn static_tga(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Tga)
+    }
+
+    ///Initializes a texture to load a qoi file in runtime
+    ///
+    ///Currently unsupported on the web target
+    #[must_use]
+    pub fn new_qoi(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Qoi)
+    }
+
+    ///Initializes a texture to parse a qoi in runtime, but being loaded at comp time
+    #[must_use]
+    pub fn static_qoi(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Qoi)
+    }
+
+    ///Initializes a texture to load a radiance hdr file in runtime
+    ///
+    ///The texture uses [`wgpu::TextureFormat::Rgba16Float`]
+    #[must_use]
+    pub fn new_hdr(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Hdr)
+    }
+
+    ///Initializes a texture to parse a radiance hdr in runtime, but being loaded at comp time
+    ///
+    ///The texture uses [`wgpu::TextureFormat::Rgba16Float`]
+    #[must_use]
+    pub fn static_hdr(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Hdr)
+    }
+
+    ///Initializes a texture to load a ktx2 file in runtime, keeping its format and mips
+    ///
+    ///Block compressed formats the device doesn't support are decompressed on the cpu. Rows are
+    ///used in the order they are stored in, so they should go from bottom to top, for example
+    ///using `toktx --lower_left_maps_to_s0t0`. A single file cubemap is loaded as a cubemap.
+    ///Currently unsupported on the web target
+    #[must_use]
+    pub fn new_ktx2(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Ktx2)
+    }
+
+    ///Initializes a texture to parse a ktx2 in runtime, but being loaded at comp time
+    ///
+    ///See [`Texture::new_ktx2`]
+    #[must_use]
+    pub fn static_ktx2(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Ktx2)
+    }
+
+    ///Initializes a texture to load a dds file in runtime, keeping its format and mips
+    ///
+    ///Block compressed formats the device doesn't support are decompressed on the cpu. Rows are
+    ///used in the order they are stored in, so they should go from bottom to top, for example
+    ///using `texconv -vflip`. A single file cubemap is loaded as a cubemap. Currently unsupported
+    ///on the web target
+    #[must_use]
+    pub fn new_dds(path: &Path) -> Self {
+        Self::new(path, ImageFormat::Dds)
+    }
+
+    ///Initializes a texture to parse a dds in runtime, but being loaded at comp time
+    ///
+    ///See [`Texture::new_dds`]
+    #[must_use]
+    pub fn static_dds(data: &'static [u8]) -> Self {
+        Self::from_static(data, ImageFormat::Dds)
+    }
+
+    ///Initializes a texture to load a bmp file out of a mounted asset pack in runtime
+    ///
+    ///See [`AssetStore::pack_file`](crate::asset_managment::AssetStore::pack_file)
+    #[must_use]
+    pub const fn new_packed_bmp(file: PackFile) -> Self {
+        Self::new_packed(file, ImageFormat::Bmp)
+    }
+
+    ///Initializes a texture to load a png file out of a mounted asset pack in runtime
+    ///
+    ///See [`AssetStore::pack_file`](crate::asset_managment::AssetStore::pack_file)
+    #[must_use]
+    pub const fn new_packed_png(file: PackFile) -> Self {
+        Self::new_packed(file, ImageFormat::Png)
+    }
+
+    ///Initializes a cubemap texture to load 6 image files in runtime, one for each face in the
+    ///+X, -X, +Y, -Y, +Z, -Z order
+    ///
+    ///The faces must be square and of the same size. Currently unsupported on the web target
+    #[must_use]
+    pub fn new_cubemap(faces: [&Path; 6], format: ImageFormat) -> Self {
         Self {
-            id: None,
-            initialized: false,
-            image_format: ImageFormat::Png,
             filepath: None,
-            r#static: Static::Yes(data.to_vec(), None),
+            layout: Layout::Faces(
+                faces
+                    .iter()
+                    .map(|f| FaceSource::File(f.to_path_buf()))
+                    .collect(),
+            ),
+            ..Self::new(faces[0], format)
+        }
+    }
+
+    ///Initializes a cubemap texture to parse 6 images in runtime, but being loaded at comp time,
+    ///one for each face in the +X, -X, +Y, -Y, +Z, -Z order
+    ///
+    ///The faces must be square and of the same size
+    #[must_use]
+    pub fn static_cubemap(faces: [&'static [u8]; 6], format: ImageFormat) -> Self {
+        Self {
+            layout: Layout::Faces(faces.into_iter().map(FaceSource::Static).collect()),
+            ..Self::from_bytes(Vec::new(), format)
+        }
+    }
+
+    ///Initializes a cubemap texture to load an equirectangular (latitude-longitude) image in
+    ///runtime, usually an hdr environment map, converting it into faces of the given size
+    ///
+    ///Currently unsupported on the web target
+    #[must_use]
+    pub fn new_equirectangular(path: &Path, format: ImageFormat, face_size: u32) -> Self {
+        Self {
+            layout: Layout::Equirectangular(face_size),
+            ..Self::new(path, format)
+        }
+    }
+
+    ///Initializes a cubemap texture to parse an equirectangular (latitude-longitude) image in
+    ///runtime, but being loaded at comp time, converting it into faces of the given size
+    #[must_use]
+    pub fn static_equirectangular(
+        data: &'static [u8],
+        format: ImageFormat,
+        face_size: u32,
+    ) -> Self {
+        Self {
+            layout: Layout::Equirectangular(face_size),
+            ..Self::from_static(data, format)
+        }
+    }
+
+    ///Initializes a texture that cameras can render into, using
+    ///[`crate::components::camera::Camera::target`]
+    ///
+    ///The texture has no mips and uses the format of the scene, see
+    ///[`crate::rendering::scene_format`]
+    #[must_use]
+    pub fn new_render_target(width: u32, height: u32) -> Self {
+        Self {
+            layout: Layout::RenderTarget(width.max(1), height.max(1)),
             mip_count: 1,
-            sample_count: 1,
-            adress_mode: wgpu::AddressMode::ClampToEdge,
-            filter: wgpu::FilterMode::Linear,
-            sampler: None,
-            texture: None,
+            ..Self::from_bytes(Vec::new(), ImageFormat::Png)
+        }
+    }
+
+    ///Initializes a texture from an already decoded image, with rows stored from bottom to top
+    pub(crate) fn from_image(image: Image) -> Self {
+        Self {
+            r#static: Static::Yes(Vec::new(), Some(Arc::new(RwLock::new(image)))),
+            ..Self::from_bytes(Vec::new(), ImageFormat::Png)
+        }
+    }
+
+    ///Initializes a 1x1 cubemap with all of the faces filled with a single rgba8 color
+    pub(crate) fn solid_cubemap(color: [u8; 4]) -> Self {
+        let image = Image {
+            img_type: lunar_png::ImageType::Rgba8,
+            width: 1,
+            height: 6,
+            data: color.repeat(6),
+        };
+        Self {
+            r#static: Static::Yes(Vec::new(), Some(Arc::new(RwLock::new(image)))),
+            layout: Layout::Faces(Vec::new()),
+            ..Self::from_bytes(Vec::new(), ImageFormat::Png)
         }
     }
 
+    ///Returns whether the texture is a cubemap
+    #[must_use]
+    pub const fn is_cubemap(&self) -> bool {
+        matches!(self.layout, Layout::Faces(_) | Layout::Equirectangular(_))
+    }
+
+    ///Returns whether cameras can render into the texture
+    #[must_use]
+    pub const fn is_render_target(&self) -> bool {
+        matches!(self.layout, Layout::RenderTarget(..))
+    }
+
+    ///Returns the width and the height of the texture, `None` if it is not initialized
+    #[must_use]
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.texture.as_ref().map(|t| (t.width(), t.height()))
+    }
+
+    ///Reads the data of a non cubemap texture or of an equirectangular cubemap
+    fn read_data(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + Send>> {
+        if let Static::Yes(d, _) = &self.r#static {
+            return Ok(d.clone());
+        }
+
+        if let Some(file) = &self.filepath {
+            match std::fs::read(file) {
+                Ok(it) => return Ok(it),
+                Err(err) => return Err(Box::new(err)),
+            }
+        }
+        if let Some(file) = &self.pack_file {
+            match file.read() {
+                Ok(it) => return Ok(it),
+                Err(err) => return Err(Box::new(err)),
+            }
+        }
+
+        Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "File not found",
+        )))
+    }
+
+    ///Reads and decodes the image, cubemaps are stored as 6 stacked faces, with rows stored from
+    ///top to bottom
+    fn decode(&self) -> Result<Image, Box<dyn std::error::Error + Send>> {
+        match &self.layout {
+            Layout::Flat => self.image_format.decode(self.read_data()?),
+            Layout::RenderTarget(..) => Err(crate::import::invalid(
+                "Render targets don't contain an image",
+            )),
+            Layout::Equirectangular(size) => {
+                let mut image = self.image_format.decode(self.read_data()?)?;
+                flip_texture(&mut image);
+                Ok(cubemap::from_equirectangular(&image, *size))
+            }
+            Layout::Faces(faces) => {
+                let mut images = Vec::new();
+                for face in faces {
+                    let data = match face {
+                        FaceSource::File(path) => match std::fs::read(path) {
+                            Ok(it) => it,
+                            Err(err) => return Err(Box::new(err)),
+                        },
+                        FaceSource::Static(data) => data.to_vec(),
+                    };
+                    let mut image = self.image_format.decode(data)?;
+                    //Cubemap faces are stored from top to bottom
+                    flip_texture(&mut image);
+                    images.push(image);
+                }
+                cubemap::stack_faces(images)
+            }
+        }
+    }
+
+    ///Loads a ktx2 or dds container into `wgpu::Texture` with all of its mips, decompressing it
+    ///if the device doesn't support its format
+    fn load_container(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let mut image = self.image_format.parse_container(&self.read_data()?)?;
+
+        if !image.is_supported(crate::DEVICE.get().unwrap().features()) {
+            log::warn!(
+                "Texture format {:?} is not supported by the device, decompressing it",
+                image.format
+            );
+            image = image.decompress();
+        }
+        if self.color_space == ColorSpace::Srgb {
+            image.format = image.format.add_srgb_suffix();
+        }
+        self.layout = if image.layers == 6 {
+            Layout::Faces(Vec::new())
+        } else {
+            Layout::Flat
+        };
+
+        self.upload(
+            image.format,
+            image.width,
+            image.height,
+            image.layers,
+            image.mip_count,
+            &image.data,
+        );
+        Ok(())
+    }
+
     /// Loads image data into `wgpu::Texture`
     fn load_into_gpu(&mut self, image: &Arc<RwLock<Image>>) {
+        let image = image.read().unwrap();
+
+        let format = self.image_format.texture_format(&image, self.color_space);
+        let layers = if self.is_cubemap() { 6 } else { 1 };
+        let (width, height) = (image.width, image.height / layers);
+        let mip_count = u32::from(self.mip_count).min(max_mip_count(width, height));
+
+        //Layers are stored one after another, each followed by its mips
+        let data = image
+            .data
+            .chunks(image.data.len() / layers as usize)
+            .flat_map(|layer| generate_mips(layer, width, height, format, mip_count))
+            .collect::<Vec<_>>();
+
+        drop(image);
+        self.upload(format, width, height, layers, mip_count, &data);
+    }
+
+    ///Creates the texture and its sampler, the data contains the layers one after another, each
+    ///followed by its mips
+    fn upload(
+        &mut self,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        layers: u32,
+        mip_count: u32,
+        data: &[u8],
+    ) {
         let device = crate::DEVICE.get().unwrap();
         let queue = crate::QUEUE.get().unwrap();
-        let image = image.read().unwrap();
 
         let var_name = &format!("{}", self.get_id());
         let label = Some(var_name.as_str());
@@ -140,63 +639,269 @@ impl Texture {
             &wgpu::TextureDescriptor {
                 label,
                 size: wgpu::Extent3d {
-                    width: image.width,
-                    height: image.height,
-                    depth_or_array_layers: 1,
+                    width,
+                    height,
+                    depth_or_array_layers: layers,
                 },
-                mip_level_count: self.mip_count.into(),
+                mip_level_count: mip_count,
                 sample_count: self.sample_count.into(),
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                format,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+                view_formats: &[format],
             },
             wgpu::util::TextureDataOrder::LayerMajor,
-            &image.data,
+            data,
         );
 
-        drop(image);
+        self.sampler = Some(self.create_sampler());
+        self.texture = Some(texture);
+    }
+
+    ///Creates the texture of a render target
+    fn create_render_target(&mut self, width: u32, height: u32) {
+        let device = crate::DEVICE.get().unwrap();
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label,
-            address_mode_u: self.adress_mode,
-            address_mode_v: self.adress_mode,
-            address_mode_w: self.adress_mode,
-            mag_filter: self.filter,
-            min_filter: self.filter,
-            mipmap_filter: self.filter,
-            lod_min_clamp: 1.0,
-            lod_max_clamp: 1.0,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
+        let label = format!("{}", self.get_id());
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: crate::rendering::scene_format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
         });
 
+        self.sampler = Some(self.create_sampler());
         self.texture = Some(texture);
-        self.sampler = Some(sampler);
     }
 
-    // ///Sets the mip count of the texture and generates those mips
-    // pub fn set_mip_count(&mut self, count: u8) {
-    //     todo!("Not yet implemented")
-    // }
+    ///Creates the sampler of the texture using its settings
+    fn create_sampler(&self) -> wgpu::Sampler {
+        let label = format!("{}", self.get_id());
+        crate::DEVICE
+            .get()
+            .unwrap()
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(&label),
+                address_mode_u: self.adress_mode,
+                address_mode_v: self.adress_mode,
+                address_mode_w: self.adress_mode,
+                mag_filter: self.filter,
+                min_filter: self.filter,
+                mipmap_filter: self.filter,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 32.0,
+                compare: None,
+                //Anisotropic filtering requires linear filtering
+                anisotropy_clamp: if self.filter == wgpu::FilterMode::Linear {
+                    self.anisotropy
+                } else {
+                    1
+                },
+                border_color: None,
+            })
+    }
+
+    ///Creates a view of the whole texture, including all of its mips
+    pub(crate) fn create_view(&self) -> wgpu::TextureView {
+        self.texture
+            .as_ref()
+            .unwrap()
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: self
+                    .is_cubemap()
+                    .then_some(wgpu::TextureViewDimension::Cube),
+                usage: Some(wgpu::TextureUsages::TEXTURE_BINDING),
+                ..Default::default()
+            })
+    }
+
+    ///Sets the number of mips of the texture, the mips are generated when the texture is
+    ///initialized
+    ///
+    ///The count is clamped to the number of mips the texture can have, so [`u8::MAX`] generates
+    ///the whole chain. Ktx2 and dds textures use their own mips instead. Takes effect the next
+    ///time the texture is initialized
+    pub const fn set_mip_count(&mut self, count: u8) {
+        self.mip_count = if count == 0 { 1 } else { count };
+    }
+
+    ///Returns the requested number of mips
+    #[must_use]
+    pub const fn mip_count(&self) -> u8 {
+        self.mip_count
+    }
 
     // ///
     // pub fn set_sample_count(&mut self, count: u8) {
     //     todo!("Not yet implemented")
     // }
 
-    //Sets the filter of the texture
-    // pub fn set_filter(&mut self, filter: wgpu::FilterMode) {
-    //Change the filter and recreate the sampler
-    // todo!("Not yet implemented")
-    // }
+    ///Sets the filter of the texture, used for magnification, minification and between mips
+    ///
+    ///Takes effect the next time the texture is initialized
+    pub const fn set_filter(&mut self, filter: wgpu::FilterMode) {
+        self.filter = filter;
+    }
 
-    //Sets the address of the texture
-    // pub fn set_adress_mode(&mut self, filter: wgpu::AddressMode) {
-    //Change the address mode and recreate the sampler
-    // todo!("Not yet implemented")
-    // }
+    ///Returns the filter of the texture
+    #[must_use]
+    pub const fn filter(&self) -> wgpu::FilterMode {
+        self.filter
+    }
+
+    ///Sets the address mode of the texture in all directions
+    ///
+    ///Takes effect the next time the texture is initialized
+    pub const fn set_adress_mode(&mut self, adress_mode: wgpu::AddressMode) {
+        self.adress_mode = adress_mode;
+    }
+
+    ///Returns the address mode of the texture
+    #[must_use]
+    pub const fn adress_mode(&self) -> wgpu::AddressMode {
+        self.adress_mode
+    }
+
+    ///Sets the maximum anisotropy used when sampling the texture, clamped between 1 (disabled)
+    ///and 16
+    ///
+    ///Only used with [`wgpu::FilterMode::Linear`] filtering. Takes effect the next time the
+    ///texture is initialized
+    pub fn set_anisotropy(&mut self, anisotropy: u16) {
+        self.anisotropy = anisotropy.clamp(1, 16);
+    }
+
+    ///Returns the maximum anisotropy of the texture
+    #[must_use]
+    pub const fn anisotropy(&self) -> u16 {
+        self.anisotropy
+    }
+
+    ///Sets the color space of the texture data, ignored for hdr textures
+    ///
+    ///Ktx2 and dds textures already in an srgb format stay srgb. Takes effect the next time the texture is initialized
+    pub const fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    ///Returns the color space of the texture data
+    #[must_use]
+    pub const fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+}
+
+///Maximum number of mips a texture of the given size can have
+pub(crate) const fn max_mip_count(width: u32, height: u32) -> u32 {
+    let size = if width > height { width } else { height };
+    32 - size.leading_zeros()
+}
+
+///Generates mips of the image using a box filter, returns the data of all levels one after
+///another, starting with the image itself
+///
+///Srgb data is filtered in linear space
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn generate_mips(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    count: u32,
+) -> Vec<u8> {
+    use crate::import::hdr::{f16_to_f32, f32_to_f16};
+
+    let mut out = data.to_vec();
+    if count <= 1 {
+        return out;
+    }
+
+    let half = format == wgpu::TextureFormat::Rgba16Float;
+    let channel_size = if half { 2 } else { 1 };
+    let decode = |b: &[u8]| {
+        if half {
+            f16_to_f32(u16::from_ne_bytes([b[0], b[1]]))
+        } else {
+            f32::from(b[0]) / 255.0
+        }
+    };
+    let encode = |v: f32, out: &mut Vec<u8>| {
+        if half {
+            out.extend_from_slice(&f32_to_f16(v).to_ne_bytes());
+        } else {
+            out.push((v * 255.0).round().clamp(0.0, 255.0) as u8);
+        }
+    };
+    let srgb = format.is_srgb();
+
+    //Previous level as linear floats
+    let mut level = data
+        .chunks(channel_size)
+        .enumerate()
+        .map(|(i, c)| {
+            let v = decode(c);
+            if srgb && i % 4 != 3 {
+                srgb_to_linear(v)
+            } else {
+                v
+            }
+        })
+        .collect::<Vec<_>>();
+    let (mut width, mut height) = (width as usize, height as usize);
+
+    for _ in 1..count {
+        let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut next = Vec::with_capacity(new_width * new_height * 4);
+
+        for y in 0..new_height {
+            for x in 0..new_width {
+                let (x0, y0) = ((x * 2).min(width - 1), (y * 2).min(height - 1));
+                let (x1, y1) = ((x * 2 + 1).min(width - 1), (y * 2 + 1).min(height - 1));
+                for c in 0..4 {
+                    let at = |x: usize, y: usize| level[(y * width + x) * 4 + c];
+                    next.push((at(x0, y0) + at(x1, y0) + at(x0, y1) + at(x1, y1)) / 4.0);
+                }
+            }
+        }
+
+        for (i, v) in next.iter().enumerate() {
+            if srgb && i % 4 != 3 {
+                encode(linear_to_srgb(*v), &mut out);
+            } else {
+                encode(*v, &mut out);
+            }
+        }
+        level = next;
+        (width, height) = (new_width, new_height);
+    }
+
+    out
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055f32.mul_add(v.powf(1.0 / 2.4), -0.055)
+    }
 }
 
 impl Asset for Texture {
@@ -205,42 +910,30 @@ impl Asset for Texture {
     }
 
     fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        if let Layout::RenderTarget(width, height) = self.layout {
+            self.create_render_target(width, height);
+            self.initialized = true;
+            return Ok(());
+        }
         if let Static::Yes(_, Some(img)) = &self.r#static {
             self.load_into_gpu(&img.clone());
             self.initialized = true;
             return Ok(());
         }
 
-        let image = match &self.r#static {
-            Static::Yes(d, _) => d.clone(),
-            Static::No => {
-                if let Some(file) = &self.filepath {
-                    match std::fs::read(file) {
-                        Ok(it) => it,
-                        Err(err) => return Err(Box::new(err)),
-                    }
-                } else {
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::NotFound,
-                        "File not found",
-                    )));
-                }
-            }
+        //Single file containers keep their own mips, and can contain a whole cubemap
+        let single_file = match &self.layout {
+            Layout::Flat | Layout::RenderTarget(..) => true,
+            Layout::Faces(faces) => faces.is_empty(),
+            Layout::Equirectangular(_) => false,
         };
+        if self.image_format.is_container() && single_file {
+            self.load_container()?;
+            self.initialized = true;
+            return Ok(());
+        }
 
-        let image = match self.image_format {
-            ImageFormat::Bmp => crate::import::bmp::parse(&image)?,
-            ImageFormat::Png => match lunar_png::decode_png(&mut image.into_iter()) {
-                Ok(mut img) => {
-                    flip_texture(&mut img);
-                    img.add_alpha();
-                    img.add_channels();
-
-                    img
-                }
-                Err(err) => return Err(Box::new(err)),
-            },
-        };
+        let image = self.decode()?;
 
         //This is so trash
         let image = Arc::new(RwLock::new(image));
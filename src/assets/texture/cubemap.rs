@@ -0,0 +1,169 @@
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+use std::f32::consts::PI;
+
+use lunar_png::{Image, ImageType};
+
+use crate::{
+    import::{
+        hdr::{f16_to_f32, f32_to_f16},
+        invalid,
+    },
+    math::{Vec3, Vector},
+};
+
+///Returns the size of a single channel of the image in bytes, images are always rgba
+const fn channel_size(image: &Image) -> usize {
+    if matches!(image.img_type, ImageType::Rgba16) {
+        2
+    } else {
+        1
+    }
+}
+
+///Stacks 6 square faces of the same size into a single image, one after another
+pub(super) fn stack_faces(faces: Vec<Image>) -> Result<Image, Box<dyn std::error::Error + Send>> {
+    let mut faces = faces.into_iter();
+    let mut image = faces.next().unwrap();
+
+    if image.width != image.height {
+        return Err(invalid("Cubemap faces must be square"));
+    }
+
+    for face in faces {
+        if face.width != image.width || face.height != image.height {
+            return Err(invalid("All cubemap faces must be of the same size"));
+        }
+        image.data.extend_from_slice(&face.data);
+    }
+    image.height *= 6;
+
+    Ok(image)
+}
+
+///Returns the direction a pixel of a cubemap face is pointing in, `s` and `t` are in the -1..1
+///range, `t` goes from the top of the face to the bottom
+fn face_direction(face: usize, s: f32, t: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    }
+}
+
+///Converts an equirectangular image, stored from top to bottom, into 6 stacked cubemap faces of
+///the given size
+pub(super) fn from_equirectangular(image: &Image, size: u32) -> Image {
+    let channel = channel_size(image);
+    let (width, height) = (image.width as usize, image.height as usize);
+    let read = |x: usize, y: usize, c: usize| {
+        let i = ((y * width + x) * 4 + c) * channel;
+        if channel == 2 {
+            f16_to_f32(u16::from_ne_bytes([image.data[i], image.data[i + 1]]))
+        } else {
+            f32::from(image.data[i])
+        }
+    };
+
+    let size = size.max(1) as usize;
+    let mut data = Vec::with_capacity(size * size * 6 * 4 * channel);
+
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = face_direction(face, s, t).normalized();
+
+                let u = f32::atan2(direction.x, direction.z) / (2.0 * PI) + 0.5;
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+                //Bilinear sampling, wrapping horizontally
+                let px = u.mul_add(width as f32, -0.5);
+                let py = v
+                    .mul_add(height as f32, -0.5)
+                    .clamp(0.0, (height - 1) as f32);
+                let (fx, fy) = (px - px.floor(), py - py.floor());
+                let x0 = (px.floor().rem_euclid(width as f32) as usize).min(width - 1);
+                let x1 = (x0 + 1) % width;
+                let y0 = py.floor() as usize;
+                let y1 = (y0 + 1).min(height - 1);
+
+                for c in 0..4 {
+                    let top = (read(x1, y0, c) - read(x0, y0, c)).mul_add(fx, read(x0, y0, c));
+                    let bottom = (read(x1, y1, c) - read(x0, y1, c)).mul_add(fx, read(x0, y1, c));
+                    let value = (bottom - top).mul_add(fy, top);
+
+                    if channel == 2 {
+                        data.extend_from_slice(&f32_to_f16(value).to_ne_bytes());
+                    } else {
+                        data.push(value.round().clamp(0.0, 255.0) as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    Image {
+        img_type: if channel == 2 {
+            ImageType::Rgba16
+        } else {
+            ImageType::Rgba8
+        },
+        width: size as u32,
+        height: size as u32 * 6,
+        data,
+    }
+}
+
+#[test]
+fn test_from_equirectangular() {
+    //Top half red, bottom half blue
+    let image = Image {
+        img_type: ImageType::Rgba8,
+        width: 8,
+        height: 4,
+        data: (0..32)
+            .flat_map(|i| {
+                if i < 16 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                }
+            })
+            .collect(),
+    };
+
+    let cube = from_equirectangular(&image, 4);
+    assert_eq!((cube.width, cube.height), (4, 24));
+    let face = |f: usize| &cube.data[f * 64..(f + 1) * 64];
+    //+Y is red, -Y is blue
+    assert!(face(2).chunks(4).all(|p| p == [255, 0, 0, 255]));
+    assert!(face(3).chunks(4).all(|p| p == [0, 0, 255, 255]));
+    //Side faces are red on the top and blue on the bottom
+    assert_eq!(&face(4)[..4], &[255, 0, 0, 255]);
+    assert_eq!(&face(4)[60..], &[0, 0, 255, 255]);
+
+    let square = |size: u32| Image {
+        img_type: ImageType::Rgba8,
+        width: size,
+        height: size,
+        data: vec![0; (size * size * 4) as usize],
+    };
+    let stacked = stack_faces((0..6).map(|_| square(2)).collect()).unwrap();
+    assert_eq!(
+        (stacked.width, stacked.height, stacked.data.len()),
+        (2, 12, 96)
+    );
+
+    let mut faces = (0..5).map(|_| square(2)).collect::<Vec<_>>();
+    faces.push(square(3));
+    assert!(stack_faces(faces).is_err());
+    assert!(stack_faces(vec![image]).is_err());
+}
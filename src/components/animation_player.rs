@@ -0,0 +1,302 @@
+use std::cell::OnceCell;
+
+use lunar_engine_derive::dependencies;
+
+use crate as lunar_engine;
+use crate::{
+    asset_managment::AssetReference,
+    assets::{AnimationClip, Material, animation::PropertyTrack},
+    delta_time,
+    ecs::{Component, ComponentReference},
+};
+
+use super::{
+    light::{DirectionalLight, PointLight},
+    transform::Transform,
+};
+
+///Plays an [`AnimationClip`] on the entity, animating its transform, lights and material
+///
+///The transform is required, lights are optional and must be added to the entity before the
+///player. The animated material is set using [`AnimationPlayer::set_material`]
+pub struct AnimationPlayer {
+    clip: Option<AssetReference<AnimationClip>>,
+    material: Option<AssetReference<Material>>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    playing: bool,
+    ///Whether events at the current time were not fired yet
+    at_start: bool,
+    fired_events: Vec<String>,
+    transform: OnceCell<ComponentReference<Transform>>,
+    directional_light: OnceCell<ComponentReference<DirectionalLight>>,
+    point_light: OnceCell<ComponentReference<PointLight>>,
+}
+
+impl std::fmt::Debug for AnimationPlayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnimationPlayer")
+            .field("time", &self.time)
+            .field("speed", &self.speed)
+            .field("looping", &self.looping)
+            .field("playing", &self.playing)
+            .field("fired_events", &self.fired_events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Component for AnimationPlayer {
+    #[dependencies(Transform)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            clip: None,
+            material: None,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: false,
+            at_start: true,
+            fired_events: Vec::new(),
+            transform: OnceCell::new(),
+            directional_light: OnceCell::new(),
+            point_light: OnceCell::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.advance(delta_time());
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        self.transform
+            .set(reference.get_component().unwrap())
+            .unwrap();
+        if let Ok(light) = reference.get_component() {
+            self.directional_light.set(light).unwrap();
+        }
+        if let Ok(light) = reference.get_component() {
+            self.point_light.set(light).unwrap();
+        }
+    }
+}
+
+impl AnimationPlayer {
+    ///Creates a new player for the clip, the clip must be initialized before the player is
+    ///updated
+    #[must_use]
+    pub fn new(clip: AssetReference<AnimationClip>) -> Self {
+        let mut player = Self::mew();
+        player.set_clip(clip);
+        player
+    }
+
+    ///Changes the played clip, stops the playback
+    pub fn set_clip(&mut self, clip: AssetReference<AnimationClip>) {
+        self.clip = Some(clip);
+        self.stop();
+    }
+
+    ///Sets the material animated by [`PropertyTrack::MaterialColor`] tracks
+    pub fn set_material(&mut self, material: AssetReference<Material>) {
+        self.material = Some(material);
+    }
+
+    ///Starts playing the clip from the beginning
+    pub fn play(&mut self) {
+        self.time = if self.speed < 0.0 {
+            self.clip
+                .as_ref()
+                .map_or(0.0, |c| c.borrow().get_duration())
+        } else {
+            0.0
+        };
+        self.at_start = true;
+        self.playing = true;
+    }
+
+    ///Pauses the playback, keeping the current time
+    pub const fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    ///Continues the playback from the current time
+    pub const fn resume(&mut self) {
+        self.playing = true;
+    }
+
+    ///Stops the playback and rewinds the clip to the beginning, animated properties keep their
+    ///current values
+    pub const fn stop(&mut self) {
+        self.playing = false;
+        self.time = 0.0;
+        self.at_start = true;
+    }
+
+    ///Returns whether the clip is playing
+    #[must_use]
+    pub const fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    ///Returns the current time in seconds
+    #[must_use]
+    pub const fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    ///Jumps to the given time in seconds and applies the clip at that time, without firing any
+    ///events
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+        self.at_start = false;
+        self.apply();
+    }
+
+    ///Sets the playback speed multiplier, negative speeds play the clip backwards
+    pub const fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    ///Returns the playback speed multiplier
+    #[must_use]
+    pub const fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    ///Sets whether the clip loops, if it does not the playback stops at the end of the clip
+    pub const fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    ///Returns whether the clip loops
+    #[must_use]
+    pub const fn get_looping(&self) -> bool {
+        self.looping
+    }
+
+    ///Returns the names of the events that were reached since the last call, in the order they
+    ///were reached
+    pub fn take_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.fired_events)
+    }
+
+    ///Advances the playback by `delta` seconds, firing the events that were passed, and applies
+    ///the clip to the components
+    ///
+    ///Called automatically every frame
+    pub fn advance(&mut self, delta: f32) {
+        let Some(clip) = &self.clip else {
+            return;
+        };
+        if !self.playing {
+            return;
+        }
+        let clip = clip.borrow();
+        let duration = clip.get_duration();
+        let events = clip.get_events();
+
+        let step = delta * self.speed;
+        //Fires the events between `from` and `to`, `from` is only included at the start of the
+        //playback or after wrapping around
+        let mut fire = |from: f32, to: f32, inclusive: bool| {
+            let passed = |t: f32| {
+                if from <= to {
+                    (if inclusive { from <= t } else { from < t }) && t <= to
+                } else {
+                    to <= t && (if inclusive { t <= from } else { t < from })
+                }
+            };
+            let reached = events
+                .iter()
+                .filter(|e| passed(e.time))
+                .map(|e| e.name.clone());
+            if from <= to {
+                self.fired_events.extend(reached);
+            } else {
+                self.fired_events.extend(reached.rev());
+            }
+        };
+
+        let mut time = self.time + step;
+        let mut start = self.time;
+        let mut inclusive = self.at_start;
+        self.at_start = false;
+
+        if self.looping && duration > 0.0 {
+            //Skip whole loops, so that events are not fired more than once per frame
+            if step.abs() > duration {
+                time = start + step % duration;
+            }
+            //Fire the events of the loop that was completed
+            while time > duration || time < 0.0 {
+                if time > duration {
+                    fire(start, duration, inclusive);
+                    start = 0.0;
+                    time -= duration;
+                } else {
+                    fire(start, 0.0, inclusive);
+                    start = duration;
+                    time += duration;
+                }
+                inclusive = true;
+            }
+            fire(start, time, inclusive);
+        } else {
+            time = time.clamp(0.0, duration);
+            fire(start, time, inclusive);
+            if (step >= 0.0 && time >= duration) || (step < 0.0 && time <= 0.0) {
+                self.playing = false;
+            }
+        }
+
+        drop(clip);
+        self.time = time;
+        self.apply();
+    }
+
+    ///Writes the values of the tracks at the current time into the animated components
+    fn apply(&self) {
+        let Some(clip) = &self.clip else {
+            return;
+        };
+        let clip = clip.borrow();
+        let time = self.time;
+
+        for track in clip.get_tracks() {
+            match track {
+                PropertyTrack::Position(t) => {
+                    self.transform.get().unwrap().borrow_mut().position = t.sample(time);
+                }
+                PropertyTrack::Rotation(t) => {
+                    self.transform.get().unwrap().borrow_mut().rotation = t.sample(time);
+                }
+                PropertyTrack::Scale(t) => {
+                    self.transform.get().unwrap().borrow_mut().scale = t.sample(time);
+                }
+                PropertyTrack::LightColor(t) => {
+                    if let Some(light) = self.directional_light.get() {
+                        light.borrow_mut().color = t.sample(time);
+                    } else if let Some(light) = self.point_light.get() {
+                        light.borrow_mut().set_color(t.sample(time));
+                    }
+                }
+                PropertyTrack::LightIntensity(t) => {
+                    if let Some(light) = self.directional_light.get() {
+                        light.borrow_mut().intensity = t.sample(time);
+                    } else if let Some(light) = self.point_light.get() {
+                        light.borrow_mut().set_intensity(t.sample(time));
+                    }
+                }
+                PropertyTrack::MaterialColor(t) => {
+                    if let Some(material) = &self.material {
+                        material.borrow_mut().set_color(t.sample(time));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::cell::OnceCell;
+
+use lunar_engine_derive::dependencies;
+
+use crate as lunar_engine;
+
+use crate::{
+    asset_managment::AssetReference,
+    assets::{
+        Skeleton,
+        skeleton::{JointPose, MAX_JOINTS},
+    },
+    delta_time,
+    ecs::{Component, ComponentReference},
+    math::Mat4x4,
+};
+
+use super::mesh::Mesh;
+
+///A clip that is being played by an animator
+#[derive(Debug, Clone, Copy)]
+struct Layer {
+    clip: usize,
+    time: f32,
+    weight: f32,
+    ///Change of the weight per second
+    fade_rate: f32,
+}
+
+///Gpu resources used for rendering the skinned mesh
+pub(crate) struct AnimatorGpu {
+    ///Skinning matrices of the joints
+    pub joint_buffer: wgpu::Buffer,
+    ///Transformation matrix of the mesh
+    pub instance_buffer: wgpu::Buffer,
+    ///Bind groups containing the camera and the joint matrices, one for every camera buffer
+    pub bind_groups: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+impl AnimatorGpu {
+    ///Returns the bind group created for the camera buffer
+    ///
+    ///# Panics
+    ///Panics if no bind group was created for the camera
+    pub fn bind_group(&self, camera: &wgpu::Buffer) -> &wgpu::BindGroup {
+        &self
+            .bind_groups
+            .iter()
+            .find(|(buffer, _)| buffer == camera)
+            .expect("Bind group was not created for the camera")
+            .1
+    }
+}
+
+///Animates a skinned [`Mesh`] on the same entity by playing and blending clips of a [`Skeleton`]
+pub struct Animator {
+    skeleton: Option<AssetReference<Skeleton>>,
+    layers: Vec<Layer>,
+    speed: f32,
+    looping: bool,
+    pose: Vec<JointPose>,
+    joint_matrices: Vec<Mat4x4>,
+    mesh_reference: OnceCell<ComponentReference<Mesh>>,
+    pub(crate) gpu: Option<AnimatorGpu>,
+}
+
+impl std::fmt::Debug for Animator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Animator")
+            .field("layers", &self.layers)
+            .field("speed", &self.speed)
+            .field("looping", &self.looping)
+            .field("pose", &self.pose)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Component for Animator {
+    #[dependencies(Mesh)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            skeleton: None,
+            layers: Vec::new(),
+            speed: 1.0,
+            looping: true,
+            pose: Vec::new(),
+            joint_matrices: Vec::new(),
+            mesh_reference: OnceCell::new(),
+            gpu: None,
+        }
+    }
+
+    fn update(&mut self) {
+        self.advance(delta_time());
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        let mesh: ComponentReference<Mesh> = reference.get_component().unwrap();
+        mesh.borrow_mut().set_animated(true);
+        self.mesh_reference.set(mesh).unwrap();
+    }
+
+    fn decatification(&mut self) {
+        if let Some(mesh) = self.mesh_reference.get() {
+            mesh.borrow_mut().set_animated(false);
+        }
+    }
+}
+
+impl Animator {
+    ///Creates a new animator that animates the given skeleton, the skeleton must be initialized
+    ///before the animator is updated
+    #[must_use]
+    pub fn new(skeleton: AssetReference<Skeleton>) -> Self {
+        let mut animator = Self::mew();
+        animator.set_skeleton(skeleton);
+        animator
+    }
+
+    ///Changes the animated skeleton, stops all the playing clips
+    pub fn set_skeleton(&mut self, skeleton: AssetReference<Skeleton>) {
+        self.skeleton = Some(skeleton);
+        self.layers.clear();
+        self.pose.clear();
+        self.joint_matrices.clear();
+    }
+
+    ///Immediately starts playing the clip with the given name from the beginning, stopping all
+    ///other clips
+    ///
+    ///Returns false if the skeleton has no such clip
+    pub fn play(&mut self, clip: &str) -> bool {
+        let Some(clip) = self.find_clip(clip) else {
+            return false;
+        };
+
+        self.layers = vec![Layer {
+            clip,
+            time: 0.0,
+            weight: 1.0,
+            fade_rate: 0.0,
+        }];
+        true
+    }
+
+    ///Starts playing the clip with the given name from the beginning, blending from the currently
+    ///playing clips to it over `duration` seconds
+    ///
+    ///Returns false if the skeleton has no such clip
+    pub fn cross_fade(&mut self, clip: &str, duration: f32) -> bool {
+        if duration <= 0.0 || self.layers.is_empty() {
+            return self.play(clip);
+        }
+        let Some(clip) = self.find_clip(clip) else {
+            return false;
+        };
+
+        let rate = 1.0 / duration;
+        for layer in &mut self.layers {
+            layer.fade_rate = -rate;
+        }
+        self.layers.push(Layer {
+            clip,
+            time: 0.0,
+            weight: 0.0,
+            fade_rate: rate,
+        });
+        true
+    }
+
+    ///Stops all clips, returning the skeleton to its rest pose
+    pub fn stop(&mut self) {
+        self.layers.clear();
+    }
+
+    ///Returns the name of the clip that is being faded to or played
+    #[must_use]
+    pub fn get_current_clip(&self) -> Option<String> {
+        let layer = self.layers.last()?;
+        let skeleton = self.skeleton.as_ref()?.borrow();
+        Some(skeleton.get_clips()[layer.clip].name.clone())
+    }
+
+    ///Sets the playback speed multiplier
+    pub const fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    ///Returns the playback speed multiplier
+    #[must_use]
+    pub const fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    ///Sets whether the clips loop, clips that do not loop hold their last frame
+    pub const fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    ///Returns whether the clips loop
+    #[must_use]
+    pub const fn get_looping(&self) -> bool {
+        self.looping
+    }
+
+    ///Returns the current pose of the joints, relative to their parents
+    #[must_use]
+    pub fn get_pose(&self) -> &[JointPose] {
+        &self.pose
+    }
+
+    ///Returns the skinning matrices of the current pose
+    #[must_use]
+    pub fn get_joint_matrices(&self) -> &[Mat4x4] {
+        &self.joint_matrices
+    }
+
+    ///Advances the playing clips by `delta` seconds, and recalculates the pose
+    ///
+    ///Called automatically every frame
+    ///
+    ///# Panics
+    ///Panics if the skeleton is not initialized
+    pub fn advance(&mut self, delta: f32) {
+        let Some(skeleton) = &self.skeleton else {
+            return;
+        };
+        let skeleton = skeleton.borrow();
+        let clips = skeleton.get_clips();
+
+        for layer in &mut self.layers {
+            let duration = clips[layer.clip].duration;
+            layer.time += delta * self.speed;
+            layer.time = if self.looping && duration > 0.0 {
+                layer.time.rem_euclid(duration)
+            } else {
+                layer.time.clamp(0.0, duration)
+            };
+            layer.weight = layer.fade_rate.mul_add(delta, layer.weight).clamp(0.0, 1.0);
+        }
+        //Remove clips that have faded out
+        self.layers.retain(|l| l.weight > 0.0 || l.fade_rate > 0.0);
+
+        let rest = skeleton.rest_pose();
+        let mut pose = rest.clone();
+        let mut total_weight = 0.0;
+        let mut clip_pose = Vec::with_capacity(rest.len());
+
+        //Blend the layers using a running weighted average
+        for layer in &self.layers {
+            if layer.weight <= 0.0 {
+                continue;
+            }
+            clip_pose.clone_from(&rest);
+            clips[layer.clip].sample(layer.time, &mut clip_pose);
+
+            total_weight += layer.weight;
+            let t = layer.weight / total_weight;
+            for (p, c) in pose.iter_mut().zip(&clip_pose) {
+                *p = p.blend(c, t);
+            }
+        }
+
+        self.joint_matrices = skeleton.skinning_matrices(&pose);
+        drop(skeleton);
+        self.pose = pose;
+    }
+
+    fn find_clip(&self, name: &str) -> Option<usize> {
+        self.skeleton.as_ref()?.borrow().clip_index(name)
+    }
+
+    ///Returns the skinning matrices transposed for uploading to the gpu, padded to
+    ///[`MAX_JOINTS`]
+    pub(crate) fn joint_data(&self) -> Vec<Mat4x4> {
+        let mut data = self
+            .joint_matrices
+            .iter()
+            .copied()
+            .map(Mat4x4::transpose)
+            .collect::<Vec<_>>();
+        data.resize(MAX_JOINTS, Mat4x4::identity());
+        data
+    }
+
+    ///Returns a reference to the mesh component
+    pub(crate) fn get_mesh(&self) -> Option<&ComponentReference<Mesh>> {
+        self.mesh_reference.get()
+    }
+}
@@ -1,17 +1,18 @@
-use std::cell::OnceCell;
+use std::cell::{Cell, OnceCell, Ref};
 use std::num::NonZeroU64;
 
 use lunar_engine_derive::{alias, dependencies};
 use wgpu::BufferUsages;
 
 use crate as lunar_engine;
-use crate::math::{Vec3, Vec4Swizzles};
+use crate::math::{Ray, Vec2, Vec3, Vec4Swizzles, Vector as _};
 
 use crate::{
-    DEVICE, RESOLUTION, STAGING_BELT,
-    ecs::{Component, ComponentReference},
+    DEVICE, RESOLUTION, STAGING_BELT, UUID,
+    ecs::{Component, ComponentReference, World},
     grimoire::{CAMERA_BIND_GROUP_INDEX, CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR},
     math::{Mat4x4, Vec4},
+    structures::Color,
 };
 
 use super::transform::Transform;
@@ -51,8 +52,90 @@ impl ProjectionType {
     }
 }
 
+///Rectangle of the render target a camera renders into, in fractions of the size of the target,
+///starting at the top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    ///Left edge of the viewport
+    pub x: f32,
+    ///Top edge of the viewport
+    pub y: f32,
+    ///Width of the viewport
+    pub width: f32,
+    ///Height of the viewport
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    ///Viewport covering the whole target
+    pub const FULL: Self = Self::new(0.0, 0.0, 1.0, 1.0);
+
+    ///Creates a new viewport
+    #[must_use]
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    ///Returns whether the viewport covers the whole target
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.x <= 0.0 && self.y <= 0.0 && self.x + self.width >= 1.0 && self.y + self.height >= 1.0
+    }
+
+    ///Returns the `(x, y, width, height)` of the viewport in pixels for a target of the given size,
+    ///clamped to the target
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    #[must_use]
+    pub fn pixels(&self, size: (u32, u32)) -> (u32, u32, u32, u32) {
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        let x = (self.x.clamp(0.0, 1.0) * width).round();
+        let y = (self.y.clamp(0.0, 1.0) * height).round();
+        let right = ((self.x + self.width).clamp(0.0, 1.0) * width).round();
+        let bottom = ((self.y + self.height).clamp(0.0, 1.0) * height).round();
+
+        (
+            x as u32,
+            y as u32,
+            (right - x).max(0.0) as u32,
+            (bottom - y).max(0.0) as u32,
+        )
+    }
+}
+
+///What is cleared before a camera renders into its viewport
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ClearMode {
+    ///The color is cleared with the clear color of the renderer, and the depth is cleared
+    #[default]
+    Default,
+    ///The color is cleared with the given color, and the depth is cleared
+    Color(Color),
+    ///Only the depth is cleared, so the camera renders on top of the cameras before it
+    DepthOnly,
+    ///Nothing is cleared
+    Nothing,
+}
+
 #[derive(Debug)]
 ///Camera used for rendering of the objects
+///
+///Any number of cameras can be rendered, every camera renders into its viewport of either the
+///screen or its target texture, in the order of their [`Camera::order`]
 pub struct Camera {
     ///Projection type of the camera
     pub projection_type: ProjectionType,
@@ -60,6 +143,22 @@ pub struct Camera {
     pub near: f32,
     ///Far plane of the camera
     pub far: f32,
+    ///Part of the target the camera renders into
+    pub viewport: Viewport,
+    ///Cameras with smaller orders are rendered first, cameras that render into textures are
+    ///rendered before the other cameras with the same order
+    pub order: i32,
+    ///What is cleared before the camera renders
+    ///
+    ///The first camera that renders into a target always clears the whole target
+    pub clear: ClearMode,
+    ///Id of the texture the camera renders into instead of the screen, the texture must be
+    ///created with [`crate::assets::Texture::new_render_target`]
+    ///
+    ///A camera must not see materials that use its own target texture
+    pub target: Option<UUID>,
+    ///Size of the target texture, `None` when rendering to the screen
+    target_size: Cell<Option<(u32, u32)>>,
     transorm_reference: OnceCell<ComponentReference<Transform>>,
     buffer: Option<wgpu::Buffer>,
     bind_group: Option<wgpu::BindGroup>,
@@ -77,6 +176,11 @@ impl Default for Camera {
             },
             near: 0.1,
             far: 100.0,
+            viewport: Viewport::FULL,
+            order: 0,
+            clear: ClearMode::Default,
+            target: None,
+            target_size: Cell::new(None),
             transorm_reference: OnceCell::new(),
             buffer: None,
             bind_group: None,
@@ -133,11 +237,7 @@ impl Camera {
         let forward = (rotation_matrix * Vec4::new(0.0, 0.0, 1.0, 1.0)).xyz() + transform.position;
 
         let camera_matrix = Mat4x4::look_at_matrix(transform.position, up, forward);
-
-        let resolution = RESOLUTION.read().unwrap();
-        let aspect = resolution.width as f32 / resolution.height as f32;
-
-        drop(resolution);
+        let aspect = self.aspect();
 
         let projection_matrix = match self.projection_type {
             ProjectionType::Perspective { fov } => {
@@ -151,6 +251,33 @@ impl Camera {
         camera_matrix * projection_matrix
     }
 
+    ///Returns the size of the target of the camera in pixels
+    #[must_use]
+    pub fn target_size(&self) -> (u32, u32) {
+        self.target_size.get().unwrap_or_else(|| {
+            let resolution = RESOLUTION.read().unwrap();
+            (resolution.width, resolution.height)
+        })
+    }
+
+    ///Returns the aspect ratio of the viewport of the camera
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn aspect(&self) -> f32 {
+        let (width, height) = self.target_size();
+        let aspect = (width as f32 * self.viewport.width) / (height as f32 * self.viewport.height);
+        if aspect.is_finite() && aspect > 0.0 {
+            aspect
+        } else {
+            1.0
+        }
+    }
+
+    ///Sets the size of the target texture, used for the aspect ratio
+    pub(crate) fn set_target_size(&self, size: Option<(u32, u32)>) {
+        self.target_size.set(size);
+    }
+
     ///Initializes gpu related components of the camera: Buffers, bindgroups, etc.
     pub(crate) fn initialize_gpu(&mut self) {
         let device = DEVICE.get().unwrap();
@@ -217,6 +344,11 @@ impl Camera {
             .copy_from_slice(bytemuck::bytes_of(&data));
     }
 
+    ///Returns the uniform buffer of the camera
+    pub(crate) const fn get_buffer(&self) -> &wgpu::Buffer {
+        self.buffer.as_ref().unwrap()
+    }
+
     ///Sets bindgroups of the camera for rendering
     pub(crate) fn set_bindgroup<'a, 'b>(&'a self, render_pass: &mut wgpu::RenderPass<'b>)
     where
@@ -229,6 +361,67 @@ impl Camera {
         );
     }
 
+    ///Returns the fraction of the screen height covered by a sphere
+    ///
+    ///Returns infinity if the camera is inside of the sphere
+    #[must_use]
+    pub fn screen_size(&self, center: Vec3, radius: f32) -> f32 {
+        match self.projection_type {
+            ProjectionType::Perspective { fov } => {
+                let position = self.transorm_reference.get().unwrap().borrow().position;
+                let distance = (center - position).length();
+                if distance <= radius {
+                    return f32::INFINITY;
+                }
+                radius / (distance * (fov / 2.0).tan())
+            }
+            ProjectionType::Orthographic { size } => 2.0 * radius / size,
+        }
+    }
+
+    ///Returns the position of the camera
+    pub(crate) fn get_position(&self) -> Vec3 {
+        self.transorm_reference.get().unwrap().borrow().position
+    }
+
+    ///Returns the distance of the point from the camera along its view direction
+    pub fn view_depth(&self, point: Vec3) -> f32 {
+        (point - self.get_position()).dot_product(&self.view_direction())
+    }
+
+    ///Returns the point of the world at the given depth, that is seen at the point of the target
+    ///in pixels, depth 0 is on the near plane and depth 1 on the far plane
+    ///
+    ///Returns `None` if the point is outside of the viewport of the camera
+    #[must_use]
+    pub fn unproject(&self, point: Vec2, depth: f32) -> Option<Vec3> {
+        let ndc = self.screen_to_ndc(point)?;
+        unproject_ndc(&self.inverse_matrix()?, ndc, depth)
+    }
+
+    ///Returns the ray going from the near plane through the point of the target in pixels, the
+    ///direction of the ray is normalized
+    ///
+    ///Returns `None` if the point is outside of the viewport of the camera
+    #[must_use]
+    pub fn screen_ray(&self, point: Vec2) -> Option<Ray> {
+        let ndc = self.screen_to_ndc(point)?;
+        let inverse = self.inverse_matrix()?;
+        ndc_ray(&inverse, ndc)
+    }
+
+    ///Converts a point of the target in pixels to normalized device coordinates
+    ///
+    ///Returns `None` if the point is outside of the viewport of the camera
+    pub(crate) fn screen_to_ndc(&self, point: Vec2) -> Option<Vec2> {
+        pixel_to_ndc(self.viewport.pixels(self.target_size()), point)
+    }
+
+    ///Returns the matrix that transforms normalized device coordinates into the world
+    pub(crate) fn inverse_matrix(&self) -> Option<Mat4x4> {
+        self.matrix().transpose().inverted()
+    }
+
     ///Returns the rotated forwrard vector of the camera
     pub fn view_direction(&self) -> Vec3 {
         let t = self.transorm_reference.get().unwrap().borrow();
@@ -240,6 +433,63 @@ impl Camera {
     }
 }
 
+///Converts a point in pixels to normalized device coordinates of the viewport given as
+///`(x, y, width, height)` in pixels
+///
+///Returns `None` if the point is outside of the viewport
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn pixel_to_ndc(viewport: (u32, u32, u32, u32), point: Vec2) -> Option<Vec2> {
+    let (x, y, width, height) = viewport;
+    let x = (point.x - x as f32) / width as f32;
+    let y = (point.y - y as f32) / height as f32;
+    ((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y))
+        .then(|| Vec2::new(x.mul_add(2.0, -1.0), y.mul_add(-2.0, 1.0)))
+}
+
+///Transforms a point in normalized device coordinates at the depth into the world using the
+///inverse of the camera matrix
+pub(crate) fn unproject_ndc(inverse: &Mat4x4, ndc: Vec2, depth: f32) -> Option<Vec3> {
+    let point = inverse.transform(Vec4::new(ndc.x, ndc.y, depth, 1.0));
+    (point.w.abs() > f32::EPSILON).then(|| point.xyz() / point.w)
+}
+
+///Returns the normalized ray going from the near plane to the far plane through the point in
+///normalized device coordinates
+pub(crate) fn ndc_ray(inverse: &Mat4x4, ndc: Vec2) -> Option<Ray> {
+    let near = unproject_ndc(inverse, ndc, 0.0)?;
+    let far = unproject_ndc(inverse, ndc, 1.0)?;
+    let direction = far - near;
+    (direction.length() > 0.0).then(|| Ray::new(near, direction.normalized()))
+}
+
 // #[derive(Debug, Default)]
 #[alias(Camera)]
 pub struct MainCamera;
+
+///References to all the cameras of a world
+pub(crate) struct Cameras {
+    cameras: Vec<ComponentReference<Camera>>,
+    main_cameras: Vec<ComponentReference<MainCamera>>,
+}
+
+impl Cameras {
+    ///Finds all the cameras and main cameras of the world
+    pub fn new(world: &World) -> Self {
+        Self {
+            cameras: world.get_all_components::<Camera>().unwrap_or_default(),
+            main_cameras: world.get_all_components::<MainCamera>().unwrap_or_default(),
+        }
+    }
+
+    ///Borrows the cameras, sorted in the order they are rendered in
+    pub fn borrow(&self) -> Vec<Ref<'_, Camera>> {
+        let mut cameras = self
+            .main_cameras
+            .iter()
+            .map(|c| Ref::map(c.borrow(), |c| &c.inner))
+            .chain(self.cameras.iter().map(ComponentReference::borrow))
+            .collect::<Vec<_>>();
+        cameras.sort_by_key(|c| (c.order, c.target.is_none()));
+        cameras
+    }
+}
@@ -21,9 +21,16 @@ impl Component for FpsRecorder {
     }
 
     fn decatification(&mut self) {
-        let avg_delta = self.delta / self.frames as f64;
-        let avg_fps = 1.0 / avg_delta as f32;
+        log::info!("Avg fps: {}", self.get_average_fps());
+    }
+}
 
-        log::info!("Avg fps: {avg_fps}");
+impl FpsRecorder {
+    ///Returns the average fps since the component was added, for example to show it with a
+    ///[`super::text::Text`]
+    #[must_use]
+    pub fn get_average_fps(&self) -> f32 {
+        let avg_delta = self.delta / self.frames as f64;
+        1.0 / avg_delta as f32
     }
 }
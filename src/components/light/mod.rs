@@ -6,8 +6,8 @@ use crate::ecs::ComponentReference;
 use crate::{
     components::transform::Transform,
     ecs::Component,
-    math::{Vec3, Vector},
-    structures::{Color, LightBuffer},
+    math::{Mat4x4, Vec3, Vec4, Vec4Swizzles, Vector},
+    structures::{Color, LightBuffer, SpotLightBuffer},
 };
 
 use lunar_engine_derive::{dependencies, unique};
@@ -144,3 +144,179 @@ impl Component for PointLight {
             .unwrap();
     }
 }
+
+#[derive(Debug)]
+///A spot light, shines in a cone along the forward direction of its transform
+pub struct SpotLight {
+    ///Color of the light
+    color: Color,
+    ///Brightness of the light
+    intensity: f32,
+    ///Range of the light
+    range: f32,
+    ///Angle between the direction and the edge of the fully lit part of the cone, in radians
+    inner_angle: f32,
+    ///Angle between the direction and the edge of the cone, in radians
+    outer_angle: f32,
+    ///Whether the light casts shadows
+    cast_shadows: bool,
+    pub(crate) transform_ref: OnceCell<ComponentReference<Transform>>,
+    pub(crate) modified: bool,
+}
+
+impl SpotLight {
+    ///Near plane of the shadow map projection
+    const SHADOW_NEAR: f32 = 0.05;
+
+    ///Creates a new spot light, the angles are in radians, measured from the direction of the
+    ///light to the edge of the cone
+    #[must_use]
+    pub const fn new(
+        color: Color,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            color,
+            intensity,
+            range,
+            inner_angle: inner_angle.min(outer_angle),
+            outer_angle,
+            cast_shadows: false,
+            transform_ref: OnceCell::new(),
+            modified: false,
+        }
+    }
+
+    ///Returns the color of the light
+    pub const fn get_color(&self) -> Color {
+        self.color
+    }
+    ///Sets the color of the light
+    pub const fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.modified = true;
+    }
+
+    ///Returns the intensity of the light
+    pub const fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+    ///Sets the intensity of the light
+    pub const fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+        self.modified = true;
+    }
+
+    ///Returns the range of the light
+    pub const fn get_range(&self) -> f32 {
+        self.range
+    }
+    ///Sets the range of the light
+    pub const fn set_range(&mut self, range: f32) {
+        self.range = range;
+        self.modified = true;
+    }
+
+    ///Returns the inner and the outer angles of the cone, in radians
+    pub const fn get_angles(&self) -> (f32, f32) {
+        (self.inner_angle, self.outer_angle)
+    }
+    ///Sets the inner and the outer angles of the cone, in radians
+    ///
+    ///The light fades out between the inner and the outer angle, the inner angle is clamped to
+    ///the outer angle
+    pub const fn set_angles(&mut self, inner_angle: f32, outer_angle: f32) {
+        self.inner_angle = inner_angle.min(outer_angle);
+        self.outer_angle = outer_angle;
+        self.modified = true;
+    }
+
+    ///Returns whether the light casts shadows
+    pub const fn get_cast_shadows(&self) -> bool {
+        self.cast_shadows
+    }
+    ///Sets whether the light casts shadows, only the first
+    ///[`MAX_SPOT_SHADOWS`](crate::rendering::extensions::MAX_SPOT_SHADOWS) lights that cast shadows
+    ///get them
+    pub const fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.cast_shadows = cast_shadows;
+        self.modified = true;
+    }
+
+    ///Returns the position and the normalized direction of the light
+    pub fn get_position_direction(&self) -> (Vec3, Vec3) {
+        let t = self.transform_ref.get().unwrap().borrow();
+        let direction = (t.rotation.matrix() * Vec4::new(0.0, 0.0, 1.0, 1.0)).xyz();
+        (t.position_global(), direction.normalize())
+    }
+
+    ///Returns the view projection matrix used for rendering the shadow map of the light
+    pub(crate) fn shadow_matrix(&self) -> Mat4x4 {
+        let t = self.transform_ref.get().unwrap().borrow();
+        let rotation = t.rotation.matrix();
+        drop(t);
+        let (position, direction) = self.get_position_direction();
+        let up = (rotation * Vec4::new(0.0, 1.0, 0.0, 1.0)).xyz();
+
+        Mat4x4::look_at_matrix(position, up, position + direction)
+            * Mat4x4::perspercive_projection(
+                (self.outer_angle * 2.0).min(3.1),
+                1.0,
+                Self::SHADOW_NEAR,
+                self.range,
+            )
+    }
+
+    ///Returns the data of the light uploaded to the gpu, `shadow_index` is the layer of the
+    ///shadow map of the light, or -1 if it does not cast shadows
+    pub(crate) fn get_light(&self, shadow_index: i32) -> SpotLightBuffer {
+        let (position, direction) = self.get_position_direction();
+        SpotLightBuffer {
+            position,
+            range: self.range,
+            direction,
+            intensity: self.intensity,
+            color: self.color.into(),
+            inner_cos: self.inner_angle.cos(),
+            outer_cos: self.outer_angle.cos(),
+            shadow_index,
+            padding: [0.0; 2],
+            shadow_matrix: if shadow_index < 0 {
+                Mat4x4::identity()
+            } else {
+                self.shadow_matrix()
+            },
+        }
+    }
+}
+
+impl Component for SpotLight {
+    #[dependencies(Transform)]
+
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            color: Color::white(),
+            intensity: 10.0,
+            range: 10.0,
+            inner_angle: std::f32::consts::FRAC_PI_8,
+            outer_angle: std::f32::consts::FRAC_PI_6,
+            cast_shadows: false,
+            transform_ref: OnceCell::new(),
+            modified: true,
+        }
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        //We can safely unwrap all of this since we can not add this component without adding
+        //transform first
+        self.transform_ref
+            .set(reference.get_component().unwrap())
+            .unwrap();
+    }
+}
@@ -18,6 +18,10 @@ pub struct Mesh {
     mesh_id: Option<UUID>,
 
     material_id: Option<UUID>,
+    ///Level of detail the mesh was last rendered with
+    lod: usize,
+    ///Whether the mesh is posed by an [`super::animator::Animator`] on the same entity
+    animated: bool,
     transform_reference: OnceCell<ComponentReference<Transform>>,
 }
 
@@ -27,6 +31,8 @@ impl Default for Mesh {
             visible: true,
             mesh_id: None,
             material_id: None,
+            lod: 0,
+            animated: false,
             transform_reference: OnceCell::new(),
         }
     }
@@ -56,6 +62,8 @@ impl Mesh {
             visible: true,
             mesh_id: Some(mesh),
             material_id: Some(material),
+            lod: 0,
+            animated: false,
             transform_reference: OnceCell::new(),
         }
     }
@@ -97,6 +105,29 @@ impl Mesh {
         self.material_id
     }
 
+    ///Returns the level of detail the mesh was last rendered with
+    #[must_use]
+    pub const fn get_lod(&self) -> usize {
+        self.lod
+    }
+
+    ///Sets the level of detail the mesh is rendered with
+    pub(crate) const fn set_lod(&mut self, lod: usize) {
+        self.lod = lod;
+    }
+
+    ///Returns whether the mesh is posed by an [`super::animator::Animator`], animated meshes are
+    ///rendered individually instead of being instanced
+    #[must_use]
+    pub const fn is_animated(&self) -> bool {
+        self.animated
+    }
+
+    ///Sets whether the mesh is posed by an animator
+    pub(crate) const fn set_animated(&mut self, value: bool) {
+        self.animated = value;
+    }
+
     ///Returns a reference to the transform component
     #[must_use]
     pub fn get_transform(&self) -> ComponentReference<Transform> {
@@ -1,4 +1,8 @@
 //!Implemented components
+///Keyframe animation of component properties
+pub mod animation_player;
+///Skeletal animation component
+pub mod animator;
 ///Camera component
 pub mod camera;
 ///Fps recording component
@@ -7,7 +11,13 @@ pub mod fps;
 pub mod light;
 ///Mesh component
 pub mod mesh;
+///Particle emitter component
+pub mod particles;
+///Sprite and sprite sheet animation components
+pub mod sprite;
 #[cfg(test)]
 mod tests;
+///Text component
+pub mod text;
 ///Transformation component
 pub mod transform;
@@ -0,0 +1,551 @@
+use std::{cell::OnceCell, f32::consts::TAU, ops::RangeInclusive};
+
+use lunar_engine_derive::dependencies;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate as lunar_engine;
+use crate::{
+    UUID,
+    assets::{
+        BlendMode,
+        animation::{Interpolation, Keyframe, Track, keyframes_valid},
+        materials::helpers::storage_buffer_available,
+    },
+    delta_time,
+    ecs::{Component, ComponentReference},
+    math::{Mat4x4, Vec3, Vec4Swizzles as _, Vector as _},
+    structures::{Color, Mesh},
+};
+
+use super::transform::Transform;
+
+///Where the particles of a [`ParticleEmitter`] are simulated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleSimulation {
+    ///The particles are simulated by a compute shader, which can handle many more particles
+    Gpu,
+    ///The particles are simulated by the component and uploaded every frame, used with the
+    ///`webgl` feature, since compute shaders are not available there
+    Cpu,
+}
+
+impl Default for ParticleSimulation {
+    fn default() -> Self {
+        if storage_buffer_available() {
+            Self::Gpu
+        } else {
+            Self::Cpu
+        }
+    }
+}
+
+///How the particles are oriented
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ParticleRenderMode {
+    ///Square quads facing the camera
+    #[default]
+    Billboard,
+    ///Quads stretched along the velocity of the particles, useful for sparks and rain
+    Stretched {
+        ///Length added to the quad per unit of speed
+        length_scale: f32,
+    },
+}
+
+///Triangles of a mesh that particles are emitted from, created from the mesh data since
+///[`crate::assets::Mesh`] assets don't keep their vertices after being uploaded
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshSurface {
+    triangles: Vec<[Vec3; 3]>,
+    ///Running sum of the triangle areas, used to pick triangles proportionally to their area
+    areas: Vec<f32>,
+}
+
+impl MeshSurface {
+    ///Creates a new surface from the triangles of the mesh
+    #[must_use]
+    pub fn new(mesh: &Mesh) -> Self {
+        let mut area = 0.0;
+        let (triangles, areas) = mesh
+            .indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| mesh.vertices.get(i as usize));
+                Some([a?.coords, b?.coords, c?.coords])
+            })
+            .map(|t| {
+                area += (t[1] - t[0]).cross(&(t[2] - t[0])).length() / 2.0;
+                (t, area)
+            })
+            .unzip();
+        Self { triangles, areas }
+    }
+
+    ///Returns the total area of the triangles
+    #[must_use]
+    pub fn area(&self) -> f32 {
+        self.areas.last().copied().unwrap_or_default()
+    }
+
+    ///Returns a random point on the surface and the normal of its triangle
+    fn sample(&self, rng: &mut impl Rng) -> Option<(Vec3, Vec3)> {
+        if self.area() <= 0.0 {
+            return None;
+        }
+        let target = rng.gen_range(0.0..self.area());
+        let index = self
+            .areas
+            .partition_point(|a| *a <= target)
+            .min(self.triangles.len() - 1);
+        let [first, second, third] = self.triangles[index];
+
+        //Uniformly distributed barycentric coordinates
+        let (along, across): (f32, f32) = (rng.r#gen(), rng.r#gen());
+        let along = along.sqrt();
+        let point =
+            first * (1.0 - along) + second * (along * (1.0 - across)) + third * (along * across);
+        Some((point, (second - first).cross(&(third - first)).normalize()))
+    }
+}
+
+///Shape of the volume or the surface the particles are emitted from, in the local space of the
+///emitter
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmissionShape {
+    ///Particles start at the origin and move in random directions
+    Point,
+    ///Particles start inside of the sphere and move away from its center
+    Sphere {
+        ///Radius of the sphere
+        radius: f32,
+    },
+    ///Particles start on a disc in the xy plane and move along the z axis, within the angle
+    Cone {
+        ///Largest angle between the z axis and the direction of the particles, in radians
+        angle: f32,
+        ///Radius of the disc
+        radius: f32,
+    },
+    ///Particles start inside of the box centered at the origin and move along the z axis
+    Box {
+        ///Size of the box along every axis
+        size: Vec3,
+    },
+    ///Particles start on the surface of the mesh and move along the normals of its triangles
+    Surface(MeshSurface),
+}
+
+///Returns a uniformly distributed random direction
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    let z: f32 = rng.gen_range(-1.0..=1.0);
+    let angle = rng.gen_range(0.0..TAU);
+    let radius = z.mul_add(-z, 1.0).sqrt();
+    Vec3::new(radius * angle.cos(), radius * angle.sin(), z)
+}
+
+impl EmissionShape {
+    ///Returns a random starting position and direction in the local space of the emitter
+    fn sample(&self, rng: &mut impl Rng) -> (Vec3, Vec3) {
+        match self {
+            Self::Sphere { radius } => {
+                let direction = random_direction(rng);
+                let distance = rng.r#gen::<f32>().cbrt() * radius;
+                (direction * distance, direction)
+            }
+            Self::Cone { angle, radius } => {
+                let distance = rng.r#gen::<f32>().sqrt() * radius;
+                let (around, around_direction) = (rng.gen_range(0.0..TAU), rng.gen_range(0.0..TAU));
+                let z = rng.gen_range(angle.cos().min(1.0)..=1.0);
+                let side = z.mul_add(-z, 1.0).sqrt();
+                (
+                    Vec3::new(distance * around.cos(), distance * around.sin(), 0.0),
+                    Vec3::new(
+                        side * around_direction.cos(),
+                        side * around_direction.sin(),
+                        z,
+                    ),
+                )
+            }
+            Self::Box { size } => {
+                let mut offset = || rng.gen_range(-0.5..=0.5);
+                (
+                    Vec3::new(size.x * offset(), size.y * offset(), size.z * offset()),
+                    Vec3::new(0, 0, 1),
+                )
+            }
+            Self::Surface(surface) => surface
+                .sample(rng)
+                .unwrap_or_else(|| (Vec3::default(), random_direction(rng))),
+            Self::Point => (Vec3::default(), random_direction(rng)),
+        }
+    }
+}
+
+///State of a simulated particle, also used by the compute shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct Particle {
+    pub position: Vec3,
+    ///Time since the particle was emitted, the particle is dead once it reaches the lifetime
+    pub age: f32,
+    pub velocity: Vec3,
+    pub lifetime: f32,
+}
+
+impl Particle {
+    const fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+///A particle ready to be drawn
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ParticleInstance {
+    pub position: Vec3,
+    pub size: f32,
+    pub color: Color,
+    ///Velocity scaled by the velocity curve, used for stretching the quad
+    pub velocity: Vec3,
+    pub padding: f32,
+}
+
+///Changes to the particles that have to be applied on the gpu
+#[derive(Debug, Default)]
+pub(crate) struct GpuUpdate {
+    ///Time the particles have to be simulated for
+    pub delta: f32,
+    ///New particles with the slot they replace
+    pub spawned: Vec<(u32, Particle)>,
+    ///Whether every particle has to be removed before adding the new ones
+    pub clear: bool,
+}
+
+///Gpu resources of a [`ParticleEmitter`]
+#[derive(Debug)]
+pub(crate) struct ParticlesGpu {
+    ///Number of particles the buffers hold
+    pub capacity: u32,
+    ///Instances drawn by the renderer
+    pub instance_buffer: wgpu::Buffer,
+    ///Particle states, the simulation parameters and the bind group of the compute shader,
+    ///only used with [`ParticleSimulation::Gpu`]
+    pub simulation: Option<(wgpu::Buffer, wgpu::Buffer, wgpu::BindGroup)>,
+    ///Alpha cutoff and stretching of the quads
+    pub render_buffer: wgpu::Buffer,
+    ///Bind group of the texture and the render parameters, with the texture it was created for
+    pub render_bind_group: Option<(Option<UUID>, wgpu::BindGroup)>,
+}
+
+///Returns the value of the track at the normalized age of a particle, or the default if the
+///track is empty or invalid
+pub(crate) fn sample_curve<T: Keyframe>(track: &Track<T>, t: f32, default: T) -> T {
+    if keyframes_valid(&track.times, track.values.len(), track.interpolation) {
+        track.sample(t)
+    } else {
+        default
+    }
+}
+
+///Emits, simulates and draws particles, using the
+///[`crate::rendering::extensions::particles::ParticleRenderer`]
+///
+///Particles are emitted from the [`EmissionShape`] placed by the transform of the entity, after
+///that they are simulated in world space, so they don't follow the emitter. The size, the color
+///and the speed of the particles change over their lifetime according to curves, which are
+///sampled at the age of the particle divided by its lifetime, so the keyframes should be between
+///0 and 1
+///
+///The particles are stored in a ring buffer of [`ParticleEmitter::max_particles`] slots, when it
+///is full the oldest particles are replaced
+pub struct ParticleEmitter {
+    ///Whether new particles are emitted every frame
+    pub emitting: bool,
+    ///Number of particles emitted per second
+    pub rate: f32,
+    ///Largest number of particles alive at the same time, changing it removes all particles
+    pub max_particles: u32,
+    ///Volume or surface the particles are emitted from
+    pub shape: EmissionShape,
+    ///Range of the lifetime of the particles in seconds
+    pub lifetime: RangeInclusive<f32>,
+    ///Range of the starting speed of the particles
+    pub speed: RangeInclusive<f32>,
+    ///Size of the particles in world units over their lifetime
+    pub size: Track<f32>,
+    ///Color of the particles over their lifetime, multiplied by the texture
+    pub color: Track<Color>,
+    ///Multiplier of the velocity of the particles over their lifetime
+    pub velocity: Track<f32>,
+    ///Acceleration applied to the particles
+    pub gravity: Vec3,
+    ///Fraction of the velocity lost per second
+    pub drag: f32,
+    ///How the quads of the particles are oriented
+    pub render_mode: ParticleRenderMode,
+    ///How the particles are blended, transparent particles are not sorted, so additive blending
+    ///usually looks best
+    pub blend_mode: BlendMode,
+    ///Id of a [`crate::assets::Texture`] drawn on the particles, without a texture the particles
+    ///are soft circles
+    pub texture: Option<UUID>,
+    ///Whether or not the particles are rendered
+    pub visible: bool,
+    simulation: ParticleSimulation,
+    ///Particles simulated on the cpu
+    particles: Vec<Particle>,
+    ///Time at which the particle in every slot dies
+    deaths: Vec<f32>,
+    time: f32,
+    next_slot: u32,
+    ///Fraction of a particle left over from the last emission
+    accumulator: f32,
+    gpu_update: GpuUpdate,
+    rng: StdRng,
+    transform: OnceCell<ComponentReference<Transform>>,
+    pub(crate) gpu: Option<ParticlesGpu>,
+}
+
+impl std::fmt::Debug for ParticleEmitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParticleEmitter")
+            .field("emitting", &self.emitting)
+            .field("rate", &self.rate)
+            .field("max_particles", &self.max_particles)
+            .field("shape", &self.shape)
+            .field("simulation", &self.simulation)
+            .field("particle_count", &self.particle_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Component for ParticleEmitter {
+    #[dependencies(Transform)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            emitting: true,
+            rate: 10.0,
+            max_particles: 1000,
+            shape: EmissionShape::Cone {
+                angle: 0.4,
+                radius: 0.0,
+            },
+            lifetime: 1.0..=2.0,
+            speed: 1.0..=2.0,
+            size: Track::from_keyframes(Interpolation::Linear, &[(0.0, 0.1)]),
+            color: Track::from_keyframes(
+                Interpolation::Linear,
+                &[
+                    (0.0, Color::white()),
+                    (
+                        1.0,
+                        Color {
+                            a: 0.0,
+                            ..Color::white()
+                        },
+                    ),
+                ],
+            ),
+            velocity: Track::from_keyframes(Interpolation::Linear, &[(0.0, 1.0)]),
+            gravity: Vec3::default(),
+            drag: 0.0,
+            render_mode: ParticleRenderMode::Billboard,
+            blend_mode: BlendMode::Additive,
+            texture: None,
+            visible: true,
+            simulation: ParticleSimulation::default(),
+            particles: Vec::new(),
+            deaths: Vec::new(),
+            time: 0.0,
+            next_slot: 0,
+            accumulator: 0.0,
+            gpu_update: GpuUpdate::default(),
+            rng: StdRng::from_entropy(),
+            transform: OnceCell::new(),
+            gpu: None,
+        }
+    }
+
+    fn update(&mut self) {
+        self.advance(delta_time());
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        self.transform
+            .set(reference.get_component().unwrap())
+            .unwrap();
+    }
+}
+
+impl ParticleEmitter {
+    ///Creates a new emitter that emits the number of particles per second from the shape
+    #[must_use]
+    pub fn new(rate: f32, shape: EmissionShape) -> Self {
+        Self {
+            rate,
+            shape,
+            ..Self::mew()
+        }
+    }
+
+    ///Returns a reference to the transform component
+    #[must_use]
+    pub fn get_transform(&self) -> ComponentReference<Transform> {
+        self.transform.get().unwrap().clone()
+    }
+
+    ///Sets where the particles are simulated, removes all particles if it changes
+    ///
+    ///Gpu simulation is used by default, unless the `webgl` feature is enabled
+    pub fn set_simulation(&mut self, simulation: ParticleSimulation) {
+        if simulation != self.simulation {
+            self.simulation = simulation;
+            self.particles.clear();
+            self.deaths.clear();
+            self.gpu_update = GpuUpdate::default();
+            self.gpu = None;
+        }
+    }
+
+    ///Returns where the particles are simulated
+    #[must_use]
+    pub const fn get_simulation(&self) -> ParticleSimulation {
+        self.simulation
+    }
+
+    ///Returns the number of particles that are alive
+    #[must_use]
+    pub fn particle_count(&self) -> usize {
+        self.deaths.iter().filter(|d| **d > self.time).count()
+    }
+
+    ///Removes all particles
+    pub fn clear(&mut self) {
+        self.particles.fill(Particle::default());
+        self.deaths.fill(f32::MIN);
+        self.next_slot = 0;
+        self.accumulator = 0.0;
+        self.gpu_update = GpuUpdate {
+            clear: true,
+            ..Default::default()
+        };
+    }
+
+    ///Emits the number of particles at once, even if the emitter is not emitting
+    pub fn burst(&mut self, count: u32) {
+        let matrix = self
+            .transform
+            .get()
+            .map_or_else(Mat4x4::identity, |t| t.borrow().matrix());
+        self.emit(count, &matrix);
+    }
+
+    ///Emits new particles and simulates the particles for `delta` seconds
+    ///
+    ///Called automatically every frame
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn advance(&mut self, delta: f32) {
+        self.allocate_slots();
+        //New particles are simulated for the whole step, like the ones that already exist
+        if self.emitting && self.rate > 0.0 {
+            self.accumulator += self.rate * delta;
+            let count = self.accumulator.floor();
+            self.accumulator -= count;
+            self.burst(count as u32);
+        }
+        self.time += delta;
+
+        match self.simulation {
+            ParticleSimulation::Cpu => self.simulate(delta),
+            ParticleSimulation::Gpu => self.gpu_update.delta += delta,
+        }
+    }
+
+    ///Removes all particles if the number of slots changed
+    fn allocate_slots(&mut self) {
+        let slots = self.max_particles as usize;
+        if self.deaths.len() != slots {
+            self.deaths = vec![f32::MIN; slots];
+            if self.simulation == ParticleSimulation::Cpu {
+                self.particles = vec![Particle::default(); slots];
+            }
+            self.clear();
+        }
+    }
+
+    fn emit(&mut self, count: u32, matrix: &Mat4x4) {
+        self.allocate_slots();
+        if self.max_particles == 0 {
+            return;
+        }
+
+        let (lifetime, speed) = (self.lifetime.clone(), self.speed.clone());
+        let range = |rng: &mut StdRng, range: RangeInclusive<f32>| {
+            if range.start() < range.end() {
+                rng.gen_range(range)
+            } else {
+                *range.start()
+            }
+        };
+        //Only the newest particles are emitted if there are more than the slots
+        for _ in 0..count.saturating_sub(self.max_particles) {
+            self.shape.sample(&mut self.rng);
+        }
+        for _ in 0..count.min(self.max_particles) {
+            let (position, direction) = self.shape.sample(&mut self.rng);
+            let direction = matrix.transform((direction, 0.0).into()).xyz().normalize();
+            let particle = Particle {
+                position: matrix.transform3(position),
+                age: 0.0,
+                velocity: direction * range(&mut self.rng, speed.clone()),
+                lifetime: range(&mut self.rng, lifetime.clone()).max(0.0),
+            };
+
+            let slot = self.next_slot;
+            self.next_slot = (slot + 1) % self.max_particles;
+            self.deaths[slot as usize] = self.time + particle.lifetime;
+            match self.simulation {
+                ParticleSimulation::Cpu => self.particles[slot as usize] = particle,
+                ParticleSimulation::Gpu => self.gpu_update.spawned.push((slot, particle)),
+            }
+        }
+    }
+
+    fn simulate(&mut self, delta: f32) {
+        let damping = (-self.drag * delta).exp();
+        for particle in self.particles.iter_mut().filter(|p| p.is_alive()) {
+            let speed = sample_curve(&self.velocity, particle.age / particle.lifetime, 1.0);
+            particle.velocity += self.gravity * delta;
+            particle.velocity *= damping;
+            particle.position += particle.velocity * (speed * delta);
+            particle.age += delta;
+        }
+    }
+
+    ///Returns the instances of the particles simulated on the cpu that are alive
+    pub(crate) fn instances(&self) -> Vec<ParticleInstance> {
+        self.particles
+            .iter()
+            .filter(|p| p.is_alive())
+            .map(|p| {
+                let t = p.age / p.lifetime;
+                ParticleInstance {
+                    position: p.position,
+                    size: sample_curve(&self.size, t, 0.1),
+                    color: sample_curve(&self.color, t, Color::white()),
+                    velocity: p.velocity * sample_curve(&self.velocity, t, 1.0),
+                    padding: 0.0,
+                }
+            })
+            .collect()
+    }
+
+    ///Returns the changes that have to be applied to the particles on the gpu since the last
+    ///call
+    pub(crate) fn take_gpu_update(&mut self) -> GpuUpdate {
+        std::mem::take(&mut self.gpu_update)
+    }
+}
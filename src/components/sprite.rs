@@ -0,0 +1,300 @@
+use std::cell::OnceCell;
+
+use lunar_engine_derive::dependencies;
+
+use crate as lunar_engine;
+use crate::{
+    UUID, delta_time,
+    ecs::{Component, ComponentReference},
+    math::{Mat4x4, Vec2},
+    structures::Color,
+};
+
+use super::transform::Transform;
+
+///Part of the texture drawn by a [`Sprite`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SpriteRegion {
+    ///The whole texture
+    #[default]
+    Full,
+    ///Region of a [`crate::assets::TextureAtlas`] with the index
+    Index(usize),
+    ///Region of a [`crate::assets::TextureAtlas`] with the name
+    Named(String),
+    ///Rectangle in pixels from the top left corner of the texture
+    Pixels {
+        ///Distance of the left edge from the left of the texture
+        x: u32,
+        ///Distance of the top edge from the top of the texture
+        y: u32,
+        ///Width of the rectangle
+        width: u32,
+        ///Height of the rectangle
+        height: u32,
+    },
+}
+
+///A textured quad drawn by the [`crate::rendering::extensions::sprites::SpriteRenderer`]
+///
+///The sprite lies in the xy plane of its transform, its size in world units is the size of the
+///region in pixels divided by [`Sprite::pixels_per_unit`]
+#[derive(Debug)]
+pub struct Sprite {
+    ///Id of a [`crate::assets::Texture`] or a [`crate::assets::TextureAtlas`]
+    pub texture: Option<UUID>,
+    ///Drawn part of the texture
+    pub region: SpriteRegion,
+    ///Color the texture is multiplied by
+    pub tint: Color,
+    ///Mirrors the sprite horizontally
+    pub flip_x: bool,
+    ///Mirrors the sprite vertically
+    pub flip_y: bool,
+    ///Point of the sprite placed at the position of the transform, (0, 0) is the bottom left
+    ///corner and (1, 1) the top right one
+    pub pivot: Vec2,
+    ///Sprites on higher layers are drawn over the ones on lower layers, sprites on the same layer
+    ///are drawn back to front
+    pub layer: i32,
+    ///Number of texture pixels per world unit
+    pub pixels_per_unit: f32,
+    ///Whether or not the sprite is rendered
+    pub visible: bool,
+    transform: OnceCell<ComponentReference<Transform>>,
+}
+
+impl Component for Sprite {
+    #[dependencies(Transform)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            texture: None,
+            region: SpriteRegion::Full,
+            tint: Color::white(),
+            flip_x: false,
+            flip_y: false,
+            pivot: Vec2 { x: 0.5, y: 0.5 },
+            layer: 0,
+            pixels_per_unit: 100.0,
+            visible: true,
+            transform: OnceCell::new(),
+        }
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        self.transform
+            .set(reference.get_component().unwrap())
+            .unwrap();
+    }
+}
+
+impl Sprite {
+    ///Creates a new sprite drawing the region of the texture or the atlas
+    #[must_use]
+    pub fn new(texture: UUID, region: SpriteRegion) -> Self {
+        Self {
+            texture: Some(texture),
+            region,
+            ..Self::mew()
+        }
+    }
+
+    ///Returns a reference to the transform component
+    #[must_use]
+    pub fn get_transform(&self) -> ComponentReference<Transform> {
+        self.transform.get().unwrap().clone()
+    }
+
+    #[must_use]
+    pub(crate) fn get_matrix(&self) -> Mat4x4 {
+        self.transform.get().unwrap().borrow().matrix_transposed()
+    }
+}
+
+///Frames of a sprite sheet animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    ///Indices of the atlas regions shown one after another, see
+    ///[`crate::assets::TextureAtlas::frames`]
+    pub frames: Vec<usize>,
+    ///Number of frames shown per second
+    pub frame_rate: f32,
+    ///Whether the animation starts over after the last frame
+    pub looping: bool,
+}
+
+impl SpriteAnimation {
+    ///Creates a new looping animation
+    #[must_use]
+    pub const fn new(frames: Vec<usize>, frame_rate: f32) -> Self {
+        Self {
+            frames,
+            frame_rate,
+            looping: true,
+        }
+    }
+
+    ///Returns the length of the animation in seconds
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.frame_rate
+    }
+}
+
+///Plays named [`SpriteAnimation`]s by changing the region of the [`Sprite`] on the entity
+pub struct SpriteAnimator {
+    animations: Vec<(String, SpriteAnimation)>,
+    current: Option<usize>,
+    time: f32,
+    speed: f32,
+    playing: bool,
+    sprite: OnceCell<ComponentReference<Sprite>>,
+}
+
+impl std::fmt::Debug for SpriteAnimator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpriteAnimator")
+            .field("animations", &self.animations)
+            .field("current", &self.current)
+            .field("time", &self.time)
+            .field("speed", &self.speed)
+            .field("playing", &self.playing)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Component for SpriteAnimator {
+    #[dependencies(Sprite)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            animations: Vec::new(),
+            current: None,
+            time: 0.0,
+            speed: 1.0,
+            playing: false,
+            sprite: OnceCell::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.advance(delta_time());
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        self.sprite.set(reference.get_component().unwrap()).unwrap();
+    }
+}
+
+impl SpriteAnimator {
+    ///Adds an animation with the name, replacing the animation with the same name
+    pub fn add_animation(&mut self, name: &str, animation: SpriteAnimation) {
+        if let Some(a) = self.animations.iter_mut().find(|a| a.0 == name) {
+            a.1 = animation;
+        } else {
+            self.animations.push((name.to_owned(), animation));
+        }
+    }
+
+    ///Returns the animation with the name
+    #[must_use]
+    pub fn get_animation(&self, name: &str) -> Option<&SpriteAnimation> {
+        self.animations.iter().find(|a| a.0 == name).map(|a| &a.1)
+    }
+
+    ///Plays the animation with the name from its first frame, does nothing if it is already
+    ///playing
+    ///
+    ///Returns false if there is no animation with the name
+    pub fn play(&mut self, name: &str) -> bool {
+        let Some(index) = self.animations.iter().position(|a| a.0 == name) else {
+            return false;
+        };
+        if self.current != Some(index) || !self.playing {
+            self.current = Some(index);
+            self.time = 0.0;
+            self.playing = true;
+            self.apply();
+        }
+        true
+    }
+
+    ///Stops the playback, the sprite keeps showing the current frame
+    pub const fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    ///Returns whether an animation is playing
+    #[must_use]
+    pub const fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    ///Returns the name of the last played animation
+    #[must_use]
+    pub fn current_animation(&self) -> Option<&str> {
+        self.current.map(|i| self.animations[i].0.as_str())
+    }
+
+    ///Returns the index of the shown frame within the current animation
+    #[must_use]
+    pub fn current_frame(&self) -> Option<usize> {
+        let animation = &self.animations[self.current?].1;
+        if animation.frames.is_empty() {
+            return None;
+        }
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let frame = (self.time * animation.frame_rate).max(0.0) as usize;
+        Some(if animation.looping {
+            frame % animation.frames.len()
+        } else {
+            frame.min(animation.frames.len() - 1)
+        })
+    }
+
+    ///Sets the playback speed multiplier
+    pub const fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    ///Returns the playback speed multiplier
+    #[must_use]
+    pub const fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    ///Advances the playback by `delta` seconds and shows the current frame on the sprite
+    ///
+    ///Called automatically every frame
+    pub fn advance(&mut self, delta: f32) {
+        let Some(current) = self.current else {
+            return;
+        };
+        if !self.playing {
+            return;
+        }
+        self.time += delta * self.speed;
+
+        let animation = &self.animations[current].1;
+        if !animation.looping && self.time >= animation.duration() {
+            self.playing = false;
+        }
+        self.apply();
+    }
+
+    ///Shows the current frame on the sprite
+    fn apply(&self) {
+        let (Some(frame), Some(current)) = (self.current_frame(), self.current) else {
+            return;
+        };
+        if let Some(sprite) = self.sprite.get() {
+            sprite.borrow_mut().region =
+                SpriteRegion::Index(self.animations[current].1.frames[frame]);
+        }
+    }
+}
@@ -22,3 +22,468 @@ fn test_transform() {
     let t = e.get_component::<Transform>().unwrap();
     _ = t.borrow_mut().matrix();
 }
+
+#[test]
+fn test_animator() {
+    use super::animator::Animator;
+    use crate::{
+        asset_managment::AssetStore,
+        assets::{
+            Skeleton,
+            animation::Interpolation,
+            skeleton::{ChannelValues, Joint, JointChannel, JointPose, SkeletalClip},
+        },
+        math::{Mat4x4, Vec3},
+    };
+
+    let clip = |name: &str, x: f32| SkeletalClip {
+        name: name.to_owned(),
+        duration: 1.0,
+        channels: vec![JointChannel {
+            joint: 0,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0],
+            values: ChannelValues::Translation(vec![
+                Vec3::new(x, 0.0, 0.0),
+                Vec3::new(x, 1.0, 0.0),
+            ]),
+        }],
+    };
+    let joint = Joint {
+        name: "root".to_owned(),
+        parent: None,
+        inverse_bind_matrix: Mat4x4::identity(),
+        rest_pose: JointPose::default(),
+    };
+
+    let mut assets = AssetStore::new();
+    let id = assets.register(Skeleton::new(
+        vec![joint],
+        vec![clip("a", 0.0), clip("b", 2.0)],
+    ));
+    assets.intialize_all().unwrap();
+
+    let mut animator = Animator::new(assets.get_by_id::<Skeleton>(id).unwrap());
+    assert!(!animator.play("missing"));
+
+    //Rest pose when nothing is playing
+    animator.advance(0.1);
+    assert_eq!(animator.get_pose()[0], JointPose::default());
+
+    assert!(animator.play("a"));
+    animator.advance(0.5);
+    assert!((animator.get_pose()[0].translation.y - 0.5).abs() < 1e-5);
+
+    //Looping wraps the time around
+    animator.advance(0.75);
+    assert!((animator.get_pose()[0].translation.y - 0.25).abs() < 1e-5);
+
+    //Halfway through the cross fade both clips have the same weight
+    assert!(animator.cross_fade("b", 1.0));
+    animator.advance(0.5);
+    let pose = animator.get_pose()[0];
+    assert!((pose.translation.x - 1.0).abs() < 1e-5);
+    assert_eq!(animator.get_current_clip().as_deref(), Some("b"));
+    assert!(
+        (animator.get_joint_matrices()[0].m03 - pose.translation.x).abs() < 1e-5,
+        "Joint matrices must follow the pose"
+    );
+
+    //The old clip is removed once it fades out
+    animator.advance(0.5);
+    assert!((animator.get_pose()[0].translation.x - 2.0).abs() < 1e-5);
+
+    //Clips that don't loop hold their last frame
+    animator.set_looping(false);
+    animator.set_speed(2.0);
+    animator.advance(1.0);
+    assert!((animator.get_pose()[0].translation.y - 1.0).abs() < 1e-5);
+
+    animator.stop();
+    animator.advance(0.1);
+    assert_eq!(animator.get_pose()[0], JointPose::default());
+}
+
+#[test]
+fn test_animation_player() {
+    use super::animation_player::AnimationPlayer;
+    use crate::{
+        asset_managment::AssetStore,
+        assets::{
+            AnimationClip,
+            animation::{AnimationEvent, Interpolation, PropertyTrack, Track},
+        },
+        math::{Quaternion, Vec3, Vector as _},
+    };
+
+    let event = |time: f32, name: &str| AnimationEvent {
+        time,
+        name: name.to_owned(),
+    };
+    let clip = AnimationClip::new(
+        "move",
+        vec![
+            PropertyTrack::Position(Track::from_keyframes(
+                Interpolation::Linear,
+                &[
+                    (0.0, Vec3::new(0.0, 0.0, 0.0)),
+                    (2.0, Vec3::new(2.0, 4.0, 0.0)),
+                ],
+            )),
+            PropertyTrack::Rotation(Track::from_keyframes(
+                Interpolation::Linear,
+                &[
+                    (0.0, Quaternion::default()),
+                    (2.0, Quaternion::from_euler(Vec3::new(0.0, 90.0, 0.0))),
+                ],
+            )),
+            PropertyTrack::Scale(Track::from_keyframes(
+                Interpolation::Step,
+                &[
+                    (0.0, Vec3::new(1.0, 1.0, 1.0)),
+                    (1.5, Vec3::new(2.0, 2.0, 2.0)),
+                ],
+            )),
+        ],
+        vec![event(1.0, "middle"), event(0.0, "start"), event(2.0, "end")],
+    );
+    assert!((clip.get_duration() - 2.0).abs() < f32::EPSILON);
+    assert_eq!(clip.get_events()[0].name, "start");
+
+    let mut assets = AssetStore::new();
+    let id = assets.register(clip);
+    assets.intialize_all().unwrap();
+
+    let mut world = World::new();
+    let entity = EntityBuilder::new()
+        .add_component::<Transform>()
+        .add_existing_component(AnimationPlayer::new(assets.get_by_id(id).unwrap()))
+        .create()
+        .unwrap();
+    let player = entity.get_component::<AnimationPlayer>().unwrap();
+    let transform = entity.get_component::<Transform>().unwrap();
+    world.add_entity(entity).unwrap();
+
+    let mut player = player.borrow_mut();
+
+    //Nothing happens until the clip is played
+    player.advance(0.5);
+    assert_eq!(transform.borrow().position, Vec3::default());
+
+    player.play();
+    player.advance(0.5);
+    assert_eq!(player.take_events(), vec!["start"]);
+    assert!((transform.borrow().position - Vec3::new(0.5, 1.0, 0.0)).length() < 1e-5);
+    assert_eq!(transform.borrow().scale, Vec3::new(1.0, 1.0, 1.0));
+    let rotation = transform.borrow().rotation.euler();
+    assert!((rotation.y - 22.5).abs() < 1e-3);
+
+    //Pausing keeps the time
+    player.pause();
+    player.advance(1.0);
+    assert!((player.get_time() - 0.5).abs() < 1e-5);
+    player.resume();
+
+    //Looping wraps around and fires the events of both loops in order
+    player.advance(2.0);
+    assert_eq!(player.take_events(), vec!["middle", "end", "start"]);
+    assert!((player.get_time() - 0.5).abs() < 1e-5);
+
+    //Faster playback
+    player.set_speed(2.0);
+    player.advance(0.5);
+    assert_eq!(player.take_events(), vec!["middle"]);
+    assert_eq!(transform.borrow().scale, Vec3::new(2.0, 2.0, 2.0));
+
+    //Without looping the playback stops at the end
+    player.set_looping(false);
+    player.advance(1.0);
+    assert_eq!(player.take_events(), vec!["end"]);
+    assert!(!player.is_playing());
+    assert!((transform.borrow().position - Vec3::new(2.0, 4.0, 0.0)).length() < 1e-5);
+
+    //Backwards playback
+    player.set_speed(-1.0);
+    player.play();
+    player.advance(1.5);
+    assert_eq!(player.take_events(), vec!["end", "middle"]);
+    assert!((transform.borrow().position - Vec3::new(0.5, 1.0, 0.0)).length() < 1e-5);
+
+    //Seeking does not fire events
+    player.set_time(0.0);
+    assert!(player.take_events().is_empty());
+    assert_eq!(transform.borrow().position, Vec3::default());
+}
+
+#[test]
+fn test_camera_viewport() {
+    use super::camera::{Camera, Viewport};
+
+    assert!(Viewport::FULL.is_full());
+    assert_eq!(Viewport::FULL.pixels((800, 600)), (0, 0, 800, 600));
+
+    //Right half of the screen
+    let right = Viewport::new(0.5, 0.0, 0.5, 1.0);
+    assert!(!right.is_full());
+    assert_eq!(right.pixels((800, 600)), (400, 0, 400, 600));
+
+    //Viewports are clamped to the target
+    let outside = Viewport::new(0.75, 0.75, 0.5, 0.5);
+    assert_eq!(outside.pixels((100, 100)), (75, 75, 25, 25));
+
+    let mut camera = Camera::default();
+    camera.viewport = right;
+    camera.set_target_size(Some((800, 600)));
+    assert_eq!(camera.target_size(), (800, 600));
+    assert!((camera.aspect() - 400.0 / 600.0).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_spot_light() {
+    use super::light::SpotLight;
+    use crate::math::{Vec3, Vector as _};
+
+    let mut world = World::new();
+    let entity = EntityBuilder::new()
+        .add_component::<Transform>()
+        .add_component::<SpotLight>()
+        .create()
+        .unwrap();
+    let t = entity.get_component::<Transform>().unwrap();
+    let l = entity.get_component::<SpotLight>().unwrap();
+    world.add_entity(entity).unwrap();
+
+    t.borrow_mut().position = Vec3::new(1.0, 2.0, 3.0);
+
+    //The inner angle can't be larger than the outer one
+    l.borrow_mut().set_angles(1.0, 0.5);
+    assert_eq!(l.borrow().get_angles(), (0.5, 0.5));
+    l.borrow_mut().set_angles(0.25, 0.5);
+
+    let (position, direction) = l.borrow().get_position_direction();
+    assert_eq!(position, Vec3::new(1.0, 2.0, 3.0));
+    assert!((direction.length() - 1.0).abs() < 0.0001);
+
+    let data = l.borrow().get_light(-1);
+    assert_eq!(data.shadow_index, -1);
+    assert!((data.inner_cos - 0.25f32.cos()).abs() < f32::EPSILON);
+    assert!((data.outer_cos - 0.5f32.cos()).abs() < f32::EPSILON);
+    assert!(data.inner_cos > data.outer_cos);
+}
+
+#[test]
+fn test_sprite_animator() {
+    use super::sprite::{Sprite, SpriteAnimation, SpriteAnimator, SpriteRegion};
+
+    let mut world = World::new();
+    let entity = EntityBuilder::new()
+        .add_component::<Transform>()
+        .add_component::<Sprite>()
+        .add_component::<SpriteAnimator>()
+        .create()
+        .unwrap();
+    let sprite = entity.get_component::<Sprite>().unwrap();
+    let animator = entity.get_component::<SpriteAnimator>().unwrap();
+    world.add_entity(entity).unwrap();
+
+    let mut animator = animator.borrow_mut();
+    animator.add_animation("walk", SpriteAnimation::new(vec![4, 5, 6], 10.0));
+    animator.add_animation(
+        "jump",
+        SpriteAnimation {
+            looping: false,
+            ..SpriteAnimation::new(vec![1, 2], 4.0)
+        },
+    );
+    assert!(!animator.play("run"));
+
+    //Playing shows the first frame right away
+    assert!(animator.play("walk"));
+    assert_eq!(sprite.borrow().region, SpriteRegion::Index(4));
+    animator.advance(0.15);
+    assert_eq!(animator.current_frame(), Some(1));
+    assert_eq!(sprite.borrow().region, SpriteRegion::Index(5));
+
+    //Playing the same animation again does not restart it
+    animator.play("walk");
+    assert_eq!(animator.current_frame(), Some(1));
+
+    //Looping wraps around
+    animator.advance(0.2);
+    assert_eq!(sprite.borrow().region, SpriteRegion::Index(4));
+
+    //Stopping keeps the frame
+    animator.stop();
+    animator.advance(0.1);
+    assert_eq!(sprite.borrow().region, SpriteRegion::Index(4));
+
+    //Without looping the animation stops on the last frame
+    animator.play("jump");
+    animator.set_speed(2.0);
+    animator.advance(0.3);
+    assert_eq!(sprite.borrow().region, SpriteRegion::Index(2));
+    assert!(!animator.is_playing());
+    assert_eq!(animator.current_animation(), Some("jump"));
+}
+
+#[test]
+fn test_particle_emitter() {
+    use super::particles::{EmissionShape, MeshSurface, ParticleEmitter, ParticleSimulation};
+    use crate::{
+        assets::animation::{Interpolation, Track},
+        math::{Vec3, Vector as _},
+        structures::{self, Vertex},
+    };
+
+    let mut world = World::new();
+    let entity = EntityBuilder::new()
+        .add_component::<Transform>()
+        .add_component::<ParticleEmitter>()
+        .create()
+        .unwrap();
+    let transform = entity.get_component::<Transform>().unwrap();
+    let emitter = entity.get_component::<ParticleEmitter>().unwrap();
+    world.add_entity(entity).unwrap();
+    transform.borrow_mut().position = Vec3::new(10, 0, 0);
+
+    let mut emitter = emitter.borrow_mut();
+    assert_eq!(emitter.get_simulation(), ParticleSimulation::default());
+    emitter.set_simulation(ParticleSimulation::Cpu);
+    emitter.shape = EmissionShape::Point;
+    emitter.lifetime = 1.0..=1.0;
+    emitter.speed = 2.0..=2.0;
+
+    //Particles are emitted at the rate and move with their starting speed
+    emitter.advance(0.55);
+    assert_eq!(emitter.particle_count(), 5);
+    let instances = emitter.instances();
+    assert_eq!(instances.len(), 5);
+    for instance in &instances {
+        assert!(((instance.position - Vec3::new(10, 0, 0)).length() - 1.1).abs() < 1e-4);
+        assert!((instance.velocity.length() - 2.0).abs() < 1e-4);
+        assert!((instance.size - 0.1).abs() < f32::EPSILON);
+        //The default color fades out over the lifetime
+        assert!((instance.color.a - 0.45).abs() < 1e-4);
+    }
+
+    //Particles die at the end of their lifetime
+    emitter.emitting = false;
+    emitter.advance(0.5);
+    assert_eq!(emitter.particle_count(), 0);
+    assert!(emitter.instances().is_empty());
+
+    //Gravity and drag change the velocity, the velocity curve scales it
+    emitter.speed = 0.0..=0.0;
+    emitter.gravity = Vec3::new(0, -10, 0);
+    emitter.velocity = Track::from_keyframes(Interpolation::Step, &[(0.0, 0.5)]);
+    emitter.burst(1);
+    emitter.advance(0.1);
+    let instance = emitter.instances()[0];
+    assert!((instance.position - Vec3::new(10.0, -0.05, 0.0)).length() < 1e-4);
+    assert!((instance.velocity - Vec3::new(0.0, -0.5, 0.0)).length() < 1e-4);
+    emitter.gravity = Vec3::default();
+    emitter.drag = 2.0;
+    emitter.advance(0.5);
+    assert!(((-1.0f32).exp() * 0.5 - emitter.instances()[0].velocity.length()).abs() < 1e-4);
+
+    //The oldest particles are replaced when every slot is used
+    emitter.clear();
+    assert_eq!(emitter.particle_count(), 0);
+    emitter.max_particles = 3;
+    emitter.burst(5);
+    assert_eq!(emitter.particle_count(), 3);
+
+    //Particles start inside of the shapes
+    emitter.max_particles = 100;
+    emitter.speed = 1.0..=1.0;
+    emitter.velocity = Track::from_keyframes(Interpolation::Linear, &[(0.0, 1.0)]);
+    transform.borrow_mut().position = Vec3::default();
+    let positions = |emitter: &mut ParticleEmitter, shape| {
+        emitter.clear();
+        emitter.shape = shape;
+        emitter.burst(100);
+        emitter.advance(0.0);
+        emitter.instances()
+    };
+    for i in positions(&mut emitter, EmissionShape::Sphere { radius: 2.0 }) {
+        assert!(i.position.length() <= 2.0 + 1e-4);
+        //Particles move away from the center
+        assert!(i.position.dot_product(&i.velocity) >= -1e-4);
+    }
+    let size = Vec3::new(1, 2, 3);
+    for i in positions(&mut emitter, EmissionShape::Box { size }) {
+        assert!(i.position.abs().less(size / 2.0 + 1e-4));
+        assert_eq!(i.velocity, Vec3::new(0, 0, 1));
+    }
+    let cone = EmissionShape::Cone {
+        angle: 0.5,
+        radius: 1.0,
+    };
+    for i in positions(&mut emitter, cone) {
+        assert!(i.position.z.abs() < f32::EPSILON && i.position.length() <= 1.0 + 1e-4);
+        assert!(i.velocity.z >= 0.5f32.cos() - 1e-4);
+    }
+
+    let vertex = |x, y| Vertex {
+        coords: Vec3::new(x, y, 0),
+        ..Default::default()
+    };
+    let mesh = structures::Mesh {
+        vertices: vec![vertex(0, 0), vertex(2, 0), vertex(0, 1), vertex(2, 1)],
+        indices: vec![0, 1, 2, 2, 1, 3],
+    };
+    let surface = MeshSurface::new(&mesh);
+    assert!((surface.area() - 2.0).abs() < f32::EPSILON);
+    for i in positions(&mut emitter, EmissionShape::Surface(surface)) {
+        assert!(i.position.z.abs() < f32::EPSILON);
+        assert!((0.0..=2.0).contains(&i.position.x) && (0.0..=1.0).contains(&i.position.y));
+        assert_eq!(i.velocity, Vec3::new(0, 0, 1));
+    }
+
+    //Particles simulated on the gpu are only emitted by the component
+    emitter.set_simulation(ParticleSimulation::Gpu);
+    emitter.rate = 10.0;
+    emitter.emitting = true;
+    emitter.advance(0.25);
+    emitter.advance(0.1);
+    let update = emitter.take_gpu_update();
+    assert!(update.clear);
+    assert!((update.delta - 0.35).abs() < 1e-6);
+    assert_eq!(
+        update.spawned.iter().map(|s| s.0).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    assert!(emitter.instances().is_empty());
+    assert_eq!(emitter.particle_count(), 3);
+}
+
+#[test]
+fn test_camera_unproject() {
+    use super::camera::{ndc_ray, pixel_to_ndc, unproject_ndc};
+    use crate::math::{Mat4x4, Vec2, Vec3, Vector as _};
+
+    //The center of the top left pixel and the bottom right corner of the viewport
+    let viewport = (100, 0, 200, 100);
+    let ndc = pixel_to_ndc(viewport, Vec2::new(100.5, 0.5)).unwrap();
+    assert!((ndc.x + 0.995).abs() < 0.0001 && (ndc.y - 0.99).abs() < 0.0001);
+    assert_eq!(pixel_to_ndc(viewport, Vec2::new(50.0, 50.0)), None);
+    assert_eq!(pixel_to_ndc(viewport, Vec2::new(300.0, 50.0)), None);
+
+    //Camera at (0, 0, -5) looking along +z, same matrix as the one of the camera component
+    let position = Vec3::new(0, 0, -5);
+    let matrix =
+        Mat4x4::look_at_matrix(position, Vec3::new(0, 1, 0), position + Vec3::new(0, 0, 1))
+            * Mat4x4::perspercive_projection(90f32.to_radians(), 1.0, 1.0, 100.0);
+    let inverse = matrix.transpose().inverted().unwrap();
+
+    let ray = ndc_ray(&inverse, Vec2::new(0.0, 0.0)).unwrap();
+    assert!((ray.origin - Vec3::new(0, 0, -4)).length() < 0.001);
+    assert!((ray.direction - Vec3::new(0, 0, 1)).length() < 0.001);
+
+    let far = unproject_ndc(&inverse, Vec2::new(0.0, 0.0), 1.0).unwrap();
+    assert!((far - Vec3::new(0, 0, 95)).length() < 0.1);
+
+    //Top right corner of the near plane, the right of a camera looking along +z is -x
+    let corner = unproject_ndc(&inverse, Vec2::new(1.0, 1.0), 0.0).unwrap();
+    assert!((corner - Vec3::new(-1, 1, -4)).length() < 0.001);
+}
@@ -0,0 +1,110 @@
+use std::cell::OnceCell;
+
+use lunar_engine_derive::dependencies;
+
+use crate as lunar_engine;
+use crate::{
+    UUID,
+    assets::font::{LayoutOptions, TextAlignment},
+    ecs::{Component, ComponentReference},
+    structures::Color,
+};
+
+use super::transform::Transform;
+
+///Where a [`Text`] is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextSpace {
+    ///The text lies in the xy plane of its transform, its size is in world units and it is seen
+    ///by the cameras like any other object
+    #[default]
+    World,
+    ///The text is drawn over the whole window, the x and y position of the transform are in
+    ///pixels from the top left corner of the window and the size is in pixels
+    Screen,
+}
+
+///Text drawn by the [`crate::rendering::extensions::text::TextRenderer`]
+///
+///The origin of the text is at the top of its first line, where it is depends on the alignment,
+///see [`TextAlignment`]
+#[derive(Debug)]
+pub struct Text {
+    ///Drawn text, lines are separated by `\n`
+    pub text: String,
+    ///Id of a [`crate::assets::Font`]
+    pub font: Option<UUID>,
+    ///Height of an em, in pixels for screen text and in world units for world text
+    pub size: f32,
+    ///Color of the text
+    pub color: Color,
+    ///Alignment of the lines
+    pub alignment: TextAlignment,
+    ///Lines wider than this are wrapped
+    pub max_width: Option<f32>,
+    ///Multiplier of the distance between lines
+    pub line_spacing: f32,
+    ///Where the text is drawn
+    pub space: TextSpace,
+    ///Whether or not the text is rendered
+    pub visible: bool,
+    transform: OnceCell<ComponentReference<Transform>>,
+}
+
+impl Component for Text {
+    #[dependencies(Transform)]
+    fn mew() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            text: String::new(),
+            font: None,
+            size: 1.0,
+            color: Color::white(),
+            alignment: TextAlignment::Left,
+            max_width: None,
+            line_spacing: 1.0,
+            space: TextSpace::World,
+            visible: true,
+            transform: OnceCell::new(),
+        }
+    }
+
+    fn set_self_reference(&mut self, reference: crate::ecs::SelfReferenceGuard) {
+        self.transform
+            .set(reference.get_component().unwrap())
+            .unwrap();
+    }
+}
+
+impl Text {
+    ///Creates a new text drawn with the font in the space
+    #[must_use]
+    pub fn new(text: &str, font: UUID, size: f32, space: TextSpace) -> Self {
+        Self {
+            text: text.to_owned(),
+            font: Some(font),
+            size,
+            space,
+            ..Self::mew()
+        }
+    }
+
+    ///Returns the options the text is laid out with
+    #[must_use]
+    pub const fn layout_options(&self) -> LayoutOptions {
+        LayoutOptions {
+            size: self.size,
+            max_width: self.max_width,
+            alignment: self.alignment,
+            line_spacing: self.line_spacing,
+        }
+    }
+
+    ///Returns a reference to the transform component
+    #[must_use]
+    pub fn get_transform(&self) -> ComponentReference<Transform> {
+        self.transform.get().unwrap().clone()
+    }
+}
@@ -17,6 +17,34 @@ pub const CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
         }],
     };
 
+///Camera bind group of skinned meshes, also contains the joint matrices of the mesh
+pub const SKINNED_CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Skinned camera binding"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    };
+
 pub const DIRECTIONAL_LIGHT_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
     wgpu::BindGroupLayoutDescriptor {
         label: Some("Directional Light"),
@@ -32,61 +60,74 @@ pub const DIRECTIONAL_LIGHT_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutD
         }],
     };
 
+///Spot light shadow maps and their comparison sampler, shared by both light layouts
+const SHADOW_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 2] = [
+    wgpu::BindGroupLayoutEntry {
+        binding: 3,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+            multisampled: false,
+        },
+        count: None,
+    },
+    wgpu::BindGroupLayoutEntry {
+        binding: 4,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+        count: None,
+    },
+];
+
+const fn light_buffer_entry(binding: u32, storage: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: if storage {
+                wgpu::BufferBindingType::Storage { read_only: true }
+            } else {
+                wgpu::BufferBindingType::Uniform
+            },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+//The light clusters are only used with storage buffers
+const STORAGE_LIGHT_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 7] = [
+    light_buffer_entry(0, false),
+    light_buffer_entry(1, true),
+    light_buffer_entry(2, true),
+    SHADOW_LAYOUT_ENTRIES[0],
+    SHADOW_LAYOUT_ENTRIES[1],
+    light_buffer_entry(5, true),
+    light_buffer_entry(6, true),
+];
+
+const UNIFORM_LIGHT_LAYOUT_ENTRIES: [wgpu::BindGroupLayoutEntry; 5] = [
+    light_buffer_entry(0, false),
+    light_buffer_entry(1, false),
+    light_buffer_entry(2, false),
+    SHADOW_LAYOUT_ENTRIES[0],
+    SHADOW_LAYOUT_ENTRIES[1],
+];
+
+///Layout of the point and spot lights, the light counts, the point lights, the spot lights, the
+///spot light shadow maps and, with storage buffers, the point light clusters
 pub const fn point_light_bind_group_layout_descriptor(
     storage_buffer_support: bool,
 ) -> wgpu::BindGroupLayoutDescriptor<'static> {
-    if storage_buffer_support {
-        wgpu::BindGroupLayoutDescriptor {
-            label: Some("Point Lights"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        }
-    } else {
-        wgpu::BindGroupLayoutDescriptor {
-            label: Some("Point Lights"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        }
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Point Lights"),
+        entries: if storage_buffer_support {
+            &STORAGE_LIGHT_LAYOUT_ENTRIES
+        } else {
+            &UNIFORM_LIGHT_LAYOUT_ENTRIES
+        },
     }
 }
 
@@ -95,4 +136,12 @@ pub const DIRECT_LIGHT_BIND_GROUP_INDEX: u32 = 2;
 pub const POINT_LIGHT_BIND_GROUP_INDEX: u32 = 3;
 pub const NUM_THREADS: usize = 8;
 
+///Number of point lights in the uniform buffer, when storage buffers are not available
+pub const MAX_UNIFORM_POINT_LIGHTS: usize = 256;
+///Number of spot lights in the uniform buffer, when storage buffers are not available
+pub const MAX_UNIFORM_SPOT_LIGHTS: usize = 128;
+///Width and height of the spot light shadow maps
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+
 pub const DEFAULT_TEXTURE_ASSET_ID: u128 = 0;
+pub const DEFAULT_CUBEMAP_ASSET_ID: u128 = 1;
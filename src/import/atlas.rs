@@ -0,0 +1,51 @@
+//! Loading of texture atlas descriptions exported by texture packing tools
+//!
+//! Both the hash (`"frames": {"name": {...}}`) and the array (`"frames": [{"filename": ...}]`)
+//! layouts of the common json format are supported, rotated frames are not
+use super::{invalid, json::Value};
+use crate::assets::atlas::AtlasRegion;
+
+///Parses the regions of an atlas json file, in the order they were written in
+///
+///# Errors
+///Fails if the text is not valid json, if a frame is missing its rectangle or if a frame is rotated
+pub fn parse(text: &str) -> Result<Vec<AtlasRegion>, Box<dyn std::error::Error + Send>> {
+    let json = super::json::parse(text)?;
+
+    let frames: Vec<(&str, &Value)> = match json.get("frames") {
+        Value::Object(members) => members.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        Value::Array(elements) => elements
+            .iter()
+            .map(|f| {
+                f.get("filename")
+                    .as_str()
+                    .map(|name| (name, f))
+                    .ok_or_else(|| invalid("Atlas frame is missing its file name"))
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(invalid("Atlas json does not contain any frames")),
+    };
+
+    frames
+        .into_iter()
+        .map(|(name, frame)| {
+            if frame.get("rotated").as_bool() == Some(true) {
+                return Err(invalid("Rotated atlas frames are not supported"));
+            }
+            let rect = frame.get("frame");
+            let value = |key: &str| {
+                rect.get(key)
+                    .as_usize()
+                    .and_then(|v| u32::try_from(v).ok())
+                    .ok_or_else(|| invalid("Atlas frame has an invalid rectangle"))
+            };
+            Ok(AtlasRegion {
+                name: name.to_owned(),
+                x: value("x")?,
+                y: value("y")?,
+                width: value("w")?,
+                height: value("h")?,
+            })
+        })
+        .collect()
+}
@@ -1,6 +1,8 @@
-#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
 use lunar_png::Image;
 
+use super::invalid;
+
 //Packed may cause issues with incorrect signature
 #[repr(C, packed)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
@@ -15,11 +17,11 @@ struct Header {
 #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct InfoHeader {
     header_size: u32,
-    width: u32,
-    height: u32,
+    width: i32,
+    ///Negative if the image is stored from top to bottom
+    height: i32,
     color_planes: u16,
     bpp: u16,
-    ///Ignored for simplicity
     compression_method: u32,
     raw_bitmap_size: u32,
     ppm_widh: i32,
@@ -29,66 +31,436 @@ struct InfoHeader {
 }
 
 const HEADER_SIZE: usize = 54;
+const FILE_HEADER_SIZE: usize = 14;
+
+///Size of `BITMAPINFOHEADER`, larger headers (`BITMAPV4HEADER`, `BITMAPV5HEADER`) start with it
+const INFO_HEADER_SIZE: u32 = 40;
+
+const COMPRESSION_RGB: u32 = 0;
+const COMPRESSION_RLE8: u32 = 1;
+const COMPRESSION_RLE4: u32 = 2;
+const COMPRESSION_BITFIELDS: u32 = 3;
+const COMPRESSION_ALPHABITFIELDS: u32 = 6;
+
+///Largest number of pixels of an rle compressed image, runs and skips can cover any number of
+///pixels, so the size of the compressed data doesn't limit the size of the image
+const MAX_RLE_PIXELS: usize = 1 << 28;
+
+///A color channel described by a bit mask
+#[derive(Clone, Copy)]
+struct Channel {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    const fn new(mask: u32) -> Self {
+        let shift = if mask == 0 { 0 } else { mask.trailing_zeros() };
+        Self {
+            mask,
+            shift,
+            max: mask >> shift,
+        }
+    }
+
+    const fn read(self, pixel: u32, default: u8) -> u8 {
+        if self.max == 0 {
+            return default;
+        }
+        (((pixel & self.mask) >> self.shift) * 255 / self.max) as u8
+    }
+}
+
+///Decodes run length encoded palette indices, returns the indices (or `None` for skipped pixels)
+///in the order they are stored in the file
+fn decode_rle(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    four_bit: bool,
+) -> Result<Vec<Option<u8>>, Box<dyn std::error::Error + Send>> {
+    let mut out = vec![None; width * height];
+    let (mut x, mut y) = (0, 0);
+    let mut position = 0;
+
+    let mut put = |x: &mut usize, y: usize, value: u8| {
+        if *x < width && y < height {
+            out[y * width + *x] = Some(value);
+        }
+        *x += 1;
+    };
+
+    //Some encoders don't write the end of bitmap marker
+    while let (Some(&count), Some(&value)) = (data.get(position), data.get(position + 1)) {
+        position += 2;
+
+        if count > 0 {
+            for i in 0..count {
+                let v = if four_bit {
+                    if i % 2 == 0 { value >> 4 } else { value & 0x0F }
+                } else {
+                    value
+                };
+                put(&mut x, y, v);
+            }
+            continue;
+        }
+
+        match value {
+            //End of line
+            0 => {
+                x = 0;
+                y += 1;
+            }
+            //End of bitmap
+            1 => break,
+            //Delta
+            2 => {
+                let delta = data
+                    .get(position..position + 2)
+                    .ok_or_else(|| invalid("Unexpected end of data"))?;
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+                position += 2;
+            }
+            //Absolute mode
+            count => {
+                let count = count as usize;
+                let len = if four_bit { count.div_ceil(2) } else { count };
+                let values = data
+                    .get(position..position + len)
+                    .ok_or_else(|| invalid("Unexpected end of data"))?;
+                for i in 0..count {
+                    let v = if four_bit {
+                        let b = values[i / 2];
+                        if i % 2 == 0 { b >> 4 } else { b & 0x0F }
+                    } else {
+                        values[i]
+                    };
+                    put(&mut x, y, v);
+                }
+                //Padded to 16 bits
+                position += len + len % 2;
+            }
+        }
+    }
+
+    Ok(out)
+}
 
 ///Parses a byte array as a bmp image
+///
+///Supports 1, 4, 8, 16, 24 and 32 bpp images, uncompressed, rle compressed or using bit fields,
+///with any header version starting with `BITMAPINFOHEADER` (including `BITMAPV4HEADER` and
+///`BITMAPV5HEADER`). The image is always decoded into rgba8, rows are stored from bottom to top
 ///# Errors
-///fails if the file isn't 32 bit or has multiple color planes
+///fails if the file is invalid, has multiple color planes or uses an unsupported format
+#[allow(clippy::too_many_lines)]
 pub fn parse(data: &[u8]) -> Result<Image, Box<dyn std::error::Error + Send>> {
     if data.len() <= HEADER_SIZE {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Wrong header size",
-        )));
+        return Err(invalid("Wrong header size"));
     }
 
-    let header: &Header = bytemuck::from_bytes(&data[..14]);
+    let header: Header = bytemuck::pod_read_unaligned(&data[..FILE_HEADER_SIZE]);
     if header.signature != [0x42, 0x4D] {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Wrong signature",
-        )));
+        return Err(invalid("Wrong signature"));
     }
     if header.size != data.len() as u32 {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Invalid size",
-        )));
+        return Err(invalid("Invalid size"));
     }
 
-    let info_header: &InfoHeader = bytemuck::from_bytes(&data[14..54]);
-    // if info_header.header_size != 40 {
-    //     return Err(format!(
-    //         "YOUR INFO HEADER IS TOO BIG. YOU SHOULD FEEL BAD {:?}",
-    //         info_header
-    //     )
-    //     .into());
-    // }
+    let info_header: InfoHeader =
+        bytemuck::pod_read_unaligned(&data[FILE_HEADER_SIZE..HEADER_SIZE]);
+    if info_header.header_size < INFO_HEADER_SIZE {
+        return Err(invalid("Unsupported info header"));
+    }
     if info_header.color_planes != 1 {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Wrong number of color planes != 1",
-        )));
+        return Err(invalid("Wrong number of color planes != 1"));
+    }
+
+    let bpp = info_header.bpp;
+    let compression = info_header.compression_method;
+    let valid = match compression {
+        COMPRESSION_RGB => matches!(bpp, 1 | 4 | 8 | 16 | 24 | 32),
+        COMPRESSION_RLE8 => bpp == 8,
+        COMPRESSION_RLE4 => bpp == 4,
+        COMPRESSION_BITFIELDS | COMPRESSION_ALPHABITFIELDS => matches!(bpp, 16 | 32),
+        _ => false,
+    };
+    if !valid {
+        return Err(invalid("Unsported bits per pixel or compression"));
+    }
+
+    let width = info_header.width.unsigned_abs() as usize;
+    let height = info_header.height.unsigned_abs() as usize;
+    let top_to_bottom = info_header.height < 0;
+    if width == 0 || height == 0 {
+        return Err(invalid("Image has no pixels"));
+    }
+    let pixel_count = width
+        .checked_mul(height)
+        .ok_or_else(|| invalid("Image is too large"))?;
+
+    //The masks are stored after the header, or inside of it for newer header versions
+    let mut color_table_start = FILE_HEADER_SIZE + info_header.header_size as usize;
+    let masks = if matches!(
+        compression,
+        COMPRESSION_BITFIELDS | COMPRESSION_ALPHABITFIELDS
+    ) {
+        let count = if compression == COMPRESSION_ALPHABITFIELDS {
+            4
+        } else {
+            3
+        };
+        if info_header.header_size == INFO_HEADER_SIZE {
+            color_table_start += count * 4;
+        }
+        //Newer headers always contain the alpha mask
+        let count = if info_header.header_size >= 56 {
+            4
+        } else {
+            count
+        };
+        let start = HEADER_SIZE;
+        let bytes = data
+            .get(start..start + count * 4)
+            .ok_or_else(|| invalid("Unexpected end of data"))?;
+        let mut masks = [0u32; 4];
+        for (m, b) in masks.iter_mut().zip(bytes.chunks(4)) {
+            *m = u32::from_le_bytes(b.try_into().unwrap());
+        }
+        masks
+    } else if bpp == 16 {
+        //X1R5G5B5
+        [0x7C00, 0x03E0, 0x001F, 0]
+    } else {
+        [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000]
+    };
+    let channels = masks.map(Channel::new);
+
+    let color_table = if bpp <= 8 {
+        let count = match info_header.num_colors {
+            0 => 1 << bpp,
+            n => n as usize,
+        };
+        data.get(color_table_start..color_table_start + count * 4)
+            .ok_or_else(|| invalid("Unexpected end of data"))?
+            .chunks(4)
+            .map(|c| [c[2], c[1], c[0], 255])
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+    let color = |index: u8| {
+        color_table
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| invalid("Color table index out of bounds"))
+    };
+
+    let pixel_data = data
+        .get(header.data_offset as usize..)
+        .ok_or_else(|| invalid("Unexpected end of data"))?;
+
+    let mut pixels;
+    if matches!(compression, COMPRESSION_RLE4 | COMPRESSION_RLE8) {
+        if pixel_count > MAX_RLE_PIXELS {
+            return Err(invalid("Image is too large"));
+        }
+
+        pixels = Vec::with_capacity(pixel_count);
+        for index in decode_rle(pixel_data, width, height, compression == COMPRESSION_RLE4)? {
+            pixels.push(match index {
+                Some(i) => color(i)?,
+                //Skipped pixels are transparent
+                None => [0, 0, 0, 0],
+            });
+        }
+    } else {
+        //Rows are padded to 4 bytes, the image is checked against the size of the data before
+        //anything is allocated
+        let stride = width
+            .checked_mul(bpp as usize)
+            .map(|bits| bits.div_ceil(32) * 4)
+            .ok_or_else(|| invalid("Image is too large"))?;
+        if stride
+            .checked_mul(height)
+            .is_none_or(|size| pixel_data.len() < size)
+        {
+            return Err(invalid("Unexpected end of data"));
+        }
+
+        pixels = Vec::with_capacity(pixel_count);
+
+        for row in pixel_data.chunks(stride).take(height) {
+            match bpp {
+                1 | 4 | 8 => {
+                    let per_byte = 8 / bpp as usize;
+                    for x in 0..width {
+                        let byte = row[x / per_byte];
+                        let shift = 8 - bpp as usize * (x % per_byte + 1);
+                        let index = (byte >> shift) & ((1u16 << bpp) - 1) as u8;
+                        pixels.push(color(index)?);
+                    }
+                }
+                24 => pixels.extend(row[..width * 3].chunks(3).map(|c| [c[2], c[1], c[0], 255])),
+                //Uncompressed 32 bpp images use the 4th byte as alpha
+                32 if compression == COMPRESSION_RGB => {
+                    pixels.extend(row[..width * 4].chunks(4).map(|c| [c[2], c[1], c[0], c[3]]));
+                }
+                _ => {
+                    let size = bpp as usize / 8;
+                    for p in row[..width * size].chunks(size) {
+                        let p = if size == 2 {
+                            u32::from(u16::from_le_bytes([p[0], p[1]]))
+                        } else {
+                            u32::from_le_bytes([p[0], p[1], p[2], p[3]])
+                        };
+                        pixels.push([
+                            channels[0].read(p, 0),
+                            channels[1].read(p, 0),
+                            channels[2].read(p, 0),
+                            channels[3].read(p, 255),
+                        ]);
+                    }
+                }
+            }
+        }
     }
-    // if info_header.compression_method != 0 {
-    //     let a = info_header.compression_method;
-    //     return Err(format!("Compression not supported found compression {}", a).into());
-    // }
-    // (info_header.bpp == 24)  ||
-    if !info_header.bpp == 32 {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Unsported bits per pixel",
-        )));
+
+    //The engine expects images to be stored from bottom to top
+    if top_to_bottom {
+        pixels = pixels.chunks(width).rev().flatten().copied().collect();
     }
-    let out = data[header.data_offset as usize..]
-        .chunks(4)
-        .flat_map(|c| [c[2], c[1], c[0], c[3]])
-        .collect();
 
     Ok(Image {
         img_type: lunar_png::ImageType::Rgba8,
-        width: info_header.width,
-        height: info_header.height,
-        data: out,
+        width: width as u32,
+        height: height as u32,
+        data: pixels.into_iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+fn build_bmp(info_header: InfoHeader, extra: &[u8], pixels: &[u8]) -> Vec<u8> {
+    let data_offset = (HEADER_SIZE - 40 + info_header.header_size as usize + extra.len()) as u32;
+    let mut data = bytemuck::bytes_of(&Header {
+        signature: [0x42, 0x4D],
+        size: data_offset + pixels.len() as u32,
+        reserved: 0,
+        data_offset,
     })
+    .to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&info_header));
+    data.resize(FILE_HEADER_SIZE + info_header.header_size as usize, 0);
+    data.extend_from_slice(extra);
+    data.extend_from_slice(pixels);
+    data
+}
+
+#[cfg(test)]
+const fn info_header(width: i32, height: i32, bpp: u16, compression_method: u32) -> InfoHeader {
+    InfoHeader {
+        header_size: INFO_HEADER_SIZE,
+        width,
+        height,
+        color_planes: 1,
+        bpp,
+        compression_method,
+        raw_bitmap_size: 0,
+        ppm_widh: 0,
+        ppm_height: 0,
+        num_colors: 0,
+        important_colors: 0,
+    }
+}
+
+#[test]
+fn test_parse_24bpp() {
+    //2x2, top to bottom, rows padded to 8 bytes
+    let data = build_bmp(
+        info_header(2, -2, 24, COMPRESSION_RGB),
+        &[],
+        &[
+            0, 0, 255, 0, 255, 0, 0, 0, //
+            255, 0, 0, 255, 255, 255, 0, 0, //
+        ],
+    );
+    let image = parse(&data).unwrap();
+    assert_eq!((image.width, image.height), (2, 2));
+    //Flipped to bottom to top
+    assert_eq!(
+        image.data,
+        vec![
+            0, 0, 255, 255, 255, 255, 255, 255, //
+            255, 0, 0, 255, 0, 255, 0, 255, //
+        ]
+    );
+}
+
+#[test]
+fn test_parse_rle8() {
+    let mut header = info_header(4, 2, 8, COMPRESSION_RLE8);
+    header.num_colors = 2;
+    let palette = [0, 0, 255, 0, 255, 0, 0, 0];
+
+    let data = build_bmp(
+        header,
+        &palette,
+        &[
+            //3 times color 0, then absolute mode with a single pixel of color 1
+            3, 0, 0, 3, 1, 0, 0, 0, //
+            //End of line, then delta to the end of the second line and the end of the bitmap
+            0, 0, 0, 2, 4, 0, 0, 1,
+        ],
+    );
+    let image = parse(&data).unwrap();
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    assert_eq!(&image.data[..16], [red, red, red, blue].concat().as_slice());
+    assert_eq!(&image.data[16..], &[0; 16]);
+}
+
+#[test]
+fn test_parse_v5_bitfields() {
+    let mut header = info_header(1, 1, 32, COMPRESSION_BITFIELDS);
+    header.header_size = 124;
+
+    //Masks are stored inside of the header, right after the info header
+    let mut data = build_bmp(header, &[], &[0x11, 0x22, 0x33, 0x44]);
+    let masks = [0x0000_00FFu32, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000];
+    for (i, m) in masks.iter().enumerate() {
+        let start = HEADER_SIZE + i * 4;
+        data[start..start + 4].copy_from_slice(&m.to_le_bytes());
+    }
+
+    let image = parse(&data).unwrap();
+    assert_eq!(image.data, vec![0x11, 0x22, 0x33, 0x44]);
+}
+
+#[test]
+fn test_parse_invalid_dimensions() {
+    //Images without pixels
+    for (width, height) in [(0, 2), (2, 0), (0, -2)] {
+        let data = build_bmp(
+            info_header(width, height, 24, COMPRESSION_RGB),
+            &[],
+            &[0; 8],
+        );
+        assert!(parse(&data).is_err());
+    }
+
+    //Dimensions that don't fit into the data are rejected before the pixels are allocated
+    let data = build_bmp(
+        info_header(i32::MAX, -i32::MAX, 32, COMPRESSION_RGB),
+        &[],
+        &[0; 8],
+    );
+    assert!(parse(&data).is_err());
+
+    let mut header = info_header(65536, 65536, 8, COMPRESSION_RLE8);
+    header.num_colors = 1;
+    let data = build_bmp(header, &[0; 4], &[0, 1]);
+    assert!(parse(&data).is_err());
 }
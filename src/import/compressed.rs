@@ -0,0 +1,366 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+use lunar_png::{Image, ImageType};
+
+use super::{hdr::f32_to_f16, invalid};
+
+///Astc block decoding
+mod astc;
+///Bc1-7 block decoding
+mod bc;
+///Etc2 and eac block decoding
+mod etc;
+
+///An image stored in a gpu texture format, usually block compressed, together with its mips
+pub struct CompressedImage {
+    ///Format of the data
+    pub format: wgpu::TextureFormat,
+    ///Width of the largest mip
+    pub width: u32,
+    ///Height of the largest mip
+    pub height: u32,
+    ///Number of layers, 6 for cubemaps
+    pub layers: u32,
+    ///Number of mips of every layer
+    pub mip_count: u32,
+    ///Data of the layers one after another, each followed by its mips
+    pub data: Vec<u8>,
+}
+
+impl CompressedImage {
+    ///Size of a single layer of a mip level in bytes
+    ///
+    ///# Panics
+    ///Panics if the size does not fit into `usize`, which can't happen for validated images
+    #[must_use]
+    pub fn level_size(&self, level: u32) -> usize {
+        self.checked_level_size(level)
+            .expect("Mip level size does not fit into usize")
+    }
+
+    ///Size of a single layer of a mip level in bytes, `None` if it does not fit into `usize`
+    pub(crate) fn checked_level_size(&self, level: u32) -> Option<usize> {
+        level_size(self.format, self.width, self.height, level)
+    }
+
+    ///Size of all the layers and their mips in bytes, `None` if it does not fit into `usize`
+    pub(crate) fn checked_size(&self) -> Option<usize> {
+        (0..self.mip_count)
+            .try_fold(0usize, |size, l| {
+                size.checked_add(self.checked_level_size(l)?)
+            })?
+            .checked_mul(self.layers as usize)
+    }
+
+    ///Checks that the image is not empty and doesn't have more mips than its size allows
+    pub(crate) fn validate_dimensions(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        if self.width == 0 || self.height == 0 || self.layers == 0 || self.mip_count == 0 {
+            return Err(invalid("Texture is empty"));
+        }
+        if self.mip_count > 32 - self.width.max(self.height).leading_zeros() {
+            return Err(invalid("Texture has too many mips"));
+        }
+        Ok(())
+    }
+
+    ///Checks that the data contains all of the layers and mips
+    pub(crate) fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
+        self.validate_dimensions()?;
+
+        let size = self
+            .checked_size()
+            .ok_or_else(|| invalid("Texture is too large"))?;
+        if self.data.len() != size {
+            return Err(invalid("Texture data is incomplete"));
+        }
+        Ok(())
+    }
+
+    ///Returns whether the image can be uploaded as is to a device with the given features
+    ///
+    ///Compressed textures need the size to be a multiple of the block size
+    #[must_use]
+    pub fn is_supported(&self, features: wgpu::Features) -> bool {
+        let (block_width, block_height) = self.format.block_dimensions();
+        features.contains(self.format.required_features())
+            && self.width.is_multiple_of(block_width)
+            && self.height.is_multiple_of(block_height)
+    }
+
+    ///Decompresses the image on the cpu, keeping all of its layers and mips
+    ///
+    ///Low dynamic range unsigned formats are decompressed into
+    ///[`wgpu::TextureFormat::Rgba8Unorm`] or [`wgpu::TextureFormat::Rgba8UnormSrgb`], the rest
+    ///into [`wgpu::TextureFormat::Rgba16Float`]. Uncompressed images are returned as is
+    #[must_use]
+    pub fn decompress(self) -> Self {
+        if self.format.block_dimensions() == (1, 1) {
+            return self;
+        }
+
+        let format = decompressed_format(self.format);
+        let mut data = Vec::new();
+        let mut offset = 0;
+
+        for _ in 0..self.layers {
+            for level in 0..self.mip_count {
+                let size = self.level_size(level);
+                let (width, height) = mip_size(self.width, self.height, level);
+                let texels = decompress_level(
+                    self.format,
+                    &self.data[offset..offset + size],
+                    width as usize,
+                    height as usize,
+                );
+                encode_texels(&texels, format, &mut data);
+                offset += size;
+            }
+        }
+
+        Self {
+            format,
+            data,
+            ..self
+        }
+    }
+
+    ///Converts the first mip of the first layer into an rgba [`Image`], decompressing it if
+    ///needed, rows are stored in the same order as in the file
+    ///
+    ///16 bit float formats are converted into [`ImageType::Rgba16`] images with native byte order
+    ///half floats, everything else into [`ImageType::Rgba8`]
+    #[must_use]
+    pub fn to_image(self) -> Image {
+        let image = Self {
+            mip_count: 1,
+            layers: 1,
+            data: self.data[..self.level_size(0)].to_vec(),
+            ..self
+        }
+        .decompress();
+
+        Image {
+            img_type: if image.format == wgpu::TextureFormat::Rgba16Float {
+                ImageType::Rgba16
+            } else {
+                ImageType::Rgba8
+            },
+            width: image.width,
+            height: image.height,
+            data: image.data,
+        }
+    }
+}
+
+///Size of a mip level of the given format in bytes, `None` if it does not fit into `usize`
+pub(crate) fn level_size(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> Option<usize> {
+    let (block_width, block_height) = format.block_dimensions();
+    let (width, height) = mip_size(width, height, level);
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    //The number of blocks always fits into an u64, the size in bytes might not
+    let blocks = u64::from(width.div_ceil(block_width)) * u64::from(height.div_ceil(block_height));
+    usize::try_from(blocks.checked_mul(u64::from(block_size))?).ok()
+}
+
+///Size of a mip level in pixels
+const fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    let (width, height) = (
+        match width.checked_shr(level) {
+            Some(width) => width,
+            None => 0,
+        },
+        match height.checked_shr(level) {
+            Some(height) => height,
+            None => 0,
+        },
+    );
+    (
+        if width == 0 { 1 } else { width },
+        if height == 0 { 1 } else { height },
+    )
+}
+
+///Format a compressed format is decompressed into
+fn decompressed_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat as F;
+
+    match format {
+        F::Bc4RSnorm
+        | F::Bc5RgSnorm
+        | F::Bc6hRgbUfloat
+        | F::Bc6hRgbFloat
+        | F::EacR11Unorm
+        | F::EacR11Snorm
+        | F::EacRg11Unorm
+        | F::EacRg11Snorm
+        | F::Astc {
+            channel: wgpu::AstcChannel::Hdr,
+            ..
+        } => F::Rgba16Float,
+        _ if format.is_srgb() => F::Rgba8UnormSrgb,
+        _ => F::Rgba8Unorm,
+    }
+}
+
+///Decompresses a single mip level into rgba texels, rows are stored from top to bottom
+fn decompress_level(
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Vec<[f32; 4]> {
+    let (block_width, block_height) = format.block_dimensions();
+    let (block_width, block_height) = (block_width as usize, block_height as usize);
+    let block_size = format.block_copy_size(None).unwrap() as usize;
+    let blocks_x = width.div_ceil(block_width);
+
+    let mut texels = vec![[0.0; 4]; width * height];
+    let mut block_texels = vec![[0.0, 0.0, 0.0, 1.0]; block_width * block_height];
+
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        decode_block(format, block, &mut block_texels);
+
+        let (left, top) = (i % blocks_x * block_width, i / blocks_x * block_height);
+        for y in 0..block_height.min(height - top) {
+            for x in 0..block_width.min(width - left) {
+                texels[(top + y) * width + left + x] = block_texels[y * block_width + x];
+            }
+        }
+    }
+
+    texels
+}
+
+///Decodes a single block into texels, rows are stored from top to bottom
+fn decode_block(format: wgpu::TextureFormat, block: &[u8], texels: &mut [[f32; 4]]) {
+    use wgpu::TextureFormat as F;
+
+    match format {
+        F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => bc::decode_bc1(block, texels),
+        F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => bc::decode_bc2(block, texels),
+        F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => bc::decode_bc3(block, texels),
+        F::Bc4RUnorm => bc::decode_bc4(block, false, texels),
+        F::Bc4RSnorm => bc::decode_bc4(block, true, texels),
+        F::Bc5RgUnorm => bc::decode_bc5(block, false, texels),
+        F::Bc5RgSnorm => bc::decode_bc5(block, true, texels),
+        F::Bc6hRgbUfloat => bc::decode_bc6h(block, false, texels),
+        F::Bc6hRgbFloat => bc::decode_bc6h(block, true, texels),
+        F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => bc::decode_bc7(block, texels),
+        F::Etc2Rgb8Unorm | F::Etc2Rgb8UnormSrgb => etc::decode_etc2(block, false, texels),
+        F::Etc2Rgb8A1Unorm | F::Etc2Rgb8A1UnormSrgb => etc::decode_etc2(block, true, texels),
+        F::Etc2Rgba8Unorm | F::Etc2Rgba8UnormSrgb => etc::decode_etc2_rgba(block, texels),
+        F::EacR11Unorm => etc::decode_eac_r11(block, false, texels),
+        F::EacR11Snorm => etc::decode_eac_r11(block, true, texels),
+        F::EacRg11Unorm => etc::decode_eac_rg11(block, false, texels),
+        F::EacRg11Snorm => etc::decode_eac_rg11(block, true, texels),
+        F::Astc { channel, .. } => {
+            let (width, height) = format.block_dimensions();
+            astc::decode(
+                block,
+                width as usize,
+                height as usize,
+                channel == wgpu::AstcChannel::UnormSrgb,
+                texels,
+            );
+        }
+        _ => unreachable!("Not a compressed format"),
+    }
+}
+
+///Encodes texels into 8 bit unorm or 16 bit float data
+fn encode_texels(texels: &[[f32; 4]], format: wgpu::TextureFormat, out: &mut Vec<u8>) {
+    if format == wgpu::TextureFormat::Rgba16Float {
+        out.extend(
+            texels
+                .iter()
+                .flatten()
+                .flat_map(|v| f32_to_f16(*v).to_ne_bytes()),
+        );
+    } else {
+        out.extend(
+            texels
+                .iter()
+                .flatten()
+                .map(|v| (v * 255.0).round().clamp(0.0, 255.0) as u8),
+        );
+    }
+}
+
+#[test]
+fn test_decompress() {
+    //Red and blue bc1 endpoints with every index in the first row
+    let mut block = vec![0x00, 0xF8, 0x1F, 0x00, 0b1110_0100, 0, 0, 0];
+    //A 2x2 mip
+    block.extend_from_slice(&[0x00, 0xF8, 0x1F, 0x00, 0, 0, 0, 0]);
+
+    let image = CompressedImage {
+        format: wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+        width: 4,
+        height: 4,
+        layers: 1,
+        mip_count: 2,
+        data: block,
+    };
+    assert!(image.validate().is_ok());
+    assert!(image.is_supported(wgpu::Features::TEXTURE_COMPRESSION_BC));
+    assert!(!image.is_supported(wgpu::Features::empty()));
+
+    let image = image.decompress();
+    assert_eq!(image.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(image.data.len(), (16 + 4) * 4);
+    assert_eq!(
+        &image.data[..16],
+        &[
+            255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255
+        ]
+    );
+    assert_eq!(&image.data[64..], [255, 0, 0, 255].repeat(4));
+
+    //Astc void extent block with a single color
+    let mut block = vec![0xFC, 0xFD, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+    for channel in [0xFFFF_u16, 0x8080, 0, 0xFFFF] {
+        block.extend_from_slice(&channel.to_le_bytes());
+    }
+    let image = CompressedImage {
+        format: wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B6x6,
+            channel: wgpu::AstcChannel::Unorm,
+        },
+        width: 5,
+        height: 3,
+        layers: 1,
+        mip_count: 1,
+        data: block,
+    };
+    assert!(!image.is_supported(wgpu::Features::TEXTURE_COMPRESSION_ASTC));
+
+    let image = image.to_image();
+    assert!(matches!(image.img_type, ImageType::Rgba8));
+    assert_eq!((image.width, image.height), (5, 3));
+    assert_eq!(image.data, [255, 128, 0, 255].repeat(15));
+}
+
+#[test]
+fn test_validate_size_overflow() {
+    let mut image = CompressedImage {
+        format: wgpu::TextureFormat::Rgba32Float,
+        width: u32::MAX,
+        height: u32::MAX,
+        layers: 1,
+        mip_count: 1,
+        data: vec![0; 16],
+    };
+    assert_eq!(image.checked_level_size(0), None);
+    assert!(image.validate().is_err());
+
+    //Every level fits, but all of the layers don't
+    image.format = wgpu::TextureFormat::Bc1RgbaUnorm;
+    image.layers = u32::MAX;
+    assert!(image.checked_level_size(0).is_some());
+    assert_eq!(image.checked_size(), None);
+    assert!(image.validate().is_err());
+}
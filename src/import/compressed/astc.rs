@@ -0,0 +1,890 @@
+#![allow(
+    clippy::cast_possible_wrap,
+    clippy::cast_precision_loss,
+    clippy::many_single_char_names
+)]
+use crate::import::hdr::f16_to_f32;
+
+///Color of blocks that fail to decode
+const ERROR_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+///Number of trits, quints and bits of every value of the integer sequence encoding ranges
+///
+///The ranges are 2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192 and
+///256, weights can only use the first 12
+const RANGES: [(u32, u32, u32); 21] = [
+    (0, 0, 1),
+    (1, 0, 0),
+    (0, 0, 2),
+    (0, 1, 0),
+    (1, 0, 1),
+    (0, 0, 3),
+    (0, 1, 1),
+    (1, 0, 2),
+    (0, 0, 4),
+    (0, 1, 2),
+    (1, 0, 3),
+    (0, 0, 5),
+    (0, 1, 3),
+    (1, 0, 4),
+    (0, 0, 6),
+    (0, 1, 4),
+    (1, 0, 5),
+    (0, 0, 7),
+    (0, 1, 5),
+    (1, 0, 6),
+    (0, 0, 8),
+];
+
+///Reads `count` bits starting at `start`, bits past `end` are read as zeros
+fn read(data: u128, start: u32, count: u32, end: u32) -> u32 {
+    if start >= end || count == 0 {
+        return 0;
+    }
+    let count = count.min(end - start);
+    ((data >> start) & ((1 << count) - 1)) as u32
+}
+
+///Number of bits used by `count` values of the range
+const fn sequence_size(count: u32, range: usize) -> u32 {
+    let (trits, quints, bits) = RANGES[range];
+    count * bits + trits * (8 * count).div_ceil(5) + quints * (7 * count).div_ceil(3)
+}
+
+///Decodes 5 trits out of 8 bits
+const fn decode_trits(t: u32) -> [u32; 5] {
+    let (c, t4, t3) = if (t >> 2) & 7 == 7 {
+        ((((t >> 5) & 7) << 2) | (t & 3), 2, 2)
+    } else if (t >> 5) & 3 == 3 {
+        (t & 0x1F, 2, (t >> 7) & 1)
+    } else {
+        (t & 0x1F, (t >> 7) & 1, (t >> 5) & 3)
+    };
+
+    let (t2, t1, t0) = if c & 3 == 3 {
+        (
+            2,
+            (c >> 4) & 1,
+            (((c >> 3) & 1) << 1) | ((c >> 2) & 1 & !(c >> 3)),
+        )
+    } else if (c >> 2) & 3 == 3 {
+        (2, 2, c & 3)
+    } else {
+        ((c >> 4) & 1, (c >> 2) & 3, (c & 2) | (c & 1 & !(c >> 1)))
+    };
+
+    [t0, t1, t2, t3, t4]
+}
+
+///Decodes 3 quints out of 7 bits
+const fn decode_quints(q: u32) -> [u32; 3] {
+    if (q >> 1) & 3 == 3 && (q >> 5).trailing_zeros() >= 2 {
+        let q2 = ((q & 1) << 2) | (((q >> 4) & 1 & !q) << 1) | ((q >> 3) & 1 & !q);
+        return [4, 4, q2];
+    }
+
+    let (q2, c) = if (q >> 1) & 3 == 3 {
+        (4, (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | (q & 1))
+    } else {
+        ((q >> 5) & 3, q & 0x1F)
+    };
+    let (q1, q0) = if c & 7 == 5 {
+        (4, (c >> 3) & 3)
+    } else {
+        ((c >> 3) & 3, c & 7)
+    };
+
+    [q0, q1, q2]
+}
+
+///Decodes an integer sequence, returns the bits and the trit or quint of every value
+fn decode_sequence(data: u128, start: u32, count: u32, range: usize) -> Vec<(u32, u32)> {
+    let (trits, quints, bits) = RANGES[range];
+    let end = start + sequence_size(count, range);
+    let mut position = start;
+    let mut next = |count: u32| {
+        let value = read(data, position, count, end);
+        position += count;
+        value
+    };
+
+    let mut values = Vec::with_capacity(count as usize + 4);
+    while values.len() < count as usize {
+        if trits != 0 {
+            let mut m = [0; 5];
+            let mut t = 0;
+            for (i, (shift, size)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)]
+                .into_iter()
+                .enumerate()
+            {
+                m[i] = next(bits);
+                t |= next(size) << shift;
+            }
+            values.extend(m.into_iter().zip(decode_trits(t)));
+        } else if quints != 0 {
+            let mut m = [0; 3];
+            let mut q = 0;
+            for (i, (shift, size)) in [(0, 3), (3, 2), (5, 2)].into_iter().enumerate() {
+                m[i] = next(bits);
+                q |= next(size) << shift;
+            }
+            values.extend(m.into_iter().zip(decode_quints(q)));
+        } else {
+            values.push((next(bits), 0));
+        }
+    }
+    values.truncate(count as usize);
+
+    values
+}
+
+///Repeats the bits of a value to fill the given number of bits
+const fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let mut out = 0;
+    let mut remaining = to;
+    while remaining >= bits {
+        remaining -= bits;
+        out |= value << remaining;
+    }
+    out | (value >> (bits - remaining))
+}
+
+///Unquantizes a color endpoint value into the 0..=255 range
+const fn unquantize_color(range: usize, (m, tq): (u32, u32)) -> u32 {
+    let (trits, _, bits) = RANGES[range];
+    if RANGES[range].0 == 0 && RANGES[range].1 == 0 {
+        return replicate(m, bits, 8);
+    }
+
+    let a = if m & 1 == 1 { 0x1FF } else { 0 };
+    let v = m >> 1;
+    let (b, c) = match (trits, bits) {
+        (1, 1) => (0, 204),
+        (1, 2) => (
+            ((v & 1) << 8) | ((v & 1) << 4) | ((v & 1) << 2) | ((v & 1) << 1),
+            93,
+        ),
+        (1, 3) => ((v << 7) | (v << 2) | v, 44),
+        (1, 4) => ((v << 6) | v, 22),
+        (1, 5) => ((v << 5) | (v >> 2), 11),
+        (1, _) => ((v << 4) | (v >> 4), 5),
+        (_, 1) => (0, 113),
+        (_, 2) => (((v & 1) << 8) | ((v & 1) << 3) | ((v & 1) << 2), 54),
+        (_, 3) => ((v << 7) | (v << 1) | (v >> 1), 26),
+        (_, 4) => ((v << 6) | (v >> 1), 13),
+        _ => ((v << 5) | (v >> 3), 6),
+    };
+
+    let t = (tq * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+///Unquantizes a weight into the 0..=64 range
+const fn unquantize_weight(range: usize, (m, tq): (u32, u32)) -> u32 {
+    let (trits, quints, bits) = RANGES[range];
+    let value = if trits == 0 && quints == 0 {
+        replicate(m, bits, 6)
+    } else if bits == 0 {
+        if trits == 1 {
+            [0, 32, 63][tq as usize]
+        } else {
+            [0, 16, 32, 47, 63][tq as usize]
+        }
+    } else {
+        let a = if m & 1 == 1 { 0x7F } else { 0 };
+        let v = m >> 1;
+        let (b, c) = match (trits, bits) {
+            (1, 1) => (0, 50),
+            (_, 1) => (0, 28),
+            (1, 2) => (((v & 1) << 6) | ((v & 1) << 2) | (v & 1), 23),
+            (_, 2) => (((v & 1) << 6) | ((v & 1) << 1), 13),
+            _ => ((v << 5) | v, 11),
+        };
+        let t = (tq * c + b) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+
+    if value > 32 { value + 1 } else { value }
+}
+
+///Weight grid of a block
+struct Grid {
+    width: usize,
+    height: usize,
+    dual_plane: bool,
+    range: usize,
+}
+
+///Decodes the 11 bit block mode
+const fn decode_block_mode(mode: u32) -> Option<Grid> {
+    let a = (mode >> 5) & 3;
+    let mut high_precision = (mode >> 9) & 1;
+    let mut dual_plane = (mode >> 10) & 1;
+    let mut range = (mode >> 4) & 1;
+
+    let (width, height) = if mode.trailing_zeros() >= 2 {
+        range |= ((mode >> 2) & 3) << 1;
+        if (mode >> 2).trailing_zeros() >= 2 {
+            return None;
+        }
+        let b = (mode >> 9) & 3;
+        match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                dual_plane = 0;
+                high_precision = 0;
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        }
+    } else {
+        range |= (mode & 3) << 1;
+        let b = (mode >> 7) & 3;
+        match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 != 0 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        }
+    };
+
+    let range = (range - 2 + 6 * high_precision) as usize;
+    let count = width * height * (dual_plane + 1);
+    if count > 64 {
+        return None;
+    }
+    let bits = sequence_size(count, range);
+    if bits < 24 || bits > 96 {
+        return None;
+    }
+
+    Some(Grid {
+        width: width as usize,
+        height: height as usize,
+        dual_plane: dual_plane == 1,
+        range,
+    })
+}
+
+///Hash used for selecting partitions
+const fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+///Returns the partition of a texel
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+
+    //Only the seeds affecting x and y are needed for 2d blocks
+    let mut seeds = [0, 4, 8, 12, 16, 20, 24, 28].map(|s| ((rnum >> s) & 0xF) as u8);
+    for s in &mut seeds {
+        *s *= *s;
+    }
+
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (
+            if seed & 2 == 2 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 == 2 { 4 } else { 5 },
+        )
+    };
+    let s = |i: usize| u32::from(seeds[i] >> if i.is_multiple_of(2) { sh1 } else { sh2 });
+
+    let a = (s(0) * x + s(1) * y + (rnum >> 14)) & 0x3F;
+    let b = (s(2) * x + s(3) * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions < 3 {
+        0
+    } else {
+        (s(4) * x + s(5) * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if partitions < 4 {
+        0
+    } else {
+        (s(6) * x + s(7) * y + (rnum >> 2)) & 0x3F
+    };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+///Moves the top bit of `b` into `a`, turning `a` into a signed 6 bit offset
+const fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    (if a & 0x20 == 0 { a } else { a - 0x40 }, b)
+}
+
+///Moves blue into red and green, used to gain precision for blue-ish colors
+const fn blue_contract([r, g, b, a]: [i32; 4]) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+///Endpoints of a partition
+struct Endpoints {
+    ///Low dynamic range channels are 8 bit, high dynamic range ones are 12 bit
+    values: [[i32; 4]; 2],
+    ///Whether each channel is high dynamic range
+    hdr: [bool; 4],
+}
+
+impl Endpoints {
+    const fn ldr(e0: [i32; 4], e1: [i32; 4]) -> Self {
+        Self {
+            values: [e0, e1],
+            hdr: [false; 4],
+        }
+    }
+
+    ///Endpoints with hdr rgb and an alpha of 1
+    const fn hdr(e0: [i32; 3], e1: [i32; 3]) -> Self {
+        Self {
+            values: [[e0[0], e0[1], e0[2], 0xFF], [e1[0], e1[1], e1[2], 0xFF]],
+            hdr: [true, true, true, false],
+        }
+    }
+}
+
+///Decodes hdr rgb endpoints of mode 7, base and scale
+fn hdr_rgb_scale(v: &[i32]) -> Endpoints {
+    let mode_value = ((v[0] >> 6) & 3) | ((v[1] >> 5) & 4) | ((v[2] >> 4) & 8);
+    let (major, mode) = if mode_value & 0xC != 0xC {
+        (mode_value >> 2, mode_value & 3)
+    } else if mode_value != 0xF {
+        (mode_value & 3, 4)
+    } else {
+        (0, 5)
+    };
+
+    let mut red = v[0] & 0x3F;
+    let mut green = v[1] & 0x1F;
+    let mut blue = v[2] & 0x1F;
+    let mut scale = v[3] & 0x1F;
+
+    //The remaining bits are spread out differently in every mode
+    match mode {
+        0 => {
+            scale |= v[3] & 0x60;
+            red |= ((v[3] >> 1) & 0x40)
+                | ((v[2] << 1) & 0x80)
+                | ((v[1] << 3) & 0x300)
+                | ((v[2] << 5) & 0x400);
+        }
+        1 => {
+            green |= v[1] & 0x20;
+            blue |= v[2] & 0x20;
+            red |= ((v[3] >> 1) & 0x40)
+                | ((v[2] << 1) & 0x80)
+                | ((v[1] << 2) & 0x100)
+                | ((v[3] << 4) & 0x600);
+        }
+        2 => {
+            scale |= v[3] & 0xE0;
+            red |= ((v[2] << 1) & 0xC0) | ((v[1] << 3) & 0x300);
+        }
+        3 => {
+            green |= v[1] & 0x20;
+            blue |= v[2] & 0x20;
+            scale |= v[3] & 0x60;
+            red |= ((v[3] >> 1) & 0x40) | ((v[2] << 1) & 0x80) | ((v[1] << 2) & 0x100);
+        }
+        4 => {
+            green |= v[1] & 0x60;
+            blue |= v[2] & 0x60;
+            scale |= v[3] & 0x20;
+            red |= ((v[3] >> 1) & 0x40) | ((v[3] << 1) & 0x80);
+        }
+        _ => {
+            green |= v[1] & 0x60;
+            blue |= v[2] & 0x60;
+            scale |= v[3] & 0x60;
+            red |= (v[3] >> 1) & 0x40;
+        }
+    }
+
+    let shift = [1, 1, 2, 3, 4, 5][mode as usize];
+    red <<= shift;
+    green <<= shift;
+    blue <<= shift;
+    scale <<= shift;
+
+    if mode != 5 {
+        green = red - green;
+        blue = red - blue;
+    }
+
+    let mut e1 = [red, green, blue];
+    match major {
+        1 => e1.swap(0, 1),
+        2 => e1.swap(0, 2),
+        _ => {}
+    }
+    let e0 = e1.map(|c| (c - scale).clamp(0, 0xFFF));
+    Endpoints::hdr(e0, e1.map(|c| c.clamp(0, 0xFFF)))
+}
+
+///Decodes hdr rgb endpoints of modes 11, 14 and 15
+fn hdr_rgb(v: &[i32]) -> Endpoints {
+    let major = ((v[4] >> 7) & 1) | ((v[5] >> 6) & 2);
+    if major == 3 {
+        return Endpoints::hdr(
+            [v[0] << 4, v[2] << 4, (v[4] & 0x7F) << 5],
+            [v[1] << 4, v[3] << 4, (v[5] & 0x7F) << 5],
+        );
+    }
+
+    let mode = ((v[1] >> 7) & 1) | ((v[2] >> 6) & 2) | ((v[3] >> 5) & 4);
+    let mut a = v[0] | ((v[1] & 0x40) << 2);
+    let mut b0 = v[2] & 0x3F;
+    let mut b1 = v[3] & 0x3F;
+    let mut c = v[1] & 0x3F;
+
+    let d_bits = [7, 6, 7, 6, 5, 6, 5, 6][mode as usize];
+    let sign_extend = |value: i32| {
+        let shift = 32 - d_bits;
+        ((value & ((1 << d_bits) - 1)) << shift) >> shift
+    };
+    let mut d0 = sign_extend(v[4]);
+    let mut d1 = sign_extend(v[5]);
+
+    let bit = |value: i32, bit: u32| (value >> bit) & 1;
+    match mode {
+        0 => {
+            b0 |= bit(v[2], 6) << 6;
+            b1 |= bit(v[3], 6) << 6;
+        }
+        1 => {
+            b0 |= (bit(v[2], 6) << 6) | (bit(v[4], 6) << 7);
+            b1 |= (bit(v[3], 6) << 6) | (bit(v[5], 6) << 7);
+        }
+        2 => {
+            a |= bit(v[2], 6) << 9;
+            c |= bit(v[3], 6) << 6;
+        }
+        3 => {
+            a |= bit(v[4], 6) << 9;
+            c |= bit(v[5], 6) << 6;
+            b0 |= bit(v[2], 6) << 6;
+            b1 |= bit(v[3], 6) << 6;
+        }
+        4 => {
+            a |= (bit(v[4], 5) << 9) | (bit(v[5], 5) << 10);
+            b0 |= (bit(v[2], 6) << 6) | (bit(v[4], 6) << 7);
+            b1 |= (bit(v[3], 6) << 6) | (bit(v[5], 6) << 7);
+        }
+        5 => {
+            a |= (bit(v[2], 6) << 9) | (bit(v[3], 6) << 10);
+            c |= (bit(v[5], 6) << 6) | (bit(v[4], 6) << 7);
+        }
+        6 => {
+            a |= (bit(v[4], 5) << 9) | (bit(v[5], 5) << 10) | (bit(v[4], 6) << 11);
+            c |= bit(v[5], 6) << 6;
+            b0 |= bit(v[2], 6) << 6;
+            b1 |= bit(v[3], 6) << 6;
+        }
+        _ => {
+            a |= (bit(v[2], 6) << 9) | (bit(v[3], 6) << 10) | (bit(v[4], 6) << 11);
+            c |= bit(v[5], 6) << 6;
+        }
+    }
+
+    let shift = (mode >> 1) ^ 3;
+    a <<= shift;
+    b0 <<= shift;
+    b1 <<= shift;
+    c <<= shift;
+    d0 <<= shift;
+    d1 <<= shift;
+
+    let mut e0 = [a - c, a - b0 - c - d0, a - b1 - c - d1];
+    let mut e1 = [a, a - b0, a - b1];
+    match major {
+        1 => {
+            e0.swap(0, 1);
+            e1.swap(0, 1);
+        }
+        2 => {
+            e0.swap(0, 2);
+            e1.swap(0, 2);
+        }
+        _ => {}
+    }
+    Endpoints::hdr(e0.map(|c| c.clamp(0, 0xFFF)), e1.map(|c| c.clamp(0, 0xFFF)))
+}
+
+///Decodes the hdr alpha of mode 15
+fn hdr_alpha(v6: i32, v7: i32) -> [i32; 2] {
+    let selector = ((v6 >> 7) & 1) | ((v7 >> 6) & 2);
+    let (v6, v7) = (v6 & 0x7F, v7 & 0x7F);
+    if selector == 3 {
+        return [v6 << 5, v7 << 5];
+    }
+
+    let a0 = v6 | ((v7 << (selector + 1)) & 0x780);
+    let a1 = ((v7 & (0x3F >> selector)) ^ (0x20 >> selector)) - (0x20 >> selector);
+    let (a0, a1) = (a0 << (4 - selector), a1 << (4 - selector));
+    [a0, (a0 + a1).clamp(0, 0xFFF)]
+}
+
+///Decodes the endpoints of a partition
+#[allow(clippy::too_many_lines)]
+fn decode_endpoints(mode: u32, v: &[i32]) -> Endpoints {
+    match mode {
+        0 => Endpoints::ldr([v[0], v[0], v[0], 0xFF], [v[1], v[1], v[1], 0xFF]),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(0xFF);
+            Endpoints::ldr([l0, l0, l0, 0xFF], [l1, l1, l1, 0xFF])
+        }
+        2 => {
+            let (y0, y1) = if v[1] >= v[0] {
+                (v[0] << 4, v[1] << 4)
+            } else {
+                ((v[1] << 4) + 8, (v[0] << 4) - 8)
+            };
+            Endpoints::hdr([y0; 3], [y1; 3])
+        }
+        3 => {
+            let (y0, d) = if v[0] & 0x80 == 0 {
+                (
+                    ((v[1] & 0xF0) << 4) | ((v[0] & 0x7F) << 1),
+                    (v[1] & 0x0F) << 1,
+                )
+            } else {
+                (
+                    ((v[1] & 0xE0) << 4) | ((v[0] & 0x7F) << 2),
+                    (v[1] & 0x1F) << 2,
+                )
+            };
+            Endpoints::hdr([y0; 3], [(y0 + d).min(0xFFF); 3])
+        }
+        4 => Endpoints::ldr([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+            let (d0, b0) = bit_transfer_signed(v[1], v[0]);
+            let (d1, b1) = bit_transfer_signed(v[3], v[2]);
+            let l1 = (b0 + d0).clamp(0, 0xFF);
+            Endpoints::ldr([b0, b0, b0, b1], [l1, l1, l1, (b1 + d1).clamp(0, 0xFF)])
+        }
+        6 => Endpoints::ldr(
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                0xFF,
+            ],
+            [v[0], v[1], v[2], 0xFF],
+        ),
+        7 => hdr_rgb_scale(v),
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 {
+                (v[6], v[7])
+            } else {
+                (0xFF, 0xFF)
+            };
+            let e0 = [v[0], v[2], v[4], a0];
+            let e1 = [v[1], v[3], v[5], a1];
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                Endpoints::ldr(e0, e1)
+            } else {
+                Endpoints::ldr(blue_contract(e1), blue_contract(e0))
+            }
+        }
+        9 | 13 => {
+            let mut base = [0; 4];
+            let mut offset = [0; 4];
+            let channels = if mode == 13 { 4 } else { 3 };
+            for c in 0..channels {
+                (offset[c], base[c]) = bit_transfer_signed(v[c * 2 + 1], v[c * 2]);
+            }
+            if mode == 9 {
+                base[3] = 0xFF;
+            }
+            let sum = std::array::from_fn(|c| base[c] + offset[c]);
+            let (e0, e1) = if offset[0] + offset[1] + offset[2] >= 0 {
+                (base, sum)
+            } else {
+                (blue_contract(sum), blue_contract(base))
+            };
+            Endpoints::ldr(e0.map(|c| c.clamp(0, 0xFF)), e1.map(|c| c.clamp(0, 0xFF)))
+        }
+        10 => Endpoints::ldr(
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ),
+        11 => hdr_rgb(v),
+        14 => {
+            let mut endpoints = hdr_rgb(v);
+            endpoints.values[0][3] = v[6];
+            endpoints.values[1][3] = v[7];
+            endpoints
+        }
+        _ => {
+            let mut endpoints = hdr_rgb(v);
+            [endpoints.values[0][3], endpoints.values[1][3]] = hdr_alpha(v[6], v[7]);
+            endpoints.hdr[3] = true;
+            endpoints
+        }
+    }
+}
+
+///Converts a 16 bit logarithmic value into a half float
+const fn lns_to_f16(value: u32) -> u16 {
+    let mantissa = value & 0x7FF;
+    let exponent = value >> 11;
+    let mantissa = if mantissa < 512 {
+        3 * mantissa
+    } else if mantissa < 1536 {
+        4 * mantissa - 512
+    } else {
+        5 * mantissa - 2048
+    };
+    let value = (exponent << 10) | (mantissa >> 3);
+    if value > 0x7BFF { 0x7BFF } else { value as u16 }
+}
+
+///Decodes a void extent block, which has a single color
+fn decode_void_extent(data: u128, srgb: bool, out: &mut [[f32; 4]]) {
+    let hdr = (data >> 9) & 1 == 1;
+    let color: [f32; 4] = std::array::from_fn(|c| {
+        let value = (data >> (64 + c * 16)) as u16;
+        if hdr {
+            f16_to_f32(value)
+        } else if srgb {
+            f32::from(value >> 8) / 255.0
+        } else {
+            f32::from(value) / 65535.0
+        }
+    });
+    out.fill(color);
+}
+
+///Infills the weights of a plane from the weight grid for every texel
+fn infill_weights(
+    weights: &[u32],
+    grid: &Grid,
+    plane: usize,
+    width: usize,
+    height: usize,
+) -> Vec<u32> {
+    let planes = if grid.dual_plane { 2 } else { 1 };
+    let weight = |i: usize| weights.get(i * planes + plane).copied().unwrap_or(0);
+    let ds = (1024 + width / 2) / (width - 1);
+    let dt = (1024 + height / 2) / (height - 1);
+
+    let mut out = Vec::with_capacity(width * height);
+    for t in 0..height {
+        for s in 0..width {
+            let gs = (ds * s * (grid.width - 1) + 32) >> 6;
+            let gt = (dt * t * (grid.height - 1) + 32) >> 6;
+            let (js, fs) = (gs >> 4, gs & 0xF);
+            let (jt, ft) = (gt >> 4, gt & 0xF);
+
+            let w11 = (fs * ft + 8) >> 4;
+            let w10 = ft - w11;
+            let w01 = fs - w11;
+            let w00 = 16 - fs - ft + w11;
+
+            let v0 = js + jt * grid.width;
+            let sum = weight(v0) as usize * w00
+                + weight(v0 + 1) as usize * w01
+                + weight(v0 + grid.width) as usize * w10
+                + weight(v0 + grid.width + 1) as usize * w11;
+            out.push(((sum + 8) >> 4) as u32);
+        }
+    }
+    out
+}
+
+///Decodes an astc block of the given size, invalid blocks are decoded as magenta
+pub(super) fn decode(block: &[u8], width: usize, height: usize, srgb: bool, out: &mut [[f32; 4]]) {
+    if decode_block(block, width, height, srgb, out).is_none() {
+        out.fill(ERROR_COLOR);
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn decode_block(
+    block: &[u8],
+    width: usize,
+    height: usize,
+    srgb: bool,
+    out: &mut [[f32; 4]],
+) -> Option<()> {
+    let data = u128::from_le_bytes(block[..16].try_into().unwrap());
+    let mode = data as u32 & 0x7FF;
+
+    if mode & 0x1FF == 0x1FC {
+        decode_void_extent(data, srgb, out);
+        return Some(());
+    }
+
+    let grid = decode_block_mode(mode)?;
+    if grid.width > width || grid.height > height {
+        return None;
+    }
+
+    let partitions = read(data, 11, 2, 128) + 1;
+    if grid.dual_plane && partitions == 4 {
+        return None;
+    }
+
+    let weight_count = (grid.width * grid.height) as u32 * if grid.dual_plane { 2 } else { 1 };
+    let weight_bits = sequence_size(weight_count, grid.range);
+    let mut below_weights = 128 - weight_bits;
+
+    let mut modes = [0; 4];
+    let color_start = if partitions == 1 {
+        modes[0] = read(data, 13, 4, 128);
+        17
+    } else {
+        let encoded = read(data, 23, 6, 128);
+        if encoded.trailing_zeros() >= 2 {
+            modes = [encoded >> 2; 4];
+        } else {
+            //The rest of the modes is stored below the weights
+            let extra = 3 * partitions - 4;
+            below_weights = below_weights.checked_sub(extra)?;
+            let encoded = encoded | (read(data, below_weights, extra, 128) << 6);
+            let class = (encoded & 3) - 1;
+
+            for (i, mode) in modes.iter_mut().take(partitions as usize).enumerate() {
+                let c = (encoded >> (2 + i)) & 1;
+                let m = (encoded >> (2 + partitions as usize + 2 * i)) & 3;
+                *mode = ((class + c) << 2) | m;
+            }
+        }
+        29
+    };
+    let modes = &modes[..partitions as usize];
+
+    let plane_channel = if grid.dual_plane {
+        below_weights = below_weights.checked_sub(2)?;
+        read(data, below_weights, 2, 128) as usize
+    } else {
+        4
+    };
+
+    let value_count = modes.iter().map(|m| ((m >> 2) + 1) * 2).sum::<u32>();
+    if value_count > 18 || color_start > below_weights {
+        return None;
+    }
+    let available = below_weights - color_start;
+    let color_range = (0..RANGES.len())
+        .rev()
+        .find(|r| sequence_size(value_count, *r) <= available)?;
+    //At least a range of 6 is required
+    if color_range < 4 {
+        return None;
+    }
+
+    let values = decode_sequence(data, color_start, value_count, color_range)
+        .into_iter()
+        .map(|v| unquantize_color(color_range, v) as i32)
+        .collect::<Vec<_>>();
+    let mut offset = 0;
+    let endpoints = modes
+        .iter()
+        .map(|m| {
+            let endpoints = decode_endpoints(*m, &values[offset..]);
+            offset += ((m >> 2) as usize + 1) * 2;
+            endpoints
+        })
+        .collect::<Vec<_>>();
+
+    //Weights are stored in reverse, starting from the top of the block
+    let weights = decode_sequence(data.reverse_bits(), 0, weight_count, grid.range)
+        .into_iter()
+        .map(|w| unquantize_weight(grid.range, w))
+        .collect::<Vec<_>>();
+    let planes = [
+        infill_weights(&weights, &grid, 0, width, height),
+        if grid.dual_plane {
+            infill_weights(&weights, &grid, 1, width, height)
+        } else {
+            Vec::new()
+        },
+    ];
+
+    let seed = read(data, 13, 10, 128);
+    let small_block = width * height < 31;
+    for (i, texel) in out.iter_mut().enumerate().take(width * height) {
+        let partition = if partitions == 1 {
+            0
+        } else {
+            select_partition(
+                seed,
+                (i % width) as u32,
+                (i / width) as u32,
+                partitions,
+                small_block,
+            )
+        };
+        let endpoints = &endpoints[partition];
+
+        for c in 0..4 {
+            let weight = if c == plane_channel {
+                planes[1][i]
+            } else {
+                planes[0][i]
+            };
+            let [e0, e1] = endpoints.values.map(|e| {
+                let e = e[c] as u32;
+                if endpoints.hdr[c] {
+                    e << 4
+                } else if srgb {
+                    (e << 8) | 0x80
+                } else {
+                    (e << 8) | e
+                }
+            });
+            let value = (e0 * (64 - weight) + e1 * weight + 32) >> 6;
+
+            texel[c] = if endpoints.hdr[c] {
+                f16_to_f32(lns_to_f16(value))
+            } else if srgb {
+                (value >> 8) as f32 / 255.0
+            } else {
+                value as f32 / 65535.0
+            };
+        }
+    }
+
+    Some(())
+}
@@ -0,0 +1,1002 @@
+#![allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
+use crate::import::hdr::f16_to_f32;
+
+///Subset of every pixel of the partitions with 2 subsets, a bit per pixel
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+///Subset of every pixel of the partitions with 3 subsets, 2 bits per pixel
+const PARTITIONS_3: [u32; 64] = [
+    0xAA68_5050,
+    0x6A5A_5040,
+    0x5A5A_4200,
+    0x5450_A0A8,
+    0xA5A5_0000,
+    0xA0A0_5050,
+    0x5555_A0A0,
+    0x5A5A_5050,
+    0xAA55_0000,
+    0xAA55_5500,
+    0xAAAA_5500,
+    0x9090_9090,
+    0x9494_9494,
+    0xA4A4_A4A4,
+    0xA9A5_9450,
+    0x2A0A_4250,
+    0xA594_5040,
+    0x0A42_5054,
+    0xA5A5_A500,
+    0x55A0_A0A0,
+    0xA8A8_5454,
+    0x6A6A_4040,
+    0xA4A4_5000,
+    0x1A1A_0500,
+    0x0050_A4A4,
+    0xAAA5_9090,
+    0x1469_6914,
+    0x6969_1400,
+    0xA085_85A0,
+    0xAA82_1414,
+    0x50A4_A450,
+    0x6A5A_0200,
+    0xA9A5_8000,
+    0x5090_A0A8,
+    0xA8A0_9050,
+    0x2424_2424,
+    0x00AA_5500,
+    0x2492_4924,
+    0x2449_9224,
+    0x50A5_0A50,
+    0x500A_A550,
+    0xAAAA_4444,
+    0x6666_0000,
+    0xA5A0_A5A0,
+    0x50A0_50A0,
+    0x6928_6928,
+    0x44AA_AA44,
+    0x6666_6600,
+    0xAA44_4444,
+    0x54A8_54A8,
+    0x9580_9580,
+    0x9696_9600,
+    0xA854_54A8,
+    0x8095_9580,
+    0xAA14_1414,
+    0x9696_0000,
+    0xAAAA_1414,
+    0xA050_50A0,
+    0xA0A5_A5A0,
+    0x9600_0000,
+    0x4080_4080,
+    0xA9A8_A9A8,
+    0xAAAA_AA44,
+    0x2A4A_5254,
+];
+
+///Anchor pixel of the second subset of the partitions with 2 subsets
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+///Anchor pixels of the second and third subsets of the partitions with 3 subsets
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+///Interpolation weights for 2, 3 and 4 bit indices
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+///Reads bits of a 16 byte block, starting from the least significant bit of the first byte
+struct BitReader {
+    data: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            data: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    const fn read(&mut self, count: u32) -> u32 {
+        if count == 0 || self.position >= 128 {
+            return 0;
+        }
+        let value = (self.data >> self.position) as u32 & (u32::MAX >> (32 - count));
+        self.position += count;
+        value
+    }
+}
+
+///Returns the weights table for indices of the given size
+const fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+///Returns the subset of a pixel
+fn subset(subsets: u32, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => usize::from(PARTITIONS_2[partition] >> pixel) & 1,
+        3 => (PARTITIONS_3[partition] >> (pixel * 2)) as usize & 3,
+        _ => 0,
+    }
+}
+
+///Returns whether the pixel is the anchor of its subset, anchors are stored with one bit less
+fn is_anchor(subsets: u32, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => usize::from(ANCHORS_2[partition]) == pixel,
+            3 => ANCHORS_3[partition].contains(&(pixel as u8)),
+            _ => false,
+        }
+}
+
+///Interpolates between two endpoints using a weight in the 0..=64 range
+const fn interpolate(a: u32, b: u32, weight: u32) -> u32 {
+    (a * (64 - weight) + b * weight + 32) >> 6
+}
+
+fn unorm8(value: u32) -> f32 {
+    value as f32 / 255.0
+}
+
+///Expands an rgb565 color into 8 bit channels
+const fn rgb565(color: u16) -> [u32; 3] {
+    let (r, g, b) = (
+        (color >> 11) as u32,
+        ((color >> 5) & 0x3F) as u32,
+        (color & 0x1F) as u32,
+    );
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+///Decodes the color part of bc1-3 blocks, `punch_through` enables the transparent mode of bc1
+fn decode_color(block: &[u8], punch_through: bool, out: &mut [[f32; 4]]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let three_colors = punch_through && c0 <= c1;
+
+    let mut palette = [[0.0, 0.0, 0.0, 1.0]; 4];
+    for c in 0..3 {
+        let (a, b) = (e0[c], e1[c]);
+        palette[0][c] = unorm8(a);
+        palette[1][c] = unorm8(b);
+        if three_colors {
+            palette[2][c] = unorm8(u32::midpoint(a, b));
+        } else {
+            palette[2][c] = unorm8((2 * a + b) / 3);
+            palette[3][c] = unorm8((a + 2 * b) / 3);
+        }
+    }
+    if three_colors {
+        palette[3] = [0.0; 4];
+    }
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2)) as usize & 3];
+    }
+}
+
+///Decodes an 8 byte bc4 block into a channel of the texels
+fn decode_channel(block: &[u8], signed: bool, channel: usize, out: &mut [[f32; 4]]) {
+    let (a, b, min, max) = if signed {
+        let value = |v: u8| i32::from((v as i8).max(-127));
+        (value(block[0]), value(block[1]), -127, 127)
+    } else {
+        (i32::from(block[0]), i32::from(block[1]), 0, 255)
+    };
+
+    let mut palette = [a, b, min, max, min, max, min, max].map(|v| v as f32);
+    if a > b {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i) as f32).mul_add(a as f32, i as f32 * b as f32) / 7.0;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i) as f32).mul_add(a as f32, i as f32 * b as f32) / 5.0;
+        }
+    }
+
+    let indices = u64::from_le_bytes([
+        block[2], block[3], block[4], block[5], block[6], block[7], 0, 0,
+    ]);
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[channel] = palette[(indices >> (i * 3)) as usize & 7] / max as f32;
+    }
+}
+
+///Decodes a bc1 block, 4 color blocks are opaque and 3 color blocks have transparent black
+pub(super) fn decode_bc1(block: &[u8], out: &mut [[f32; 4]]) {
+    decode_color(block, true, out);
+}
+
+///Decodes a bc2 block, which has explicit 4 bit alpha
+pub(super) fn decode_bc2(block: &[u8], out: &mut [[f32; 4]]) {
+    decode_color(&block[8..], false, out);
+
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (i * 4)) & 0xF) as f32 / 15.0;
+    }
+}
+
+///Decodes a bc3 block, which has interpolated alpha
+pub(super) fn decode_bc3(block: &[u8], out: &mut [[f32; 4]]) {
+    decode_color(&block[8..], false, out);
+    decode_channel(block, false, 3, out);
+}
+
+///Decodes a single channel bc4 block
+pub(super) fn decode_bc4(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    out.fill([0.0, 0.0, 0.0, 1.0]);
+    decode_channel(block, signed, 0, out);
+}
+
+///Decodes a two channel bc5 block
+pub(super) fn decode_bc5(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    out.fill([0.0, 0.0, 0.0, 1.0]);
+    decode_channel(block, signed, 0, out);
+    decode_channel(&block[8..], signed, 1, out);
+}
+
+///Targets of the bits stored in a bc6h block, the endpoints are in the rgb order, w and x are the
+///endpoints of the first subset, y and z of the second one
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+///Partition
+const D: u8 = 12;
+
+///Layout of a bc6h mode
+struct Bc6hMode {
+    ///Whether the endpoints are stored as deltas from the first one
+    transformed: bool,
+    ///Precision of the endpoints
+    precision: u32,
+    ///Precision of the deltas in every channel
+    delta: [u32; 3],
+    ///Fields stored after the mode bits, the target, the lowest bit and the number of bits
+    fields: &'static [(u8, u8, u8)],
+}
+
+///Modes of bc6h blocks, the first 10 have 2 subsets
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        transformed: true,
+        precision: 10,
+        delta: [5, 5, 5],
+        fields: &[
+            (GY, 4, 1),
+            (BY, 4, 1),
+            (BZ, 4, 1),
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 7,
+        delta: [6, 6, 6],
+        fields: &[
+            (GY, 5, 1),
+            (GZ, 4, 1),
+            (GZ, 5, 1),
+            (RW, 0, 7),
+            (BZ, 0, 1),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 7),
+            (BY, 5, 1),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 7),
+            (BZ, 3, 1),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 11,
+        delta: [5, 4, 4],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 5),
+            (RW, 10, 1),
+            (GY, 0, 4),
+            (GX, 0, 4),
+            (GW, 10, 1),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 4),
+            (BW, 10, 1),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 11,
+        delta: [4, 5, 4],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 10, 1),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (GW, 10, 1),
+            (GZ, 0, 4),
+            (BX, 0, 4),
+            (BW, 10, 1),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 4),
+            (BZ, 0, 1),
+            (BZ, 2, 1),
+            (RZ, 0, 4),
+            (GY, 4, 1),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 11,
+        delta: [4, 4, 5],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 10, 1),
+            (BY, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 4),
+            (GW, 10, 1),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BW, 10, 1),
+            (BY, 0, 4),
+            (RY, 0, 4),
+            (BZ, 1, 1),
+            (BZ, 2, 1),
+            (RZ, 0, 4),
+            (BZ, 4, 1),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 9,
+        delta: [5, 5, 5],
+        fields: &[
+            (RW, 0, 9),
+            (BY, 4, 1),
+            (GW, 0, 9),
+            (GY, 4, 1),
+            (BW, 0, 9),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 8,
+        delta: [6, 5, 5],
+        fields: &[
+            (RW, 0, 8),
+            (GZ, 4, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (BZ, 3, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 8,
+        delta: [5, 6, 5],
+        fields: &[
+            (RW, 0, 8),
+            (BZ, 0, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (GY, 5, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (GZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 5),
+            (BZ, 1, 1),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 8,
+        delta: [5, 5, 6],
+        fields: &[
+            (RW, 0, 8),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 8),
+            (BY, 5, 1),
+            (GY, 4, 1),
+            (BW, 0, 8),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 5),
+            (GZ, 4, 1),
+            (GY, 0, 4),
+            (GX, 0, 5),
+            (BZ, 0, 1),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 5),
+            (BZ, 2, 1),
+            (RZ, 0, 5),
+            (BZ, 3, 1),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: false,
+        precision: 6,
+        delta: [6, 6, 6],
+        fields: &[
+            (RW, 0, 6),
+            (GZ, 4, 1),
+            (BZ, 0, 1),
+            (BZ, 1, 1),
+            (BY, 4, 1),
+            (GW, 0, 6),
+            (GY, 5, 1),
+            (BY, 5, 1),
+            (BZ, 2, 1),
+            (GY, 4, 1),
+            (BW, 0, 6),
+            (GZ, 5, 1),
+            (BZ, 3, 1),
+            (BZ, 5, 1),
+            (BZ, 4, 1),
+            (RX, 0, 6),
+            (GY, 0, 4),
+            (GX, 0, 6),
+            (GZ, 0, 4),
+            (BX, 0, 6),
+            (BY, 0, 4),
+            (RY, 0, 6),
+            (RZ, 0, 6),
+            (D, 0, 5),
+        ],
+    },
+    Bc6hMode {
+        transformed: false,
+        precision: 10,
+        delta: [10, 10, 10],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 10),
+            (GX, 0, 10),
+            (BX, 0, 10),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 11,
+        delta: [9, 9, 9],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 9),
+            (RW, 10, 1),
+            (GX, 0, 9),
+            (GW, 10, 1),
+            (BX, 0, 9),
+            (BW, 10, 1),
+        ],
+    },
+    //The high bits of the first endpoint are stored in reverse
+    Bc6hMode {
+        transformed: true,
+        precision: 12,
+        delta: [8, 8, 8],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 8),
+            (RW, 11, 1),
+            (RW, 10, 1),
+            (GX, 0, 8),
+            (GW, 11, 1),
+            (GW, 10, 1),
+            (BX, 0, 8),
+            (BW, 11, 1),
+            (BW, 10, 1),
+        ],
+    },
+    Bc6hMode {
+        transformed: true,
+        precision: 16,
+        delta: [4, 4, 4],
+        fields: &[
+            (RW, 0, 10),
+            (GW, 0, 10),
+            (BW, 0, 10),
+            (RX, 0, 4),
+            (RW, 15, 1),
+            (RW, 14, 1),
+            (RW, 13, 1),
+            (RW, 12, 1),
+            (RW, 11, 1),
+            (RW, 10, 1),
+            (GX, 0, 4),
+            (GW, 15, 1),
+            (GW, 14, 1),
+            (GW, 13, 1),
+            (GW, 12, 1),
+            (GW, 11, 1),
+            (GW, 10, 1),
+            (BX, 0, 4),
+            (BW, 15, 1),
+            (BW, 14, 1),
+            (BW, 13, 1),
+            (BW, 12, 1),
+            (BW, 11, 1),
+            (BW, 10, 1),
+        ],
+    },
+];
+
+const fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+///Unquantizes a bc6h endpoint into the 16 bit range
+const fn unquantize_bc6h(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    } else if bits >= 15 || value == 0 {
+        value
+    } else if value == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+///Converts an interpolated bc6h value into a half float
+const fn finish_bc6h(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+///Decodes a bc6h block containing half float rgb data
+pub(super) fn decode_bc6h(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    let mut bits = BitReader::new(block);
+
+    let mode = match bits.data as u32 & 0x1F {
+        m if m & 3 < 2 => {
+            bits.read(2);
+            (m & 3) as usize
+        }
+        m => {
+            bits.read(5);
+            match m {
+                2 => 2,
+                6 => 3,
+                10 => 4,
+                14 => 5,
+                18 => 6,
+                22 => 7,
+                26 => 8,
+                30 => 9,
+                3 => 10,
+                7 => 11,
+                11 => 12,
+                15 => 13,
+                //Reserved
+                _ => {
+                    out.fill([0.0, 0.0, 0.0, 1.0]);
+                    return;
+                }
+            }
+        }
+    };
+    let mode_info = &BC6H_MODES[mode];
+
+    let mut endpoints = [[0; 3]; 4];
+    let mut partition = 0;
+    for &(target, low, count) in mode_info.fields {
+        let value = bits.read(u32::from(count)) << low;
+        if target == D {
+            partition = value as usize;
+        } else {
+            endpoints[usize::from(target / 3)][usize::from(target % 3)] |= value as i32;
+        }
+    }
+
+    let subsets = if mode < 10 { 2 } else { 1 };
+    let precision = mode_info.precision;
+    #[allow(clippy::needless_range_loop)]
+    for c in 0..3 {
+        if signed {
+            endpoints[0][c] = sign_extend(endpoints[0][c], precision);
+        }
+        for e in 1..subsets * 2 {
+            if mode_info.transformed || signed {
+                endpoints[e][c] = sign_extend(endpoints[e][c], mode_info.delta[c]);
+            }
+            if mode_info.transformed {
+                endpoints[e][c] = (endpoints[0][c] + endpoints[e][c]) & ((1 << precision) - 1);
+                if signed {
+                    endpoints[e][c] = sign_extend(endpoints[e][c], precision);
+                }
+            }
+        }
+    }
+    for endpoint in &mut endpoints {
+        for c in endpoint {
+            *c = unquantize_bc6h(*c, precision, signed);
+        }
+    }
+
+    let index_bits = if subsets == 2 { 3 } else { 4 };
+    for (i, texel) in out.iter_mut().enumerate() {
+        let subset = subset(subsets as u32, partition, i);
+        let index = bits.read(index_bits - u32::from(is_anchor(subsets as u32, partition, i)));
+        let weight = weights(index_bits)[index as usize] as i32;
+
+        for c in 0..3 {
+            let (a, b) = (endpoints[subset * 2][c], endpoints[subset * 2 + 1][c]);
+            let value = (a * (64 - weight) + b * weight + 32) >> 6;
+            texel[c] = f16_to_f32(finish_bc6h(value, signed));
+        }
+        texel[3] = 1.0;
+    }
+}
+
+///Layout of a bc7 mode
+struct Bc7Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    ///Whether every endpoint has a p bit
+    endpoint_p_bits: bool,
+    ///Whether every subset has a p bit shared by both of its endpoints
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+const fn bc7_mode(
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_p_bits,
+        shared_p_bits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+///Expands an endpoint of the given precision into 8 bits
+const fn expand(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | (value >> bits)
+}
+
+///Decodes a bc7 block
+pub(super) fn decode_bc7(block: &[u8], out: &mut [[f32; 4]]) {
+    let mut bits = BitReader::new(block);
+
+    let mode = block[0].trailing_zeros();
+    if mode >= 8 {
+        out.fill([0.0; 4]);
+        return;
+    }
+    bits.read(mode + 1);
+    let m = &BC7_MODES[mode as usize];
+
+    let partition = bits.read(m.partition_bits) as usize;
+    let rotation = bits.read(m.rotation_bits);
+    let index_selection = bits.read(m.index_selection_bits);
+
+    let endpoint_count = m.subsets as usize * 2;
+    let mut endpoints = [[0, 0, 0, 255]; 6];
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[c] = bits.read(m.color_bits);
+        }
+    }
+    if m.alpha_bits > 0 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[3] = bits.read(m.alpha_bits);
+        }
+    }
+
+    let mut p_bits = [0; 6];
+    if m.endpoint_p_bits {
+        for p in &mut p_bits[..endpoint_count] {
+            *p = bits.read(1);
+        }
+    } else if m.shared_p_bits {
+        for s in 0..endpoint_count / 2 {
+            let p = bits.read(1);
+            p_bits[s * 2] = p;
+            p_bits[s * 2 + 1] = p;
+        }
+    }
+    let extra = u32::from(m.endpoint_p_bits || m.shared_p_bits);
+
+    for (endpoint, p) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+        for c in &mut endpoint[..3] {
+            *c = expand((*c << extra) | p, m.color_bits + extra);
+        }
+        if m.alpha_bits > 0 {
+            endpoint[3] = expand((endpoint[3] << extra) | p, m.alpha_bits + extra);
+        }
+    }
+
+    let mut indices = [0; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = bits.read(m.index_bits - u32::from(is_anchor(m.subsets, partition, i)));
+    }
+    let mut secondary = [0; 16];
+    if m.secondary_index_bits > 0 {
+        for (i, index) in secondary.iter_mut().enumerate() {
+            *index = bits.read(m.secondary_index_bits - u32::from(i == 0));
+        }
+    }
+
+    for (i, texel) in out.iter_mut().enumerate() {
+        let subset = subset(m.subsets, partition, i);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let primary = weights(m.index_bits)[indices[i] as usize];
+        let (color_weight, alpha_weight) = if m.secondary_index_bits == 0 {
+            (primary, primary)
+        } else {
+            let secondary = weights(m.secondary_index_bits)[secondary[i] as usize];
+            if index_selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            }
+        };
+
+        let mut color = [0; 4];
+        for c in 0..3 {
+            color[c] = interpolate(e0[c], e1[c], color_weight);
+        }
+        color[3] = interpolate(e0[3], e1[3], alpha_weight);
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+
+        *texel = color.map(unorm8);
+    }
+}
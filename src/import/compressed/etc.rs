@@ -0,0 +1,243 @@
+#![allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
+
+///Intensity modifiers of the individual and differential modes
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+///Distances of the t and h modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+///Modifiers of eac blocks
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+///Expands a value of the given precision into 8 bits
+const fn expand(value: i32, bits: u32) -> i32 {
+    let value = value << (8 - bits);
+    value | (value >> bits)
+}
+
+fn rgb(color: [i32; 3]) -> [f32; 4] {
+    let [r, g, b] = color.map(|c| c.clamp(0, 255) as f32 / 255.0);
+    [r, g, b, 1.0]
+}
+
+///Decodes an etc2 rgb block, `punch_through` enables the 1 bit alpha of the rgb8a1 format
+#[allow(clippy::many_single_char_names)]
+pub(super) fn decode_etc2(block: &[u8], punch_through: bool, out: &mut [[f32; 4]]) {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    //Reads `count` bits, starting at the `high` bit and going down
+    let field = |high: u32, count: u32| ((bits >> (high + 1 - count)) & ((1 << count) - 1)) as i32;
+
+    let differential = field(33, 1) == 1;
+    //The differential bit marks opaque blocks in the punch through format
+    let transparent = punch_through && !differential;
+    //Pixels are stored in columns
+    let index = |x: usize, y: usize| {
+        let i = x * 4 + y;
+        (((bits >> (16 + i)) & 1) << 1 | ((bits >> i) & 1)) as usize
+    };
+
+    if !differential && !punch_through {
+        let base = [
+            [field(63, 4), field(55, 4), field(47, 4)].map(|c| expand(c, 4)),
+            [field(59, 4), field(51, 4), field(43, 4)].map(|c| expand(c, 4)),
+        ];
+        return decode_subblocks(base, field, index, false, out);
+    }
+
+    let (r, g, b) = (field(63, 5), field(55, 5), field(47, 5));
+    let delta = |high: u32| (field(high, 3) << 29) >> 29;
+    let (r2, g2, b2) = (r + delta(58), g + delta(50), b + delta(42));
+
+    if !(0..32).contains(&r2) {
+        //T mode
+        let c1 = [
+            (field(60, 2) << 2) | field(57, 2),
+            field(55, 4),
+            field(51, 4),
+        ]
+        .map(|c| expand(c, 4));
+        let c2 = [field(47, 4), field(43, 4), field(39, 4)].map(|c| expand(c, 4));
+        let d = DISTANCES[((field(35, 2) << 1) | field(32, 1)) as usize];
+
+        let paint = [c1, c2.map(|c| c + d), c2, c2.map(|c| c - d)];
+        decode_paint(&paint, index, transparent, out);
+    } else if !(0..32).contains(&g2) {
+        //H mode
+        let c1 = [
+            field(62, 4),
+            (field(58, 3) << 1) | field(52, 1),
+            (field(51, 1) << 3) | field(49, 3),
+        ];
+        let c2 = [field(46, 4), field(42, 4), field(38, 4)];
+        let value = |c: [i32; 3]| (c[0] << 8) | (c[1] << 4) | c[2];
+        let d = DISTANCES[((field(34, 1) << 2)
+            | (field(32, 1) << 1)
+            | i32::from(value(c1) >= value(c2))) as usize];
+
+        let (c1, c2) = (c1.map(|c| expand(c, 4)), c2.map(|c| expand(c, 4)));
+        let paint = [
+            c1.map(|c| c + d),
+            c1.map(|c| c - d),
+            c2.map(|c| c + d),
+            c2.map(|c| c - d),
+        ];
+        decode_paint(&paint, index, transparent, out);
+    } else if !(0..32).contains(&b2) {
+        //Planar mode, always opaque
+        let o = [
+            expand(field(62, 6), 6),
+            expand((field(56, 1) << 6) | field(54, 6), 7),
+            expand((field(48, 1) << 5) | (field(44, 2) << 3) | field(41, 3), 6),
+        ];
+        let h = [
+            expand((field(38, 5) << 1) | field(32, 1), 6),
+            expand(field(31, 7), 7),
+            expand(field(24, 6), 6),
+        ];
+        let v = [
+            expand(field(18, 6), 6),
+            expand(field(12, 7), 7),
+            expand(field(5, 6), 6),
+        ];
+
+        for (i, texel) in out.iter_mut().enumerate() {
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+            *texel = rgb(std::array::from_fn(|c| {
+                (x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2
+            }));
+        }
+    } else {
+        let base = [
+            [r, g, b].map(|c| expand(c, 5)),
+            [r2, g2, b2].map(|c| expand(c, 5)),
+        ];
+        decode_subblocks(base, field, index, transparent, out);
+    }
+}
+
+///Decodes the two sub blocks of the individual and differential modes
+fn decode_subblocks(
+    base: [[i32; 3]; 2],
+    field: impl Fn(u32, u32) -> i32,
+    index: impl Fn(usize, usize) -> usize,
+    transparent: bool,
+    out: &mut [[f32; 4]],
+) {
+    let flip = field(32, 1) == 1;
+    let tables = [field(39, 3), field(36, 3)].map(|t| MODIFIERS[t as usize]);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let subblock = usize::from(if flip { y >= 2 } else { x >= 2 });
+        let table = tables[subblock];
+
+        let modifier = match index(x, y) {
+            //Transparent blocks don't use the small modifier
+            0 if transparent => 0,
+            0 => table[0],
+            1 => table[1],
+            2 if transparent => {
+                *texel = [0.0; 4];
+                continue;
+            }
+            2 => -table[0],
+            _ => -table[1],
+        };
+        *texel = rgb(base[subblock].map(|c| c + modifier));
+    }
+}
+
+///Decodes the pixels of the t and h modes
+fn decode_paint(
+    paint: &[[i32; 3]; 4],
+    index: impl Fn(usize, usize) -> usize,
+    transparent: bool,
+    out: &mut [[f32; 4]],
+) {
+    for (i, texel) in out.iter_mut().enumerate() {
+        let index = index(i % 4, i / 4);
+        *texel = if transparent && index == 2 {
+            [0.0; 4]
+        } else {
+            rgb(paint[index])
+        };
+    }
+}
+
+///Decodes an eac block into a channel of the texels
+///
+///The alpha of etc2 rgba blocks is 8 bit, while the r11 and rg11 blocks are 11 bit and can be
+///signed
+fn decode_eac(block: &[u8], eleven: bool, signed: bool, channel: usize, out: &mut [[f32; 4]]) {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let multiplier = i32::from(block[1] >> 4);
+    let modifiers = EAC_MODIFIERS[usize::from(block[1] & 0xF)];
+
+    for i in 0..16 {
+        //Pixels are stored in columns
+        let (x, y) = (i / 4, i % 4);
+        let modifier = modifiers[((bits >> (45 - 3 * i)) & 7) as usize];
+
+        let value = if eleven {
+            let modifier = if multiplier == 0 {
+                modifier
+            } else {
+                modifier * multiplier * 8
+            };
+            if signed {
+                let base = i32::from((block[0] as i8).max(-127));
+                (base * 8 + modifier).clamp(-1023, 1023) as f32 / 1023.0
+            } else {
+                (i32::from(block[0]) * 8 + 4 + modifier).clamp(0, 2047) as f32 / 2047.0
+            }
+        } else {
+            (i32::from(block[0]) + modifier * multiplier).clamp(0, 255) as f32 / 255.0
+        };
+        out[y * 4 + x][channel] = value;
+    }
+}
+
+///Decodes an etc2 rgba block, which has an eac alpha block followed by an etc2 rgb block
+pub(super) fn decode_etc2_rgba(block: &[u8], out: &mut [[f32; 4]]) {
+    decode_etc2(&block[8..], false, out);
+    decode_eac(block, false, false, 3, out);
+}
+
+///Decodes a single channel eac r11 block
+pub(super) fn decode_eac_r11(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    out.fill([0.0, 0.0, 0.0, 1.0]);
+    decode_eac(block, true, signed, 0, out);
+}
+
+///Decodes a two channel eac rg11 block
+pub(super) fn decode_eac_rg11(block: &[u8], signed: bool, out: &mut [[f32; 4]]) {
+    out.fill([0.0, 0.0, 0.0, 1.0]);
+    decode_eac(block, true, signed, 0, out);
+    decode_eac(&block[8..], true, signed, 1, out);
+}
@@ -0,0 +1,240 @@
+#![allow(clippy::cast_possible_truncation)]
+use super::{compressed::CompressedImage, invalid};
+
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+///The pixel format contains a four character code
+const FOURCC: u32 = 0x4;
+///The pixel format contains rgb masks
+const RGB: u32 = 0x40;
+///The pixel format has an alpha channel
+const ALPHA_PIXELS: u32 = 0x1;
+///The texture is a cubemap
+const CUBEMAP: u32 = 0x200;
+///The texture is a volume texture
+const VOLUME: u32 = 0x20_0000;
+///The dx10 texture is a cubemap
+const DX10_CUBEMAP: u32 = 0x4;
+///Dx10 3d texture dimension
+const DX10_TEXTURE_3D: u32 = 4;
+
+///Converts a dxgi format into a texture format, bgra formats are converted into rgba ones
+const fn dxgi_format(format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+
+    Some(match format {
+        10 => F::Rgba16Float,
+        27 | 28 | 87 | 90 => F::Rgba8Unorm,
+        29 | 91 => F::Rgba8UnormSrgb,
+        70 | 71 => F::Bc1RgbaUnorm,
+        72 => F::Bc1RgbaUnormSrgb,
+        73 | 74 => F::Bc2RgbaUnorm,
+        75 => F::Bc2RgbaUnormSrgb,
+        76 | 77 => F::Bc3RgbaUnorm,
+        78 => F::Bc3RgbaUnormSrgb,
+        79 | 80 => F::Bc4RUnorm,
+        81 => F::Bc4RSnorm,
+        82 | 83 => F::Bc5RgUnorm,
+        84 => F::Bc5RgSnorm,
+        94 | 95 => F::Bc6hRgbUfloat,
+        96 => F::Bc6hRgbFloat,
+        97 | 98 => F::Bc7RgbaUnorm,
+        99 => F::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+///Converts a legacy four character code into a texture format
+const fn fourcc_format(fourcc: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+
+    Some(match &fourcc.to_le_bytes() {
+        b"DXT1" => F::Bc1RgbaUnorm,
+        b"DXT2" | b"DXT3" => F::Bc2RgbaUnorm,
+        b"DXT4" | b"DXT5" => F::Bc3RgbaUnorm,
+        b"ATI1" | b"BC4U" => F::Bc4RUnorm,
+        b"BC4S" => F::Bc4RSnorm,
+        b"ATI2" | b"BC5U" => F::Bc5RgUnorm,
+        b"BC5S" => F::Bc5RgSnorm,
+        //D3DFMT_A16B16G16R16F
+        [113, 0, 0, 0] => F::Rgba16Float,
+        _ => return None,
+    })
+}
+
+///Parses a dds file containing a 2d texture or a cubemap
+///
+///Block compressed, 8 bit rgba and bgra, as well as 16 bit float formats are supported. Rows are
+///stored in the same order as in the file
+///# Errors
+///Fails if the data is not a valid dds file or uses an unsupported format
+pub fn parse(data: &[u8]) -> Result<CompressedImage, Box<dyn std::error::Error + Send>> {
+    if data.len() < HEADER_SIZE || &data[..4] != b"DDS " {
+        return Err(invalid("Not a dds file"));
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    let height = read_u32(12);
+    let width = read_u32(16);
+    let mip_count = read_u32(28).max(1);
+    let pixel_flags = read_u32(80);
+    let fourcc = read_u32(84);
+    let caps2 = read_u32(112);
+
+    if caps2 & VOLUME != 0 {
+        return Err(invalid("Volume textures are not supported"));
+    }
+    let mut layers = if caps2 & CUBEMAP == 0 { 1 } else { 6 };
+    let mut swizzle = false;
+    let mut opaque = false;
+    let mut offset = HEADER_SIZE;
+
+    let format = if pixel_flags & FOURCC != 0 && &fourcc.to_le_bytes() == b"DX10" {
+        if data.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+            return Err(invalid("Unexpected end of file"));
+        }
+        let dxgi = read_u32(128);
+        let dimension = read_u32(132);
+        let flags = read_u32(136);
+        let array_size = read_u32(140);
+
+        if dimension == DX10_TEXTURE_3D || array_size > 1 {
+            return Err(invalid("3d textures and texture arrays are not supported"));
+        }
+        layers = if flags & DX10_CUBEMAP == 0 { 1 } else { 6 };
+        swizzle = matches!(dxgi, 87 | 90 | 91);
+        offset += DX10_HEADER_SIZE;
+
+        dxgi_format(dxgi)
+    } else if pixel_flags & FOURCC != 0 {
+        fourcc_format(fourcc)
+    } else if pixel_flags & RGB != 0 && read_u32(88) == 32 {
+        opaque = pixel_flags & ALPHA_PIXELS == 0;
+        match (read_u32(92), read_u32(96), read_u32(100)) {
+            (0xFF, 0xFF00, 0xFF_0000) => Some(wgpu::TextureFormat::Rgba8Unorm),
+            (0xFF_0000, 0xFF00, 0xFF) => {
+                swizzle = true;
+                Some(wgpu::TextureFormat::Rgba8Unorm)
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+    .ok_or_else(|| invalid("Unsupported texture format"))?;
+
+    let mut image = CompressedImage {
+        format,
+        width,
+        height,
+        layers,
+        mip_count,
+        data: Vec::new(),
+    };
+
+    image.validate_dimensions()?;
+    let size = image
+        .checked_size()
+        .ok_or_else(|| invalid("Texture is too large"))?;
+    image.data = data
+        .get(offset..)
+        .and_then(|data| data.get(..size))
+        .ok_or_else(|| invalid("Unexpected end of file"))?
+        .to_vec();
+
+    for pixel in image.data.chunks_exact_mut(4).filter(|_| swizzle || opaque) {
+        if swizzle {
+            pixel.swap(0, 2);
+        }
+        if opaque {
+            pixel[3] = 255;
+        }
+    }
+
+    image.validate()?;
+    Ok(image)
+}
+
+///Builds a dds file with the given pixel format
+#[cfg(test)]
+pub(crate) fn build_dds(
+    size: u32,
+    mip_count: u32,
+    pixel_format: [u32; 8],
+    dx10: Option<[u32; 5]>,
+) -> Vec<u8> {
+    let mut header = [0u32; 32];
+    header[0] = u32::from_le_bytes(*b"DDS ");
+    header[1] = 124;
+    header[3] = size;
+    header[4] = size;
+    header[7] = mip_count;
+    header[19..27].copy_from_slice(&pixel_format);
+    if dx10.is_some_and(|h| h[2] & DX10_CUBEMAP != 0) {
+        header[28] = CUBEMAP;
+    }
+
+    let mut data = header
+        .into_iter()
+        .chain(dx10.into_iter().flatten())
+        .flat_map(u32::to_le_bytes)
+        .collect::<Vec<_>>();
+    data.extend((0..).map(|i: u32| i as u8).take(1024));
+    data
+}
+
+#[test]
+fn test_parse_dds() {
+    let dxt5 = u32::from_le_bytes(*b"DXT5");
+    let dx10 = u32::from_le_bytes(*b"DX10");
+
+    //4x4 bc3 with 3 mips
+    let image = parse(&build_dds(4, 3, [32, FOURCC, dxt5, 0, 0, 0, 0, 0], None)).unwrap();
+    assert_eq!(image.format, wgpu::TextureFormat::Bc3RgbaUnorm);
+    assert_eq!((image.width, image.height), (4, 4));
+    assert_eq!((image.layers, image.mip_count), (1, 3));
+    assert_eq!(image.data.len(), 48);
+    assert_eq!(image.data[47], 47);
+
+    //Bc7 srgb cubemap
+    let image = parse(&build_dds(
+        8,
+        1,
+        [32, FOURCC, dx10, 0, 0, 0, 0, 0],
+        Some([99, 3, DX10_CUBEMAP, 1, 0]),
+    ))
+    .unwrap();
+    assert_eq!(image.format, wgpu::TextureFormat::Bc7RgbaUnormSrgb);
+    assert_eq!(image.layers, 6);
+    assert_eq!(image.data.len(), 6 * 64);
+
+    //Bgr without alpha is converted into rgba
+    let image = parse(&build_dds(
+        1,
+        1,
+        [32, RGB, 0, 32, 0xFF_0000, 0xFF00, 0xFF, 0],
+        None,
+    ))
+    .unwrap();
+    assert_eq!(image.format, wgpu::TextureFormat::Rgba8Unorm);
+    assert_eq!(image.data, [2, 1, 0, 255]);
+
+    assert!(parse(&[0; 200]).is_err());
+    //Missing data
+    assert!(parse(&build_dds(64, 1, [32, FOURCC, dxt5, 0, 0, 0, 0, 0], None)).is_err());
+    //The size of the texture does not fit into an u64, or it has more mips than its size allows
+    let rgba = [32, RGB, 0, 32, 0xFF, 0xFF00, 0xFF_0000, 0];
+    assert!(parse(&build_dds(u32::MAX, 1, rgba, None)).is_err());
+    assert!(parse(&build_dds(4, u32::MAX, rgba, None)).is_err());
+    //Unsupported format
+    assert!(
+        parse(&build_dds(
+            4,
+            1,
+            [32, RGB, 0, 16, 0xF800, 0x7E0, 0x1F, 0],
+            None
+        ))
+        .is_err()
+    );
+}
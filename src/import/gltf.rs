@@ -0,0 +1,828 @@
+//! Loading of skinned meshes and their animations from gltf files
+//!
+//! Only the parts of glTF 2.0 needed for skinned meshes and their animations are supported:
+//! triangle meshes, skins and joint animations. Materials, cameras, morph targets and sparse
+//! accessors are ignored or rejected
+use std::path::Path;
+
+use super::{invalid, json::Value};
+use crate::{
+    assets::{
+        animation::Interpolation,
+        mesh::processing::{NormalMode, recalculate_normals},
+        skeleton::{ChannelValues, Joint, JointChannel, JointPose, SkeletalClip},
+    },
+    math::{Mat4x4, Quaternion, Vec2, Vec3, Vec4, Vector as _},
+    structures::{Index, Mesh, Vertex, VertexSkin},
+};
+
+///Magic bytes at the start of a binary gltf file
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+///Triangle list primitive mode
+const MODE_TRIANGLES: usize = 4;
+
+///A parsed gltf document together with the data of its buffers
+pub struct Gltf {
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Gltf {
+    ///Parses a `.gltf` or a `.glb` file
+    ///
+    ///Buffers must either be embedded as base64 data uris or stored in the binary chunk of a glb
+    ///file, use [`Gltf::load`] for files that reference external buffers
+    ///# Errors
+    ///Fails if the data is not a valid gltf file or references external files
+    pub fn parse(data: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send>> {
+        Self::parse_with(data, |_| {
+            Err(invalid("External gltf buffers are not supported"))
+        })
+    }
+
+    ///Loads a `.gltf` or a `.glb` file, external buffers are loaded relative to the file
+    ///# Errors
+    ///Fails if the files can't be read or are not valid gltf files
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send>> {
+        let data = std::fs::read(path).map_err(|e| Box::new(e) as _)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        Self::parse_with(&data, |uri| {
+            std::fs::read(directory.join(uri)).map_err(|e| Box::new(e) as _)
+        })
+    }
+
+    fn parse_with(
+        data: &[u8],
+        load_external: impl Fn(&str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send>>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send>> {
+        let (json, mut binary_chunk) = if data.starts_with(GLB_MAGIC) {
+            let (json, binary) = parse_glb(data)?;
+            (json, binary.map(<[u8]>::to_vec))
+        } else {
+            let text =
+                std::str::from_utf8(data).map_err(|_| invalid("Gltf json is not valid utf-8"))?;
+            (text, None)
+        };
+        let json = super::json::parse(json)?;
+
+        if !json
+            .get("asset")
+            .get("version")
+            .as_str()
+            .is_some_and(|v| v.starts_with("2."))
+        {
+            return Err(invalid("Only gltf 2.0 is supported"));
+        }
+
+        let mut buffers = Vec::new();
+        for buffer in json.get("buffers").elements() {
+            let data = match buffer.get("uri").as_str() {
+                None => binary_chunk
+                    .take()
+                    .ok_or_else(|| invalid("Gltf buffer has no data"))?,
+                Some(uri) if uri.starts_with("data:") => {
+                    let (_, encoded) = uri
+                        .split_once(";base64,")
+                        .ok_or_else(|| invalid("Only base64 gltf data uris are supported"))?;
+                    decode_base64(encoded)?
+                }
+                Some(uri) => load_external(uri)?,
+            };
+
+            let length = buffer.get("byteLength").as_usize().unwrap_or_default();
+            if data.len() < length {
+                return Err(invalid("Gltf buffer is shorter than its byte length"));
+            }
+            buffers.push(data);
+        }
+
+        Ok(Self { json, buffers })
+    }
+
+    ///Returns the number of meshes in the file
+    #[must_use]
+    pub fn mesh_count(&self) -> usize {
+        self.json.get("meshes").elements().len()
+    }
+
+    ///Returns the number of skins in the file
+    #[must_use]
+    pub fn skin_count(&self) -> usize {
+        self.json.get("skins").elements().len()
+    }
+
+    ///Reads a mesh, merging all of its triangle primitives into one
+    ///
+    ///Also returns the joints and weights of the vertices if all of the primitives have them.
+    ///Texture coordinates are flipped vertically, since gltf has its origin in the top left
+    ///corner. Normals are calculated if the mesh does not have them
+    ///# Errors
+    ///Fails if the mesh does not exist or has invalid data
+    #[allow(clippy::type_complexity)]
+    pub fn mesh(
+        &self,
+        index: usize,
+    ) -> Result<(Mesh, Option<Vec<VertexSkin>>), Box<dyn std::error::Error + Send>> {
+        let mesh = self.json.get("meshes").at(index);
+        if mesh.is_null() {
+            return Err(invalid("Gltf mesh does not exist"));
+        }
+
+        let mut output = Mesh::default();
+        let mut skin = Some(Vec::new());
+        let mut missing_normals = false;
+
+        for primitive in mesh.get("primitives").elements() {
+            if primitive.get("mode").as_usize().unwrap_or(MODE_TRIANGLES) != MODE_TRIANGLES {
+                continue;
+            }
+            let attributes = primitive.get("attributes");
+            let attribute = |name: &str| attributes.get(name).as_usize();
+
+            let positions = self.read_vectors(
+                attribute("POSITION").ok_or_else(|| invalid("Gltf primitive has no positions"))?,
+                3,
+            )?;
+            let count = positions.len();
+            let read_optional = |name: &str, components: usize| {
+                attribute(name)
+                    .map(|a| self.read_vectors(a, components))
+                    .transpose()
+                    .and_then(|v| match v {
+                        Some(v) if v.len() != count => {
+                            Err(invalid("Gltf attributes have different lengths"))
+                        }
+                        v => Ok(v),
+                    })
+            };
+
+            let normals = read_optional("NORMAL", 3)?;
+            let uvs = read_optional("TEXCOORD_0", 2)?;
+            missing_normals |= normals.is_none();
+
+            let offset = output.vertices.len() as Index;
+            for i in 0..count {
+                let [x, y, z, ..] = positions[i];
+                let normal = normals.as_ref().map_or([0.0; 4], |n| n[i]);
+                let uv = uvs.as_ref().map_or([0.0; 4], |uv| uv[i]);
+
+                output.vertices.push(Vertex {
+                    coords: Vec3::new(x, y, z),
+                    texture: Vec2::new(uv[0], 1.0 - uv[1]),
+                    normal: Vec3::new(normal[0], normal[1], normal[2]),
+                });
+            }
+
+            let indices = match primitive.get("indices").as_usize() {
+                Some(accessor) => self.read_integers(accessor)?,
+                None => (0..count as u32).collect(),
+            };
+            if indices.len() % 3 != 0 || indices.iter().any(|i| *i as usize >= count) {
+                return Err(invalid("Invalid gltf indices"));
+            }
+            output
+                .indices
+                .extend(indices.into_iter().map(|i| i + offset));
+
+            match (attribute("JOINTS_0"), read_optional("WEIGHTS_0", 4)?) {
+                (Some(joints), Some(weights)) if skin.is_some() => {
+                    let joints = self.read_integers(joints)?;
+                    if joints.len() != count * 4 {
+                        return Err(invalid("Gltf joints must have 4 components"));
+                    }
+
+                    skin.as_mut().unwrap().extend(
+                        joints
+                            .chunks_exact(4)
+                            .zip(weights)
+                            .map(|(joints, weights)| skin_vertex(joints, weights)),
+                    );
+                }
+                _ => skin = None,
+            }
+        }
+
+        if output.vertices.is_empty() {
+            return Err(invalid("Gltf mesh has no triangles"));
+        }
+        if missing_normals {
+            recalculate_normals(&mut output, NormalMode::Smooth);
+        }
+
+        Ok((output, skin))
+    }
+
+    ///Reads the joints of a skin, in the order used by the joint indices of its meshes
+    ///
+    ///Transformations of nodes that are not a part of the skin are ignored
+    ///# Errors
+    ///Fails if the skin does not exist or has invalid data
+    pub fn skeleton(&self, skin: usize) -> Result<Vec<Joint>, Box<dyn std::error::Error + Send>> {
+        let nodes = self.skin_joints(skin)?;
+        let nodes_json = self.json.get("nodes");
+
+        let inverse_bind_matrices = match self
+            .json
+            .get("skins")
+            .at(skin)
+            .get("inverseBindMatrices")
+            .as_usize()
+        {
+            Some(accessor) => {
+                let values = self.read_components(accessor, false)?;
+                if values.1 != 16 || values.0.len() != nodes.len() * 16 {
+                    return Err(invalid("Invalid gltf inverse bind matrices"));
+                }
+                values.0.chunks_exact(16).map(column_major).collect()
+            }
+            None => vec![Mat4x4::identity(); nodes.len()],
+        };
+
+        nodes
+            .iter()
+            .zip(inverse_bind_matrices)
+            .map(|(node, inverse_bind_matrix)| {
+                let parent = nodes.iter().position(|p| {
+                    nodes_json
+                        .at(*p)
+                        .get("children")
+                        .elements()
+                        .iter()
+                        .any(|c| c.as_usize() == Some(*node))
+                });
+                let json = nodes_json.at(*node);
+
+                Ok(Joint {
+                    name: json.get("name").as_str().unwrap_or_default().to_owned(),
+                    parent,
+                    inverse_bind_matrix,
+                    rest_pose: node_pose(json)?,
+                })
+            })
+            .collect()
+    }
+
+    ///Reads all animations that affect the joints of a skin, animations of other nodes and
+    ///morph target weights are ignored
+    ///# Errors
+    ///Fails if the skin does not exist or the animations have invalid data
+    pub fn animations(
+        &self,
+        skin: usize,
+    ) -> Result<Vec<SkeletalClip>, Box<dyn std::error::Error + Send>> {
+        let nodes = self.skin_joints(skin)?;
+        let mut clips = Vec::new();
+
+        for animation in self.json.get("animations").elements() {
+            let mut channels = Vec::new();
+
+            for channel in animation.get("channels").elements() {
+                let target = channel.get("target");
+                let Some(joint) = target
+                    .get("node")
+                    .as_usize()
+                    .and_then(|n| nodes.iter().position(|j| *j == n))
+                else {
+                    continue;
+                };
+                let components = match target.get("path").as_str() {
+                    Some("translation" | "scale") => 3,
+                    Some("rotation") => 4,
+                    _ => continue,
+                };
+
+                let sampler = channel
+                    .get("sampler")
+                    .as_usize()
+                    .map(|s| animation.get("samplers").at(s))
+                    .filter(|s| !s.is_null())
+                    .ok_or_else(|| invalid("Gltf animation sampler does not exist"))?;
+                let interpolation = match sampler.get("interpolation").as_str() {
+                    None | Some("LINEAR") => Interpolation::Linear,
+                    Some("STEP") => Interpolation::Step,
+                    Some("CUBICSPLINE") => Interpolation::CubicSpline,
+                    Some(_) => return Err(invalid("Unknown gltf interpolation")),
+                };
+
+                let (times, _) = self.read_components(
+                    sampler
+                        .get("input")
+                        .as_usize()
+                        .ok_or_else(|| invalid("Gltf sampler has no input"))?,
+                    false,
+                )?;
+                let values = self.read_vectors(
+                    sampler
+                        .get("output")
+                        .as_usize()
+                        .ok_or_else(|| invalid("Gltf sampler has no output"))?,
+                    components,
+                )?;
+
+                let values_per_key = if interpolation == Interpolation::CubicSpline {
+                    3
+                } else {
+                    1
+                };
+                if times.is_empty() || values.len() != times.len() * values_per_key {
+                    return Err(invalid("Gltf animation sampler has invalid keyframes"));
+                }
+
+                let values = match target.get("path").as_str() {
+                    Some("translation") => ChannelValues::Translation(
+                        values.iter().map(|v| Vec3::new(v[0], v[1], v[2])).collect(),
+                    ),
+                    Some("scale") => ChannelValues::Scale(
+                        values.iter().map(|v| Vec3::new(v[0], v[1], v[2])).collect(),
+                    ),
+                    _ => ChannelValues::Rotation(
+                        values
+                            .iter()
+                            .map(|v| Quaternion::new(v[3], v[0], v[1], v[2]))
+                            .collect(),
+                    ),
+                };
+
+                channels.push(JointChannel {
+                    joint,
+                    interpolation,
+                    times: times.into_iter().map(|t| t as f32).collect(),
+                    values,
+                });
+            }
+
+            if channels.is_empty() {
+                continue;
+            }
+            clips.push(SkeletalClip {
+                name: animation
+                    .get("name")
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned(),
+                duration: channels
+                    .iter()
+                    .filter_map(|c| c.times.last().copied())
+                    .fold(0.0, f32::max),
+                channels,
+            });
+        }
+
+        Ok(clips)
+    }
+
+    ///Returns the node indices of the joints of the skin
+    fn skin_joints(&self, skin: usize) -> Result<Vec<usize>, Box<dyn std::error::Error + Send>> {
+        let node_count = self.json.get("nodes").elements().len();
+
+        let joints = self
+            .json
+            .get("skins")
+            .at(skin)
+            .get("joints")
+            .elements()
+            .iter()
+            .map(|j| j.as_usize().filter(|j| *j < node_count))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("Invalid gltf skin joints"))?;
+
+        if joints.is_empty() {
+            return Err(invalid("Gltf skin does not exist or has no joints"));
+        }
+        Ok(joints)
+    }
+
+    ///Reads an accessor, returning the values of all components and the number of components
+    ///per element
+    fn read_components(
+        &self,
+        index: usize,
+        normalize: bool,
+    ) -> Result<(Vec<f64>, usize), Box<dyn std::error::Error + Send>> {
+        let accessor = self.json.get("accessors").at(index);
+        if accessor.is_null() {
+            return Err(invalid("Gltf accessor does not exist"));
+        }
+        if !accessor.get("sparse").is_null() {
+            return Err(invalid("Sparse gltf accessors are not supported"));
+        }
+
+        let count = accessor
+            .get("count")
+            .as_usize()
+            .ok_or_else(|| invalid("Gltf accessor has no count"))?;
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4" | "MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid("Unknown gltf accessor type")),
+        };
+        let component_type = accessor.get("componentType").as_usize();
+        let component_size = match component_type {
+            Some(5120 | 5121) => 1,
+            Some(5122 | 5123) => 2,
+            Some(5125 | 5126) => 4,
+            _ => return Err(invalid("Unknown gltf component type")),
+        };
+        let normalized = normalize && accessor.get("normalized").as_bool().unwrap_or_default();
+
+        //Accessors without a buffer view are filled with zeros
+        let Some(view) = accessor.get("bufferView").as_usize() else {
+            return Ok((vec![0.0; count * components], components));
+        };
+        let view = self.json.get("bufferViews").at(view);
+        let buffer = view
+            .get("buffer")
+            .as_usize()
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| invalid("Gltf buffer does not exist"))?;
+
+        let view_start = view.get("byteOffset").as_usize().unwrap_or_default();
+        let view_length = view.get("byteLength").as_usize().unwrap_or_default();
+        let view_data = buffer
+            .get(view_start..view_start + view_length)
+            .ok_or_else(|| invalid("Gltf buffer view out of bounds"))?;
+
+        let element_size = component_size * components;
+        let stride = view
+            .get("byteStride")
+            .as_usize()
+            .unwrap_or(element_size)
+            .max(element_size);
+        let offset = accessor.get("byteOffset").as_usize().unwrap_or_default();
+        if count > 0 && offset + stride * (count - 1) + element_size > view_data.len() {
+            return Err(invalid("Gltf accessor out of bounds"));
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            let start = offset + stride * element;
+            for component in view_data[start..start + element_size].chunks_exact(component_size) {
+                let value = match component_type {
+                    Some(5120) => {
+                        let v = f64::from(i8::from_le_bytes([component[0]]));
+                        if normalized { (v / 127.0).max(-1.0) } else { v }
+                    }
+                    Some(5121) => {
+                        let v = f64::from(component[0]);
+                        if normalized { v / 255.0 } else { v }
+                    }
+                    Some(5122) => {
+                        let v = f64::from(i16::from_le_bytes([component[0], component[1]]));
+                        if normalized {
+                            (v / 32767.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    Some(5123) => {
+                        let v = f64::from(u16::from_le_bytes([component[0], component[1]]));
+                        if normalized { v / 65535.0 } else { v }
+                    }
+                    Some(5125) => f64::from(u32::from_le_bytes(component.try_into().unwrap())),
+                    _ => f64::from(f32::from_le_bytes(component.try_into().unwrap())),
+                };
+                values.push(value);
+            }
+        }
+
+        Ok((values, components))
+    }
+
+    ///Reads an accessor of vectors, padding them to 4 components
+    fn read_vectors(
+        &self,
+        index: usize,
+        components: usize,
+    ) -> Result<Vec<[f32; 4]>, Box<dyn std::error::Error + Send>> {
+        let (values, actual) = self.read_components(index, true)?;
+        if actual != components {
+            return Err(invalid("Gltf accessor has the wrong type"));
+        }
+
+        Ok(values
+            .chunks_exact(components)
+            .map(|v| std::array::from_fn(|i| v.get(i).copied().unwrap_or_default() as f32))
+            .collect())
+    }
+
+    ///Reads an accessor of integers
+    #[allow(clippy::cast_sign_loss)]
+    fn read_integers(&self, index: usize) -> Result<Vec<u32>, Box<dyn std::error::Error + Send>> {
+        let (values, _) = self.read_components(index, false)?;
+        if values.iter().any(|v| *v < 0.0 || v.fract() != 0.0) {
+            return Err(invalid("Gltf accessor does not contain integers"));
+        }
+        Ok(values.into_iter().map(|v| v as u32).collect())
+    }
+}
+
+///Splits a glb file into its json and binary chunks
+#[allow(clippy::type_complexity)]
+fn parse_glb(data: &[u8]) -> Result<(&str, Option<&[u8]>), Box<dyn std::error::Error + Send>> {
+    let read_u32 = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("Unexpected end of glb file"))
+    };
+
+    if read_u32(4)? != 2 {
+        return Err(invalid("Only glb version 2 is supported"));
+    }
+    let length = (read_u32(8)? as usize).min(data.len());
+
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(offset)? as usize;
+        let chunk_type = read_u32(offset + 4)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| invalid("Unexpected end of glb file"))?;
+
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if binary.is_none() => binary = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+
+    let json = json.ok_or_else(|| invalid("Glb file has no json chunk"))?;
+    let json = std::str::from_utf8(json).map_err(|_| invalid("Gltf json is not valid utf-8"))?;
+    Ok((json, binary))
+}
+
+///Decodes standard base64 with optional padding
+fn decode_base64(data: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send>> {
+    let mut output = Vec::with_capacity(data.len() / 4 * 3);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+
+    for c in data.bytes().filter(|c| *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(invalid("Invalid base64 data")),
+        };
+        accumulator = (accumulator << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((accumulator >> bits) as u8);
+        }
+    }
+
+    Ok(output)
+}
+
+///Converts 16 column major values into a matrix
+fn column_major(values: &[f64]) -> Mat4x4 {
+    let v = |row: usize, column: usize| values[column * 4 + row] as f32;
+    Mat4x4::new(
+        v(0, 0),
+        v(0, 1),
+        v(0, 2),
+        v(0, 3),
+        v(1, 0),
+        v(1, 1),
+        v(1, 2),
+        v(1, 3),
+        v(2, 0),
+        v(2, 1),
+        v(2, 2),
+        v(2, 3),
+        v(3, 0),
+        v(3, 1),
+        v(3, 2),
+        v(3, 3),
+    )
+}
+
+///Reads the local transformation of a node
+fn node_pose(node: &Value) -> Result<JointPose, Box<dyn std::error::Error + Send>> {
+    let floats =
+        |key: &str, default: &[f32]| -> Result<Vec<f32>, Box<dyn std::error::Error + Send>> {
+            let value = node.get(key);
+            if value.is_null() {
+                return Ok(default.to_vec());
+            }
+            let values = value
+                .elements()
+                .iter()
+                .map(|v| v.as_f64().map(|v| v as f32))
+                .collect::<Option<Vec<_>>>()
+                .filter(|v| v.len() == default.len())
+                .ok_or_else(|| invalid("Invalid gltf node transformation"))?;
+            Ok(values)
+        };
+
+    if !node.get("matrix").is_null() {
+        let values = floats("matrix", &[0.0; 16])?;
+        let matrix = column_major(&values.iter().map(|v| f64::from(*v)).collect::<Vec<_>>());
+
+        let column = |c: u32| -> Vec3 { matrix.col(c).into() };
+        let scale = Vec3::new(column(0).length(), column(1).length(), column(2).length());
+        //Remove the scale from the matrix, leaving only the rotation
+        let divisor = |s: f32| if s == 0.0 { 1.0 } else { s };
+        let (x, y, z) = (divisor(scale.x), divisor(scale.y), divisor(scale.z));
+        let rotation = Mat4x4 {
+            m00: matrix.m00 / x,
+            m01: matrix.m01 / y,
+            m02: matrix.m02 / z,
+            m10: matrix.m10 / x,
+            m11: matrix.m11 / y,
+            m12: matrix.m12 / z,
+            m20: matrix.m20 / x,
+            m21: matrix.m21 / y,
+            m22: matrix.m22 / z,
+            ..Mat4x4::identity()
+        };
+
+        return Ok(JointPose {
+            translation: column(3),
+            rotation: Quaternion::from_rotation_matrix(&rotation),
+            scale,
+        });
+    }
+
+    let t = floats("translation", &[0.0; 3])?;
+    let r = floats("rotation", &[0.0, 0.0, 0.0, 1.0])?;
+    let s = floats("scale", &[1.0; 3])?;
+
+    Ok(JointPose {
+        translation: Vec3::new(t[0], t[1], t[2]),
+        rotation: Quaternion::new(r[3], r[0], r[1], r[2]),
+        scale: Vec3::new(s[0], s[1], s[2]),
+    })
+}
+
+///Creates the skin of a vertex, normalizing the weights
+fn skin_vertex(joints: &[u32], weights: [f32; 4]) -> VertexSkin {
+    let sum = weights.iter().sum::<f32>();
+    let weights = if sum > 0.0 {
+        weights.map(|w| w / sum)
+    } else {
+        [1.0, 0.0, 0.0, 0.0]
+    };
+
+    VertexSkin {
+        joints: std::array::from_fn(|i| joints[i] as u16),
+        weights: Vec4::new(weights[0], weights[1], weights[2], weights[3]),
+    }
+}
+
+#[test]
+fn test_gltf() {
+    let mut binary = Vec::new();
+    //Positions
+    for v in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+        binary.extend(v.iter().flat_map(|c| c.to_le_bytes()));
+    }
+    //Indices, padded to 4 bytes
+    binary.extend([0u16, 1, 2, 0].iter().flat_map(|i| i.to_le_bytes()));
+    //Joints
+    binary.extend([0u8, 1, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+    //Weights
+    for w in [
+        [0.5f32, 0.5, 0.0, 0.0],
+        [2.0, 0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+    ] {
+        binary.extend(w.iter().flat_map(|c| c.to_le_bytes()));
+    }
+    //Inverse bind matrices, the second joint is 1 unit above the first one
+    let identity = [
+        1.0f32, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+    binary.extend(
+        identity
+            .iter()
+            .chain(&[0.0, 0.0, 0.0, 1.0])
+            .flat_map(|c| c.to_le_bytes()),
+    );
+    binary.extend(
+        identity
+            .iter()
+            .chain(&[0.0, -1.0, 0.0, 1.0])
+            .flat_map(|c| c.to_le_bytes()),
+    );
+    //Keyframe times
+    binary.extend([0.0f32, 2.0].iter().flat_map(|c| c.to_le_bytes()));
+    //Rotations of the second joint, 0 and 90 degrees around z
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    binary.extend(
+        [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, half, half]
+            .iter()
+            .flat_map(|c| c.to_le_bytes()),
+    );
+    assert_eq!(binary.len(), 272);
+
+    let json = r#"{
+        "asset": {"version": "2.0"},
+        "buffers": [{"byteLength": 272}],
+        "bufferViews": [
+            {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 6},
+            {"buffer": 0, "byteOffset": 44, "byteLength": 12},
+            {"buffer": 0, "byteOffset": 56, "byteLength": 48},
+            {"buffer": 0, "byteOffset": 104, "byteLength": 128},
+            {"buffer": 0, "byteOffset": 232, "byteLength": 8},
+            {"buffer": 0, "byteOffset": 240, "byteLength": 32}
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"},
+            {"bufferView": 2, "componentType": 5121, "count": 3, "type": "VEC4"},
+            {"bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC4"},
+            {"bufferView": 4, "componentType": 5126, "count": 2, "type": "MAT4"},
+            {"bufferView": 5, "componentType": 5126, "count": 2, "type": "SCALAR"},
+            {"bufferView": 6, "componentType": 5126, "count": 2, "type": "VEC4"}
+        ],
+        "meshes": [{"primitives": [{
+            "attributes": {"POSITION": 0, "JOINTS_0": 2, "WEIGHTS_0": 3},
+            "indices": 1
+        }]}],
+        "nodes": [
+            {"name": "root", "children": [1]},
+            {"name": "tip", "translation": [0, 1, 0]},
+            {"mesh": 0, "skin": 0}
+        ],
+        "skins": [{"joints": [0, 1], "inverseBindMatrices": 4}],
+        "animations": [{
+            "name": "bend",
+            "samplers": [{"input": 5, "output": 6}],
+            "channels": [{"sampler": 0, "target": {"node": 1, "path": "rotation"}}]
+        }]
+    }"#;
+
+    //Assemble a glb file
+    let mut glb = Vec::new();
+    let json = format!("{json:<width$}", width = json.len().next_multiple_of(4));
+    glb.extend(GLB_MAGIC);
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + 8 + json.len() + 8 + binary.len()) as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(CHUNK_JSON.to_le_bytes());
+    glb.extend(json.as_bytes());
+    glb.extend((binary.len() as u32).to_le_bytes());
+    glb.extend(CHUNK_BIN.to_le_bytes());
+    glb.extend(&binary);
+
+    let gltf = Gltf::parse(&glb).unwrap();
+    assert_eq!(gltf.mesh_count(), 1);
+    assert_eq!(gltf.skin_count(), 1);
+
+    let (mesh, skin) = gltf.mesh(0).unwrap();
+    assert_eq!(mesh.indices, vec![0, 1, 2]);
+    assert_eq!(mesh.vertices[1].coords, Vec3::new(1.0, 0.0, 0.0));
+    //Flipped texture coordinates
+    assert_eq!(mesh.vertices[0].texture, Vec2::new(0.0, 1.0));
+    //Normals are calculated when missing
+    assert!((mesh.vertices[0].normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+    let skin = skin.unwrap();
+    assert_eq!(skin[0].joints, [0, 1, 0, 0]);
+    //Weights are normalized
+    assert_eq!(skin[1].weights, Vec4::new(1.0, 0.0, 0.0, 0.0));
+
+    let joints = gltf.skeleton(0).unwrap();
+    assert_eq!(joints.len(), 2);
+    assert_eq!(joints[0].parent, None);
+    assert_eq!(joints[1].parent, Some(0));
+    assert_eq!(joints[1].name, "tip");
+    assert_eq!(joints[1].rest_pose.translation, Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(joints[1].inverse_bind_matrix.m13, -1.0);
+
+    let clips = gltf.animations(0).unwrap();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].name, "bend");
+    assert!((clips[0].duration - 2.0).abs() < f32::EPSILON);
+    assert_eq!(clips[0].channels[0].joint, 1);
+    assert_eq!(clips[0].channels[0].interpolation, Interpolation::Linear);
+
+    assert!(gltf.mesh(1).is_err());
+    assert!(gltf.skeleton(1).is_err());
+
+    //Json gltf files with embedded buffers
+    assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+    let embedded = r#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 5, "uri": "data:application/octet-stream;base64,aGVsbG8="}]}"#;
+    assert_eq!(
+        Gltf::parse(embedded.as_bytes()).unwrap().buffers[0],
+        b"hello"
+    );
+    assert!(Gltf::parse(br#"{"asset": {"version": "1.0"}}"#).is_err());
+    assert!(
+        Gltf::parse(br#"{"asset": {"version": "2.0"}, "buffers": [{"uri": "a.bin"}]}"#).is_err()
+    );
+}
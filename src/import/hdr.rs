@@ -0,0 +1,279 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+use lunar_png::Image;
+
+use super::invalid;
+
+///A high dynamic range image
+pub struct HdrImage {
+    ///Width of the image
+    pub width: u32,
+    ///Height of the image
+    pub height: u32,
+    ///Rgba pixel data, rows are stored from top to bottom
+    pub data: Vec<f32>,
+}
+
+impl HdrImage {
+    ///Converts the image into an [`Image`] containing rgba 16 bit float data
+    ///
+    ///The type of the image is set to [`lunar_png::ImageType::Rgba16`], the data is in the native
+    ///byte order and is meant to be uploaded as [`wgpu::TextureFormat::Rgba16Float`]
+    #[must_use]
+    pub fn to_rgba16_float(&self) -> Image {
+        Image {
+            img_type: lunar_png::ImageType::Rgba16,
+            width: self.width,
+            height: self.height,
+            data: self
+                .data
+                .iter()
+                .flat_map(|v| f32_to_f16(*v).to_ne_bytes())
+                .collect(),
+        }
+    }
+}
+
+///Converts a float into a half precision float, rounding towards zero
+#[must_use]
+pub const fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF).cast_signed();
+    let mantissa = bits & 0x007F_FFFF;
+
+    //Nan and infinity
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa == 0 { 0 } else { 0x200 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        //Too big, infinity
+        sign | 0x7C00
+    } else if exponent <= 0 {
+        //Subnormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        sign | (mantissa >> (14 - exponent)) as u16
+    } else {
+        sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
+
+///Converts a half precision float into a float
+#[must_use]
+pub fn f16_to_f32(value: u16) -> f32 {
+    let sign = u32::from(value & 0x8000) << 16;
+    let exponent = u32::from((value >> 10) & 0x1F);
+    let mantissa = u32::from(value & 0x03FF);
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        //Subnormal, representable as a normal float
+        (0, _) => {
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x03FF) << 13
+        }
+        (0x1F, _) => sign | 0x7F80_0000 | mantissa << 13,
+        _ => sign | ((exponent + 112) << 23) | mantissa << 13,
+    };
+    f32::from_bits(bits)
+}
+
+///Converts a shared exponent rgbe pixel into rgb floats
+fn rgbe_to_rgb([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    //(value + 0.5) / 256 * 2^(e - 128)
+    let scale = f32::powi(2.0, i32::from(e) - 136);
+    [
+        (f32::from(r) + 0.5) * scale,
+        (f32::from(g) + 0.5) * scale,
+        (f32::from(b) + 0.5) * scale,
+    ]
+}
+
+///Reads a scanline of rgbe pixels, either flat or using the new rle encoding
+fn read_scanline(
+    data: &[u8],
+    position: &mut usize,
+    width: usize,
+) -> Result<Vec<[u8; 4]>, Box<dyn std::error::Error + Send>> {
+    let eof = || invalid("Unexpected end of data");
+    let mut read = |len: usize| {
+        let out = data.get(*position..*position + len).ok_or_else(eof);
+        *position += len;
+        out
+    };
+
+    let start = read(4)?;
+    let rle = (8..0x8000).contains(&width)
+        && start[0] == 2
+        && start[1] == 2
+        && start[2] & 0x80 == 0
+        && (usize::from(start[2]) << 8 | usize::from(start[3])) == width;
+
+    if !rle {
+        let mut out = vec![[start[0], start[1], start[2], start[3]]];
+        for p in read((width - 1) * 4)?.chunks(4) {
+            out.push([p[0], p[1], p[2], p[3]]);
+        }
+        return Ok(out);
+    }
+
+    //Each channel is stored separately
+    let mut out = vec![[0u8; 4]; width];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = read(1)?[0] as usize;
+            if count > 128 {
+                let count = count - 128;
+                let value = read(1)?[0];
+                for p in out.get_mut(x..x + count).ok_or_else(eof)? {
+                    p[channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 {
+                    return Err(invalid("Invalid run length"));
+                }
+                let values = read(count)?;
+                for (p, v) in out
+                    .get_mut(x..x + count)
+                    .ok_or_else(eof)?
+                    .iter_mut()
+                    .zip(values)
+                {
+                    p[channel] = *v;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+///Parses a byte array as a radiance hdr (.hdr) image
+///
+///Only the rgbe pixel format is supported
+///# Errors
+///Fails if the data is not a valid hdr image
+pub fn parse(data: &[u8]) -> Result<HdrImage, Box<dyn std::error::Error + Send>> {
+    if !data.starts_with(b"#?") {
+        return Err(invalid("Wrong signature"));
+    }
+
+    //Read the header, ending with an empty line
+    let mut position = 0;
+    let mut read_line = || {
+        let end = data[position..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| invalid("Unexpected end of data"))?;
+        let line = String::from_utf8_lossy(&data[position..position + end]).into_owned();
+        position += end + 1;
+        Ok::<_, Box<dyn std::error::Error + Send>>(line)
+    };
+
+    loop {
+        let line = read_line()?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if line
+            .strip_prefix("FORMAT=")
+            .is_some_and(|f| f.trim() != "32-bit_rle_rgbe")
+        {
+            return Err(invalid("Unsupported pixel format"));
+        }
+    }
+
+    //Resolution line, for example `-Y 512 +X 1024`
+    let resolution = read_line()?;
+    let resolution = resolution.split_whitespace().collect::<Vec<_>>();
+    let (top_to_bottom, height, width) = match resolution.as_slice() {
+        ["-Y", h, "+X", w] => (true, h, w),
+        ["+Y", h, "+X", w] => (false, h, w),
+        _ => return Err(invalid("Unsupported image orientation")),
+    };
+    let height: u32 = height.parse().map_err(|_| invalid("Invalid height"))?;
+    let width: u32 = width.parse().map_err(|_| invalid("Invalid width"))?;
+
+    if width == 0 || height == 0 {
+        return Err(invalid("Empty image"));
+    }
+
+    let mut rows = Vec::new();
+    for _ in 0..height {
+        let row = read_scanline(data, &mut position, width as usize)?
+            .into_iter()
+            .flat_map(|p| {
+                let [r, g, b] = rgbe_to_rgb(p);
+                [r, g, b, 1.0]
+            })
+            .collect::<Vec<_>>();
+        rows.push(row);
+    }
+
+    if !top_to_bottom {
+        rows.reverse();
+    }
+
+    Ok(HdrImage {
+        width,
+        height,
+        data: rows.concat(),
+    })
+}
+
+#[test]
+fn test_f32_to_f16() {
+    assert_eq!(f32_to_f16(0.0), 0);
+    assert_eq!(f32_to_f16(1.0), 0x3C00);
+    assert_eq!(f32_to_f16(-2.0), 0xC000);
+    assert_eq!(f32_to_f16(65504.0), 0x7BFF);
+    assert_eq!(f32_to_f16(1e10), 0x7C00);
+    assert_eq!(f32_to_f16(f32::NAN) & 0x7C00, 0x7C00);
+    //Smallest subnormal
+    assert_eq!(f32_to_f16(5.960_464_5e-8), 1);
+
+    for value in [0.0, 1.0, -2.0, 0.5, 65504.0, 5.960_464_5e-8, 6.1e-5, 1234.5] {
+        let half = f32_to_f16(value);
+        assert_eq!(f32_to_f16(f16_to_f32(half)), half);
+    }
+    assert!(f16_to_f32(0x7C00).is_infinite());
+}
+
+#[test]
+fn test_parse_hdr() {
+    let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n+Y 2 +X 8\n".to_vec();
+    //Bottom row, rle encoded, 8 pixels of 1.0 (128 with exponent 129)
+    data.extend_from_slice(&[2, 2, 0, 8]);
+    for value in [128, 128, 128, 129] {
+        data.extend_from_slice(&[128 + 8, value]);
+    }
+    //Top row, flat
+    for _ in 0..8 {
+        data.extend_from_slice(&[128, 0, 0, 130]);
+    }
+
+    let image = parse(&data).unwrap();
+    assert_eq!((image.width, image.height), (8, 2));
+
+    let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+    assert!(close(image.data[0], 2.0) && close(image.data[1], 0.0));
+    assert!(close(image.data[8 * 4], 1.0) && close(image.data[8 * 4 + 2], 1.0));
+    assert!(close(image.data[3], 1.0));
+
+    let half = image.to_rgba16_float();
+    assert_eq!(half.data.len(), 8 * 2 * 4 * 2);
+
+    assert!(parse(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n").is_err());
+    data.pop();
+    assert!(parse(&data).is_err());
+}
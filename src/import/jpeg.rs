@@ -0,0 +1,550 @@
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+use lunar_png::Image;
+
+use super::invalid;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send>>;
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+///A huffman table in the canonical form used by the jpeg specification
+#[derive(Clone)]
+struct Huffman {
+    ///Largest code of each length, -1 if there are no codes of that length
+    max_code: [i32; 17],
+    ///Offset of the first value of each length
+    val_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut max_code = [-1; 17];
+        let mut val_offset = [0; 17];
+        let mut code = 0;
+        let mut k = 0;
+        for length in 1..=16 {
+            let count = i32::from(counts[length - 1]);
+            if count != 0 {
+                val_offset[length] = k - code;
+                code += count;
+                k += count;
+                max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        Self {
+            max_code,
+            val_offset,
+            values: values.to_vec(),
+        }
+    }
+}
+
+///Reads entropy coded data bit by bit, skipping stuffed bytes
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    const fn new(data: &'a [u8], position: usize) -> Self {
+        Self {
+            data,
+            position,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn bit(&mut self) -> Result<u32> {
+        if self.bits == 0 {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| invalid("Unexpected end of data"))?;
+            if byte == 0xFF {
+                //A marker, pad with ones
+                if self.data.get(self.position + 1) != Some(&0) {
+                    self.buffer = 0xFF;
+                    self.bits = 8;
+                    return self.bit();
+                }
+                self.position += 2;
+            } else {
+                self.position += 1;
+            }
+            self.buffer = u32::from(byte);
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        Ok((self.buffer >> self.bits) & 1)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        let mut out = 0;
+        for _ in 0..count {
+            out = (out << 1) | self.bit()?;
+        }
+        Ok(out)
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8> {
+        let mut code = 0;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit()?.cast_signed();
+            if code <= table.max_code[length] {
+                return table
+                    .values
+                    .get((code + table.val_offset[length]) as usize)
+                    .copied()
+                    .ok_or_else(|| invalid("Invalid huffman code"));
+            }
+        }
+        Err(invalid("Invalid huffman code"))
+    }
+
+    ///Reads a value of the given size and extends its sign
+    fn receive_extend(&mut self, size: u8) -> Result<i32> {
+        if size == 0 {
+            return Ok(0);
+        }
+        if size > 16 {
+            return Err(invalid("Invalid coefficient size"));
+        }
+        let value = self.bits(u32::from(size))?.cast_signed();
+        if value < 1 << (size - 1) {
+            Ok(value - (1 << size) + 1)
+        } else {
+            Ok(value)
+        }
+    }
+
+    ///Skips to the restart marker that must follow
+    fn restart(&mut self) -> Result<()> {
+        self.bits = 0;
+        while self.position + 1 < self.data.len() {
+            if self.data[self.position] == 0xFF
+                && (0xD0..=0xD7).contains(&self.data[self.position + 1])
+            {
+                self.position += 2;
+                return Ok(());
+            }
+            self.position += 1;
+        }
+        Err(invalid("Missing restart marker"))
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc_table: usize,
+    ac_table: usize,
+    ///Width of the component plane in blocks, padded to whole mcus
+    blocks_w: usize,
+    ///Decoded samples
+    plane: Vec<u8>,
+    prediction: i32,
+}
+
+///Performs the inverse discrete cosine transform of a block of dequantized coefficients
+fn idct(coefficients: &[f32; 64], out: &mut [u8], stride: usize) {
+    use std::f32::consts::PI;
+    let table: [[f32; 8]; 8] = std::array::from_fn(|x| {
+        std::array::from_fn(|u| {
+            let c = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            c * f32::cos(2.0f32.mul_add(x as f32, 1.0) * u as f32 * PI / 16.0)
+        })
+    });
+
+    //Rows, then columns
+    let mut temp = [0.0; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            temp[v * 8 + x] = (0..8).map(|u| table[x][u] * coefficients[v * 8 + u]).sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|v| table[y][v] * temp[v * 8 + x]).sum();
+            out[y * stride + x] = (value / 4.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+///Parses a byte array as a jpeg image
+///
+///Only baseline (sequential huffman coded) grayscale and YCbCr images are supported. The image is
+///always decoded into rgba8, rows are stored from top to bottom
+///# Errors
+///Fails if the data is not a valid jpeg image or uses an unsupported encoding
+#[allow(clippy::too_many_lines)]
+pub fn parse(data: &[u8]) -> Result<Image> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(invalid("Wrong signature"));
+    }
+
+    let eof = || invalid("Unexpected end of data");
+    let mut quant = [[0u16; 64]; 4];
+    let mut dc_tables: [Option<Huffman>; 4] = Default::default();
+    let mut ac_tables: [Option<Huffman>; 4] = Default::default();
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0, 0);
+    let (mut mcus_x, mut mcus_y) = (0, 0);
+    let mut restart_interval = 0;
+    let mut position = 2;
+
+    loop {
+        //Skip fill bytes
+        while data.get(position) == Some(&0xFF) && data.get(position + 1) == Some(&0xFF) {
+            position += 1;
+        }
+        let marker = *data.get(position + 1).ok_or_else(eof)?;
+        if data[position] != 0xFF {
+            return Err(invalid("Expected a marker"));
+        }
+        position += 2;
+
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+
+        let length = usize::from(u16::from_be_bytes(
+            data.get(position..position + 2)
+                .ok_or_else(eof)?
+                .try_into()
+                .unwrap(),
+        ));
+        let segment = data.get(position + 2..position + length).ok_or_else(eof)?;
+        position += length;
+
+        match marker {
+            //Quantization tables
+            0xDB => {
+                let mut s = segment;
+                while let [info, rest @ ..] = s {
+                    let id = usize::from(info & 0x0F);
+                    let wide = info >> 4 != 0;
+                    let size = if wide { 128 } else { 64 };
+                    if id > 3 || rest.len() < size {
+                        return Err(invalid("Invalid quantization table"));
+                    }
+                    for i in 0..64 {
+                        quant[id][ZIGZAG[i]] = if wide {
+                            u16::from_be_bytes([rest[i * 2], rest[i * 2 + 1]])
+                        } else {
+                            u16::from(rest[i])
+                        };
+                    }
+                    s = &rest[size..];
+                }
+            }
+            //Huffman tables
+            0xC4 => {
+                let mut s = segment;
+                while let [info, rest @ ..] = s {
+                    let id = usize::from(info & 0x0F);
+                    let counts = rest.get(..16).ok_or_else(eof)?;
+                    let total = counts.iter().map(|c| usize::from(*c)).sum::<usize>();
+                    let values = rest.get(16..16 + total).ok_or_else(eof)?;
+                    if id > 3 {
+                        return Err(invalid("Invalid huffman table"));
+                    }
+                    let table = Some(Huffman::new(counts, values));
+                    if info >> 4 == 0 {
+                        dc_tables[id] = table;
+                    } else {
+                        ac_tables[id] = table;
+                    }
+                    s = &rest[16 + total..];
+                }
+            }
+            //Restart interval
+            0xDD => {
+                let bytes = segment.get(..2).ok_or_else(eof)?;
+                restart_interval = usize::from(u16::from_be_bytes([bytes[0], bytes[1]]));
+            }
+            //Baseline and extended sequential frames
+            0xC0 | 0xC1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err(invalid("Unsupported sample precision"));
+                }
+                height = usize::from(u16::from_be_bytes([segment[1], segment[2]]));
+                width = usize::from(u16::from_be_bytes([segment[3], segment[4]]));
+                let count = usize::from(segment[5]);
+                if width == 0 || height == 0 {
+                    return Err(invalid("Empty image"));
+                }
+                if !matches!(count, 1 | 3) {
+                    return Err(invalid("Unsupported number of components"));
+                }
+
+                for c in segment.get(6..6 + count * 3).ok_or_else(eof)?.chunks(3) {
+                    let (h, v) = (usize::from(c[1] >> 4), usize::from(c[1] & 0x0F));
+                    if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                        return Err(invalid("Invalid component"));
+                    }
+                    components.push(Component {
+                        id: c[0],
+                        h,
+                        v,
+                        quant: usize::from(c[2]),
+                        dc_table: 0,
+                        ac_table: 0,
+                        blocks_w: 0,
+                        plane: Vec::new(),
+                        prediction: 0,
+                    });
+                }
+
+                let h_max = components.iter().map(|c| c.h).max().unwrap();
+                let v_max = components.iter().map(|c| c.v).max().unwrap();
+                mcus_x = width.div_ceil(8 * h_max);
+                mcus_y = height.div_ceil(8 * v_max);
+                for c in &mut components {
+                    c.blocks_w = mcus_x * c.h;
+                    c.plane = vec![0; c.blocks_w * 8 * mcus_y * c.v * 8];
+                }
+            }
+            //Other frame types
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err(invalid("Only baseline jpeg images are supported"));
+            }
+            //Start of scan
+            0xDA => {
+                if components.is_empty() {
+                    return Err(invalid("Scan before frame header"));
+                }
+                let count = usize::from(*segment.first().ok_or_else(eof)?);
+                let mut scan = Vec::new();
+                for c in segment.get(1..1 + count * 2).ok_or_else(eof)?.chunks(2) {
+                    let index = components
+                        .iter()
+                        .position(|i| i.id == c[0])
+                        .ok_or_else(|| invalid("Unknown scan component"))?;
+                    let component = &mut components[index];
+                    component.dc_table = usize::from(c[1] >> 4);
+                    component.ac_table = usize::from(c[1] & 0x0F);
+                    component.prediction = 0;
+                    if component.dc_table > 3 || component.ac_table > 3 {
+                        return Err(invalid("Invalid huffman table"));
+                    }
+                    scan.push(index);
+                }
+                if scan.is_empty() {
+                    return Err(invalid("Empty scan"));
+                }
+
+                //Non interleaved scans cover only the blocks inside of the image
+                let units = if scan.len() == 1 {
+                    let c = &components[scan[0]];
+                    let h_max = components.iter().map(|c| c.h).max().unwrap();
+                    let v_max = components.iter().map(|c| c.v).max().unwrap();
+                    let w = (width * c.h).div_ceil(h_max).div_ceil(8);
+                    let h = (height * c.v).div_ceil(v_max).div_ceil(8);
+                    (w, h)
+                } else {
+                    (mcus_x, mcus_y)
+                };
+
+                let mut reader = BitReader::new(data, position);
+                let mut coefficients = [0.0f32; 64];
+                for unit in 0..units.0 * units.1 {
+                    if restart_interval != 0 && unit != 0 && unit % restart_interval == 0 {
+                        reader.restart()?;
+                        for &i in &scan {
+                            components[i].prediction = 0;
+                        }
+                    }
+                    let (unit_x, unit_y) = (unit % units.0, unit / units.0);
+
+                    for &i in &scan {
+                        let (bh, bv) = if scan.len() == 1 {
+                            (1, 1)
+                        } else {
+                            (components[i].h, components[i].v)
+                        };
+                        for by in 0..bv {
+                            for bx in 0..bh {
+                                let component = &mut components[i];
+                                let dc = dc_tables[component.dc_table]
+                                    .as_ref()
+                                    .ok_or_else(|| invalid("Missing huffman table"))?;
+                                let ac = ac_tables[component.ac_table]
+                                    .as_ref()
+                                    .ok_or_else(|| invalid("Missing huffman table"))?;
+                                let table = &quant[component.quant];
+
+                                coefficients.fill(0.0);
+                                let size = reader.decode(dc)?;
+                                component.prediction += reader.receive_extend(size)?;
+                                coefficients[0] =
+                                    (component.prediction * i32::from(table[0])) as f32;
+
+                                let mut k = 1;
+                                while k < 64 {
+                                    let symbol = reader.decode(ac)?;
+                                    let (run, size) = (usize::from(symbol >> 4), symbol & 0x0F);
+                                    if size == 0 {
+                                        if run != 15 {
+                                            break;
+                                        }
+                                        k += 16;
+                                        continue;
+                                    }
+                                    k += run;
+                                    if k > 63 {
+                                        return Err(invalid("Invalid coefficient index"));
+                                    }
+                                    let index = ZIGZAG[k];
+                                    coefficients[index] = (reader.receive_extend(size)?
+                                        * i32::from(table[index]))
+                                        as f32;
+                                    k += 1;
+                                }
+
+                                let block_x = unit_x * bh + bx;
+                                let block_y = unit_y * bv + by;
+                                let stride = component.blocks_w * 8;
+                                let start = block_y * 8 * stride + block_x * 8;
+                                idct(&coefficients, &mut component.plane[start..], stride);
+                            }
+                        }
+                    }
+                }
+
+                //Continue after the entropy coded data
+                position = reader.position;
+                while position + 1 < data.len()
+                    && !(data[position] == 0xFF
+                        && data[position + 1] != 0
+                        && !(0xD0..=0xD7).contains(&data[position + 1]))
+                {
+                    position += 1;
+                }
+            }
+            //Application data, comments and others
+            _ => {}
+        }
+    }
+
+    if components.is_empty() {
+        return Err(invalid("Missing frame header"));
+    }
+
+    //Upsample and convert into rgb
+    let h_max = components.iter().map(|c| c.h).max().unwrap();
+    let v_max = components.iter().map(|c| c.v).max().unwrap();
+    //Subsampled components are linearly interpolated between sample centers
+    let sample = |c: &Component, x: usize, y: usize| {
+        if c.h == h_max && c.v == v_max {
+            return f32::from(c.plane[y * c.blocks_w * 8 + x]);
+        }
+        let w = (width * c.h).div_ceil(h_max);
+        let h = (height * c.v).div_ceil(v_max);
+        let position = |p: usize, factor: usize, max: usize, len: usize| {
+            let p = ((p as f32 + 0.5) * factor as f32 / max as f32 - 0.5).max(0.0);
+            let low = (p as usize).min(len - 1);
+            (low, (low + 1).min(len - 1), p.fract())
+        };
+        let (x0, x1, fx) = position(x, c.h, h_max, w);
+        let (y0, y1, fy) = position(y, c.v, v_max, h);
+        let at = |x: usize, y: usize| f32::from(c.plane[y * c.blocks_w * 8 + x]);
+        let top = (at(x1, y0) - at(x0, y0)).mul_add(fx, at(x0, y0));
+        let bottom = (at(x1, y1) - at(x0, y1)).mul_add(fx, at(x0, y1));
+        (bottom - top).mul_add(fy, top)
+    };
+
+    let mut out = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            if let [gray] = components.as_slice() {
+                let v = sample(gray, x, y) as u8;
+                out.extend_from_slice(&[v, v, v, 255]);
+                continue;
+            }
+            let luma = sample(&components[0], x, y);
+            let cb = sample(&components[1], x, y) - 128.0;
+            let cr = sample(&components[2], x, y) - 128.0;
+            let to_u8 = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+            out.extend_from_slice(&[
+                to_u8(1.402f32.mul_add(cr, luma)),
+                to_u8(0.714_136f32.mul_add(-cr, 0.344_136f32.mul_add(-cb, luma))),
+                to_u8(1.772f32.mul_add(cb, luma)),
+                255,
+            ]);
+        }
+    }
+
+    Ok(Image {
+        img_type: lunar_png::ImageType::Rgba8,
+        width: width as u32,
+        height: height as u32,
+        data: out,
+    })
+}
+
+#[test]
+fn test_parse_jpeg() {
+    //A 16x16 baseline grayscale image with a single flat gray block per 8x8 block
+    let mut data = vec![0xFF, 0xD8];
+    //Quantization table with all ones
+    data.extend_from_slice(&[0xFF, 0xDB, 0, 67, 0]);
+    data.extend_from_slice(&[1; 64]);
+    //Frame header, 16x16, 1 component
+    data.extend_from_slice(&[0xFF, 0xC0, 0, 11, 8, 0, 16, 0, 16, 1, 1, 0x11, 0]);
+    //Dc table, sizes 0 and 6 coded as `0` and `1`
+    data.extend_from_slice(&[0xFF, 0xC4, 0, 21, 0x00, 2]);
+    data.extend_from_slice(&[0; 15]);
+    data.extend_from_slice(&[0, 6]);
+    //Ac table, only the end of block symbol coded as `0`
+    data.extend_from_slice(&[0xFF, 0xC4, 0, 20, 0x10, 1]);
+    data.extend_from_slice(&[0; 15]);
+    data.push(0);
+    //Start of scan
+    data.extend_from_slice(&[0xFF, 0xDA, 0, 8, 1, 1, 0x00, 0, 63, 0]);
+    //Dc differences: +32, 0, -63, 0 (a dc of 32 raises the block by 32 / 8 = 4)
+    //1 100000 0 | 0 0 | 1 000000 0 | 0 0, padded with ones
+    data.extend_from_slice(&[0b1100_0000, 0b0010_0000, 0b0000_1111]);
+    data.extend_from_slice(&[0xFF, 0xD9]);
+
+    let image = parse(&data).unwrap();
+    assert_eq!((image.width, image.height), (16, 16));
+    let value = |x: usize, y: usize| image.data[(y * 16 + x) * 4];
+    assert_eq!(value(0, 0), 132);
+    assert_eq!(value(8, 0), 132);
+    assert_eq!(value(0, 8), 124);
+    assert_eq!(value(15, 15), 124);
+    assert_eq!(image.data[3], 255);
+
+    //Progressive images are not supported
+    let mut progressive = data.clone();
+    let frame = progressive
+        .windows(2)
+        .position(|w| w == [0xFF, 0xC0])
+        .unwrap();
+    progressive[frame + 1] = 0xC2;
+    assert!(parse(&progressive).is_err());
+
+    assert!(parse(&data[..data.len() / 2]).is_err());
+}
@@ -0,0 +1,320 @@
+use super::invalid;
+
+///A parsed json value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Self>),
+    ///Members of the object in the order they were written in
+    Object(Vec<(String, Self)>),
+}
+
+///Value returned when indexing into something that is not an array or an object
+static NULL: Value = Value::Null;
+
+impl Value {
+    ///Returns the member of the object with the given key, or null if there is no such member
+    pub fn get(&self, key: &str) -> &Self {
+        match self {
+            Self::Object(members) => members
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    ///Returns the element of the array at the given index, or null if there is no such element
+    pub fn at(&self, index: usize) -> &Self {
+        match self {
+            Self::Array(elements) => elements.get(index).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+
+    pub const fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    pub const fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    ///Returns the value as an unsigned integer, if it is a non negative whole number
+    #[allow(clippy::cast_sign_loss)]
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+            .map(|n| n as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub const fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    ///Returns the elements of the array, or an empty slice if the value is not an array
+    pub fn elements(&self) -> &[Self] {
+        match self {
+            Self::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+}
+
+///Parses a json document
+///
+///# Errors
+///Fails if the text is not valid json
+pub fn parse(text: &str) -> Result<Value, Box<dyn std::error::Error + Send>> {
+    let mut parser = Parser {
+        data: text.as_bytes(),
+        position: 0,
+        depth: 0,
+    };
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position != parser.data.len() {
+        return Err(invalid(
+            "Unexpected data after the end of the json document",
+        ));
+    }
+    Ok(value)
+}
+
+///Maximum nesting of arrays and objects
+const MAX_DEPTH: u32 = 256;
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+    depth: u32,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|c| matches!(c, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), Box<dyn std::error::Error + Send>> {
+        if self.data[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(())
+        } else {
+            Err(invalid("Invalid json literal"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Box<dyn std::error::Error + Send>> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.expect("true").map(|()| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|()| Value::Bool(false)),
+            Some(b'n') => self.expect("null").map(|()| Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(invalid("Unexpected character in json")),
+            None => Err(invalid("Unexpected end of json")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, Box<dyn std::error::Error + Send>>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send>> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid("Json is nested too deeply"));
+        }
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Value, Box<dyn std::error::Error + Send>> {
+        //Skip {
+        self.position += 1;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(invalid("Expected a json object key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.push((key, self.value()?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(invalid("Expected , or } in a json object")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Box<dyn std::error::Error + Send>> {
+        //Skip [
+        self.position += 1;
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(elements));
+        }
+
+        loop {
+            elements.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(elements));
+                }
+                _ => return Err(invalid("Expected , or ] in a json array")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Box<dyn std::error::Error + Send>> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.position += 1;
+        }
+
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| invalid("Invalid json number"))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, Box<dyn std::error::Error + Send>> {
+        let digits = self
+            .data
+            .get(self.position..self.position + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| invalid("Invalid json unicode escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn std::error::Error + Send>> {
+        //Skip "
+        self.position += 1;
+        let mut bytes = Vec::new();
+
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| invalid("Unterminated json string"))?;
+            self.position += 1;
+
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self
+                        .peek()
+                        .ok_or_else(|| invalid("Unterminated json string"))?;
+                    self.position += 1;
+
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex_escape()?;
+                            //Surrogate pair
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect("\\u")?;
+                                let low = self.hex_escape()?;
+                                code =
+                                    0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00));
+                            }
+                            char::from_u32(code)
+                                .ok_or_else(|| invalid("Invalid json unicode escape"))?
+                        }
+                        _ => return Err(invalid("Invalid json escape sequence")),
+                    };
+                    bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => bytes.push(c),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| invalid("Json string is not valid utf-8"))
+    }
+}
+
+#[test]
+fn test_json() {
+    let value =
+        parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d\né😀"}, "e": [] } "#).unwrap();
+
+    assert_eq!(value.get("a").at(0).as_usize(), Some(1));
+    assert_eq!(value.get("a").at(1).as_f64(), Some(-25.0));
+    assert_eq!(value.get("a").at(1).as_usize(), None);
+    assert_eq!(value.get("a").at(2).as_bool(), Some(true));
+    assert!(value.get("a").at(3).is_null());
+    assert!(value.get("a").at(4).is_null());
+    assert_eq!(value.get("b").get("c").as_str(), Some("d\né😀"));
+    assert!(value.get("e").elements().is_empty());
+    assert!(value.get("missing").get("nested").is_null());
+
+    assert!(parse("[1, 2").is_err());
+    assert!(parse("{\"a\" 1}").is_err());
+    assert!(parse("[1] 2").is_err());
+    assert!(parse("\"unterminated").is_err());
+    assert!(parse(&"[".repeat(1000)).is_err());
+}
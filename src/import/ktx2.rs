@@ -0,0 +1,272 @@
+#![allow(clippy::cast_possible_truncation)]
+use super::{compressed::CompressedImage, invalid};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+///Astc block sizes in the order of the vulkan formats
+const ASTC_BLOCKS: [wgpu::AstcBlock; 14] = [
+    wgpu::AstcBlock::B4x4,
+    wgpu::AstcBlock::B5x4,
+    wgpu::AstcBlock::B5x5,
+    wgpu::AstcBlock::B6x5,
+    wgpu::AstcBlock::B6x6,
+    wgpu::AstcBlock::B8x5,
+    wgpu::AstcBlock::B8x6,
+    wgpu::AstcBlock::B8x8,
+    wgpu::AstcBlock::B10x5,
+    wgpu::AstcBlock::B10x6,
+    wgpu::AstcBlock::B10x8,
+    wgpu::AstcBlock::B10x10,
+    wgpu::AstcBlock::B12x10,
+    wgpu::AstcBlock::B12x12,
+];
+
+///Converts a vulkan format into a texture format, bgra formats are converted into rgba ones
+const fn texture_format(format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+
+    Some(match format {
+        37 | 44 => F::Rgba8Unorm,
+        43 | 50 => F::Rgba8UnormSrgb,
+        97 => F::Rgba16Float,
+        131 | 133 => F::Bc1RgbaUnorm,
+        132 | 134 => F::Bc1RgbaUnormSrgb,
+        135 => F::Bc2RgbaUnorm,
+        136 => F::Bc2RgbaUnormSrgb,
+        137 => F::Bc3RgbaUnorm,
+        138 => F::Bc3RgbaUnormSrgb,
+        139 => F::Bc4RUnorm,
+        140 => F::Bc4RSnorm,
+        141 => F::Bc5RgUnorm,
+        142 => F::Bc5RgSnorm,
+        143 => F::Bc6hRgbUfloat,
+        144 => F::Bc6hRgbFloat,
+        145 => F::Bc7RgbaUnorm,
+        146 => F::Bc7RgbaUnormSrgb,
+        147 => F::Etc2Rgb8Unorm,
+        148 => F::Etc2Rgb8UnormSrgb,
+        149 => F::Etc2Rgb8A1Unorm,
+        150 => F::Etc2Rgb8A1UnormSrgb,
+        151 => F::Etc2Rgba8Unorm,
+        152 => F::Etc2Rgba8UnormSrgb,
+        153 => F::EacR11Unorm,
+        154 => F::EacR11Snorm,
+        155 => F::EacRg11Unorm,
+        156 => F::EacRg11Snorm,
+        157..=184 => F::Astc {
+            block: ASTC_BLOCKS[(format - 157) as usize / 2],
+            channel: if format % 2 == 1 {
+                wgpu::AstcChannel::Unorm
+            } else {
+                wgpu::AstcChannel::UnormSrgb
+            },
+        },
+        1_000_066_000..=1_000_066_013 => F::Astc {
+            block: ASTC_BLOCKS[(format - 1_000_066_000) as usize],
+            channel: wgpu::AstcChannel::Hdr,
+        },
+        _ => return None,
+    })
+}
+
+///Finds the value of a key in the key value data
+fn find_value<'a>(data: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let length = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let entry = data.get(offset + 4..offset + 4 + length)?;
+
+        if let Some(value) = entry
+            .strip_prefix(key.as_bytes())
+            .and_then(|v| v.strip_prefix(b"\0"))
+        {
+            return Some(value);
+        }
+        offset += (4 + length).next_multiple_of(4);
+    }
+    None
+}
+
+///Parses a ktx2 file containing a 2d texture or a cubemap
+///
+///Supercompressed and basis universal files, as well as 3d textures and texture arrays are not
+///supported. Rows are stored in the same order as in the file
+///# Errors
+///Fails if the data is not a valid ktx2 file or uses an unsupported format or layout
+pub fn parse(data: &[u8]) -> Result<CompressedImage, Box<dyn std::error::Error + Send>> {
+    if data.len() < HEADER_SIZE || data[..12] != IDENTIFIER {
+        return Err(invalid("Not a ktx2 file"));
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let read_u64 = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+    let vk_format = read_u32(12);
+    let width = read_u32(20);
+    //1d textures have a height of 0
+    let height = read_u32(24).max(1);
+    let depth = read_u32(28);
+    let layer_count = read_u32(32);
+    let faces = read_u32(36);
+    let mip_count = read_u32(40).max(1);
+    let supercompression = read_u32(44);
+
+    if vk_format == 0 {
+        return Err(invalid("Basis universal ktx2 files are not supported"));
+    }
+    let format = texture_format(vk_format).ok_or_else(|| invalid("Unsupported texture format"))?;
+    if supercompression != 0 {
+        return Err(invalid("Supercompressed ktx2 files are not supported"));
+    }
+    if depth > 1 || layer_count > 1 {
+        return Err(invalid("3d textures and texture arrays are not supported"));
+    }
+    if faces != 1 && faces != 6 {
+        return Err(invalid("Invalid number of faces"));
+    }
+    if (mip_count as usize)
+        .checked_mul(LEVEL_INDEX_SIZE)
+        .and_then(|size| size.checked_add(HEADER_SIZE))
+        .is_none_or(|end| end > data.len())
+    {
+        return Err(invalid("Unexpected end of file"));
+    }
+
+    if faces == 1 {
+        let kvd_offset = read_u32(56) as usize;
+        let kvd_length = read_u32(60) as usize;
+        let orientation = data
+            .get(kvd_offset..kvd_offset + kvd_length)
+            .and_then(|kvd| find_value(kvd, "KTXorientation"));
+        //Rows are expected to go from bottom to top
+        if orientation.is_none_or(|o| o.get(1) != Some(&b'u')) {
+            log::warn!(
+                "Ktx2 texture rows are stored from top to bottom, it will appear upside down"
+            );
+        }
+    }
+
+    let mut image = CompressedImage {
+        format,
+        width,
+        height,
+        layers: faces,
+        mip_count,
+        data: Vec::new(),
+    };
+
+    //Levels contain all of the faces, while the faces need to be stored with all of their levels
+    let mut levels = Vec::new();
+    for level in 0..mip_count {
+        let index = HEADER_SIZE + LEVEL_INDEX_SIZE * level as usize;
+        let offset = read_u64(index) as usize;
+        let length = read_u64(index + 8) as usize;
+        let face_size = image
+            .checked_level_size(level)
+            .ok_or_else(|| invalid("Texture is too large"))?;
+
+        if face_size.checked_mul(faces as usize) != Some(length) {
+            return Err(invalid("Invalid level size"));
+        }
+        levels.push(
+            data.get(offset..)
+                .and_then(|data| data.get(..length))
+                .ok_or_else(|| invalid("Unexpected end of file"))?,
+        );
+    }
+    for face in 0..faces as usize {
+        for (level, data) in levels.iter().enumerate() {
+            let size = image.level_size(level as u32);
+            image
+                .data
+                .extend_from_slice(&data[face * size..(face + 1) * size]);
+        }
+    }
+
+    if matches!(vk_format, 44 | 50) {
+        for pixel in image.data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    image.validate()?;
+    Ok(image)
+}
+
+///Builds a ktx2 file with the given levels, each level contains all of the faces
+#[cfg(test)]
+fn build_ktx2(vk_format: u32, size: u32, faces: u32, levels: &[Vec<u8>], kvd: &[u8]) -> Vec<u8> {
+    let mut header = IDENTIFIER.to_vec();
+    for value in [
+        vk_format,
+        1,
+        size,
+        size,
+        0,
+        0,
+        faces,
+        levels.len() as u32,
+        0,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    let kvd_offset = (HEADER_SIZE + LEVEL_INDEX_SIZE * levels.len()) as u32;
+    for value in [0, 0, kvd_offset, kvd.len() as u32] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&[0; 16]);
+
+    let mut offset = kvd_offset as usize + kvd.len();
+    for level in levels {
+        for value in [offset, level.len(), level.len()] {
+            header.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        offset += level.len();
+    }
+    header.extend_from_slice(kvd);
+    for level in levels {
+        header.extend_from_slice(level);
+    }
+    header
+}
+
+#[test]
+fn test_parse_ktx2() {
+    let mut kvd = Vec::new();
+    kvd.extend_from_slice(&18u32.to_le_bytes());
+    kvd.extend_from_slice(b"KTXorientation\0ru\0\0\0");
+
+    //4x4 bc1 with 3 mips
+    let levels = [vec![1; 8], vec![2; 8], vec![3; 8]];
+    let image = parse(&build_ktx2(131, 4, 1, &levels, &kvd)).unwrap();
+    assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+    assert_eq!((image.width, image.height), (4, 4));
+    assert_eq!((image.layers, image.mip_count), (1, 3));
+    assert_eq!(image.data, levels.concat());
+
+    //Cubemap faces are reordered to be stored with their mips
+    let levels = [
+        (0..6).flat_map(|f| [f; 16]).collect::<Vec<_>>(),
+        (0..6).flat_map(|f| [f + 10; 16]).collect::<Vec<_>>(),
+    ];
+    let image = parse(&build_ktx2(157, 4, 6, &levels, &[])).unwrap();
+    assert_eq!(image.layers, 6);
+    assert_eq!(&image.data[..32], &[[0; 16], [10; 16]].concat());
+    assert_eq!(&image.data[32..64], &[[1; 16], [11; 16]].concat());
+
+    //Bgra is converted into rgba
+    let image = parse(&build_ktx2(44, 1, 1, &[vec![1, 2, 3, 4]], &[])).unwrap();
+    assert_eq!(image.format, wgpu::TextureFormat::Rgba8Unorm);
+    assert_eq!(image.data, [3, 2, 1, 4]);
+
+    assert!(parse(&[0; 100]).is_err());
+    //Missing data
+    assert!(parse(&build_ktx2(131, 8, 1, &[vec![0; 8]], &[])).is_err());
+    //Basis universal
+    assert!(parse(&build_ktx2(0, 4, 1, &[vec![0; 8]], &[])).is_err());
+    //The size of the level does not fit into an u64
+    assert!(parse(&build_ktx2(97, u32::MAX, 1, &[vec![0; 8]], &[])).is_err());
+}
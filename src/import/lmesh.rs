@@ -0,0 +1,416 @@
+//! Binary preprocessed mesh format (`.lmesh`)
+//!
+//! Stores the mesh exactly as it is uploaded to the gpu, so loading it does not require any
+//! parsing, the vertex and index data is read directly out of the loaded bytes
+//!
+//! # Format
+//!
+//! All numbers are stored in the native byte order of the target (little endian on all supported
+//! targets), all sections are 4 byte aligned
+//!
+//! | Section   | Size                     | Description                                       |
+//! |-----------|--------------------------|---------------------------------------------------|
+//! | Header    | 52                       | Magic, version, flags, counts and bounds          |
+//! | Lod table | `lod_count * 8`          | Offset and count of indices of each lod           |
+//! | Vertices  | `vertex_count * 32`      | [`Vertex`] data                                   |
+//! | Tangents  | `vertex_count * 16`      | Only present if the tangent flag is set           |
+//! | Indices   | `index_count * 4`        | Indices of all lods                               |
+#![allow(clippy::cast_possible_truncation)]
+use std::{borrow::Cow, ops::Range, path::Path};
+
+use super::invalid;
+use crate::{
+    math::{Vec3, Vec4, Vector},
+    structures::{Index, Mesh, Vertex},
+};
+
+///Magic bytes at the start of every binary mesh
+pub const MAGIC: [u8; 4] = *b"LMSH";
+///Current version of the format
+pub const VERSION: u32 = 1;
+
+///Set if the mesh contains tangents
+const FLAG_TANGENTS: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+///Header of a binary mesh
+struct Header {
+    magic: [u8; 4],
+    version: u32,
+    flags: u32,
+    vertex_count: u32,
+    ///Total number of indices of all lods
+    index_count: u32,
+    lod_count: u32,
+    bounds_min: Vec3,
+    bounds_max: Vec3,
+    ///Distance to the vertex furthest from the origin
+    extent: f32,
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<Header>();
+
+///Casts the bytes into a slice of `T` without copying if the data is aligned, otherwise copies
+fn cast<T: bytemuck::Pod>(data: &[u8]) -> Cow<'_, [T]> {
+    bytemuck::try_cast_slice(data).map_or_else(
+        |_| Cow::Owned(bytemuck::pod_collect_to_vec(data)),
+        Cow::Borrowed,
+    )
+}
+
+///A binary mesh read out of a byte slice
+///
+///If the bytes are 4 byte aligned, the vertex and index data is borrowed directly from them
+pub struct MeshView<'a> {
+    vertices: Cow<'a, [Vertex]>,
+    tangents: Option<Cow<'a, [Vec4]>>,
+    indices: Cow<'a, [Index]>,
+    ///Offset and count of the indices of each lod
+    lods: Vec<[u32; 2]>,
+    bounds_min: Vec3,
+    bounds_max: Vec3,
+    extent: f32,
+}
+
+impl<'a> MeshView<'a> {
+    ///Reads a binary mesh
+    ///
+    ///# Errors
+    ///Returns an error if the data is not a valid binary mesh
+    pub fn parse(data: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Send>> {
+        if data.len() < HEADER_SIZE {
+            return Err(invalid("Wrong header size"));
+        }
+        let header: Header = bytemuck::pod_read_unaligned(&data[..HEADER_SIZE]);
+
+        if header.magic != MAGIC {
+            return Err(invalid("Not a binary mesh"));
+        }
+        if header.version != VERSION {
+            return Err(invalid("Unsupported binary mesh version"));
+        }
+        if header.lod_count == 0 {
+            return Err(invalid("Mesh has no lods"));
+        }
+
+        let vertex_count = header.vertex_count as usize;
+        let index_count = header.index_count as usize;
+        let lod_count = header.lod_count as usize;
+        let has_tangents = header.flags & FLAG_TANGENTS != 0;
+
+        //The counts can describe sections larger than the address space on 32 bit targets
+        let section_end = |start: usize, count: usize, size: usize| {
+            count
+                .checked_mul(size)
+                .and_then(|size| start.checked_add(size))
+                .ok_or_else(|| invalid("Unexpected end of data"))
+        };
+
+        let lods_start = HEADER_SIZE;
+        let vertices_start = section_end(lods_start, lod_count, 8)?;
+        let tangents_start =
+            section_end(vertices_start, vertex_count, std::mem::size_of::<Vertex>())?;
+        let indices_start = if has_tangents {
+            section_end(tangents_start, vertex_count, std::mem::size_of::<Vec4>())?
+        } else {
+            tangents_start
+        };
+        let end = section_end(indices_start, index_count, std::mem::size_of::<Index>())?;
+
+        if data.len() < end {
+            return Err(invalid("Unexpected end of data"));
+        }
+
+        let lods = cast::<[u32; 2]>(&data[lods_start..vertices_start]).into_owned();
+        for [offset, count] in &lods {
+            if offset
+                .checked_add(*count)
+                .is_none_or(|end| end as usize > index_count)
+            {
+                return Err(invalid("Lod indices out of bounds"));
+            }
+        }
+
+        let indices = cast::<Index>(&data[indices_start..end]);
+        if indices.iter().any(|i| *i as usize >= vertex_count) {
+            return Err(invalid("Index out of bounds"));
+        }
+
+        Ok(Self {
+            vertices: cast(&data[vertices_start..tangents_start]),
+            tangents: has_tangents.then(|| cast(&data[tangents_start..indices_start])),
+            indices,
+            lods,
+            bounds_min: header.bounds_min,
+            bounds_max: header.bounds_max,
+            extent: header.extent,
+        })
+    }
+
+    ///Returns the vertices of the mesh
+    #[must_use]
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    ///Returns the tangents of the mesh, if it has them
+    #[must_use]
+    pub fn tangents(&self) -> Option<&[Vec4]> {
+        self.tangents.as_deref()
+    }
+
+    ///Returns the indices of the full detail mesh (lod 0)
+    #[must_use]
+    pub fn indices(&self) -> &[Index] {
+        self.lod_indices(0).unwrap()
+    }
+
+    ///Returns the number of lods in the mesh, including the full detail mesh
+    #[must_use]
+    pub const fn lod_count(&self) -> usize {
+        self.lods.len()
+    }
+
+    ///Returns the indices of the given lod
+    #[must_use]
+    pub fn lod_indices(&self, lod: usize) -> Option<&[Index]> {
+        let range = self.lod_range(lod)?;
+        Some(&self.indices[range.start as usize..range.end as usize])
+    }
+
+    ///Returns the range of the given lod in the indices of all lods
+    #[must_use]
+    pub fn lod_range(&self, lod: usize) -> Option<Range<u32>> {
+        let [offset, count] = *self.lods.get(lod)?;
+        Some(offset..offset + count)
+    }
+
+    ///Returns the indices of all lods
+    #[must_use]
+    pub fn all_indices(&self) -> &[Index] {
+        &self.indices
+    }
+
+    ///Returns the minimum corner of the bounding box of the mesh
+    #[must_use]
+    pub const fn bounds_min(&self) -> Vec3 {
+        self.bounds_min
+    }
+
+    ///Returns the maximum corner of the bounding box of the mesh
+    #[must_use]
+    pub const fn bounds_max(&self) -> Vec3 {
+        self.bounds_max
+    }
+
+    ///Returns the distance to the vertex furthest from the origin
+    #[must_use]
+    pub const fn extent(&self) -> f32 {
+        self.extent
+    }
+
+    ///Checks if the vertex and index data is borrowed from the original bytes
+    #[must_use]
+    pub const fn is_borrowed(&self) -> bool {
+        matches!(self.vertices, Cow::Borrowed(_)) && matches!(self.indices, Cow::Borrowed(_))
+    }
+
+    ///Copies the full detail mesh into a [`Mesh`]
+    #[must_use]
+    pub fn to_mesh(&self) -> Mesh {
+        Mesh {
+            vertices: self.vertices.to_vec(),
+            indices: self.indices().to_vec(),
+        }
+    }
+}
+
+///Writes meshes in the binary format
+pub struct Writer<'a> {
+    mesh: &'a Mesh,
+    tangents: Option<&'a [Vec4]>,
+    lods: Vec<&'a [Index]>,
+}
+
+impl<'a> Writer<'a> {
+    ///Creates a new writer for the given mesh, the indices of the mesh are used as lod 0
+    #[must_use]
+    pub fn new(mesh: &'a Mesh) -> Self {
+        Self {
+            mesh,
+            tangents: None,
+            lods: vec![&mesh.indices],
+        }
+    }
+
+    ///Adds tangents to the mesh
+    ///
+    ///# Panics
+    ///Panics if the number of tangents does not match the number of vertices
+    #[must_use]
+    pub fn with_tangents(mut self, tangents: &'a [Vec4]) -> Self {
+        assert_eq!(
+            tangents.len(),
+            self.mesh.vertices.len(),
+            "Number of tangents must match the number of vertices"
+        );
+        self.tangents = Some(tangents);
+        self
+    }
+
+    ///Adds a lower detail level of the mesh, that uses the same vertices
+    ///
+    ///Lods should be added from the most detailed to the least detailed
+    #[must_use]
+    pub fn with_lod(mut self, indices: &'a [Index]) -> Self {
+        self.lods.push(indices);
+        self
+    }
+
+    ///Encodes the mesh
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let bounds =
+            crate::assets::mesh::processing::calculate_bounds(self.mesh).unwrap_or_default();
+        let extent = self
+            .mesh
+            .vertices
+            .iter()
+            .map(|v| v.coords.square_length())
+            .fold(0.0f32, f32::max);
+
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            flags: if self.tangents.is_some() {
+                FLAG_TANGENTS
+            } else {
+                0
+            },
+            vertex_count: self.mesh.vertices.len() as u32,
+            index_count: self.lods.iter().map(|l| l.len() as u32).sum(),
+            lod_count: self.lods.len() as u32,
+            bounds_min: bounds.min,
+            bounds_max: bounds.max,
+            extent: extent.sqrt(),
+        };
+
+        let mut out = bytemuck::bytes_of(&header).to_vec();
+
+        let mut offset = 0;
+        for l in &self.lods {
+            out.extend_from_slice(bytemuck::bytes_of(&[offset, l.len() as u32]));
+            offset += l.len() as u32;
+        }
+
+        out.extend_from_slice(bytemuck::cast_slice(&self.mesh.vertices));
+        if let Some(t) = self.tangents {
+            out.extend_from_slice(bytemuck::cast_slice(t));
+        }
+        for l in &self.lods {
+            out.extend_from_slice(bytemuck::cast_slice(l));
+        }
+
+        out
+    }
+
+    ///Encodes the mesh and writes it into a file
+    ///
+    ///# Errors
+    ///Returns an error if the file could not be written
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        std::fs::write(path, self.encode())
+    }
+}
+
+///Converts the first object of a wavefront obj file into a binary mesh
+///
+///# Errors
+///Returns an error if the obj file is invalid
+pub fn convert_obj(obj: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send>> {
+    let mesh = match super::obj::parse(obj) {
+        Ok(it) => it.into_iter().next().unwrap(),
+        Err(err) => return Err(Box::new(err)),
+    };
+
+    Ok(Writer::new(&mesh).encode())
+}
+
+#[test]
+fn test_obj_roundtrip() {
+    let obj = include_str!("../../assets/cube_triangulated.obj");
+    let expected = super::obj::parse(obj).unwrap().remove(0);
+
+    //Copy into a u32 buffer to guarantee alignment
+    let data = convert_obj(obj).unwrap();
+    let mut aligned = vec![0u32; data.len() / 4];
+    bytemuck::cast_slice_mut(&mut aligned).copy_from_slice(&data);
+
+    let view = MeshView::parse(bytemuck::cast_slice(&aligned)).unwrap();
+    assert!(view.is_borrowed());
+    assert_eq!(view.vertices(), expected.vertices.as_slice());
+    assert_eq!(view.indices(), expected.indices.as_slice());
+    assert_eq!(view.lod_count(), 1);
+    assert!(view.tangents().is_none());
+    assert_eq!(view.bounds_min(), Vec3::new(-1.0, -1.0, -1.0));
+    assert_eq!(view.bounds_max(), Vec3::new(1.0, 1.0, 1.0));
+    assert!((view.extent() - 3.0f32.sqrt()).abs() < 0.0001);
+
+    //Misaligned data is copied
+    let mut misaligned = vec![0u8];
+    misaligned.extend_from_slice(&data);
+    let view = MeshView::parse(&misaligned[1..]).unwrap();
+    assert_eq!(view.to_mesh().vertices, expected.vertices);
+    assert_eq!(view.to_mesh().indices, expected.indices);
+}
+
+#[test]
+fn test_lods_and_tangents() {
+    let mesh = Mesh {
+        vertices: vec![Vertex::default(); 4],
+        indices: vec![0, 1, 2, 2, 3, 0],
+    };
+    let tangents = vec![Vec4::new(1.0, 0.0, 0.0, 1.0); 4];
+    let lod = [0, 1, 2];
+
+    let data = Writer::new(&mesh)
+        .with_tangents(&tangents)
+        .with_lod(&lod)
+        .encode();
+    let view = MeshView::parse(&data).unwrap();
+
+    assert_eq!(view.lod_count(), 2);
+    assert_eq!(view.indices(), mesh.indices.as_slice());
+    assert_eq!(view.lod_indices(1).unwrap(), &lod);
+    assert_eq!(view.lod_range(1), Some(6..9));
+    assert_eq!(view.all_indices().len(), 9);
+    assert!(view.lod_indices(2).is_none());
+    assert_eq!(view.tangents().unwrap(), tangents.as_slice());
+}
+
+#[test]
+fn test_invalid_data() {
+    assert!(MeshView::parse(b"LMSH").is_err());
+
+    let mesh = Mesh {
+        vertices: vec![Vertex::default(); 3],
+        indices: vec![0, 1, 2],
+    };
+    let mut data = Writer::new(&mesh).encode();
+    data.pop();
+    assert!(MeshView::parse(&data).is_err());
+
+    let mesh = Mesh {
+        vertices: vec![Vertex::default(); 3],
+        indices: vec![0, 1, 3],
+    };
+    assert!(MeshView::parse(&Writer::new(&mesh).encode()).is_err());
+
+    //Counts describing more data than can be addressed
+    for offset in [12, 16, 20] {
+        let mut data = Writer::new(&mesh).encode();
+        data[offset..offset + 4].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert!(MeshView::parse(&data).is_err());
+    }
+}
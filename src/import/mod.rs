@@ -1,5 +1,39 @@
 //! Asset import
+///.json texture atlas loading
+pub mod atlas;
 ///.bmp image loading
 pub mod bmp;
+///Gpu texture formats and their cpu decompression
+pub mod compressed;
+///.dds texture loading
+pub mod dds;
+///.gltf and .glb skinned mesh and animation loading
+pub mod gltf;
+///.hdr image loading
+pub mod hdr;
+///.jpg image loading
+pub mod jpeg;
+///Json parsing, used by the gltf and atlas loaders
+mod json;
+///.ktx2 texture loading
+pub mod ktx2;
+///.lmesh binary mesh loading and writing
+pub mod lmesh;
+///.mtl material loading
+pub mod mtl;
 ///.obj mesh loading
 pub mod obj;
+///.qoi image loading
+pub mod qoi;
+///.tga image loading
+pub mod tga;
+///.ttf and .otf font loading
+pub mod ttf;
+
+///Creates an error for invalid input data
+pub(crate) fn invalid(message: &str) -> Box<dyn std::error::Error + Send> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        message.to_owned(),
+    ))
+}
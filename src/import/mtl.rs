@@ -0,0 +1,191 @@
+use crate::{
+    UUID,
+    assets::{
+        Material,
+        materials::{Lit, Unlit},
+    },
+    structures::Color,
+};
+
+pub use super::obj::Error;
+
+///Description of a material read from an mtl file
+///
+///Textures are not loaded, only their paths are stored, as written in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    ///Name of the material, as used by `usemtl`
+    pub name: String,
+    ///Ambient color (`Ka`)
+    pub ambient_color: Color,
+    ///Diffuse color (`Kd`), the alpha is set from the dissolve (`d` or `Tr`)
+    pub diffuse_color: Color,
+    ///Specular color (`Ks`)
+    pub specular_color: Color,
+    ///Specular exponent (`Ns`), from 0 to 1000
+    pub specular_exponent: f32,
+    ///Path to the diffuse texture (`map_Kd`)
+    pub diffuse_texture: Option<String>,
+    ///Path to the specular texture (`map_Ks`)
+    pub specular_texture: Option<String>,
+    ///Path to the normal map (`norm`, `map_Bump` or `bump`)
+    pub normal_texture: Option<String>,
+    ///Path to the alpha texture (`map_d`)
+    pub alpha_texture: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ambient_color: Color::black(),
+            diffuse_color: Color::white(),
+            specular_color: Color::black(),
+            specular_exponent: 0.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+            alpha_texture: None,
+        }
+    }
+
+    ///Creates a [`Lit`] material from the description
+    ///
+    ///The specular exponent is mapped from the mtl range of 0 to 1000 onto the shininess range of
+    ///0 to 1. `texture_id` should be the id of the [`diffuse_texture`](Self::diffuse_texture)
+    #[must_use]
+    pub fn to_lit(&self, texture_id: Option<UUID>) -> Material {
+        Lit::new(
+            texture_id,
+            Some(self.diffuse_color),
+            Some(self.specular_color),
+            (self.specular_exponent / 1000.0).clamp(0.0, 1.0),
+        )
+    }
+
+    ///Creates an [`Unlit`] material from the description
+    ///
+    ///`texture_id` should be the id of the [`diffuse_texture`](Self::diffuse_texture)
+    #[must_use]
+    pub fn to_unlit(&self, texture_id: Option<UUID>) -> Material {
+        Unlit::new(texture_id, Some(self.diffuse_color))
+    }
+}
+
+fn read_color(input: &str, line: usize) -> Result<Color, Error> {
+    let values = input
+        .split_whitespace()
+        .map(str::parse::<f32>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::InvalidLine(line))?;
+
+    match values.as_slice() {
+        //A single value is used for all channels
+        [v] => Ok(Color::new(*v, *v, *v, 1.0)),
+        [r, g, b] => Ok(Color::new(*r, *g, *b, 1.0)),
+        _ => Err(Error::InvalidLine(line)),
+    }
+}
+
+fn read_float(input: &str, line: usize) -> Result<f32, Error> {
+    input.trim().parse().map_err(|_| Error::InvalidLine(line))
+}
+
+///Reads the path of a texture map, ignoring the options before it
+fn read_map(input: &str) -> String {
+    input
+        .split_whitespace()
+        .last()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+///Parses the given string as a wavefront mtl file
+///
+///# Errors
+///Returns an error if the file is invalid
+pub fn parse(file: &str) -> Result<Vec<ObjMaterial>, Error> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (line, l) in file.lines().enumerate() {
+        let line = line + 1;
+        let l = l.split('#').next().unwrap_or_default().trim();
+        let (keyword, rest) = l.split_once(char::is_whitespace).unwrap_or((l, ""));
+        let rest = rest.trim();
+
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(rest));
+            continue;
+        }
+
+        if keyword.is_empty() {
+            continue;
+        }
+
+        //Statements before the first material are invalid
+        let Some(material) = materials.last_mut() else {
+            return Err(Error::InvalidLine(line));
+        };
+
+        match keyword {
+            "Ka" => material.ambient_color = read_color(rest, line)?,
+            "Kd" => {
+                let a = material.diffuse_color.a;
+                material.diffuse_color = read_color(rest, line)?;
+                material.diffuse_color.a = a;
+            }
+            "Ks" => material.specular_color = read_color(rest, line)?,
+            "Ns" => material.specular_exponent = read_float(rest, line)?,
+            "d" => material.diffuse_color.a = read_float(rest, line)?,
+            "Tr" => material.diffuse_color.a = 1.0 - read_float(rest, line)?,
+            "map_Kd" => material.diffuse_texture = Some(read_map(rest)),
+            "map_Ks" => material.specular_texture = Some(read_map(rest)),
+            "norm" | "map_Bump" | "map_bump" | "bump" => {
+                material.normal_texture = Some(read_map(rest));
+            }
+            "map_d" => material.alpha_texture = Some(read_map(rest)),
+            //Other statements are not supported by the engine materials
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+#[test]
+fn test_parse_mtl() {
+    let file = "
+# Comment
+newmtl red
+Ka 0.1
+Kd 1.0 0.0 0.0
+Ks 0.5 0.5 0.5
+Ns 250
+d 0.5
+map_Kd -bm 1.0 textures/red.png
+
+newmtl plain
+Tr 0.25
+Kd 0 1 0
+";
+    let materials = parse(file).unwrap();
+    assert_eq!(materials.len(), 2);
+
+    let red = &materials[0];
+    assert_eq!(red.name, "red");
+    assert_eq!(red.ambient_color, Color::new(0.1, 0.1, 0.1, 1.0));
+    assert_eq!(red.diffuse_color, Color::new(1.0, 0.0, 0.0, 0.5));
+    assert_eq!(red.specular_color, Color::new(0.5, 0.5, 0.5, 1.0));
+    assert!((red.specular_exponent - 250.0).abs() < f32::EPSILON);
+    assert_eq!(red.diffuse_texture.as_deref(), Some("textures/red.png"));
+
+    let plain = &materials[1];
+    assert_eq!(plain.diffuse_color, Color::new(0.0, 1.0, 0.0, 0.75));
+    assert!(plain.diffuse_texture.is_none());
+
+    assert!(matches!(parse("Kd 1 1 1"), Err(Error::InvalidLine(1))));
+    assert!(matches!(
+        parse("newmtl a\nKd 1 1"),
+        Err(Error::InvalidLine(2))
+    ));
+}
@@ -1,38 +1,405 @@
 #![allow(clippy::cast_possible_truncation)]
+use std::{collections::HashMap, fmt::Display};
+
 use crate::{
-    math::{Vec2, Vec3},
-    structures::{Mesh, Vertex},
+    math::{Vec2, Vec3, Vector},
+    structures::{Index, Mesh, Vertex},
 };
 
-fn read_vec3(input: &str) -> Option<Vec3> {
-    let split: Vec<Option<f32>> = input.split(' ').map(|i| str::parse(i).ok()).collect();
-    if split.len() != 3 {
-        return None;
+#[derive(Debug)]
+///Errors that may occur when parsing obj and mtl files
+pub enum Error {
+    ///A line could not be parsed, contains the line number (starting at 1)
+    InvalidLine(usize),
+    ///A face references an element that does not exist
+    IndexOutOfBounds {
+        ///Number of the line containing the face (starting at 1)
+        line: usize,
+        ///The index as written in the file
+        index: i64,
+    },
+    ///A face has less than 3 vertices, contains the line number (starting at 1)
+    DegenerateFace(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "Invalid data on line {line}"),
+            Self::IndexOutOfBounds { line, index } => {
+                write!(f, "Index {index} on line {line} is out of bounds")
+            }
+            Self::DegenerateFace(line) => write!(f, "Face on line {line} has less than 3 vertices"),
+        }
     }
-    Some(Vec3 {
-        x: split[0]?,
-        y: split[1]?,
-        z: split[2]?,
-    })
+}
+
+impl std::error::Error for Error {}
+
+///A part of an obj file that uses a single material
+pub struct ObjMesh {
+    ///Name of the object (`o`) this part belongs to
+    pub object: Option<String>,
+    ///Name of the group (`g`) this part belongs to
+    pub group: Option<String>,
+    ///Name of the material (`usemtl`) used by this part
+    pub material: Option<String>,
+    ///Geometry of this part
+    pub mesh: Mesh,
+}
+
+///Contents of an obj file
+pub struct ObjFile {
+    ///All parts of the file, split by object, group and material
+    pub meshes: Vec<ObjMesh>,
+    ///Names of the material libraries (`mtllib`) referenced by the file, see [`super::mtl`]
+    pub material_libraries: Vec<String>,
+}
+
+fn read_floats<const N: usize>(input: &str, min: usize) -> Option<[f32; N]> {
+    let mut out = [0.0; N];
+    let mut count = 0;
+    for (i, s) in input.split_whitespace().enumerate() {
+        //Ignore optional trailing components, like the w of positions
+        if i < N {
+            out[i] = s.parse().ok()?;
+        }
+        count += 1;
+    }
+    (count >= min).then_some(out)
+}
+
+fn read_vec3(input: &str) -> Option<Vec3> {
+    let [x, y, z] = read_floats(input, 3)?;
+    Some(Vec3 { x, y, z })
 }
 
 fn read_vec2(input: &str) -> Option<Vec2> {
-    let split: Vec<Option<f32>> = input.split(' ').map(|i| str::parse(i).ok()).collect();
-    if split.len() != 2 {
+    //The v component is optional
+    let [x, y] = read_floats(input, 1)?;
+    Some(Vec2 { x, y })
+}
+
+///Reads the `v`, `v/vt`, `v//vn` or `v/vt/vn` indices of a face vertex, as written in the file
+fn get_indecies(input: &str) -> Option<(i64, Option<i64>, Option<i64>)> {
+    let mut split = input.split('/');
+    let v = split.next()?.parse().ok()?;
+
+    let mut optional = || match split.next() {
+        None | Some("") => Some(None),
+        Some(i) => i.parse().ok().map(Some),
+    };
+    let vt = optional()?;
+    let vn = optional()?;
+
+    if split.next().is_some() {
         return None;
     }
-    Some(Vec2 {
-        x: split[0]?,
-        y: split[1]?,
+    Some((v, vt, vn))
+}
+
+///Converts an index as written in the file into an index into an array of `len` elements
+///
+///Positive indices start at 1, negative indices are relative to the end of the array
+fn resolve_index(index: i64, len: usize, line: usize) -> Result<usize, Error> {
+    let resolved = match index {
+        1.. => usize::try_from(index - 1).ok(),
+        ..0 => usize::try_from(index.unsigned_abs())
+            .ok()
+            .and_then(|i| len.checked_sub(i)),
+        0 => None,
+    };
+    resolved
+        .filter(|i| *i < len)
+        .ok_or(Error::IndexOutOfBounds { line, index })
+}
+
+///Triangulates a planar polygon using ear clipping, returns indices into `points`
+///
+///Falls back to a triangle fan if the polygon is degenerate
+fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    //Newell's method, works for concave polygons
+    let mut normal = Vec3::default();
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal += Vec3::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+
+    //Project onto the plane the polygon is the most aligned with, keeping the winding
+    let (ax, ay, az) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+    let project: fn(Vec3) -> Vec2 = if az >= ax && az >= ay {
+        |p| Vec2::new(p.x, p.y)
+    } else if ax >= ay {
+        |p| Vec2::new(p.y, p.z)
+    } else {
+        |p| Vec2::new(p.z, p.x)
+    };
+    let sign = if az >= ax && az >= ay {
+        normal.z.signum()
+    } else if ax >= ay {
+        normal.x.signum()
+    } else {
+        normal.y.signum()
+    };
+
+    let projected = points.iter().map(|p| project(*p)).collect::<Vec<_>>();
+    let cross = |a: Vec2, b: Vec2, c: Vec2| {
+        sign * (b.x - a.x).mul_add(c.y - a.y, -((b.y - a.y) * (c.x - a.x)))
+    };
+
+    let mut remaining = (0..points.len()).collect::<Vec<_>>();
+    let mut out = Vec::new();
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|i| {
+            let triangle = [
+                remaining[(i + len - 1) % len],
+                remaining[*i],
+                remaining[(i + 1) % len],
+            ];
+            let [prev, current, next] = triangle.map(|t| projected[t]);
+
+            //Must be convex
+            if cross(prev, current, next) <= 0.0 {
+                return false;
+            }
+
+            //Must not contain any other vertex
+            !remaining.iter().any(|j| {
+                let point = projected[*j];
+                !triangle.contains(j)
+                    && cross(prev, current, point) >= 0.0
+                    && cross(current, next, point) >= 0.0
+                    && cross(next, prev, point) >= 0.0
+            })
+        });
+
+        let Some(ear) = ear else {
+            break;
+        };
+
+        out.push([
+            remaining[(ear + len - 1) % len],
+            remaining[ear],
+            remaining[(ear + 1) % len],
+        ]);
+        remaining.remove(ear);
+    }
+
+    //Either a triangle or a degenerate polygon is left
+    for i in 1..remaining.len() - 1 {
+        out.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    out
+}
+
+///Vertex data of a part of the file that is being parsed
+#[derive(Default)]
+struct PartBuilder {
+    object: Option<String>,
+    group: Option<String>,
+    material: Option<String>,
+    vertices: Vec<Vertex>,
+    indices: Vec<Index>,
+    ///Maps the (position, uv, normal) indecies of a vertex to its index in the mesh
+    lookup: HashMap<(usize, Option<usize>, Option<usize>), Index>,
+    ///Sum of the normals of faces using the position, for vertices that have no normal
+    generated_normals: HashMap<usize, Vec3>,
+}
+
+impl PartBuilder {
+    fn new_part(&self) -> Self {
+        Self {
+            object: self.object.clone(),
+            group: self.group.clone(),
+            material: self.material.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn finish(mut self) -> ObjMesh {
+        for ((position, _, normal), index) in &self.lookup {
+            if normal.is_none() {
+                //Degenerate faces sum up to a zero normal and huge ones can overflow, neither of
+                //which has a direction, so the vertex is left without a normal
+                let normal = self.generated_normals[position].normalize();
+                self.vertices[*index as usize].normal =
+                    if normal.x.is_finite() && normal.y.is_finite() && normal.z.is_finite() {
+                        normal
+                    } else {
+                        Vec3::default()
+                    };
+            }
+        }
+
+        ObjMesh {
+            object: self.object,
+            group: self.group,
+            material: self.material,
+            mesh: Mesh {
+                vertices: self.vertices,
+                indices: self.indices,
+            },
+        }
+    }
+}
+
+///Parses the given string as a wavefront obj file, returning a mesh for each part of the file
+///
+///Polygons are triangulated, vertices without normals get smooth normals generated from the faces
+///that use them and vertices without texture coordinates get `(0, 0)`
+///
+///# Errors
+///Returns an error if the file is invalid
+pub fn parse_file(file: &str) -> Result<ObjFile, Error> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+
+    let mut parts = Vec::new();
+    let mut material_libraries = Vec::new();
+    let mut current = PartBuilder::default();
+
+    for (line, l) in file.lines().enumerate() {
+        let line = line + 1;
+        let l = l.split('#').next().unwrap_or_default().trim();
+        let (keyword, rest) = l.split_once(char::is_whitespace).unwrap_or((l, ""));
+        let rest = rest.trim();
+
+        match keyword {
+            "v" => positions.push(read_vec3(rest).ok_or(Error::InvalidLine(line))?),
+            "vt" => uvs.push(read_vec2(rest).ok_or(Error::InvalidLine(line))?),
+            "vn" => normals.push(read_vec3(rest).ok_or(Error::InvalidLine(line))?),
+            "o" | "g" | "usemtl" => {
+                let mut next = current.new_part();
+                let name = (!rest.is_empty()).then(|| rest.to_owned());
+                match keyword {
+                    "o" => {
+                        next.object = name;
+                        next.group = None;
+                    }
+                    "g" => next.group = name,
+                    _ => next.material = name,
+                }
+
+                let previous = std::mem::replace(&mut current, next);
+                if !previous.indices.is_empty() {
+                    parts.push(previous.finish());
+                }
+            }
+            "mtllib" => material_libraries.extend(rest.split_whitespace().map(str::to_owned)),
+            "f" => {
+                let corners = rest
+                    .split_whitespace()
+                    .map(|c| {
+                        let (v, vt, vn) = get_indecies(c).ok_or(Error::InvalidLine(line))?;
+                        Ok((
+                            resolve_index(v, positions.len(), line)?,
+                            vt.map(|i| resolve_index(i, uvs.len(), line)).transpose()?,
+                            vn.map(|i| resolve_index(i, normals.len(), line))
+                                .transpose()?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                if corners.len() < 3 {
+                    return Err(Error::DegenerateFace(line));
+                }
+
+                let points = corners.iter().map(|c| positions[c.0]).collect::<Vec<_>>();
+
+                for triangle in triangulate(&points) {
+                    let [a, b, c] = triangle.map(|i| points[i]);
+                    //Not normalized, so that bigger faces have more influence
+                    let face_normal = (b - a).cross(&(c - a));
+
+                    for i in triangle {
+                        let key = corners[i];
+
+                        if key.2.is_none() {
+                            *current.generated_normals.entry(key.0).or_default() += face_normal;
+                        }
+
+                        //If found an existing vertex, push it's index
+                        if let Some(j) = current.lookup.get(&key) {
+                            current.indices.push(*j);
+                            continue;
+                        }
+
+                        //Create the new vertex
+                        let index = current.vertices.len() as Index;
+                        current.lookup.insert(key, index);
+                        current.vertices.push(Vertex {
+                            coords: positions[key.0],
+                            texture: key.1.map(|i| uvs[i]).unwrap_or_default(),
+                            normal: key.2.map(|i| normals[i]).unwrap_or_default(),
+                        });
+                        current.indices.push(index);
+                    }
+                }
+            }
+            //Smoothing groups, lines, points and other unsupported statements are ignored
+            _ => {}
+        }
+    }
+
+    if !current.indices.is_empty() {
+        parts.push(current.finish());
+    }
+
+    Ok(ObjFile {
+        meshes: parts,
+        material_libraries,
     })
 }
 
-fn get_indecies(input: &str) -> Option<(u32, u32, u32)> {
-    let split: Vec<Option<u32>> = input.split('/').map(|i| str::parse(i).ok()).collect();
-    if split.len() != 3 {
-        return None;
+///Parses the given string as a wavefront obj file, returning a mesh for every object in the file
+///
+///Groups and materials of an object are merged into a single mesh, use [`parse_file`] to keep
+///them separate. The returned vector always contains at least one mesh, if the file has no faces
+///the mesh is empty
+///
+///# Errors
+///Returns an error if the file is invalid
+pub fn parse(file: &str) -> Result<Vec<Mesh>, Error> {
+    let mut meshes: Vec<(Option<String>, Mesh)> = Vec::new();
+
+    for part in parse_file(file)?.meshes {
+        match meshes.last_mut() {
+            Some((object, mesh)) if *object == part.object => {
+                let offset = mesh.vertices.len() as Index;
+                mesh.vertices.extend(part.mesh.vertices);
+                mesh.indices
+                    .extend(part.mesh.indices.into_iter().map(|i| i + offset));
+            }
+            _ => meshes.push((part.object, part.mesh)),
+        }
+    }
+
+    if meshes.is_empty() {
+        meshes.push((None, Mesh::default()));
     }
-    Some((split[0]?, split[1]?, split[2]?))
+
+    log::info!("Read {} meshes", meshes.len());
+    for (_, i) in &meshes {
+        log::info!(
+            "verex len = {}, ind len = {}",
+            i.vertices.len(),
+            i.indices.len()
+        );
+    }
+
+    Ok(meshes.into_iter().map(|(_, m)| m).collect())
 }
 
 #[test]
@@ -53,105 +420,134 @@ fn test_read_vec3() {
 fn test_get_indecies() {
     let input = "1/2/3";
     let output = get_indecies(input).unwrap();
-    let expected = (1, 2, 3);
+    let expected = (1, Some(2), Some(3));
     assert_eq!(output, expected);
+
+    assert_eq!(get_indecies("1"), Some((1, None, None)));
+    assert_eq!(get_indecies("1/2"), Some((1, Some(2), None)));
+    assert_eq!(get_indecies("-1//-2"), Some((-1, None, Some(-2))));
+    assert_eq!(get_indecies("1/2/3/4"), None);
+    assert_eq!(get_indecies("a"), None);
 }
 #[test]
 fn test_loading_single() {
     parse(include_str!("../../assets/cube_triangulated.obj")).unwrap();
 }
+#[test]
+fn test_ngons_and_negative_indices() {
+    //A quad and a concave pentagon, using relative indices and no uvs
+    let file = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+f -4//1 -3//1 -2//1 -1//1
+v 0 0 1
+v 2 0 1
+v 2 2 1
+v 1 0.5 1
+v 0 2 1
+f 5 6 7 8 9
+";
+    let mesh = parse(file).unwrap().remove(0);
 
-///Parses the given string as a wavefront obj file
-pub fn parse(file: &str) -> Option<Vec<Mesh>> {
-    let mut meshes = Vec::new();
-    let mut positions = Vec::new();
-    let mut normals = Vec::new();
-    let mut uvs = Vec::new();
-
-    let mut vertices_indecies = Vec::new();
-    let mut vertices = Vec::new();
-    let mut indecies = Vec::new();
-
-    let mut first = true;
-    for l in file.lines() {
-        //Hit a new object reset
-        if l.starts_with("o ") {
-            if first {
-                first = false;
-                continue;
-            }
-            //Insert into the out vector
-            meshes.push(Mesh {
-                vertices,
-                indices: indecies,
-            });
-
-            //Clear data
-            vertices = Vec::new();
-            indecies = Vec::new();
-            vertices_indecies = Vec::new();
-            positions = Vec::new();
-            normals = Vec::new();
-            uvs = Vec::new();
-        }
-        if let Some(stripped) = l.strip_prefix("v ") {
-            //read the position
-            positions.push(read_vec3(stripped)?);
-        }
-        if let Some(stripped) = l.strip_prefix("vt ") {
-            //read the position
-            uvs.push(read_vec2(stripped)?);
-        }
-        if let Some(stripped) = l.strip_prefix("vn ") {
-            //read the position
-            normals.push(read_vec3(stripped)?);
-        }
-        if let Some(stripped) = l.strip_prefix("f ") {
-            let component_indecies: Vec<Option<(u32, u32, u32)>> =
-                stripped.split(' ').map(get_indecies).collect();
-            for i in &component_indecies {
-                let i = (*i)?;
-
-                let mut found = false;
-
-                for (j, item) in vertices_indecies.iter().enumerate() {
-                    if item == &i {
-                        //If found an existing vertex, push it's index
-                        indecies.push(j as u32);
-                        found = true;
-                        break;
-                    }
-                }
-                if found {
-                    continue;
-                }
-
-                //Create the new vertex
-                vertices_indecies.push(i);
-                vertices.push(Vertex {
-                    coords: (positions[(i.0 - 1) as usize]),
-                    texture: uvs[(i.1 - 1) as usize],
-                    normal: normals[(i.2 - 1) as usize],
-                });
+    //4 + 5 vertices, 2 + 3 triangles
+    assert_eq!(mesh.vertices.len(), 9);
+    assert_eq!(mesh.indices.len(), 15);
 
-                indecies.push((vertices.len() - 1) as u32);
-            }
-        }
+    //Generated normals of the pentagon
+    for v in &mesh.vertices[4..] {
+        assert_eq!(v.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(v.texture, Vec2::default());
     }
 
-    meshes.push(Mesh {
-        vertices,
-        indices: indecies,
-    });
+    //Every triangle of the concave polygon must face the same way
+    for t in mesh.indices[6..].chunks(3) {
+        let [a, b, c] = [t[0], t[1], t[2]].map(|i| mesh.vertices[i as usize].coords);
+        assert!((b - a).cross(&(c - a)).z > 0.0);
+    }
+}
+#[test]
+fn test_degenerate_normals() {
+    //A triangle with all corners on a line and one too large for its normal to fit into an f32
+    let file = "
+v 0 0 0
+v 1 1 1
+v 2 2 2
+f 1 2 3
+v 1e30 0 0
+v 0 1e30 0
+v 0 0 1e30
+f 4 5 6
+";
+    let mesh = parse(file).unwrap().remove(0);
 
-    log::info!("Read {} meshes", meshes.len());
-    for i in &meshes {
-        log::info!(
-            "verex len = {}, ind len = {}",
-            i.vertices.len(),
-            i.indices.len()
-        );
+    assert_eq!(mesh.vertices.len(), 6);
+    for v in &mesh.vertices {
+        assert_eq!(v.normal, Vec3::default());
     }
+}
+#[test]
+fn test_groups_and_materials() {
+    let file = "
+mtllib a.mtl b.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+o first
+g one
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 2 3
+g two
+f 1 2 3
+o second
+f 3 2 1
+";
+    let obj = parse_file(file).unwrap();
+    assert_eq!(obj.material_libraries, vec!["a.mtl", "b.mtl"]);
+
+    let names = obj
+        .meshes
+        .iter()
+        .map(|m| {
+            (
+                m.object.as_deref(),
+                m.group.as_deref(),
+                m.material.as_deref(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            (Some("first"), Some("one"), Some("red")),
+            (Some("first"), Some("one"), Some("blue")),
+            (Some("first"), Some("two"), Some("blue")),
+            (Some("second"), None, Some("blue")),
+        ]
+    );
 
-    Some(meshes)
+    let meshes = parse(file).unwrap();
+    assert_eq!(meshes.len(), 2);
+    assert_eq!(meshes[0].indices.len(), 9);
+}
+#[test]
+fn test_errors() {
+    assert!(matches!(parse("v 0 0"), Err(Error::InvalidLine(1))));
+    assert!(parse("v 0 0 0").unwrap()[0].indices.is_empty());
+    assert!(matches!(
+        parse("v 0 0 0\nf 1 1"),
+        Err(Error::DegenerateFace(2))
+    ));
+    assert!(matches!(
+        parse("v 0 0 0\nf 1 2 -2"),
+        Err(Error::IndexOutOfBounds { line: 2, index: 2 })
+    ));
+    assert!(matches!(
+        parse("v 0 0 0\nf 1 1 0"),
+        Err(Error::IndexOutOfBounds { line: 2, index: 0 })
+    ));
 }
@@ -0,0 +1,157 @@
+#![allow(clippy::cast_possible_truncation)]
+use lunar_png::Image;
+
+use super::invalid;
+
+const HEADER_SIZE: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_INDEX: u8 = 0b0000_0000;
+const OP_DIFF: u8 = 0b0100_0000;
+const OP_LUMA: u8 = 0b1000_0000;
+const OP_RUN: u8 = 0b1100_0000;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK: u8 = 0b1100_0000;
+
+const fn hash(p: [u8; 4]) -> usize {
+    (p[0] as usize * 3 + p[1] as usize * 5 + p[2] as usize * 7 + p[3] as usize * 11) % 64
+}
+
+///Parses a byte array as a qoi image
+///
+///The image is always decoded into rgba8, rows are stored from top to bottom
+///# Errors
+///Fails if the data is not a valid qoi image
+pub fn parse(data: &[u8]) -> Result<Image, Box<dyn std::error::Error + Send>> {
+    if data.len() < HEADER_SIZE + END_MARKER.len() {
+        return Err(invalid("Wrong header size"));
+    }
+    if &data[..4] != b"qoif" {
+        return Err(invalid("Wrong signature"));
+    }
+
+    let width = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    let height = u32::from_be_bytes(data[8..12].try_into().unwrap());
+    if width == 0 || height == 0 {
+        return Err(invalid("Image has no pixels"));
+    }
+    if !matches!(data[12], 3 | 4) {
+        return Err(invalid("Invalid number of channels"));
+    }
+
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| invalid("Image is too large"))?;
+    let mut out = Vec::new();
+
+    let mut index = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+
+    let chunks = &data[HEADER_SIZE..data.len() - END_MARKER.len()];
+    let mut position = 0;
+    let mut next = || {
+        let b = chunks.get(position).copied();
+        position += 1;
+        b.ok_or_else(|| invalid("Unexpected end of data"))
+    };
+
+    while out.len() < size {
+        let op = next()?;
+        let mut run = 1;
+
+        match op {
+            OP_RGB => {
+                pixel[0] = next()?;
+                pixel[1] = next()?;
+                pixel[2] = next()?;
+            }
+            OP_RGBA => {
+                pixel = [next()?, next()?, next()?, next()?];
+            }
+            _ => match op & MASK {
+                OP_INDEX => pixel = index[(op & 0x3F) as usize],
+                OP_DIFF => {
+                    pixel[0] = pixel[0].wrapping_add(((op >> 4) & 0x03).wrapping_sub(2));
+                    pixel[1] = pixel[1].wrapping_add(((op >> 2) & 0x03).wrapping_sub(2));
+                    pixel[2] = pixel[2].wrapping_add((op & 0x03).wrapping_sub(2));
+                }
+                OP_LUMA => {
+                    let b = next()?;
+                    let dg = (op & 0x3F).wrapping_sub(32);
+                    pixel[0] = pixel[0].wrapping_add(dg.wrapping_sub(8).wrapping_add(b >> 4));
+                    pixel[1] = pixel[1].wrapping_add(dg);
+                    pixel[2] = pixel[2].wrapping_add(dg.wrapping_sub(8).wrapping_add(b & 0x0F));
+                }
+                OP_RUN => run = (op & 0x3F) as usize + 1,
+                _ => unreachable!(),
+            },
+        }
+
+        index[hash(pixel)] = pixel;
+        for _ in 0..run {
+            out.extend_from_slice(&pixel);
+        }
+    }
+    out.truncate(size);
+
+    Ok(Image {
+        img_type: lunar_png::ImageType::Rgba8,
+        width,
+        height,
+        data: out,
+    })
+}
+
+#[test]
+fn test_parse_qoi() {
+    let mut data = b"qoif".to_vec();
+    data.extend_from_slice(&7u32.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&[4, 0]);
+    data.extend_from_slice(&[
+        //Red
+        OP_RGBA,
+        255,
+        0,
+        0,
+        255,
+        //Repeat it twice
+        OP_RUN | 1,
+        //Green, slightly transparent
+        OP_RGBA,
+        0,
+        255,
+        0,
+        128,
+        //Green + (1, 0, -1)
+        OP_DIFF | (3 << 4) | (2 << 2) | 1,
+        //Red again from the index
+        OP_INDEX | hash([255, 0, 0, 255]) as u8,
+        //Red + (-1, 2, 3)
+        OP_LUMA | (2 + 32),
+        ((-3i8 + 8) as u8) << 4 | (1 + 8),
+    ]);
+    data.extend_from_slice(&END_MARKER);
+
+    let image = parse(&data).unwrap();
+    assert_eq!((image.width, image.height), (7, 1));
+    assert_eq!(
+        image.data,
+        vec![
+            255, 0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 255, //
+            0, 255, 0, 128, 1, 255, 255, 128, 255, 0, 0, 255, //
+            254, 2, 3, 255,
+        ]
+    );
+
+    assert!(parse(b"qoif").is_err());
+
+    //No pixels
+    for offset in [4, 8] {
+        let mut empty = data.clone();
+        empty[offset..offset + 4].copy_from_slice(&0u32.to_be_bytes());
+        assert!(parse(&empty).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+#![allow(clippy::cast_possible_truncation)]
+use lunar_png::Image;
+
+use super::invalid;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct Header {
+    id_length: u8,
+    color_map_type: u8,
+    image_type: u8,
+    color_map_start: u16,
+    color_map_length: u16,
+    color_map_bpp: u8,
+    x_origin: u16,
+    y_origin: u16,
+    width: u16,
+    height: u16,
+    bpp: u8,
+    descriptor: u8,
+}
+
+const HEADER_SIZE: usize = 18;
+
+///Image is stored from top to bottom
+const DESCRIPTOR_TOP_TO_BOTTOM: u8 = 0x20;
+///Image is stored from right to left
+const DESCRIPTOR_RIGHT_TO_LEFT: u8 = 0x10;
+
+///Converts a pixel stored in the file into rgba8
+fn to_rgba(pixel: &[u8], grayscale: bool, alpha_bits: u8) -> [u8; 4] {
+    match *pixel {
+        [v] => [v, v, v, 255],
+        [v, a] if grayscale => [v, v, v, a],
+        //A1R5G5B5
+        [lo, hi] => {
+            let v = u16::from_le_bytes([lo, hi]);
+            let expand = |c: u16| ((c & 0x1F) * 255 / 31) as u8;
+            [
+                expand(v >> 10),
+                expand(v >> 5),
+                expand(v),
+                //The alpha bit is only used if the descriptor says so
+                if alpha_bits == 0 || v & 0x8000 != 0 {
+                    255
+                } else {
+                    0
+                },
+            ]
+        }
+        [b, g, r] => [r, g, b, 255],
+        [b, g, r, a] => [r, g, b, a],
+        _ => unreachable!(),
+    }
+}
+
+///Parses a byte array as a tga image
+///
+///Supports uncompressed and rle compressed color mapped, true color and grayscale images. The
+///image is always decoded into rgba8, rows are stored from top to bottom
+///# Errors
+///Fails if the data is not a valid tga image or uses an unsupported pixel format
+#[allow(clippy::too_many_lines)]
+pub fn parse(data: &[u8]) -> Result<Image, Box<dyn std::error::Error + Send>> {
+    if data.len() < HEADER_SIZE {
+        return Err(invalid("Wrong header size"));
+    }
+    let header: Header = bytemuck::pod_read_unaligned(&data[..HEADER_SIZE]);
+    if header.width == 0 || header.height == 0 {
+        return Err(invalid("Image has no pixels"));
+    }
+
+    let rle = match header.image_type {
+        1..=3 => false,
+        9..=11 => true,
+        _ => return Err(invalid("Unsupported image type")),
+    };
+    let color_mapped = header.image_type & 0x07 == 1;
+    let grayscale = header.image_type & 0x07 == 3;
+    let alpha_bits = header.descriptor & 0x0F;
+
+    if !matches!(header.bpp, 8 | 15 | 16 | 24 | 32) {
+        return Err(invalid("Unsported bits per pixel"));
+    }
+    let pixel_size = header.bpp.div_ceil(8) as usize;
+
+    let mut position = HEADER_SIZE + header.id_length as usize;
+
+    //Read the color map, converting it into rgba
+    let mut color_map = Vec::new();
+    if header.color_map_type == 1 {
+        if !matches!(header.color_map_bpp, 15 | 16 | 24 | 32) {
+            return Err(invalid("Unsupported color map bits per pixel"));
+        }
+        let entry_size = header.color_map_bpp.div_ceil(8) as usize;
+        let end = position + header.color_map_length as usize * entry_size;
+        let map = data
+            .get(position..end)
+            .ok_or_else(|| invalid("Unexpected end of data"))?;
+        color_map = map
+            .chunks(entry_size)
+            .map(|p| to_rgba(p, false, alpha_bits))
+            .collect();
+        position = end;
+    }
+    if color_mapped && color_map.is_empty() {
+        return Err(invalid("Missing color map"));
+    }
+
+    let width = u32::from(header.width);
+    let height = u32::from(header.height);
+    let pixel_count = width as usize * height as usize;
+
+    let convert = |pixel: &[u8]| -> Result<[u8; 4], Box<dyn std::error::Error + Send>> {
+        if color_mapped {
+            let index = match *pixel {
+                [i] => usize::from(i),
+                [lo, hi] => usize::from(u16::from_le_bytes([lo, hi])),
+                _ => return Err(invalid("Unsupported color map index size")),
+            };
+            color_map
+                .get(index.wrapping_sub(header.color_map_start as usize))
+                .copied()
+                .ok_or_else(|| invalid("Color map index out of bounds"))
+        } else {
+            Ok(to_rgba(pixel, grayscale, alpha_bits))
+        }
+    };
+
+    let mut pixels = Vec::new();
+    let mut read = |len: usize| {
+        let out = data.get(position..position + len);
+        position += len;
+        out.ok_or_else(|| invalid("Unexpected end of data"))
+    };
+
+    while pixels.len() < pixel_count {
+        if rle {
+            let packet = read(1)?[0];
+            let count = (packet & 0x7F) as usize + 1;
+
+            if packet & 0x80 == 0 {
+                for p in read(count * pixel_size)?.chunks(pixel_size) {
+                    pixels.push(convert(p)?);
+                }
+            } else {
+                let p = convert(read(pixel_size)?)?;
+                pixels.extend(std::iter::repeat_n(p, count));
+            }
+        } else {
+            for p in read(pixel_count * pixel_size)?.chunks(pixel_size) {
+                pixels.push(convert(p)?);
+            }
+        }
+    }
+    pixels.truncate(pixel_count);
+
+    //Reorder into top to bottom, left to right
+    if header.descriptor & DESCRIPTOR_RIGHT_TO_LEFT != 0 {
+        for row in pixels.chunks_mut(width as usize) {
+            row.reverse();
+        }
+    }
+    if header.descriptor & DESCRIPTOR_TOP_TO_BOTTOM == 0 {
+        pixels = pixels
+            .chunks(width as usize)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+    }
+
+    Ok(Image {
+        img_type: lunar_png::ImageType::Rgba8,
+        width,
+        height,
+        data: pixels.into_iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+fn header(image_type: u8, bpp: u8, descriptor: u8) -> Vec<u8> {
+    bytemuck::bytes_of(&Header {
+        id_length: 0,
+        color_map_type: 0,
+        image_type,
+        color_map_start: 0,
+        color_map_length: 0,
+        color_map_bpp: 0,
+        x_origin: 0,
+        y_origin: 0,
+        width: 2,
+        height: 2,
+        bpp,
+        descriptor,
+    })
+    .to_vec()
+}
+
+#[test]
+fn test_parse_uncompressed() {
+    //Bottom to top
+    let mut data = header(2, 24, 0);
+    data.extend_from_slice(&[0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255]);
+
+    let image = parse(&data).unwrap();
+    assert_eq!((image.width, image.height), (2, 2));
+    assert_eq!(
+        image.data,
+        vec![
+            0, 0, 255, 255, 255, 255, 255, 255, //
+            255, 0, 0, 255, 0, 255, 0, 255, //
+        ]
+    );
+
+    //Top to bottom grayscale
+    let mut data = header(3, 8, DESCRIPTOR_TOP_TO_BOTTOM);
+    data.extend_from_slice(&[0, 64, 128, 255]);
+    let image = parse(&data).unwrap();
+    assert_eq!(&image.data[..8], &[0, 0, 0, 255, 64, 64, 64, 255]);
+}
+
+#[test]
+fn test_parse_rle() {
+    let mut data = header(10, 32, DESCRIPTOR_TOP_TO_BOTTOM | 8);
+    //3 repeated pixels and a raw one
+    data.extend_from_slice(&[0x82, 1, 2, 3, 4, 0x00, 5, 6, 7, 8]);
+
+    let image = parse(&data).unwrap();
+    assert_eq!(
+        image.data,
+        vec![3, 2, 1, 4, 3, 2, 1, 4, 3, 2, 1, 4, 7, 6, 5, 8]
+    );
+
+    data.pop();
+    assert!(parse(&data).is_err());
+}
+
+#[test]
+fn test_parse_no_pixels() {
+    //Zero width, then zero height
+    for offset in [12, 14] {
+        let mut data = header(2, 24, 0);
+        data[offset..offset + 2].fill(0);
+        data.extend_from_slice(&[0; 12]);
+        assert!(parse(&data).is_err());
+    }
+}
@@ -0,0 +1,879 @@
+//! Loading of TrueType and OpenType fonts
+//!
+//! Glyphs are mapped using the unicode `cmap` subtables, and their outlines are read from the
+//! `glyf` table, including composite glyphs. Kerning is read from the `kern` table and from the
+//! pair adjustment lookups of the `kern` feature in the `GPOS` table. Fonts with `CFF` outlines
+//! and font collections are not supported
+#![allow(clippy::cast_possible_truncation)]
+use std::collections::HashMap;
+
+use super::invalid;
+
+///Maximum nesting of composite glyphs
+const MAX_COMPONENT_DEPTH: u32 = 8;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    u16_at(data, offset).map(u16::cast_signed)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+///Reads a 2.14 fixed point number
+fn f2dot14_at(data: &[u8], offset: usize) -> Option<f32> {
+    i16_at(data, offset).map(|v| f32::from(v) / 16384.0)
+}
+
+///A segment of a glyph outline, in font units with y pointing up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    ///A straight line between the points
+    Line([f32; 2], [f32; 2]),
+    ///A quadratic curve from the first point to the last one, bent towards the control point
+    Quad([f32; 2], [f32; 2], [f32; 2]),
+}
+
+///Horizontal metrics of a font, in font units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontMetrics {
+    ///Number of font units per em, the size of the font
+    pub units_per_em: u16,
+    ///Distance from the baseline to the top of the highest glyphs
+    pub ascender: i16,
+    ///Distance from the baseline to the bottom of the lowest glyphs, usually negative
+    pub descender: i16,
+    ///Additional space between lines
+    pub line_gap: i16,
+}
+
+impl FontMetrics {
+    ///Returns the distance between the baselines of two lines
+    #[must_use]
+    pub fn line_height(&self) -> f32 {
+        f32::from(self.ascender) - f32::from(self.descender) + f32::from(self.line_gap)
+    }
+}
+
+///A parsed font file
+#[derive(Debug)]
+pub struct FontFile {
+    data: Vec<u8>,
+    metrics: FontMetrics,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    long_loca: bool,
+    cmap: usize,
+    hmtx: usize,
+    loca: usize,
+    glyf: usize,
+    gpos: Option<usize>,
+    ///Pairs from the `kern` table
+    kern_pairs: HashMap<(u16, u16), i16>,
+    ///Offsets of the pair adjustment subtables of the `kern` feature
+    gpos_pairs: Vec<usize>,
+}
+
+///Parses a font file
+///
+///# Errors
+///Fails if the data is not a TrueType or OpenType font with TrueType outlines, or if any of the
+///required tables are missing or invalid
+pub fn parse(data: Vec<u8>) -> Result<FontFile, Box<dyn std::error::Error + Send>> {
+    match u32_at(&data, 0) {
+        Some(0x0001_0000 | 0x7472_7565) => {}
+        Some(0x4F54_544F) => return Err(invalid("Fonts with CFF outlines are not supported")),
+        Some(0x7474_6366) => return Err(invalid("Font collections are not supported")),
+        _ => return Err(invalid("Not a font file")),
+    }
+    let num_tables = u16_at(&data, 4).ok_or_else(|| invalid("Font file is truncated"))?;
+
+    let mut tables = HashMap::new();
+    for i in 0..usize::from(num_tables) {
+        let record = 12 + i * 16;
+        let (Some(tag), Some(offset), Some(length)) = (
+            data.get(record..record + 4),
+            u32_at(&data, record + 8),
+            u32_at(&data, record + 12),
+        ) else {
+            return Err(invalid("Font table directory is truncated"));
+        };
+        if offset as usize + length as usize > data.len() {
+            return Err(invalid("Font table is outside of the file"));
+        }
+        tables.insert(<[u8; 4]>::try_from(tag).unwrap(), offset as usize);
+    }
+    let table = |tag: &[u8; 4]| {
+        tables.get(tag).copied().ok_or_else(|| {
+            invalid(&format!(
+                "Font is missing the {} table",
+                String::from_utf8_lossy(tag)
+            ))
+        })
+    };
+
+    if tables.contains_key(b"CFF ") || tables.contains_key(b"CFF2") {
+        return Err(invalid("Fonts with CFF outlines are not supported"));
+    }
+
+    let (head, maxp, hhea) = (table(b"head")?, table(b"maxp")?, table(b"hhea")?);
+    let truncated = || invalid("Font header tables are truncated");
+    let metrics = FontMetrics {
+        units_per_em: u16_at(&data, head + 18).ok_or_else(truncated)?,
+        ascender: i16_at(&data, hhea + 4).ok_or_else(truncated)?,
+        descender: i16_at(&data, hhea + 6).ok_or_else(truncated)?,
+        line_gap: i16_at(&data, hhea + 8).ok_or_else(truncated)?,
+    };
+    if metrics.units_per_em == 0 {
+        return Err(invalid("Font has 0 units per em"));
+    }
+    let long_loca = i16_at(&data, head + 50).ok_or_else(truncated)? != 0;
+    let num_glyphs = u16_at(&data, maxp + 4).ok_or_else(truncated)?;
+    let num_h_metrics = u16_at(&data, hhea + 34).ok_or_else(truncated)?;
+    if num_h_metrics == 0 {
+        return Err(invalid("Font has no horizontal metrics"));
+    }
+
+    let cmap = find_cmap(&data, table(b"cmap")?)
+        .ok_or_else(|| invalid("Font has no supported unicode character map"))?;
+
+    let kern_pairs = tables
+        .get(b"kern")
+        .and_then(|k| kern_pairs(&data[*k..]))
+        .unwrap_or_default();
+    let gpos = tables.get(b"GPOS").copied();
+    let gpos_pairs = gpos
+        .and_then(|g| gpos_pair_subtables(&data[g..]))
+        .unwrap_or_default();
+
+    Ok(FontFile {
+        metrics,
+        num_glyphs,
+        num_h_metrics,
+        long_loca,
+        cmap,
+        hmtx: table(b"hmtx")?,
+        loca: table(b"loca")?,
+        glyf: table(b"glyf")?,
+        gpos,
+        kern_pairs,
+        gpos_pairs,
+        data,
+    })
+}
+
+///Finds the best unicode subtable of the character map, returns its offset
+fn find_cmap(data: &[u8], cmap: usize) -> Option<usize> {
+    let num_subtables = u16_at(data, cmap + 2)?;
+    let mut best = None;
+    for i in 0..usize::from(num_subtables) {
+        let record = cmap + 4 + i * 8;
+        let (platform, encoding) = (u16_at(data, record)?, u16_at(data, record + 2)?);
+        let offset = cmap + u32_at(data, record + 4)? as usize;
+        let format = u16_at(data, offset)?;
+
+        //Prefer full unicode tables over the basic multilingual plane ones
+        let priority = match (platform, encoding, format) {
+            (0 | 3, _, 12) if platform == 0 || encoding == 10 => 2,
+            (0, _, 4) | (3, 1, 4) => 1,
+            _ => continue,
+        };
+        if best.is_none_or(|(p, _)| priority > p) {
+            best = Some((priority, offset));
+        }
+    }
+    best.map(|b| b.1)
+}
+
+///Reads the pairs of the horizontal format 0 subtables of a `kern` table
+fn kern_pairs(kern: &[u8]) -> Option<HashMap<(u16, u16), i16>> {
+    let mut pairs = HashMap::new();
+    let num_subtables = u16_at(kern, 2)?;
+    let mut offset = 4;
+    for _ in 0..num_subtables {
+        let length = usize::from(u16_at(kern, offset + 2)?);
+        let coverage = u16_at(kern, offset + 4)?;
+        //Horizontal kerning values in format 0
+        if coverage & 0xFF07 == 0x0001 {
+            let num_pairs = usize::from(u16_at(kern, offset + 6)?);
+            for i in 0..num_pairs {
+                let pair = offset + 14 + i * 6;
+                pairs.insert(
+                    (u16_at(kern, pair)?, u16_at(kern, pair + 2)?),
+                    i16_at(kern, pair + 4)?,
+                );
+            }
+        }
+        offset += length;
+    }
+    Some(pairs)
+}
+
+///Returns the offsets of the pair adjustment subtables used by the `kern` feature, relative to
+///the start of the `GPOS` table
+fn gpos_pair_subtables(gpos: &[u8]) -> Option<Vec<usize>> {
+    let feature_list = usize::from(u16_at(gpos, 6)?);
+    let lookup_list = usize::from(u16_at(gpos, 8)?);
+
+    let mut lookups = Vec::new();
+    for i in 0..usize::from(u16_at(gpos, feature_list)?) {
+        let record = feature_list + 2 + i * 6;
+        if gpos.get(record..record + 4)? != b"kern" {
+            continue;
+        }
+        let feature = feature_list + usize::from(u16_at(gpos, record + 4)?);
+        for j in 0..usize::from(u16_at(gpos, feature + 2)?) {
+            let index = u16_at(gpos, feature + 4 + j * 2)?;
+            if !lookups.contains(&index) {
+                lookups.push(index);
+            }
+        }
+    }
+    lookups.sort_unstable();
+
+    let mut subtables = Vec::new();
+    for index in lookups {
+        let lookup =
+            lookup_list + usize::from(u16_at(gpos, lookup_list + 2 + usize::from(index) * 2)?);
+        let lookup_type = u16_at(gpos, lookup)?;
+        for i in 0..usize::from(u16_at(gpos, lookup + 4)?) {
+            let subtable = lookup + usize::from(u16_at(gpos, lookup + 6 + i * 2)?);
+            match lookup_type {
+                2 => subtables.push(subtable),
+                //Extension lookups point to the actual subtable
+                9 if u16_at(gpos, subtable + 2)? == 2 => {
+                    subtables.push(subtable + u32_at(gpos, subtable + 4)? as usize);
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(subtables)
+}
+
+///Returns the coverage index of the glyph
+fn coverage_index(data: &[u8], coverage: usize, glyph: u16) -> Option<usize> {
+    match u16_at(data, coverage)? {
+        1 => {
+            let count = usize::from(u16_at(data, coverage + 2)?);
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let middle = usize::midpoint(low, high);
+                let value = u16_at(data, coverage + 4 + middle * 2)?;
+                match value.cmp(&glyph) {
+                    std::cmp::Ordering::Less => low = middle + 1,
+                    std::cmp::Ordering::Greater => high = middle,
+                    std::cmp::Ordering::Equal => return Some(middle),
+                }
+            }
+            None
+        }
+        2 => {
+            let count = usize::from(u16_at(data, coverage + 2)?);
+            (0..count).find_map(|i| {
+                let range = coverage + 4 + i * 6;
+                let (start, end) = (u16_at(data, range)?, u16_at(data, range + 2)?);
+                (start..=end).contains(&glyph).then(|| {
+                    u16_at(data, range + 4).map(|s| usize::from(s) + usize::from(glyph - start))
+                })?
+            })
+        }
+        _ => None,
+    }
+}
+
+///Returns the class of the glyph, glyphs that are not listed are in class 0
+fn glyph_class(data: &[u8], class_def: usize, glyph: u16) -> Option<u16> {
+    match u16_at(data, class_def)? {
+        1 => {
+            let start = u16_at(data, class_def + 2)?;
+            let count = u16_at(data, class_def + 4)?;
+            if glyph < start || glyph - start >= count {
+                return Some(0);
+            }
+            u16_at(data, class_def + 6 + usize::from(glyph - start) * 2)
+        }
+        2 => {
+            let count = usize::from(u16_at(data, class_def + 2)?);
+            Some(
+                (0..count)
+                    .find_map(|i| {
+                        let range = class_def + 4 + i * 6;
+                        let (start, end) = (u16_at(data, range)?, u16_at(data, range + 2)?);
+                        (start..=end)
+                            .contains(&glyph)
+                            .then(|| u16_at(data, range + 4))?
+                    })
+                    .unwrap_or(0),
+            )
+        }
+        _ => None,
+    }
+}
+
+///Returns the x advance adjustment of the first glyph of a pair adjustment subtable
+fn pair_adjustment(data: &[u8], subtable: usize, left: u16, right: u16) -> Option<i16> {
+    let format = u16_at(data, subtable)?;
+    let coverage = subtable + usize::from(u16_at(data, subtable + 2)?);
+    let value_format_1 = u16_at(data, subtable + 4)?;
+    let value_format_2 = u16_at(data, subtable + 6)?;
+    //Only the x advance of the first glyph is used
+    if value_format_1 & 0x0004 == 0 {
+        return None;
+    }
+    let advance_offset = 2 * (value_format_1 & 0x0003).count_ones() as usize;
+    let record_size =
+        2 * ((value_format_1 & 0xFF).count_ones() + (value_format_2 & 0xFF).count_ones()) as usize;
+    let index = coverage_index(data, coverage, left)?;
+
+    match format {
+        1 => {
+            let pair_set = subtable + usize::from(u16_at(data, subtable + 10 + index * 2)?);
+            let count = usize::from(u16_at(data, pair_set)?);
+            //Records are sorted by the second glyph
+            let (mut low, mut high) = (0, count);
+            while low < high {
+                let middle = usize::midpoint(low, high);
+                let record = pair_set + 2 + middle * (2 + record_size);
+                match u16_at(data, record)?.cmp(&right) {
+                    std::cmp::Ordering::Less => low = middle + 1,
+                    std::cmp::Ordering::Greater => high = middle,
+                    std::cmp::Ordering::Equal => return i16_at(data, record + 2 + advance_offset),
+                }
+            }
+            None
+        }
+        2 => {
+            let class_def_1 = subtable + usize::from(u16_at(data, subtable + 8)?);
+            let class_def_2 = subtable + usize::from(u16_at(data, subtable + 10)?);
+            let class_1_count = u16_at(data, subtable + 12)?;
+            let class_2_count = usize::from(u16_at(data, subtable + 14)?);
+            let class_1 = glyph_class(data, class_def_1, left)?;
+            let class_2 = usize::from(glyph_class(data, class_def_2, right)?);
+            if class_1 >= class_1_count || class_2 >= class_2_count {
+                return None;
+            }
+            let record =
+                subtable + 16 + (usize::from(class_1) * class_2_count + class_2) * record_size;
+            i16_at(data, record + advance_offset)
+        }
+        _ => None,
+    }
+}
+
+impl FontFile {
+    ///Returns the metrics of the font
+    #[must_use]
+    pub const fn metrics(&self) -> FontMetrics {
+        self.metrics
+    }
+
+    ///Returns the number of glyphs in the font
+    #[must_use]
+    pub const fn num_glyphs(&self) -> u16 {
+        self.num_glyphs
+    }
+
+    ///Returns the index of the glyph of the character, `None` if the font does not contain it
+    #[must_use]
+    pub fn glyph_index(&self, character: char) -> Option<u16> {
+        let data = self.data.as_slice();
+        let code = u32::from(character);
+        let glyph = match u16_at(data, self.cmap)? {
+            4 => {
+                let code = u16::try_from(code).ok()?;
+                let segments = usize::from(u16_at(data, self.cmap + 6)? / 2);
+                let ends = self.cmap + 14;
+                let starts = ends + segments * 2 + 2;
+                let deltas = starts + segments * 2;
+                let range_offsets = deltas + segments * 2;
+
+                //The first segment whose end is not smaller than the code
+                let (mut low, mut high) = (0, segments);
+                while low < high {
+                    let middle = usize::midpoint(low, high);
+                    if u16_at(data, ends + middle * 2)? < code {
+                        low = middle + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+                let segment = low;
+                let start = u16_at(data, starts + segment * 2)?;
+                if segment == segments || start > code {
+                    return None;
+                }
+                let delta = u16_at(data, deltas + segment * 2)?;
+                let range_offset = usize::from(u16_at(data, range_offsets + segment * 2)?);
+                if range_offset == 0 {
+                    code.wrapping_add(delta)
+                } else {
+                    let address =
+                        range_offsets + segment * 2 + range_offset + usize::from(code - start) * 2;
+                    match u16_at(data, address)? {
+                        0 => 0,
+                        glyph => glyph.wrapping_add(delta),
+                    }
+                }
+            }
+            12 => {
+                let groups = u32_at(data, self.cmap + 12)? as usize;
+                let (mut low, mut high) = (0, groups);
+                while low < high {
+                    let middle = usize::midpoint(low, high);
+                    let group = self.cmap + 16 + middle * 12;
+                    let (start, end) = (u32_at(data, group)?, u32_at(data, group + 4)?);
+                    if end < code {
+                        low = middle + 1;
+                    } else if start > code {
+                        high = middle;
+                    } else {
+                        return u16::try_from(u32_at(data, group + 8)? + code - start)
+                            .ok()
+                            .filter(|g| *g != 0 && *g < self.num_glyphs);
+                    }
+                }
+                0
+            }
+            _ => 0,
+        };
+        (glyph != 0 && glyph < self.num_glyphs).then_some(glyph)
+    }
+
+    ///Returns the horizontal advance of the glyph in font units
+    #[must_use]
+    pub fn advance(&self, glyph: u16) -> f32 {
+        let index = usize::from(glyph.min(self.num_h_metrics - 1));
+        u16_at(&self.data, self.hmtx + index * 4).map_or(0.0, f32::from)
+    }
+
+    ///Returns the adjustment of the advance of the left glyph when it is followed by the right
+    ///one, in font units
+    #[must_use]
+    pub fn kerning(&self, left: u16, right: u16) -> f32 {
+        if let Some(gpos) = self.gpos {
+            let data = &self.data[gpos..];
+            if let Some(value) = self
+                .gpos_pairs
+                .iter()
+                .find_map(|s| pair_adjustment(data, *s, left, right))
+            {
+                return f32::from(value);
+            }
+        }
+        self.kern_pairs
+            .get(&(left, right))
+            .map_or(0.0, |v| f32::from(*v))
+    }
+
+    ///Returns the range of the glyph in the `glyf` table
+    fn glyph_range(&self, glyph: u16) -> Option<std::ops::Range<usize>> {
+        if glyph >= self.num_glyphs {
+            return None;
+        }
+        let index = usize::from(glyph);
+        let (start, end) = if self.long_loca {
+            (
+                u32_at(&self.data, self.loca + index * 4)? as usize,
+                u32_at(&self.data, self.loca + index * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                usize::from(u16_at(&self.data, self.loca + index * 2)?) * 2,
+                usize::from(u16_at(&self.data, self.loca + index * 2 + 2)?) * 2,
+            )
+        };
+        (start <= end).then(|| self.glyf + start..self.glyf + end)
+    }
+
+    ///Returns the outline of the glyph, empty for glyphs without one, like the space
+    ///
+    ///# Errors
+    ///Fails if the outline data is invalid
+    pub fn outline(&self, glyph: u16) -> Result<Vec<Segment>, Box<dyn std::error::Error + Send>> {
+        let mut segments = Vec::new();
+        self.append_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut segments)
+            .ok_or_else(|| invalid("Invalid glyph outline"))?;
+        Ok(segments)
+    }
+
+    ///Appends the outline of the glyph transformed by the 2x3 matrix to the segments
+    fn append_outline(
+        &self,
+        glyph: u16,
+        transform: [f32; 6],
+        depth: u32,
+        segments: &mut Vec<Segment>,
+    ) -> Option<()> {
+        let range = self.glyph_range(glyph)?;
+        if range.is_empty() {
+            return Some(());
+        }
+        let data = self.data.get(range)?;
+        let num_contours = i16_at(data, 0)?;
+
+        if num_contours >= 0 {
+            let points = simple_glyph_points(data, num_contours.unsigned_abs().into())?;
+            let apply = |[x, y]: [f32; 2]| {
+                [
+                    transform[0].mul_add(x, transform[2] * y) + transform[4],
+                    transform[1].mul_add(x, transform[3] * y) + transform[5],
+                ]
+            };
+            for contour in points {
+                append_contour(
+                    &contour
+                        .into_iter()
+                        .map(|(p, on)| (apply(p), on))
+                        .collect::<Vec<_>>(),
+                    segments,
+                );
+            }
+            return Some(());
+        }
+
+        if depth >= MAX_COMPONENT_DEPTH {
+            return None;
+        }
+        let mut offset = 10;
+        loop {
+            let flags = u16_at(data, offset)?;
+            let component = u16_at(data, offset + 2)?;
+            offset += 4;
+
+            let (dx, dy) = if flags & 0x0001 == 0 {
+                let (x, y) = (data.get(offset)?, data.get(offset + 1)?);
+                offset += 2;
+                (f32::from(x.cast_signed()), f32::from(y.cast_signed()))
+            } else {
+                let (x, y) = (i16_at(data, offset)?, i16_at(data, offset + 2)?);
+                offset += 4;
+                (f32::from(x), f32::from(y))
+            };
+            //Matching points are not supported, the component is placed at the origin
+            let (dx, dy) = if flags & 0x0002 == 0 {
+                (0.0, 0.0)
+            } else {
+                (dx, dy)
+            };
+
+            let mut matrix = [1.0, 0.0, 0.0, 1.0];
+            if flags & 0x0008 != 0 {
+                let scale = f2dot14_at(data, offset)?;
+                matrix = [scale, 0.0, 0.0, scale];
+                offset += 2;
+            } else if flags & 0x0040 != 0 {
+                matrix = [
+                    f2dot14_at(data, offset)?,
+                    0.0,
+                    0.0,
+                    f2dot14_at(data, offset + 2)?,
+                ];
+                offset += 4;
+            } else if flags & 0x0080 != 0 {
+                matrix = [
+                    f2dot14_at(data, offset)?,
+                    f2dot14_at(data, offset + 2)?,
+                    f2dot14_at(data, offset + 4)?,
+                    f2dot14_at(data, offset + 6)?,
+                ];
+                offset += 8;
+            }
+
+            //Combine the component transform with the parent one
+            let combined = [
+                transform[0].mul_add(matrix[0], transform[2] * matrix[1]),
+                transform[1].mul_add(matrix[0], transform[3] * matrix[1]),
+                transform[0].mul_add(matrix[2], transform[2] * matrix[3]),
+                transform[1].mul_add(matrix[2], transform[3] * matrix[3]),
+                transform[0].mul_add(dx, transform[2] * dy) + transform[4],
+                transform[1].mul_add(dx, transform[3] * dy) + transform[5],
+            ];
+            self.append_outline(component, combined, depth + 1, segments)?;
+
+            if flags & 0x0020 == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+///Points of a contour and whether they are on the curve
+type Contour = Vec<([f32; 2], bool)>;
+
+///Reads the points of a simple glyph, grouped by contours
+fn simple_glyph_points(data: &[u8], num_contours: usize) -> Option<Vec<Contour>> {
+    let ends = (0..num_contours)
+        .map(|i| u16_at(data, 10 + i * 2).map(usize::from))
+        .collect::<Option<Vec<_>>>()?;
+    let num_points = ends.last().map_or(0, |e| e + 1);
+    let instructions = usize::from(u16_at(data, 10 + num_contours * 2)?);
+    let mut offset = 12 + num_contours * 2 + instructions;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = *data.get(offset)?;
+        offset += 1;
+        flags.push(flag);
+        if flag & 0x08 != 0 {
+            let repeat = *data.get(offset)?;
+            offset += 1;
+            flags.extend(std::iter::repeat_n(flag, usize::from(repeat)));
+        }
+    }
+    flags.truncate(num_points);
+
+    //Reads the x or the y coordinates, which are deltas from the previous point
+    let mut read = |short: u8, same: u8| {
+        let mut value = 0i32;
+        flags
+            .iter()
+            .map(|flag| {
+                if flag & short != 0 {
+                    let delta = i32::from(*data.get(offset)?);
+                    offset += 1;
+                    value += if flag & same == 0 { -delta } else { delta };
+                } else if flag & same == 0 {
+                    value += i32::from(i16_at(data, offset)?);
+                    offset += 2;
+                }
+                Some(value as f32)
+            })
+            .collect::<Option<Vec<_>>>()
+    };
+    let xs = read(0x02, 0x10)?;
+    let ys = read(0x04, 0x20)?;
+
+    let mut start = 0;
+    ends.into_iter()
+        .map(|end| {
+            let contour = (start..=end)
+                .map(|i| Some(([*xs.get(i)?, *ys.get(i)?], flags.get(i)? & 0x01 != 0)))
+                .collect::<Option<Vec<_>>>();
+            start = end + 1;
+            contour
+        })
+        .collect()
+}
+
+///Converts a closed contour of on and off curve points into segments
+#[allow(clippy::float_cmp)]
+fn append_contour(points: &[([f32; 2], bool)], segments: &mut Vec<Segment>) {
+    if points.len() < 2 {
+        return;
+    }
+    let midpoint =
+        |a: [f32; 2], b: [f32; 2]| [f32::midpoint(a[0], b[0]), f32::midpoint(a[1], b[1])];
+
+    //Start on a point that is on the curve, or between two off curve points
+    let (start, first) = points.iter().position(|p| p.1).map_or_else(
+        || (1, midpoint(points[0].0, points[1].0)),
+        |i| (i + 1, points[i].0),
+    );
+
+    let mut current = first;
+    let mut control = None;
+    for i in 0..points.len() {
+        let (point, on_curve) = points[(start + i) % points.len()];
+        match (on_curve, control) {
+            (true, None) => {
+                segments.push(Segment::Line(current, point));
+                current = point;
+            }
+            (true, Some(c)) => {
+                segments.push(Segment::Quad(current, c, point));
+                current = point;
+                control = None;
+            }
+            (false, None) => control = Some(point),
+            (false, Some(c)) => {
+                let middle = midpoint(c, point);
+                segments.push(Segment::Quad(current, c, middle));
+                current = middle;
+                control = Some(point);
+            }
+        }
+    }
+    match control {
+        Some(c) => segments.push(Segment::Quad(current, c, first)),
+        //Nothing to close if the last point is exactly the first one
+        None if current != first => segments.push(Segment::Line(current, first)),
+        None => {}
+    }
+}
+
+///Builds a font with a space, a square `A`, a `B` that is the `A` moved right, and a bowl shaped
+///`V` with a curve. `A` is kerned with `V` in the `kern` table
+#[cfg(test)]
+pub(crate) fn test_font(gpos: Option<&[u8]>) -> Vec<u8> {
+    let be16 = |v: &[i32]| {
+        v.iter()
+            .flat_map(|v| (*v as u16).to_be_bytes())
+            .collect::<Vec<_>>()
+    };
+    //Simple glyph with a single contour of points with i16 coordinates
+    let simple = |points: &[(i32, i32, bool)]| {
+        let mut glyph = be16(&[1, 0, 0, 0, 0, points.len() as i32 - 1, 0]);
+        glyph.extend(points.iter().map(|p| u8::from(p.2)));
+        let (mut x, mut y) = (0, 0);
+        for p in points {
+            glyph.extend(be16(&[p.0 - x]));
+            x = p.0;
+        }
+        for p in points {
+            glyph.extend(be16(&[p.1 - y]));
+            y = p.1;
+        }
+        glyph
+    };
+    let glyphs = [
+        Vec::new(),
+        Vec::new(),
+        simple(&[
+            (100, 0, true),
+            (100, 700, true),
+            (900, 700, true),
+            (900, 0, true),
+        ]),
+        //Component 2 moved by (100, 0), the arguments are words with x and y values
+        be16(&[-1, 0, 0, 0, 0, 0x0003, 2, 100, 0]),
+        simple(&[(0, 700, true), (500, -100, false), (1000, 700, true)]),
+    ];
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    for glyph in &glyphs {
+        loca.extend(be16(&[glyf.len() as i32 / 2]));
+        glyf.extend(glyph);
+        glyf.resize(glyf.len().next_multiple_of(2), 0);
+    }
+    loca.extend(be16(&[glyf.len() as i32 / 2]));
+
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+    let maxp = be16(&[0, 0x5000, glyphs.len() as i32]);
+    let mut hhea = be16(&[1, 0, 800, -200, 0]);
+    hhea.resize(34, 0);
+    hhea.extend(be16(&[glyphs.len() as i32]));
+    let hmtx = be16(&[500, 0, 500, 0, 1000, 100, 1000, 200, 1000, 0]);
+    //Format 4 segments for ' ', 'A' to 'B', 'V' and the required last one
+    let mut cmap = be16(&[0, 1, 3, 1, 0, 12, 4, 48, 0, 8, 8, 2, 0]);
+    cmap.extend(be16(&[32, 66, 86, 0xFFFF, 0, 32, 65, 86, 0xFFFF]));
+    cmap.extend(be16(&[1 - 32, 2 - 65, 4 - 86, 1, 0, 0, 0, 0]));
+    let kern = be16(&[0, 1, 0, 20, 0x0001, 1, 6, 0, 0, 2, 4, -100]);
+
+    let mut tables = vec![
+        (*b"cmap", cmap),
+        (*b"glyf", glyf),
+        (*b"head", head),
+        (*b"hhea", hhea),
+        (*b"hmtx", hmtx),
+        (*b"kern", kern),
+        (*b"loca", loca),
+        (*b"maxp", maxp),
+    ];
+    if let Some(gpos) = gpos {
+        tables.push((*b"GPOS", gpos.to_vec()));
+    }
+
+    let mut font = 0x0001_0000u32.to_be_bytes().to_vec();
+    font.extend(be16(&[tables.len() as i32, 0, 0, 0]));
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in &tables {
+        font.extend(tag);
+        font.extend([0; 4]);
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in tables {
+        font.extend(&table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    font
+}
+
+#[test]
+fn test_parse_ttf() {
+    let font = parse(test_font(None)).unwrap();
+    assert_eq!(
+        font.metrics(),
+        FontMetrics {
+            units_per_em: 1000,
+            ascender: 800,
+            descender: -200,
+            line_gap: 0,
+        }
+    );
+    assert_eq!(font.num_glyphs(), 5);
+    assert_eq!(font.metrics().line_height(), 1000.0);
+
+    let indices = [' ', 'A', 'B', 'V', 'C', '\u{1F600}'].map(|c| font.glyph_index(c));
+    assert_eq!(indices, [Some(1), Some(2), Some(3), Some(4), None, None]);
+    assert_eq!(font.advance(1), 500.0);
+    assert_eq!(font.advance(4), 1000.0);
+    assert_eq!(font.kerning(2, 4), -100.0);
+    assert_eq!(font.kerning(4, 2), 0.0);
+
+    assert!(font.outline(1).unwrap().is_empty());
+    let square = font.outline(2).unwrap();
+    assert_eq!(
+        square,
+        vec![
+            Segment::Line([100.0, 0.0], [100.0, 700.0]),
+            Segment::Line([100.0, 700.0], [900.0, 700.0]),
+            Segment::Line([900.0, 700.0], [900.0, 0.0]),
+            Segment::Line([900.0, 0.0], [100.0, 0.0]),
+        ]
+    );
+    //The composite glyph is the square moved right
+    let moved = font.outline(3).unwrap();
+    assert_eq!(moved.len(), 4);
+    assert_eq!(moved[0], Segment::Line([200.0, 0.0], [200.0, 700.0]));
+    assert_eq!(
+        font.outline(4).unwrap(),
+        vec![
+            Segment::Quad([0.0, 700.0], [500.0, -100.0], [1000.0, 700.0]),
+            Segment::Line([1000.0, 700.0], [0.0, 700.0]),
+        ]
+    );
+
+    let mut cff = test_font(None);
+    cff[..4].copy_from_slice(b"OTTO");
+    assert!(parse(cff).is_err());
+    assert!(parse(b"not a font".to_vec()).is_err());
+}
+
+#[test]
+fn test_gpos_kerning() {
+    #[rustfmt::skip]
+    let gpos: &[u8] = &[
+        //Version, script list, feature list and lookup list
+        0, 1, 0, 0, 0, 10, 0, 12, 0, 26,
+        //No scripts
+        0, 0,
+        //The kern feature using lookup 0
+        0, 1, b'k', b'e', b'r', b'n', 0, 8,
+        0, 0, 0, 1, 0, 0,
+        //A pair adjustment lookup with one subtable
+        0, 1, 0, 4,
+        0, 2, 0, 0, 0, 1, 0, 8,
+        //Format 1, the first glyph has an x advance
+        0, 1, 0, 12, 0, 4, 0, 0, 0, 1, 0, 18,
+        //Coverage of V
+        0, 1, 0, 1, 0, 4,
+        //V followed by A moves A 50 units left
+        0, 1, 0, 2, 0xFF, 0xCE,
+    ];
+    let font = parse(test_font(Some(gpos))).unwrap();
+    assert_eq!(font.kerning(4, 2), -50.0);
+    //Pairs that are not in the GPOS table still use the kern table
+    assert_eq!(font.kerning(2, 4), -100.0);
+    assert_eq!(font.kerning(2, 2), 0.0);
+}
@@ -29,6 +29,8 @@ pub(crate) struct InputState {
     //Cursor delta stuff
     pub(crate) raw_curosor_delta: RwLock<Vec2>,
     pub(crate) delta_changed: RwLock<bool>,
+    ///Text typed since the last frame
+    pub(crate) text: RwLock<String>,
 }
 
 pub(crate) static INPUT: OnceLock<InputState> = OnceLock::new();
@@ -61,6 +63,11 @@ pub(crate) fn set_cursor_position(pos: Vec2) {
     *i = pos;
 }
 
+///Adds typed text
+pub(crate) fn push_text(text: &str) {
+    INPUT.get().unwrap().text.write().unwrap().push_str(text);
+}
+
 ///Returns the state of the requested mouse button
 pub fn mouse_btn(btn: MouseButton) -> KeyState {
     let mut i = INPUT.get().unwrap().mouse_button_map.write().unwrap();
@@ -73,6 +80,13 @@ pub fn cursor_position() -> Vec2 {
     *INPUT.get().unwrap().cursor_position.read().unwrap()
 }
 
+///Returns the text typed during the last frame, taking the keyboard layout into account
+///
+///Can contain control characters, like `\u{8}` for backspace
+pub fn text_input() -> String {
+    INPUT.get().unwrap().text.read().unwrap().clone()
+}
+
 ///Returns the cursor movement delta
 pub fn cursor_delta() -> Vec2 {
     *INPUT.get().unwrap().cursor_delta.read().unwrap()
@@ -118,6 +132,7 @@ pub(crate) fn update() {
     drop(changed);
 
     *input.cursor_delta.write().unwrap() = d;
+    input.text.write().unwrap().clear();
 
     *last = *cur;
 }
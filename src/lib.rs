@@ -77,6 +77,7 @@ pub mod rendering;
 pub mod structures;
 #[cfg(test)]
 mod test_utils;
+pub mod ui;
 mod utils;
 
 mod windowing;
@@ -503,6 +504,9 @@ impl<T> ApplicationHandler for State<T> {
                 } else {
                     None
                 };
+                if let (event::ElementState::Pressed, Some(text)) = (event.state, &event.text) {
+                    input::push_text(text);
+                }
                 if keycode.is_none() {
                     return;
                 }
@@ -0,0 +1,108 @@
+use super::{Mat4x4, Vec3};
+
+///An axis aligned bounding box
+#[derive(Clone, Copy, Default, Debug, PartialEq, PartialOrd)]
+pub struct Aabb {
+    ///Corner of the box with the smallest coordinates
+    pub min: Vec3,
+    ///Corner of the box with the largest coordinates
+    pub max: Vec3,
+}
+
+impl Aabb {
+    ///Creates a new bounding box from its corners
+    #[must_use]
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    ///Creates the smallest bounding box that contains all of the points
+    ///
+    ///Returns `None` if there are no points
+    #[must_use]
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self::new(first, first), Self::expanded))
+    }
+
+    ///Returns the center of the box
+    #[must_use]
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    ///Returns the dimensions of the box
+    #[must_use]
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    ///Returns half of the dimensions of the box
+    #[must_use]
+    pub fn half_extents(&self) -> Vec3 {
+        self.size() / 2.0
+    }
+
+    ///Returns the 8 corners of the box
+    #[must_use]
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        std::array::from_fn(|i| {
+            Vec3::new(
+                [a.x, b.x][i & 1],
+                [a.y, b.y][(i >> 1) & 1],
+                [a.z, b.z][i >> 2],
+            )
+        })
+    }
+
+    ///Returns true if the point is inside of the box or on its surface
+    #[must_use]
+    pub fn contains(&self, point: Vec3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    ///Returns true if the boxes overlap or touch
+    #[must_use]
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    ///Returns the box grown to contain the point
+    #[must_use]
+    pub const fn expanded(self, point: Vec3) -> Self {
+        Self {
+            min: Vec3 {
+                x: self.min.x.min(point.x),
+                y: self.min.y.min(point.y),
+                z: self.min.z.min(point.z),
+            },
+            max: Vec3 {
+                x: self.max.x.max(point.x),
+                y: self.max.y.max(point.y),
+                z: self.max.z.max(point.z),
+            },
+        }
+    }
+
+    ///Returns the smallest box that contains both of the boxes
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        self.expanded(other.min).expanded(other.max)
+    }
+
+    ///Returns the smallest box that contains this box after it was transformed by the matrix
+    #[must_use]
+    pub fn transformed(&self, matrix: &Mat4x4) -> Self {
+        Self::from_points(self.corners().map(|c| matrix.transform3(c))).unwrap()
+    }
+}
@@ -1,8 +1,10 @@
 //! The math library
 //!
-//! Contains implementations of vectors with length 2,3,4 and 4x4 matrices
+//! Contains implementations of vectors with length 2,3,4, 4x4 matrices, bounding boxes and rays
+mod aabb;
 mod mat4x4;
 mod quaternion;
+mod ray;
 #[cfg(test)]
 mod tests;
 mod traits;
@@ -12,8 +14,10 @@ mod vec4;
 
 use std::ops::{Add, Mul, Sub};
 
+pub use aabb::Aabb;
 pub use mat4x4::Mat4x4;
 pub use quaternion::Quaternion;
+pub use ray::Ray;
 pub use traits::IntoFloat32;
 pub use traits::Vector;
 pub use vec2::Vec2;
@@ -173,6 +173,98 @@ impl Quaternion {
         }
     }
 
+    ///Returns the dot product of the quaternions
+    #[must_use]
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    ///Linearly interpolates between the rotations and normalizes the result, taking the shortest
+    ///path
+    ///
+    ///Faster than [`Quaternion::slerp`], but the rotation speed is not constant
+    #[must_use]
+    pub fn nlerp(&self, other: &Self, t: f32) -> Self {
+        let sign = if self.dot(other) < 0.0 { -1.0 } else { 1.0 };
+
+        Self {
+            w: self.w + (other.w * sign - self.w) * t,
+            x: self.x + (other.x * sign - self.x) * t,
+            y: self.y + (other.y * sign - self.y) * t,
+            z: self.z + (other.z * sign - self.z) * t,
+        }
+        .normalize()
+    }
+
+    ///Spherically interpolates between the rotations, taking the shortest path
+    #[must_use]
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let a = self.normalize();
+        let mut b = other.normalize();
+        let mut cos = a.dot(&b);
+        if cos < 0.0 {
+            b = Self::new(-b.w, -b.x, -b.y, -b.z);
+            cos = -cos;
+        }
+        //Nearly identical rotations, avoid dividing by 0
+        if cos > 0.9995 {
+            return a.nlerp(&b, t);
+        }
+
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let wa = ((1.0 - t) * angle).sin() / sin;
+        let wb = (t * angle).sin() / sin;
+
+        Self {
+            w: a.w * wa + b.w * wb,
+            x: a.x * wa + b.x * wb,
+            y: a.y * wa + b.y * wb,
+            z: a.z * wa + b.z * wb,
+        }
+    }
+
+    ///Creates a quaternion from the rotation part of the matrix, the matrix must not contain any
+    ///scaling
+    #[must_use]
+    pub fn from_rotation_matrix(m: &Mat4x4) -> Self {
+        let trace = m.m00 + m.m11 + m.m22;
+
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self {
+                w: s / 4.0,
+                x: (m.m21 - m.m12) / s,
+                y: (m.m02 - m.m20) / s,
+                z: (m.m10 - m.m01) / s,
+            }
+        } else if m.m00 > m.m11 && m.m00 > m.m22 {
+            let s = (1.0 + m.m00 - m.m11 - m.m22).sqrt() * 2.0;
+            Self {
+                w: (m.m21 - m.m12) / s,
+                x: s / 4.0,
+                y: (m.m01 + m.m10) / s,
+                z: (m.m02 + m.m20) / s,
+            }
+        } else if m.m11 > m.m22 {
+            let s = (1.0 + m.m11 - m.m00 - m.m22).sqrt() * 2.0;
+            Self {
+                w: (m.m02 - m.m20) / s,
+                x: (m.m01 + m.m10) / s,
+                y: s / 4.0,
+                z: (m.m12 + m.m21) / s,
+            }
+        } else {
+            let s = (1.0 + m.m22 - m.m00 - m.m11).sqrt() * 2.0;
+            Self {
+                w: (m.m10 - m.m01) / s,
+                x: (m.m02 + m.m20) / s,
+                y: (m.m12 + m.m21) / s,
+                z: s / 4.0,
+            }
+        }
+    }
+
     #[cfg(test)]
     ///Makes all values of the quaternion positive
     pub fn abs(&self) -> Self {
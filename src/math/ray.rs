@@ -0,0 +1,108 @@
+use super::{Aabb, Mat4x4, Vec3, Vec4, Vec4Swizzles, Vector};
+
+///A half line starting at the origin, going in the direction
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Ray {
+    ///Start of the ray
+    pub origin: Vec3,
+    ///Direction of the ray, distances along the ray are in multiples of its length
+    pub direction: Vec3,
+}
+
+impl Ray {
+    ///Creates a new ray
+    #[must_use]
+    pub const fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    ///Returns the point at the distance `t` along the ray
+    #[must_use]
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    ///Returns the ray transformed by the matrix
+    ///
+    ///The direction is not normalized, so distances along the transformed ray lead to the same
+    ///points as the distances along the original one
+    #[must_use]
+    pub fn transformed(&self, matrix: &Mat4x4) -> Self {
+        Self {
+            origin: matrix.transform3(self.origin),
+            direction: matrix
+                .transform(Vec4::new(
+                    self.direction.x,
+                    self.direction.y,
+                    self.direction.z,
+                    0.0,
+                ))
+                .xyz(),
+        }
+    }
+
+    ///Returns the distance at which the ray enters the box, 0 if it starts inside of it
+    ///
+    ///Returns `None` if the ray misses the box
+    #[must_use]
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3u32 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            let (min, max) = (aabb.min[axis], aabb.max[axis]);
+
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+
+            let (a, b) = ((min - origin) / direction, (max - origin) / direction);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    ///Returns the distance at which the ray hits the triangle, both sides of the triangle are hit
+    ///
+    ///Returns `None` if the ray misses the triangle or is parallel to it
+    #[must_use]
+    pub fn intersect_triangle(&self, triangle: &[Vec3; 3]) -> Option<f32> {
+        let first = triangle[1] - triangle[0];
+        let second = triangle[2] - triangle[0];
+
+        //The determinant is relative to the size of the triangle and the length of the ray, so
+        //small triangles are not mistaken for parallel ones
+        let across = self.direction.cross(&second);
+        let determinant = first.dot_product(&across);
+        let scale = first.cross(&second).length() * self.direction.length();
+        if determinant.abs() <= f32::EPSILON * scale {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+
+        //Barycentric coordinates of the hit along the first and the second edge
+        let offset = self.origin - triangle[0];
+        let along_first = offset.dot_product(&across) * inverse;
+        if !(0.0..=1.0).contains(&along_first) {
+            return None;
+        }
+
+        let offset_cross = offset.cross(&first);
+        let along_second = self.direction.dot_product(&offset_cross) * inverse;
+        if along_second < 0.0 || along_first + along_second > 1.0 {
+            return None;
+        }
+
+        let distance = second.dot_product(&offset_cross) * inverse;
+        (distance >= 0.0).then_some(distance)
+    }
+}
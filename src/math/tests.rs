@@ -457,3 +457,114 @@ fn vec_min_max() {
     assert_eq!(v.xyz().min(), 1.0);
     assert_eq!(v.xy().max(), 2.0);
 }
+
+#[test]
+fn test_aabb() {
+    assert_eq!(Aabb::from_points([]), None);
+
+    let aabb =
+        Aabb::from_points([Vec3::new(1, -2, 0), Vec3::new(-1, 2, 4), Vec3::new(0, 0, 1)]).unwrap();
+    assert_eq!(aabb, Aabb::new(Vec3::new(-1, -2, 0), Vec3::new(1, 2, 4)));
+    assert_eq!(aabb.center(), Vec3::new(0, 0, 2));
+    assert_eq!(aabb.half_extents(), Vec3::new(1, 2, 2));
+
+    assert!(aabb.contains(Vec3::new(1, 0, 4)));
+    assert!(!aabb.contains(Vec3::new(0, 0, 5)));
+    assert!(aabb.intersects(&Aabb::new(Vec3::new(1, 2, 4), Vec3::new(2, 3, 5))));
+    assert!(!aabb.intersects(&Aabb::new(Vec3::new(2, 0, 0), Vec3::new(3, 1, 1))));
+
+    let union = aabb.union(Aabb::new(Vec3::new(0, 0, 0), Vec3::new(5, 1, 1)));
+    assert_eq!(union, Aabb::new(Vec3::new(-1, -2, 0), Vec3::new(5, 2, 4)));
+
+    let translated = aabb.transformed(&Mat4x4::translation_matrix(&Vec3::new(1, 1, 1)));
+    assert_eq!(
+        translated,
+        Aabb::new(Vec3::new(0, -1, 1), Vec3::new(2, 3, 5))
+    );
+}
+
+#[test]
+fn quaternion_interpolation() {
+    let delta = 0.0001;
+    let a = Quaternion::default();
+    let b = Quaternion::from_euler(Vec3::new(0, 90, 0));
+
+    let half = Quaternion::from_euler(Vec3::new(0, 45, 0));
+    assert!(a.slerp(&b, 0.5).dot(&half).abs() > 1.0 - delta);
+    assert!(a.nlerp(&b, 0.5).dot(&half).abs() > 1.0 - delta);
+    assert!(a.slerp(&b, 0.0).dot(&a).abs() > 1.0 - delta);
+    assert!(a.slerp(&b, 1.0).dot(&b).abs() > 1.0 - delta);
+
+    //Takes the shortest path even if the quaternions are in opposite hemispheres
+    let negative = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+    assert!(a.slerp(&negative, 0.5).dot(&half).abs() > 1.0 - delta);
+
+    for q in [a, b, half, Quaternion::from_euler(Vec3::new(170, -30, 100))] {
+        let q = q.normalize();
+        assert!(Quaternion::from_rotation_matrix(&q.matrix()).dot(&q).abs() > 1.0 - delta);
+    }
+
+    assert_eq!(
+        Vec3::new(0, 2, 4).lerp(Vec3::new(2, 4, 0), 0.25),
+        Vec3::new(0.5, 2.5, 3.0)
+    );
+}
+
+#[test]
+fn test_ray() {
+    let ray = Ray::new(Vec3::new(0, 0, -5), Vec3::new(0, 0, 1));
+    assert_eq!(ray.at(2.0), Vec3::new(0, 0, -3));
+
+    let aabb = Aabb::new(Vec3::new(-1, -1, -1), Vec3::new(1, 1, 1));
+    assert_approx_eq!(ray.intersect_aabb(&aabb).unwrap(), 4.0);
+    //Starting inside of the box
+    assert_eq!(
+        Ray::new(Vec3::new(0, 0, 0), Vec3::new(1, 0, 0)).intersect_aabb(&aabb),
+        Some(0.0)
+    );
+    //Parallel to a face outside of the box, pointing away and missing it
+    assert_eq!(
+        Ray::new(Vec3::new(2, 0, -5), Vec3::new(0, 0, 1)).intersect_aabb(&aabb),
+        None
+    );
+    assert_eq!(
+        Ray::new(Vec3::new(0, 0, -5), Vec3::new(0, 0, -1)).intersect_aabb(&aabb),
+        None
+    );
+    assert_eq!(
+        Ray::new(Vec3::new(0, 0, -5), Vec3::new(1, 0, 1)).intersect_aabb(&aabb),
+        None
+    );
+
+    let triangle = [
+        Vec3::new(-1, -1, 0),
+        Vec3::new(1, -1, 0),
+        Vec3::new(0, 1, 0),
+    ];
+    assert_approx_eq!(ray.intersect_triangle(&triangle).unwrap(), 5.0);
+    //Hit from the back side
+    let back = Ray::new(Vec3::new(0, 0, 5), Vec3::new(0, 0, -1));
+    assert_approx_eq!(back.intersect_triangle(&triangle).unwrap(), 5.0);
+    assert_eq!(
+        Ray::new(Vec3::new(0.9, 0.9, -5), Vec3::new(0, 0, 1)).intersect_triangle(&triangle),
+        None
+    );
+    assert_eq!(
+        Ray::new(Vec3::new(0, 0, -5), Vec3::new(1, 0, 0)).intersect_triangle(&triangle),
+        None
+    );
+    assert_eq!(back.transformed(&Mat4x4::identity()), back);
+
+    //Distances along a transformed ray lead to the transformed points
+    let matrix = Mat4x4::transform_matrix_euler(
+        &Vec3::new(1, 2, 3),
+        &Vec3::new(2, 2, 2),
+        &Quaternion::from_euler(Vec3::new(0, 90, 0)),
+    );
+    let transformed = ray.transformed(&matrix);
+    let expected = matrix.transform3(ray.at(3.0));
+    let point = transformed.at(3.0);
+    assert_approx_eq!(point.x, expected.x, 0.0001);
+    assert_approx_eq!(point.y, expected.y, 0.0001);
+    assert_approx_eq!(point.z, expected.z, 0.0001);
+}
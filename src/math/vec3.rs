@@ -110,6 +110,13 @@ impl Vec3 {
     pub const fn less(self, rhs: Self) -> bool {
         self.x < rhs.x && self.y < rhs.y && self.z < rhs.z
     }
+
+    ///Linearly interpolates between self and `other`, returns self if `t` is 0 and `other` if `t`
+    ///is 1
+    #[must_use]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
 }
 
 impl Vector for Vec3 {
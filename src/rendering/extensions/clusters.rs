@@ -0,0 +1,335 @@
+use std::num::NonZeroU64;
+
+use crate::{
+    DEVICE, STAGING_BELT,
+    components::camera::{Camera, ProjectionType},
+    math::{Mat4x4, Vec3, Vec4, Vector as _},
+};
+
+///Number of tiles along the x and y axis of the viewport and the number of depth slices
+pub(super) const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+const CLUSTER_COUNT: usize = (CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2]) as usize;
+///Smallest near plane used for the depth slices, since they are logarithmic
+const MIN_NEAR: f32 = 0.001;
+
+///Data used by the lit shader to find the cluster of a fragment, stored after the light counts
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy, Debug)]
+pub(super) struct ClusterParams {
+    ///Viewport of the camera in pixels
+    viewport: [f32; 4],
+    position: Vec3,
+    near: f32,
+    forward: Vec3,
+    far: f32,
+    grid: [u32; 4],
+}
+
+///Camera data needed to bin the lights into clusters
+pub(super) struct ClusterView {
+    //Transposed, so it gives the same clip space as the shaders
+    matrix: Mat4x4,
+    position: Vec3,
+    forward: Vec3,
+    //Scale of the projection along the x and y axis
+    scale: (f32, f32),
+    perspective: bool,
+    near: f32,
+    far: f32,
+}
+
+impl ClusterView {
+    pub(super) fn new(camera: &Camera) -> Self {
+        Self::from_parts(
+            camera.matrix(),
+            camera.get_position(),
+            camera.view_direction(),
+            &camera.projection_type,
+            camera.aspect(),
+            camera.near,
+            camera.far,
+        )
+    }
+
+    fn from_parts(
+        matrix: Mat4x4,
+        position: Vec3,
+        forward: Vec3,
+        projection_type: &ProjectionType,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        let (scale, perspective) = match *projection_type {
+            ProjectionType::Perspective { fov } => {
+                let h = 1.0 / (fov / 2.0).tan();
+                ((h / aspect, h), true)
+            }
+            ProjectionType::Orthographic { size } => ((2.0 / (size * aspect), 2.0 / size), false),
+        };
+        let near = near.max(MIN_NEAR);
+
+        Self {
+            matrix: matrix.transpose(),
+            position,
+            forward,
+            scale,
+            perspective,
+            near,
+            far: far.max(near * 2.0),
+        }
+    }
+
+    ///Returns the parameters of the shader for a viewport in pixels
+    #[allow(clippy::cast_precision_loss)]
+    pub(super) const fn params(
+        &self,
+        (x, y, width, height): (u32, u32, u32, u32),
+    ) -> ClusterParams {
+        ClusterParams {
+            viewport: [x as f32, y as f32, width as f32, height as f32],
+            position: self.position,
+            near: self.near,
+            forward: self.forward,
+            far: self.far,
+            grid: [CLUSTER_GRID[0], CLUSTER_GRID[1], CLUSTER_GRID[2], 0],
+        }
+    }
+
+    ///Returns the depth slice of a distance from the camera
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn slice(&self, depth: f32) -> u32 {
+        let slice = (depth / self.near).log(self.far / self.near) * CLUSTER_GRID[2] as f32;
+        (slice.max(0.0) as u32).min(CLUSTER_GRID[2] - 1)
+    }
+
+    ///Returns the inclusive range of tiles along the x axis, the y axis and the depth slices
+    ///covered by a light, None if the light can't be seen
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn light_bounds(&self, position: Vec3, range: f32) -> Option<[(u32, u32); 3]> {
+        let depth = (position - self.position).dot_product(&self.forward);
+        let z_min = (depth - range).max(self.near);
+        let z_max = (depth + range).min(self.far);
+        if z_min > z_max {
+            return None;
+        }
+
+        let clip = self
+            .matrix
+            .transform(Vec4::new(position.x, position.y, position.z, 1.0));
+
+        //Conservative bounds of the light sphere in the normalized device coordinates
+        let ndc_range = |center: f32, scale: f32| {
+            let (low, high) = (range.mul_add(-scale, center), range.mul_add(scale, center));
+            if !self.perspective {
+                return (low, high);
+            }
+            //The light crosses the near plane, so it may cover the whole viewport
+            if depth - range <= self.near {
+                return (-1.0, 1.0);
+            }
+            (
+                low / if low >= 0.0 { z_max } else { z_min },
+                high / if high >= 0.0 { z_min } else { z_max },
+            )
+        };
+        let tiles = |(low, high): (f32, f32), count: u32| {
+            if high < -1.0 || low > 1.0 {
+                return None;
+            }
+            let tile = |ndc: f32| ((f32::midpoint(ndc, 1.0) * count as f32) as u32).min(count - 1);
+            Some((tile(low.max(-1.0)), tile(high.min(1.0))))
+        };
+
+        let x = tiles(ndc_range(clip.x, self.scale.0), CLUSTER_GRID[0])?;
+        //The tiles go from the top of the viewport to the bottom
+        let (low, high) = ndc_range(clip.y, self.scale.1);
+        let y = tiles((-high, -low), CLUSTER_GRID[1])?;
+
+        Some([x, y, (self.slice(z_min), self.slice(z_max))])
+    }
+
+    ///Bins the lights, given as their positions and ranges, into the clusters of the camera
+    ///
+    ///Returns the offset and the number of lights of every cluster, and the indices of the
+    ///lights of all the clusters
+    pub(super) fn bin_lights(&self, lights: &[(Vec3, f32)]) -> (Vec<[u32; 2]>, Vec<u32>) {
+        let bounds = lights
+            .iter()
+            .map(|(position, range)| self.light_bounds(*position, *range))
+            .collect::<Vec<_>>();
+
+        let mut clusters = vec![[0u32; 2]; CLUSTER_COUNT];
+        for cluster in bounds.iter().flatten().flat_map(|b| clusters_in(*b)) {
+            clusters[cluster][1] += 1;
+        }
+
+        let mut offset = 0;
+        for cluster in &mut clusters {
+            cluster[0] = offset;
+            offset += cluster[1];
+            cluster[1] = 0;
+        }
+
+        let mut indices = vec![0; offset as usize];
+        for (light, bounds) in bounds.iter().enumerate() {
+            for cluster in bounds.iter().flat_map(|b| clusters_in(*b)) {
+                let [offset, count] = &mut clusters[cluster];
+                indices[(*offset + *count) as usize] = light as u32;
+                *count += 1;
+            }
+        }
+
+        (clusters, indices)
+    }
+}
+
+///Returns the indices of the clusters inside of the bounds
+fn clusters_in([x, y, z]: [(u32, u32); 3]) -> impl Iterator<Item = usize> {
+    let [width, height, _] = CLUSTER_GRID;
+    (z.0..=z.1).flat_map(move |z| {
+        (y.0..=y.1).flat_map(move |y| {
+            (x.0..=x.1).map(move |x| (x + y * width + z * width * height) as usize)
+        })
+    })
+}
+
+///Buffers with the point lights of every cluster, used when storage buffers are available
+#[derive(Debug)]
+pub(super) struct LightClusters {
+    ///Offset and number of lights of every cluster
+    pub clusters: wgpu::Buffer,
+    ///Indices of the point lights of all the clusters
+    pub indices: wgpu::Buffer,
+}
+
+impl LightClusters {
+    pub(super) fn new() -> Self {
+        Self {
+            clusters: create_buffer(
+                "Light clusters",
+                (CLUSTER_COUNT * size_of::<[u32; 2]>()) as u64,
+            ),
+            indices: create_buffer("Light cluster indices", 1024 * size_of::<u32>() as u64),
+        }
+    }
+
+    ///Bins the point lights for the camera and uploads the clusters
+    ///
+    ///Returns true if the buffers were recreated, so the bind group needs to be recreated too
+    pub(super) fn update(
+        &mut self,
+        view: &ClusterView,
+        lights: &[(Vec3, f32)],
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> bool {
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Light clustering");
+
+        let (clusters, indices) = view.bin_lights(lights);
+
+        let size = size_of_val(indices.as_slice()) as u64;
+        let recreated = self.indices.size() < size;
+        if recreated {
+            self.indices = create_buffer("Light cluster indices", size.next_power_of_two());
+        }
+
+        let device = DEVICE.get().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            &self.clusters,
+            0,
+            NonZeroU64::new(size_of_val(clusters.as_slice()) as u64).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&clusters));
+
+        if let Some(size) = NonZeroU64::new(size) {
+            belt.write_buffer(encoder, &self.indices, 0, size, device)
+                .copy_from_slice(bytemuck::cast_slice(&indices));
+        }
+
+        recreated
+    }
+}
+
+fn create_buffer(label: &str, size: u64) -> wgpu::Buffer {
+    DEVICE
+        .get()
+        .unwrap()
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
+
+#[test]
+fn test_light_clusters() {
+    let position = Vec3::new(0.0, 0.0, 0.0);
+    let forward = Vec3::new(0.0, 0.0, 1.0);
+    let matrix = Mat4x4::look_at_matrix(position, Vec3::new(0.0, 1.0, 0.0), forward)
+        * Mat4x4::perspercive_projection(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+    let view = ClusterView::from_parts(
+        matrix,
+        position,
+        forward,
+        &ProjectionType::Perspective {
+            fov: std::f32::consts::FRAC_PI_2,
+        },
+        1.0,
+        0.1,
+        100.0,
+    );
+
+    //Slices grow exponentially with the depth
+    assert_eq!(view.slice(0.1), 0);
+    assert_eq!(view.slice(100.0), CLUSTER_GRID[2] - 1);
+    assert_eq!(view.slice(1.01), CLUSTER_GRID[2] / 3);
+
+    let lights = [
+        //Small light in the middle of the view
+        (Vec3::new(0.0, 0.0, 10.0), 0.5),
+        //Behind the camera
+        (Vec3::new(0.0, 0.0, -10.0), 1.0),
+        //Far outside of the view on the side
+        (Vec3::new(50.0, 0.0, 10.0), 1.0),
+        //Contains the camera
+        (Vec3::new(0.0, 0.0, 0.0), 5.0),
+    ];
+    assert!(view.light_bounds(lights[1].0, lights[1].1).is_none());
+    assert!(view.light_bounds(lights[2].0, lights[2].1).is_none());
+
+    let [x, y, z] = view.light_bounds(lights[0].0, lights[0].1).unwrap();
+    assert!(x.0 <= CLUSTER_GRID[0] / 2 && x.1 >= CLUSTER_GRID[0] / 2 - 1);
+    assert!(x.1 - x.0 <= 1 && y.1 - y.0 <= 1);
+    assert_eq!(z, (view.slice(9.5), view.slice(10.5)));
+
+    let (clusters, indices) = view.bin_lights(&lights);
+    assert_eq!(clusters.len(), CLUSTER_COUNT);
+
+    //The light around the camera covers every tile of the first slices
+    let lights_of = |cluster: usize| {
+        let [offset, count] = clusters[cluster];
+        &indices[offset as usize..(offset + count) as usize]
+    };
+    assert_eq!(lights_of(0), [3]);
+    let center = (x.0
+        + y.0 * CLUSTER_GRID[0]
+        + view.slice(10.0) * CLUSTER_GRID[0] * CLUSTER_GRID[1]) as usize;
+    assert_eq!(lights_of(center), [0]);
+    assert_eq!(
+        indices.iter().filter(|i| **i == 0).count(),
+        clusters_in(view.light_bounds(lights[0].0, lights[0].1).unwrap()).count()
+    );
+}
@@ -0,0 +1,710 @@
+//! Immediate mode drawing of debug shapes
+//!
+//! Shapes can be drawn from anywhere during a frame, they are collected until the [`DebugDraw`]
+//! node renders them as lines in the viewport of every camera that renders to the screen
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::math::{Vec3, Aabb};
+//!# use lunar_engine::structures::Color;
+//! use lunar_engine::rendering::extensions::debug_draw::{self, DrawOptions};
+//!
+//! debug_draw::line(Vec3::new(0, 0, 0), Vec3::new(0, 1, 0), DrawOptions::new(Color::red()));
+//! //Stays visible for 2 seconds and is drawn on top of everything
+//! debug_draw::aabb(
+//!     Aabb::new(Vec3::new(-1, -1, -1), Vec3::new(1, 1, 1)),
+//!     DrawOptions::new(Color::green()).with_duration(2.0).on_top(),
+//! );
+//! debug_draw::text(Vec3::new(0, 2, 0), "Hello", 0.5, DrawOptions::default());
+//!# debug_draw::clear();
+//!```
+use std::{num::NonZeroU64, sync::Mutex};
+
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, STAGING_BELT,
+    components::{camera::Cameras, transform::Transform},
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    math::{Aabb, Mat4x4, Vec3, Vec4, Vector as _},
+    rendering::{
+        graph::{DEPTH, NodeBuilder, NodeContext, RenderNode},
+        scene_target,
+    },
+    structures::Color,
+};
+
+///Number of segments of the circles of a sphere
+const CIRCLE_SEGMENTS: u32 = 24;
+
+///Strokes of the characters of the labels, in a grid 4 units wide and 6 units tall
+///
+///Every pair of digits is a point, the points of a stroke are connected with lines and the strokes
+///are separated by spaces
+const GLYPHS: [(char, &str); 60] = [
+    ('0', "0040460600 0046"),
+    ('1', "152620 1030"),
+    ('2', "0646430040"),
+    ('3', "06464000 1343"),
+    ('4', "060343 3630"),
+    ('5', "4606043443413000"),
+    ('6', "460600404303"),
+    ('7', "064620"),
+    ('8', "0040460600 0343"),
+    ('9', "430306464000"),
+    ('A', "002640 1333"),
+    ('B', "00063645443303 3342413000"),
+    ('C', "46060040"),
+    ('D', "00062644422000"),
+    ('E', "46060040 0333"),
+    ('F', "460600 0333"),
+    ('G', "460600404323"),
+    ('H', "0006 4046 0343"),
+    ('I', "0646 2620 0040"),
+    ('J', "0646 36300002"),
+    ('K', "0006 460340"),
+    ('L', "060040"),
+    ('M', "0006234640"),
+    ('N', "00064046"),
+    ('O', "0040460600"),
+    ('P', "0006464303"),
+    ('Q', "0040460600 2240"),
+    ('R', "0006464303 1340"),
+    ('S', "460603434000"),
+    ('T', "0646 2620"),
+    ('U', "06004046"),
+    ('V', "062046"),
+    ('W', "0610233046"),
+    ('X', "0046 0640"),
+    ('Y', "062346 2320"),
+    ('Z', "06460040"),
+    ('.', "2021"),
+    (',', "2110"),
+    (':', "2122 2425"),
+    (';', "2110 2425"),
+    ('-', "1333"),
+    ('+', "1333 2125"),
+    ('_', "0040"),
+    ('/', "0046"),
+    ('\\', "0640"),
+    ('(', "36252130"),
+    (')', "16252110"),
+    ('[', "36161030"),
+    (']', "16363010"),
+    ('<', "350331"),
+    ('>', "154311"),
+    ('=', "0242 0444"),
+    ('!', "2622 2021"),
+    ('?', "05163645442322 2021"),
+    ('\'', "2624"),
+    ('"', "1614 3634"),
+    ('%', "0046 0506 4041"),
+    ('#', "1115 3135 0242 0444"),
+    ('*', "1335 1533 2125"),
+    ('|', "2026"),
+];
+///Horizontal distance between the characters, in glyph units
+const GLYPH_ADVANCE: f32 = 6.0;
+///Vertical distance between the lines, in glyph units
+const LINE_HEIGHT: f32 = 9.0;
+///Height of the capital letters, in glyph units
+const GLYPH_HEIGHT: f32 = 6.0;
+
+///Appearance and lifetime of a debug shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawOptions {
+    ///Color of the lines
+    pub color: Color,
+    ///If false the shape is drawn on top of everything
+    pub depth_test: bool,
+    ///How long the shape stays visible in seconds, 0 draws it for a single frame
+    pub duration: f32,
+}
+
+impl Default for DrawOptions {
+    ///White, depth tested, drawn for a single frame
+    fn default() -> Self {
+        Self::new(Color::white())
+    }
+}
+
+impl DrawOptions {
+    ///Creates new options with the color, the shape is depth tested and drawn for a single frame
+    #[must_use]
+    pub const fn new(color: Color) -> Self {
+        Self {
+            color,
+            depth_test: true,
+            duration: 0.0,
+        }
+    }
+
+    ///Keeps the shape visible for the duration in seconds
+    #[must_use]
+    pub const fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    ///Draws the shape on top of everything, ignoring the depth buffer
+    #[must_use]
+    pub const fn on_top(mut self) -> Self {
+        self.depth_test = false;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: Vec3,
+    color: Color,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: Vec3,
+    end: Vec3,
+    options: DrawOptions,
+}
+
+///Text that always faces the camera
+#[derive(Debug, Clone)]
+struct Label {
+    position: Vec3,
+    //Strokes of the text, relative to the position and already scaled
+    strokes: Vec<[f32; 4]>,
+    options: DrawOptions,
+}
+
+///Shapes that are waiting to be drawn
+struct DebugState {
+    segments: Vec<Segment>,
+    labels: Vec<Label>,
+}
+
+impl DebugState {
+    const fn new() -> Self {
+        Self {
+            segments: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    ///Removes the shapes whose time ran out after the frame
+    fn advance(&mut self, delta: f32) {
+        self.segments.retain_mut(|s| {
+            s.options.duration -= delta;
+            s.options.duration > 0.0
+        });
+        self.labels.retain_mut(|l| {
+            l.options.duration -= delta;
+            l.options.duration > 0.0
+        });
+    }
+}
+
+static DEBUG_STATE: Mutex<DebugState> = Mutex::new(DebugState::new());
+
+fn add_lines(lines: impl IntoIterator<Item = (Vec3, Vec3)>, options: DrawOptions) {
+    DEBUG_STATE
+        .lock()
+        .unwrap()
+        .segments
+        .extend(lines.into_iter().map(|(start, end)| Segment {
+            start,
+            end,
+            options,
+        }));
+}
+
+///Draws a line between two points
+pub fn line(start: Vec3, end: Vec3, options: DrawOptions) {
+    add_lines([(start, end)], options);
+}
+
+///Draws a line from the origin along the direction, the length of the line is the length of the
+///direction
+pub fn ray(origin: Vec3, direction: Vec3, options: DrawOptions) {
+    line(origin, origin + direction, options);
+}
+
+///Draws the edges of a bounding box
+pub fn aabb(aabb: Aabb, options: DrawOptions) {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+            if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+            if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+        )
+    };
+    add_lines(box_edges().map(|(a, b)| (corner(a), corner(b))), options);
+}
+
+///Draws a sphere as three circles around its axes
+pub fn sphere(center: Vec3, radius: f32, options: DrawOptions) {
+    add_lines(sphere_lines(center, radius), options);
+}
+
+///Draws the edges of the volume seen through a view projection matrix, for example the one
+///returned by [`Camera::matrix`](crate::components::camera::Camera::matrix)
+pub fn frustum(view_projection: Mat4x4, options: DrawOptions) {
+    let Some(corners) = frustum_corners(view_projection) else {
+        log::warn!("Can't draw a frustum of a matrix that can't be inverted");
+        return;
+    };
+    add_lines(box_edges().map(|(a, b)| (corners[a], corners[b])), options);
+}
+
+///Draws the local axes of a transform, x in red, y in green and z in blue
+///
+///The color of the options is ignored
+pub fn axes(transform: &Transform, size: f32, options: DrawOptions) {
+    let matrix = transform.matrix();
+    let origin = matrix.transform3(Vec3::new(0, 0, 0));
+    let axis = |direction: Vec3, color: Color| {
+        let end = origin + (matrix.transform3(direction) - origin).normalized() * size;
+        line(origin, end, DrawOptions { color, ..options });
+    };
+    axis(Vec3::new(1, 0, 0), Color::red());
+    axis(Vec3::new(0, 1, 0), Color::green());
+    axis(Vec3::new(0, 0, 1), Color::blue());
+}
+
+///Draws text that always faces the camera, centered above the position
+///
+///The size is the height of the capital letters in world units. Only ascii letters, numbers and
+///basic punctuation are supported, lowercase letters are drawn as capital ones
+pub fn text(position: Vec3, text: &str, size: f32, options: DrawOptions) {
+    let strokes = text_strokes(text, size);
+    DEBUG_STATE.lock().unwrap().labels.push(Label {
+        position,
+        strokes,
+        options,
+    });
+}
+
+///Removes all of the shapes, including the ones that have time left
+pub fn clear() {
+    let mut state = DEBUG_STATE.lock().unwrap();
+    state.segments.clear();
+    state.labels.clear();
+}
+
+///Pairs of the corners of a box that are connected by an edge, the bits of the index of a corner
+///choose between the smallest and the largest x, y and z
+fn box_edges() -> impl Iterator<Item = (usize, usize)> {
+    (0..8).flat_map(|a: usize| {
+        [1, 2, 4]
+            .into_iter()
+            .filter(move |bit| a & bit == 0)
+            .map(move |bit| (a, a | bit))
+    })
+}
+
+fn sphere_lines(center: Vec3, radius: f32) -> Vec<(Vec3, Vec3)> {
+    let point = |axis: usize, i: u32| {
+        let angle = std::f32::consts::TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+        let (sin, cos) = angle.sin_cos();
+        let (sin, cos) = (sin * radius, cos * radius);
+        center
+            + match axis {
+                0 => Vec3::new(0.0, cos, sin),
+                1 => Vec3::new(cos, 0.0, sin),
+                _ => Vec3::new(cos, sin, 0.0),
+            }
+    };
+    (0..3)
+        .flat_map(|axis| (0..CIRCLE_SEGMENTS).map(move |i| (point(axis, i), point(axis, i + 1))))
+        .collect()
+}
+
+///Returns the corners of the volume seen through the matrix, in the same order as the corners of a
+///box
+fn frustum_corners(view_projection: Mat4x4) -> Option<[Vec3; 8]> {
+    //The shaders use the transposed matrix
+    let inverse = view_projection.transpose().inverted()?;
+    Some(std::array::from_fn(|i| {
+        let ndc = Vec4::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { 0.0 } else { 1.0 },
+            1.0,
+        );
+        let point = inverse.transform(ndc);
+        Vec3::new(point.x, point.y, point.z) / point.w
+    }))
+}
+
+///Returns the strokes of the text as lines from x, y to z, w, with the origin at the middle of the
+///baseline of the first line
+fn text_strokes(text: &str, size: f32) -> Vec<[f32; 4]> {
+    let scale = size / GLYPH_HEIGHT;
+    let columns = text
+        .lines()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or_default();
+    //The last character is only 4 units wide
+    let width = (columns as f32).mul_add(GLYPH_ADVANCE, 4.0 - GLYPH_ADVANCE);
+
+    let mut strokes = Vec::new();
+    for (row, line) in text.lines().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let c = c.to_ascii_uppercase();
+            let glyph = GLYPHS
+                .iter()
+                .find(|g| g.0 == c)
+                .map_or("05163645442322 2021", |g| g.1);
+            let origin = (
+                (column as f32).mul_add(GLYPH_ADVANCE, -width / 2.0),
+                -(row as f32) * LINE_HEIGHT,
+            );
+
+            for stroke in glyph.split(' ') {
+                let points = stroke
+                    .as_bytes()
+                    .chunks_exact(2)
+                    .map(|p| {
+                        (
+                            (origin.0 + f32::from(p[0] - b'0')) * scale,
+                            (origin.1 + f32::from(p[1] - b'0')) * scale,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                strokes.extend(points.windows(2).map(|p| [p[0].0, p[0].1, p[1].0, p[1].1]));
+            }
+        }
+    }
+    strokes
+}
+
+///Renders the shapes of the [`debug_draw`](self) functions in the viewport of every camera that
+///renders to the screen
+///
+///The shapes are drawn over the scene, so the priority of the node should be larger than the
+///priority of the [`Base`](super::Base) node. Shapes are only removed when this node runs, or with
+///[`clear`]
+///
+///# Usage
+///```
+///# use lunar_engine::rendering::extensions::{Base, debug_draw::DebugDraw};
+///# use lunar_engine::rendering::render;
+///# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, debug: DebugDraw}
+///fn update(state: &mut State) {
+/// render(
+///   &state.world,
+///   &mut state.assets,
+///   &mut [&mut state.base, &mut state.debug]
+///  );
+///}
+///```
+pub struct DebugDraw {
+    ///Priority of the extension
+    pub priority: u32,
+    ///Pipelines with and without the depth test
+    pipelines: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    vertex_buffer: Option<wgpu::Buffer>,
+}
+
+impl Default for DebugDraw {
+    ///Creates a new [`DebugDraw`] with a priority of 10
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl DebugDraw {
+    ///Creates a new [`DebugDraw`]
+    #[must_use]
+    pub const fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            pipelines: None,
+            vertex_buffer: None,
+        }
+    }
+
+    fn create_pipeline(depth_test: bool) -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/debug_draw.wgsl"));
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug draw"),
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug draw"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<LineVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: if depth_test {
+                    wgpu::CompareFunction::LessEqual
+                } else {
+                    wgpu::CompareFunction::Always
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: crate::rendering::scene_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+///Appends the vertices of the lines that use or skip the depth test, returns the range of the
+///vertices
+fn push_lines(
+    vertices: &mut Vec<LineVertex>,
+    lines: impl Iterator<Item = (Vec3, Vec3, DrawOptions)>,
+    depth_test: bool,
+) -> std::ops::Range<u32> {
+    let start = vertices.len() as u32;
+    for (start, end, options) in lines.filter(|l| l.2.depth_test == depth_test) {
+        vertices.push(LineVertex {
+            position: start,
+            color: options.color,
+        });
+        vertices.push(LineVertex {
+            position: end,
+            color: options.color,
+        });
+    }
+    start..vertices.len() as u32
+}
+
+impl RenderNode for DebugDraw {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.write(scene_target());
+        builder.read(DEPTH);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        let color = context.texture(scene_target());
+        let depth_stencil = context.texture(DEPTH);
+        let encoder = &mut *context.encoder;
+
+        let mut state = DEBUG_STATE.lock().unwrap();
+        if state.segments.is_empty() && state.labels.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Debug draw");
+
+        let cameras = Cameras::new(context.world);
+        let cameras: Vec<_> = cameras
+            .borrow()
+            .into_iter()
+            .filter(|c| c.target.is_none())
+            .collect();
+
+        //The lines are shared by all the cameras, the labels face every camera
+        let mut vertices = Vec::new();
+        let segments = || state.segments.iter().map(|s| (s.start, s.end, s.options));
+        let lines = [
+            push_lines(&mut vertices, segments(), true),
+            push_lines(&mut vertices, segments(), false),
+        ];
+        let labels = cameras
+            .iter()
+            .map(|camera| {
+                let matrix = camera.matrix();
+                let right = Vec3::new(matrix.m00, matrix.m10, matrix.m20).normalized();
+                let up = Vec3::new(matrix.m01, matrix.m11, matrix.m21).normalized();
+                let strokes = || {
+                    state.labels.iter().flat_map(move |l| {
+                        l.strokes.iter().map(move |s| {
+                            (
+                                l.position + right * s[0] + up * s[1],
+                                l.position + right * s[2] + up * s[3],
+                                l.options,
+                            )
+                        })
+                    })
+                };
+                [
+                    push_lines(&mut vertices, strokes(), true),
+                    push_lines(&mut vertices, strokes(), false),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        state.advance(crate::delta_time());
+        drop(state);
+
+        if cameras.is_empty() || vertices.is_empty() {
+            return;
+        }
+        for camera in &cameras {
+            camera.update_gpu(encoder);
+        }
+
+        let device = DEVICE.get().unwrap();
+        let size = size_of_val(vertices.as_slice()) as u64;
+        if self.vertex_buffer.as_ref().is_none_or(|b| b.size() < size) {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Debug draw vertices"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = self.vertex_buffer.as_ref().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            vertex_buffer,
+            0,
+            NonZeroU64::new(size).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&vertices));
+        drop(belt);
+
+        let (depth_pipeline, overlay_pipeline) = &*self
+            .pipelines
+            .get_or_insert_with(|| (Self::create_pipeline(true), Self::create_pipeline(false)));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug draw pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_stencil,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+        for (camera, labels) in cameras.iter().zip(labels) {
+            let (x, y, width, height) = camera.viewport.pixels(camera.target_size());
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            camera.set_bindgroup(&mut render_pass);
+
+            for (range, pipeline) in lines
+                .iter()
+                .chain(&labels)
+                .zip([depth_pipeline, overlay_pipeline].iter().cycle())
+            {
+                if !range.is_empty() {
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.draw(range.clone(), 0..1);
+                }
+            }
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_debug_shapes() {
+    use crate::math::Vector as _;
+
+    //Every edge of a box connects corners that differ in one coordinate
+    let edges = box_edges().collect::<Vec<_>>();
+    assert_eq!(edges.len(), 12);
+    assert!(edges.iter().all(|(a, b)| (a ^ b).count_ones() == 1));
+
+    let lines = sphere_lines(Vec3::new(1, 2, 3), 2.0);
+    assert_eq!(lines.len(), 3 * CIRCLE_SEGMENTS as usize);
+    assert!(
+        lines
+            .iter()
+            .all(|(a, _)| ((*a - Vec3::new(1, 2, 3)).length() - 2.0).abs() < 1e-4)
+    );
+
+    //The sides of an orthographic volume are the sides of the projection
+    let matrix = Mat4x4::orth_projection(-1.0, 1.0, -2.0, 2.0, 0.0, 10.0);
+    let bounds = Aabb::from_points(frustum_corners(matrix).unwrap()).unwrap();
+    assert!((bounds.size().x - 4.0).abs() < 1e-3);
+    assert!((bounds.size().y - 2.0).abs() < 1e-3);
+
+    //Text is centered and lowercase letters are drawn as capital ones
+    let strokes = text_strokes("a b", 6.0);
+    assert_eq!(strokes.len(), text_strokes("AB", 6.0).len());
+    let min_x = strokes
+        .iter()
+        .map(|s| s[0].min(s[2]))
+        .fold(f32::MAX, f32::min);
+    let max_x = strokes
+        .iter()
+        .map(|s| s[0].max(s[2]))
+        .fold(f32::MIN, f32::max);
+    assert!((min_x + max_x).abs() < 1e-4);
+    assert!((max_x - min_x - 16.0).abs() < 1e-4);
+
+    //Unknown characters are drawn as question marks, spaces are empty
+    assert_eq!(text_strokes("~", 6.0), text_strokes("?", 6.0));
+    assert!(text_strokes(" ", 1.0).is_empty());
+
+    let mut state = DebugState::new();
+    let options = DrawOptions::new(Color::red());
+    state.segments.push(Segment {
+        start: Vec3::default(),
+        end: Vec3::default(),
+        options,
+    });
+    state.segments.push(Segment {
+        start: Vec3::default(),
+        end: Vec3::default(),
+        options: options.with_duration(1.0),
+    });
+    //Shapes without a duration are only drawn once
+    state.advance(0.5);
+    assert_eq!(state.segments.len(), 1);
+    state.advance(0.6);
+    assert!(state.segments.is_empty());
+}
@@ -3,88 +3,230 @@
 use core::f32;
 use std::{cell::OnceCell, num::NonZeroU64};
 
+use clusters::{ClusterParams, ClusterView, LightClusters};
 use log::{debug, trace};
+use shadows::{SpotShadows, shadow_indices};
 use vec_key_value_pair::set::VecSet;
-use wgpu::{BufferUsages, util::DeviceExt};
+use wgpu::{BufferUsages, include_wgsl, util::DeviceExt};
 
 use crate::{
-    DEVICE, RESOLUTION, STAGING_BELT,
+    DEVICE, RESOLUTION, STAGING_BELT, UUID,
     asset_managment::AssetStore,
+    assets::Texture,
+    assets::skeleton::MAX_JOINTS,
     assets::{BindgroupState, Material, Mesh, materials::helpers::storage_buffer_available},
     components::{
         self,
-        light::{DirectionalLight, PointLight},
+        animator::{Animator, AnimatorGpu},
+        camera::{Camera, Cameras, ClearMode},
+        light::{DirectionalLight, PointLight, SpotLight},
     },
     ecs::{ComponentReference, World},
-    grimoire::{self, point_light_bind_group_layout_descriptor},
+    grimoire::{self, MAX_UNIFORM_SPOT_LIGHTS, point_light_bind_group_layout_descriptor},
     math::{Mat4x4, Vec3, Vec4, Vec4Swizzles, Vector as _},
-    structures::{Color, LightBuffer},
+    rendering::{
+        HDR_COLOR, HDR_FORMAT,
+        graph::{
+            DEPTH, NodeBuilder, NodeContext, RenderNode, SURFACE, TextureDescription, TextureSize,
+        },
+        is_hdr, scene_format, scene_target,
+    },
+    structures::{Color, LightBuffer, SpotLightBuffer},
 };
 
+mod clusters;
+pub mod debug_draw;
+pub mod particles;
+pub mod picking;
+///Hdr post processing effects
+pub mod post_processing;
 ///Screenshot stuff
 pub mod screenshot;
+mod shadows;
+///Cubemap skybox rendering
+pub mod skybox;
+pub mod sprites;
+pub mod text;
+pub mod ui;
 
-///A color buffer and a depth stencil buffer
-pub struct AttachmentData {
-    ///Color buffer
-    pub color: wgpu::TextureView,
-    ///Depth stencil buffer
-    pub depth_stencil: wgpu::TextureView,
-}
+///Maximum number of spot lights that cast shadows, the other lights don't cast shadows
+pub const MAX_SPOT_SHADOWS: u32 = 4;
 
-///Trait that all rendering extensions must implement
-///
-///Allows for extending the renderer
-pub trait RenderingExtension {
-    ///Uses the extension to render scene
-    fn render(
-        &mut self,
-        encoder: &mut wgpu::CommandEncoder,
-        world: &World,
-        assets: &mut AssetStore,
-        attachments: &AttachmentData,
-    );
+#[derive(Debug)]
+struct PointLights {
+    buffer: wgpu::Buffer,
+    count_buf: wgpu::Buffer,
+    num_lights: usize,
+    spot_buffer: wgpu::Buffer,
+    num_spot_lights: usize,
+    //Only used with storage buffers, otherwise every fragment loops over all the point lights
+    clusters: Option<LightClusters>,
+    bindgroup: wgpu::BindGroup,
+}
 
-    ///Allows the extension to do some work after all the extensions have been rendered, but before
-    ///the frame buffer is swapped
-    #[allow(unused)]
-    fn post_render(&mut self, attachments: &AttachmentData) {}
+///Creates the bind group of the point and spot lights
+fn light_bind_group(
+    clusters: Option<&LightClusters>,
+    count: &wgpu::Buffer,
+    point_lights: &wgpu::Buffer,
+    spot_lights: &wgpu::Buffer,
+    shadows: &SpotShadows,
+) -> wgpu::BindGroup {
+    let device = DEVICE.get().unwrap();
+    let layout = device.create_bind_group_layout(&point_light_bind_group_layout_descriptor(
+        clusters.is_some(),
+    ));
+
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: count.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 1,
+            resource: point_lights.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 2,
+            resource: spot_lights.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 3,
+            resource: wgpu::BindingResource::TextureView(&shadows.array_view),
+        },
+        wgpu::BindGroupEntry {
+            binding: 4,
+            resource: wgpu::BindingResource::Sampler(&shadows.sampler),
+        },
+    ];
+    if let Some(clusters) = clusters {
+        entries.extend([
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: clusters.clusters.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: clusters.indices.as_entire_binding(),
+            },
+        ]);
+    }
 
-    ///Returns the priority of the extension, extensions with smaller priorities are rendered first.
-    fn get_priority(&self) -> u32;
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Point lights bind group"),
+        layout: &layout,
+        entries: &entries,
+    })
 }
 
-impl std::cmp::PartialEq for dyn RenderingExtension {
-    fn eq(&self, other: &Self) -> bool {
-        self.get_priority().eq(&other.get_priority())
-    }
+///Instanced meshes of a camera, reused while the visible meshes don't change
+#[derive(Default)]
+struct CameraCache {
+    //Stores vector of ((mesh_id, lod), material_id) for caching
+    identifier: Vec<((u128, usize), u128)>,
+    v_buffers: Vec<wgpu::Buffer>,
+    mesh_materials: Vec<MeshMaterial>,
+    num_instances: Vec<usize>,
+    mesh_refs: Vec<Vec<ComponentReference<components::mesh::Mesh>>>,
+    //Matrices of the transparent meshes, in the order they are rendered in
+    transparent_buffer: Option<wgpu::Buffer>,
 }
 
-impl std::cmp::Eq for dyn RenderingExtension {}
+///Draw of the transparent queue
+#[derive(Clone, Copy)]
+enum TransparentDraw {
+    ///A mesh, with the index of its matrix in the transparent instance buffer
+    Mesh(MeshMaterial, u32),
+    ///An animated mesh, with the index of its animator
+    Animated(usize),
+}
 
-impl std::cmp::PartialOrd for dyn RenderingExtension {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
+///Pipelines that clear the viewport of a camera by drawing a fullscreen triangle on the far plane
+struct ClearPipelines {
+    ///Clears the color to the blend constant and the depth
+    color: wgpu::RenderPipeline,
+    ///Only clears the depth
+    depth: wgpu::RenderPipeline,
 }
 
-impl std::cmp::Ord for dyn RenderingExtension {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.get_priority().cmp(&other.get_priority())
+impl ClearPipelines {
+    fn new() -> Self {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/clear.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Viewport clear"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        //The color is replaced with the blend constant
+        let constant = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::Zero,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        let create_pipeline = |write_mask| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Viewport clear"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: scene_format(),
+                        blend: Some(wgpu::BlendState {
+                            color: constant,
+                            alpha: constant,
+                        }),
+                        write_mask,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        Self {
+            color: create_pipeline(wgpu::ColorWrites::ALL),
+            depth: create_pipeline(wgpu::ColorWrites::empty()),
+        }
     }
 }
 
-#[derive(Debug)]
-struct PointLights {
-    buffer: wgpu::Buffer,
-    count_buf: wgpu::Buffer,
-    num_lights: usize,
-    bindgroup: wgpu::BindGroup,
+///Attachments a camera renders into
+struct CameraTarget {
+    color: wgpu::TextureView,
+    depth: wgpu::TextureView,
+    size: (u32, u32),
+    ///Whether this is the first camera that renders into the target this frame
+    first: bool,
 }
 
 #[derive(Default)]
 ///Basic renderer that renders all [`crate::components::mesh::Mesh`] components
 ///
+///Renders into the surface, or into the [`HDR_COLOR`] texture if hdr rendering is enabled using
+///[`crate::rendering::set_hdr`]
+///
+///Every [`Camera`] and [`components::camera::MainCamera`] in the world is rendered, into its
+///viewport of either the screen or its target texture
+///
 ///# Usage
 ///```
 ///# use lunar_engine::rendering::extensions::Base;
@@ -110,14 +252,17 @@ pub struct Base {
     pub clear_color: Color,
     ///Whether or not to use frustum culling
     pub frustum_culling: bool,
-    //Stores vector of (mesh_id, material_id) for caching
-    identifier: Vec<(u128, u128)>,
-    v_buffers: Vec<wgpu::Buffer>,
-    mesh_materials: Vec<MeshMaterial>,
-    num_instances: Vec<usize>,
-    mesh_refs: Vec<Vec<ComponentReference<components::mesh::Mesh>>>,
+    ///How far past the switching screen size a mesh has to be for its level of detail to change,
+    ///relative to that size. Prevents meshes from switching back and forth between levels
+    pub lod_hysteresis: f32,
+    //Instancing cache of every camera, in the order the cameras are rendered in
+    caches: Vec<CameraCache>,
+    //Depth textures of the target textures of cameras
+    target_depths: Vec<(UUID, (u32, u32), wgpu::TextureView)>,
+    clear_pipelines: OnceCell<ClearPipelines>,
     light_buffer: OnceCell<(wgpu::Buffer, wgpu::BindGroup)>,
     point_light_buffer: OnceCell<PointLights>,
+    shadows: OnceCell<SpotShadows>,
     storage_buffer_available: OnceCell<bool>,
 }
 
@@ -127,6 +272,7 @@ impl Base {
     pub const fn new(order: u32, frustum_culling: bool) -> Self {
         Self {
             frustum_culling,
+            lod_hysteresis: 0.1,
             priority: order,
             clear_color: Color {
                 r: 0.0,
@@ -134,13 +280,12 @@ impl Base {
                 b: 0.0,
                 a: 1.0,
             },
-            identifier: Vec::new(),
-            v_buffers: Vec::new(),
-            mesh_materials: Vec::new(),
-            num_instances: Vec::new(),
-            mesh_refs: Vec::new(),
+            caches: Vec::new(),
+            target_depths: Vec::new(),
+            clear_pipelines: OnceCell::new(),
             light_buffer: OnceCell::new(),
             point_light_buffer: OnceCell::new(),
+            shadows: OnceCell::new(),
             storage_buffer_available: OnceCell::new(),
         }
     }
@@ -154,76 +299,33 @@ impl Base {
     pub const fn new_with_color(order: u32, frustum_culling: bool, color: Color) -> Self {
         Self {
             frustum_culling,
+            lod_hysteresis: 0.1,
             priority: order,
             clear_color: color,
-            identifier: Vec::new(),
-            v_buffers: Vec::new(),
-            mesh_materials: Vec::new(),
-            num_instances: Vec::new(),
-            mesh_refs: Vec::new(),
+            caches: Vec::new(),
+            target_depths: Vec::new(),
+            clear_pipelines: OnceCell::new(),
             light_buffer: OnceCell::new(),
             point_light_buffer: OnceCell::new(),
+            shadows: OnceCell::new(),
             storage_buffer_available: OnceCell::new(),
         }
     }
-}
 
-#[derive(Clone, Copy)]
-struct MeshMaterial {
-    mesh_id: u128,
-    material_id: u128,
-}
-
-impl PartialEq<(u128, u128)> for MeshMaterial {
-    fn eq(&self, other: &(u128, u128)) -> bool {
-        self.mesh_id == other.0 && self.material_id == other.1
-    }
-}
-
-impl MeshMaterial {
-    const fn new(mesh_id: u128, material_id: u128) -> Self {
-        Self {
-            mesh_id,
-            material_id,
-        }
-    }
-}
-
-impl RenderingExtension for Base {
+    ///Renders the meshes seen by the camera into its target
     #[allow(clippy::cognitive_complexity)]
-    fn render(
+    fn render_camera(
         &mut self,
+        index: usize,
+        camera: &Camera,
+        target: &CameraTarget,
         encoder: &mut wgpu::CommandEncoder,
         world: &World,
         assets: &mut AssetStore,
-        attachments: &AttachmentData,
     ) {
-        //Initialize needed stuff
-        if self.storage_buffer_available.get().is_none() {
-            let storage_buf_available = storage_buffer_available();
-            self.storage_buffer_available
-                .set(storage_buf_available)
-                .unwrap();
-
-            if storage_buf_available {
-                log::info!("Storage buffer supported");
-            } else {
-                log::info!("Storage buffer not supported");
-            }
-        }
-
-        #[cfg(feature = "tracy")]
-        let _span = tracy_client::span!("Frustum culling render");
-        trace!("Started frame");
-
         //Update camera first
-        let binding = world
-            .get_all_components::<components::camera::MainCamera>()
-            .expect("Could not find the main camera");
-
-        let camera = binding.first().unwrap().borrow();
         camera.update_gpu(encoder);
-        trace!("Accquired camera");
+        let mut cache = std::mem::take(&mut self.caches[index]);
 
         //This is cached, so should be reasonably fast
         let binding = world
@@ -235,9 +337,10 @@ impl RenderingExtension for Base {
 
         let meshes = if self.frustum_culling {
             let frustum = calculate_frustum(
-                camera.inner.near,
-                camera.inner.far,
-                camera.inner.projection_type.fov().unwrap_or_default(),
+                camera.near,
+                camera.far,
+                camera.projection_type.fov().unwrap_or_default(),
+                camera.aspect(),
             );
             let camera_transform = camera.camera_transform();
             //Precompute the transformation matrix, since it's the same for all the objects
@@ -250,6 +353,7 @@ impl RenderingExtension for Base {
                     let t = binding.borrow();
 
                     m.get_visible()
+                        && !m.is_animated()
                         && check_frustum(
                             frustum.z,
                             matrix,
@@ -266,23 +370,37 @@ impl RenderingExtension for Base {
         } else {
             binding
                 .iter()
-                .filter(|i| i.borrow().get_visible())
+                .filter(|i| {
+                    let m = i.borrow();
+                    m.get_visible() && !m.is_animated()
+                })
                 .collect::<Vec<_>>()
         };
         #[cfg(feature = "tracy")]
         drop(_frustum_span);
         trace!("Got all the meshes");
 
+        //Transparent meshes are not instanced, they are sorted and rendered after the opaque ones
+        let (meshes, transparent_meshes): (Vec<_>, Vec<_>) = meshes
+            .into_iter()
+            .partition(|m| !is_transparent(&m.borrow(), assets));
+
         //List of materials used for rendering
         let mut materials = VecSet::new();
-        //List of (mesh_ID, (transformation matrix, material_id))
+        //List of ((mesh_ID, lod), (transformation matrix, material_id))
         let mut matrices = Vec::new();
 
-        //Collect all the matrices
+        //Collect all the matrices and pick the level of detail of every mesh
         for m in &meshes {
-            let m = m.borrow();
+            let mut m = m.borrow_mut();
+            let mesh_id = m.get_mesh_id().unwrap();
+            let mesh = assets.borrow_by_id::<Mesh>(mesh_id).unwrap();
+
+            let lod = self.select_lod(camera, &mut m, &mesh);
+            drop(mesh);
+
             materials.insert(m.get_material_id().unwrap());
-            matrices.push((m.get_mesh_id().unwrap(), (m.get_material_id().unwrap())));
+            matrices.push(((mesh_id, lod), (m.get_material_id().unwrap())));
         }
 
         //What is even going on here?
@@ -299,8 +417,8 @@ impl RenderingExtension for Base {
         #[cfg(feature = "tracy")]
         let _cache_check_span = tracy_client::span!("Cache reuse check");
 
-        if matrices.len() == self.identifier.len() {
-            for (index, data) in self.identifier.iter().enumerate() {
+        if matrices.len() == cache.identifier.len() {
+            for (index, data) in cache.identifier.iter().enumerate() {
                 if data.0 == matrices[index].0 && data.1 == matrices[index].1.0 {
                     continue;
                 }
@@ -320,7 +438,7 @@ impl RenderingExtension for Base {
             let _span = tracy_client::span!("Cache generation");
 
             debug!("Generating new cache data");
-            self.identifier = matrices.iter().map(|i| (i.0, i.1.0)).collect::<Vec<_>>();
+            cache.identifier = matrices.iter().map(|i| (i.0, i.1.0)).collect::<Vec<_>>();
 
             //Sort meshes by mesh id for easier buffer creation
             //NO Sort by material id?
@@ -329,13 +447,13 @@ impl RenderingExtension for Base {
             //This is so jank omg
             //Yea... i agree
 
-            //Find points where mesh changes
+            //Find points where mesh or its level of detail changes
             let mut split_points = Vec::new();
-            let mut old = 0;
+            let mut old = None;
             for (index, m) in matrices.iter().enumerate() {
-                if m.0 != old {
+                if Some(m.0) != old {
                     split_points.push(index);
-                    old = m.0;
+                    old = Some(m.0);
                 }
             }
 
@@ -380,6 +498,7 @@ impl RenderingExtension for Base {
 
                 let mut last = MeshMaterial {
                     mesh_id: 0,
+                    lod: 0,
                     material_id: 0,
                 };
 
@@ -446,10 +565,10 @@ impl RenderingExtension for Base {
                 "You are an idiot, they're not the same"
             );
 
-            self.v_buffers = v_buffers;
-            self.mesh_materials = mesh_materials;
-            self.num_instances = num_instances;
-            self.mesh_refs = mesh_refs;
+            cache.v_buffers = v_buffers;
+            cache.mesh_materials = mesh_materials;
+            cache.num_instances = num_instances;
+            cache.mesh_refs = mesh_refs;
         } else {
             #[cfg(feature = "tracy")]
             let _span = tracy_client::span!("Cache reuse");
@@ -459,7 +578,7 @@ impl RenderingExtension for Base {
             let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
             let device = DEVICE.get().unwrap();
 
-            for (buffer, meshes) in self.v_buffers.iter().zip(self.mesh_refs.iter()) {
+            for (buffer, meshes) in cache.v_buffers.iter().zip(cache.mesh_refs.iter()) {
                 //I do have to collect here
                 let matrices = meshes
                     .iter()
@@ -483,6 +602,39 @@ impl RenderingExtension for Base {
             }
         }
 
+        //Animated meshes are rendered individually, since every one of them has its own pose
+        let animators = world
+            .get_all_components::<Animator>()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|a| {
+                a.borrow()
+                    .get_mesh()
+                    .is_some_and(|m| m.borrow().get_visible())
+            })
+            .collect::<Vec<_>>();
+
+        for a in &animators {
+            update_animator(&mut a.borrow_mut(), camera, encoder, assets);
+            let material = a.borrow().get_mesh().unwrap().borrow().get_material_id();
+            materials.insert(material.unwrap());
+        }
+        let (animators, transparent_animators): (Vec<_>, Vec<_>) = animators
+            .into_iter()
+            .partition(|a| !is_transparent(&a.borrow().get_mesh().unwrap().borrow(), assets));
+
+        for m in &transparent_meshes {
+            materials.insert(m.borrow().get_material_id().unwrap());
+        }
+        let transparent_draws = self.sort_transparent(
+            camera,
+            &transparent_meshes,
+            &transparent_animators,
+            &mut cache,
+            encoder,
+            assets,
+        );
+
         trace!("Initializing the bindgroups");
 
         let mut is_lit = false;
@@ -494,10 +646,10 @@ impl RenderingExtension for Base {
 
             is_lit = is_lit || m.is_lit();
 
-            if matches!(m.get_bindgroup_state(), BindgroupState::Initialized) {
-                continue;
+            if !matches!(m.get_bindgroup_state(), BindgroupState::Initialized) {
+                m.initialize_bindgroups(assets);
             }
-            m.initialize_bindgroups(assets);
+            //Uploads the data of the material if it was changed
             m.update_bindgroups(encoder);
         }
 
@@ -579,45 +731,50 @@ impl RenderingExtension for Base {
                     mapped_at_creation: false,
                 });
 
+                //The light counts followed by the data needed to find the light clusters
                 let buf1 = device.create_buffer(&wgpu::BufferDescriptor {
                     label: Some("Empty point lights buffer"),
-                    size: 16,
+                    size: 16 + size_of::<ClusterParams>() as u64,
                     usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
                     mapped_at_creation: false,
                 });
 
-                let bg_layout =
-                    device.create_bind_group_layout(&point_light_bind_group_layout_descriptor(
-                        *self.storage_buffer_available.get().unwrap(),
-                    ));
-
-                let bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Point lights bind group"),
-                    layout: &bg_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::Buffer(
-                                buf1.as_entire_buffer_binding(),
-                            ),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Buffer(buf.as_entire_buffer_binding()),
-                        },
-                    ],
+                let spot_buf = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Empty spot lights buffer"),
+                    size: (size_of::<SpotLightBuffer>() * MAX_UNIFORM_SPOT_LIGHTS) as u64,
+                    usage: buf.usage(),
+                    mapped_at_creation: false,
                 });
 
+                let clusters = self
+                    .storage_buffer_available
+                    .get()
+                    .unwrap()
+                    .then(LightClusters::new);
+                let bg = light_bind_group(
+                    clusters.as_ref(),
+                    &buf1,
+                    &buf,
+                    &spot_buf,
+                    self.shadows.get_or_init(SpotShadows::new),
+                );
+
                 self.point_light_buffer
                     .set(PointLights {
                         buffer: buf,
                         count_buf: buf1,
                         num_lights: 0,
+                        spot_buffer: spot_buf,
+                        num_spot_lights: MAX_UNIFORM_SPOT_LIGHTS,
+                        clusters,
                         bindgroup: bg,
                     })
                     .unwrap();
             }
 
+            //Positions and ranges of the point lights, binned into the clusters of the camera
+            let mut cluster_lights = Vec::new();
+
             //Handle point lights
             if let Some(lights) = world.get_all_components::<PointLight>() {
                 #[repr(C)]
@@ -652,39 +809,14 @@ impl RenderingExtension for Base {
 
                         mapped_at_creation: false,
                     });
-                    let layout =
-                        device.create_bind_group_layout(&point_light_bind_group_layout_descriptor(
-                            *self.storage_buffer_available.get().unwrap(),
-                        ));
-
-                    let belt = STAGING_BELT.get().unwrap();
-                    belt.write()
-                        .unwrap()
-                        .write_buffer(
-                            encoder,
-                            &p_l.count_buf,
-                            0,
-                            NonZeroU64::new(4).unwrap(),
-                            device,
-                        )
-                        .copy_from_slice(bytemuck::bytes_of(&(lights.len() as u32)));
-
-                    p_l.bindgroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: Some("Point lights bindgroup"),
-                        layout: &layout,
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: p_l.count_buf.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 1,
-                                resource: wgpu::BindingResource::Buffer(
-                                    p_l.buffer.as_entire_buffer_binding(),
-                                ),
-                            },
-                        ],
-                    });
+                    p_l.num_lights = lights.len();
+                    p_l.bindgroup = light_bind_group(
+                        p_l.clusters.as_ref(),
+                        &p_l.count_buf,
+                        &p_l.buffer,
+                        &p_l.spot_buffer,
+                        self.shadows.get().unwrap(),
+                    );
                 }
 
                 let data = lights
@@ -699,6 +831,7 @@ impl RenderingExtension for Base {
                         }
                     })
                     .collect::<Vec<_>>();
+                cluster_lights = data.iter().map(|l| (l.position, l.range)).collect();
 
                 let data = data
                     .iter()
@@ -717,26 +850,43 @@ impl RenderingExtension for Base {
                 )
                 .copy_from_slice(data.as_slice());
             }
+
+            self.update_spot_lights(world, encoder);
+            self.update_clusters(camera, &cluster_lights, encoder);
         }
 
         trace!("Starting the render pass");
 
+        let clear_color = match camera.clear {
+            ClearMode::Color(color) => color,
+            _ => self.clear_color,
+        };
+        //The first camera clears the whole target, the others only clear their viewports
+        let (color_load, depth_load) = if target.first {
+            (
+                wgpu::LoadOp::Clear(clear_color.into()),
+                wgpu::LoadOp::Clear(1.0),
+            )
+        } else {
+            (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+        };
+
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("First pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &attachments.color,
+                view: &target.color,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(self.clear_color.into()),
+                    load: color_load,
                     store: wgpu::StoreOp::Store,
                 },
                 //I hope this is fine, i can't find any info on this
                 depth_slice: None,
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &attachments.depth_stencil,
+                view: &target.depth,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: depth_load,
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -745,6 +895,30 @@ impl RenderingExtension for Base {
             occlusion_query_set: None,
         });
 
+        let (x, y, width, height) = camera.viewport.pixels(target.size);
+        if width == 0 || height == 0 {
+            drop(render_pass);
+            self.caches[index] = cache;
+            return;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(x, y, width, height);
+
+        if !target.first {
+            let pipelines = self.clear_pipelines.get_or_init(ClearPipelines::new);
+            let pipeline = match camera.clear {
+                ClearMode::Default | ClearMode::Color(_) => Some(&pipelines.color),
+                ClearMode::DepthOnly => Some(&pipelines.depth),
+                ClearMode::Nothing => None,
+            };
+            if let Some(pipeline) = pipeline {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_blend_constant(clear_color.into());
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+
         //Set the camera
         camera.set_bindgroup(&mut render_pass);
 
@@ -752,26 +926,12 @@ impl RenderingExtension for Base {
 
         trace!("Rendering materials");
         //Iterate through the meshes and render them
-        for (i, m) in self.mesh_materials.iter().enumerate() {
+        for (i, m) in cache.mesh_materials.iter().enumerate() {
             let mat = m.material_id;
 
             if mat != previous_mat {
                 let mat = assets.borrow_by_id::<Material>(mat).unwrap();
-
-                if mat.is_lit() {
-                    render_pass.set_bind_group(
-                        grimoire::DIRECT_LIGHT_BIND_GROUP_INDEX,
-                        &self.light_buffer.get().unwrap().1,
-                        &[],
-                    );
-                    if let Some(light) = self.point_light_buffer.get() {
-                        render_pass.set_bind_group(
-                            grimoire::POINT_LIGHT_BIND_GROUP_INDEX,
-                            &light.bindgroup,
-                            &[],
-                        );
-                    }
-                }
+                self.set_light_bind_groups(&mut render_pass, &mat);
                 mat.render(&mut render_pass);
             }
             previous_mat = mat;
@@ -779,17 +939,517 @@ impl RenderingExtension for Base {
             let mesh = assets.borrow_by_id::<Mesh>(m.mesh_id).unwrap();
 
             render_pass.set_vertex_buffer(0, mesh.get_vertex_buffer().slice(..));
-            render_pass.set_vertex_buffer(1, self.v_buffers[i].slice(..));
+            render_pass.set_vertex_buffer(1, cache.v_buffers[i].slice(..));
 
             render_pass
                 .set_index_buffer(mesh.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
             render_pass.draw_indexed(
-                0..mesh.get_index_count(),
+                mesh.get_lod_range(m.lod),
                 0,
-                0..(self.num_instances[i] as u32),
+                0..(cache.num_instances[i] as u32),
             );
         }
+
+        trace!("Rendering animated meshes");
+        for a in &animators {
+            self.draw_animated(&mut render_pass, camera, &a.borrow(), assets);
+        }
         drop(render_pass);
+
+        if !transparent_draws.is_empty() {
+            trace!("Rendering transparent meshes");
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.color,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &target.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            #[allow(clippy::cast_precision_loss)]
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            camera.set_bindgroup(&mut render_pass);
+
+            let mut previous_mat = 0;
+            for draw in transparent_draws {
+                match draw {
+                    TransparentDraw::Mesh(m, instance) => {
+                        //Animated meshes change the pipeline and the camera bind group
+                        if m.material_id != previous_mat {
+                            let mat = assets.borrow_by_id::<Material>(m.material_id).unwrap();
+                            self.set_light_bind_groups(&mut render_pass, &mat);
+                            mat.render(&mut render_pass);
+                            drop(mat);
+                            camera.set_bindgroup(&mut render_pass);
+                            previous_mat = m.material_id;
+                        }
+
+                        let mesh = assets.borrow_by_id::<Mesh>(m.mesh_id).unwrap();
+                        render_pass.set_vertex_buffer(0, mesh.get_vertex_buffer().slice(..));
+                        render_pass.set_vertex_buffer(
+                            1,
+                            cache.transparent_buffer.as_ref().unwrap().slice(..),
+                        );
+                        render_pass.set_index_buffer(
+                            mesh.get_index_buffer().slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        render_pass.draw_indexed(
+                            mesh.get_lod_range(m.lod),
+                            0,
+                            instance..instance + 1,
+                        );
+                    }
+                    TransparentDraw::Animated(i) => {
+                        let a = transparent_animators[i].borrow();
+                        self.draw_animated(&mut render_pass, camera, &a, assets);
+                        previous_mat = 0;
+                    }
+                }
+            }
+        }
+        self.caches[index] = cache;
+    }
+
+    ///Picks the level of detail of the mesh for the camera
+    fn select_lod(&self, camera: &Camera, m: &mut components::mesh::Mesh, mesh: &Mesh) -> usize {
+        let binding = m.get_transform();
+        let t = binding.borrow();
+        let radius = mesh.get_extent() * t.scale.x.abs().max(t.scale.y.abs()).max(t.scale.z.abs());
+        let screen_size = camera.screen_size(t.position, radius);
+        drop(t);
+
+        let lod = mesh.select_lod(screen_size, m.get_lod(), self.lod_hysteresis);
+        m.set_lod(lod);
+        lod
+    }
+
+    ///Sorts the transparent meshes from back to front, and uploads their matrices
+    fn sort_transparent(
+        &self,
+        camera: &Camera,
+        meshes: &[&ComponentReference<components::mesh::Mesh>],
+        animators: &[ComponentReference<Animator>],
+        cache: &mut CameraCache,
+        encoder: &mut wgpu::CommandEncoder,
+        assets: &AssetStore,
+    ) -> Vec<TransparentDraw> {
+        let mut draws = Vec::with_capacity(meshes.len() + animators.len());
+        let mut matrices = Vec::with_capacity(meshes.len());
+
+        for m in meshes {
+            let mut m = m.borrow_mut();
+            let mesh_id = m.get_mesh_id().unwrap();
+            let mesh = assets.borrow_by_id::<Mesh>(mesh_id).unwrap();
+            let lod = self.select_lod(camera, &mut m, &mesh);
+            drop(mesh);
+
+            let depth = camera.view_depth(m.get_transform().borrow().position);
+            let mesh_material = MeshMaterial::new((mesh_id, lod), m.get_material_id().unwrap());
+            draws.push((
+                depth,
+                TransparentDraw::Mesh(mesh_material, 0),
+                m.get_matrix(),
+            ));
+        }
+        for (i, a) in animators.iter().enumerate() {
+            let a = a.borrow();
+            let position = a
+                .get_mesh()
+                .unwrap()
+                .borrow()
+                .get_transform()
+                .borrow()
+                .position;
+            draws.push((
+                camera.view_depth(position),
+                TransparentDraw::Animated(i),
+                Mat4x4::identity(),
+            ));
+        }
+
+        //Back to front, so the meshes behind are blended with the ones in front of them
+        draws.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let draws = draws
+            .into_iter()
+            .map(|(_, draw, matrix)| match draw {
+                TransparentDraw::Mesh(m, _) => {
+                    matrices.push(matrix);
+                    TransparentDraw::Mesh(m, matrices.len() as u32 - 1)
+                }
+                TransparentDraw::Animated(_) => draw,
+            })
+            .collect::<Vec<_>>();
+
+        if matrices.is_empty() {
+            return draws;
+        }
+
+        let device = DEVICE.get().unwrap();
+        let size = (matrices.len() * size_of::<Mat4x4>()) as u64;
+        if cache
+            .transparent_buffer
+            .as_ref()
+            .is_none_or(|b| b.size() < size)
+        {
+            cache.transparent_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Transparent instances"),
+                size: size.next_power_of_two(),
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+
+        STAGING_BELT
+            .get()
+            .unwrap()
+            .write()
+            .unwrap()
+            .write_buffer(
+                encoder,
+                cache.transparent_buffer.as_ref().unwrap(),
+                0,
+                NonZeroU64::new(size).unwrap(),
+                device,
+            )
+            .copy_from_slice(bytemuck::cast_slice(&matrices));
+
+        draws
+    }
+
+    ///Uploads the spot lights and the number of the point and spot lights
+    fn update_spot_lights(&mut self, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        let storage_buffer = *self.storage_buffer_available.get().unwrap();
+        let mut lights = world.get_all_components::<SpotLight>().unwrap_or_default();
+        if !storage_buffer {
+            lights.truncate(MAX_UNIFORM_SPOT_LIGHTS);
+        }
+        let num_point_lights = world
+            .get_all_components::<PointLight>()
+            .map_or(0, |l| l.len());
+
+        let device = DEVICE.get().unwrap();
+        let p_l = self.point_light_buffer.get_mut().unwrap();
+
+        //Uniform buffers always have the maximum size
+        if p_l.num_spot_lights < lights.len() {
+            p_l.spot_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Spot lights buffer"),
+                size: (size_of::<SpotLightBuffer>() * lights.len()) as u64,
+                usage: p_l.spot_buffer.usage(),
+                mapped_at_creation: false,
+            });
+            p_l.num_spot_lights = lights.len();
+            p_l.bindgroup = light_bind_group(
+                p_l.clusters.as_ref(),
+                &p_l.count_buf,
+                &p_l.buffer,
+                &p_l.spot_buffer,
+                self.shadows.get().unwrap(),
+            );
+        }
+
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            &p_l.count_buf,
+            0,
+            NonZeroU64::new(8).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&[
+            num_point_lights as u32,
+            lights.len() as u32,
+        ]));
+
+        if lights.is_empty() {
+            return;
+        }
+        let data = lights
+            .iter()
+            .zip(shadow_indices(&lights))
+            .map(|(l, shadow_index)| l.borrow().get_light(shadow_index))
+            .collect::<Vec<_>>();
+        belt.write_buffer(
+            encoder,
+            &p_l.spot_buffer,
+            0,
+            NonZeroU64::new((data.len() * size_of::<SpotLightBuffer>()) as u64).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&data));
+    }
+
+    ///Bins the point lights into the clusters of the camera and uploads the data the shaders need
+    ///to find them
+    fn update_clusters(
+        &mut self,
+        camera: &Camera,
+        lights: &[(Vec3, f32)],
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let p_l = self.point_light_buffer.get_mut().unwrap();
+        let view = ClusterView::new(camera);
+        let params = view.params(camera.viewport.pixels(camera.target_size()));
+
+        let device = DEVICE.get().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            &p_l.count_buf,
+            16,
+            NonZeroU64::new(size_of::<ClusterParams>() as u64).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::bytes_of(&params));
+        drop(belt);
+
+        let recreated = p_l
+            .clusters
+            .as_mut()
+            .is_some_and(|c| c.update(&view, lights, encoder));
+        if recreated {
+            p_l.bindgroup = light_bind_group(
+                p_l.clusters.as_ref(),
+                &p_l.count_buf,
+                &p_l.buffer,
+                &p_l.spot_buffer,
+                self.shadows.get().unwrap(),
+            );
+        }
+    }
+
+    ///Sets the light bind groups if the material is lit
+    fn set_light_bind_groups(&self, render_pass: &mut wgpu::RenderPass, material: &Material) {
+        if !material.is_lit() {
+            return;
+        }
+        render_pass.set_bind_group(
+            grimoire::DIRECT_LIGHT_BIND_GROUP_INDEX,
+            &self.light_buffer.get().unwrap().1,
+            &[],
+        );
+        if let Some(light) = self.point_light_buffer.get() {
+            render_pass.set_bind_group(
+                grimoire::POINT_LIGHT_BIND_GROUP_INDEX,
+                &light.bindgroup,
+                &[],
+            );
+        }
+    }
+
+    ///Renders an animated mesh, with its own pose
+    fn draw_animated<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a Camera,
+        a: &Animator,
+        assets: &AssetStore,
+    ) {
+        let m = a.get_mesh().unwrap().borrow();
+        let mat = assets
+            .borrow_by_id::<Material>(m.get_material_id().unwrap())
+            .unwrap();
+        let mesh = assets
+            .borrow_by_id::<Mesh>(m.get_mesh_id().unwrap())
+            .unwrap();
+        drop(m);
+        let gpu = a.gpu.as_ref().unwrap();
+        self.set_light_bind_groups(render_pass, &mat);
+
+        //Fall back to rendering the bind pose if the mesh or the material can't be skinned
+        match mesh.get_skin_buffer() {
+            Some(skin) if mat.render_skinned(render_pass) => {
+                render_pass.set_bind_group(
+                    grimoire::CAMERA_BIND_GROUP_INDEX,
+                    gpu.bind_group(camera.get_buffer()),
+                    &[],
+                );
+                render_pass.set_vertex_buffer(2, skin.slice(..));
+            }
+            _ => {
+                camera.set_bindgroup(render_pass);
+                mat.render(render_pass);
+            }
+        }
+        drop(mat);
+
+        render_pass.set_vertex_buffer(0, mesh.get_vertex_buffer().slice(..));
+        render_pass.set_vertex_buffer(1, gpu.instance_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(mesh.get_lod_range(0), 0, 0..1);
+    }
+
+    ///Returns the color and depth attachments and the size of the target texture of a camera
+    fn target_attachments(
+        &mut self,
+        id: UUID,
+        assets: &AssetStore,
+    ) -> Option<(wgpu::TextureView, wgpu::TextureView, (u32, u32))> {
+        let texture = assets.borrow_by_id::<Texture>(id).ok();
+        let Some(texture) = texture.filter(|t| t.is_render_target() && t.texture.is_some()) else {
+            log::error!("Camera target is not an initialized render target texture");
+            return None;
+        };
+        let raw = texture.texture.as_ref().unwrap();
+        let size = (raw.width(), raw.height());
+        let color = raw.create_view(&wgpu::TextureViewDescriptor::default());
+        drop(texture);
+
+        if let Some(depth) = self.target_depths.iter().find(|d| d.0 == id && d.1 == size) {
+            return Some((color, depth.2.clone(), size));
+        }
+
+        let depth = DEVICE
+            .get()
+            .unwrap()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Camera target depth"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.target_depths.retain(|d| d.0 != id);
+        self.target_depths.push((id, size, depth.clone()));
+        Some((color, depth, size))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct MeshMaterial {
+    mesh_id: u128,
+    lod: usize,
+    material_id: u128,
+}
+
+impl PartialEq<((u128, usize), u128)> for MeshMaterial {
+    fn eq(&self, other: &((u128, usize), u128)) -> bool {
+        self.mesh_id == other.0.0 && self.lod == other.0.1 && self.material_id == other.1
+    }
+}
+
+impl MeshMaterial {
+    const fn new((mesh_id, lod): (u128, usize), material_id: u128) -> Self {
+        Self {
+            mesh_id,
+            lod,
+            material_id,
+        }
+    }
+}
+
+impl RenderNode for Base {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        if is_hdr() {
+            builder.create_texture(
+                HDR_COLOR,
+                TextureDescription::new(
+                    TextureSize::Surface,
+                    HDR_FORMAT,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                ),
+            );
+        } else {
+            builder.write(SURFACE);
+        }
+        builder.write(DEPTH);
+    }
+
+    #[allow(clippy::cognitive_complexity)]
+    fn run(&mut self, context: &mut NodeContext) {
+        let color = context.texture(scene_target());
+        let depth_stencil = context.texture(DEPTH);
+        let encoder = &mut *context.encoder;
+        let world = context.world;
+        let assets = &mut *context.assets;
+
+        //Initialize needed stuff
+        if self.storage_buffer_available.get().is_none() {
+            let storage_buf_available = storage_buffer_available();
+            self.storage_buffer_available
+                .set(storage_buf_available)
+                .unwrap();
+
+            if storage_buf_available {
+                log::info!("Storage buffer supported");
+            } else {
+                log::info!("Storage buffer not supported");
+            }
+        }
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Frustum culling render");
+        trace!("Started frame");
+
+        let cameras = Cameras::new(world);
+        let cameras = cameras.borrow();
+        assert!(!cameras.is_empty(), "Could not find a camera");
+        self.caches.resize_with(cameras.len(), CameraCache::default);
+
+        let resolution = RESOLUTION.read().unwrap();
+        let screen_size = (resolution.width, resolution.height);
+        drop(resolution);
+
+        //Shadows don't depend on the camera, so they are rendered once for all of them
+        if let Some(lights) = world.get_all_components::<SpotLight>() {
+            self.shadows.get_or_init(SpotShadows::new);
+            self.shadows
+                .get_mut()
+                .unwrap()
+                .render(&lights, encoder, world, assets);
+        }
+
+        //Targets that were already rendered into this frame
+        let mut used_targets = Vec::new();
+        for (index, camera) in cameras.iter().enumerate() {
+            let (color, depth, size) = match camera.target {
+                Some(id) => {
+                    let Some(attachments) = self.target_attachments(id, assets) else {
+                        continue;
+                    };
+                    attachments
+                }
+                None => (color.clone(), depth_stencil.clone(), screen_size),
+            };
+            camera.set_target_size(camera.target.map(|_| size));
+
+            let first = !used_targets.contains(&camera.target);
+            if first {
+                used_targets.push(camera.target);
+            }
+            let target = CameraTarget {
+                color,
+                depth,
+                size,
+                first,
+            };
+            self.render_camera(index, camera, &target, encoder, world, assets);
+        }
     }
 
     fn get_priority(&self) -> u32 {
@@ -797,14 +1457,103 @@ impl RenderingExtension for Base {
     }
 }
 
-fn calculate_frustum(near: f32, far: f32, fov: f32) -> Vec3 {
+///Creates the gpu resources of the animator if needed, and uploads its pose and transformation
+fn update_animator(
+    animator: &mut Animator,
+    camera: &components::camera::Camera,
+    encoder: &mut wgpu::CommandEncoder,
+    assets: &AssetStore,
+) {
+    let device = DEVICE.get().unwrap();
+
+    if animator.gpu.is_none() {
+        let joint_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Joint matrices"),
+            size: (size_of::<Mat4x4>() * MAX_JOINTS) as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Animated mesh transformation"),
+            size: size_of::<Mat4x4>() as u64,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        animator.gpu = Some(AnimatorGpu {
+            joint_buffer,
+            instance_buffer,
+            bind_groups: Vec::new(),
+        });
+    }
+
+    //Every camera needs its own bind group
+    let gpu = animator.gpu.as_mut().unwrap();
+    let camera_buffer = camera.get_buffer();
+    if !gpu.bind_groups.iter().any(|(b, _)| b == camera_buffer) {
+        let layout =
+            device.create_bind_group_layout(&grimoire::SKINNED_CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skinned camera"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: gpu.joint_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        gpu.bind_groups.push((camera_buffer.clone(), bind_group));
+    }
+
+    let mesh = animator.get_mesh().unwrap().borrow();
+    let matrix = mesh.get_matrix();
+    let skinned = assets
+        .borrow_by_id::<Mesh>(mesh.get_mesh_id().unwrap())
+        .unwrap()
+        .is_skinned();
+    drop(mesh);
+
+    let gpu = animator.gpu.as_ref().unwrap();
+    let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+    belt.write_buffer(
+        encoder,
+        &gpu.instance_buffer,
+        0,
+        NonZeroU64::new(size_of::<Mat4x4>() as u64).unwrap(),
+        device,
+    )
+    .copy_from_slice(bytemuck::bytes_of(&matrix));
+
+    if skinned {
+        let joints = animator.joint_data();
+        belt.write_buffer(
+            encoder,
+            &gpu.joint_buffer,
+            0,
+            NonZeroU64::new(gpu.joint_buffer.size()).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&joints));
+    }
+}
+
+///Returns whether the material of the mesh is rendered in the transparent queue
+fn is_transparent(mesh: &components::mesh::Mesh, assets: &AssetStore) -> bool {
+    assets
+        .borrow_by_id::<Material>(mesh.get_material_id().unwrap())
+        .unwrap()
+        .is_transparent()
+}
+
+fn calculate_frustum(near: f32, far: f32, fov: f32, aspect: f32) -> Vec3 {
     let beta = f32::consts::FRAC_PI_2 - (fov / 2.0);
     let bottom = 2.0 * (((near + far) * f32::sin(fov / 2.0)) / f32::sin(beta));
 
-    let resolution = RESOLUTION.read().unwrap();
-    let aspect = resolution.width as f32 / resolution.height as f32;
-    drop(resolution);
-
     let side = bottom / aspect;
 
     (bottom, side, near + far).into()
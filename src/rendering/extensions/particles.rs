@@ -0,0 +1,737 @@
+//! Simulation and rendering of particles
+//!
+//! Every [`ParticleEmitter`] in the world is simulated by a compute shader, or by the component
+//! itself with [`ParticleSimulation::Cpu`], and its particles are drawn as instanced quads. The
+//! particles of an emitter are drawn with a single draw call
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::rendering::extensions::{Base, particles::ParticleRenderer};
+//!# use lunar_engine::rendering::render;
+//!# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, particles: ParticleRenderer}
+//! fn update(state: &mut State) {
+//!     render(
+//!         &state.world,
+//!         &mut state.assets,
+//!         &mut [&mut state.base, &mut state.particles],
+//!     );
+//! }
+//!```
+use std::num::NonZeroU64;
+
+use wgpu::util::DeviceExt;
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, QUEUE, STAGING_BELT,
+    asset_managment::AssetStore,
+    assets::{BlendMode, Texture},
+    components::{
+        camera::Cameras,
+        particles::{
+            GpuUpdate, Particle, ParticleEmitter, ParticleInstance, ParticleRenderMode,
+            ParticleSimulation, ParticlesGpu, sample_curve,
+        },
+    },
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    math::Vec3,
+    rendering::{
+        graph::{DEPTH, NodeBuilder, NodeContext, RenderNode},
+        scene_format, scene_target,
+    },
+    structures::Color,
+};
+
+///Number of samples of the curves used by the compute shader
+const CURVE_SAMPLES: usize = 32;
+///Size of a workgroup of the compute shader
+const WORKGROUP_SIZE: u32 = 64;
+///Width and height of the texture used by emitters without a texture
+const DEFAULT_TEXTURE_SIZE: u32 = 32;
+
+const PARTICLE_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Particle emitter"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    };
+
+const fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+const SIMULATION_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Particle simulation"),
+        entries: &[
+            storage_entry(0),
+            storage_entry(1),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    };
+
+///Parameters of the compute shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimulationParams {
+    gravity: Vec3,
+    drag: f32,
+    delta: f32,
+    count: u32,
+    padding: [u32; 2],
+    size: [[f32; 4]; CURVE_SAMPLES / 4],
+    velocity: [[f32; 4]; CURVE_SAMPLES / 4],
+    color: [Color; CURVE_SAMPLES],
+}
+
+///Parameters of the particle shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct RenderParams {
+    alpha_cutoff: f32,
+    length_scale: f32,
+    padding: [f32; 2],
+}
+
+///Returns the parameters of the compute shader of the emitter, with the curves sampled at evenly
+///spaced points of the lifetime
+#[allow(clippy::cast_precision_loss)]
+fn simulation_params(emitter: &ParticleEmitter, delta: f32) -> SimulationParams {
+    let t = |i: usize| i as f32 / (CURVE_SAMPLES - 1) as f32;
+    let scalars = |track, default| {
+        let mut samples = [[0.0; 4]; CURVE_SAMPLES / 4];
+        for (i, sample) in samples.as_flattened_mut().iter_mut().enumerate() {
+            *sample = sample_curve(track, t(i), default);
+        }
+        samples
+    };
+
+    SimulationParams {
+        gravity: emitter.gravity,
+        drag: emitter.drag,
+        delta,
+        count: emitter.max_particles,
+        padding: [0; 2],
+        size: scalars(&emitter.size, 0.1),
+        velocity: scalars(&emitter.velocity, 1.0),
+        color: std::array::from_fn(|i| sample_curve(&emitter.color, t(i), Color::white())),
+    }
+}
+
+const fn render_params(emitter: &ParticleEmitter) -> RenderParams {
+    RenderParams {
+        alpha_cutoff: emitter.blend_mode.alpha_cutoff(),
+        length_scale: match emitter.render_mode {
+            ParticleRenderMode::Billboard => 0.0,
+            ParticleRenderMode::Stretched { length_scale } => length_scale.max(f32::EPSILON),
+        },
+        padding: [0.0; 2],
+    }
+}
+
+///Groups the spawned particles into runs of consecutive slots
+fn spawn_runs(spawned: &[(u32, Particle)]) -> Vec<(u32, Vec<Particle>)> {
+    let mut runs: Vec<(u32, Vec<Particle>)> = Vec::new();
+    for (slot, particle) in spawned {
+        match runs.last_mut() {
+            Some((start, particles)) if *start + particles.len() as u32 == *slot => {
+                particles.push(*particle);
+            }
+            _ => runs.push((*slot, vec![*particle])),
+        }
+    }
+    runs
+}
+
+///Returns the pixels of a soft white circle
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn soft_circle(size: u32) -> Vec<u8> {
+    (0..size * size)
+        .flat_map(|i| {
+            let center = size as f32 / 2.0;
+            let x = (i % size) as f32 + 0.5 - center;
+            let y = (i / size) as f32 + 0.5 - center;
+            let falloff = (1.0 - x.hypot(y) / center).clamp(0.0, 1.0);
+            [255, 255, 255, (falloff * falloff * 255.0).round() as u8]
+        })
+        .collect()
+}
+
+///A particle emitter ready to be drawn
+struct DrawnEmitter {
+    index: usize,
+    count: u32,
+    position: Vec3,
+    blend_mode: BlendMode,
+}
+
+///Simulates and renders the particles of every [`ParticleEmitter`] in the viewport of every camera
+///that renders to the screen
+///
+///Transparent emitters are sorted back to front, but the particles within an emitter are not.
+///They are tested against the depth buffer, so the node should run after the
+///[`Base`](super::Base) node
+pub struct ParticleRenderer {
+    ///Priority of the extension
+    pub priority: u32,
+    ///Pipelines for every blend mode
+    pipelines: Vec<(Option<wgpu::BlendState>, bool, wgpu::RenderPipeline)>,
+    compute_pipeline: Option<wgpu::ComputePipeline>,
+    ///Used by the emitters without a texture
+    default_texture: Option<(wgpu::TextureView, wgpu::Sampler)>,
+}
+
+impl Default for ParticleRenderer {
+    fn default() -> Self {
+        Self::new(6)
+    }
+}
+
+impl ParticleRenderer {
+    ///Creates a new [`ParticleRenderer`]
+    #[must_use]
+    pub const fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            pipelines: Vec::new(),
+            compute_pipeline: None,
+            default_texture: None,
+        }
+    }
+
+    fn create_pipeline(blend_mode: BlendMode) -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/particles.wgsl"));
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let emitter_layout =
+            device.create_bind_group_layout(&PARTICLE_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particles"),
+            bind_group_layouts: &[&camera_layout, &emitter_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particles"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<ParticleInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Float32x3
+                    ],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: blend_mode.depth_write(),
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: scene_format(),
+                    blend: blend_mode.blend_state(),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_compute_pipeline() -> wgpu::ComputePipeline {
+        let device = DEVICE.get().unwrap();
+        let shader =
+            device.create_shader_module(include_wgsl!("../../shaders/particles_simulate.wgsl"));
+        let simulation_layout =
+            device.create_bind_group_layout(&SIMULATION_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle simulation"),
+            bind_group_layouts: &[&simulation_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle simulation"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        })
+    }
+
+    fn create_default_texture() -> (wgpu::TextureView, wgpu::Sampler) {
+        let device = DEVICE.get().unwrap();
+        let texture = device.create_texture_with_data(
+            QUEUE.get().unwrap(),
+            &wgpu::TextureDescriptor {
+                label: Some("Default particle"),
+                size: wgpu::Extent3d {
+                    width: DEFAULT_TEXTURE_SIZE,
+                    height: DEFAULT_TEXTURE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &soft_circle(DEFAULT_TEXTURE_SIZE),
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        (
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+        )
+    }
+
+    ///Creates the buffers of the emitter if it has none or if the number of particles changed
+    fn prepare_buffers(emitter: &mut ParticleEmitter) {
+        let capacity = emitter.max_particles.max(1);
+        if emitter.gpu.as_ref().is_some_and(|g| g.capacity == capacity) {
+            return;
+        }
+        let device = DEVICE.get().unwrap();
+        let gpu_simulation = emitter.get_simulation() == ParticleSimulation::Gpu;
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle instances"),
+            size: u64::from(capacity) * size_of::<ParticleInstance>() as u64,
+            usage: if gpu_simulation {
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+            } else {
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+            },
+            mapped_at_creation: false,
+        });
+
+        let simulation = gpu_simulation.then(|| {
+            let particles = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particles"),
+                size: u64::from(capacity) * size_of::<Particle>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let params = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particle simulation"),
+                size: size_of::<SimulationParams>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Particle simulation"),
+                layout: &device.create_bind_group_layout(&SIMULATION_BIND_GROUP_LAYOUT_DESCRIPTOR),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: particles.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: instance_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            });
+            (particles, params, bind_group)
+        });
+
+        let render_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle emitter"),
+            contents: bytemuck::bytes_of(&render_params(emitter)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        emitter.gpu = Some(ParticlesGpu {
+            capacity,
+            instance_buffer,
+            simulation,
+            render_buffer,
+            render_bind_group: None,
+        });
+    }
+
+    ///Creates the bind group of the texture of the emitter if the texture changed
+    fn prepare_bind_group(&mut self, assets: &AssetStore, emitter: &mut ParticleEmitter) {
+        let texture = emitter.texture;
+        let gpu = emitter.gpu.as_mut().unwrap();
+        if gpu
+            .render_bind_group
+            .as_ref()
+            .is_some_and(|(t, _)| *t == texture)
+        {
+            return;
+        }
+
+        let binding = texture.and_then(|id| {
+            assets
+                .is_type::<Texture>(id)
+                .then(|| assets.borrow_by_id::<Texture>(id).ok())
+                .flatten()
+                .filter(|t| !t.is_cubemap())
+                .and_then(|t| Some((t.create_view(), t.sampler.clone()?)))
+        });
+        if texture.is_some() && binding.is_none() {
+            log::warn!("Particle texture is not a 2D texture");
+        }
+        let (view, sampler) = binding.unwrap_or_else(|| {
+            self.default_texture
+                .get_or_insert_with(Self::create_default_texture)
+                .clone()
+        });
+
+        let device = DEVICE.get().unwrap();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle emitter"),
+            layout: &device.create_bind_group_layout(&PARTICLE_BIND_GROUP_LAYOUT_DESCRIPTOR),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: gpu.render_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        gpu.render_bind_group = Some((texture, bind_group));
+    }
+
+    ///Uploads the new particles and simulates the particles of the emitter on the gpu
+    fn simulate(&mut self, encoder: &mut wgpu::CommandEncoder, emitter: &mut ParticleEmitter) {
+        let update: GpuUpdate = emitter.take_gpu_update();
+        let params = simulation_params(emitter, update.delta);
+        let gpu = emitter.gpu.as_ref().unwrap();
+        let Some((particles, params_buffer, bind_group)) = &gpu.simulation else {
+            return;
+        };
+
+        let device = DEVICE.get().unwrap();
+        if update.clear {
+            encoder.clear_buffer(particles, 0, None);
+        }
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        for (slot, spawned) in spawn_runs(&update.spawned) {
+            let data: &[u8] = bytemuck::cast_slice(&spawned);
+            belt.write_buffer(
+                encoder,
+                particles,
+                u64::from(slot) * size_of::<Particle>() as u64,
+                NonZeroU64::new(data.len() as u64).unwrap(),
+                device,
+            )
+            .copy_from_slice(data);
+        }
+        belt.write_buffer(
+            encoder,
+            params_buffer,
+            0,
+            NonZeroU64::new(size_of::<SimulationParams>() as u64).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::bytes_of(&params));
+        drop(belt);
+
+        let pipeline = self
+            .compute_pipeline
+            .get_or_insert_with(Self::create_compute_pipeline);
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle simulation"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(gpu.capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    ///Uploads the particles simulated on the cpu, returns the number of particles
+    fn upload_instances(encoder: &mut wgpu::CommandEncoder, emitter: &ParticleEmitter) -> u32 {
+        let instances = emitter.instances();
+        let gpu = emitter.gpu.as_ref().unwrap();
+        let Some(size) = NonZeroU64::new(size_of_val(instances.as_slice()) as u64) else {
+            return 0;
+        };
+
+        let device = DEVICE.get().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(encoder, &gpu.instance_buffer, 0, size, device)
+            .copy_from_slice(bytemuck::cast_slice(&instances));
+        drop(belt);
+        instances.len() as u32
+    }
+
+    ///Returns the pipeline for the blend mode
+    fn pipeline(&mut self, blend_mode: BlendMode) -> &wgpu::RenderPipeline {
+        let key = (blend_mode.blend_state(), blend_mode.depth_write());
+        let index = self
+            .pipelines
+            .iter()
+            .position(|(blend, depth_write, _)| (*blend, *depth_write) == key)
+            .unwrap_or_else(|| {
+                self.pipelines
+                    .push((key.0, key.1, Self::create_pipeline(blend_mode)));
+                self.pipelines.len() - 1
+            });
+        &self.pipelines[index].2
+    }
+}
+
+impl RenderNode for ParticleRenderer {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.write(scene_target());
+        builder.read(DEPTH);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        let color = context.texture(scene_target());
+        let depth_stencil = context.texture(DEPTH);
+        let encoder = &mut *context.encoder;
+        let assets = &*context.assets;
+
+        let Some(emitters) = context.world.get_all_components::<ParticleEmitter>() else {
+            return;
+        };
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Particle render");
+
+        //Particles are simulated even if they are not visible
+        let mut drawn = Vec::new();
+        for (index, emitter) in emitters.iter().enumerate() {
+            let mut emitter = emitter.borrow_mut();
+            Self::prepare_buffers(&mut emitter);
+
+            let count = match emitter.get_simulation() {
+                ParticleSimulation::Gpu => {
+                    self.simulate(encoder, &mut emitter);
+                    emitter.max_particles
+                }
+                ParticleSimulation::Cpu => Self::upload_instances(encoder, &emitter),
+            };
+            if !emitter.visible || count == 0 {
+                continue;
+            }
+
+            self.prepare_bind_group(assets, &mut emitter);
+            let params = render_params(&emitter);
+            let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+            belt.write_buffer(
+                encoder,
+                &emitter.gpu.as_ref().unwrap().render_buffer,
+                0,
+                NonZeroU64::new(size_of::<RenderParams>() as u64).unwrap(),
+                DEVICE.get().unwrap(),
+            )
+            .copy_from_slice(bytemuck::bytes_of(&params));
+            drop(belt);
+
+            drawn.push(DrawnEmitter {
+                index,
+                count,
+                position: emitter.get_transform().borrow().position_global(),
+                blend_mode: emitter.blend_mode,
+            });
+        }
+
+        let cameras = Cameras::new(context.world);
+        let cameras: Vec<_> = cameras
+            .borrow()
+            .into_iter()
+            .filter(|c| c.target.is_none())
+            .collect();
+        if cameras.is_empty() || drawn.is_empty() {
+            return;
+        }
+        for camera in &cameras {
+            camera.update_gpu(encoder);
+        }
+        for emitter in &drawn {
+            self.pipeline(emitter.blend_mode);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Particle pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_stencil,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        for camera in &cameras {
+            let (x, y, width, height) = camera.viewport.pixels(camera.target_size());
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            camera.set_bindgroup(&mut render_pass);
+
+            //Opaque emitters first, then the transparent ones from back to front
+            let mut order = drawn
+                .iter()
+                .map(|e| (e, camera.view_depth(e.position)))
+                .collect::<Vec<_>>();
+            order.sort_by(|a, b| {
+                let transparent = |e: &DrawnEmitter| e.blend_mode.is_transparent();
+                transparent(a.0)
+                    .cmp(&transparent(b.0))
+                    .then(b.1.total_cmp(&a.1))
+            });
+
+            for (drawn, _) in order {
+                let emitter = emitters[drawn.index].borrow();
+                let gpu = emitter.gpu.as_ref().unwrap();
+                render_pass.set_pipeline(self.pipeline(drawn.blend_mode));
+                render_pass.set_bind_group(1, &gpu.render_bind_group.as_ref().unwrap().1, &[]);
+                render_pass.set_vertex_buffer(0, gpu.instance_buffer.slice(..));
+                render_pass.draw(0..4, 0..drawn.count);
+            }
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_particle_gpu_data() {
+    use crate::{
+        assets::animation::{Interpolation, Track},
+        ecs::Component as _,
+    };
+
+    let mut emitter = ParticleEmitter::mew();
+    emitter.size = Track::from_keyframes(Interpolation::Linear, &[(0.0, 0.0), (1.0, 2.0)]);
+    emitter.velocity = Track::new(Interpolation::Linear, Vec::new(), Vec::new());
+    let params = simulation_params(&emitter, 0.5);
+    assert_eq!(params.count, 1000);
+    assert!((params.delta - 0.5).abs() < f32::EPSILON);
+    let size = params.size.as_flattened();
+    assert!(size[0].abs() < f32::EPSILON);
+    assert!((size[CURVE_SAMPLES - 1] - 2.0).abs() < f32::EPSILON);
+    assert!((size[8] - 16.0 / 31.0).abs() < 1e-5);
+    //Empty curves use the default
+    assert!(params.velocity.as_flattened().iter().all(|v| *v == 1.0));
+    assert!((params.color[CURVE_SAMPLES - 1].a).abs() < f32::EPSILON);
+
+    emitter.render_mode = ParticleRenderMode::Stretched { length_scale: 0.1 };
+    emitter.blend_mode = BlendMode::Cutout(0.25);
+    assert_eq!(
+        render_params(&emitter),
+        RenderParams {
+            alpha_cutoff: 0.25,
+            length_scale: 0.1,
+            padding: [0.0; 2]
+        }
+    );
+
+    let particle = Particle::default();
+    let runs = spawn_runs(&[(3, particle), (4, particle), (0, particle), (2, particle)]);
+    assert_eq!(
+        runs.iter().map(|r| (r.0, r.1.len())).collect::<Vec<_>>(),
+        vec![(3, 2), (0, 1), (2, 1)]
+    );
+
+    let circle = soft_circle(4);
+    //Opaque in the center and transparent in the corners
+    assert!(circle[(4 + 1) * 4 + 3] > 0);
+    assert_eq!(circle[3], 0);
+}
@@ -0,0 +1,627 @@
+//! Picking of the entities under the cursor
+//!
+//! With [`PickingMode::Gpu`] the meshes seen by the camera under the cursor are drawn into an id
+//! buffer, that stores the index of the entity and the depth of every pixel. Only the pixel under
+//! the cursor is drawn, it is copied into a buffer that is read back asynchronously, so the
+//! result arrives a frame or more after the pick was made. The picked point is reconstructed
+//! from the depth.
+//!
+//! With [`PickingMode::Cpu`] a ray is cast from the camera through the cursor and tested against
+//! the bounding boxes of the meshes, or their triangles if the mesh asset keeps them, see
+//! [`Mesh::set_keep_triangles`]. The result is available immediately. The same raycast can also
+//! be done manually with [`raycast`]
+//!
+//! In both modes animated meshes are picked in their bind pose and transparent parts of the
+//! materials are not ignored
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::rendering::extensions::{Base, picking::Picking};
+//!# use lunar_engine::rendering::render;
+//!# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, picking: Picking}
+//! fn update(state: &mut State) {
+//!     render(
+//!         &state.world,
+//!         &mut state.assets,
+//!         &mut [&mut state.base, &mut state.picking],
+//!     );
+//!
+//!     if let Some(pick) = state.picking.get_result() {
+//!         log::info!("Entity {} at {}", pick.entity, pick.point);
+//!     }
+//! }
+//!```
+use std::{num::NonZeroU64, sync::mpsc};
+
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, STAGING_BELT, UUID,
+    asset_managment::AssetStore,
+    assets::{Mesh, materials::helpers::vertex_binding},
+    components::{
+        self,
+        camera::{Camera, Cameras, pixel_to_ndc, unproject_ndc},
+    },
+    ecs::World,
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    math::{Aabb, Mat4x4, Ray, Vec2, Vec3},
+    rendering::graph::{GraphResources, NodeBuilder, NodeContext, RenderNode},
+};
+
+///Format of the id buffer, the index of the entity and the depth
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+
+///How the entities are picked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PickingMode {
+    ///The meshes are drawn into an id buffer that is read back asynchronously, the result
+    ///matches the rendered triangles
+    #[default]
+    Gpu,
+    ///A ray is cast against the bounding boxes or the triangles of the meshes, the result is
+    ///available immediately
+    Cpu,
+}
+
+///Entity that was picked and the point on its mesh that was hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    ///Id of the entity
+    pub entity: UUID,
+    ///Point that was hit in world space
+    pub point: Vec3,
+}
+
+///A pick that was drawn, but not read back yet
+struct PendingPick {
+    ///Entities in the order of their indices in the id buffer, starting from 1
+    entities: Vec<UUID>,
+    ///Inverse of the camera matrix when the pick was made
+    inverse: Mat4x4,
+    ///Center of the picked pixel in normalized device coordinates
+    ndc: Vec2,
+}
+
+///Gpu resources of the picking extension
+struct PickingGpu {
+    pipeline: wgpu::RenderPipeline,
+    ///Single pixel id and depth targets, the viewport is moved so the picked pixel lands on them
+    id_texture: wgpu::Texture,
+    id_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    matrices: Option<wgpu::Buffer>,
+    indices: Option<wgpu::Buffer>,
+}
+
+///Rendering extension that finds the entity under the cursor
+///
+///Picks with the last camera rendering to the screen whose viewport contains the cursor, the
+///meshes that are not visible can't be picked
+pub struct Picking {
+    ///Priority of the extension
+    pub priority: u32,
+    ///How the entities are picked
+    pub mode: PickingMode,
+    ///Whether the entity under the cursor is picked every frame
+    pub enabled: bool,
+    result: Option<PickResult>,
+    gpu: Option<PickingGpu>,
+    ///Pick drawn during this frame, the readback starts after the commands are submitted
+    recorded: Option<PendingPick>,
+    ///Pick that is being read back
+    pending: Option<(
+        PendingPick,
+        mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    )>,
+}
+
+impl Default for Picking {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Picking {
+    ///Creates a new [`Picking`] extension
+    #[must_use]
+    pub const fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            mode: PickingMode::Gpu,
+            enabled: true,
+            result: None,
+            gpu: None,
+            recorded: None,
+            pending: None,
+        }
+    }
+
+    ///Returns the entity under the cursor and the point where it was hit
+    ///
+    ///Returns `None` if there is nothing under the cursor. With [`PickingMode::Gpu`] the result
+    ///is from the last pick that was read back, the entity may no longer exist
+    #[must_use]
+    pub const fn get_result(&self) -> Option<PickResult> {
+        self.result
+    }
+
+    fn create_gpu() -> PickingGpu {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/picking.wgsl"));
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Picking"),
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let [vertices, matrices] = vertex_binding();
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Picking"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    vertices,
+                    matrices,
+                    //Index of the entity
+                    wgpu::VertexBufferLayout {
+                        array_stride: 4,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[wgpu::VertexAttribute {
+                            format: wgpu::VertexFormat::Uint32,
+                            offset: 0,
+                            shader_location: 7,
+                        }],
+                    },
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            //Not culled, same as the cpu raycasts
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ID_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        let pixel = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let id_texture = pixel(
+            "Picking ids",
+            ID_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth_texture = pixel(
+            "Picking depth",
+            wgpu::TextureFormat::Depth32Float,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+
+        PickingGpu {
+            pipeline,
+            id_view: id_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            id_texture,
+            depth_view: depth_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            readback: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Picking readback"),
+                size: ID_FORMAT.block_copy_size(None).unwrap().into(),
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            matrices: None,
+            indices: None,
+        }
+    }
+
+    ///Finishes the pick that is being read back, returns false if it is still in progress
+    fn finish_readback(&mut self) -> bool {
+        let Some((pick, receiver)) = &self.pending else {
+            return true;
+        };
+
+        _ = DEVICE.get().unwrap().poll(wgpu::PollType::Poll);
+        match receiver.try_recv() {
+            Ok(Ok(())) => {
+                let readback = &self.gpu.as_ref().unwrap().readback;
+                let data = readback.slice(..).get_mapped_range();
+                let pixel = bytemuck::pod_read_unaligned::<[u32; 2]>(&data);
+                drop(data);
+                readback.unmap();
+
+                self.result = resolve_pick(pixel, pick);
+            }
+            Ok(Err(e)) => log::error!("Could not read back the picked entity {e:?}"),
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.pending = None;
+        true
+    }
+
+    ///Draws the meshes under the cursor into the id buffer and copies the picked pixel into the
+    ///readback buffer
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        clippy::too_many_lines
+    )]
+    fn record_pick(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        world: &World,
+        assets: &AssetStore,
+        camera: &Camera,
+        cursor: Vec2,
+    ) {
+        let viewport = camera.viewport.pixels(camera.target_size());
+        let pixel = (cursor.x.floor(), cursor.y.floor());
+        let Some(ndc) = pixel_to_ndc(viewport, Vec2::new(pixel.0 + 0.5, pixel.1 + 0.5)) else {
+            self.result = None;
+            return;
+        };
+        let Some(inverse) = camera.inverse_matrix() else {
+            self.result = None;
+            return;
+        };
+
+        //Sorted by mesh, so every mesh is drawn with one instanced draw call
+        let mut entities = Vec::new();
+        let mut instances = Vec::new();
+        for entity in world
+            .get_all_entities_with_component::<components::mesh::Mesh>()
+            .unwrap_or_default()
+        {
+            let entity = entity.borrow();
+            let mesh = entity.get_component::<components::mesh::Mesh>().unwrap();
+            let mesh = mesh.borrow();
+            let Some(mesh_id) = mesh.get_mesh_id().filter(|_| mesh.get_visible()) else {
+                continue;
+            };
+            entities.push(entity.get_id());
+            instances.push((mesh_id, mesh.get_matrix(), entities.len() as u32));
+        }
+        instances.sort_unstable_by_key(|i| i.0);
+
+        let device = DEVICE.get().unwrap();
+        let gpu = self.gpu.get_or_insert_with(Self::create_gpu);
+
+        if !instances.is_empty() {
+            let matrices = instances.iter().map(|i| i.1).collect::<Vec<_>>();
+            let indices = instances.iter().map(|i| i.2).collect::<Vec<_>>();
+
+            let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+            for (buffer, data, label) in [
+                (
+                    &mut gpu.matrices,
+                    bytemuck::cast_slice::<Mat4x4, u8>(&matrices),
+                    "Picking matrices",
+                ),
+                (
+                    &mut gpu.indices,
+                    bytemuck::cast_slice(&indices),
+                    "Picking indices",
+                ),
+            ] {
+                let size = data.len() as u64;
+                if buffer.as_ref().is_none_or(|b| b.size() < size) {
+                    *buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(label),
+                        size: size.next_power_of_two(),
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }));
+                }
+                belt.write_buffer(
+                    encoder,
+                    buffer.as_ref().unwrap(),
+                    0,
+                    NonZeroU64::new(size).unwrap(),
+                    device,
+                )
+                .copy_from_slice(data);
+            }
+            drop(belt);
+        }
+
+        camera.update_gpu(encoder);
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Picking"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &gpu.id_view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &gpu.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        if !instances.is_empty() {
+            //The viewport of the camera, moved so the picked pixel is the only pixel of the target
+            pass.set_viewport(
+                viewport.0 as f32 - pixel.0,
+                viewport.1 as f32 - pixel.1,
+                viewport.2 as f32,
+                viewport.3 as f32,
+                0.0,
+                1.0,
+            );
+            pass.set_pipeline(&gpu.pipeline);
+            camera.set_bindgroup(&mut pass);
+            pass.set_vertex_buffer(1, gpu.matrices.as_ref().unwrap().slice(..));
+            pass.set_vertex_buffer(2, gpu.indices.as_ref().unwrap().slice(..));
+
+            let mut start = 0;
+            for group in instances.chunk_by(|a, b| a.0 == b.0) {
+                let end = start + group.len() as u32;
+                let mesh = assets.borrow_by_id::<Mesh>(group[0].0).unwrap();
+                pass.set_vertex_buffer(0, mesh.get_vertex_buffer().slice(..));
+                pass.set_index_buffer(mesh.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(mesh.get_lod_range(0), 0, start..end);
+                drop(mesh);
+                start = end;
+            }
+        }
+        drop(pass);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfoBase {
+                texture: &gpu.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfoBase {
+                buffer: &gpu.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.recorded = Some(PendingPick {
+            entities,
+            inverse,
+            ndc,
+        });
+    }
+}
+
+impl RenderNode for Picking {
+    fn setup(&mut self, _: &mut NodeBuilder) {
+        //Uses its own targets, so it doesn't depend on any other node
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        //Only one pick is read back at a time
+        if !self.finish_readback() || !self.enabled {
+            return;
+        }
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Picking");
+
+        let cursor = crate::input::cursor_position();
+        let cameras = Cameras::new(context.world);
+        let cameras = cameras.borrow();
+        let Some(camera) = cameras
+            .iter()
+            .rev()
+            .find(|c| c.target.is_none() && c.screen_to_ndc(cursor).is_some())
+        else {
+            self.result = None;
+            return;
+        };
+
+        match self.mode {
+            PickingMode::Gpu => {
+                self.record_pick(
+                    context.encoder,
+                    context.world,
+                    context.assets,
+                    camera,
+                    cursor,
+                );
+            }
+            PickingMode::Cpu => {
+                self.result = camera
+                    .screen_ray(cursor)
+                    .and_then(|ray| raycast(context.world, context.assets, &ray));
+            }
+        }
+    }
+
+    fn post_run(&mut self, _: &GraphResources) {
+        let Some(pick) = self.recorded.take() else {
+            return;
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        self.gpu.as_ref().unwrap().readback.slice(..).map_async(
+            wgpu::MapMode::Read,
+            move |result| {
+                _ = sender.send(result);
+            },
+        );
+        self.pending = Some((pick, receiver));
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+///Casts the ray against the visible meshes of the world and returns the closest hit
+///
+///The ray is tested against the triangles of the meshes that keep them on the cpu, see
+///[`Mesh::set_keep_triangles`], and against the bounding boxes of the other meshes
+///
+///# Panics
+///Panics if a mesh asset used by a mesh component does not exist
+#[must_use]
+pub fn raycast(world: &World, assets: &AssetStore, ray: &Ray) -> Option<PickResult> {
+    world
+        .get_all_entities_with_component::<components::mesh::Mesh>()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entity| {
+            let entity = entity.borrow();
+            let id = entity.get_id();
+            let component = entity.get_component::<components::mesh::Mesh>().unwrap();
+            drop(entity);
+            let component = component.borrow();
+            let mesh_id = component
+                .get_mesh_id()
+                .filter(|_| component.get_visible())?;
+            let matrix = component.get_transform().borrow().matrix();
+            drop(component);
+
+            let mesh = assets.borrow_by_id::<Mesh>(mesh_id).unwrap();
+            hit_distance(ray, &matrix, &mesh.get_bounds(), mesh.get_triangles())
+                .map(|distance| (id, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, distance)| PickResult {
+            entity,
+            point: ray.at(distance),
+        })
+}
+
+///Returns the distance along the world space ray at which it hits the mesh with the matrix
+fn hit_distance(
+    ray: &Ray,
+    matrix: &Mat4x4,
+    bounds: &Aabb,
+    triangles: Option<&[[Vec3; 3]]>,
+) -> Option<f32> {
+    let local = ray.transformed(&matrix.inverted()?);
+    let distance = local.intersect_aabb(bounds)?;
+
+    triangles.map_or(Some(distance), |triangles| {
+        triangles
+            .iter()
+            .filter_map(|t| local.intersect_triangle(t))
+            .min_by(f32::total_cmp)
+    })
+}
+
+///Returns the entity and the point of the pixel that was read back from the id buffer
+fn resolve_pick(pixel: [u32; 2], pick: &PendingPick) -> Option<PickResult> {
+    let index = pixel[0].checked_sub(1)?;
+    Some(PickResult {
+        entity: *pick.entities.get(index as usize)?,
+        point: unproject_ndc(&pick.inverse, pick.ndc, f32::from_bits(pixel[1]))?,
+    })
+}
+
+#[test]
+fn test_picking_hits() {
+    use crate::math::{Quaternion, Vector as _};
+
+    let ray = Ray::new(Vec3::new(0, 0, -10), Vec3::new(0, 0, 1));
+    let bounds = Aabb::new(Vec3::new(-1, -1, -1), Vec3::new(1, 1, 1));
+
+    //A box scaled 2 times and moved along the ray is entered at z = -2
+    let matrix = Mat4x4::transform_matrix_euler(
+        &Vec3::new(0, 0, 0),
+        &Vec3::new(2, 2, 2),
+        &Quaternion::default(),
+    );
+    let distance = hit_distance(&ray, &matrix, &bounds, None).unwrap();
+    assert!((distance - 8.0).abs() < 0.001);
+    let moved = Mat4x4::transform_matrix_euler(
+        &Vec3::new(5, 0, 0),
+        &Vec3::new(1, 1, 1),
+        &Quaternion::default(),
+    );
+    assert_eq!(hit_distance(&ray, &moved, &bounds, None), None);
+
+    //Triangles are tested instead of the box, the diagonal triangle is hit at z = 0.5
+    let triangles = [[
+        Vec3::new(-1, -1, 1),
+        Vec3::new(1, -1, 0),
+        Vec3::new(0, 1, 0.5),
+    ]];
+    let distance = hit_distance(&ray, &Mat4x4::identity(), &bounds, Some(&triangles)).unwrap();
+    assert!((ray.at(distance) - Vec3::new(0, 0, 0.5)).length() < 0.01);
+    let missed = [[
+        Vec3::new(0.5, 0.5, 0),
+        Vec3::new(1, 0.5, 0),
+        Vec3::new(1, 1, 0),
+    ]];
+    assert_eq!(
+        hit_distance(&ray, &Mat4x4::identity(), &bounds, Some(&missed)),
+        None
+    );
+
+    //Index 0 is the cleared background, indices start from 1
+    let inverse = (Mat4x4::orth_projection(-1.0, 1.0, -1.0, 1.0, 0.0, 10.0))
+        .transpose()
+        .inverted()
+        .unwrap();
+    let pick = PendingPick {
+        entities: vec![7, 9],
+        inverse,
+        ndc: Vec2::new(0.0, 0.0),
+    };
+    assert_eq!(resolve_pick([0, 0.5f32.to_bits()], &pick), None);
+    assert_eq!(resolve_pick([3, 0.5f32.to_bits()], &pick), None);
+    let result = resolve_pick([2, 0.5f32.to_bits()], &pick).unwrap();
+    assert_eq!(result.entity, 9);
+    let expected = unproject_ndc(&inverse, Vec2::new(0.0, 0.0), 0.5).unwrap();
+    assert!((result.point - expected).length() < 0.0001);
+}
@@ -0,0 +1,568 @@
+use std::num::NonZeroU64;
+
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, FORMAT, STAGING_BELT, UUID,
+    assets::{ColorSpace, Texture},
+    rendering::{
+        HDR_COLOR, HDR_FORMAT,
+        graph::{NodeBuilder, NodeContext, RenderNode, SURFACE, TextureDescription, TextureSize},
+    },
+};
+
+///Name of the tonemapped texture that fxaa is applied to
+const TONEMAPPED: &str = "post_processing_tonemapped";
+
+const POST_PROCESSING_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Post processing binding"),
+        entries: &[
+            texture_entry(0),
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            texture_entry(3),
+            texture_entry(4),
+        ],
+    };
+
+const fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+///Returns the name of a level of the bloom mip chain
+fn bloom_level(level: u32) -> String {
+    format!("post_processing_bloom_{level}")
+}
+
+///How hdr colors are mapped to the range the screen can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapping {
+    ///Colors are clamped
+    None,
+    ///Simple reinhard operator, never fully saturates
+    Reinhard,
+    ///Filmic aces curve
+    #[default]
+    Aces,
+}
+
+///Settings of the bloom effect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    ///Brightness above which pixels start to bloom
+    pub threshold: f32,
+    ///Softness of the threshold, relative to the threshold
+    pub knee: f32,
+    ///Strength of the bloom added to the image
+    pub intensity: f32,
+    ///Number of mips the bloom is blurred over, more levels make the bloom wider
+    pub levels: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.1,
+            levels: 5,
+        }
+    }
+}
+
+///Settings of the vignette effect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    ///How much the corners of the screen are darkened, from 0 to 1
+    pub intensity: f32,
+    ///How far towards the center of the screen the darkening reaches, from 0 to 1
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            smoothness: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+struct Params {
+    exposure: f32,
+    tonemapping: u32,
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+    bloom_knee: f32,
+    vignette_intensity: f32,
+    vignette_smoothness: f32,
+    lut_mode: u32,
+    lut_size: f32,
+    encode_srgb: u32,
+    padding: [u32; 2],
+}
+
+///Gpu resources of the effects
+struct PostProcessingGpu {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+    ///Bound in place of disabled effects
+    black: wgpu::TextureView,
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+    fxaa: wgpu::RenderPipeline,
+}
+
+impl PostProcessingGpu {
+    fn new() -> Self {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/post_process.wgsl"));
+        let layout = device.create_bind_group_layout(&POST_PROCESSING_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post processing"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline =
+            |entry_point: &str, format: wgpu::TextureFormat, blend: Option<wgpu::BlendState>| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Post processing"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some(entry_point),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    multiview: None,
+                    cache: None,
+                })
+            };
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let format = *FORMAT.get().unwrap();
+
+        let black = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Post processing black"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Post processing"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+            params: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Post processing parameters"),
+                size: size_of::<Params>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            black,
+            prefilter: create_pipeline("bloom_prefilter", HDR_FORMAT, None),
+            downsample: create_pipeline("bloom_downsample", HDR_FORMAT, None),
+            upsample: create_pipeline(
+                "bloom_upsample",
+                HDR_FORMAT,
+                Some(wgpu::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+            ),
+            composite: create_pipeline("composite", format, None),
+            fxaa: create_pipeline("fxaa", format, None),
+            layout,
+        }
+    }
+
+    fn bind_group(
+        &self,
+        source: &wgpu::TextureView,
+        bloom: Option<&wgpu::TextureView>,
+        lut: Option<&wgpu::TextureView>,
+    ) -> wgpu::BindGroup {
+        DEVICE
+            .get()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post processing"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(bloom.unwrap_or(&self.black)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(lut.unwrap_or(&self.black)),
+                    },
+                ],
+            })
+    }
+}
+
+///Draws a fullscreen triangle into the target
+fn pass(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Post processing pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+///Applies post processing effects to the [`HDR_COLOR`] texture and writes the result into the
+///surface
+///
+///The effects are applied in this order: bloom, exposure, tonemapping, color grading, vignette and
+///fxaa. Every effect can be toggled or changed at any time using the fields of the node.
+///
+///Hdr rendering must be enabled using [`crate::rendering::set_hdr`], otherwise rendering panics,
+///since there is no [`HDR_COLOR`] texture
+///
+///# Usage
+///```
+///# use lunar_engine::rendering::{extensions::{Base, post_processing::PostProcessing}, render};
+///# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, post_processing: PostProcessing}
+///fn init(state: &mut State) {
+/// //Before any materials are initialized
+/// lunar_engine::rendering::set_hdr(true);
+/// state.post_processing = PostProcessing::default();
+///}
+///
+///fn update(state: &mut State) {
+/// render(
+///   &state.world,
+///   &mut state.assets,
+///   &mut [&mut state.base, &mut state.post_processing]
+///  );
+///}
+///```
+pub struct PostProcessing {
+    ///Priority of the node, compared to other nodes that write the surface
+    pub priority: u32,
+    ///Multiplier of the scene color, applied before tonemapping
+    pub exposure: f32,
+    ///Tonemapping operator
+    pub tonemapping: Tonemapping,
+    ///Bloom settings, disabled if `None`
+    pub bloom: Option<Bloom>,
+    ///Id of the color grading lut texture, disabled if `None`
+    ///
+    ///The lut is a horizontal strip of `size` slices of `size` by `size` pixels, for example a
+    ///256x16 image. Blue selects the slice, red increases to the right and green increases
+    ///downwards
+    pub color_grading: Option<UUID>,
+    ///Vignette settings, disabled if `None`
+    pub vignette: Option<Vignette>,
+    ///Whether fast approximate anti aliasing is applied
+    pub fxaa: bool,
+    gpu: Option<PostProcessingGpu>,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            priority: 0,
+            exposure: 1.0,
+            tonemapping: Tonemapping::default(),
+            bloom: Some(Bloom::default()),
+            color_grading: None,
+            vignette: None,
+            fxaa: true,
+            gpu: None,
+        }
+    }
+}
+
+impl PostProcessing {
+    ///Creates a new node with the given priority and the default effects
+    #[must_use]
+    pub fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            ..Default::default()
+        }
+    }
+
+    ///Returns the number of bloom mips, 0 if bloom is disabled
+    fn bloom_levels(&self) -> u32 {
+        self.bloom.map_or(0, |b| b.levels.clamp(1, 8))
+    }
+
+    ///Returns the shader parameters, `lut` is the size and color space of the lut texture
+    fn params(&self, lut: Option<(u32, ColorSpace)>, encode_srgb: bool) -> Params {
+        let bloom = self.bloom.unwrap_or_else(|| Bloom {
+            intensity: 0.0,
+            ..Default::default()
+        });
+        let vignette = self.vignette.unwrap_or_else(|| Vignette {
+            intensity: 0.0,
+            ..Default::default()
+        });
+
+        #[allow(clippy::cast_precision_loss)]
+        Params {
+            exposure: self.exposure,
+            tonemapping: self.tonemapping as u32,
+            bloom_intensity: bloom.intensity,
+            bloom_threshold: bloom.threshold,
+            bloom_knee: bloom.knee,
+            vignette_intensity: vignette.intensity,
+            vignette_smoothness: vignette.smoothness,
+            lut_mode: match lut {
+                None => 0,
+                Some((_, ColorSpace::Srgb)) => 1,
+                Some((_, ColorSpace::Linear)) => 2,
+            },
+            lut_size: lut.map_or(0.0, |l| l.0 as f32),
+            encode_srgb: u32::from(encode_srgb),
+            padding: [0; 2],
+        }
+    }
+}
+
+impl RenderNode for PostProcessing {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.read(HDR_COLOR);
+
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        for level in 0..self.bloom_levels() {
+            #[allow(clippy::cast_possible_wrap)]
+            builder.create_texture(
+                &bloom_level(level),
+                TextureDescription::new(
+                    TextureSize::Scaled(0.5f32.powi(level as i32 + 1)),
+                    HDR_FORMAT,
+                    usage,
+                ),
+            );
+        }
+        if self.fxaa {
+            builder.create_texture(
+                TONEMAPPED,
+                TextureDescription::new(TextureSize::Surface, *FORMAT.get().unwrap(), usage),
+            );
+        }
+        builder.write(SURFACE);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Post processing");
+
+        let hdr = context.texture(HDR_COLOR);
+        let surface = context.texture(SURFACE);
+        let levels = (0..self.bloom_levels())
+            .map(|l| context.texture(&bloom_level(l)))
+            .collect::<Vec<_>>();
+        let tonemapped = self.fxaa.then(|| context.texture(TONEMAPPED));
+
+        //Find the lut
+        let mut lut = None;
+        if let Some(id) = self.color_grading {
+            match context.assets.borrow_by_id::<Texture>(id) {
+                Ok(texture) if !texture.is_cubemap() && texture.texture.is_some() => {
+                    let size = texture.texture.as_ref().unwrap().height();
+                    lut = Some((texture.create_view(), size, texture.color_space()));
+                }
+                _ => log::error!("Color grading lut is not an initialized 2d texture"),
+            }
+        }
+
+        if self.gpu.is_none() {
+            self.gpu = Some(PostProcessingGpu::new());
+        }
+        let gpu = self.gpu.as_ref().unwrap();
+        let encoder = &mut *context.encoder;
+
+        let params = self.params(
+            lut.as_ref().map(|l| (l.1, l.2)),
+            !FORMAT.get().unwrap().is_srgb(),
+        );
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            &gpu.params,
+            0,
+            NonZeroU64::new(size_of::<Params>() as u64).unwrap(),
+            DEVICE.get().unwrap(),
+        )
+        .copy_from_slice(bytemuck::bytes_of(&params));
+        drop(belt);
+
+        //Bloom, thresholded and downsampled into the mip chain, then blurred back up
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        if let Some((first, _)) = levels.split_first() {
+            pass(
+                encoder,
+                &gpu.prefilter,
+                &gpu.bind_group(hdr, None, None),
+                first,
+                clear,
+            );
+            for pair in levels.windows(2) {
+                pass(
+                    encoder,
+                    &gpu.downsample,
+                    &gpu.bind_group(pair[0], None, None),
+                    pair[1],
+                    clear,
+                );
+            }
+            for pair in levels.windows(2).rev() {
+                pass(
+                    encoder,
+                    &gpu.upsample,
+                    &gpu.bind_group(pair[1], None, None),
+                    pair[0],
+                    wgpu::LoadOp::Load,
+                );
+            }
+        }
+
+        pass(
+            encoder,
+            &gpu.composite,
+            &gpu.bind_group(hdr, levels.first().copied(), lut.as_ref().map(|l| &l.0)),
+            tonemapped.unwrap_or(surface),
+            clear,
+        );
+
+        if let Some(tonemapped) = tonemapped {
+            pass(
+                encoder,
+                &gpu.fxaa,
+                &gpu.bind_group(tonemapped, None, None),
+                surface,
+                clear,
+            );
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_post_processing_params() {
+    let mut post = PostProcessing {
+        exposure: 2.0,
+        tonemapping: Tonemapping::Reinhard,
+        vignette: Some(Vignette::default()),
+        ..Default::default()
+    };
+    let params = post.params(Some((16, ColorSpace::Linear)), true);
+    assert_eq!(params.exposure, 2.0);
+    assert_eq!(params.tonemapping, 1);
+    assert_eq!(params.bloom_intensity, Bloom::default().intensity);
+    assert_eq!(params.vignette_intensity, Vignette::default().intensity);
+    assert_eq!(params.lut_mode, 2);
+    assert_eq!(params.lut_size, 16.0);
+    assert_eq!(params.encode_srgb, 1);
+    assert_eq!(post.bloom_levels(), 5);
+
+    //Disabled effects have no influence
+    post.bloom = None;
+    post.vignette = None;
+    let params = post.params(None, false);
+    assert_eq!(params.bloom_intensity, 0.0);
+    assert_eq!(params.vignette_intensity, 0.0);
+    assert_eq!(params.lut_mode, 0);
+    assert_eq!(post.bloom_levels(), 0);
+
+    assert_eq!(size_of::<Params>(), 48);
+}
@@ -5,7 +5,7 @@ use lunar_png::{CompressionLevel, Image, PngEncodingOptions};
 
 use crate::{
     internal::{DEVICE, FORMAT, QUEUE, RESOLUTION},
-    rendering::extensions::RenderingExtension,
+    rendering::graph::{GraphResources, NodeBuilder, NodeContext, RenderNode, SURFACE},
 };
 
 ///An extension for capturing screenshots
@@ -167,18 +167,16 @@ fn save_image(
     Ok(())
 }
 
-impl RenderingExtension for Screenshot {
-    fn render(
-        &mut self,
-        _: &mut wgpu::CommandEncoder,
-        _: &crate::ecs::World,
-        _: &mut crate::asset_managment::AssetStore,
-        _: &super::AttachmentData,
-    ) {
+impl RenderNode for Screenshot {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.read(SURFACE);
+    }
+
+    fn run(&mut self, _: &mut NodeContext) {
         //DO nothing :3
     }
 
-    fn post_render(&mut self, attachment_data: &super::AttachmentData) {
+    fn post_run(&mut self, resources: &GraphResources) {
         if !crate::APP_INFO
             .get()
             .unwrap()
@@ -235,7 +233,7 @@ impl RenderingExtension for Screenshot {
 
         enc.copy_texture_to_buffer(
             wgpu::TexelCopyTextureInfoBase {
-                texture: attachment_data.color.texture(),
+                texture: resources.raw_texture(SURFACE),
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -0,0 +1,293 @@
+use std::num::NonZeroU64;
+
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, STAGING_BELT,
+    asset_managment::AssetStore,
+    assets::{Material, Mesh, materials::helpers::vertex_binding},
+    components::{self, light::SpotLight},
+    ecs::{ComponentReference, World},
+    grimoire::{CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR, SHADOW_MAP_SIZE},
+    math::{Mat4x4, Vec4},
+};
+
+use super::MAX_SPOT_SHADOWS;
+
+///Camera data of a shadow map, same layout as the one used by the cameras
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct ShadowCamera {
+    matrix: Mat4x4,
+    t_matrix: Mat4x4,
+    position: Vec4,
+}
+
+///Shadow maps of the spot lights, one layer of a depth texture array per light
+pub(super) struct SpotShadows {
+    ///View of all the layers, sampled by the lit materials
+    pub array_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    layer_views: Vec<wgpu::TextureView>,
+    pipeline: wgpu::RenderPipeline,
+    cameras: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    //Matrices of all the shadow casting meshes, sorted by mesh
+    instances: Option<wgpu::Buffer>,
+}
+
+impl SpotShadows {
+    pub(super) fn new() -> Self {
+        let device = DEVICE.get().unwrap();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Spot light shadow maps"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: MAX_SPOT_SHADOWS,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Spot light shadow maps"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..MAX_SPOT_SHADOWS)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Spot light shadow map"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let cameras = (0..MAX_SPOT_SHADOWS)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Shadow camera"),
+                    size: size_of::<ShadowCamera>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Shadow camera"),
+                    layout: &camera_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                (buffer, bind_group)
+            })
+            .collect();
+
+        Self {
+            array_view,
+            sampler,
+            layer_views,
+            pipeline: Self::create_pipeline(&camera_layout),
+            cameras,
+            instances: None,
+        }
+    }
+
+    fn create_pipeline(camera_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/vertex.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadows"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadows"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("main"),
+                buffers: &vertex_binding(),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            //Not culled, so single sided meshes cast shadows too
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                //Avoids shadow acne on surfaces facing away from the light
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: None,
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    ///Renders the shadow maps of the lights that cast shadows
+    ///
+    ///Only visible meshes that are not animated and not transparent cast shadows
+    pub(super) fn render(
+        &mut self,
+        lights: &[ComponentReference<SpotLight>],
+        encoder: &mut wgpu::CommandEncoder,
+        world: &World,
+        assets: &AssetStore,
+    ) {
+        let indices = shadow_indices(lights);
+        if indices.iter().all(|i| *i < 0) {
+            return;
+        }
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Spot light shadows");
+
+        //Collect the casters sorted by mesh, so every mesh is drawn with one instanced draw call
+        let mut casters = world
+            .get_all_components::<components::mesh::Mesh>()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|m| {
+                let m = m.borrow();
+                let material = m.get_material_id()?;
+                let casts = m.get_visible()
+                    && !m.is_animated()
+                    && !assets
+                        .borrow_by_id::<Material>(material)
+                        .is_ok_and(|m| m.is_transparent());
+                casts.then(|| (m.get_mesh_id().unwrap(), m.get_matrix()))
+            })
+            .collect::<Vec<_>>();
+        if casters.is_empty() {
+            return;
+        }
+        casters.sort_unstable_by_key(|c| c.0);
+
+        let device = DEVICE.get().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+
+        let matrices = casters.iter().map(|c| c.1).collect::<Vec<_>>();
+        let size = (matrices.len() * size_of::<Mat4x4>()) as u64;
+        if self.instances.as_ref().is_none_or(|b| b.size() < size) {
+            self.instances = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Shadow caster instances"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let instances = self.instances.as_ref().unwrap();
+        belt.write_buffer(
+            encoder,
+            instances,
+            0,
+            NonZeroU64::new(size).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&matrices));
+
+        let shadowed = lights
+            .iter()
+            .zip(indices)
+            .filter_map(|(light, index)| Some((light, usize::try_from(index).ok()?)))
+            .collect::<Vec<_>>();
+
+        for (light, index) in &shadowed {
+            let light = light.borrow();
+            let matrix = light.shadow_matrix();
+            let camera = ShadowCamera {
+                matrix,
+                t_matrix: matrix.inverted().unwrap_or_else(Mat4x4::identity),
+                position: light.get_position_direction().0.into(),
+            };
+            drop(light);
+            belt.write_buffer(
+                encoder,
+                &self.cameras[*index].0,
+                0,
+                NonZeroU64::new(size_of::<ShadowCamera>() as u64).unwrap(),
+                device,
+            )
+            .copy_from_slice(bytemuck::bytes_of(&camera));
+        }
+        drop(belt);
+
+        for (_, index) in shadowed {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[index],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.cameras[index].1, &[]);
+            render_pass.set_vertex_buffer(1, instances.slice(..));
+
+            let mut start = 0;
+            for group in casters.chunk_by(|a, b| a.0 == b.0) {
+                let end = start + group.len() as u32;
+                let mesh = assets.borrow_by_id::<Mesh>(group[0].0).unwrap();
+                render_pass.set_vertex_buffer(0, mesh.get_vertex_buffer().slice(..));
+                render_pass
+                    .set_index_buffer(mesh.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(mesh.get_lod_range(0), 0, start..end);
+                drop(mesh);
+                start = end;
+            }
+        }
+    }
+}
+
+///Returns the shadow map layer of every light, -1 for the lights that don't cast shadows
+///
+///The first [`MAX_SPOT_SHADOWS`] lights that cast shadows get a layer
+pub(super) fn shadow_indices(lights: &[ComponentReference<SpotLight>]) -> Vec<i32> {
+    let mut next = 0;
+    lights
+        .iter()
+        .map(|l| {
+            if l.borrow().get_cast_shadows() && next < MAX_SPOT_SHADOWS {
+                next += 1;
+                next.cast_signed() - 1
+            } else {
+                -1
+            }
+        })
+        .collect()
+}
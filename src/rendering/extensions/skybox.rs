@@ -0,0 +1,241 @@
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, RESOLUTION, UUID,
+    assets::Texture,
+    components::camera::{Cameras, ClearMode},
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    rendering::{
+        graph::{DEPTH, NodeBuilder, NodeContext, RenderNode},
+        scene_target,
+    },
+};
+
+const SKYBOX_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Skybox binding"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    };
+
+///Renders a cubemap [`Texture`] behind everything that was rendered before it, in the viewport of
+///every camera that renders to the screen and uses [`ClearMode::Default`]
+///
+///The skybox is only drawn where nothing else was, it reads the depth buffer written by the
+///[`Base`](super::Base) node, so it always runs after it, and must have a larger priority than it
+///
+///# Usage
+///```
+///# use lunar_engine::rendering::extensions::{Base, skybox::Skybox};
+///# use lunar_engine::rendering::render;
+///# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, skybox: Skybox}
+///fn update(state: &mut State) {
+/// render(
+///   &state.world,
+///   &mut state.assets,
+///   &mut [&mut state.base, &mut state.skybox]
+///  );
+///}
+///```
+pub struct Skybox {
+    ///Priority of the extension
+    pub priority: u32,
+    texture_id: UUID,
+    pipeline: Option<wgpu::RenderPipeline>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl Skybox {
+    ///Creates a new [`Skybox`] that renders the given cubemap texture
+    #[must_use]
+    pub const fn new(priority: u32, texture_id: UUID) -> Self {
+        Self {
+            priority,
+            texture_id,
+            pipeline: None,
+            bind_group: None,
+        }
+    }
+
+    ///Returns the id of the rendered cubemap
+    #[must_use]
+    pub const fn get_texture(&self) -> UUID {
+        self.texture_id
+    }
+
+    ///Sets the cubemap texture that is rendered
+    pub fn set_texture(&mut self, texture_id: UUID) {
+        self.texture_id = texture_id;
+        self.bind_group = None;
+    }
+
+    fn create_pipeline() -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/skybox.wgsl"));
+
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let skybox_layout = device.create_bind_group_layout(&SKYBOX_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox"),
+            bind_group_layouts: &[&camera_layout, &skybox_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            //Only draw where nothing was rendered
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: crate::rendering::scene_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+impl RenderNode for Skybox {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.write(scene_target());
+        builder.read(DEPTH);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        let color = context.texture(scene_target());
+        let depth_stencil = context.texture(DEPTH);
+        let encoder = &mut *context.encoder;
+        let world = context.world;
+        let assets = &*context.assets;
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Skybox render");
+
+        let cameras = Cameras::new(world);
+        let cameras: Vec<_> = cameras
+            .borrow()
+            .into_iter()
+            .filter(|c| c.target.is_none() && c.clear == ClearMode::Default)
+            .collect();
+        if cameras.is_empty() {
+            return;
+        }
+        for camera in &cameras {
+            camera.update_gpu(encoder);
+        }
+
+        if self.pipeline.is_none() {
+            self.pipeline = Some(Self::create_pipeline());
+        }
+
+        if self.bind_group.is_none() {
+            let device = DEVICE.get().unwrap();
+            let texture = assets
+                .borrow_by_id::<Texture>(self.texture_id)
+                .expect("Could not find the skybox texture");
+
+            if !texture.is_cubemap() {
+                log::error!("Skybox texture is not a cubemap");
+                return;
+            }
+
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Skybox"),
+                layout: &device.create_bind_group_layout(&SKYBOX_BIND_GROUP_LAYOUT_DESCRIPTOR),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.create_view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(texture.sampler.as_ref().unwrap()),
+                    },
+                ],
+            }));
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Skybox pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_stencil,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(1, self.bind_group.as_ref().unwrap(), &[]);
+
+        let resolution = RESOLUTION.read().unwrap();
+        let size = (resolution.width, resolution.height);
+        drop(resolution);
+        for camera in &cameras {
+            let (x, y, width, height) = camera.viewport.pixels(size);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            #[allow(clippy::cast_precision_loss)]
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            camera.set_bindgroup(&mut render_pass);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
@@ -0,0 +1,503 @@
+//! Batched rendering of 2D sprites
+//!
+//! Every [`Sprite`] in the world is drawn as a textured quad, sprites that use the same texture
+//! or atlas and are next to each other in the drawing order are drawn with a single draw call.
+//! The sprites are usually viewed by a camera with an orthographic projection, but any camera
+//! works
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::rendering::extensions::{Base, sprites::SpriteRenderer};
+//!# use lunar_engine::rendering::render;
+//!# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, sprites: SpriteRenderer}
+//! fn update(state: &mut State) {
+//!     render(
+//!         &state.world,
+//!         &mut state.assets,
+//!         &mut [&mut state.base, &mut state.sprites],
+//!     );
+//! }
+//!```
+use std::{num::NonZeroU64, ops::Range};
+
+use vec_key_value_pair::map::VecMap;
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, STAGING_BELT, UUID,
+    asset_managment::AssetStore,
+    assets::{Texture, TextureAtlas, atlas::AtlasRegion},
+    components::{
+        camera::Cameras,
+        sprite::{Sprite, SpriteRegion},
+    },
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    math::{Mat4x4, Vec2, Vec3},
+    rendering::{
+        graph::{DEPTH, NodeBuilder, NodeContext, RenderNode},
+        scene_target,
+    },
+    structures::Color,
+};
+
+const SPRITE_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Sprite texture"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    };
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    matrix: Mat4x4,
+    ///Offset of the bottom left corner and the size of the quad
+    rect: [f32; 4],
+    ///Texture coordinates of the bottom left and the top right corner
+    uv_rect: [f32; 4],
+    tint: Color,
+}
+
+///A sprite ready to be drawn
+struct DrawnSprite {
+    texture: UUID,
+    layer: i32,
+    position: Vec3,
+    instance: SpriteInstance,
+}
+
+///Renders every visible [`Sprite`] in the viewport of every camera that renders to the screen
+///
+///Sprites are sorted by their layer and then back to front, they are blended with what was
+///rendered before them and tested against the depth buffer without writing to it, so the node
+///should run after the [`Base`](super::Base) node
+pub struct SpriteRenderer {
+    ///Priority of the extension
+    pub priority: u32,
+    pipeline: Option<wgpu::RenderPipeline>,
+    instance_buffer: Option<wgpu::Buffer>,
+    bind_groups: VecMap<UUID, wgpu::BindGroup>,
+}
+
+impl Default for SpriteRenderer {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl SpriteRenderer {
+    ///Creates a new [`SpriteRenderer`]
+    #[must_use]
+    pub fn new(priority: u32) -> Self {
+        Self {
+            priority,
+            pipeline: None,
+            instance_buffer: None,
+            bind_groups: VecMap::new(),
+        }
+    }
+
+    fn create_pipeline() -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/sprite.wgsl"));
+        let camera_layout = device.create_bind_group_layout(&CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR);
+        let texture_layout = device.create_bind_group_layout(&SPRITE_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprites"),
+            bind_group_layouts: &[&camera_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprites"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<SpriteInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Float32x4,
+                        3 => Float32x4,
+                        4 => Float32x4,
+                        5 => Float32x4,
+                        6 => Float32x4
+                    ],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: crate::rendering::scene_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    ///Creates the bind group of the texture or the atlas with the id if it does not exist yet,
+    ///returns false if there is no such texture
+    fn prepare_bind_group(&mut self, assets: &AssetStore, id: UUID) -> bool {
+        if self.bind_groups.contains_key(&id) {
+            return true;
+        }
+        let create = |texture: &Texture| {
+            let device = DEVICE.get().unwrap();
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Sprite texture"),
+                layout: &device.create_bind_group_layout(&SPRITE_BIND_GROUP_LAYOUT_DESCRIPTOR),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.create_view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(texture.sampler.as_ref().unwrap()),
+                    },
+                ],
+            })
+        };
+
+        let bind_group = if assets.is_type::<TextureAtlas>(id) {
+            assets
+                .borrow_by_id::<TextureAtlas>(id)
+                .ok()
+                .and_then(|a| a.texture().map(create))
+        } else if assets.is_type::<Texture>(id) {
+            assets
+                .borrow_by_id::<Texture>(id)
+                .ok()
+                .filter(|t| !t.is_cubemap())
+                .map(|t| create(&t))
+        } else {
+            None
+        };
+
+        bind_group.is_some_and(|b| {
+            self.bind_groups.insert(id, b);
+            true
+        })
+    }
+}
+
+///Returns the drawn region of the sprite in pixels from the top left corner of the texture
+fn resolve_region(
+    region: &SpriteRegion,
+    (width, height): (u32, u32),
+    regions: &[AtlasRegion],
+) -> Option<AtlasRegion> {
+    match region {
+        SpriteRegion::Full => Some(AtlasRegion {
+            name: String::new(),
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }),
+        SpriteRegion::Index(i) => regions.get(*i).cloned(),
+        SpriteRegion::Named(name) => regions.iter().find(|r| r.name == *name).cloned(),
+        SpriteRegion::Pixels {
+            x,
+            y,
+            width,
+            height,
+        } => Some(AtlasRegion {
+            name: String::new(),
+            x: *x,
+            y: *y,
+            width: *width,
+            height: *height,
+        }),
+    }
+}
+
+///Returns the local rectangle and the texture coordinates of the sprite quad
+fn sprite_quad(
+    sprite: &Sprite,
+    region: &AtlasRegion,
+    texture_size: (u32, u32),
+) -> ([f32; 4], [f32; 4]) {
+    let size = Vec2::new(
+        region.width as f32 / sprite.pixels_per_unit,
+        region.height as f32 / sprite.pixels_per_unit,
+    );
+    let rect = [
+        -sprite.pivot.x * size.x,
+        -sprite.pivot.y * size.y,
+        size.x,
+        size.y,
+    ];
+
+    let mut uv_rect = region.uv_rect(texture_size);
+    if sprite.flip_x {
+        uv_rect.swap(0, 2);
+    }
+    if sprite.flip_y {
+        uv_rect.swap(1, 3);
+    }
+    (rect, uv_rect)
+}
+
+///Splits the sorted sprites into runs that use the same texture
+fn batches(textures: impl Iterator<Item = UUID>) -> Vec<(UUID, Range<u32>)> {
+    let mut batches: Vec<(UUID, Range<u32>)> = Vec::new();
+    for (i, texture) in (0u32..).zip(textures) {
+        match batches.last_mut() {
+            Some((id, range)) if *id == texture => range.end = i + 1,
+            _ => batches.push((texture, i..i + 1)),
+        }
+    }
+    batches
+}
+
+impl RenderNode for SpriteRenderer {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.write(scene_target());
+        builder.read(DEPTH);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        let color = context.texture(scene_target());
+        let depth_stencil = context.texture(DEPTH);
+        let encoder = &mut *context.encoder;
+        let assets = &*context.assets;
+
+        let Some(sprites) = context.world.get_all_components::<Sprite>() else {
+            return;
+        };
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Sprite render");
+
+        let mut drawn = Vec::new();
+        for sprite in &sprites {
+            let sprite = sprite.borrow();
+            let Some(texture) = sprite.texture.filter(|_| sprite.visible) else {
+                continue;
+            };
+            if !self.prepare_bind_group(assets, texture) {
+                log::warn!("Sprite texture is not a 2D texture or an atlas");
+                continue;
+            }
+
+            let (size, region) = if assets.is_type::<TextureAtlas>(texture) {
+                let atlas = assets.borrow_by_id::<TextureAtlas>(texture).unwrap();
+                let Some(size) = atlas.size() else {
+                    continue;
+                };
+                (size, resolve_region(&sprite.region, size, atlas.regions()))
+            } else {
+                let Some(size) = assets.borrow_by_id::<Texture>(texture).unwrap().size() else {
+                    continue;
+                };
+                (size, resolve_region(&sprite.region, size, &[]))
+            };
+            let Some(region) = region else {
+                log::warn!("Sprite region {:?} does not exist", sprite.region);
+                continue;
+            };
+
+            let (rect, uv_rect) = sprite_quad(&sprite, &region, size);
+            drawn.push(DrawnSprite {
+                texture,
+                layer: sprite.layer,
+                position: sprite.get_transform().borrow().position_global(),
+                instance: SpriteInstance {
+                    matrix: sprite.get_matrix(),
+                    rect,
+                    uv_rect,
+                    tint: sprite.tint,
+                },
+            });
+        }
+        drop(sprites);
+
+        let cameras = Cameras::new(context.world);
+        let cameras: Vec<_> = cameras
+            .borrow()
+            .into_iter()
+            .filter(|c| c.target.is_none())
+            .collect();
+        if cameras.is_empty() || drawn.is_empty() {
+            return;
+        }
+
+        //Every camera sees the sprites in its own order
+        let mut instances = Vec::with_capacity(drawn.len() * cameras.len());
+        let mut camera_batches = Vec::with_capacity(cameras.len());
+        for camera in &cameras {
+            camera.update_gpu(encoder);
+
+            let mut order = drawn
+                .iter()
+                .map(|s| (s, camera.view_depth(s.position)))
+                .collect::<Vec<_>>();
+            order.sort_by(|a, b| a.0.layer.cmp(&b.0.layer).then(b.1.total_cmp(&a.1)));
+
+            let start = instances.len() as u32;
+            camera_batches.push(
+                batches(order.iter().map(|s| s.0.texture))
+                    .into_iter()
+                    .map(|(id, range)| (id, range.start + start..range.end + start))
+                    .collect::<Vec<_>>(),
+            );
+            instances.extend(order.iter().map(|s| s.0.instance));
+        }
+
+        let device = DEVICE.get().unwrap();
+        let size = size_of_val(instances.as_slice()) as u64;
+        if self
+            .instance_buffer
+            .as_ref()
+            .is_none_or(|b| b.size() < size)
+        {
+            self.instance_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Sprite instances"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let instance_buffer = self.instance_buffer.as_ref().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            instance_buffer,
+            0,
+            NonZeroU64::new(size).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&instances));
+        drop(belt);
+
+        let pipeline = &*self.pipeline.get_or_insert_with(Self::create_pipeline);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sprite pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_stencil,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+
+        for (camera, batches) in cameras.iter().zip(camera_batches) {
+            let (x, y, width, height) = camera.viewport.pixels(camera.target_size());
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            camera.set_bindgroup(&mut render_pass);
+
+            for (texture, range) in batches {
+                render_pass.set_bind_group(1, self.bind_groups.get(&texture).unwrap(), &[]);
+                render_pass.draw(0..4, range);
+            }
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_sprite_batches() {
+    use crate::ecs::Component as _;
+
+    let texture_size = (64, 32);
+    let regions = [AtlasRegion {
+        name: "idle".to_owned(),
+        x: 16,
+        y: 0,
+        width: 16,
+        height: 32,
+    }];
+
+    let full = resolve_region(&SpriteRegion::Full, texture_size, &regions).unwrap();
+    assert_eq!((full.width, full.height), texture_size);
+    assert_eq!(
+        resolve_region(
+            &SpriteRegion::Named("idle".to_owned()),
+            texture_size,
+            &regions
+        ),
+        resolve_region(&SpriteRegion::Index(0), texture_size, &regions)
+    );
+    assert!(resolve_region(&SpriteRegion::Index(1), texture_size, &regions).is_none());
+
+    let mut sprite = Sprite::mew();
+    sprite.pixels_per_unit = 16.0;
+    let (rect, uv_rect) = sprite_quad(&sprite, &regions[0], texture_size);
+    //The pivot is in the center
+    assert_eq!(rect, [-0.5, -1.0, 1.0, 2.0]);
+    assert_eq!(uv_rect, [0.25, 0.0, 0.5, 1.0]);
+
+    sprite.flip_x = true;
+    sprite.pivot = Vec2::new(0, 0);
+    let (rect, uv_rect) = sprite_quad(&sprite, &regions[0], texture_size);
+    assert_eq!(rect, [0.0, 0.0, 1.0, 2.0]);
+    assert_eq!(uv_rect, [0.5, 0.0, 0.25, 1.0]);
+
+    let (a, b): (UUID, UUID) = (1, 2);
+    assert_eq!(
+        batches([a, a, b, a].into_iter()),
+        vec![(a, 0..2), (b, 2..3), (a, 3..4)]
+    );
+}
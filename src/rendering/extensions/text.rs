@@ -0,0 +1,672 @@
+//! Rendering of [`Text`] components
+//!
+//! Every text is laid out with its [`Font`], the glyphs it uses are rasterized into the glyph
+//! atlas of the font when they are first drawn and every glyph is drawn as a textured quad. One
+//! [`TextRenderer`] draws the texts of one [`TextSpace`], so world space and screen space text
+//! need a node each
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::rendering::extensions::{Base, text::TextRenderer};
+//!# use lunar_engine::components::text::TextSpace;
+//!# use lunar_engine::rendering::render;
+//!# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, base: Base, world_text: TextRenderer, screen_text: TextRenderer}
+//! fn init(state: &mut State) {
+//!     state.world_text = TextRenderer::new(6, TextSpace::World);
+//!     //Screen text is drawn over everything, after the post processing
+//!     state.screen_text = TextRenderer::new(20, TextSpace::Screen);
+//! }
+//!
+//! fn update(state: &mut State) {
+//!     render(
+//!         &state.world,
+//!         &mut state.assets,
+//!         &mut [&mut state.base, &mut state.world_text, &mut state.screen_text],
+//!     );
+//! }
+//!```
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+use std::{num::NonZeroU64, ops::Range};
+
+use vec_key_value_pair::map::VecMap;
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, RESOLUTION, STAGING_BELT, UUID,
+    asset_managment::{Asset as _, AssetStore},
+    assets::{Font, font::GlyphRect},
+    components::{
+        camera::Cameras,
+        text::{Text, TextSpace},
+    },
+    grimoire::CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+    math::{Mat4x4, Vec3},
+    rendering::{
+        graph::{DEPTH, NodeBuilder, NodeContext, RenderNode, SURFACE},
+        scene_format, scene_target,
+    },
+    structures::Color,
+};
+
+///Size in pixels the glyphs of world space text are rasterized at, unless the font uses signed
+///distance fields
+const WORLD_RASTER_SIZE: u32 = 64;
+
+const FONT_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Glyph atlas"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    };
+
+const EMPTY_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Screen text"),
+        entries: &[],
+    };
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    ///World position for world text, normalized device coordinates for screen text
+    position: [f32; 3],
+    tex_coord: [f32; 2],
+    color: Color,
+}
+
+///A text whose glyphs are in the atlas of its font
+struct PreparedText {
+    font: UUID,
+    sdf: bool,
+    color: Color,
+    ///Size of an atlas pixel in the units of the text
+    scale: f32,
+    ///Positions of the glyph origins and where the glyphs are in the atlas
+    glyphs: Vec<([f32; 2], GlyphRect)>,
+    matrix: Mat4x4,
+    position: Vec3,
+}
+
+///Renders every visible [`Text`] in one [`TextSpace`]
+///
+///World space text is drawn in the viewport of every camera that renders to the screen, sorted
+///back to front and tested against the depth buffer without writing to it, so the node should
+///run after the [`Base`](super::Base) node. Screen space text is drawn on the surface over the
+///whole window, so the priority of the node should be larger than the priority of the
+///[`PostProcessing`](super::post_processing::PostProcessing) node
+pub struct TextRenderer {
+    ///Priority of the extension
+    pub priority: u32,
+    space: TextSpace,
+    ///Pipelines for coverage and signed distance field fonts
+    pipelines: Option<[wgpu::RenderPipeline; 2]>,
+    ///Bound in place of the camera by screen text
+    empty_bind_group: Option<wgpu::BindGroup>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    ///Bind groups of the glyph atlases and the generations of the atlases they were created for
+    bind_groups: VecMap<UUID, (u32, wgpu::BindGroup)>,
+}
+
+impl TextRenderer {
+    ///Creates a new [`TextRenderer`] that draws the texts in the space
+    #[must_use]
+    pub fn new(priority: u32, space: TextSpace) -> Self {
+        Self {
+            priority,
+            space,
+            pipelines: None,
+            empty_bind_group: None,
+            vertex_buffer: None,
+            bind_groups: VecMap::new(),
+        }
+    }
+
+    ///Returns the space of the drawn texts
+    #[must_use]
+    pub const fn space(&self) -> TextSpace {
+        self.space
+    }
+
+    fn create_pipelines(space: TextSpace) -> [wgpu::RenderPipeline; 2] {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/text.wgsl"));
+        let first_layout = device.create_bind_group_layout(match space {
+            TextSpace::World => &CAMERA_BIND_GROUP_LAYOUT_DESCRIPTOR,
+            TextSpace::Screen => &EMPTY_BIND_GROUP_LAYOUT_DESCRIPTOR,
+        });
+        let font_layout = device.create_bind_group_layout(&FONT_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text"),
+            bind_group_layouts: &[&first_layout, &font_layout],
+            push_constant_ranges: &[],
+        });
+
+        let (vertex_entry, format, depth_stencil) = match space {
+            TextSpace::World => (
+                "vs_world",
+                scene_format(),
+                Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+            ),
+            TextSpace::Screen => ("vs_screen", *crate::FORMAT.get().unwrap(), None),
+        };
+
+        ["fs_main", "fs_sdf"].map(|fragment_entry| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Text"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(vertex_entry),
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: size_of::<TextVertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x3,
+                            1 => Float32x2,
+                            2 => Float32x4
+                        ],
+                    }],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: depth_stencil.clone(),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fragment_entry),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    ///Creates the bind group of the glyph atlas of the font if it does not exist yet or if the
+    ///atlas texture was recreated
+    fn prepare_bind_group(&mut self, font: &Font, id: UUID) {
+        let Some((_, generation)) = font.atlas_info() else {
+            return;
+        };
+        if self
+            .bind_groups
+            .get(&id)
+            .is_some_and(|(g, _)| *g == generation)
+        {
+            return;
+        }
+        let (view, sampler) = font.atlas_binding().unwrap();
+        let device = DEVICE.get().unwrap();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Glyph atlas"),
+            layout: &device.create_bind_group_layout(&FONT_BIND_GROUP_LAYOUT_DESCRIPTOR),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        self.bind_groups.insert(id, (generation, bind_group));
+    }
+
+    ///Lays out the texts in the space of the node and rasterizes their glyphs
+    fn prepare_texts(&self, context: &NodeContext) -> Vec<PreparedText> {
+        let Some(texts) = context.world.get_all_components::<Text>() else {
+            return Vec::new();
+        };
+        let assets = &*context.assets;
+
+        let mut prepared = Vec::new();
+        for text in &texts {
+            let text = text.borrow();
+            if !text.visible || text.space != self.space || text.text.is_empty() {
+                continue;
+            }
+            let Some(id) = text.font else {
+                continue;
+            };
+            if !assets.is_type::<Font>(id) {
+                log::warn!("Text font is not a font");
+                continue;
+            }
+            let Ok(mut font) = assets.borrow_by_id_mut::<Font>(id) else {
+                continue;
+            };
+            if !font.is_initialized() {
+                continue;
+            }
+
+            let requested = match self.space {
+                TextSpace::World => WORLD_RASTER_SIZE,
+                TextSpace::Screen => text.size.round().max(1.0) as u32,
+            };
+            let layout = font.layout(&text.text, &text.layout_options());
+            let glyphs = layout
+                .glyphs
+                .iter()
+                .filter_map(|g| font.glyph(g.glyph, requested).map(|r| ([g.x, g.y], r)))
+                .collect();
+
+            let transform = text.get_transform();
+            let transform = transform.borrow();
+            prepared.push(PreparedText {
+                font: id,
+                sdf: font.is_sdf(),
+                color: text.color,
+                scale: text.size / font.raster_size(requested) as f32,
+                glyphs,
+                matrix: transform.matrix(),
+                position: transform.position_global(),
+            });
+        }
+        prepared
+    }
+
+    ///Returns the vertices of the glyph quads of the text
+    fn text_vertices(&self, text: &PreparedText, atlas_size: (u32, u32)) -> Vec<TextVertex> {
+        let resolution = *RESOLUTION.read().unwrap();
+        let (width, height) = (
+            resolution.width.max(1) as f32,
+            resolution.height.max(1) as f32,
+        );
+
+        let mut vertices = Vec::with_capacity(text.glyphs.len() * 6);
+        for (pen, rect) in &text.glyphs {
+            let corners = match self.space {
+                TextSpace::World => {
+                    let Some((quad, uv)) = glyph_quad(*pen, rect, text.scale, atlas_size) else {
+                        continue;
+                    };
+                    //The layout points down and the text points up
+                    let corner = |x: f32, y: f32| {
+                        let p = text.matrix.transform3(Vec3::new(x, -y, 0.0));
+                        [p.x, p.y, p.z]
+                    };
+                    (
+                        [
+                            corner(quad[0], quad[1]),
+                            corner(quad[2], quad[1]),
+                            corner(quad[0], quad[3]),
+                            corner(quad[2], quad[3]),
+                        ],
+                        uv,
+                    )
+                }
+                TextSpace::Screen => {
+                    //Glyphs are drawn at whole pixels, so they stay sharp
+                    let pen = [
+                        (text.position.x + pen[0]).round(),
+                        (text.position.y + pen[1]).round(),
+                    ];
+                    let Some((quad, uv)) = glyph_quad(pen, rect, text.scale, atlas_size) else {
+                        continue;
+                    };
+                    let corner = |x: f32, y: f32| {
+                        [
+                            (x / width).mul_add(2.0, -1.0),
+                            (y / height).mul_add(-2.0, 1.0),
+                            0.0,
+                        ]
+                    };
+                    (
+                        [
+                            corner(quad[0], quad[1]),
+                            corner(quad[2], quad[1]),
+                            corner(quad[0], quad[3]),
+                            corner(quad[2], quad[3]),
+                        ],
+                        uv,
+                    )
+                }
+            };
+            vertices.extend(quad_vertices(corners.0, corners.1, text.color));
+        }
+        vertices
+    }
+}
+
+///Returns the rectangle of the glyph quad in the units of the text and its texture coordinates,
+///as the left, top, right and bottom edges, or `None` if the glyph is empty
+pub(super) fn glyph_quad(
+    pen: [f32; 2],
+    rect: &GlyphRect,
+    scale: f32,
+    (atlas_width, atlas_height): (u32, u32),
+) -> Option<([f32; 4], [f32; 4])> {
+    if rect.width == 0 || rect.height == 0 {
+        return None;
+    }
+    let left = (rect.left as f32).mul_add(scale, pen[0]);
+    let top = (-rect.top as f32).mul_add(scale, pen[1]);
+    let quad = [
+        left,
+        top,
+        (rect.width as f32).mul_add(scale, left),
+        (rect.height as f32).mul_add(scale, top),
+    ];
+    //The atlas is stored from the top to the bottom
+    let uv = [
+        rect.x as f32 / atlas_width as f32,
+        rect.y as f32 / atlas_height as f32,
+        (rect.x + rect.width) as f32 / atlas_width as f32,
+        (rect.y + rect.height) as f32 / atlas_height as f32,
+    ];
+    Some((quad, uv))
+}
+
+///Returns the two triangles of a quad with the top left, top right, bottom left and bottom right
+///corners
+fn quad_vertices(corners: [[f32; 3]; 4], uv: [f32; 4], color: Color) -> [TextVertex; 6] {
+    let vertex = |i: usize, u, v| TextVertex {
+        position: corners[i],
+        tex_coord: [u, v],
+        color,
+    };
+    [
+        vertex(0, uv[0], uv[1]),
+        vertex(2, uv[0], uv[3]),
+        vertex(1, uv[2], uv[1]),
+        vertex(1, uv[2], uv[1]),
+        vertex(2, uv[0], uv[3]),
+        vertex(3, uv[2], uv[3]),
+    ]
+}
+
+///Splits the vertices of the texts into runs that use the same font
+fn batches<T: PartialEq + Copy>(texts: impl Iterator<Item = (T, u32)>) -> Vec<(T, Range<u32>)> {
+    let mut batches: Vec<(T, Range<u32>)> = Vec::new();
+    let mut end = 0;
+    for (key, count) in texts {
+        let start = end;
+        end += count;
+        match batches.last_mut() {
+            Some((k, range)) if *k == key => range.end = end,
+            _ => batches.push((key, start..end)),
+        }
+    }
+    batches
+}
+
+impl RenderNode for TextRenderer {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        match self.space {
+            TextSpace::World => {
+                builder.write(scene_target());
+                builder.read(DEPTH);
+            }
+            TextSpace::Screen => builder.write(SURFACE),
+        }
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Text render");
+
+        let texts = self.prepare_texts(context);
+        if texts.is_empty() {
+            return;
+        }
+
+        //The atlases only change size while the glyphs are rasterized
+        let assets: &AssetStore = context.assets;
+        let mut fonts = texts.iter().map(|t| t.font).collect::<Vec<_>>();
+        fonts.sort_unstable();
+        fonts.dedup();
+        let mut atlas_sizes = VecMap::new();
+        for id in fonts {
+            let mut font = assets.borrow_by_id_mut::<Font>(id).unwrap();
+            font.upload();
+            self.prepare_bind_group(&font, id);
+            if let Some((size, _)) = font.atlas_info() {
+                atlas_sizes.insert(id, size);
+            }
+        }
+        let vertices = texts
+            .iter()
+            .map(|t| {
+                atlas_sizes
+                    .get(&t.font)
+                    .map_or_else(Vec::new, |size| self.text_vertices(t, *size))
+            })
+            .collect::<Vec<_>>();
+
+        let encoder = &mut *context.encoder;
+        let cameras = Cameras::new(context.world);
+        let cameras: Vec<_> = match self.space {
+            TextSpace::World => cameras
+                .borrow()
+                .into_iter()
+                .filter(|c| c.target.is_none())
+                .collect(),
+            TextSpace::Screen => Vec::new(),
+        };
+        if self.space == TextSpace::World && cameras.is_empty() {
+            return;
+        }
+
+        //Every camera sees the world texts in its own order
+        let mut data = Vec::new();
+        let mut view_batches = Vec::new();
+        let orders = match self.space {
+            TextSpace::World => cameras
+                .iter()
+                .map(|camera| {
+                    camera.update_gpu(encoder);
+                    let mut order = (0..texts.len())
+                        .map(|i| (i, camera.view_depth(texts[i].position)))
+                        .collect::<Vec<_>>();
+                    order.sort_by(|a, b| b.1.total_cmp(&a.1));
+                    order.into_iter().map(|o| o.0).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>(),
+            TextSpace::Screen => vec![(0..texts.len()).collect()],
+        };
+        for order in orders {
+            let start = data.len() as u32;
+            view_batches.push(
+                batches(
+                    order
+                        .iter()
+                        .map(|i| ((texts[*i].font, texts[*i].sdf), vertices[*i].len() as u32)),
+                )
+                .into_iter()
+                .map(|(key, range)| (key, range.start + start..range.end + start))
+                .collect::<Vec<_>>(),
+            );
+            for i in order {
+                data.extend_from_slice(&vertices[i]);
+            }
+        }
+        if data.is_empty() {
+            return;
+        }
+
+        let device = DEVICE.get().unwrap();
+        let size = size_of_val(data.as_slice()) as u64;
+        if self.vertex_buffer.as_ref().is_none_or(|b| b.size() < size) {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Text vertices"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = self.vertex_buffer.as_ref().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            vertex_buffer,
+            0,
+            NonZeroU64::new(size).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&data));
+        drop(belt);
+
+        let space = self.space;
+        let pipelines = &*self
+            .pipelines
+            .get_or_insert_with(|| Self::create_pipelines(space));
+
+        let (color, depth_stencil) = match self.space {
+            TextSpace::World => (
+                context.texture(scene_target()),
+                Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: context.texture(DEPTH),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+            ),
+            TextSpace::Screen => (context.texture(SURFACE), None),
+        };
+        let mut render_pass = context
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: depth_stencil,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+        let draw = |render_pass: &mut wgpu::RenderPass,
+                    batches: Vec<((UUID, bool), Range<u32>)>| {
+            for ((font, sdf), range) in batches {
+                render_pass.set_pipeline(&pipelines[usize::from(sdf)]);
+                render_pass.set_bind_group(1, &self.bind_groups.get(&font).unwrap().1, &[]);
+                render_pass.draw(range, 0..1);
+            }
+        };
+
+        match self.space {
+            TextSpace::World => {
+                for (camera, batches) in cameras.iter().zip(view_batches) {
+                    let (x, y, width, height) = camera.viewport.pixels(camera.target_size());
+                    if width == 0 || height == 0 {
+                        continue;
+                    }
+                    render_pass.set_viewport(
+                        x as f32,
+                        y as f32,
+                        width as f32,
+                        height as f32,
+                        0.0,
+                        1.0,
+                    );
+                    render_pass.set_scissor_rect(x, y, width, height);
+                    camera.set_bindgroup(&mut render_pass);
+                    draw(&mut render_pass, batches);
+                }
+            }
+            TextSpace::Screen => {
+                let empty = self.empty_bind_group.get_or_insert_with(|| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Screen text"),
+                        layout: &device
+                            .create_bind_group_layout(&EMPTY_BIND_GROUP_LAYOUT_DESCRIPTOR),
+                        entries: &[],
+                    })
+                });
+                render_pass.set_bind_group(0, &*empty, &[]);
+                for batches in view_batches {
+                    draw(&mut render_pass, batches);
+                }
+            }
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_text_quads() {
+    let rect = GlyphRect {
+        x: 16,
+        y: 32,
+        width: 8,
+        height: 16,
+        left: 1,
+        top: 12,
+    };
+    let (quad, uv) = glyph_quad([10.0, 20.0], &rect, 0.5, (64, 64)).unwrap();
+    assert_eq!(quad, [10.5, 14.0, 14.5, 22.0]);
+    assert_eq!(uv, [0.25, 0.5, 0.375, 0.75]);
+
+    let empty = GlyphRect {
+        width: 0,
+        height: 0,
+        ..rect
+    };
+    assert!(glyph_quad([0.0, 0.0], &empty, 1.0, (64, 64)).is_none());
+
+    let vertices = quad_vertices(
+        [
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ],
+        uv,
+        Color::white(),
+    );
+    //The top left corner samples the top left of the glyph
+    assert_eq!(vertices[0].tex_coord, [0.25, 0.5]);
+    assert_eq!(vertices[5].tex_coord, [0.375, 0.75]);
+
+    let (a, b): (UUID, UUID) = (1, 2);
+    assert_eq!(
+        batches([(a, 6), (a, 12), (b, 6), (a, 0), (a, 6)].into_iter()),
+        vec![(a, 0..18), (b, 18..24), (a, 24..30)]
+    );
+}
@@ -0,0 +1,476 @@
+//! Rendering of the immediate mode [`Ui`]
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+use std::{num::NonZeroU64, ops::Range};
+
+use wgpu_shader_checker::include_wgsl;
+
+use crate::{
+    DEVICE, RESOLUTION, STAGING_BELT, UUID,
+    asset_managment::Asset as _,
+    assets::{
+        Font,
+        font::{GlyphRect, LayoutOptions},
+    },
+    rendering::graph::{NodeBuilder, NodeContext, RenderNode, SURFACE},
+    structures::Color,
+    ui::{DrawCommand, Rect, Shape, Ui},
+};
+
+use super::text::glyph_quad;
+
+const UI_BIND_GROUP_LAYOUT_DESCRIPTOR: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Ui font"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    };
+
+///Kinds of vertices, see the ui shader
+const SOLID: f32 = 0.0;
+const GLYPH: f32 = 1.0;
+const SDF_GLYPH: f32 = 2.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct UiVertex {
+    ///Normalized device coordinates
+    position: [f32; 2],
+    tex_coord: [f32; 2],
+    color: Color,
+    kind: f32,
+}
+
+///Gpu resources of the [`Ui`]
+#[derive(Default)]
+pub(crate) struct UiGpu {
+    pipeline: Option<wgpu::RenderPipeline>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    ///Bind group of the glyph atlas of the font and the generation of the atlas
+    bind_group: Option<(UUID, u32, wgpu::BindGroup)>,
+    ///Bound when there is no font
+    empty_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl UiGpu {
+    fn create_pipeline() -> wgpu::RenderPipeline {
+        let device = DEVICE.get().unwrap();
+        let shader = device.create_shader_module(include_wgsl!("../../shaders/ui.wgsl"));
+        let font_layout = device.create_bind_group_layout(&UI_BIND_GROUP_LAYOUT_DESCRIPTOR);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ui"),
+            bind_group_layouts: &[&font_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ui"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<UiVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x4,
+                        3 => Float32
+                    ],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: *crate::FORMAT.get().unwrap(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_bind_group(view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+        let device = DEVICE.get().unwrap();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ui font"),
+            layout: &device.create_bind_group_layout(&UI_BIND_GROUP_LAYOUT_DESCRIPTOR),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    ///Creates a bind group of a single pixel texture, used when only solid shapes are drawn
+    fn create_empty_bind_group() -> wgpu::BindGroup {
+        let device = DEVICE.get().unwrap();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Ui empty"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        Self::create_bind_group(
+            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &sampler,
+        )
+    }
+}
+
+///A text command whose glyphs are in the atlas of the font
+struct PreparedText {
+    ///Size of an atlas pixel in physical pixels
+    scale: f32,
+    ///Positions of the glyph origins in physical pixels and where the glyphs are in the atlas
+    glyphs: Vec<([f32; 2], GlyphRect)>,
+}
+
+///Returns the clip rectangle in physical pixels, clamped to the target
+fn scissor(clip: Rect, scale: f32, (width, height): (u32, u32)) -> [u32; 4] {
+    let x0 = (clip.x * scale).round().clamp(0.0, width as f32) as u32;
+    let y0 = (clip.y * scale).round().clamp(0.0, height as f32) as u32;
+    let x1 = ((clip.x + clip.width) * scale)
+        .round()
+        .clamp(0.0, width as f32) as u32;
+    let y1 = ((clip.y + clip.height) * scale)
+        .round()
+        .clamp(0.0, height as f32) as u32;
+    [x0, y0, x1.max(x0) - x0, y1.max(y0) - y0]
+}
+
+///Returns the two triangles of a rectangle in physical pixels, given as the left, top, right
+///and bottom edges
+fn rect_vertices(
+    rect: [f32; 4],
+    uv: [f32; 4],
+    color: Color,
+    kind: f32,
+    (width, height): (u32, u32),
+) -> [UiVertex; 6] {
+    let vertex = |x: f32, y: f32, u, v| UiVertex {
+        position: [
+            (x / width as f32).mul_add(2.0, -1.0),
+            (y / height as f32).mul_add(-2.0, 1.0),
+        ],
+        tex_coord: [u, v],
+        color,
+        kind,
+    };
+    [
+        vertex(rect[0], rect[1], uv[0], uv[1]),
+        vertex(rect[0], rect[3], uv[0], uv[3]),
+        vertex(rect[2], rect[1], uv[2], uv[1]),
+        vertex(rect[2], rect[1], uv[2], uv[1]),
+        vertex(rect[0], rect[3], uv[0], uv[3]),
+        vertex(rect[2], rect[3], uv[2], uv[3]),
+    ]
+}
+
+///Vertices drawn with the same scissor rectangle, `None` draws on the whole target
+type Batch = (Option<[u32; 4]>, Range<u32>);
+
+///Returns the vertices of the commands and the ranges of vertices that use the same clip
+///rectangle
+fn command_vertices(
+    commands: &[DrawCommand],
+    texts: &[Option<PreparedText>],
+    atlas: Option<((u32, u32), bool)>,
+    scale: f32,
+    target: (u32, u32),
+) -> (Vec<UiVertex>, Vec<Batch>) {
+    let mut vertices = Vec::new();
+    let mut batches: Vec<Batch> = Vec::new();
+    for (command, text) in commands.iter().zip(texts) {
+        let start = vertices.len() as u32;
+        match (&command.shape, text, atlas) {
+            (Shape::Rect { rect, color }, _, _) => {
+                let edges = [
+                    rect.x * scale,
+                    rect.y * scale,
+                    (rect.x + rect.width) * scale,
+                    (rect.y + rect.height) * scale,
+                ];
+                vertices.extend(rect_vertices(edges, [0.0; 4], *color, SOLID, target));
+            }
+            (Shape::Text { color, .. }, Some(text), Some((atlas_size, sdf))) => {
+                let kind = if sdf { SDF_GLYPH } else { GLYPH };
+                for (pen, rect) in &text.glyphs {
+                    if let Some((quad, uv)) = glyph_quad(*pen, rect, text.scale, atlas_size) {
+                        vertices.extend(rect_vertices(quad, uv, *color, kind, target));
+                    }
+                }
+            }
+            (Shape::Text { .. }, _, _) => {}
+        }
+
+        let end = vertices.len() as u32;
+        let clip = command.clip.map(|clip| scissor(clip, scale, target));
+        match batches.last_mut() {
+            Some((c, range)) if *c == clip => range.end = end,
+            _ if start == end => {}
+            _ => batches.push((clip, start..end)),
+        }
+    }
+    (vertices, batches)
+}
+
+impl RenderNode for Ui {
+    fn setup(&mut self, builder: &mut NodeBuilder) {
+        builder.write(SURFACE);
+    }
+
+    fn run(&mut self, context: &mut NodeContext) {
+        if self.commands().is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Ui render");
+
+        let scale = self.get_scale();
+        let resolution = *RESOLUTION.read().unwrap();
+        let target = (resolution.width.max(1), resolution.height.max(1));
+        let assets = &*context.assets;
+        let font_id = self.get_font().filter(|id| assets.is_type::<Font>(*id));
+        let mut font = font_id
+            .and_then(|id| assets.borrow_by_id_mut::<Font>(id).ok())
+            .filter(|f| f.is_initialized());
+
+        //The glyphs are rasterized before the atlas size is known
+        let texts = self
+            .commands()
+            .iter()
+            .map(|command| {
+                let (
+                    Shape::Text {
+                        position,
+                        text,
+                        size,
+                        ..
+                    },
+                    Some(font),
+                ) = (&command.shape, &mut font)
+                else {
+                    return None;
+                };
+                let pixels = size * scale;
+                let requested = pixels.round().max(1.0) as u32;
+                let layout = font.layout(text, &LayoutOptions::new(pixels));
+                let glyphs = layout
+                    .glyphs
+                    .iter()
+                    .filter_map(|g| {
+                        //Glyphs are drawn at whole pixels, so they stay sharp
+                        let pen = [
+                            position.x.mul_add(scale, g.x).round(),
+                            position.y.mul_add(scale, g.y).round(),
+                        ];
+                        font.glyph(g.glyph, requested).map(|r| (pen, r))
+                    })
+                    .collect();
+                Some(PreparedText {
+                    scale: pixels / font.raster_size(requested) as f32,
+                    glyphs,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut atlas = None;
+        if let (Some(font), Some(id)) = (&mut font, font_id) {
+            font.upload();
+            if let (Some((size, generation)), Some((view, sampler))) =
+                (font.atlas_info(), font.atlas_binding())
+            {
+                atlas = Some((size, font.is_sdf()));
+                if self
+                    .gpu
+                    .bind_group
+                    .as_ref()
+                    .is_none_or(|(i, g, _)| *i != id || *g != generation)
+                {
+                    self.gpu.bind_group =
+                        Some((id, generation, UiGpu::create_bind_group(view, sampler)));
+                }
+            }
+        }
+        drop(font);
+
+        let (vertices, batches) = command_vertices(self.commands(), &texts, atlas, scale, target);
+        if vertices.is_empty() {
+            return;
+        }
+
+        let color = context.texture(SURFACE);
+        let encoder = &mut *context.encoder;
+        let device = DEVICE.get().unwrap();
+        let size = size_of_val(vertices.as_slice()) as u64;
+        let gpu = &mut self.gpu;
+        if gpu.vertex_buffer.as_ref().is_none_or(|b| b.size() < size) {
+            gpu.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Ui vertices"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = gpu.vertex_buffer.as_ref().unwrap();
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.write_buffer(
+            encoder,
+            vertex_buffer,
+            0,
+            NonZeroU64::new(size).unwrap(),
+            device,
+        )
+        .copy_from_slice(bytemuck::cast_slice(&vertices));
+        drop(belt);
+
+        let pipeline = &*gpu.pipeline.get_or_insert_with(UiGpu::create_pipeline);
+        let bind_group = match (&gpu.bind_group, atlas) {
+            (Some((_, _, bind_group)), Some(_)) => bind_group,
+            _ => gpu
+                .empty_bind_group
+                .get_or_insert_with(UiGpu::create_empty_bind_group),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ui pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+        for (clip, range) in batches {
+            let [x, y, width, height] = clip.unwrap_or([0, 0, target.0, target.1]);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.draw(range, 0..1);
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[test]
+fn test_ui_vertices() {
+    use crate::math::Vec2;
+
+    let commands = [
+        DrawCommand {
+            shape: Shape::Rect {
+                rect: Rect::new(0.0, 0.0, 50.0, 25.0),
+                color: Color::white(),
+            },
+            clip: None,
+        },
+        DrawCommand {
+            shape: Shape::Text {
+                position: Vec2::new(10, 10),
+                text: "A".to_owned(),
+                size: 16.0,
+                color: Color::white(),
+            },
+            clip: Some(Rect::new(5.0, 5.0, 20.0, 500.0)),
+        },
+        DrawCommand {
+            shape: Shape::Rect {
+                rect: Rect::new(5.0, 5.0, 10.0, 10.0),
+                color: Color::white(),
+            },
+            clip: Some(Rect::new(5.0, 5.0, 20.0, 500.0)),
+        },
+    ];
+    let rect = GlyphRect {
+        x: 0,
+        y: 0,
+        width: 8,
+        height: 8,
+        left: 0,
+        top: 8,
+    };
+    let texts = [
+        None,
+        Some(PreparedText {
+            scale: 1.0,
+            glyphs: vec![([20.0, 36.0], rect)],
+        }),
+        None,
+    ];
+
+    let (vertices, batches) =
+        command_vertices(&commands, &texts, Some(((64, 64), false)), 2.0, (100, 100));
+    assert_eq!(vertices.len(), 18);
+    //The first rectangle covers the top half of the target
+    assert_eq!(vertices[0].position, [-1.0, 1.0]);
+    assert_eq!(vertices[5].position, [1.0, 0.0]);
+    assert_eq!(vertices[6].kind, GLYPH);
+    assert_eq!(vertices[6].position, [-0.6, 0.44]);
+    //The clip rectangle is scaled and clamped to the target
+    assert_eq!(batches, vec![(None, 0..6), (Some([10, 10, 40, 90]), 6..18)]);
+
+    //Text is skipped without a font
+    let (vertices, batches) = command_vertices(&commands, &texts, None, 1.0, (100, 100));
+    assert_eq!(vertices.len(), 12);
+    assert_eq!(batches, vec![(None, 0..6), (Some([5, 5, 20, 95]), 6..12)]);
+}
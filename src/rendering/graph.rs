@@ -0,0 +1,823 @@
+//! Render graph used for ordering render nodes and managing the resources they share
+//!
+//! # Render graph
+//!
+//! Every frame, each [`RenderNode`] declares the resources it creates, reads and writes using a
+//! [`NodeBuilder`]. Resources are identified by their names, the [`SURFACE`] texture and the
+//! [`DEPTH`] texture are always available, all the other resources are transient and are created
+//! by the nodes.
+//!
+//! The graph then orders the nodes so that every resource is written before it is read. Nodes
+//! that write the same resource run in the order of their priorities. Nodes whose results are not
+//! used by anything are culled. Transient resources are allocated from a pool that is kept
+//! between frames, resources with the same description that are not used at the same time share
+//! the same gpu resource.
+
+use std::collections::HashMap;
+
+use log::trace;
+use wgpu::TextureUsages;
+
+use crate::{
+    DEPTH as DEPTH_TEXTURE, DEVICE, FORMAT, QUEUE, RESOLUTION, STAGING_BELT,
+    SURFACE as SURFACE_TEXTURE, asset_managment::AssetStore, ecs::World,
+};
+
+///Name of the color texture that is presented to the screen
+pub const SURFACE: &str = "surface";
+///Name of the depth texture of the screen
+pub const DEPTH: &str = "depth";
+
+///Size of a texture created by the graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    ///Same size as the surface
+    Surface,
+    ///Size of the surface multiplied by the factor
+    Scaled(f32),
+    ///Fixed width and height
+    Fixed(u32, u32),
+}
+
+impl TextureSize {
+    ///Returns the size in pixels, given the size of the surface
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    #[must_use]
+    pub fn resolve(self, surface: (u32, u32)) -> (u32, u32) {
+        let (width, height) = match self {
+            Self::Surface => surface,
+            Self::Scaled(factor) => (
+                (surface.0 as f32 * factor) as u32,
+                (surface.1 as f32 * factor) as u32,
+            ),
+            Self::Fixed(width, height) => (width, height),
+        };
+        (width.max(1), height.max(1))
+    }
+}
+
+///Description of a transient texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDescription {
+    ///Size of the texture
+    pub size: TextureSize,
+    ///Format of the texture
+    pub format: wgpu::TextureFormat,
+    ///Usages of the texture
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TextureDescription {
+    ///Creates a new texture description
+    #[must_use]
+    pub const fn new(
+        size: TextureSize,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Self {
+        Self {
+            size,
+            format,
+            usage,
+        }
+    }
+}
+
+///Description of a transient buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferDescription {
+    ///Size of the buffer in bytes
+    pub size: u64,
+    ///Usages of the buffer
+    pub usage: wgpu::BufferUsages,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ResourceDescription {
+    Texture(TextureDescription),
+    Buffer(BufferDescription),
+}
+
+///Resources declared by a node
+#[derive(Debug, Default, Clone)]
+struct Declarations {
+    creates: Vec<(String, ResourceDescription)>,
+    reads: Vec<String>,
+    writes: Vec<String>,
+}
+
+impl Declarations {
+    ///Returns the names of all the used resources
+    fn used(&self) -> impl Iterator<Item = &str> {
+        self.creates
+            .iter()
+            .map(|c| c.0.as_str())
+            .chain(self.reads.iter().map(String::as_str))
+            .chain(self.writes.iter().map(String::as_str))
+    }
+
+    ///Returns the names of the resources that the node creates or writes
+    fn written(&self) -> impl Iterator<Item = &str> {
+        self.creates
+            .iter()
+            .map(|c| c.0.as_str())
+            .chain(self.writes.iter().map(String::as_str))
+    }
+}
+
+///Used by nodes to declare the resources they use
+#[derive(Debug, Default)]
+pub struct NodeBuilder {
+    declarations: Declarations,
+}
+
+impl NodeBuilder {
+    ///Creates a new transient texture, the node that creates a resource is the first one to write
+    ///it
+    pub fn create_texture(&mut self, name: &str, description: TextureDescription) {
+        self.declarations
+            .creates
+            .push((name.to_owned(), ResourceDescription::Texture(description)));
+    }
+
+    ///Creates a new transient buffer, the node that creates a resource is the first one to write
+    ///it
+    pub fn create_buffer(&mut self, name: &str, description: BufferDescription) {
+        self.declarations
+            .creates
+            .push((name.to_owned(), ResourceDescription::Buffer(description)));
+    }
+
+    ///Declares that the node reads the resource, the node will run after all the nodes that
+    ///write it
+    pub fn read(&mut self, name: &str) {
+        self.declarations.reads.push(name.to_owned());
+    }
+
+    ///Declares that the node writes the resource
+    pub fn write(&mut self, name: &str) {
+        self.declarations.writes.push(name.to_owned());
+    }
+}
+
+///A gpu resource owned by the graph
+#[derive(Debug, Clone)]
+enum Resource {
+    Texture {
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+    },
+    Buffer(wgpu::Buffer),
+}
+
+///Resources of the graph for the current frame
+#[derive(Debug, Default)]
+pub struct GraphResources {
+    resources: HashMap<String, Resource>,
+}
+
+impl GraphResources {
+    ///Returns the view of the texture with the given name
+    ///
+    ///# Panics
+    ///Panics if there is no such texture
+    #[must_use]
+    pub fn texture(&self, name: &str) -> &wgpu::TextureView {
+        match self.resources.get(name) {
+            Some(Resource::Texture { view, .. }) => view,
+            _ => panic!("Render graph texture {name} does not exist"),
+        }
+    }
+
+    ///Returns the texture with the given name
+    ///
+    ///# Panics
+    ///Panics if there is no such texture
+    #[must_use]
+    pub fn raw_texture(&self, name: &str) -> &wgpu::Texture {
+        match self.resources.get(name) {
+            Some(Resource::Texture { texture, .. }) => texture,
+            _ => panic!("Render graph texture {name} does not exist"),
+        }
+    }
+
+    ///Returns the buffer with the given name
+    ///
+    ///# Panics
+    ///Panics if there is no such buffer
+    #[must_use]
+    pub fn buffer(&self, name: &str) -> &wgpu::Buffer {
+        match self.resources.get(name) {
+            Some(Resource::Buffer(buffer)) => buffer,
+            _ => panic!("Render graph buffer {name} does not exist"),
+        }
+    }
+}
+
+///Data available to a node when it is run
+pub struct NodeContext<'a> {
+    ///Encoder used for recording the commands
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    ///The rendered world
+    pub world: &'a World,
+    ///Assets used by the world
+    pub assets: &'a mut AssetStore,
+    resources: &'a GraphResources,
+    declarations: &'a Declarations,
+}
+
+impl<'a> NodeContext<'a> {
+    fn check_declared(&self, name: &str) {
+        assert!(
+            self.declarations.used().any(|n| n == name),
+            "Render graph resource {name} was not declared by the node"
+        );
+    }
+
+    ///Returns the view of a texture declared by the node
+    ///
+    ///# Panics
+    ///Panics if the node did not declare the texture
+    #[must_use]
+    pub fn texture(&self, name: &str) -> &'a wgpu::TextureView {
+        self.check_declared(name);
+        self.resources.texture(name)
+    }
+
+    ///Returns a texture declared by the node
+    ///
+    ///# Panics
+    ///Panics if the node did not declare the texture
+    #[must_use]
+    pub fn raw_texture(&self, name: &str) -> &'a wgpu::Texture {
+        self.check_declared(name);
+        self.resources.raw_texture(name)
+    }
+
+    ///Returns a buffer declared by the node
+    ///
+    ///# Panics
+    ///Panics if the node did not declare the buffer
+    #[must_use]
+    pub fn buffer(&self, name: &str) -> &'a wgpu::Buffer {
+        self.check_declared(name);
+        self.resources.buffer(name)
+    }
+}
+
+///A pass of the render graph
+pub trait RenderNode {
+    ///Declares the resources the node creates, reads and writes, called every frame before the
+    ///graph is ordered
+    fn setup(&mut self, builder: &mut NodeBuilder);
+
+    ///Records the commands of the node
+    fn run(&mut self, context: &mut NodeContext);
+
+    ///Allows the node to do some work after the commands of all the nodes were submitted, but
+    ///before the frame is presented
+    #[allow(unused)]
+    fn post_run(&mut self, resources: &GraphResources) {}
+
+    ///Returns the priority of the node, nodes that write the same resource run in the order of
+    ///their priorities, smaller priorities first
+    fn get_priority(&self) -> u32;
+
+    ///Returns the name of the node, used in errors
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+///Error type of the render graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    ///A node uses a resource that is not created by any node
+    UnknownResource {
+        ///Name of the node
+        node: String,
+        ///Name of the resource
+        resource: String,
+    },
+    ///A resource is created more than once, or has the name of a resource provided by the graph
+    DuplicateResource(String),
+    ///Nodes depend on each other in a cycle
+    ///
+    ///Contains the names of the nodes that could not be ordered
+    Cycle(Vec<String>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownResource { node, resource } => {
+                write!(
+                    f,
+                    "Node {node} uses resource {resource} that does not exist"
+                )
+            }
+            Self::DuplicateResource(name) => write!(f, "Resource {name} is created more than once"),
+            Self::Cycle(nodes) => write!(f, "Render nodes depend on each other: {nodes:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+///A transient resource and the pool slot it is allocated in
+#[derive(Debug, Clone, PartialEq)]
+struct TransientResource {
+    name: String,
+    description: ResourceDescription,
+    slot: usize,
+}
+
+///Order in which the nodes run and the resources they need
+#[derive(Debug, Clone, PartialEq)]
+struct Plan {
+    ///Indices of the nodes that are run, in order
+    order: Vec<usize>,
+    resources: Vec<TransientResource>,
+    ///Descriptions of the resources of every pool slot
+    slots: Vec<ResourceDescription>,
+}
+
+fn is_imported(name: &str) -> bool {
+    name == SURFACE || name == DEPTH
+}
+
+///Orders the nodes, culls unused nodes and assigns the transient resources to pool slots
+fn compile(nodes: &[(&Declarations, u32, &str)]) -> Result<Plan, Error> {
+    //Find the creators of the resources
+    let mut created = HashMap::new();
+    for (index, (declarations, ..)) in nodes.iter().enumerate() {
+        for (name, description) in &declarations.creates {
+            if is_imported(name)
+                || created
+                    .insert(name.as_str(), (index, *description))
+                    .is_some()
+            {
+                return Err(Error::DuplicateResource(name.clone()));
+            }
+        }
+    }
+    for (declarations, _, node) in nodes {
+        if let Some(resource) = declarations
+            .used()
+            .find(|n| !is_imported(n) && !created.contains_key(n))
+        {
+            return Err(Error::UnknownResource {
+                node: (*node).to_owned(),
+                resource: resource.to_owned(),
+            });
+        }
+    }
+
+    //Writers of every resource run in the order of their priorities, after the creator of the
+    //resource, readers run after all the writers
+    let key = |i: usize| (nodes[i].1, i);
+    let mut dependencies = vec![Vec::new(); nodes.len()];
+    let mut names = nodes.iter().flat_map(|n| n.0.used()).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    for name in names {
+        let creator = created.get(name).map(|c| c.0);
+        let mut writers = (0..nodes.len())
+            .filter(|i| Some(*i) != creator && nodes[*i].0.writes.iter().any(|w| w == name))
+            .collect::<Vec<_>>();
+        writers.sort_unstable_by_key(|i| key(*i));
+        let writers = creator.into_iter().chain(writers).collect::<Vec<_>>();
+
+        for pair in writers.windows(2) {
+            dependencies[pair[1]].push(pair[0]);
+        }
+        if let Some(last) = writers.last() {
+            for reader in (0..nodes.len())
+                .filter(|i| !writers.contains(i) && nodes[*i].0.reads.iter().any(|r| r == name))
+            {
+                dependencies[reader].push(*last);
+            }
+        }
+    }
+
+    //Topological sort, picking the node with the smallest priority when there is a choice
+    let mut order = Vec::with_capacity(nodes.len());
+    let mut done = vec![false; nodes.len()];
+    while order.len() < nodes.len() {
+        let next = (0..nodes.len())
+            .filter(|i| !done[*i] && dependencies[*i].iter().all(|d| done[*d]))
+            .min_by_key(|i| key(*i));
+
+        let Some(next) = next else {
+            return Err(Error::Cycle(
+                (0..nodes.len())
+                    .filter(|i| !done[*i])
+                    .map(|i| nodes[i].2.to_owned())
+                    .collect(),
+            ));
+        };
+        done[next] = true;
+        order.push(next);
+    }
+
+    //Cull the nodes whose results are not used, nodes that write nothing have side effects
+    let mut needed = nodes
+        .iter()
+        .map(|n| n.0.written().next().is_none() || n.0.written().any(is_imported))
+        .collect::<Vec<_>>();
+    for &node in order.iter().rev() {
+        if needed[node] {
+            for &dependency in &dependencies[node] {
+                needed[dependency] = true;
+            }
+        }
+    }
+    order.retain(|n| needed[*n]);
+
+    let (resources, slots) = assign_slots(nodes, &created, &order);
+    Ok(Plan {
+        order,
+        resources,
+        slots,
+    })
+}
+
+///Assigns the transient resources used by the ordered nodes to pool slots, resources with the
+///same description share a slot if their lifetimes don't overlap
+fn assign_slots(
+    nodes: &[(&Declarations, u32, &str)],
+    created: &HashMap<&str, (usize, ResourceDescription)>,
+    order: &[usize],
+) -> (Vec<TransientResource>, Vec<ResourceDescription>) {
+    //Find where the resources are used for the first and the last time
+    let mut lifetimes = HashMap::new();
+    for (position, node) in order.iter().enumerate() {
+        for name in nodes[*node].0.used().filter(|n| !is_imported(n)) {
+            lifetimes
+                .entry(name)
+                .and_modify(|l: &mut (usize, usize)| l.1 = position)
+                .or_insert((position, position));
+        }
+    }
+    let mut transient = lifetimes.into_iter().collect::<Vec<_>>();
+    transient.sort_unstable_by_key(|(name, lifetime)| (lifetime.0, *name));
+
+    let mut slots: Vec<(ResourceDescription, usize)> = Vec::new();
+    let mut resources = Vec::new();
+    for (name, (first, last)) in transient {
+        let description = created[name].1;
+        let slot = if let Some(slot) = slots
+            .iter()
+            .position(|(d, end)| *d == description && *end < first)
+        {
+            slots[slot].1 = last;
+            slot
+        } else {
+            slots.push((description, last));
+            slots.len() - 1
+        };
+
+        resources.push(TransientResource {
+            name: name.to_owned(),
+            description,
+            slot,
+        });
+    }
+
+    (resources, slots.into_iter().map(|s| s.0).collect())
+}
+
+///A gpu resource in the pool of the graph
+struct PooledResource {
+    description: ResourceDescription,
+    size: (u32, u32),
+    resource: Resource,
+}
+
+///Runs render nodes in the order of their dependencies, and manages the transient resources they
+///use
+///
+///The graph keeps the transient resources between frames, so it should be kept alive for as long
+///as it is used for rendering
+///
+///# Usage
+///```
+///# use lunar_engine::rendering::{extensions::Base, graph::RenderGraph};
+///# struct State {world: lunar_engine::ecs::World, assets: lunar_engine::asset_managment::AssetStore, graph: RenderGraph, base: Base}
+///fn update(state: &mut State) {
+/// state
+///   .graph
+///   .execute(&state.world, &mut state.assets, &mut [&mut state.base])
+///   .unwrap();
+///}
+///```
+#[derive(Default)]
+pub struct RenderGraph {
+    pool: Vec<PooledResource>,
+}
+
+impl RenderGraph {
+    ///Creates a new empty graph
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { pool: Vec::new() }
+    }
+
+    ///Renders a frame using the nodes
+    ///
+    ///# Errors
+    ///Returns an error if the nodes use resources that don't exist or depend on each other in a
+    ///cycle, nothing is rendered in that case
+    pub fn execute(
+        &mut self,
+        world: &World,
+        assets: &mut AssetStore,
+        nodes: &mut [&mut dyn RenderNode],
+    ) -> Result<(), Error> {
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!("Render graph");
+
+        let declarations = nodes
+            .iter_mut()
+            .map(|n| {
+                let mut builder = NodeBuilder::default();
+                n.setup(&mut builder);
+                builder.declarations
+            })
+            .collect::<Vec<_>>();
+        let plan = compile(
+            &declarations
+                .iter()
+                .zip(nodes.iter())
+                .map(|(d, n)| (d, n.get_priority(), n.name()))
+                .collect::<Vec<_>>(),
+        )?;
+        trace!("Compiled the render graph");
+
+        let device = DEVICE.get().unwrap();
+        let color = SURFACE_TEXTURE
+            .get()
+            .and_then(|i| i.read().ok())
+            .unwrap()
+            .get_current_texture()
+            .unwrap();
+        trace!("Accquiered surface");
+
+        let mut resources = GraphResources::default();
+        resources.resources.insert(
+            SURFACE.to_owned(),
+            Resource::Texture {
+                view: color.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Color attachment view"),
+                    format: Some(*FORMAT.get().unwrap()),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: wgpu::TextureAspect::All,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: 0,
+                    array_layer_count: None,
+                    usage: Some(TextureUsages::RENDER_ATTACHMENT),
+                }),
+                texture: color.texture.clone(),
+            },
+        );
+
+        let depth = DEPTH_TEXTURE.get().unwrap().read().unwrap().clone();
+        resources.resources.insert(
+            DEPTH.to_owned(),
+            Resource::Texture {
+                view: depth.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Depth stencil attachment"),
+                    format: Some(wgpu::TextureFormat::Depth32Float),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: wgpu::TextureAspect::DepthOnly,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: 0,
+                    array_layer_count: None,
+                    usage: Some(TextureUsages::RENDER_ATTACHMENT),
+                }),
+                texture: depth,
+            },
+        );
+
+        self.allocate(&plan.slots);
+        for r in &plan.resources {
+            resources
+                .resources
+                .insert(r.name.clone(), self.pool[r.slot].resource.clone());
+        }
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        for &node in &plan.order {
+            trace!("Running render node {}", nodes[node].name());
+            let mut context = NodeContext {
+                encoder: &mut encoder,
+                world,
+                assets,
+                resources: &resources,
+                declarations: &declarations[node],
+            };
+            nodes[node].run(&mut context);
+        }
+
+        let cmd_buffer = encoder.finish();
+        let queue = QUEUE.get().unwrap();
+
+        let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
+        belt.finish();
+        queue.submit(Some(cmd_buffer));
+        belt.recall();
+        drop(belt);
+        trace!("Recalled belt");
+
+        for &node in &plan.order {
+            nodes[node].post_run(&resources);
+        }
+
+        drop(resources);
+        trace!("Presenting color");
+        color.present();
+
+        #[cfg(feature = "tracy")]
+        tracy_client::frame_mark();
+
+        Ok(())
+    }
+
+    ///Creates the pooled resources that don't match the slots of the plan
+    fn allocate(&mut self, slots: &[ResourceDescription]) {
+        let device = DEVICE.get().unwrap();
+        let resolution = RESOLUTION.read().unwrap();
+        let surface = (resolution.width, resolution.height);
+        drop(resolution);
+
+        self.pool.truncate(slots.len());
+        for (index, description) in slots.iter().enumerate() {
+            let size = match description {
+                ResourceDescription::Texture(t) => t.size.resolve(surface),
+                ResourceDescription::Buffer(_) => (0, 0),
+            };
+            if self
+                .pool
+                .get(index)
+                .is_some_and(|p| p.description == *description && p.size == size)
+            {
+                continue;
+            }
+
+            let resource = match description {
+                ResourceDescription::Texture(t) => {
+                    let texture = device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("Render graph texture"),
+                        size: wgpu::Extent3d {
+                            width: size.0,
+                            height: size.1,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: t.format,
+                        usage: t.usage,
+                        view_formats: &[],
+                    });
+                    Resource::Texture {
+                        view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                        texture,
+                    }
+                }
+                ResourceDescription::Buffer(b) => {
+                    Resource::Buffer(device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Render graph buffer"),
+                        size: b.size,
+                        usage: b.usage,
+                        mapped_at_creation: false,
+                    }))
+                }
+            };
+
+            let pooled = PooledResource {
+                description: *description,
+                size,
+                resource,
+            };
+            if index < self.pool.len() {
+                self.pool[index] = pooled;
+            } else {
+                self.pool.push(pooled);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_graph_compile() {
+    let texture = |builder: &mut NodeBuilder, name: &str| {
+        builder.create_texture(
+            name,
+            TextureDescription::new(
+                TextureSize::Surface,
+                wgpu::TextureFormat::Rgba16Float,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            ),
+        );
+    };
+
+    //Scene -> bloom -> tonemap -> surface, with an unused node and a screenshot
+    let mut scene = NodeBuilder::default();
+    texture(&mut scene, "hdr");
+    scene.write(DEPTH);
+    let mut bloom = NodeBuilder::default();
+    bloom.read("hdr");
+    texture(&mut bloom, "bloom");
+    let mut tonemap = NodeBuilder::default();
+    tonemap.read("hdr");
+    tonemap.read("bloom");
+    tonemap.write(SURFACE);
+    let mut unused = NodeBuilder::default();
+    texture(&mut unused, "unused");
+    let mut screenshot = NodeBuilder::default();
+    screenshot.read(SURFACE);
+    let mut overlay = NodeBuilder::default();
+    overlay.write(SURFACE);
+    overlay.read(DEPTH);
+
+    let nodes = [
+        (&screenshot.declarations, 0, "screenshot"),
+        (&tonemap.declarations, 0, "tonemap"),
+        (&overlay.declarations, 10, "overlay"),
+        (&unused.declarations, 0, "unused"),
+        (&bloom.declarations, 0, "bloom"),
+        (&scene.declarations, 5, "scene"),
+    ];
+    let plan = compile(&nodes).unwrap();
+    let order = plan.order.iter().map(|i| nodes[*i].2).collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        ["scene", "bloom", "tonemap", "overlay", "screenshot"]
+    );
+    assert_eq!(plan.resources.len(), 2);
+    assert_eq!(plan.slots.len(), 2);
+
+    //The second texture can reuse the slot of the first one once it is no longer used
+    let mut first = NodeBuilder::default();
+    texture(&mut first, "a");
+    let mut second = NodeBuilder::default();
+    second.read("a");
+    texture(&mut second, "b");
+    let mut third = NodeBuilder::default();
+    third.read("b");
+    texture(&mut third, "c");
+    let mut last = NodeBuilder::default();
+    last.read("c");
+    last.write(SURFACE);
+    let plan = compile(&[
+        (&first.declarations, 0, "first"),
+        (&second.declarations, 0, "second"),
+        (&third.declarations, 0, "third"),
+        (&last.declarations, 0, "last"),
+    ])
+    .unwrap();
+    assert_eq!(plan.slots.len(), 2);
+    let slot = |name: &str| plan.resources.iter().find(|r| r.name == name).unwrap().slot;
+    assert_eq!(slot("a"), slot("c"));
+    assert_ne!(slot("a"), slot("b"));
+
+    //Errors
+    assert_eq!(
+        compile(&[(&second.declarations, 0, "second")]),
+        Err(Error::UnknownResource {
+            node: "second".to_owned(),
+            resource: "a".to_owned()
+        })
+    );
+    assert_eq!(
+        compile(&[
+            (&first.declarations, 0, "first"),
+            (&first.declarations, 0, "copy")
+        ]),
+        Err(Error::DuplicateResource("a".to_owned()))
+    );
+    let mut cyclic_a = NodeBuilder::default();
+    texture(&mut cyclic_a, "x");
+    cyclic_a.read("y");
+    let mut cyclic_b = NodeBuilder::default();
+    texture(&mut cyclic_b, "y");
+    cyclic_b.read("x");
+    cyclic_b.write(SURFACE);
+    assert!(matches!(
+        compile(&[
+            (&cyclic_a.declarations, 0, "a"),
+            (&cyclic_b.declarations, 0, "b")
+        ]),
+        Err(Error::Cycle(_))
+    ));
+}
@@ -9,109 +9,77 @@
 //! The render function accepts a world and an asset store.
 //! The rendering function gets the asset ids and queries them from the store.
 
-use log::trace;
-use wgpu::TextureUsages;
-
-use crate::{
-    DEPTH, DEVICE, FORMAT, QUEUE, STAGING_BELT, SURFACE, asset_managment::AssetStore, ecs::World,
+use std::sync::{
+    Mutex,
+    atomic::{AtomicBool, Ordering},
 };
 
-use self::extensions::{AttachmentData, RenderingExtension};
+use crate::{asset_managment::AssetStore, ecs::World};
+
+use self::graph::{RenderGraph, RenderNode};
 
 ///System for making custom renderers for objects, also contains implemented rendering extensions
 pub mod extensions;
+///Render graph that orders the render nodes and manages the resources they use
+pub mod graph;
+
+///Name of the hdr color texture the scene is rendered into when hdr rendering is enabled
+pub const HDR_COLOR: &str = "hdr_color";
+///Format of the hdr color texture
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+///Whether the scene is rendered into [`HDR_COLOR`]
+static HDR: AtomicBool = AtomicBool::new(false);
+
+///Sets whether the scene is rendered into the [`HDR_COLOR`] texture instead of the surface, which
+///is needed for post processing
+///
+///Materials and skyboxes create their pipelines for the format of the scene, so this must be
+///called before they are initialized
+pub fn set_hdr(enabled: bool) {
+    HDR.store(enabled, Ordering::Relaxed);
+}
 
-///Renders all the entities in the world
-pub fn render(
-    world: &World,
-    assets: &mut AssetStore,
-    extensions: &mut [&mut dyn RenderingExtension],
-) {
-    #[cfg(feature = "tracy")]
-    let _span = tracy_client::span!("render", 1000);
-
-    trace!("Beginning of the render function");
-
-    let device = DEVICE.get().unwrap();
-    let mut encoder =
-        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-    let color = SURFACE
-        .get()
-        .and_then(|i| i.read().ok())
-        .unwrap()
-        .get_current_texture()
-        .unwrap();
-    trace!("Accquiered surface");
-
-    let color_view = color.texture.create_view(&wgpu::TextureViewDescriptor {
-        label: Some("Color attachment view"),
-        format: Some(*FORMAT.get().unwrap()),
-        dimension: Some(wgpu::TextureViewDimension::D2),
-        aspect: wgpu::TextureAspect::All,
-        base_mip_level: 0,
-        mip_level_count: None,
-        base_array_layer: 0,
-        array_layer_count: None,
-        usage: Some(TextureUsages::RENDER_ATTACHMENT),
-    });
-
-    let depth_setencil_veiw =
-        DEPTH
-            .get()
-            .unwrap()
-            .read()
-            .unwrap()
-            .create_view(&wgpu::TextureViewDescriptor {
-                label: Some("Depth stencil attachment"),
-                format: Some(wgpu::TextureFormat::Depth32Float),
-                dimension: Some(wgpu::TextureViewDimension::D2),
-                aspect: wgpu::TextureAspect::DepthOnly,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-                usage: Some(TextureUsages::RENDER_ATTACHMENT),
-            });
-
-    let attachments = AttachmentData {
-        color: color_view,
-        depth_stencil: depth_setencil_veiw,
-    };
-
-    trace!("Created attachment data");
-
-    #[cfg(feature = "tracy")]
-    let _span = tracy_client::span!("Rendering extensions");
+///Returns whether the scene is rendered into the [`HDR_COLOR`] texture
+#[must_use]
+pub fn is_hdr() -> bool {
+    HDR.load(Ordering::Relaxed)
+}
 
-    for e in extensions.iter_mut() {
-        trace!("Calling render on an extension");
-        e.render(&mut encoder, world, assets, &attachments);
+///Returns the format of the texture the scene is rendered into
+///
+///# Panics
+///Panics if the surface was not created yet and hdr rendering is disabled
+#[must_use]
+pub fn scene_format() -> wgpu::TextureFormat {
+    if is_hdr() {
+        HDR_FORMAT
+    } else {
+        *crate::FORMAT.get().unwrap()
     }
+}
 
-    let cmd_buffer = encoder.finish();
-
-    let queue = QUEUE.get().unwrap();
-
-    let mut belt = STAGING_BELT.get().unwrap().write().unwrap();
-    belt.finish();
-
-    queue.submit(Some(cmd_buffer));
+///Returns the name of the render graph texture the scene is rendered into
+#[must_use]
+pub fn scene_target() -> &'static str {
+    if is_hdr() { HDR_COLOR } else { graph::SURFACE }
+}
 
-    belt.recall();
-    drop(belt);
+///Graph used by [`render`]
+static GRAPH: Mutex<RenderGraph> = Mutex::new(RenderGraph::new());
 
-    trace!("Recalled belt");
+///Renders all the entities in the world using the nodes
+///
+///The nodes are run in the order given by the resources they use, see [`graph`] for details
+///
+///# Panics
+///Panics if the nodes use resources that don't exist or depend on each other in a cycle
+pub fn render(world: &World, assets: &mut AssetStore, nodes: &mut [&mut dyn RenderNode]) {
+    #[cfg(feature = "tracy")]
+    let _span = tracy_client::span!("render", 1000);
 
-    trace!("Doing post render");
-    for e in extensions.iter_mut() {
-        trace!("Calling post render on an extension");
-        e.post_render(&attachments);
+    let mut graph = GRAPH.lock().unwrap();
+    if let Err(e) = graph.execute(world, assets, nodes) {
+        panic!("Invalid render graph: {e}");
     }
-
-    trace!("Presenting color");
-    color.present();
-
-    #[cfg(feature = "tracy")]
-    tracy_client::frame_mark();
 }
@@ -1,9 +1,45 @@
 ### 0 
 @group(3)@binding(1)
 var<storage, read> point_lights: array<PointLight>;
+@group(3)@binding(2)
+var<storage, read> spot_lights: array<SpotLight>;
+//Offset and number of the point lights of every cluster
+@group(3)@binding(5)
+var<storage, read> clusters: array<vec2<u32>>;
+@group(3)@binding(6)
+var<storage, read> cluster_lights: array<u32>;
+
+//Returns the offset and the number of the point lights of the cluster the fragment is in
+fn light_cluster(frag_pos: vec4<f32>, world_pos: vec3<f32>) -> vec2<u32> {
+    let grid = num_lights.grid;
+    let tile = (frag_pos.xy - num_lights.viewport.xy) / num_lights.viewport.zw * vec2<f32>(grid.xy);
+    let xy = min(vec2<u32>(max(tile, vec2(0.0))), grid.xy - 1u);
+
+    //The depth slices grow exponentially
+    let depth = max(dot(world_pos - num_lights.camera_position, num_lights.camera_forward), num_lights.near);
+    let slice = log(depth / num_lights.near) / log(num_lights.far / num_lights.near) * f32(grid.z);
+    let z = min(u32(max(slice, 0.0)), grid.z - 1u);
+
+    return clusters[xy.x + xy.y * grid.x + z * grid.x * grid.y];
+}
+
+fn light_index(i: u32) -> u32 {
+    return cluster_lights[i];
+}
 ### 1
 @group(3)@binding(1)
 var<uniform> point_lights: array<PointLight, 256>;
+@group(3)@binding(2)
+var<uniform> spot_lights: array<SpotLight, 128>;
+
+//Without storage buffers there are no clusters, so all the point lights are used
+fn light_cluster(frag_pos: vec4<f32>, world_pos: vec3<f32>) -> vec2<u32> {
+    return vec2(0u, num_lights.num_lights);
+}
+
+fn light_index(i: u32) -> u32 {
+    return i;
+}
 ###
 
 
@@ -18,6 +54,9 @@ struct MaterialData {
   color: vec4<f32>,
   specular_color: vec4<f32>,
   shininess: f32,
+  reflectivity: f32,
+  //Pixels with alpha below the cutoff are discarded
+  alpha_cutoff: f32,
 }
 
 struct PointLight {
@@ -27,6 +66,19 @@ struct PointLight {
   range: f32
 }
 
+struct SpotLight {
+  position: vec3<f32>,
+  range: f32,
+  direction: vec3<f32>,
+  intensity: f32,
+  color: vec3<f32>,
+  inner_cos: f32,
+  outer_cos: f32,
+  //-1 if the light does not cast shadows
+  shadow_index: i32,
+  shadow_matrix: mat4x4<f32>,
+}
+
 struct Camera {
   matrix: mat4x4<f32>,
   t_matrix: mat4x4<f32>,
@@ -40,27 +92,73 @@ var<uniform> material: MaterialData;
 var texture: texture_2d<f32>;
 @group(1)@binding(2)
 var tex_sampler: sampler;
+@group(1)@binding(3)
+var environment: texture_cube<f32>;
+@group(1)@binding(4)
+var environment_sampler: sampler;
 @group(2)@binding(0)
 var<uniform> directional_light: Light;
 
 struct n_l {
   num_lights: u32,
-  padding_0: u32,
+  num_spot_lights: u32,
   padding_1: u32,
   padding_2: u32,
+  //Viewport of the camera in pixels
+  viewport: vec4<f32>,
+  camera_position: vec3<f32>,
+  near: f32,
+  camera_forward: vec3<f32>,
+  far: f32,
+  //Number of clusters along each axis
+  grid: vec3<u32>,
 }
 
 @group(3)@binding(0)
 var<uniform> num_lights: n_l;
+@group(3)@binding(3)
+var shadow_maps: texture_depth_2d_array;
+@group(3)@binding(4)
+var shadow_sampler: sampler_comparison;
+
+//Returns how much of the spot light reaches the point, 0 if it is fully in shadow
+fn spot_shadow(light: SpotLight, world_pos: vec3<f32>) -> f32 {
+    if light.shadow_index < 0 {
+        return 1.0;
+    }
+
+    let clip = light.shadow_matrix * vec4(world_pos, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2(0.5, -0.5) + 0.5;
+    if any(uv < vec2(0.0)) || any(uv > vec2(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    //Average 4 samples around the point to soften the edges
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_maps));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x += 2) {
+        for (var y = -1; y <= 1; y += 2) {
+            let offset = vec2(f32(x), f32(y)) * texel * 0.5;
+            lit += textureSampleCompareLevel(shadow_maps, shadow_sampler, uv + offset, light.shadow_index, ndc.z - 0.0005);
+        }
+    }
+    return lit * 0.25;
+}
 
 @fragment
 fn main(@builtin(position) pos: vec4<f32>, @location(0) uvs: vec2<f32>, @location(1) normal: vec3<f32>, @location(2) view_dir: vec3<f32>, @location(3) world_pos: vec3<f32>) -> @location(0) vec4<f32> {
     var color = directional_light.ambient_color;
     var specular = vec4(0.0);
 
-    let len = num_lights.num_lights;
+    //Only the point lights that can reach the cluster of the fragment
+    let cluster = light_cluster(pos, world_pos);
 
-    for (var i: u32 = 0; i < len; i++) {
+    for (var c: u32 = cluster.x; c < cluster.x + cluster.y; c++) {
+        let i = light_index(c);
         let dir = point_lights[i].position - world_pos;
         let distance = length(dir);
 
@@ -85,6 +183,31 @@ fn main(@builtin(position) pos: vec4<f32>, @location(0) uvs: vec2<f32>, @locatio
         specular += saturate(material.shininess * attenuation * (pow(dot(refl, view_dir), 30.0) * material.specular_color));
     }
 
+    for (var i: u32 = 0; i < num_lights.num_spot_lights; i++) {
+        let light = spot_lights[i];
+        let dir = light.position - world_pos;
+        let distance = length(dir);
+
+        if distance > light.range {
+          continue;
+        }
+
+        let l_dir = dir / distance;
+        //Fade out between the inner and the outer cone
+        let cone = smoothstep(light.outer_cos, light.inner_cos, dot(-l_dir, light.direction));
+        let intensity = dot(normal, l_dir);
+
+        if cone <= 0.0 || intensity <= 0.0 {
+          continue;
+        }
+
+        let attenuation = saturate(1.0 / (distance * distance)) * cone * spot_shadow(light, world_pos);
+        color += saturate(vec4(light.color, 1.0) * intensity * light.intensity * attenuation);
+
+        let refl = normalize((l_dir - 2 * intensity * normal));
+        specular += saturate(material.shininess * attenuation * (pow(dot(refl, view_dir), 30.0) * material.specular_color));
+    }
+
     let light_dir = - directional_light.direction;
     let light_intencity = dot(normal, light_dir);
 
@@ -97,8 +220,18 @@ fn main(@builtin(position) pos: vec4<f32>, @location(0) uvs: vec2<f32>, @locatio
         specular += material.shininess * directional_light.intensity * (pow(saturate(dot(reflection, view_dir)), 30.0) * material.specular_color);
     }
 
-    color = color * material.color * textureSample(texture, tex_sampler, uvs);
+    let albedo = material.color * textureSample(texture, tex_sampler, uvs);
+    if albedo.a < material.alpha_cutoff {
+        discard;
+    }
+    color = color * albedo;
+
+    //Simple environment reflections
+    let environment_color = textureSample(environment, environment_sampler, reflect(view_dir, normal));
+    color = vec4(mix(color.rgb, environment_color.rgb, material.reflectivity), color.a);
+
     color = saturate(color + specular);
 
-    return color;
+    //The alpha only comes from the material, not from the lights
+    return vec4(color.rgb, albedo.a);
 }
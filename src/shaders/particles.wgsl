@@ -0,0 +1,75 @@
+struct Camera {
+  matrix: mat4x4<f32>,
+  t_matrix: mat4x4<f32>,
+  position: vec3<f32>
+}
+
+struct Emitter {
+  alpha_cutoff: f32,
+  //Length added to the quads per unit of speed, 0 for billboards
+  length_scale: f32,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) tex_coord: vec2<f32>,
+  @location(1) color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var tex_sampler: sampler;
+@group(1) @binding(2) var<uniform> emitter: Emitter;
+
+//Returns a normalized vector perpendicular to the direction and the fallback if there is none
+fn perpendicular(a: vec3<f32>, b: vec3<f32>, fallback: vec3<f32>) -> vec3<f32> {
+    let c = cross(a, b);
+    let length = length(c);
+    return select(fallback, c / length, length > 0.0001);
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    @location(0) position_size: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) velocity: vec3<f32>,
+) -> VertexOutput {
+    //Triangle strip of the corners (-1, -1), (1, -1), (-1, 1), (1, 1)
+    let corner = vec2(f32(index & 1u), f32(index >> 1u));
+    let offset = corner * 2.0 - 1.0;
+    let center = position_size.xyz;
+    let half_size = position_size.w * 0.5;
+
+    let to_camera = camera.position - center;
+    let speed = length(velocity);
+    var right: vec3<f32>;
+    var up: vec3<f32>;
+    var half_length = half_size;
+    if emitter.length_scale > 0.0 && speed > 0.0001 {
+        up = velocity / speed;
+        right = perpendicular(up, to_camera, vec3(1.0, 0.0, 0.0));
+        half_length += speed * emitter.length_scale * 0.5;
+    } else {
+        let forward = select(vec3(0.0, 0.0, 1.0), normalize(to_camera), length(to_camera) > 0.0001);
+        right = perpendicular(vec3(0.0, 1.0, 0.0), forward, vec3(1.0, 0.0, 0.0));
+        up = cross(forward, right);
+    }
+    let world = center + right * offset.x * half_size + up * offset.y * half_length;
+
+    var out: VertexOutput;
+    out.position = camera.matrix * vec4(world, 1.0);
+    out.tex_coord = vec2(corner.x, 1.0 - corner.y);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(texture, tex_sampler, in.tex_coord);
+    if color.a < emitter.alpha_cutoff {
+        discard;
+    }
+    return color;
+}
@@ -0,0 +1,85 @@
+const CURVE_SAMPLES: u32 = 32u;
+
+struct Particle {
+  position: vec3<f32>,
+  age: f32,
+  velocity: vec3<f32>,
+  lifetime: f32,
+}
+
+struct Instance {
+  position: vec3<f32>,
+  size: f32,
+  color: vec4<f32>,
+  velocity: vec3<f32>,
+  padding: f32,
+}
+
+struct Params {
+  gravity: vec3<f32>,
+  drag: f32,
+  delta: f32,
+  count: u32,
+  padding: vec2<u32>,
+  //Curves sampled at evenly spaced points of the lifetime, 4 samples per vector
+  size: array<vec4<f32>, 8>,
+  velocity: array<vec4<f32>, 8>,
+  color: array<vec4<f32>, 32>,
+}
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1) var<storage, read_write> instances: array<Instance>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+//Returns the indices of the samples around the normalized age and the weight of the second one
+fn samples(t: f32) -> vec3<f32> {
+    let position = clamp(t, 0.0, 1.0) * f32(CURVE_SAMPLES - 1u);
+    let first = floor(position);
+    return vec3(first, min(first + 1.0, f32(CURVE_SAMPLES - 1u)), position - first);
+}
+
+fn scalar(i: u32, size: bool) -> f32 {
+    if size {
+        return params.size[i / 4u][i % 4u];
+    }
+    return params.velocity[i / 4u][i % 4u];
+}
+
+fn sample_scalar(t: f32, size: bool) -> f32 {
+    let s = samples(t);
+    return mix(scalar(u32(s.x), size), scalar(u32(s.y), size), s.z);
+}
+
+fn sample_color(t: f32) -> vec4<f32> {
+    let s = samples(t);
+    return mix(params.color[u32(s.x)], params.color[u32(s.y)], s.z);
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.count {
+        return;
+    }
+
+    var particle = particles[i];
+    var instance: Instance;
+    if particle.age < particle.lifetime {
+        let speed = sample_scalar(particle.age / particle.lifetime, false);
+        particle.velocity += params.gravity * params.delta;
+        particle.velocity *= exp(-params.drag * params.delta);
+        particle.position += particle.velocity * (speed * params.delta);
+        particle.age += params.delta;
+        particles[i] = particle;
+
+        //Dead particles are drawn with no size
+        if particle.age < particle.lifetime {
+            let t = particle.age / particle.lifetime;
+            instance.position = particle.position;
+            instance.size = sample_scalar(t, true);
+            instance.color = sample_color(t);
+            instance.velocity = particle.velocity * sample_scalar(t, false);
+        }
+    }
+    instances[i] = instance;
+}
@@ -0,0 +1,35 @@
+struct Camera {
+  matrix: mat4x4<f32>,
+  t_matrix: mat4x4<f32>,
+  position: vec3<f32>
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) @interpolate(flat) id: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(3) trans_0: vec4<f32>,
+    @location(4) trans_1: vec4<f32>,
+    @location(5) trans_2: vec4<f32>,
+    @location(6) trans_3: vec4<f32>,
+    @location(7) id: u32,
+) -> VertexOutput {
+    let trans_mat = mat4x4<f32>(trans_0, trans_1, trans_2, trans_3);
+
+    var out: VertexOutput;
+    out.position = camera.matrix * trans_mat * vec4(position, 1.0);
+    out.id = id;
+    return out;
+}
+
+//Writes the id of the entity and the depth of the fragment, used to find the picked point
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec2<u32> {
+    return vec2(in.id, bitcast<u32>(in.position.z));
+}
@@ -0,0 +1,198 @@
+struct Params {
+  exposure: f32,
+  tonemapping: u32,
+  bloom_intensity: f32,
+  bloom_threshold: f32,
+  bloom_knee: f32,
+  vignette_intensity: f32,
+  vignette_smoothness: f32,
+  //0 - disabled, 1 - the lut is decoded from srgb by the sampler, 2 - the lut stores srgb values
+  lut_mode: u32,
+  lut_size: f32,
+  encode_srgb: u32,
+  _padding: vec2<u32>,
+}
+
+struct Output {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@group(0)@binding(0)
+var source: texture_2d<f32>;
+@group(0)@binding(1)
+var source_sampler: sampler;
+@group(0)@binding(2)
+var<uniform> params: Params;
+@group(0)@binding(3)
+var bloom: texture_2d<f32>;
+@group(0)@binding(4)
+var lut: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> Output {
+    //A single triangle covering the whole screen
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: Output;
+    out.position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    //Texture coordinates point down
+    out.uv = vec2(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+}
+
+//Averages 4 bilinear samples, which covers 4x4 texels of the source
+fn box_sample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    return (sample_source(uv + texel * vec2(-1.0, -1.0))
+        + sample_source(uv + texel * vec2(1.0, -1.0))
+        + sample_source(uv + texel * vec2(-1.0, 1.0))
+        + sample_source(uv + texel * vec2(1.0, 1.0))) * 0.25;
+}
+
+@fragment
+fn bloom_prefilter(in: Output) -> @location(0) vec4<f32> {
+    //Very bright pixels would turn into flickering squares
+    let color = min(box_sample(in.uv), vec3(1000.0));
+    let brightness = max(color.r, max(color.g, color.b));
+
+    //Soft threshold, with a quadratic curve around the threshold
+    let knee = params.bloom_threshold * params.bloom_knee + 0.00001;
+    var soft = clamp(brightness - params.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - params.bloom_threshold) / max(brightness, 0.00001);
+
+    return vec4(color * contribution, 1.0);
+}
+
+@fragment
+fn bloom_downsample(in: Output) -> @location(0) vec4<f32> {
+    return vec4(box_sample(in.uv), 1.0);
+}
+
+@fragment
+fn bloom_upsample(in: Output) -> @location(0) vec4<f32> {
+    //3x3 tent filter
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = sample_source(in.uv) * 4.0;
+    color += (sample_source(in.uv + texel * vec2(-1.0, 0.0))
+        + sample_source(in.uv + texel * vec2(1.0, 0.0))
+        + sample_source(in.uv + texel * vec2(0.0, -1.0))
+        + sample_source(in.uv + texel * vec2(0.0, 1.0))) * 2.0;
+    color += sample_source(in.uv + texel * vec2(-1.0, -1.0))
+        + sample_source(in.uv + texel * vec2(1.0, -1.0))
+        + sample_source(in.uv + texel * vec2(-1.0, 1.0))
+        + sample_source(in.uv + texel * vec2(1.0, 1.0));
+
+    return vec4(color / 16.0, 1.0);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let c = max(color, vec3(0.0));
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let c = max(color, vec3(0.0));
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    switch params.tonemapping {
+        //Reinhard
+        case 1u: {
+            return color / (1.0 + color);
+        }
+        //Aces filmic curve fit by Krzysztof Narkowicz
+        case 2u: {
+            let c = max(color, vec3(0.0));
+            return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), vec3(0.0), vec3(1.0));
+        }
+        default: {
+            return clamp(color, vec3(0.0), vec3(1.0));
+        }
+    }
+}
+
+//Looks the color up in a lut stored as a horizontal strip of blue slices, with red increasing to
+//the right and green increasing downwards in every slice
+fn grade(color: vec3<f32>) -> vec3<f32> {
+    let size = params.lut_size;
+    let coords = clamp(linear_to_srgb(color), vec3(0.0), vec3(1.0)) * (size - 1.0);
+
+    let slice = floor(coords.b);
+    let next = min(slice + 1.0, size - 1.0);
+    //Images are stored from bottom to top
+    let v = 1.0 - (coords.g + 0.5) / size;
+    let a = textureSampleLevel(lut, source_sampler, vec2((slice * size + coords.r + 0.5) / (size * size), v), 0.0).rgb;
+    let b = textureSampleLevel(lut, source_sampler, vec2((next * size + coords.r + 0.5) / (size * size), v), 0.0).rgb;
+    let graded = mix(a, b, coords.b - slice);
+
+    if params.lut_mode == 2u {
+        return srgb_to_linear(graded);
+    }
+    return graded;
+}
+
+@fragment
+fn composite(in: Output) -> @location(0) vec4<f32> {
+    var color = sample_source(in.uv);
+    color += textureSampleLevel(bloom, source_sampler, in.uv, 0.0).rgb * params.bloom_intensity;
+    color = tonemap(color * params.exposure);
+
+    if params.lut_mode != 0u {
+        color = grade(color);
+    }
+
+    if params.vignette_intensity > 0.0 {
+        let edge = distance(in.uv, vec2(0.5)) * 1.41421356;
+        let start = 1.0 - clamp(params.vignette_smoothness, 0.001, 1.0);
+        color *= 1.0 - params.vignette_intensity * smoothstep(start, 1.0, edge);
+    }
+
+    if params.encode_srgb != 0u {
+        color = linear_to_srgb(color);
+    }
+    return vec4(color, 1.0);
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+//Fast approximate anti aliasing, based on the fxaa 3.11 console version by Timothy Lottes
+@fragment
+fn fxaa(in: Output) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+
+    let color = sample_source(in.uv);
+    let nw = luma(sample_source(in.uv + texel * vec2(-1.0, -1.0)));
+    let ne = luma(sample_source(in.uv + texel * vec2(1.0, -1.0)));
+    let sw = luma(sample_source(in.uv + texel * vec2(-1.0, 1.0)));
+    let se = luma(sample_source(in.uv + texel * vec2(1.0, 1.0)));
+    let m = luma(color);
+
+    let luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    let luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    //Direction along the edge
+    var direction = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    let reduce = max((nw + ne + sw + se) * 0.25 * (1.0 / 8.0), 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-8.0), vec2(8.0)) * texel;
+
+    let a = 0.5 * (sample_source(in.uv + direction * (1.0 / 3.0 - 0.5))
+        + sample_source(in.uv + direction * (2.0 / 3.0 - 0.5)));
+    let b = a * 0.5 + 0.25 * (sample_source(in.uv - direction * 0.5)
+        + sample_source(in.uv + direction * 0.5));
+
+    let luma_b = luma(b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4(a, 1.0);
+    }
+    return vec4(b, 1.0);
+}
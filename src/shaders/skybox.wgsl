@@ -0,0 +1,38 @@
+struct Camera {
+  matrix: mat4x4<f32>,
+  t_matrix: mat4x4<f32>,
+  position: vec3<f32>
+}
+
+struct Output {
+  @builtin(position) position: vec4<f32>,
+  @location(0) clip_position: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1)@binding(0)
+var skybox: texture_cube<f32>;
+@group(1)@binding(1)
+var skybox_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> Output {
+    //A single triangle covering the whole screen
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: Output;
+    out.clip_position = uv * 2.0 - 1.0;
+    //Placed on the far plane, so it's behind everything else
+    out.position = vec4(out.clip_position, 1.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4<f32> {
+    //Unproject the point on the far plane to get the view direction
+    let world = camera.t_matrix * vec4(in.clip_position, 1.0, 1.0);
+    let direction = world.xyz / world.w - camera.position;
+
+    return textureSample(skybox, skybox_sampler, direction);
+}
@@ -0,0 +1,46 @@
+struct Camera {
+  matrix: mat4x4<f32>,
+  t_matrix: mat4x4<f32>,
+  position: vec3<f32>
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) tex_coord: vec2<f32>,
+  @location(1) tint: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var tex_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    @location(0) trans_0: vec4<f32>,
+    @location(1) trans_1: vec4<f32>,
+    @location(2) trans_2: vec4<f32>,
+    @location(3) trans_3: vec4<f32>,
+    //Offset of the bottom left corner and the size of the quad
+    @location(4) rect: vec4<f32>,
+    //Texture coordinates of the bottom left and the top right corner
+    @location(5) uv_rect: vec4<f32>,
+    @location(6) tint: vec4<f32>,
+) -> VertexOutput {
+    //Triangle strip of the corners (0, 0), (1, 0), (0, 1), (1, 1)
+    let corner = vec2(f32(index & 1u), f32(index >> 1u));
+    let position = rect.xy + corner * rect.zw;
+    let trans_mat = mat4x4(trans_0, trans_1, trans_2, trans_3);
+
+    var out: VertexOutput;
+    out.position = camera.matrix * trans_mat * vec4(position, 0.0, 1.0);
+    out.tex_coord = mix(uv_rect.xy, uv_rect.zw, corner);
+    out.tint = tint;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.tint * textureSample(texture, tex_sampler, in.tex_coord);
+}
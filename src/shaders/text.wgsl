@@ -0,0 +1,56 @@
+struct Camera {
+  matrix: mat4x4<f32>,
+  t_matrix: mat4x4<f32>,
+  position: vec3<f32>
+}
+
+struct VertexInput {
+  //World position for world text, normalized device coordinates for screen text
+  @location(0) position: vec3<f32>,
+  @location(1) tex_coord: vec2<f32>,
+  @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) tex_coord: vec2<f32>,
+  @location(1) color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var glyphs: texture_2d<f32>;
+@group(1) @binding(1) var glyph_sampler: sampler;
+
+@vertex
+fn vs_world(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.matrix * vec4(in.position, 1.0);
+    out.tex_coord = in.tex_coord;
+    out.color = in.color;
+    return out;
+}
+
+@vertex
+fn vs_screen(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4(in.position, 1.0);
+    out.tex_coord = in.tex_coord;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(glyphs, glyph_sampler, in.tex_coord).r;
+    return vec4(in.color.rgb, in.color.a * coverage);
+}
+
+@fragment
+fn fs_sdf(in: VertexOutput) -> @location(0) vec4<f32> {
+    //0.5 is the edge of the glyph, smoothed over about a pixel at any size
+    let distance = textureSample(glyphs, glyph_sampler, in.tex_coord).r;
+    let width = max(fwidth(distance) * 0.7, 0.001);
+    let coverage = smoothstep(0.5 - width, 0.5 + width, distance);
+    return vec4(in.color.rgb, in.color.a * coverage);
+}
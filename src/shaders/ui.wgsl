@@ -0,0 +1,38 @@
+struct VertexInput {
+  //Normalized device coordinates
+  @location(0) position: vec2<f32>,
+  @location(1) tex_coord: vec2<f32>,
+  @location(2) color: vec4<f32>,
+  //0 for solid shapes, 1 for glyphs with coverage and 2 for signed distance field glyphs
+  @location(3) kind: f32,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) tex_coord: vec2<f32>,
+  @location(1) color: vec4<f32>,
+  @location(2) kind: f32,
+}
+
+@group(0) @binding(0) var glyphs: texture_2d<f32>;
+@group(0) @binding(1) var glyph_sampler: sampler;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4(in.position, 0.0, 1.0);
+    out.tex_coord = in.tex_coord;
+    out.color = in.color;
+    out.kind = in.kind;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let value = textureSample(glyphs, glyph_sampler, in.tex_coord).r;
+    let width = max(fwidth(value) * 0.7, 0.001);
+    let distance = smoothstep(0.5 - width, 0.5 + width, value);
+
+    let coverage = select(select(distance, value, in.kind < 1.5), 1.0, in.kind < 0.5);
+    return vec4(in.color.rgb, in.color.a * coverage);
+}
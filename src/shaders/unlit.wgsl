@@ -1,5 +1,7 @@
 struct MaterialData {
   color: vec4<f32>,
+  //Pixels with alpha below the cutoff are discarded
+  alpha_cutoff: f32,
 }
 
 struct Camera {
@@ -21,5 +23,9 @@ var tex_sampler: sampler;
 fn main(@builtin(position) pos: vec4<f32>, @location(0) uvs: vec2<f32>, @location(1) normal: vec3<f32>, @location(2) view_dir: vec3<f32>, @location(3) world_pos: vec3<f32>) -> @location(0) vec4<f32> {
     let color = material.color * textureSample(texture, tex_sampler, uvs);
 
+    if color.a < material.alpha_cutoff {
+        discard;
+    }
+
     return color;
 }
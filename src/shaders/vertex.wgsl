@@ -18,7 +18,35 @@ struct Camera {
   position: vec3<f32>
 }
 
+struct Joints {
+  matrices: array<mat4x4<f32>, 128>
+}
+
 @group(0) @binding(0) var<uniform> camera: Camera;
+//Only used by skinned meshes
+@group(0) @binding(1) var<uniform> joints: Joints;
+
+fn transform(
+    position: vec3<f32>,
+    uvs: vec2<f32>,
+    normal: vec3<f32>,
+    trans_mat: mat4x4<f32>,
+) -> ColorOutput {
+    var res: ColorOutput;
+
+    var o = trans_mat * vec4(position, 1.0);
+    res.world_position = o.xyz;
+    res.view_dir = normalize(o.xyz - camera.position);
+
+    res.position = camera.matrix * o;
+    res.tex_coord = uvs;
+
+    //Transform the normals, and normalize them
+    //Use a 3x3 matrix to avoid doing translation
+    res.normal = normalize(mat3x3(trans_mat[0].xyz, trans_mat[1].xyz, trans_mat[2].xyz) * normal);
+
+    return res;
+}
 
 @vertex
 fn main(
@@ -37,19 +65,33 @@ fn main(
         trans_3,
     );
 
-    var res: ColorOutput;
-
-    var o = trans_mat * vec4(position, 1.0);
-    res.world_position = o.xyz;
-    res.view_dir = normalize(o.xyz - camera.position);
-
-    res.position = camera.matrix * o;
-    res.tex_coord = uvs;
+    return transform(position, uvs, normal, trans_mat);
+}
 
-    //Transform the normals, and normalize them
-    //Use a 3x3 matrix to avoid doing translation
-    res.normal = normalize(mat3x3(trans_mat[0].xyz, trans_mat[1].xyz, trans_mat[2].xyz) * normal);
+@vertex
+fn skinned(
+    @location(0) position: vec3<f32>,
+    @location(1) uvs: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) trans_0: vec4<f32>,
+    @location(4) trans_1: vec4<f32>,
+    @location(5) trans_2: vec4<f32>,
+    @location(6) trans_3: vec4<f32>,
+    @location(7) joint_indices: vec4<u32>,
+    @location(8) weights: vec4<f32>,
+) -> ColorOutput {
+    let trans_mat = mat4x4<f32>(
+        trans_0,
+        trans_1,
+        trans_2,
+        trans_3,
+    );
 
+    //Blend the matrices of the joints that influence the vertex
+    let skin_mat = joints.matrices[joint_indices.x] * weights.x
+        + joints.matrices[joint_indices.y] * weights.y
+        + joints.matrices[joint_indices.z] * weights.z
+        + joints.matrices[joint_indices.w] * weights.w;
 
-    return res;
+    return transform(position, uvs, normal, trans_mat * skin_mat);
 }
@@ -2,7 +2,7 @@
 
 use bytemuck::{Pod, Zeroable};
 
-use crate::math::{IntoFloat32, Vec2, Vec3, Vec4};
+use crate::math::{IntoFloat32, Mat4x4, Vec2, Vec3, Vec4};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Pod, Zeroable)]
@@ -15,6 +15,15 @@ pub struct Vertex {
     ///Normal direction
     pub normal: Vec3,
 }
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Pod, Zeroable)]
+///Joints that influence a vertex of a skinned mesh
+pub struct VertexSkin {
+    ///Indices of the joints in the skeleton
+    pub joints: [u16; 4],
+    ///Weights of the joints, should add up to 1
+    pub weights: Vec4,
+}
 ///Indecies of a mesh
 pub type Index = u32;
 #[repr(C)]
@@ -81,6 +90,31 @@ pub(crate) struct LightBuffer {
     pub camera_direction: Vec3,
 }
 
+///Describes a spot light
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub(crate) struct SpotLightBuffer {
+    ///Position of the light
+    pub position: Vec3,
+    ///Range of the light
+    pub range: f32,
+    ///Direction of the light
+    pub direction: Vec3,
+    ///Intensity of the light
+    pub intensity: f32,
+    ///Color of the light
+    pub color: Vec3,
+    ///Cosine of the inner angle of the cone
+    pub inner_cos: f32,
+    ///Cosine of the outer angle of the cone
+    pub outer_cos: f32,
+    ///Layer of the shadow map, -1 if the light does not cast shadows
+    pub shadow_index: i32,
+    pub padding: [f32; 2],
+    ///View projection matrix of the shadow map
+    pub shadow_matrix: Mat4x4,
+}
+
 impl Color {
     ///Create new color from the 4 components
     #[must_use]
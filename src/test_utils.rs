@@ -10,7 +10,10 @@ async fn gen_gpu_async(instance: &wgpu::Instance) -> (wgpu::Device, wgpu::Queue)
         .expect("Unable to get an adapter");
 
     adapter
-        .request_device(&wgpu::DeviceDescriptor::default())
+        .request_device(&wgpu::DeviceDescriptor {
+            required_features: adapter.features() & crate::windowing::TEXTURE_COMPRESSION_FEATURES,
+            ..Default::default()
+        })
         .await
         .expect("Can not get device and queue")
 }
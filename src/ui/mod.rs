@@ -0,0 +1,924 @@
+//! Immediate mode user interface for menus, HUDs and debug panels
+//!
+//! The interface is built every frame by calling the widget functions of a [`Frame`], which
+//! return whether the widgets were interacted with. The [`Ui`] keeps the state that has to
+//! survive between frames, like the positions of the windows and the focused text field, and it
+//! is the render node that draws the interface over everything else
+//!
+//! # Usage
+//!```
+//!# use lunar_engine::{asset_managment::AssetStore, math::Vec2, ui::Ui};
+//!# use lunar_engine::rendering::{extensions::Base, render};
+//!# struct State {world: lunar_engine::ecs::World, assets: AssetStore, base: Base, ui: Ui, volume: f32, name: String}
+//! fn update(state: &mut State) {
+//!     let mut frame = state.ui.begin(&state.assets);
+//!     if frame.button("Quit") {
+//!         lunar_engine::quit();
+//!     }
+//!     frame.window("Settings", Vec2::new(20, 80), |ui| {
+//!         ui.slider("Volume", &mut state.volume, 0.0..=1.0);
+//!         ui.text_field("Name", &mut state.name);
+//!     });
+//!     //The interface is drawn when the frame is dropped
+//!     drop(frame);
+//!
+//!     render(
+//!         &state.world,
+//!         &mut state.assets,
+//!         &mut [&mut state.base, &mut state.ui],
+//!     );
+//! }
+//!```
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::RangeInclusive,
+};
+
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{
+    UUID,
+    asset_managment::{Asset as _, AssetGuard, AssetStore},
+    assets::{Font, font::LayoutOptions},
+    input::{self, KeyState},
+    math::Vec2,
+    rendering::extensions::ui::UiGpu,
+    structures::Color,
+};
+
+mod theme;
+
+pub use theme::Theme;
+
+///A rectangle in logical pixels, from the top left corner of the window
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    ///Left edge
+    pub x: f32,
+    ///Top edge
+    pub y: f32,
+    ///Width of the rectangle
+    pub width: f32,
+    ///Height of the rectangle
+    pub height: f32,
+}
+
+impl Rect {
+    ///Creates a new rectangle
+    #[must_use]
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    ///Returns whether the point is inside of the rectangle
+    #[must_use]
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.x
+            && point.y >= self.y
+            && point.x < self.x + self.width
+            && point.y < self.y + self.height
+    }
+
+    ///Returns the rectangle grown by the amount on every side
+    #[must_use]
+    pub fn expanded(&self, amount: f32) -> Self {
+        Self::new(
+            self.x - amount,
+            self.y - amount,
+            amount.mul_add(2.0, self.width),
+            amount.mul_add(2.0, self.height),
+        )
+    }
+}
+
+///The input the interface reacts to
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiInput {
+    ///Position of the cursor in physical pixels from the top left corner of the window
+    pub cursor: Vec2,
+    ///State of the left mouse button
+    pub mouse: KeyState,
+    ///Text typed since the last frame
+    pub text: String,
+    ///Whether backspace was pressed
+    pub backspace: bool,
+    ///Whether enter or escape was pressed, which unfocuses text fields
+    pub submit: bool,
+}
+
+impl Default for UiInput {
+    fn default() -> Self {
+        Self {
+            cursor: Vec2::default(),
+            mouse: KeyState::Neutral,
+            text: String::new(),
+            backspace: false,
+            submit: false,
+        }
+    }
+}
+
+impl UiInput {
+    ///Returns the input of the current frame from the [`crate::input`] module
+    #[must_use]
+    pub fn current() -> Self {
+        let down = |k| input::key(k) == KeyState::Down;
+        Self {
+            cursor: input::cursor_position(),
+            mouse: input::mouse_btn(MouseButton::Left),
+            text: input::text_input(),
+            backspace: down(KeyCode::Backspace),
+            submit: down(KeyCode::Enter) || down(KeyCode::NumpadEnter) || down(KeyCode::Escape),
+        }
+    }
+}
+
+///Something drawn by the interface, in logical pixels
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Shape {
+    ///A filled rectangle
+    Rect { rect: Rect, color: Color },
+    ///A single line of text, the position is the top left corner of the line
+    Text {
+        position: Vec2,
+        text: String,
+        size: f32,
+        color: Color,
+    },
+}
+
+///A shape and the rectangle it is clipped to
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DrawCommand {
+    pub shape: Shape,
+    pub clip: Option<Rect>,
+}
+
+///State of a window that is kept between frames
+#[derive(Debug, Clone, Copy)]
+struct WindowState {
+    id: u64,
+    ///Position and size in the last frame
+    rect: Rect,
+    ///Whether the window was shown in the last frame
+    visible: bool,
+    ///Position of the cursor relative to the window while it is dragged
+    drag: Option<Vec2>,
+}
+
+///The state of the interface and the render node that draws it
+///
+///The interface is drawn on the surface over the whole window, after the post processing, so
+///the priority of the node should be larger than the priority of the
+///[`PostProcessing`](crate::rendering::extensions::post_processing::PostProcessing) node
+pub struct Ui {
+    ///Colors and sizes of the widgets
+    pub theme: Theme,
+    ///Priority of the extension
+    pub priority: u32,
+    font: Option<UUID>,
+    scale: Option<f32>,
+    ///Widget that is held down
+    active: Option<u64>,
+    ///Text field that receives the typed text
+    focused: Option<u64>,
+    ///Windows from the back to the front
+    windows: Vec<WindowState>,
+    ///Whether the cursor was over the interface in the last frame
+    hovered: bool,
+    ///Shapes of the last frame, from the back to the front
+    commands: Vec<DrawCommand>,
+    pub(crate) gpu: UiGpu,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new(30)
+    }
+}
+
+impl Ui {
+    ///Creates a new interface with the default theme and no font
+    #[must_use]
+    pub fn new(priority: u32) -> Self {
+        Self {
+            theme: Theme::default(),
+            priority,
+            font: None,
+            scale: None,
+            active: None,
+            focused: None,
+            windows: Vec::new(),
+            hovered: false,
+            commands: Vec::new(),
+            gpu: UiGpu::default(),
+        }
+    }
+
+    ///Sets the [`Font`] of the text, without a font the text is not drawn
+    pub const fn set_font(&mut self, font: Option<UUID>) {
+        self.font = font;
+    }
+
+    ///Returns the id of the font of the text
+    #[must_use]
+    pub const fn get_font(&self) -> Option<UUID> {
+        self.font
+    }
+
+    ///Sets the number of physical pixels per logical pixel, `None` uses the scale factor of the
+    ///window
+    pub const fn set_scale(&mut self, scale: Option<f32>) {
+        self.scale = scale;
+    }
+
+    ///Returns the number of physical pixels per logical pixel
+    #[must_use]
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+            .unwrap_or_else(|| crate::WINDOW.get().map_or(1.0, |w| w.scale_factor() as f32))
+    }
+
+    ///Returns whether the cursor was over a window or a widget in the last frame, or a widget is
+    ///held down, in which case the game should usually ignore the mouse
+    #[must_use]
+    pub const fn is_hovered(&self) -> bool {
+        self.hovered || self.active.is_some()
+    }
+
+    ///Returns whether a text field is focused, in which case the game should usually ignore the
+    ///keyboard
+    #[must_use]
+    pub const fn is_typing(&self) -> bool {
+        self.focused.is_some()
+    }
+
+    ///Starts a new frame of the interface using the input from the [`crate::input`] module
+    pub fn begin<'a>(&'a mut self, assets: &'a AssetStore) -> Frame<'a> {
+        self.begin_with_input(assets, UiInput::current())
+    }
+
+    ///Starts a new frame of the interface that reacts to the input
+    pub fn begin_with_input<'a>(&'a mut self, assets: &'a AssetStore, input: UiInput) -> Frame<'a> {
+        let font = self
+            .font
+            .filter(|id| assets.is_type::<Font>(*id))
+            .and_then(|id| assets.borrow_by_id::<Font>(id).ok())
+            .filter(|f| f.is_initialized());
+
+        let scale = self.get_scale();
+        let input = UiInput {
+            cursor: Vec2::new(input.cursor.x / scale, input.cursor.y / scale),
+            ..input
+        };
+        if input.mouse == KeyState::Down {
+            self.focused = None;
+        }
+        //The windows only get input if they are the top most window under the cursor
+        let hovered_window = self
+            .windows
+            .iter()
+            .rev()
+            .find(|w| w.visible && w.rect.contains(input.cursor))
+            .map(|w| w.id);
+        let start = Vec2::new(self.theme.spacing, self.theme.spacing);
+
+        Frame {
+            theme: self.theme,
+            ui: self,
+            font,
+            input,
+            hovered_window,
+            hovered: hovered_window.is_some(),
+            layers: vec![Layer {
+                window: None,
+                commands: Vec::new(),
+            }],
+            layer: 0,
+            layouts: vec![Layout::new(start, false)],
+        }
+    }
+
+    pub(crate) fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+}
+
+///Shapes of the background or of a window
+struct Layer {
+    window: Option<u64>,
+    commands: Vec<DrawCommand>,
+}
+
+///Places the widgets one after another
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    start: Vec2,
+    cursor: Vec2,
+    horizontal: bool,
+    ///Size of the placed widgets
+    size: Vec2,
+}
+
+impl Layout {
+    fn new(start: Vec2, horizontal: bool) -> Self {
+        Self {
+            start,
+            cursor: start,
+            horizontal,
+            size: Vec2::default(),
+        }
+    }
+
+    fn allocate(&mut self, size: Vec2, spacing: f32) -> Rect {
+        let rect = Rect::new(self.cursor.x, self.cursor.y, size.x, size.y);
+        self.size.x = self.size.x.max(self.cursor.x + size.x - self.start.x);
+        self.size.y = self.size.y.max(self.cursor.y + size.y - self.start.y);
+        if self.horizontal {
+            self.cursor.x += size.x + spacing;
+        } else {
+            self.cursor.y += size.y + spacing;
+        }
+        rect
+    }
+}
+
+///How a widget was interacted with
+#[derive(Debug, Clone, Copy)]
+struct Interaction {
+    hovered: bool,
+    ///The widget is held down
+    active: bool,
+    ///The widget was pressed and released
+    clicked: bool,
+}
+
+///A frame of the interface, the widgets are placed from the top to the bottom in the order they
+///are added, starting at the top left corner of the window
+///
+///The frame is finished and the interface is drawn when it is dropped. Widgets are identified by
+///their labels, so the labels of widgets of the same kind must be unique in every window
+pub struct Frame<'a> {
+    ui: &'a mut Ui,
+    theme: Theme,
+    font: Option<AssetGuard<'a, Font>>,
+    ///Input with the cursor in logical pixels
+    input: UiInput,
+    hovered_window: Option<u64>,
+    hovered: bool,
+    layers: Vec<Layer>,
+    layer: usize,
+    layouts: Vec<Layout>,
+}
+
+impl Frame<'_> {
+    ///Returns the size of the text in logical pixels
+    #[must_use]
+    pub fn text_size(&self, text: &str) -> Vec2 {
+        self.font.as_ref().map_or_else(
+            || {
+                Vec2::new(
+                    text.chars().count() as f32 * self.theme.font_size * 0.5,
+                    self.line_height(),
+                )
+            },
+            |font| {
+                let layout = font.layout(text, &LayoutOptions::new(self.theme.font_size));
+                Vec2::new(layout.width, layout.height)
+            },
+        )
+    }
+
+    ///Height of a line of text
+    fn line_height(&self) -> f32 {
+        self.font
+            .as_ref()
+            .and_then(|f| f.metrics())
+            .map_or(self.theme.font_size * 1.2, |m| {
+                (f32::from(m.ascender) - f32::from(m.descender)) * self.theme.font_size
+                    / f32::from(m.units_per_em)
+            })
+    }
+
+    ///Height of the widgets with a line of text
+    fn widget_height(&self) -> f32 {
+        self.theme.padding.mul_add(2.0, self.line_height())
+    }
+
+    fn id(&self, kind: &str, label: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.layers[self.layer].window, kind, label).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn allocate(&mut self, size: Vec2) -> Rect {
+        let spacing = self.theme.spacing;
+        self.layouts.last_mut().unwrap().allocate(size, spacing)
+    }
+
+    fn interact(&mut self, id: u64, rect: Rect) -> Interaction {
+        let hovered = self.hovered_window == self.layers[self.layer].window
+            && self.ui.active.is_none_or(|a| a == id)
+            && rect.contains(self.input.cursor);
+        if hovered {
+            self.hovered = true;
+            if self.input.mouse == KeyState::Down {
+                self.ui.active = Some(id);
+            }
+        }
+        let active = self.ui.active == Some(id);
+        Interaction {
+            hovered,
+            active,
+            clicked: active && hovered && self.input.mouse == KeyState::Up,
+        }
+    }
+
+    fn draw(&mut self, shape: Shape, clip: Option<Rect>) {
+        self.layers[self.layer]
+            .commands
+            .push(DrawCommand { shape, clip });
+    }
+
+    fn fill(&mut self, rect: Rect, color: Color) {
+        self.draw(Shape::Rect { rect, color }, None);
+    }
+
+    fn text(&mut self, position: Vec2, text: &str, color: Color) {
+        if !text.is_empty() {
+            self.draw(
+                Shape::Text {
+                    position,
+                    text: text.to_owned(),
+                    size: self.theme.font_size,
+                    color,
+                },
+                None,
+            );
+        }
+    }
+
+    const fn widget_color(&self, interaction: Interaction) -> Color {
+        if interaction.active {
+            self.theme.widget_active
+        } else if interaction.hovered {
+            self.theme.widget_hovered
+        } else {
+            self.theme.widget
+        }
+    }
+
+    ///Adds a line of text
+    pub fn label(&mut self, text: &str) {
+        let size = self.text_size(text);
+        let rect = self.allocate(Vec2::new(size.x, self.widget_height()));
+        let color = self.theme.text;
+        self.text(Vec2::new(rect.x, rect.y + self.theme.padding), text, color);
+    }
+
+    ///Adds a button, returns true if it was clicked
+    pub fn button(&mut self, text: &str) -> bool {
+        let padding = self.theme.padding;
+        let size = self.text_size(text);
+        let rect = self.allocate(Vec2::new(
+            padding.mul_add(2.0, size.x),
+            self.widget_height(),
+        ));
+        let interaction = self.interact(self.id("button", text), rect);
+
+        self.fill(rect, self.widget_color(interaction));
+        let color = self.theme.text;
+        self.text(Vec2::new(rect.x + padding, rect.y + padding), text, color);
+        interaction.clicked
+    }
+
+    ///Adds a checkbox, clicking it toggles the value, returns true if the value changed
+    pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+        let (padding, spacing) = (self.theme.padding, self.theme.spacing);
+        let side = self.line_height();
+        let width = side + spacing + self.text_size(text).x;
+        let rect = self.allocate(Vec2::new(width, self.widget_height()));
+        let interaction = self.interact(self.id("checkbox", text), rect);
+        if interaction.clicked {
+            *value = !*value;
+        }
+
+        let check = Rect::new(rect.x, rect.y + padding, side, side);
+        self.fill(check, self.widget_color(interaction));
+        if *value {
+            self.fill(check.expanded(-side / 5.0), self.theme.accent);
+        }
+        let color = self.theme.text;
+        self.text(
+            Vec2::new(rect.x + side + spacing, rect.y + padding),
+            text,
+            color,
+        );
+        interaction.clicked
+    }
+
+    ///Adds a slider that changes the value within the range while it is held, returns true if
+    ///the value changed
+    pub fn slider(&mut self, text: &str, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+        let rect = self.allocate(Vec2::new(self.theme.widget_width, self.widget_height()));
+        let interaction = self.interact(self.id("slider", text), rect);
+
+        let (start, end) = (*range.start(), *range.end());
+        let previous = *value;
+        if interaction.active && rect.width > 0.0 {
+            let t = ((self.input.cursor.x - rect.x) / rect.width).clamp(0.0, 1.0);
+            *value = (end - start).mul_add(t, start);
+        }
+        let t = if end > start {
+            ((*value - start) / (end - start)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        self.fill(rect, self.widget_color(interaction));
+        self.fill(
+            Rect::new(rect.x, rect.y, rect.width * t, rect.height),
+            self.theme.accent,
+        );
+        let label = format!("{text}: {value:.2}");
+        let size = self.text_size(&label);
+        let color = self.theme.text;
+        self.text(
+            Vec2::new(
+                rect.x + (rect.width - size.x) / 2.0,
+                rect.y + self.theme.padding,
+            ),
+            &label,
+            color,
+        );
+        (*value - previous).abs() > f32::EPSILON
+    }
+
+    ///Adds a single line text field, the hint is shown when the text is empty
+    ///
+    ///Clicking the field focuses it, typed text is added to the end of the value while it is
+    ///focused and enter, escape or clicking somewhere else unfocus it. Returns true if the value
+    ///changed
+    pub fn text_field(&mut self, hint: &str, value: &mut String) -> bool {
+        let padding = self.theme.padding;
+        let rect = self.allocate(Vec2::new(self.theme.widget_width, self.widget_height()));
+        let id = self.id("text_field", hint);
+        let interaction = self.interact(id, rect);
+        if interaction.hovered && self.input.mouse == KeyState::Down {
+            self.ui.focused = Some(id);
+        }
+
+        let focused = self.ui.focused == Some(id);
+        let mut changed = false;
+        if focused {
+            let typed = self.input.text.chars().filter(|c| !c.is_control());
+            for character in typed {
+                value.push(character);
+                changed = true;
+            }
+            if self.input.backspace {
+                changed |= value.pop().is_some();
+            }
+            if self.input.submit {
+                self.ui.focused = None;
+            }
+        }
+
+        let background = if focused {
+            self.theme.widget_active
+        } else {
+            self.widget_color(interaction)
+        };
+        self.fill(rect, background);
+
+        //The end of long text stays visible
+        let size = self.text_size(value);
+        let x = (rect.x + padding).min(rect.x + rect.width - padding - size.x);
+        let clip = Some(rect.expanded(-padding / 2.0));
+        let (text, color) = if value.is_empty() && !focused {
+            (
+                hint,
+                Color {
+                    a: self.theme.text.a * 0.5,
+                    ..self.theme.text
+                },
+            )
+        } else {
+            (value.as_str(), self.theme.text)
+        };
+        if !text.is_empty() {
+            self.draw(
+                Shape::Text {
+                    position: Vec2::new(x, rect.y + padding),
+                    text: text.to_owned(),
+                    size: self.theme.font_size,
+                    color,
+                },
+                clip,
+            );
+        }
+        if focused {
+            self.draw(
+                Shape::Rect {
+                    rect: Rect::new(x + size.x, rect.y + padding, 2.0, self.line_height()),
+                    color: self.theme.accent,
+                },
+                clip,
+            );
+        }
+        changed
+    }
+
+    ///Adds a horizontal line
+    pub fn separator(&mut self) {
+        let rect = self.allocate(Vec2::new(self.theme.widget_width, 1.0));
+        self.fill(rect, self.theme.border);
+    }
+
+    ///Adds empty space
+    pub fn space(&mut self, amount: f32) {
+        self.allocate(Vec2::new(amount, amount));
+    }
+
+    ///Places the widgets added by the function next to each other from the left to the right
+    pub fn horizontal<R>(&mut self, contents: impl FnOnce(&mut Self) -> R) -> R {
+        let start = self.layouts.last().unwrap().cursor;
+        self.layouts.push(Layout::new(start, true));
+        let result = contents(self);
+        let layout = self.layouts.pop().unwrap();
+        self.allocate(layout.size);
+        result
+    }
+
+    ///Adds a window with the widgets added by the function
+    ///
+    ///The window is placed at the position in logical pixels the first time it is shown, it can
+    ///be moved by dragging its title bar and it is as large as its contents. Clicking a window
+    ///brings it to the front
+    pub fn window<R>(
+        &mut self,
+        title: &str,
+        position: Vec2,
+        contents: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let (padding, line_height) = (self.theme.padding, self.widget_height());
+        let id = self.id("window", title);
+        let index = self
+            .ui
+            .windows
+            .iter()
+            .position(|w| w.id == id)
+            .unwrap_or_else(|| {
+                self.ui.windows.push(WindowState {
+                    id,
+                    rect: Rect::new(position.x, position.y, 0.0, 0.0),
+                    visible: false,
+                    drag: None,
+                });
+                self.ui.windows.len() - 1
+            });
+
+        let previous = self.layer;
+        self.layers.push(Layer {
+            window: Some(id),
+            commands: Vec::new(),
+        });
+        self.layer = self.layers.len() - 1;
+
+        //Dragging the title bar moves the window
+        let title_width = padding.mul_add(2.0, self.text_size(title).x);
+        let mut rect = self.ui.windows[index].rect;
+        let bar = Rect::new(rect.x, rect.y, rect.width.max(title_width), line_height);
+        let interaction = self.interact(self.id("title", title), bar);
+        let window = &mut self.ui.windows[index];
+        if !interaction.active {
+            window.drag = None;
+        } else if let Some(offset) = window.drag {
+            rect.x = self.input.cursor.x - offset.x;
+            rect.y = self.input.cursor.y - offset.y;
+        } else {
+            window.drag = Some(Vec2::new(
+                self.input.cursor.x - rect.x,
+                self.input.cursor.y - rect.y,
+            ));
+        }
+
+        self.layouts.push(Layout::new(
+            Vec2::new(rect.x + padding, rect.y + line_height + padding),
+            false,
+        ));
+        let result = contents(self);
+        let content = self.layouts.pop().unwrap().size;
+        rect.width = padding.mul_add(2.0, content.x).max(title_width);
+        rect.height = padding.mul_add(2.0, line_height + content.y);
+
+        //The background is drawn under the contents
+        let title = DrawCommand {
+            shape: Shape::Text {
+                position: Vec2::new(rect.x + padding, rect.y + padding),
+                text: title.to_owned(),
+                size: self.theme.font_size,
+                color: self.theme.text,
+            },
+            clip: None,
+        };
+        let background = [
+            (rect.expanded(1.0), self.theme.border),
+            (rect, self.theme.window),
+            (
+                Rect::new(rect.x, rect.y, rect.width, line_height),
+                self.theme.title_bar,
+            ),
+        ]
+        .map(|(rect, color)| DrawCommand {
+            shape: Shape::Rect { rect, color },
+            clip: None,
+        });
+        self.layers[self.layer]
+            .commands
+            .splice(0..0, background.into_iter().chain([title]));
+
+        let window = &mut self.ui.windows[index];
+        window.rect = rect;
+        window.visible = true;
+        self.layer = previous;
+        result
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        let shown = self
+            .layers
+            .iter()
+            .filter_map(|l| l.window)
+            .collect::<Vec<_>>();
+        for window in &mut self.ui.windows {
+            window.visible = shown.contains(&window.id);
+        }
+        //Clicking a window brings it to the front
+        if let Some(index) = self
+            .hovered_window
+            .filter(|_| self.input.mouse == KeyState::Down)
+            .and_then(|id| self.ui.windows.iter().position(|w| w.id == id))
+        {
+            let window = self.ui.windows.remove(index);
+            self.ui.windows.push(window);
+        }
+        if matches!(self.input.mouse, KeyState::Up | KeyState::Neutral) {
+            self.ui.active = None;
+        }
+        self.ui.hovered = self.hovered;
+
+        //The background is drawn first and the windows from the back to the front
+        let mut layers = std::mem::take(&mut self.layers);
+        let order = |layer: &Layer| {
+            layer
+                .window
+                .and_then(|id| self.ui.windows.iter().position(|w| w.id == id))
+        };
+        layers.sort_by_key(order);
+        self.ui.commands = layers.into_iter().flat_map(|l| l.commands).collect();
+    }
+}
+
+#[test]
+fn test_ui_widgets() {
+    fn frame<R>(
+        ui: &mut Ui,
+        assets: &AssetStore,
+        input: UiInput,
+        contents: impl FnOnce(&mut Frame) -> R,
+    ) -> R {
+        let mut frame = ui.begin_with_input(assets, input);
+        contents(&mut frame)
+    }
+    let mouse = |x, y, mouse| UiInput {
+        cursor: Vec2::new(x, y),
+        mouse,
+        ..Default::default()
+    };
+    let keyboard = |text: &str, backspace, submit| UiInput {
+        text: text.to_owned(),
+        backspace,
+        submit,
+        ..Default::default()
+    };
+
+    let mut assets = AssetStore::new();
+    let data = crate::import::ttf::test_font(None).into_boxed_slice();
+    let font = assets.register(Font::from_static(Box::leak(data)));
+    assets.intialize_all().unwrap();
+    let mut ui = Ui::default();
+    ui.set_font(Some(font));
+    ui.set_scale(Some(1.0));
+
+    //A line of the test font is as high as the font size, so the widgets are 28 pixels high
+    let mut values = (false, 0.0, String::new());
+    let widgets = |ui: &mut Ui, values: &mut (bool, f32, String), input| {
+        frame(ui, &assets, input, |f| {
+            [
+                f.button("A"),
+                f.checkbox("A", &mut values.0),
+                f.slider("A", &mut values.1, 0.0..=2.0),
+                f.text_field("A", &mut values.2),
+            ]
+        })
+    };
+
+    //Buttons are clicked when the mouse is released over them
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(10.0, 10.0, KeyState::Down)),
+        [false; 4]
+    );
+    assert!(ui.is_hovered());
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(10.0, 10.0, KeyState::Pressed)),
+        [false; 4]
+    );
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(10.0, 10.0, KeyState::Up)),
+        [true, false, false, false]
+    );
+    widgets(&mut ui, &mut values, mouse(10.0, 10.0, KeyState::Down));
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(100.0, 10.0, KeyState::Up)),
+        [false; 4]
+    );
+    assert!(!ui.is_hovered());
+
+    widgets(&mut ui, &mut values, mouse(10.0, 40.0, KeyState::Down));
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(10.0, 40.0, KeyState::Up)),
+        [false, true, false, false]
+    );
+
+    //Sliders follow the cursor while they are held
+    assert_eq!(
+        widgets(&mut ui, &mut values, mouse(104.0, 80.0, KeyState::Down)),
+        [false, false, true, false]
+    );
+    assert!((values.1 - 1.0).abs() < 1e-5);
+    widgets(&mut ui, &mut values, mouse(500.0, 10.0, KeyState::Pressed));
+    widgets(&mut ui, &mut values, mouse(500.0, 10.0, KeyState::Up));
+    widgets(&mut ui, &mut values, mouse(10.0, 80.0, KeyState::Neutral));
+
+    //Text fields receive the typed text while they are focused
+    widgets(&mut ui, &mut values, keyboard("x", false, false));
+    widgets(&mut ui, &mut values, mouse(10.0, 110.0, KeyState::Down));
+    assert!(ui.is_typing());
+    assert_eq!(
+        widgets(&mut ui, &mut values, keyboard("Hi!\u{8}", false, false)),
+        [false, false, false, true]
+    );
+    widgets(&mut ui, &mut values, keyboard("", true, false));
+    widgets(&mut ui, &mut values, keyboard("", false, true));
+    assert!(!ui.is_typing());
+    widgets(&mut ui, &mut values, keyboard("x", false, false));
+    assert_eq!(values, (true, 2.0, "Hi".to_owned()));
+
+    //Windows block the widgets under them and are moved by their title bars
+    let mut clicks = (0, 0);
+    let window = |ui: &mut Ui, clicks: &mut (usize, usize), input| {
+        frame(ui, &assets, input, |f| {
+            clicks.0 += usize::from(f.button("A"));
+            f.window("Window", Vec2::new(0.0, 0.0), |f| {
+                clicks.1 += usize::from(f.button("A"));
+            });
+        });
+    };
+    window(&mut ui, &mut clicks, UiInput::default());
+    assert_eq!(ui.windows[0].rect, Rect::new(0.0, 0.0, 60.0, 68.0));
+    window(&mut ui, &mut clicks, mouse(10.0, 10.0, KeyState::Down));
+    window(&mut ui, &mut clicks, mouse(60.0, 60.0, KeyState::Pressed));
+    window(&mut ui, &mut clicks, mouse(60.0, 60.0, KeyState::Up));
+    assert_eq!(ui.windows[0].rect, Rect::new(50.0, 50.0, 60.0, 68.0));
+    assert_eq!(clicks, (0, 0));
+    window(&mut ui, &mut clicks, mouse(60.0, 90.0, KeyState::Down));
+    window(&mut ui, &mut clicks, mouse(60.0, 90.0, KeyState::Up));
+    window(&mut ui, &mut clicks, mouse(10.0, 10.0, KeyState::Down));
+    window(&mut ui, &mut clicks, mouse(10.0, 10.0, KeyState::Up));
+    assert_eq!(clicks, (1, 1));
+    //The window is drawn over the background
+    assert_eq!(
+        ui.commands().last().map(|c| &c.shape),
+        Some(&Shape::Text {
+            position: Vec2::new(62.0, 90.0),
+            text: "A".to_owned(),
+            size: 16.0,
+            color: ui.theme.text
+        })
+    );
+
+    //The cursor is converted to logical pixels
+    ui.set_scale(Some(2.0));
+    let button = |ui: &mut Ui, input| frame(ui, &assets, input, |f| f.button("A"));
+    button(&mut ui, mouse(20.0, 20.0, KeyState::Down));
+    assert!(button(&mut ui, mouse(20.0, 20.0, KeyState::Up)));
+    assert!(!button(&mut ui, mouse(40.0, 40.0, KeyState::Down)));
+}
@@ -0,0 +1,78 @@
+use crate::structures::Color;
+
+const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Color {
+    Color { r, g, b, a }
+}
+
+///Colors and sizes of the widgets, sizes are in logical pixels which are scaled by the scale
+///factor of the [`super::Ui`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    ///Size of the text
+    pub font_size: f32,
+    ///Space between the edges of the widgets and their contents
+    pub padding: f32,
+    ///Space between the widgets
+    pub spacing: f32,
+    ///Width of sliders, text fields and separators
+    pub widget_width: f32,
+    ///Color of the text
+    pub text: Color,
+    ///Background of the windows
+    pub window: Color,
+    ///Background of the title bars of the windows
+    pub title_bar: Color,
+    ///Background of the widgets
+    pub widget: Color,
+    ///Background of the widgets under the cursor
+    pub widget_hovered: Color,
+    ///Background of the widgets that are pressed or focused
+    pub widget_active: Color,
+    ///Filled part of sliders, marks of checkboxes and the caret of text fields
+    pub accent: Color,
+    ///Lines around windows and separators
+    pub border: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    ///Light text on dark widgets
+    #[must_use]
+    pub const fn dark() -> Self {
+        Self {
+            font_size: 16.0,
+            padding: 6.0,
+            spacing: 4.0,
+            widget_width: 200.0,
+            text: rgba(0.9, 0.9, 0.9, 1.0),
+            window: rgba(0.08, 0.08, 0.09, 0.94),
+            title_bar: rgba(0.16, 0.16, 0.2, 1.0),
+            widget: rgba(0.2, 0.2, 0.23, 1.0),
+            widget_hovered: rgba(0.28, 0.28, 0.33, 1.0),
+            widget_active: rgba(0.35, 0.35, 0.42, 1.0),
+            accent: rgba(0.3, 0.55, 0.95, 1.0),
+            border: rgba(0.3, 0.3, 0.35, 1.0),
+        }
+    }
+
+    ///Dark text on light widgets
+    #[must_use]
+    pub const fn light() -> Self {
+        Self {
+            text: rgba(0.08, 0.08, 0.08, 1.0),
+            window: rgba(0.94, 0.94, 0.94, 0.96),
+            title_bar: rgba(0.8, 0.8, 0.84, 1.0),
+            widget: rgba(0.85, 0.85, 0.87, 1.0),
+            widget_hovered: rgba(0.78, 0.78, 0.82, 1.0),
+            widget_active: rgba(0.7, 0.7, 0.76, 1.0),
+            accent: rgba(0.2, 0.45, 0.9, 1.0),
+            border: rgba(0.6, 0.6, 0.64, 1.0),
+            ..Self::dark()
+        }
+    }
+}
@@ -9,6 +9,12 @@ use crate::{
     APP_INFO, DEVICE, FORMAT, QUEUE, RESOLUTION, STAGING_BELT, input::InputState, math::Vec2,
 };
 
+///Texture compression features that are enabled when the adapter supports them
+pub const TEXTURE_COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC_HDR);
+
 pub fn initialize_gpu(window: &Window) -> (Surface<'_>, SurfaceConfiguration, Texture) {
     let mut size = window.inner_size();
     size.width = size.width.max(1);
@@ -48,6 +54,7 @@ pub fn initialize_gpu(window: &Window) -> (Surface<'_>, SurfaceConfiguration, Te
         let r = futures::executor::block_on(req_device(
             &adapter,
             &wgpu::DeviceDescriptor {
+                required_features: adapter.features() & TEXTURE_COMPRESSION_FEATURES,
                 required_limits: limits,
                 ..Default::default()
             },
@@ -132,6 +139,7 @@ pub fn initialize_gpu(window: &Window) -> (Surface<'_>, SurfaceConfiguration, Te
             cursor_delta: RwLock::new(Vec2::default()),
             raw_curosor_delta: RwLock::new(Vec2::default()),
             delta_changed: RwLock::new(false),
+            text: RwLock::new(String::new()),
         })
         .unwrap();
 